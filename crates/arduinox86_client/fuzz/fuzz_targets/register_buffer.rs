@@ -0,0 +1,28 @@
+#![no_main]
+
+// Fuzz the register buffer decoders. Any buffer that decodes must re-encode to exactly the bytes
+// it was decoded from, and decoding must never panic.
+// Run with: cargo +nightly fuzz run register_buffer
+
+use arduinox86_client::*;
+use libfuzzer_sys::fuzz_target;
+
+fn check<R: RegisterBuffer>(data: &[u8]) {
+    match R::decode(data) {
+        Ok(regs) => assert_eq!(&regs.to_bytes()[..], &data[..R::BUFFER_LEN]),
+        Err(_) => assert!(data.len() < R::BUFFER_LEN),
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    check::<RemoteCpuRegistersV1>(data);
+    check::<RemoteCpuRegistersV2>(data);
+    check::<RemoteCpuRegistersV3A>(data);
+    check::<RemoteCpuRegistersV3B>(data);
+
+    if let Ok(regs) = RemoteCpuRegisters::try_from(data) {
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        regs.write(&mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), data);
+    }
+});
@@ -0,0 +1,301 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! An async front end for [CpuClient], available with the `tokio` feature.
+//!
+//! The serial port is blocking and [CpuClient] isn't `Send`, so an [AsyncCpuClient] owns its
+//! [CpuClient] on a dedicated worker thread and sends it one command at a time. Each command's
+//! result comes back over a oneshot channel, so a GUI or polling loop can await it without
+//! blocking. Handles can be cloned; commands from every handle are run in the order they arrive.
+//! The worker exits when the last handle is dropped.
+
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::oneshot;
+
+use crate::{
+    CpuClient,
+    CpuClientError,
+    CpuPin,
+    DiscoveryOptions,
+    EndCondition,
+    InterruptTrigger,
+    MemoryBatch,
+    MemoryStrategy,
+    ProgramState,
+    ProtocolLog,
+    RegisterSetType,
+    RemoteCpuRegisters,
+    ServerCpuType,
+    ServerCycleState,
+    ServerStateBudget,
+    ServerStatus,
+    STATE_POLL_INTERVAL,
+};
+
+type Job = Box<dyn FnOnce(&mut CpuClient) + Send>;
+
+/// An async handle to a [CpuClient] running on its own worker thread.
+#[derive(Clone)]
+pub struct AsyncCpuClient {
+    jobs: mpsc::Sender<Job>,
+}
+
+/// Generate async wrappers for [CpuClient] methods whose arguments can be moved to the worker.
+macro_rules! async_commands {
+    ($($(#[$meta:meta])* fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        impl AsyncCpuClient {
+            $(
+                $(#[$meta])*
+                pub async fn $name(&self, $($arg: $ty),*) -> Result<$ret, CpuClientError> {
+                    self.call(move |client| client.$name($($arg),*)).await
+                }
+            )*
+        }
+    };
+}
+
+impl AsyncCpuClient {
+    /// Find a server as [CpuClient::init] does, on a new worker thread.
+    pub async fn init(com_port: Option<String>, timeout: Option<u64>) -> Result<AsyncCpuClient, CpuClientError> {
+        AsyncCpuClient::spawn(move || CpuClient::init(com_port, timeout)).await
+    }
+
+    /// Find a server as [CpuClient::init_with] does, on a new worker thread.
+    pub async fn init_with(
+        com_port: Option<String>,
+        options: DiscoveryOptions,
+    ) -> Result<AsyncCpuClient, CpuClientError> {
+        AsyncCpuClient::spawn(move || CpuClient::init_with(com_port, &options)).await
+    }
+
+    /// Start a worker thread and create its [CpuClient] there with `connect`. Fails with the error
+    /// `connect` returns, if any.
+    pub async fn spawn<F>(connect: F) -> Result<AsyncCpuClient, CpuClientError>
+    where
+        F: FnOnce() -> Result<CpuClient, CpuClientError> + Send + 'static,
+    {
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let (ready_tx, ready_rx) = oneshot::channel();
+
+        std::thread::Builder::new()
+            .name("arduinox86-client".to_string())
+            .spawn(move || {
+                let mut client = match connect() {
+                    Ok(client) => {
+                        _ = ready_tx.send(Ok(()));
+                        client
+                    }
+                    Err(e) => {
+                        _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                while let Ok(job) = job_rx.recv() {
+                    job(&mut client);
+                }
+            })
+            .map_err(|_| CpuClientError::WorkerStopped)?;
+
+        ready_rx.await.map_err(|_| CpuClientError::WorkerStopped)??;
+        Ok(AsyncCpuClient { jobs: job_tx })
+    }
+
+    /// Run `f` on the worker's [CpuClient] and return its result. Use this for anything without
+    /// an async wrapper, such as a sequence of commands that must not be interleaved with commands
+    /// from other handles.
+    pub async fn call<T, F>(&self, f: F) -> Result<T, CpuClientError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut CpuClient) -> Result<T, CpuClientError> + Send + 'static,
+    {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |client| {
+                _ = result_tx.send(f(client));
+            }))
+            .map_err(|_| CpuClientError::WorkerStopped)?;
+        result_rx.await.map_err(|_| CpuClientError::WorkerStopped)?
+    }
+
+    pub async fn load_registers_from_buf(
+        &self,
+        reg_type: RegisterSetType,
+        reg_data: Vec<u8>,
+    ) -> Result<bool, CpuClientError> {
+        self.call(move |client| client.load_registers_from_buf(reg_type, &reg_data))
+            .await
+    }
+
+    /// Read the registers into `reg_data`, returning the register set type and the buffer.
+    pub async fn store_registers_to_buf(&self, mut reg_data: Vec<u8>) -> Result<(u8, Vec<u8>), CpuClientError> {
+        self.call(move |client| {
+            let reg_type = client.store_registers_to_buf(&mut reg_data)?;
+            Ok((reg_type, reg_data))
+        })
+        .await
+    }
+
+    pub async fn restore_registers_snapshot(&self, regs: RemoteCpuRegisters) -> Result<bool, CpuClientError> {
+        self.call(move |client| client.restore_registers_snapshot(&regs)).await
+    }
+
+    pub async fn set_memory(&self, address: u32, data_buf: Vec<u8>) -> Result<bool, CpuClientError> {
+        self.call(move |client| client.set_memory(address, &data_buf)).await
+    }
+
+    pub async fn set_memory_ranges(&self, batch: MemoryBatch) -> Result<bool, CpuClientError> {
+        self.call(move |client| client.set_memory_ranges(&batch)).await
+    }
+
+    /// Read `size` bytes of memory from `start`.
+    pub async fn read_memory(&self, start: u32, size: u32) -> Result<Vec<u8>, CpuClientError> {
+        self.call(move |client| {
+            let mut data = Vec::with_capacity(size as usize);
+            client.read_memory(start, size, &mut data)?;
+            Ok(data)
+        })
+        .await
+    }
+
+    pub async fn set_interrupt_trigger(&self, trigger: InterruptTrigger) -> Result<bool, CpuClientError> {
+        self.call(move |client| client.set_interrupt_trigger(&trigger)).await
+    }
+
+    pub async fn set_end_condition(&self, condition: Option<EndCondition>) -> Result<bool, CpuClientError> {
+        self.call(move |client| client.set_end_condition(condition)).await
+    }
+
+    /// Return the cached server state. See [CpuClient::program_state].
+    pub async fn program_state(&self) -> Result<Option<ProgramState>, CpuClientError> {
+        self.call(|client| Ok(client.program_state())).await
+    }
+
+    pub async fn set_pin_interlock(&self, enabled: bool) -> Result<(), CpuClientError> {
+        self.call(move |client| {
+            client.set_pin_interlock(enabled);
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn enable_protocol_log(&self, capacity: usize) -> Result<(), CpuClientError> {
+        self.call(move |client| {
+            client.enable_protocol_log(capacity);
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn disable_protocol_log(&self) -> Result<(), CpuClientError> {
+        self.call(|client| {
+            client.disable_protocol_log();
+            Ok(())
+        })
+        .await
+    }
+
+    /// Return a copy of the protocol log, if enabled.
+    pub async fn protocol_log(&self) -> Result<Option<ProtocolLog>, CpuClientError> {
+        self.call(|client| Ok(client.protocol_log().cloned())).await
+    }
+
+    /// Wait until the server is in `state`. See [AsyncCpuClient::wait_for_states].
+    pub async fn wait_for_state(&self, state: ProgramState, timeout: Duration) -> Result<ServerStatus, CpuClientError> {
+        self.wait_for_states(vec![state], timeout, STATE_POLL_INTERVAL).await
+    }
+
+    /// Poll the server status every `interval` until the server is in one of `states`, as
+    /// [CpuClient::wait_for_states] does. The worker is only held for each poll, so commands from
+    /// other handles can run in between.
+    pub async fn wait_for_states(
+        &self,
+        states: Vec<ProgramState>,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<ServerStatus, CpuClientError> {
+        let start = Instant::now();
+        let mut last = self.server_status().await?;
+        loop {
+            if states.contains(&last.state) {
+                return Ok(last);
+            }
+            if start.elapsed() >= timeout {
+                return Err(CpuClientError::StateTimeout(last.state));
+            }
+            tokio::time::sleep(interval).await;
+
+            let status = self.server_status().await?;
+            let missed = status.missed_transitions(&last);
+            if missed > 0 {
+                log::debug!(
+                    "Missed {} state transition(s) between {:?} and {:?}",
+                    missed,
+                    last.state,
+                    status.state
+                );
+            }
+            last = status;
+        }
+    }
+}
+
+async_commands! {
+    fn begin_store() -> bool;
+    fn store_registers() -> RemoteCpuRegisters;
+    fn save_registers_snapshot() -> RemoteCpuRegisters;
+    fn cycle() -> bool;
+    fn reset_cpu() -> bool;
+    fn cpu_type() -> (ServerCpuType, bool);
+    fn init_screen() -> bool;
+    fn read_address_latch() -> u32;
+    fn read_address() -> u32;
+    fn read_status() -> u8;
+    fn read_8288_command() -> u8;
+    fn read_8288_control() -> u8;
+    fn read_data_bus() -> u16;
+    fn write_data_bus(data: u16) -> bool;
+    fn prefetch_store() -> bool;
+    fn finalize() -> bool;
+    fn get_program_state() -> ProgramState;
+    fn get_last_error() -> String;
+    fn read_pin(pin_no: CpuPin) -> bool;
+    fn write_pin(pin_no: CpuPin, val: bool) -> bool;
+    fn get_cycle_state(cycle: bool) -> ServerCycleState;
+    fn set_flags(flags: u32) -> bool;
+    fn get_flags() -> u32;
+    fn storeall() -> bool;
+    fn randomize_memory(seed: u32) -> bool;
+    fn set_random_seed(seed: u32) -> bool;
+    fn get_cycle_states() -> Vec<ServerCycleState>;
+    fn set_memory_strategy(strategy: MemoryStrategy, start: u32, end: u32) -> bool;
+    fn erase_memory() -> ();
+    fn enable_debug(enable: bool) -> ();
+    fn server_status() -> ServerStatus;
+    fn get_state_budget() -> Vec<ServerStateBudget>;
+    fn clear_cycle_log() -> bool;
+    fn set_program_bounds(start: u32, end: u32) -> bool;
+}
@@ -0,0 +1,429 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Experiment bundles, available with the `bundle` feature.
+//!
+//! An experiment bundle is a directory holding everything needed to understand and reproduce a
+//! run, so a result can be shared without a list of the files and settings that went into it.
+//! `exec_program --bundle DIR` and the GUI's Export Experiment Bundle menu both write this
+//! layout:
+//!
+//! | File                | Contents                                                             |
+//! |---------------------|----------------------------------------------------------------------|
+//! | `bundle.toml`       | The [BundleManifest]: format version, environment and file list.     |
+//! | `program_N.bin`     | Each program blob, with its name and load address in the manifest.   |
+//! | `initial_regs.bin`  | The initial registers in the server's register file format.          |
+//! | `initial_regs.json` | The initial registers by name, as [BundleRegisters].                 |
+//! | `final_regs.bin`    | The final registers in the server's register file format.            |
+//! | `final_regs.json`   | The final registers by name, as [BundleRegisters].                   |
+//! | `trace.json`        | One [BundleCycle] per captured cycle.                                |
+//! | `bus_ops.json`      | One [BundleBusOp] per bus transfer, derived from the trace.          |
+//! | `diagnostics.json`  | The [BundleDiagnostic]s raised during the run.                       |
+//! | `bookmarks.json`    | The [BundleBookmark]s the user placed on the trace.                  |
+//!
+//! Files for data the run didn't produce are left out and missing from the manifest's file list.
+//! The `.bin` files load with `exec_program --reg-file` and `--bin-file`, so a bundle can be run
+//! again as is. [BUNDLE_FORMAT_VERSION] is raised whenever a file changes in a way older readers
+//! can't handle.
+
+use std::{collections::BTreeMap, fs, io, io::Cursor, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Registers16, Registers32, RemoteCpuRegisters, RemoteCpuRegistersV3, ServerCpuType, ServerCycleState};
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+pub const BUNDLE_MANIFEST_FILENAME: &str = "bundle.toml";
+pub const BUNDLE_BOOKMARKS_FILENAME: &str = "bookmarks.json";
+
+/// The `bundle.toml` file at the root of an experiment bundle.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub environment: BundleEnvironment,
+    #[serde(default)]
+    pub programs: Vec<BundleProgramEntry>,
+    /// Every file in the bundle besides the manifest.
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+impl BundleManifest {
+    /// Read the manifest of the bundle in `dir`. Fails on bundles written in a newer format.
+    pub fn read(dir: &Path) -> io::Result<BundleManifest> {
+        let text = fs::read_to_string(dir.join(BUNDLE_MANIFEST_FILENAME))?;
+        let manifest: BundleManifest = toml::from_str(&text).map_err(io::Error::other)?;
+        if manifest.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Bundle format version {} is newer than supported version {}",
+                    manifest.format_version, BUNDLE_FORMAT_VERSION
+                ),
+            ));
+        }
+        Ok(manifest)
+    }
+}
+
+/// What produced a bundle and the hardware it ran on.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleEnvironment {
+    pub tool: String,
+    pub tool_version: String,
+    pub os: String,
+    pub arch: String,
+    pub cpu_type: String,
+    pub fpu: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_flags: Option<u32>,
+    /// The command line or GUI settings the run was started with.
+    #[serde(default)]
+    pub arguments: Vec<String>,
+}
+
+impl BundleEnvironment {
+    /// Describe a run by `tool` on `cpu_type`, filling in the host OS and architecture.
+    pub fn new(tool: &str, tool_version: &str, cpu_type: ServerCpuType, fpu: bool) -> BundleEnvironment {
+        BundleEnvironment {
+            tool: tool.to_string(),
+            tool_version: tool_version.to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu_type: format!("{:?}", cpu_type),
+            fpu,
+            ..Default::default()
+        }
+    }
+}
+
+/// A program blob as listed in the manifest.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleProgramEntry {
+    pub name:    String,
+    pub file:    String,
+    pub address: u32,
+    pub size:    usize,
+}
+
+/// A program blob and the linear address it was loaded at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BundleProgram {
+    pub name:    String,
+    pub address: u32,
+    pub data:    Vec<u8>,
+}
+
+/// A register set by name, with 16-bit registers zero-extended.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleRegisters {
+    /// The register set type: `V1`, `V2`, `V3A` or `V3B`.
+    pub set: String,
+    pub registers: BTreeMap<String, u32>,
+}
+
+impl From<&RemoteCpuRegisters> for BundleRegisters {
+    fn from(regs: &RemoteCpuRegisters) -> Self {
+        fn regs16<R: Registers16>(regs: &R) -> BTreeMap<String, u32> {
+            [
+                ("ax", regs.ax()),
+                ("bx", regs.bx()),
+                ("cx", regs.cx()),
+                ("dx", regs.dx()),
+                ("sp", regs.sp()),
+                ("bp", regs.bp()),
+                ("si", regs.si()),
+                ("di", regs.di()),
+                ("cs", regs.cs()),
+                ("ds", regs.ds()),
+                ("es", regs.es()),
+                ("ss", regs.ss()),
+                ("ip", regs.ip()),
+                ("flags", regs.flags()),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value as u32))
+            .collect()
+        }
+
+        let (set, registers) = match regs {
+            RemoteCpuRegisters::V1(regs) => ("V1", regs16(regs)),
+            RemoteCpuRegisters::V2(regs) => ("V2", regs16(regs)),
+            RemoteCpuRegisters::V3(regs) => {
+                let set = match regs {
+                    RemoteCpuRegistersV3::A(_) => "V3A",
+                    RemoteCpuRegistersV3::B(_) => "V3B",
+                };
+                let registers = [
+                    ("cr0", regs.cr0()),
+                    ("dr6", regs.dr6()),
+                    ("dr7", regs.dr7()),
+                    ("eax", regs.eax()),
+                    ("ebx", regs.ebx()),
+                    ("ecx", regs.ecx()),
+                    ("edx", regs.edx()),
+                    ("esp", regs.esp()),
+                    ("ebp", regs.ebp()),
+                    ("esi", regs.esi()),
+                    ("edi", regs.edi()),
+                    ("eip", regs.eip()),
+                    ("eflags", regs.eflags()),
+                    ("cs", regs.cs() as u32),
+                    ("ds", regs.ds() as u32),
+                    ("es", regs.es() as u32),
+                    ("fs", regs.fs() as u32),
+                    ("gs", regs.gs() as u32),
+                    ("ss", regs.ss() as u32),
+                ]
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+                (set, registers)
+            }
+        };
+        BundleRegisters {
+            set: set.to_string(),
+            registers,
+        }
+    }
+}
+
+/// The bus state of the CPU on one cycle.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleCycle {
+    pub cycle: u32,
+    pub ale: bool,
+    /// The address latched at the last ALE.
+    pub address_latch: u32,
+    pub address_bus: u32,
+    pub bus_state: String,
+    pub t_state: String,
+    /// Data bus contents. Only meaningful when `reading` or `writing` is set.
+    pub data_bus: u16,
+    pub reading: bool,
+    pub writing: bool,
+    /// The queue operation, on CPUs with queue status lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_op: Option<String>,
+    /// The byte read from the queue, if `queue_op` is a queue read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_byte: Option<u8>,
+    /// What the host did on this cycle, for host-stepped runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl BundleCycle {
+    /// Convert the cycle states logged by the server.
+    pub fn from_server_states(states: &[ServerCycleState], cpu_type: ServerCpuType) -> Vec<BundleCycle> {
+        let mut address_latch = 0;
+        states
+            .iter()
+            .enumerate()
+            .map(|(cycle, state)| {
+                if state.ale() {
+                    address_latch = state.address_bus;
+                }
+                let transferring = state.is_transferring(cpu_type);
+                BundleCycle {
+                    cycle: cycle as u32,
+                    ale: state.ale(),
+                    address_latch,
+                    address_bus: state.address_bus,
+                    bus_state: format!("{:?}", cpu_type.decode_status(state.cpu_status_bits)),
+                    t_state: format!("{:?}", state.t_state()),
+                    data_bus: state.data_bus,
+                    reading: transferring && state.is_reading(),
+                    writing: transferring && state.is_writing(),
+                    queue_op: None,
+                    queue_byte: None,
+                    comment: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A bus transfer: the bus state latched at ALE, and the data on the last cycle of the transfer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleBusOp {
+    /// The cycle the data was transferred on.
+    pub cycle:   u32,
+    pub kind:    String,
+    pub address: u32,
+    pub data:    u16,
+}
+
+/// Collect the bus transfers in `trace`, one per bus cycle.
+pub fn bus_ops(trace: &[BundleCycle]) -> Vec<BundleBusOp> {
+    let mut ops = Vec::new();
+    let mut kind = String::new();
+    let mut pending: Option<BundleBusOp> = None;
+
+    for cycle in trace {
+        if cycle.ale {
+            ops.extend(pending.take());
+            kind = cycle.bus_state.clone();
+        }
+        if cycle.reading || cycle.writing {
+            pending = Some(BundleBusOp {
+                cycle:   cycle.cycle,
+                kind:    kind.clone(),
+                address: cycle.address_latch,
+                data:    cycle.data_bus,
+            });
+        }
+        else {
+            ops.extend(pending.take());
+        }
+    }
+    ops.extend(pending);
+    ops
+}
+
+/// A diagnostic raised during the run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleDiagnostic {
+    pub severity: String,
+    pub cycle:    u32,
+    pub category: String,
+    pub message:  String,
+}
+
+/// A note attached to a cycle of the trace.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleBookmark {
+    pub cycle: u32,
+    #[serde(default)]
+    pub note:  String,
+}
+
+/// Read the bookmarks of the bundle in `dir`. A bundle without bookmarks returns an empty list.
+pub fn read_bookmarks(dir: &Path) -> io::Result<Vec<BundleBookmark>> {
+    match fs::read_to_string(dir.join(BUNDLE_BOOKMARKS_FILENAME)) {
+        Ok(text) => serde_json::from_str(&text).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// The contents of an experiment bundle, ready to be written.
+#[derive(Clone, Debug)]
+pub struct ExperimentBundle {
+    pub environment: BundleEnvironment,
+    pub programs: Vec<BundleProgram>,
+    pub initial_regs: Option<RemoteCpuRegisters>,
+    pub final_regs: Option<RemoteCpuRegisters>,
+    pub trace: Vec<BundleCycle>,
+    pub diagnostics: Vec<BundleDiagnostic>,
+    pub bookmarks: Vec<BundleBookmark>,
+}
+
+impl ExperimentBundle {
+    pub fn new(environment: BundleEnvironment) -> ExperimentBundle {
+        ExperimentBundle {
+            environment,
+            programs: Vec::new(),
+            initial_regs: None,
+            final_regs: None,
+            trace: Vec::new(),
+            diagnostics: Vec::new(),
+            bookmarks: Vec::new(),
+        }
+    }
+
+    /// Write the bundle to `dir`, creating it if needed, and return its manifest. Fails if `dir`
+    /// already holds a bundle.
+    pub fn write(&self, dir: &Path) -> io::Result<BundleManifest> {
+        if dir.join(BUNDLE_MANIFEST_FILENAME).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already holds a bundle", dir.display()),
+            ));
+        }
+        fs::create_dir_all(dir)?;
+
+        let mut manifest = BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            environment: self.environment.clone(),
+            programs: Vec::new(),
+            files: Vec::new(),
+        };
+
+        for (i, program) in self.programs.iter().enumerate() {
+            let file = format!("program_{}.bin", i);
+            fs::write(dir.join(&file), &program.data)?;
+            manifest.programs.push(BundleProgramEntry {
+                name:    program.name.clone(),
+                file:    file.clone(),
+                address: program.address,
+                size:    program.data.len(),
+            });
+            manifest.files.push(file);
+        }
+
+        for (name, regs) in [("initial_regs", &self.initial_regs), ("final_regs", &self.final_regs)] {
+            let Some(regs) = regs
+            else {
+                continue;
+            };
+            let mut reg_buf = Cursor::new(Vec::new());
+            regs.write(&mut reg_buf)?;
+            write_file(dir, &format!("{}.bin", name), reg_buf.get_ref(), &mut manifest)?;
+            write_json(
+                dir,
+                &format!("{}.json", name),
+                &BundleRegisters::from(regs),
+                &mut manifest,
+            )?;
+        }
+
+        if !self.trace.is_empty() {
+            write_json(dir, "trace.json", &self.trace, &mut manifest)?;
+            write_json(dir, "bus_ops.json", &bus_ops(&self.trace), &mut manifest)?;
+        }
+        if !self.diagnostics.is_empty() {
+            write_json(dir, "diagnostics.json", &self.diagnostics, &mut manifest)?;
+        }
+        if !self.bookmarks.is_empty() {
+            write_json(dir, BUNDLE_BOOKMARKS_FILENAME, &self.bookmarks, &mut manifest)?;
+        }
+
+        let text = toml::to_string_pretty(&manifest).map_err(io::Error::other)?;
+        fs::write(dir.join(BUNDLE_MANIFEST_FILENAME), text)?;
+        Ok(manifest)
+    }
+}
+
+fn write_file(dir: &Path, file: &str, data: &[u8], manifest: &mut BundleManifest) -> io::Result<()> {
+    fs::write(dir.join(file), data)?;
+    manifest.files.push(file.to_string());
+    Ok(())
+}
+
+fn write_json<T: Serialize>(dir: &Path, file: &str, value: &T, manifest: &mut BundleManifest) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(value).map_err(io::Error::other)?;
+    write_file(dir, file, text.as_bytes(), manifest)
+}
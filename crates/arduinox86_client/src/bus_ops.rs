@@ -0,0 +1,161 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+//! Bus transfers collected from the cycles of a run.
+//!
+//! A bus cycle spans several CPU cycles: the bus state, address and BHE are latched at ALE, and
+//! data moves later, on the cycles a read, write or interrupt acknowledge command is active. A
+//! [BusOp] is one such transfer with the data on the bus at its last data cycle.
+//! [collect_bus_ops] folds a log of [ServerCycleState]s into them, and [BusOpCollector] does the
+//! same for cycles recorded in other forms.
+
+use crate::{BusState, CpuWidth, DataWidth, ServerCpuType, ServerCycleState, TState};
+
+/// A single bus transfer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BusOp {
+    /// The cycle the data was transferred on.
+    pub cycle: u32,
+    pub bus_state: BusState,
+    pub address: u32,
+    pub data: u16,
+    /// The half of the data bus the transfer used.
+    pub width: DataWidth,
+}
+
+impl BusOp {
+    /// Return the bytes transferred with their addresses. A 16-bit transfer is split into its low
+    /// and high byte, and a transfer to an odd address on a 16-bit bus is taken from the high half of
+    /// the data bus. A transfer with an invalid width has no bytes.
+    pub fn bytes(&self) -> Vec<(u32, u8)> {
+        let [low, high] = self.data.to_le_bytes();
+        match self.width {
+            DataWidth::EightLow => vec![(self.address, low)],
+            DataWidth::EightHigh => vec![(self.address, high)],
+            DataWidth::Sixteen => vec![(self.address, low), (self.address.wrapping_add(1), high)],
+            DataWidth::Invalid => Vec::new(),
+        }
+    }
+}
+
+/// Return the part of the data bus a transfer at `address` uses. 8-bit CPUs always use the low
+/// half; 16-bit CPUs use the halves selected by BHE and A0.
+pub fn transfer_width(cpu_width: CpuWidth, address: u32, bhe: bool) -> DataWidth {
+    match cpu_width {
+        CpuWidth::Eight => DataWidth::EightLow,
+        CpuWidth::Sixteen => DataWidth::from((bhe, address & 1 != 0)),
+    }
+}
+
+/// Folds cycles into [BusOp]s, one per bus cycle. Call [BusOpCollector::latch] on each ALE cycle
+/// and [BusOpCollector::cycle] on every cycle.
+#[derive(Clone, Debug)]
+pub struct BusOpCollector {
+    ops: Vec<BusOp>,
+    pending: Option<BusOp>,
+    bus_state: BusState,
+    address: u32,
+    width: DataWidth,
+}
+
+impl Default for BusOpCollector {
+    fn default() -> Self {
+        Self::new(BusState::PASV, 0, DataWidth::Invalid)
+    }
+}
+
+impl BusOpCollector {
+    /// Create a collector for cycles that may start partway through a bus cycle, with the state,
+    /// address and width that bus cycle latched.
+    pub fn new(bus_state: BusState, address: u32, width: DataWidth) -> Self {
+        Self {
+            ops: Vec::new(),
+            pending: None,
+            bus_state,
+            address,
+            width,
+        }
+    }
+
+    /// Start a new bus cycle.
+    pub fn latch(&mut self, bus_state: BusState, address: u32, width: DataWidth) {
+        self.ops.extend(self.pending.take());
+        self.bus_state = bus_state;
+        self.address = address;
+        self.width = width;
+    }
+
+    /// Record a cycle, with the data on the bus if data was transferred on it. Data held over
+    /// several cycles is one transfer, with the data of the last of them.
+    pub fn cycle(&mut self, cycle: u32, data: Option<u16>) {
+        match data {
+            Some(data) => {
+                self.pending = Some(BusOp {
+                    cycle,
+                    bus_state: self.bus_state,
+                    address: self.address,
+                    data,
+                    width: self.width,
+                });
+            }
+            None => self.ops.extend(self.pending.take()),
+        }
+    }
+
+    /// Return the transfers collected, including one still in progress.
+    pub fn finish(mut self) -> Vec<BusOp> {
+        self.ops.extend(self.pending.take());
+        self.ops
+    }
+}
+
+/// Collect the bus transfers in a cycle log, numbering cycles by their index in `states`.
+/// Transfers are typed by the bus state latched at ALE: CODE, MEMR, MEMW, IOR, IOW or INTA.
+pub fn collect_bus_ops(cpu_type: ServerCpuType, states: &[ServerCycleState]) -> Vec<BusOp> {
+    let cpu_width = CpuWidth::from(cpu_type);
+    let mut collector = BusOpCollector::default();
+
+    for (i, state) in states.iter().enumerate() {
+        if state.ale() {
+            collector.latch(
+                cpu_type.decode_status(state.cpu_status_bits),
+                state.address_bus,
+                transfer_width(cpu_width, state.address_bus, state.bhe()),
+            );
+        }
+        collector.cycle(i as u32, is_transfer(cpu_type, state).then_some(state.data_bus));
+    }
+    collector.finish()
+}
+
+/// Return whether data moves on the bus this cycle. The vector read of an interrupt acknowledge
+/// counts as a transfer, on the same cycles as a read.
+fn is_transfer(cpu_type: ServerCpuType, state: &ServerCycleState) -> bool {
+    if state.bus_command_bits & ServerCycleState::COMMAND_INTA_BIT != 0 {
+        // INTA is inactive.
+        return state.is_transferring(cpu_type);
+    }
+    match cpu_type {
+        ServerCpuType::Intel80286 | ServerCpuType::Intel80386 => state.t_state() != TState::T1,
+        _ => cpu_type.decode_status(state.cpu_status_bits) == BusState::PASV,
+    }
+}
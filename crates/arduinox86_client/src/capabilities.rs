@@ -0,0 +1,145 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+//! What an ArduinoX86 server can do.
+//!
+//! Servers speaking protocol version [CAPABILITIES_PROTOCOL_VER] or later report a bitmap of the
+//! optional commands they support. Older servers are assumed to support the commands their
+//! protocol version introduced, so the client can still talk to them, and refuses only the
+//! commands they lack.
+
+use std::fmt::{Display, Formatter};
+
+use crate::ServerCommand;
+
+/// The oldest protocol version the client can talk to.
+pub const MIN_PROTOCOL_VER: u8 = 3;
+/// The first protocol version with the capabilities query.
+pub const CAPABILITIES_PROTOCOL_VER: u8 = 9;
+
+/// [Capabilities] is the set of optional features a server supports, as a bitmap of the
+/// constants below.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities(pub u32);
+
+#[rustfmt::skip]
+impl Capabilities {
+    pub const CYCLE_LOG: u32          = 0x0000_0001; // CmdGetCycleStates, CmdClearCycleLog
+    pub const MEMORY_STRATEGY: u32    = 0x0000_0002; // CmdSetMemoryStrategy
+    pub const INTERRUPT_TRIGGER: u32  = 0x0000_0004; // CmdSetInterruptTrigger
+    pub const PROGRAM_BOUNDS: u32     = 0x0000_0008; // CmdSetProgramBounds
+    pub const STATE_SEQUENCE: u32     = 0x0000_0010; // State sequence number in the server status
+    pub const STATE_BUDGET: u32       = 0x0000_0020; // CmdGetStateBudget
+    pub const MEMORY_RANGES: u32      = 0x0000_0040; // CmdSetMemoryRanges
+    pub const END_CONDITION: u32      = 0x0000_0080; // CmdSetEndCondition
+    pub const CYCLE_LOG_ENCODING: u32 = 0x0000_0100; // ServerFlags::ENCODE_CYCLE_LOG
+}
+
+const NAMES: [(u32, &str); 9] = [
+    (Capabilities::CYCLE_LOG, "cycle log"),
+    (Capabilities::MEMORY_STRATEGY, "memory strategy"),
+    (Capabilities::INTERRUPT_TRIGGER, "interrupt trigger"),
+    (Capabilities::PROGRAM_BOUNDS, "program bounds"),
+    (Capabilities::STATE_SEQUENCE, "state sequence"),
+    (Capabilities::STATE_BUDGET, "state budget"),
+    (Capabilities::MEMORY_RANGES, "memory ranges"),
+    (Capabilities::END_CONDITION, "end condition"),
+    (Capabilities::CYCLE_LOG_ENCODING, "cycle log encoding"),
+];
+
+impl Capabilities {
+    /// Return the capabilities expected of a server speaking protocol `version`, from the protocol
+    /// version that introduced each of them. Servers that predate the capabilities query are
+    /// assumed to have exactly these.
+    pub fn for_version(version: u8) -> Capabilities {
+        let mut caps = 0;
+        if version >= 3 {
+            caps |= Self::CYCLE_LOG | Self::MEMORY_STRATEGY | Self::PROGRAM_BOUNDS;
+        }
+        if version >= 4 {
+            caps |= Self::INTERRUPT_TRIGGER;
+        }
+        if version >= 5 {
+            caps |= Self::STATE_SEQUENCE;
+        }
+        if version >= 6 {
+            caps |= Self::STATE_BUDGET;
+        }
+        if version >= 7 {
+            caps |= Self::MEMORY_RANGES;
+        }
+        if version >= 8 {
+            caps |= Self::END_CONDITION;
+        }
+        if version >= 9 {
+            caps |= Self::CYCLE_LOG_ENCODING;
+        }
+        Capabilities(caps)
+    }
+
+    /// Return the features a server speaking protocol `version` is expected to support that are
+    /// missing from this set.
+    pub fn missing(&self, version: u8) -> Capabilities {
+        Capabilities(Self::for_version(version).0 & !self.0)
+    }
+
+    /// Return whether every flag in `flags` is supported.
+    pub fn contains(&self, flags: u32) -> bool {
+        self.0 & flags == flags
+    }
+
+    /// Return the names of the supported features.
+    pub fn names(&self) -> Vec<&'static str> {
+        NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.names() {
+            names if names.is_empty() => write!(f, "none"),
+            names => write!(f, "{}", names.join(", ")),
+        }
+    }
+}
+
+impl ServerCommand {
+    /// The capability the server must report to accept this command, or 0 if every supported
+    /// server accepts it.
+    pub fn capability(&self) -> u32 {
+        use ServerCommand::*;
+        match self {
+            CmdGetCycleStates | CmdClearCycleLog => Capabilities::CYCLE_LOG,
+            CmdSetMemoryStrategy => Capabilities::MEMORY_STRATEGY,
+            CmdSetInterruptTrigger => Capabilities::INTERRUPT_TRIGGER,
+            CmdSetProgramBounds => Capabilities::PROGRAM_BOUNDS,
+            CmdGetStateBudget => Capabilities::STATE_BUDGET,
+            CmdSetMemoryRanges => Capabilities::MEMORY_RANGES,
+            CmdSetEndCondition => Capabilities::END_CONDITION,
+            _ => 0,
+        }
+    }
+}
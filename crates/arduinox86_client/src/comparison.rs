@@ -0,0 +1,349 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Policies for comparing a test's expected final state against an actual one.
+//!
+//! Replay, validation and test set diffs all decide whether two final states match. A
+//! [ComparisonPolicy] makes that decision in one place: which registers to compare, which flags
+//! bits count, how much of the final RAM to check, how far cycle counts may drift and whether flags
+//! a test leaves undefined are wildcards. [ComparisonPreset] provides the common choices; anything
+//! else can implement the trait.
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+#[cfg(feature = "use_moo")]
+use moo::{prelude::MooTest, types::MooRegisters};
+
+/// How much of a test's final RAM a [ComparisonPolicy] checks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MemoryScope {
+    /// Every address in the final RAM.
+    #[default]
+    All,
+    /// Only addresses whose final value differs from their initial value, or that have no
+    /// initial value. Bytes the instruction merely read are skipped.
+    Written,
+    /// No RAM at all.
+    None,
+}
+
+/// Decides which parts of a final state are compared, and how.
+pub trait ComparisonPolicy {
+    /// Return a short name for the policy, for reports.
+    fn name(&self) -> &str;
+
+    /// Return true if `register` (as named in a [RegisterMismatch]) is compared at all.
+    fn compare_register(&self, _register: &str) -> bool {
+        true
+    }
+
+    /// Return the flags bits that are compared.
+    fn flags_mask(&self) -> u32 {
+        u32::MAX
+    }
+
+    fn memory_scope(&self) -> MemoryScope {
+        MemoryScope::All
+    }
+
+    /// Return how many cycles the counts may differ by, or None to not compare cycles.
+    fn cycle_tolerance(&self) -> Option<usize> {
+        Some(0)
+    }
+
+    /// Return true if flags the test leaves undefined are wildcards.
+    fn undefined_as_wildcard(&self) -> bool {
+        false
+    }
+
+    /// Return the flags bits compared for a test that leaves `undefined_flags` undefined.
+    fn effective_flags_mask(&self, undefined_flags: u32) -> u32 {
+        match self.undefined_as_wildcard() {
+            true => self.flags_mask() & !undefined_flags,
+            false => self.flags_mask(),
+        }
+    }
+
+    /// Return true if `actual` matches `expected` for `register`.
+    fn register_matches(&self, register: &str, expected: u32, actual: u32, undefined_flags: u32) -> bool {
+        if !self.compare_register(register) {
+            return true;
+        }
+        match is_flags_register(register) {
+            true => {
+                let mask = self.effective_flags_mask(undefined_flags);
+                expected & mask == actual & mask
+            }
+            false => expected == actual,
+        }
+    }
+
+    /// Compare `(register, expected, actual)` triples, returning the ones that don't match.
+    fn register_mismatches(
+        &self,
+        registers: &[(&'static str, u32, u32)],
+        undefined_flags: u32,
+    ) -> Vec<RegisterMismatch> {
+        registers
+            .iter()
+            .filter(|(register, expected, actual)| {
+                !self.register_matches(register, *expected, *actual, undefined_flags)
+            })
+            .map(|&(register, expected, actual)| RegisterMismatch {
+                register,
+                expected,
+                actual,
+            })
+            .collect()
+    }
+
+    /// Return true if the final RAM byte at an address is checked, given its initial value.
+    fn compare_address(&self, initial: Option<u8>, expected: u8) -> bool {
+        match self.memory_scope() {
+            MemoryScope::All => true,
+            MemoryScope::Written => initial != Some(expected),
+            MemoryScope::None => false,
+        }
+    }
+
+    /// Return true if the cycle counts match within the tolerance, or cycles aren't compared.
+    fn cycles_match(&self, expected: usize, actual: usize) -> bool {
+        match self.cycle_tolerance() {
+            Some(tolerance) => expected.abs_diff(actual) <= tolerance,
+            None => true,
+        }
+    }
+}
+
+/// A register whose final value didn't match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterMismatch {
+    pub register: &'static str,
+    pub expected: u32,
+    pub actual:   u32,
+}
+
+/// Return true if `register` names the flags register.
+pub fn is_flags_register(register: &str) -> bool {
+    matches!(register, "FLAGS" | "EFLAGS")
+}
+
+/// The built-in comparison policies.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ComparisonPreset {
+    /// Every register and flag, all of the final RAM and exact cycle counts.
+    #[default]
+    Strict,
+    /// Registers and flags only.
+    RegistersOnly,
+    /// Registers and all of the final RAM, with undefined flags as wildcards. Cycles aren't
+    /// compared, as few emulators model bus timing exactly.
+    EmulatorFriendly,
+}
+
+impl ComparisonPreset {
+    pub const ALL: [ComparisonPreset; 3] = [
+        ComparisonPreset::Strict,
+        ComparisonPreset::RegistersOnly,
+        ComparisonPreset::EmulatorFriendly,
+    ];
+}
+
+impl ComparisonPolicy for ComparisonPreset {
+    fn name(&self) -> &str {
+        match self {
+            ComparisonPreset::Strict => "strict",
+            ComparisonPreset::RegistersOnly => "registers_only",
+            ComparisonPreset::EmulatorFriendly => "emulator_friendly",
+        }
+    }
+
+    fn memory_scope(&self) -> MemoryScope {
+        match self {
+            ComparisonPreset::RegistersOnly => MemoryScope::None,
+            _ => MemoryScope::All,
+        }
+    }
+
+    fn cycle_tolerance(&self) -> Option<usize> {
+        match self {
+            ComparisonPreset::Strict => Some(0),
+            _ => None,
+        }
+    }
+
+    fn undefined_as_wildcard(&self) -> bool {
+        *self == ComparisonPreset::EmulatorFriendly
+    }
+}
+
+impl FromStr for ComparisonPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase().replace('-', "_");
+        ComparisonPreset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown comparison policy '{}', expected strict, registers_only or emulator_friendly",
+                    s
+                )
+            })
+    }
+}
+
+impl Display for ComparisonPreset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Return `(register, expected, actual)` triples for two MOO register sets, or None if one is a
+/// 16-bit set and the other a 32-bit set.
+#[cfg(feature = "use_moo")]
+pub fn moo_register_pairs(expected: &MooRegisters, actual: &MooRegisters) -> Option<Vec<(&'static str, u32, u32)>> {
+    match (expected, actual) {
+        (MooRegisters::Sixteen(e), MooRegisters::Sixteen(a)) => Some(
+            [
+                ("AX", e.ax, a.ax),
+                ("BX", e.bx, a.bx),
+                ("CX", e.cx, a.cx),
+                ("DX", e.dx, a.dx),
+                ("SP", e.sp, a.sp),
+                ("BP", e.bp, a.bp),
+                ("SI", e.si, a.si),
+                ("DI", e.di, a.di),
+                ("CS", e.cs, a.cs),
+                ("DS", e.ds, a.ds),
+                ("ES", e.es, a.es),
+                ("SS", e.ss, a.ss),
+                ("IP", e.ip, a.ip),
+                ("FLAGS", e.flags, a.flags),
+            ]
+            .into_iter()
+            .map(|(register, expected, actual)| (register, expected as u32, actual as u32))
+            .collect(),
+        ),
+        (MooRegisters::ThirtyTwo(e), MooRegisters::ThirtyTwo(a)) => Some(vec![
+            ("EAX", e.eax, a.eax),
+            ("EBX", e.ebx, a.ebx),
+            ("ECX", e.ecx, a.ecx),
+            ("EDX", e.edx, a.edx),
+            ("ESP", e.esp, a.esp),
+            ("EBP", e.ebp, a.ebp),
+            ("ESI", e.esi, a.esi),
+            ("EDI", e.edi, a.edi),
+            ("CS", e.cs, a.cs),
+            ("DS", e.ds, a.ds),
+            ("ES", e.es, a.es),
+            ("FS", e.fs, a.fs),
+            ("GS", e.gs, a.gs),
+            ("SS", e.ss, a.ss),
+            ("EIP", e.eip, a.eip),
+            ("EFLAGS", e.eflags, a.eflags),
+        ]),
+        _ => None,
+    }
+}
+
+/// The differences between two MOO tests under a [ComparisonPolicy].
+#[cfg(feature = "use_moo")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MooTestComparison {
+    /// True if one test has a 16-bit register set and the other a 32-bit set.
+    pub register_width: bool,
+    pub registers: Vec<RegisterMismatch>,
+    /// Final RAM bytes that differ, as (address, expected, actual). A byte missing from one side
+    /// is None.
+    pub ram: Vec<(u32, Option<u8>, Option<u8>)>,
+    /// Expected and actual cycle counts, if they differ by more than the tolerance.
+    pub cycles: Option<(usize, usize)>,
+}
+
+#[cfg(feature = "use_moo")]
+impl MooTestComparison {
+    /// Compare the final state of `actual` against `expected`.
+    pub fn new(policy: &dyn ComparisonPolicy, expected: &MooTest, actual: &MooTest, undefined_flags: u32) -> Self {
+        use std::collections::BTreeMap;
+
+        let mut comparison = MooTestComparison::default();
+        match moo_register_pairs(expected.final_regs(), actual.final_regs()) {
+            Some(pairs) => comparison.registers = policy.register_mismatches(&pairs, undefined_flags),
+            None => comparison.register_width = true,
+        }
+
+        let ram = |test: &MooTest| -> BTreeMap<u32, u8> {
+            test.final_mem_state()
+                .entries
+                .iter()
+                .map(|entry| (entry.address, entry.value))
+                .collect()
+        };
+        let initial: BTreeMap<u32, u8> = expected
+            .initial_mem_state()
+            .entries
+            .iter()
+            .map(|entry| (entry.address, entry.value))
+            .collect();
+        let (expected_ram, actual_ram) = (ram(expected), ram(actual));
+        let mut addresses = expected_ram
+            .keys()
+            .chain(actual_ram.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        addresses.sort_unstable();
+        addresses.dedup();
+        comparison.ram = addresses
+            .into_iter()
+            .map(|address| {
+                (
+                    address,
+                    expected_ram.get(&address).copied(),
+                    actual_ram.get(&address).copied(),
+                )
+            })
+            .filter(|&(address, expected, actual)| {
+                let checked = match expected.or(actual) {
+                    Some(value) => policy.compare_address(initial.get(&address).copied(), value),
+                    None => false,
+                };
+                checked && expected != actual
+            })
+            .collect();
+
+        let (expected_cycles, actual_cycles) = (expected.cycles().len(), actual.cycles().len());
+        if !policy.cycles_match(expected_cycles, actual_cycles) {
+            comparison.cycles = Some((expected_cycles, actual_cycles));
+        }
+        comparison
+    }
+
+    pub fn is_match(&self) -> bool {
+        !self.register_width && self.registers.is_empty() && self.ram.is_empty() && self.cycles.is_none()
+    }
+}
@@ -0,0 +1,238 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+//! The encoded wire format for cycle log downloads.
+//!
+//! A raw cycle log is a fixed 12-byte record per cycle, most of which repeats from one cycle to
+//! the next. With [crate::ServerFlags::ENCODE_CYCLE_LOG] set, the server sends each record as the
+//! fields that changed since the previous one instead, with small address steps sent as a signed
+//! byte, and runs of identical records collapsed to a repeat count.
+//!
+//! Each record starts with a mask of [FIELD_ADDRESS] to [FIELD_ADDRESS_DELTA], followed by the
+//! fields it names in that order, little-endian. A mask of 0 is followed by a count of 1 to 255
+//! copies of the previous record. Records are encoded against a previous record of all zeros at
+//! the start of the log.
+
+use crate::{CpuClientError, ProgramState, ServerCommand, ServerCycleState};
+
+/// Set in the cycle count of a cycle log download when the log that follows is encoded.
+pub const CYCLE_LOG_ENCODED_BIT: u32 = 0x8000_0000;
+/// The size of a raw cycle log record.
+pub const CYCLE_RECORD_LEN: usize = 12;
+
+/// The record carries the full address bus, as a 32-bit value.
+pub const FIELD_ADDRESS: u8 = 0x01;
+/// The record carries the data bus, as a 16-bit value.
+pub const FIELD_DATA: u8 = 0x02;
+/// The record carries the CPU state byte.
+pub const FIELD_STATE: u8 = 0x04;
+/// The record carries the CPU status byte.
+pub const FIELD_STATUS: u8 = 0x08;
+/// The record carries the bus control byte.
+pub const FIELD_CONTROL: u8 = 0x10;
+/// The record carries the bus command byte.
+pub const FIELD_COMMAND: u8 = 0x20;
+/// The record carries the pins word, as a 16-bit value.
+pub const FIELD_PINS: u8 = 0x40;
+/// The address is the previous address plus a signed byte.
+pub const FIELD_ADDRESS_DELTA: u8 = 0x80;
+
+/// The fields of a cycle log record, as the server stores them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Record {
+    address_bus: u32,
+    data_bus: u16,
+    cpu_state_bits: u8,
+    cpu_status_bits: u8,
+    bus_control_bits: u8,
+    bus_command_bits: u8,
+    pins: u16,
+}
+
+impl From<&ServerCycleState> for Record {
+    fn from(state: &ServerCycleState) -> Self {
+        Record {
+            address_bus: state.address_bus,
+            data_bus: state.data_bus,
+            cpu_state_bits: state.cpu_state_bits,
+            cpu_status_bits: state.cpu_status_bits,
+            bus_control_bits: state.bus_control_bits,
+            bus_command_bits: state.bus_command_bits,
+            pins: state.pins,
+        }
+    }
+}
+
+impl From<Record> for ServerCycleState {
+    fn from(record: Record) -> Self {
+        ServerCycleState {
+            program_state: ProgramState::Execute,
+            address_bus: record.address_bus,
+            data_bus: record.data_bus,
+            cpu_state_bits: record.cpu_state_bits,
+            cpu_status_bits: record.cpu_status_bits,
+            bus_control_bits: record.bus_control_bits,
+            bus_command_bits: record.bus_command_bits,
+            pins: record.pins,
+        }
+    }
+}
+
+/// Decode `count` raw cycle log records from `data`, each `record_len` bytes long.
+pub fn decode_raw_cycle_log(
+    count: usize,
+    record_len: usize,
+    data: &[u8],
+) -> Result<Vec<ServerCycleState>, CpuClientError> {
+    if record_len < CYCLE_RECORD_LEN || data.len() < count * record_len {
+        return Err(CpuClientError::ReadFailure);
+    }
+    Ok(data
+        .chunks_exact(record_len)
+        .take(count)
+        .map(|d| {
+            ServerCycleState::from(Record {
+                address_bus: u32::from_le_bytes([d[0], d[1], d[2], d[3]]),
+                data_bus: u16::from_le_bytes([d[4], d[5]]),
+                cpu_state_bits: d[6],
+                cpu_status_bits: d[7],
+                bus_control_bits: d[8],
+                bus_command_bits: d[9],
+                pins: u16::from_le_bytes([d[10], d[11]]),
+            })
+        })
+        .collect())
+}
+
+/// Decode an encoded cycle log of `count` records.
+pub fn decode_cycle_log(count: usize, data: &[u8]) -> Result<Vec<ServerCycleState>, CpuClientError> {
+    let bad_value = || CpuClientError::BadValue(ServerCommand::CmdGetCycleStates);
+    let mut bytes = data.iter().copied();
+    let mut next_u8 = || bytes.next().ok_or_else(bad_value);
+
+    let mut states = Vec::with_capacity(count);
+    let mut prev = Record::default();
+    while states.len() < count {
+        let mask = next_u8()?;
+        if mask == 0 {
+            let run = next_u8()?;
+            if run == 0 || states.len() + run as usize > count {
+                return Err(bad_value());
+            }
+            states.extend(std::iter::repeat(ServerCycleState::from(prev)).take(run as usize));
+            continue;
+        }
+        if mask & FIELD_ADDRESS != 0 && mask & FIELD_ADDRESS_DELTA != 0 {
+            return Err(bad_value());
+        }
+
+        if mask & FIELD_ADDRESS != 0 {
+            prev.address_bus = u32::from_le_bytes([next_u8()?, next_u8()?, next_u8()?, next_u8()?]);
+        }
+        if mask & FIELD_ADDRESS_DELTA != 0 {
+            prev.address_bus = prev.address_bus.wrapping_add_signed(next_u8()? as i8 as i32);
+        }
+        if mask & FIELD_DATA != 0 {
+            prev.data_bus = u16::from_le_bytes([next_u8()?, next_u8()?]);
+        }
+        if mask & FIELD_STATE != 0 {
+            prev.cpu_state_bits = next_u8()?;
+        }
+        if mask & FIELD_STATUS != 0 {
+            prev.cpu_status_bits = next_u8()?;
+        }
+        if mask & FIELD_CONTROL != 0 {
+            prev.bus_control_bits = next_u8()?;
+        }
+        if mask & FIELD_COMMAND != 0 {
+            prev.bus_command_bits = next_u8()?;
+        }
+        if mask & FIELD_PINS != 0 {
+            prev.pins = u16::from_le_bytes([next_u8()?, next_u8()?]);
+        }
+        states.push(ServerCycleState::from(prev));
+    }
+
+    if bytes.next().is_some() {
+        return Err(bad_value());
+    }
+    Ok(states)
+}
+
+/// Encode `states` in the encoded cycle log format. The server does the same; this is the
+/// reference for it, and serves tools that store or forward cycle logs.
+pub fn encode_cycle_log(states: &[ServerCycleState]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut prev = Record::default();
+    let mut i = 0;
+    while i < states.len() {
+        let record = Record::from(&states[i]);
+        if record == prev {
+            let run = states[i..]
+                .iter()
+                .take(u8::MAX as usize)
+                .take_while(|s| Record::from(*s) == prev)
+                .count();
+            out.extend_from_slice(&[0, run as u8]);
+            i += run;
+            continue;
+        }
+
+        let mask_pos = out.len();
+        out.push(0);
+        let mut mask = 0;
+        let delta = record.address_bus.wrapping_sub(prev.address_bus) as i32;
+        if record.address_bus != prev.address_bus {
+            if let Ok(delta) = i8::try_from(delta) {
+                mask |= FIELD_ADDRESS_DELTA;
+                out.push(delta as u8);
+            }
+            else {
+                mask |= FIELD_ADDRESS;
+                out.extend_from_slice(&record.address_bus.to_le_bytes());
+            }
+        }
+        if record.data_bus != prev.data_bus {
+            mask |= FIELD_DATA;
+            out.extend_from_slice(&record.data_bus.to_le_bytes());
+        }
+        for (field, value, prev_value) in [
+            (FIELD_STATE, record.cpu_state_bits, prev.cpu_state_bits),
+            (FIELD_STATUS, record.cpu_status_bits, prev.cpu_status_bits),
+            (FIELD_CONTROL, record.bus_control_bits, prev.bus_control_bits),
+            (FIELD_COMMAND, record.bus_command_bits, prev.bus_command_bits),
+        ] {
+            if value != prev_value {
+                mask |= field;
+                out.push(value);
+            }
+        }
+        if record.pins != prev.pins {
+            mask |= FIELD_PINS;
+            out.extend_from_slice(&record.pins.to_le_bytes());
+        }
+        out[mask_pos] = mask;
+        prev = record;
+        i += 1;
+    }
+    out
+}
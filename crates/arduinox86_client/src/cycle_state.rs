@@ -24,8 +24,40 @@ use crate::{get_queue_op, BusState, CpuWidth, DataWidth, ProgramState, QueueOp,
 use std::{
     cell::Cell,
     fmt::{Display, Formatter},
+    str::FromStr,
 };
 
+/// How the CPU's bus is wired. In maximum mode an 8288 bus controller decodes the CPU status into
+/// the command bits reported in each cycle. A CPU run in minimum mode drives RD, WR and IO/M
+/// itself and there is no 8288, so the command bits have to be synthesized on the host.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusMode {
+    #[default]
+    Maximum,
+    Minimum,
+}
+
+impl FromStr for BusMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "max" | "maximum" => Ok(BusMode::Maximum),
+            "min" | "minimum" => Ok(BusMode::Minimum),
+            _ => Err(format!("Unknown bus mode '{}', expected 'maximum' or 'minimum'", s)),
+        }
+    }
+}
+
+impl Display for BusMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BusMode::Maximum => write!(f, "maximum"),
+            BusMode::Minimum => write!(f, "minimum"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ServerCycleState {
     pub program_state: ProgramState,
@@ -56,6 +88,46 @@ impl ServerCycleState {
     pub const PIN_BHE: u16 = 0b0000_0000_0000_0010;
     pub const PIN_READY: u16 = 0b0000_0000_0000_0100;
     pub const PIN_LOCK: u16 = 0b0000_0000_0000_1000;
+    // Minimum mode bus pins, set while the signal is asserted regardless of the pin's polarity.
+    pub const PIN_RD: u16 = 0b0000_0000_0001_0000;
+    pub const PIN_WR: u16 = 0b0000_0000_0010_0000;
+    // Set during an I/O cycle. The firmware folds the 8086's M/IO and the 8088's IO/M into this.
+    pub const PIN_IO: u16 = 0b0000_0000_0100_0000;
+
+    /// Synthesize the 8288 command bits for a cycle captured in minimum mode, from the RD, WR and
+    /// IO/M pins in the pins word. INTA comes from the S0-S2 status, as the 8288 would decode it,
+    /// in the T-states the 8288 asserts commands. BHE is kept as reported. Like the 8288's
+    /// outputs, the command bits are active low.
+    pub fn min_mode_command_bits(&self) -> u8 {
+        let mut command = (self.bus_command_bits & Self::COMMAND_BHE_BIT) | !Self::COMMAND_BHE_BIT;
+        let io = self.pins & Self::PIN_IO != 0;
+
+        if self.pins & Self::PIN_RD != 0 {
+            command &= match io {
+                true => !Self::COMMAND_IORC_BIT,
+                false => !Self::COMMAND_MRDC_BIT,
+            };
+        }
+        if self.pins & Self::PIN_WR != 0 {
+            // There are no advanced write strobes without an 8288, so they follow WR.
+            command &= match io {
+                true => !(Self::COMMAND_IOWC_BIT | Self::COMMAND_AIOWC_BIT),
+                false => !(Self::COMMAND_MWTC_BIT | Self::COMMAND_AMWC_BIT),
+            };
+        }
+        if self.cpu_status_bits & 0x07 == 0 && matches!(self.t_state(), TState::T2 | TState::T3 | TState::Tw) {
+            command &= !Self::COMMAND_INTA_BIT;
+        }
+        command
+    }
+
+    /// Replace the command bits with ones synthesized from the bus pins if the CPU is in minimum
+    /// mode. In maximum mode the 8288's bits are already correct and are left alone.
+    pub fn apply_bus_mode(&mut self, mode: BusMode) {
+        if mode == BusMode::Minimum {
+            self.bus_command_bits = self.min_mode_command_bits();
+        }
+    }
 
     #[inline]
     pub fn bhe(&self) -> bool {
@@ -98,26 +170,16 @@ impl ServerCycleState {
     pub fn is_writing(&self) -> bool {
         self.is_writing_mem() || self.is_writing_io()
     }
-}
-
-pub struct ServerCycleStatePrinter {
-    pub cpu_type: ServerCpuType,
-    pub address_latch: u32,
-    pub state: ServerCycleState,
-}
 
-impl ServerCycleStatePrinter {
-    pub fn data_width(&self) -> DataWidth {
-        let cpu_width = CpuWidth::from(self.cpu_type);
-        match cpu_width {
+    /// Return the part of the data bus driven in this cycle, given the address latched at ALE.
+    pub fn data_width(&self, cpu_type: ServerCpuType, address_latch: u32) -> DataWidth {
+        match CpuWidth::from(cpu_type) {
             CpuWidth::Eight => DataWidth::EightLow,
             CpuWidth::Sixteen => {
-                if (self.address_latch & 1 != 0)
-                    && (self.state.bus_command_bits & ServerCycleState::COMMAND_BHE_BIT == 0)
-                {
+                if (address_latch & 1 != 0) && (self.bus_command_bits & Self::COMMAND_BHE_BIT == 0) {
                     DataWidth::EightHigh
                 }
-                else if self.state.pins & ServerCycleState::PIN_BHE == 0 {
+                else if self.pins & Self::PIN_BHE == 0 {
                     DataWidth::Sixteen
                 }
                 else {
@@ -127,6 +189,38 @@ impl ServerCycleStatePrinter {
         }
     }
 
+    /// Return true if data is transferred in this cycle, i.e. a read or write is active and the bus
+    /// is past the address phase.
+    pub fn is_transferring(&self, cpu_type: ServerCpuType) -> bool {
+        match cpu_type {
+            ServerCpuType::Intel80386 => {
+                // The 386 can write on T1, and read after T1.
+                self.is_writing() || (self.is_reading() && self.t_state() != TState::T1)
+            }
+            ServerCpuType::Intel80286 => {
+                // The 286 can read/write after T1
+                (self.is_reading() || self.is_writing()) && self.t_state() != TState::T1
+            }
+            _ => {
+                // Older CPUs can only read/write in PASV state
+                (self.is_reading() || self.is_writing())
+                    && cpu_type.decode_status(self.cpu_status_bits) == BusState::PASV
+            }
+        }
+    }
+}
+
+pub struct ServerCycleStatePrinter {
+    pub cpu_type: ServerCpuType,
+    pub address_latch: u32,
+    pub state: ServerCycleState,
+}
+
+impl ServerCycleStatePrinter {
+    pub fn data_width(&self) -> DataWidth {
+        self.state.data_width(self.cpu_type, self.address_latch)
+    }
+
     pub fn data_bus_str(&self) -> String {
         match self.data_width() {
             DataWidth::Invalid => "----".to_string(),
@@ -205,26 +299,7 @@ impl Display for ServerCycleStatePrinter {
 
         let mut xfer_str = "        ".to_string();
 
-        let bus_active = match self.cpu_type {
-            ServerCpuType::Intel80386 => {
-                // The 386 can write on t1
-                if self.state.is_writing() {
-                    true
-                }
-                else {
-                    // The 386 can read after T1
-                    self.state.t_state() != TState::T1
-                }
-            }
-            ServerCpuType::Intel80286 => {
-                // The 286 can read/write after T1
-                self.state.t_state() != TState::T1
-            }
-            _ => {
-                // Older CPUs can only read/write in PASV state
-                bus_state == BusState::PASV
-            }
-        };
+        let bus_active = self.state.is_transferring(self.cpu_type);
 
         if bus_active {
             let value = self.data_bus_str();
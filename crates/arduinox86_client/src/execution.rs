@@ -0,0 +1,82 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Execution mode ownership.
+//!
+//! The server either runs a loaded program on its own, when [ServerFlags::EXECUTE_AUTOMATIC] is
+//! set, or waits for the client to cycle the CPU. Cycling the CPU while the server is also running
+//! it desyncs both sides, so [CpuClient] tracks the mode from the flags it reads and writes and
+//! refuses cycle commands in automatic mode.
+
+use std::fmt::Display;
+
+use crate::{CpuClient, CpuClientError, ServerFlags};
+
+/// Which side drives CPU cycles.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// The client cycles the CPU one cycle at a time.
+    Manual,
+    /// The server runs the program on its own.
+    Automatic,
+}
+
+impl ExecutionMode {
+    /// The mode selected by a set of server flags.
+    pub fn from_flags(flags: u32) -> Self {
+        if flags & ServerFlags::EXECUTE_AUTOMATIC != 0 {
+            ExecutionMode::Automatic
+        }
+        else {
+            ExecutionMode::Manual
+        }
+    }
+}
+
+impl Display for ExecutionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionMode::Manual => write!(f, "manual"),
+            ExecutionMode::Automatic => write!(f, "automatic"),
+        }
+    }
+}
+
+impl CpuClient {
+    /// The execution mode last read from or written to the server, or None if the flags have not
+    /// been seen yet.
+    pub fn execution_mode(&self) -> Option<ExecutionMode> {
+        self.mode
+    }
+
+    /// Return an error if the server is known to be in a mode other than `mode`.
+    pub fn require_mode(&self, mode: ExecutionMode) -> Result<(), CpuClientError> {
+        match self.mode {
+            Some(active) if active != mode => Err(CpuClientError::ModeConflict {
+                requested: mode,
+                active,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
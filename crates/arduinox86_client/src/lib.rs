@@ -22,9 +22,27 @@
 */
 #![allow(dead_code, unused_variables)]
 
+#[cfg(feature = "tokio")]
+mod async_client;
+#[cfg(feature = "bundle")]
+mod bundle;
+mod bus_ops;
+mod capabilities;
 mod commands;
+mod comparison;
+mod cycle_log_encoding;
 mod cycle_state;
+mod execution;
+mod memory_batch;
+mod phase_budget;
+mod pool;
+mod port_filter;
+mod protocol_log;
+mod reconnect;
 mod registers;
+mod result_cache;
+mod timeline;
+mod transport;
 
 use binrw::BinReaderExt;
 use log;
@@ -38,16 +56,36 @@ use std::{
     io::{Read, Write},
     rc::Rc,
     str,
+    time::{Duration, Instant},
 };
 
-use serialport::{ClearBuffer, SerialPort};
 use thiserror::Error;
 
 pub const ARDUINO_BAUD: u32 = 1000000;
+/// How often [CpuClient::wait_for_state] polls the server status.
+pub const STATE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+#[cfg(feature = "tokio")]
+pub use async_client::*;
 pub use binrw::BinWrite;
+#[cfg(feature = "bundle")]
+pub use bundle::*;
+pub use bus_ops::*;
+pub use capabilities::*;
+pub use comparison::*;
+pub use cycle_log_encoding::*;
 pub use cycle_state::*;
+pub use execution::*;
+pub use memory_batch::*;
+pub use phase_budget::*;
+pub use pool::*;
+pub use port_filter::*;
+pub use protocol_log::*;
+pub use reconnect::*;
 pub use register_printer::*;
 pub use registers::*;
+pub use result_cache::*;
+pub use timeline::*;
+pub use transport::*;
 
 pub struct ServerFlags;
 
@@ -70,6 +108,7 @@ impl ServerFlags {
     pub const ENABLE_CYCLE_LOGGING: u32 = 0x0000_0080; // Enable cycle logging
     pub const ENABLE_ALE_INTERRUPT: u32 = 0x0000_0100; // Enable ALE interrupt to arbitrate READY line
     pub const RESOLVE_BUS_STEP: u32     = 0x0000_0200; // Resolve bus step on each cycle
+    pub const ENCODE_CYCLE_LOG: u32     = 0x0000_0400; // Send cycle logs in the encoded format
 }
 
 /// [ServerCommand] represents the commands that can be sent to the Arduino808X server.
@@ -116,17 +155,62 @@ pub enum ServerCommand {
     CmdGetServerStatus = 0x26,
     CmdClearCycleLog = 0x27,
     CmdSetProgramBounds = 0x28,
+    CmdSetInterruptTrigger = 0x29,
+    CmdGetStateBudget = 0x2A,
+    CmdSetMemoryRanges = 0x2B,
+    CmdSetEndCondition = 0x2C,
+    CmdGetCapabilities = 0x2D,
     CmdInvalid,
 }
 
+impl ServerCommand {
+    /// Whether the server may be in a different [ProgramState] after this command.
+    pub fn may_change_state(&self) -> bool {
+        use ServerCommand::*;
+        matches!(
+            self,
+            CmdReset
+                | CmdLoad
+                | CmdCycle
+                | CmdFinalize
+                | CmdBeginStore
+                | CmdStore
+                | CmdPrefetchStore
+                | CmdPrefetch
+                | CmdStoreAll
+                | CmdGetCycleState
+        )
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct ServerStatus {
     pub state: ProgramState,
     pub cycle_ct: u64,
     pub address_latch: u32,
+    /// Number of state transitions since the server booted. If this advanced by more than one
+    /// since the last poll, the client missed a transition.
+    pub state_seq: u32,
+}
+
+impl ServerStatus {
+    /// Return how many transitions happened between `previous` and this status that neither
+    /// poll saw.
+    pub fn missed_transitions(&self, previous: &ServerStatus) -> u32 {
+        self.state_seq.wrapping_sub(previous.state_seq).saturating_sub(1)
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+/// The cycles and time the server spent in one [ProgramState] since the CPU was last reset, as
+/// returned by [CpuClient::get_state_budget].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ServerStateBudget {
+    pub state:  ProgramState,
+    pub cycles: u32,
+    pub time:   Duration,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TState {
     Ti,
     T1,
@@ -153,7 +237,7 @@ impl TryFrom<u8> for TState {
 }
 
 /// [ServerCpuType] maps to the CPU types that can be detected by the Arduino808X server.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum ServerCpuType {
     #[default]
     Undetected,
@@ -254,6 +338,11 @@ impl ServerCpuType {
             _ => false,
         }
     }
+    /// Returns whether the address and data share the same pins, so the CPU drives the data bus
+    /// with an address while ALE is active. True for every CPU before the 80286.
+    pub fn has_multiplexed_bus(&self) -> bool {
+        !matches!(self, ServerCpuType::Intel80286 | ServerCpuType::Intel80386)
+    }
     /// Returns whether we can prefetch the user program for this CPU type.
     /// Currently, all CPU types support prefetching.
     pub fn can_prefetch(&self) -> bool {
@@ -408,7 +497,7 @@ impl TryFrom<u8> for ServerCpuType {
 }
 
 /// [DataWidth] represents the current width of the data bus.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum DataWidth {
     #[default]
     Invalid,
@@ -604,6 +693,24 @@ impl TryFrom<u8> for ProgramState {
     }
 }
 
+impl ProgramState {
+    /// Whether the CPU's control pins can be written in this state. While the server is running
+    /// its own setup, register load or register store programs, toggling READY or an interrupt
+    /// line corrupts the run.
+    pub fn allows_pin_writes(&self) -> bool {
+        !matches!(
+            self,
+            ProgramState::CpuId
+                | ProgramState::CpuSetup
+                | ProgramState::JumpVector
+                | ProgramState::Load
+                | ProgramState::LoadSmm
+                | ProgramState::Store
+                | ProgramState::StoreAll
+        )
+    }
+}
+
 /// [Segment] represents the segment registers in the CPU.
 #[derive(Copy, Clone, strum_macros::Display, Debug, PartialEq)]
 pub enum Segment {
@@ -670,7 +777,152 @@ pub enum CpuPin {
     NMI,
 }
 
-pub const REQUIRED_PROTOCOL_VER: u8 = 3;
+/// [IrqLine] selects the interrupt line raised by an [InterruptTrigger].
+#[derive(Copy, Clone, strum_macros::Display, Debug, Default, PartialEq, Eq)]
+pub enum IrqLine {
+    #[default]
+    None = 0,
+    Intr,
+    Nmi,
+}
+
+/// [IrqTriggerMode] selects whether an [InterruptTrigger] offset counts instructions or cycles from
+/// the start of execution. Instruction offsets require a CPU with queue status lines.
+#[derive(Copy, Clone, strum_macros::Display, Debug, Default, PartialEq, Eq)]
+pub enum IrqTriggerMode {
+    #[default]
+    Instruction = 0,
+    Cycle,
+}
+
+/// [InterruptTrigger] describes an interrupt for the server to raise during automatic execution.
+/// The trigger stays armed across resets until replaced. The default trigger disarms it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterruptTrigger {
+    pub line:   IrqLine,
+    pub mode:   IrqTriggerMode,
+    /// Vector supplied during the INTA cycle. Ignored for NMI.
+    pub vector: u8,
+    pub offset: u32,
+}
+
+/// [EndCondition] describes an additional way for a program to end, for programs that never reach
+/// the end of their code segment on their own. Once the condition is met, the next code fetch is
+/// treated as a fetch past the end of the program and execution finalizes as usual.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EndCondition {
+    /// End on the first IRET executed by the handler for the given interrupt vector.
+    Iret(u8),
+    /// End after the CPU writes to the given I/O port.
+    PortWrite(u16),
+    /// End when the CPU fetches code from the given address.
+    Fetch(u32),
+    /// End once the given number of cycles have been executed.
+    Cycles(u32),
+}
+
+impl EndCondition {
+    /// Encode the condition as the kind byte and value the server expects. `None` disarms it.
+    pub fn to_bytes(condition: Option<EndCondition>) -> [u8; 5] {
+        let (kind, value) = match condition {
+            None => (0, 0),
+            Some(EndCondition::Iret(vector)) => (1, vector as u32),
+            Some(EndCondition::PortWrite(port)) => (2, port as u32),
+            Some(EndCondition::Fetch(address)) => (3, address),
+            Some(EndCondition::Cycles(cycles)) => (4, cycles),
+        };
+        let mut buf: [u8; 5] = [0; 5];
+        buf[0] = kind;
+        buf[1..5].copy_from_slice(&value.to_le_bytes());
+        buf
+    }
+}
+
+impl Display for EndCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndCondition::Iret(vector) => write!(f, "IRET from INT {:02X}h", vector),
+            EndCondition::PortWrite(port) => write!(f, "write to port {:04X}h", port),
+            EndCondition::Fetch(address) => write!(f, "fetch from {:05X}h", address),
+            EndCondition::Cycles(cycles) => write!(f, "{} cycles", cycles),
+        }
+    }
+}
+
+/// The protocol version this client speaks. Servers back to [MIN_PROTOCOL_VER] are also accepted,
+/// with the commands they lack disabled; see [Capabilities].
+pub const REQUIRED_PROTOCOL_VER: u8 = 9;
+
+/// The identification string the server sends in reply to a version query, followed by the
+/// protocol version byte.
+pub const SERVER_BANNER: &[u8] = b"ardx86 ";
+
+/// Search `buf` for the server banner, ignoring case and any bytes received before it. Returns
+/// the protocol version that follows the banner, or None if the banner or version byte has not
+/// been received yet.
+pub fn find_banner(buf: &[u8]) -> Option<u8> {
+    buf.windows(SERVER_BANNER.len())
+        .position(|window| window.eq_ignore_ascii_case(SERVER_BANNER))
+        .and_then(|pos| buf.get(pos + SERVER_BANNER.len()).copied())
+}
+
+/// Return the USB serial number of a port, which stays the same when the device enumerates again
+/// under a new name.
+fn usb_serial(port: &serialport::SerialPortInfo) -> Option<String> {
+    match &port.port_type {
+        serialport::SerialPortType::UsbPort(info) => info.serial_number.clone(),
+        _ => None,
+    }
+}
+
+/// [DiscoveryOptions] controls how [CpuClient] probes serial ports for a server.
+#[derive(Clone, Debug)]
+pub struct DiscoveryOptions {
+    /// Serial read timeout, in milliseconds.
+    pub timeout: u64,
+    /// Baud rate to open ports with. The Due's native USB port ignores this, so the default is 0;
+    /// boards connected through a USB-serial bridge need a real rate such as [ARDUINO_BAUD].
+    pub baud_rate: u32,
+    /// Time to wait after opening a port before querying it, in milliseconds. Boards that reset
+    /// when the port is opened need this to finish booting.
+    pub settle_delay: u64,
+    /// Number of times to send the version query before giving up on a port.
+    pub banner_attempts: u32,
+    /// Restricts which ports are probed when no port is specified.
+    pub filter: PortFilter,
+    /// Time to wait before sending each command, in microseconds. Half-duplex links such as
+    /// RS-485 need this so the server's transceiver can release the line after its last reply.
+    pub turnaround_delay: u64,
+    /// Use XON/XOFF software flow control. Only for adapters that handle flow control in the
+    /// link itself, as the protocol is binary and may contain the XON and XOFF bytes.
+    pub software_flow_control: bool,
+    /// Extra time added to the read timeout, in milliseconds, for links that add latency such as
+    /// long cables or serial-to-network bridges.
+    pub timeout_margin: u64,
+    /// How the CPU's bus is wired. In minimum mode the 8288 command bits of each cycle are
+    /// synthesized from the bus pins.
+    pub bus_mode: BusMode,
+    /// How to recover when a command fails with a read error. By default the client does not
+    /// reconnect.
+    pub retry: RetryPolicy,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            timeout: 1000,
+            baud_rate: 0,
+            settle_delay: 0,
+            banner_attempts: 1,
+            filter: PortFilter::default(),
+            turnaround_delay: 0,
+            software_flow_control: false,
+            timeout_margin: 0,
+            bus_mode: BusMode::Maximum,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
 
 #[macro_export]
 macro_rules! get_segment {
@@ -738,15 +990,64 @@ pub enum CpuClientError {
     DiscoveryError,
     #[error("{0:?} command returned failure code.")]
     CommandFailed(ServerCommand),
+    #[error("Cannot use {requested} execution while the server is in {active} mode.")]
+    ModeConflict { requested: ExecutionMode, active: ExecutionMode },
+    #[error("Timed out waiting for server state, last state was {0:?}.")]
+    StateTimeout(ProgramState),
+    #[error("Refusing to write {pin:?} while the server is in {state:?}.")]
+    PinWriteBlocked { pin: CpuPin, state: ProgramState },
+    #[error("The client worker thread has stopped.")]
+    WorkerStopped,
+    #[error("The server does not support the {0:?} command.")]
+    Unsupported(ServerCommand),
+    #[error("The server came back speaking protocol version {found}, expected {expected}.")]
+    VersionChanged { expected: u8, found: u8 },
+    #[error("Reconnected after {0:?} failed; the server may have been reset and must be restored.")]
+    Reconnected(ServerCommand),
 }
 
-/// A [CpuClient] represents a connection to an `ArduinoX86` server over a serial port.
+/// A [CpuClient] represents a connection to an `ArduinoX86` server over a [Transport], usually a
+/// serial port.
 pub struct CpuClient {
-    port: Rc<RefCell<Box<dyn serialport::SerialPort>>>,
+    port: Rc<RefCell<Box<dyn Transport>>>,
+    turnaround_delay: std::time::Duration,
+    // Execution mode last seen in the server flags.
+    mode: Option<ExecutionMode>,
+    protocol_log: Option<ProtocolLog>,
+    // Server state as of the last command that reported it, or None if a command since may have
+    // changed it.
+    program_state: Option<ProgramState>,
+    pin_interlock: bool,
+    bus_mode: BusMode,
+    protocol_ver: u8,
+    capabilities: Capabilities,
+    // Where the transport was opened, and the USB serial number of its port, for reconnecting.
+    origin: Option<TransportUri>,
+    usb_serial: Option<String>,
+    options: DiscoveryOptions,
+    reconnects: Vec<Reconnected>,
 }
 
 impl CpuClient {
     pub fn init(com_port: Option<String>, timeout: Option<u64>) -> Result<CpuClient, CpuClientError> {
+        let options = DiscoveryOptions {
+            timeout: timeout.unwrap_or(1000),
+            ..Default::default()
+        };
+        CpuClient::init_with(com_port, &options)
+    }
+
+    /// Find a server, probing either the specified port or every available port. The port may
+    /// also be a [TransportUri] such as `tcp://192.168.1.50:5555`, which is connected to directly.
+    pub fn init_with(com_port: Option<String>, options: &DiscoveryOptions) -> Result<CpuClient, CpuClientError> {
+        let com_port = match com_port {
+            Some(port) => match port.parse::<TransportUri>().map_err(CpuClientError::BadParameter)? {
+                TransportUri::Serial(name) => Some(name),
+                uri => return CpuClient::connect(&uri, options),
+            },
+            None => None,
+        };
+
         let mut matched_port = false;
         match serialport::available_ports() {
             Ok(ports) => {
@@ -757,11 +1058,18 @@ impl CpuClient {
                         }
                         matched_port = true;
                     }
+                    else if !options.filter.matches(&port) {
+                        log::trace!("Skipping filtered port: {}", port.port_name);
+                        continue;
+                    }
                     println!("Trying port: {}", port.port_name);
-                    if let Some(rtk_port) = CpuClient::try_port(port, timeout.unwrap_or(1000)) {
-                        return Ok(CpuClient {
-                            port: Rc::new(RefCell::new(rtk_port)),
-                        });
+                    let uri = TransportUri::Serial(port.port_name.clone());
+                    let usb_serial = usb_serial(&port);
+                    if let Some((rtk_port, proto_ver)) = CpuClient::try_port(port, options) {
+                        let mut client = CpuClient::new(Box::new(rtk_port), proto_ver, options);
+                        client.origin = Some(uri);
+                        client.usb_serial = usb_serial;
+                        return Ok(client);
                     }
                 }
 
@@ -784,90 +1092,396 @@ impl CpuClient {
         }
     }
 
-    /// Try to open the specified serial port and query it for an Arduino808X server.
-    pub fn try_port(port_info: serialport::SerialPortInfo, timeout: u64) -> Option<Box<dyn SerialPort>> {
-        let port_result = serialport::new(port_info.port_name.clone(), 0)
-            .dtr_on_open(true)
-            .baud_rate(0)
-            .timeout(std::time::Duration::from_millis(timeout))
-            .stop_bits(serialport::StopBits::One)
-            .data_bits(serialport::DataBits::Eight)
-            .parity(serialport::Parity::None)
-            .open();
+    /// Open the transport named by `uri` and query it for a server. Unlike [CpuClient::init_with],
+    /// a serial port is opened by name without enumerating ports first.
+    pub fn connect(uri: &TransportUri, options: &DiscoveryOptions) -> Result<CpuClient, CpuClientError> {
+        match CpuClient::open_transport(uri, options) {
+            Ok(transport) => {
+                println!("Trying transport: {}", uri);
+                let mut client = CpuClient::with_transport(transport, options)?;
+                client.origin = Some(uri.clone());
+                if let TransportUri::Serial(name) = uri {
+                    client.usb_serial = serialport::available_ports()
+                        .ok()
+                        .and_then(|ports| ports.into_iter().find(|port| port.port_name == *name))
+                        .and_then(|port| usb_serial(&port));
+                }
+                Ok(client)
+            }
+            Err(e) => {
+                log::warn!("Failed to connect to {}: {}", uri, e);
+                Err(CpuClientError::DiscoveryError)
+            }
+        }
+    }
 
-        match port_result {
-            Ok(mut new_port) => {
-                //log::trace!("Successfully opened host port {}", port_info.port_name);
+    fn open_transport(uri: &TransportUri, options: &DiscoveryOptions) -> Result<Box<dyn Transport>, String> {
+        match uri {
+            TransportUri::Serial(name) => SerialTransport::open(name, options)
+                .map(|t| Box::new(t) as Box<dyn Transport>)
+                .map_err(|e| e.to_string()),
+            TransportUri::Tcp(addr) => TcpTransport::connect(addr, options)
+                .map(|t| Box::new(t) as Box<dyn Transport>)
+                .map_err(|e| e.to_string()),
+            #[cfg(unix)]
+            TransportUri::Unix(path) => UnixTransport::connect(path, options)
+                .map(|t| Box::new(t) as Box<dyn Transport>)
+                .map_err(|e| e.to_string()),
+        }
+    }
 
-                // Flush
-                new_port.clear(ClearBuffer::Input).unwrap();
-                new_port.clear(ClearBuffer::Output).unwrap();
+    /// Query an already open transport for a server, and use it if one answers. A client made this
+    /// way has no way to reopen the transport, so it does not reconnect.
+    pub fn with_transport(
+        mut transport: Box<dyn Transport>,
+        options: &DiscoveryOptions,
+    ) -> Result<CpuClient, CpuClientError> {
+        match CpuClient::handshake(transport.as_mut(), options) {
+            Some(proto_ver) => Ok(CpuClient::new(transport, proto_ver, options)),
+            None => Err(CpuClientError::DiscoveryError),
+        }
+    }
 
-                let cmd: [u8; 1] = [1];
-                let mut buf: [u8; 100] = [0; 100];
+    fn new(transport: Box<dyn Transport>, proto_ver: u8, options: &DiscoveryOptions) -> CpuClient {
+        let mut client = CpuClient {
+            port: Rc::new(RefCell::new(transport)),
+            turnaround_delay: std::time::Duration::from_micros(options.turnaround_delay),
+            mode: None,
+            protocol_log: None,
+            program_state: None,
+            pin_interlock: true,
+            bus_mode: options.bus_mode,
+            protocol_ver: proto_ver,
+            capabilities: Capabilities::for_version(proto_ver),
+            origin: None,
+            usb_serial: None,
+            options: options.clone(),
+            reconnects: Vec::new(),
+        };
+        client.capabilities = client.query_capabilities();
+        let missing = client.capabilities.missing(REQUIRED_PROTOCOL_VER);
+        if missing != Capabilities::default() {
+            log::warn!(
+                "Server speaks protocol version {}, without capabilities: {}",
+                proto_ver,
+                missing
+            );
+        }
+        client
+    }
 
-                _ = new_port.flush();
-                log::trace!("Sending version query to {}...", port_info.port_name);
+    /// Ask the server which optional commands it supports. Servers that predate the query are
+    /// assumed to support what their protocol version introduced, as are servers that fail it.
+    fn query_capabilities(&mut self) -> Capabilities {
+        let assumed = Capabilities::for_version(self.protocol_ver);
+        if self.protocol_ver < CAPABILITIES_PROTOCOL_VER {
+            return assumed;
+        }
 
-                match new_port.write(&cmd) {
-                    Ok(_) => {
-                        log::trace!("Sent version query to {}...", port_info.port_name);
-                    }
-                    Err(e) => {
-                        log::error!("try_port: Write error to {}: {:?}", port_info.port_name, e);
-                        return None;
-                    }
+        let mut buf: [u8; 4] = [0; 4];
+        let result = self
+            .send_command_byte(ServerCommand::CmdGetCapabilities)
+            .and_then(|_| self.recv_buf(&mut buf))
+            .and_then(|_| self.read_result_code(ServerCommand::CmdGetCapabilities));
+        match result {
+            Ok(_) => Capabilities(u32::from_le_bytes(buf)),
+            Err(e) => {
+                log::warn!("Failed to query server capabilities: {}", e);
+                assumed
+            }
+        }
+    }
+
+    /// Return the protocol version the server reported when the client connected.
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_ver
+    }
+
+    /// Return the optional features the server supports. Commands that need a missing feature
+    /// fail with [CpuClientError::Unsupported] without being sent.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Return whether the server supports every feature in `flags`, a combination of the
+    /// [Capabilities] constants.
+    pub fn supports(&self, flags: u32) -> bool {
+        self.capabilities.contains(flags)
+    }
+
+    /// Reopen the transport the client was connected through, and check that the server on it
+    /// speaks the same protocol version. A USB serial port that does not come back under its old
+    /// name is looked for among the ports by its serial number. The server may have been reset, so
+    /// the client forgets the execution mode and program state it last saw.
+    pub fn reconnect(&mut self) -> Result<(), CpuClientError> {
+        let Some(origin) = self.origin.clone()
+        else {
+            log::error!("reconnect(): the client was not opened from a transport URI");
+            return Err(CpuClientError::DiscoveryError);
+        };
+
+        let policy = self.options.retry;
+        for attempt in 1..=policy.reopen_attempts.max(1) {
+            std::thread::sleep(Duration::from_millis(policy.reopen_delay));
+            log::warn!(
+                "Reconnecting to {} (attempt {}/{})...",
+                origin,
+                attempt,
+                policy.reopen_attempts
+            );
+            let Some((transport, uri, proto_ver)) = self.reopen(&origin)
+            else {
+                continue;
+            };
+            if proto_ver != self.protocol_ver {
+                log::error!(
+                    "reconnect(): server on {} speaks protocol version {}, expected {}",
+                    uri,
+                    proto_ver,
+                    self.protocol_ver
+                );
+                return Err(CpuClientError::VersionChanged {
+                    expected: self.protocol_ver,
+                    found:    proto_ver,
+                });
+            }
+
+            *self.port.borrow_mut() = transport;
+            self.origin = Some(uri);
+            self.mode = None;
+            self.program_state = None;
+            self.capabilities = self.query_capabilities();
+            return Ok(());
+        }
+
+        log::error!("reconnect(): no server came back on {}", origin);
+        Err(CpuClientError::DiscoveryError)
+    }
+
+    /// Open `uri` and query it for a server, falling back to any port with the same USB serial
+    /// number. Returns the transport, the URI it was opened from and the server's protocol version.
+    fn reopen(&self, uri: &TransportUri) -> Option<(Box<dyn Transport>, TransportUri, u8)> {
+        match CpuClient::open_transport(uri, &self.options) {
+            Ok(mut transport) => {
+                if let Some(proto_ver) = CpuClient::handshake(transport.as_mut(), &self.options) {
+                    return Some((transport, uri.clone(), proto_ver));
                 }
-                match new_port.flush() {
-                    Ok(_) => {
-                        log::trace!("Flushed output to {}...", port_info.port_name);
+            }
+            Err(e) => log::warn!("reopen(): failed to open {}: {}", uri, e),
+        }
+
+        let serial = self.usb_serial.as_ref()?;
+        let port = serialport::available_ports()
+            .ok()?
+            .into_iter()
+            .find(|port| usb_serial(port).as_ref() == Some(serial))?;
+        let name = port.port_name.clone();
+        CpuClient::try_port(port, &self.options).map(|(transport, proto_ver)| {
+            (
+                Box::new(transport) as Box<dyn Transport>,
+                TransportUri::Serial(name),
+                proto_ver,
+            )
+        })
+    }
+
+    /// Run a command, reconnecting if it fails with a read error and the [RetryPolicy] allows. A
+    /// [retryable](ServerCommand::is_retryable) command is sent again after reconnecting; any
+    /// other command fails with [CpuClientError::Reconnected] once the client has reconnected.
+    fn with_retry<T>(
+        &mut self,
+        cmd: ServerCommand,
+        mut command: impl FnMut(&mut CpuClient) -> Result<T, CpuClientError>,
+    ) -> Result<T, CpuClientError> {
+        let mut retry_ct = 0;
+        loop {
+            match command(self) {
+                Err(e @ (CpuClientError::ReadFailure | CpuClientError::ReadTimeout))
+                    if retry_ct < self.options.retry.retries =>
+                {
+                    retry_ct += 1;
+                    log::warn!(
+                        "{:?} failed: {} Reconnecting ({}/{})...",
+                        cmd,
+                        e,
+                        retry_ct,
+                        self.options.retry.retries
+                    );
+                    self.reconnect()?;
+
+                    let event = Reconnected {
+                        command:   cmd,
+                        transport: self.port.borrow().name().to_string(),
+                        retried:   cmd.is_retryable(),
+                    };
+                    log::warn!("{}", event);
+                    if let Some(log) = &mut self.protocol_log {
+                        log.push(ProtocolEvent::Reconnected(event.transport.clone()));
                     }
-                    Err(e) => {
-                        log::error!("try_port: flush error from {}: {:?}", port_info.port_name, e);
-                        return None;
+                    self.reconnects.push(event);
+                    if !cmd.is_retryable() {
+                        return Err(CpuClientError::Reconnected(cmd));
                     }
                 }
+                result => return result,
+            }
+        }
+    }
 
-                match new_port.read_exact(&mut buf[..9]) {
-                    Ok(bytes) => bytes,
-                    Err(e) => {
-                        log::error!("try_port: Read error from {}: {:?}", port_info.port_name, e);
-                        return None;
-                    }
-                };
+    /// Return the reconnects since the last call, oldest first. The server may have been reset by
+    /// each one, losing its flags and any program that was running, so callers should restore them.
+    pub fn take_reconnects(&mut self) -> Vec<Reconnected> {
+        std::mem::take(&mut self.reconnects)
+    }
+
+    /// Set how the client recovers from failed reads, replacing the policy from the
+    /// [DiscoveryOptions] it was opened with.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.options.retry = policy;
+    }
 
-                new_port.clear(serialport::ClearBuffer::Input).unwrap();
-                let ver_text = str::from_utf8(&buf).unwrap();
-                if ver_text.contains("ardX86 ") {
-                    let proto_ver = buf[7];
+    /// Try to open the specified serial port and query it for an Arduino808X server. Returns the
+    /// open port and the server's protocol version.
+    pub fn try_port(
+        port_info: serialport::SerialPortInfo,
+        options: &DiscoveryOptions,
+    ) -> Option<(SerialTransport, u8)> {
+        match SerialTransport::open(&port_info.port_name, options) {
+            Ok(mut new_port) => {
+                //log::trace!("Successfully opened host port {}", port_info.port_name);
+                CpuClient::handshake(&mut new_port, options).map(|proto_ver| (new_port, proto_ver))
+            }
+            Err(e) => {
+                log::error!("try_port: Error opening host port {}: {}", port_info.port_name, e);
+                None
+            }
+        }
+    }
+
+    /// Query a newly opened transport for an Arduino808X server with a supported protocol version,
+    /// and return the version.
+    fn handshake(transport: &mut dyn Transport, options: &DiscoveryOptions) -> Option<u8> {
+        let name = transport.name().to_string();
+        if options.settle_delay > 0 {
+            log::trace!("Waiting {}ms for {} to settle...", options.settle_delay, name);
+            std::thread::sleep(std::time::Duration::from_millis(options.settle_delay));
+        }
+
+        for attempt in 1..=options.banner_attempts.max(1) {
+            // Flush anything the board sent while booting.
+            _ = transport.clear_input();
+            _ = transport.clear_output();
+
+            if options.turnaround_delay > 0 {
+                std::thread::sleep(std::time::Duration::from_micros(options.turnaround_delay));
+            }
+
+            log::trace!(
+                "Sending version query to {} (attempt {}/{})...",
+                name,
+                attempt,
+                options.banner_attempts
+            );
+            if let Err(e) = transport.write(&[ServerCommand::CmdVersion as u8]) {
+                log::error!("try_port: Write error to {}: {:?}", name, e);
+                return None;
+            }
+            if let Err(e) = transport.flush() {
+                log::error!("try_port: flush error from {}: {:?}", name, e);
+                return None;
+            }
+
+            match CpuClient::read_banner(transport) {
+                Some(proto_ver) => {
+                    _ = transport.clear_input();
                     log::trace!(
                         "Found an ArduinoX86 server, protocol verison: {} on port {}",
                         proto_ver,
-                        port_info.port_name
+                        name
                     );
-
-                    if proto_ver != REQUIRED_PROTOCOL_VER {
-                        log::error!("Unsupported protocol version.");
+                    if !(MIN_PROTOCOL_VER..=REQUIRED_PROTOCOL_VER).contains(&proto_ver) {
+                        log::error!(
+                            "Unsupported protocol version {}, expected {} to {}.",
+                            proto_ver,
+                            MIN_PROTOCOL_VER,
+                            REQUIRED_PROTOCOL_VER
+                        );
                         return None;
                     }
+                    if proto_ver < REQUIRED_PROTOCOL_VER {
+                        log::warn!(
+                            "Server protocol version {} is older than {}; newer features will be disabled.",
+                            proto_ver,
+                            REQUIRED_PROTOCOL_VER
+                        );
+                    }
+                    return Some(proto_ver);
+                }
+                None => {
+                    log::trace!("No banner from {} on attempt {}", name, attempt);
                 }
-                Some(new_port)
             }
-            Err(e) => {
-                log::error!("try_port: Error opening host port {}: {}", port_info.port_name, e);
-                None
+        }
+
+        log::error!("try_port: No ArduinoX86 server found on {}", name);
+        None
+    }
+
+    /// Read from the port until the server banner and protocol version arrive, skipping any
+    /// leading garbage. Returns None if the read times out first.
+    fn read_banner(port: &mut dyn Transport) -> Option<u8> {
+        const MAX_BANNER_READ: usize = 256;
+        let mut received: Vec<u8> = Vec::new();
+        let mut buf: [u8; 32] = [0; 32];
+
+        while received.len() < MAX_BANNER_READ {
+            match port.read(&mut buf) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    received.extend_from_slice(&buf[..n]);
+                    if let Some(proto_ver) = find_banner(&received) {
+                        return Some(proto_ver);
+                    }
+                }
+                Err(e) => {
+                    log::trace!("read_banner: {:?} after {} bytes", e, received.len());
+                    return None;
+                }
             }
         }
+        None
+    }
+
+    /// Start keeping a [ProtocolLog] of the last `capacity` protocol events, replacing any log
+    /// already kept.
+    pub fn enable_protocol_log(&mut self, capacity: usize) {
+        self.protocol_log = Some(ProtocolLog::new(capacity));
+    }
+
+    pub fn disable_protocol_log(&mut self) {
+        self.protocol_log = None;
+    }
+
+    pub fn protocol_log(&self) -> Option<&ProtocolLog> {
+        self.protocol_log.as_ref()
     }
 
     pub fn send_command_byte(&mut self, cmd: ServerCommand) -> Result<(), CpuClientError> {
+        if !self.capabilities.contains(cmd.capability()) {
+            return Err(CpuClientError::Unsupported(cmd));
+        }
+        if let Some(log) = &mut self.protocol_log {
+            log.push(ProtocolEvent::Command(cmd));
+        }
+        if cmd.may_change_state() {
+            self.program_state = None;
+        }
         let cmd: [u8; 1] = [cmd as u8];
-        let mut flush_buf: [u8; 100] = [0; 100];
         let mut port = self.port.borrow_mut();
-        port.clear(ClearBuffer::Input)
-            .map_err(|_| CpuClientError::WriteFailure)?;
-        if port.bytes_to_read().map_err(|_| CpuClientError::ReadFailure)? > 0 {
-            let _flushed_bytes = port.read(&mut flush_buf).map_err(|_| CpuClientError::ReadFailure)?;
+        port.clear_input().map_err(|_| CpuClientError::WriteFailure)?;
+
+        if !self.turnaround_delay.is_zero() {
+            std::thread::sleep(self.turnaround_delay);
         }
 
         match port.write(&cmd) {
@@ -879,7 +1493,11 @@ impl CpuClient {
     pub fn read_result_code(&mut self, cmd: ServerCommand) -> Result<bool, CpuClientError> {
         let mut buf: [u8; 1] = [0; 1];
 
-        match self.port.borrow_mut().read_exact(&mut buf) {
+        let read = self.port.borrow_mut().read_exact(&mut buf);
+        if let Some(log) = &mut self.protocol_log {
+            log.push(ProtocolEvent::Result(cmd, read.is_ok().then_some(buf[0])));
+        }
+        match read {
             Ok(()) => {
                 if (buf[0] & 0x01) != 0 {
                     // LSB set in return code == success
@@ -898,6 +1516,9 @@ impl CpuClient {
     }
 
     pub fn send_buf(&mut self, buf: &[u8]) -> Result<bool, CpuClientError> {
+        if let Some(log) = &mut self.protocol_log {
+            log.push_sent(buf);
+        }
         match self.port.borrow_mut().write(&buf) {
             Ok(bytes) => {
                 if bytes != buf.len() {
@@ -912,25 +1533,25 @@ impl CpuClient {
     }
 
     pub fn recv_buf(&mut self, buf: &mut [u8]) -> Result<bool, CpuClientError> {
-        self.port
-            .borrow_mut()
-            .read_exact(buf)
-            .map_err(|_| CpuClientError::ReadFailure)
-            .and_then(|_| {
-                if buf.len() == 0 {
-                    Err(CpuClientError::ReadFailure)
-                }
-                else {
-                    Ok(true)
-                }
-            })
+        let read = self.port.borrow_mut().read_exact(buf);
+        if let Some(log) = &mut self.protocol_log {
+            log.push_received(read.is_ok().then_some(&*buf));
+        }
+        match read {
+            Ok(()) if !buf.is_empty() => Ok(true),
+            _ => Err(CpuClientError::ReadFailure),
+        }
     }
 
     /// Receive a buffer of dynamic size (don't expect the entire buffer read like recv_buf does)
     /// Returns the number of bytes read.
     /// Primarily used for get_last_error
     pub fn recv_dyn_buf(&mut self, buf: &mut [u8]) -> Result<usize, CpuClientError> {
-        match self.port.borrow_mut().read(buf) {
+        let read = self.port.borrow_mut().read(buf);
+        if let Some(log) = &mut self.protocol_log {
+            log.push_received(read.as_ref().ok().map(|bytes| &buf[..*bytes]));
+        }
+        match read {
             Ok(bytes) => Ok(bytes),
             Err(_) => Err(CpuClientError::ReadFailure),
         }
@@ -948,22 +1569,24 @@ impl CpuClient {
         reg_type: RegisterSetType,
         reg_data: &[u8],
     ) -> Result<bool, CpuClientError> {
-        self.send_command_byte(ServerCommand::CmdLoad)?;
-        let mut buf: [u8; 1] = [0; 1];
-        buf[0] = reg_type.into();
-        self.send_buf(&buf)?;
-
-        let expected_buf_size = reg_type.size();
-        if reg_data.len() < expected_buf_size {
-            return Err(CpuClientError::BadParameter(format!(
-                "Expected at least {} byte buffer for register data, got: {}",
-                expected_buf_size,
-                reg_data.len()
-            )));
-        }
+        self.with_retry(ServerCommand::CmdLoad, |client| {
+            client.send_command_byte(ServerCommand::CmdLoad)?;
+            let mut buf: [u8; 1] = [0; 1];
+            buf[0] = reg_type.into();
+            client.send_buf(&buf)?;
+
+            let expected_buf_size = reg_type.size();
+            if reg_data.len() < expected_buf_size {
+                return Err(CpuClientError::BadParameter(format!(
+                    "Expected at least {} byte buffer for register data, got: {}",
+                    expected_buf_size,
+                    reg_data.len()
+                )));
+            }
 
-        self.send_buf(&reg_data[0..expected_buf_size])?;
-        self.read_result_code(ServerCommand::CmdLoad)
+            client.send_buf(&reg_data[0..expected_buf_size])?;
+            client.read_result_code(ServerCommand::CmdLoad)
+        })
     }
 
     pub fn begin_store(&mut self) -> Result<bool, CpuClientError> {
@@ -1017,69 +1640,100 @@ impl CpuClient {
         Ok(regs)
     }
 
-    pub fn store_registers_to_buf(&mut self, reg_data: &mut [u8]) -> Result<u8, CpuClientError> {
-        self.send_command_byte(ServerCommand::CmdStore)?;
-        let mut buf: [u8; 1] = [0; 1];
-        self.recv_buf(&mut buf)?;
+    /// Read back the register set the server stored at the end of the last run, for use with
+    /// [CpuClient::restore_registers_snapshot].
+    pub fn save_registers_snapshot(&mut self) -> Result<RemoteCpuRegisters, CpuClientError> {
+        self.store_registers()
+    }
+
+    /// Load a register set, such as one returned by [CpuClient::save_registers_snapshot], as the
+    /// initial registers of the next run.
+    pub fn restore_registers_snapshot(&mut self, regs: &RemoteCpuRegisters) -> Result<bool, CpuClientError> {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        regs.write(&mut buf)
+            .map_err(|e| CpuClientError::BadParameter(format!("Couldn't encode registers: {e}")))?;
+        self.load_registers_from_buf(RegisterSetType::from(regs), buf.get_ref())
+    }
 
-        match buf[0] {
-            0 => {
-                // Type 1 register set (Intel808X)
-                if reg_data.len() < 28 {
-                    return Err(CpuClientError::BadParameter(
-                        "Expected at least 28 bytes for Intel808X register set".to_string(),
-                    ));
+    pub fn store_registers_to_buf(&mut self, reg_data: &mut [u8]) -> Result<u8, CpuClientError> {
+        self.with_retry(ServerCommand::CmdStore, |client| {
+            client.send_command_byte(ServerCommand::CmdStore)?;
+            let mut buf: [u8; 1] = [0; 1];
+            client.recv_buf(&mut buf)?;
+
+            match buf[0] {
+                0 => {
+                    // Type 1 register set (Intel808X)
+                    if reg_data.len() < 28 {
+                        return Err(CpuClientError::BadParameter(
+                            "Expected at least 28 bytes for Intel808X register set".to_string(),
+                        ));
+                    }
                 }
-            }
-            1 => {
-                // Type 2 register set (Intel286)
-                if reg_data.len() < 102 {
-                    return Err(CpuClientError::BadParameter(
-                        "Expected at least 102 bytes for Intel286 register set".to_string(),
-                    ));
+                1 => {
+                    // Type 2 register set (Intel286)
+                    if reg_data.len() < 102 {
+                        return Err(CpuClientError::BadParameter(
+                            "Expected at least 102 bytes for Intel286 register set".to_string(),
+                        ));
+                    }
                 }
-            }
-            2 => {
-                // Type 3A register set (Intel386 LOADALL)
-                if reg_data.len() < 204 {
-                    return Err(CpuClientError::BadParameter(
-                        "Expected at least 204 bytes for Intel386 LOADALL register set".to_string(),
-                    ));
+                2 => {
+                    // Type 3A register set (Intel386 LOADALL)
+                    if reg_data.len() < 204 {
+                        return Err(CpuClientError::BadParameter(
+                            "Expected at least 204 bytes for Intel386 LOADALL register set".to_string(),
+                        ));
+                    }
                 }
-            }
-            3 => {
-                // Type 3B register set (Intel386 SMM Register dump)
-                if reg_data.len() < 208 {
-                    return Err(CpuClientError::BadParameter(format!(
-                        "Expected at least 208 bytes for Intel386 SMM register set, got: {}",
-                        reg_data.len()
-                    )));
+                3 => {
+                    // Type 3B register set (Intel386 SMM Register dump)
+                    if reg_data.len() < 208 {
+                        return Err(CpuClientError::BadParameter(format!(
+                            "Expected at least 208 bytes for Intel386 SMM register set, got: {}",
+                            reg_data.len()
+                        )));
+                    }
+                }
+                _ => {
+                    // invalid register set type
+                    return Err(CpuClientError::BadValue(ServerCommand::CmdStore));
                 }
             }
-            _ => {
-                // invalid register set type
-                return Err(CpuClientError::BadValue(ServerCommand::CmdStore));
-            }
-        }
-        self.recv_buf(reg_data)?;
-        self.read_result_code(ServerCommand::CmdStore)?;
+            client.recv_buf(reg_data)?;
+            client.read_result_code(ServerCommand::CmdStore)?;
 
-        Ok(buf[0])
+            Ok(buf[0])
+        })
     }
 
     pub fn cycle(&mut self) -> Result<bool, CpuClientError> {
-        self.send_command_byte(ServerCommand::CmdCycle)?;
-        self.read_result_code(ServerCommand::CmdCycle)
+        self.with_retry(ServerCommand::CmdCycle, |client| {
+            client.require_mode(ExecutionMode::Manual)?;
+            client.send_command_byte(ServerCommand::CmdCycle)?;
+            client.read_result_code(ServerCommand::CmdCycle)
+        })
     }
 
-    pub fn cpu_type(&mut self) -> Result<(ServerCpuType, bool), CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        self.send_command_byte(ServerCommand::CmdCpuType)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdCpuType)?;
+    /// Hard-reset the CPU. No register state is loaded, so this is mostly useful to recover a CPU
+    /// that has shut down. Server flags and the memory strategy are not part of the reset.
+    pub fn reset_cpu(&mut self) -> Result<bool, CpuClientError> {
+        self.with_retry(ServerCommand::CmdReset, |client| {
+            client.send_command_byte(ServerCommand::CmdReset)?;
+            client.read_result_code(ServerCommand::CmdReset)
+        })
+    }
 
-        let cpu_type = ServerCpuType::try_from(buf[0])?;
-        Ok((cpu_type, buf[0] & 0x40 != 0))
+    pub fn cpu_type(&mut self) -> Result<(ServerCpuType, bool), CpuClientError> {
+        self.with_retry(ServerCommand::CmdCpuType, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            client.send_command_byte(ServerCommand::CmdCpuType)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdCpuType)?;
+
+            let cpu_type = ServerCpuType::try_from(buf[0])?;
+            Ok((cpu_type, buf[0] & 0x40 != 0))
+        })
     }
 
     pub fn init_screen(&mut self) -> Result<bool, CpuClientError> {
@@ -1092,62 +1746,74 @@ impl CpuClient {
     }
 
     pub fn read_address_latch(&mut self) -> Result<u32, CpuClientError> {
-        let mut buf: [u8; 3] = [0; 3];
-        self.send_command_byte(ServerCommand::CmdReadAddressLatch)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdReadAddressLatch)?;
+        self.with_retry(ServerCommand::CmdReadAddressLatch, |client| {
+            let mut buf: [u8; 3] = [0; 3];
+            client.send_command_byte(ServerCommand::CmdReadAddressLatch)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdReadAddressLatch)?;
 
-        let address = buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16;
+            let address = buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16;
 
-        Ok(address)
+            Ok(address)
+        })
     }
 
     pub fn read_address(&mut self) -> Result<u32, CpuClientError> {
-        let mut buf: [u8; 3] = [0; 3];
-        self.send_command_byte(ServerCommand::CmdReadAddressU)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdReadAddressU)?;
+        self.with_retry(ServerCommand::CmdReadAddressU, |client| {
+            let mut buf: [u8; 3] = [0; 3];
+            client.send_command_byte(ServerCommand::CmdReadAddressU)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdReadAddressU)?;
 
-        let address = buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16;
+            let address = buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16;
 
-        Ok(address)
+            Ok(address)
+        })
     }
 
     pub fn read_status(&mut self) -> Result<u8, CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        self.send_command_byte(ServerCommand::CmdReadStatus)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdReadStatus)?;
+        self.with_retry(ServerCommand::CmdReadStatus, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            client.send_command_byte(ServerCommand::CmdReadStatus)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdReadStatus)?;
 
-        Ok(buf[0])
+            Ok(buf[0])
+        })
     }
 
     pub fn read_8288_command(&mut self) -> Result<u8, CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        self.send_command_byte(ServerCommand::CmdRead8288Command)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdRead8288Command)?;
+        self.with_retry(ServerCommand::CmdRead8288Command, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            client.send_command_byte(ServerCommand::CmdRead8288Command)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdRead8288Command)?;
 
-        Ok(buf[0])
+            Ok(buf[0])
+        })
     }
 
     pub fn read_8288_control(&mut self) -> Result<u8, CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        self.send_command_byte(ServerCommand::CmdRead8288Control)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdRead8288Control)?;
+        self.with_retry(ServerCommand::CmdRead8288Control, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            client.send_command_byte(ServerCommand::CmdRead8288Control)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdRead8288Control)?;
 
-        Ok(buf[0])
+            Ok(buf[0])
+        })
     }
 
     pub fn read_data_bus(&mut self) -> Result<u16, CpuClientError> {
-        let mut buf: [u8; 2] = [0; 2];
-        self.send_command_byte(ServerCommand::CmdReadDataBus)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdReadDataBus)?;
-
-        let word = u16::from_le_bytes([buf[0], buf[1]]);
-        Ok(word)
+        self.with_retry(ServerCommand::CmdReadDataBus, |client| {
+            let mut buf: [u8; 2] = [0; 2];
+            client.send_command_byte(ServerCommand::CmdReadDataBus)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdReadDataBus)?;
+
+            let word = u16::from_le_bytes([buf[0], buf[1]]);
+            Ok(word)
+        })
     }
 
     pub fn write_data_bus(&mut self, data: u16) -> Result<bool, CpuClientError> {
@@ -1172,36 +1838,82 @@ impl CpuClient {
     }
 
     pub fn get_program_state(&mut self) -> Result<ProgramState, CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        self.send_command_byte(ServerCommand::CmdGetProgramState)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdGetProgramState)?;
+        self.with_retry(ServerCommand::CmdGetProgramState, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            client.send_command_byte(ServerCommand::CmdGetProgramState)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdGetProgramState)?;
+
+            let state = ProgramState::try_from(buf[0])?;
+            client.program_state = Some(state);
+            Ok(state)
+        })
+    }
 
-        ProgramState::try_from(buf[0])
+    /// Return the server state as of the last command that reported it, if no command since could
+    /// have changed it.
+    pub fn program_state(&self) -> Option<ProgramState> {
+        self.program_state
+    }
+
+    /// Enable or disable the check that refuses pin writes while the server is loading or storing
+    /// registers. It is on by default; turn it off only if you know the write is safe.
+    pub fn set_pin_interlock(&mut self, enabled: bool) {
+        self.pin_interlock = enabled;
+    }
+
+    pub fn bus_mode(&self) -> BusMode {
+        self.bus_mode
+    }
+
+    /// Set how the CPU's bus is wired. In minimum mode, the command bits of the cycles returned by
+    /// [CpuClient::get_cycle_states] are synthesized from the bus pins in each cycle's pins word.
+    /// [CpuClient::get_cycle_state] doesn't receive a pins word, so its command bits are left as
+    /// the server reported them.
+    pub fn set_bus_mode(&mut self, mode: BusMode) {
+        self.bus_mode = mode;
     }
 
     pub fn get_last_error(&mut self) -> Result<String, CpuClientError> {
-        let mut errbuf: [u8; 50] = [0; 50];
-        self.send_command_byte(ServerCommand::CmdGetLastError)?;
-        let bytes = self.recv_dyn_buf(&mut errbuf)?;
-        let err_string = str::from_utf8(&errbuf[..bytes - 1]).unwrap();
+        self.with_retry(ServerCommand::CmdGetLastError, |client| {
+            let mut errbuf: [u8; 50] = [0; 50];
+            client.send_command_byte(ServerCommand::CmdGetLastError)?;
+            let bytes = client.recv_dyn_buf(&mut errbuf)?;
+            let err_string = str::from_utf8(&errbuf[..bytes - 1]).unwrap();
 
-        Ok(err_string.to_string())
+            Ok(err_string.to_string())
+        })
     }
 
     pub fn read_pin(&mut self, pin_no: CpuPin) -> Result<bool, CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        let mut recv_buf: [u8; 1] = [0; 1];
-        buf[0] = pin_no as u8;
-        self.send_command_byte(ServerCommand::CmdReadPin)?;
-        self.send_buf(&mut buf)?;
-        self.recv_buf(&mut recv_buf)?;
-        self.read_result_code(ServerCommand::CmdReadPin)?;
-
-        Ok(recv_buf[0] != 0)
+        self.with_retry(ServerCommand::CmdReadPin, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            let mut recv_buf: [u8; 1] = [0; 1];
+            buf[0] = pin_no as u8;
+            client.send_command_byte(ServerCommand::CmdReadPin)?;
+            client.send_buf(&mut buf)?;
+            client.recv_buf(&mut recv_buf)?;
+            client.read_result_code(ServerCommand::CmdReadPin)?;
+
+            Ok(recv_buf[0] != 0)
+        })
     }
 
+    /// Set a CPU control pin. Fails with [CpuClientError::PinWriteBlocked] if the server is in a
+    /// state where writing the pin would corrupt the run, unless the interlock is disabled with
+    /// [CpuClient::set_pin_interlock].
     pub fn write_pin(&mut self, pin_no: CpuPin, val: bool) -> Result<bool, CpuClientError> {
+        if self.pin_interlock {
+            let state = match self.program_state {
+                Some(state) if state.allows_pin_writes() => state,
+                // Unknown, or blocked when last seen. The server may have moved on, so ask it.
+                _ => self.get_program_state()?,
+            };
+            if !state.allows_pin_writes() {
+                return Err(CpuClientError::PinWriteBlocked { pin: pin_no, state });
+            }
+        }
+
         let mut buf: [u8; 2] = [0; 2];
         buf[0] = pin_no as u8;
         buf[1] = val as u8;
@@ -1219,6 +1931,7 @@ impl CpuClient {
     pub fn get_cycle_state(&mut self, cycle: bool) -> Result<ServerCycleState, CpuClientError> {
         let mut send_buf: [u8; 1] = [0; 1];
         if cycle {
+            self.require_mode(ExecutionMode::Manual)?;
             send_buf[0] = 1;
         }
         let mut recv_buf: [u8; 11] = [0; 11];
@@ -1227,8 +1940,11 @@ impl CpuClient {
         self.recv_buf(&mut recv_buf)?;
         self.read_result_code(ServerCommand::CmdGetCycleState)?;
 
+        let program_state = ProgramState::try_from(recv_buf[0])?;
+        self.program_state = Some(program_state);
+
         let cycle_state = ServerCycleState {
-            program_state: ProgramState::try_from(recv_buf[0])?,
+            program_state,
             cpu_state_bits: recv_buf[1],
             cpu_status_bits: recv_buf[2],
             bus_control_bits: recv_buf[3],
@@ -1244,19 +1960,29 @@ impl CpuClient {
     }
 
     pub fn set_flags(&mut self, flags: u32) -> Result<bool, CpuClientError> {
-        let buf: [u8; 4] = flags.to_le_bytes();
-        self.send_command_byte(ServerCommand::CmdSetFlags)?;
-        self.send_buf(&buf)?;
-        self.read_result_code(ServerCommand::CmdSetFlags)
+        self.with_retry(ServerCommand::CmdSetFlags, |client| {
+            let buf: [u8; 4] = flags.to_le_bytes();
+            client.send_command_byte(ServerCommand::CmdSetFlags)?;
+            client.send_buf(&buf)?;
+            let result = client.read_result_code(ServerCommand::CmdSetFlags)?;
+            if result {
+                client.mode = Some(ExecutionMode::from_flags(flags));
+            }
+            Ok(result)
+        })
     }
 
     pub fn get_flags(&mut self) -> Result<u32, CpuClientError> {
-        let mut buf: [u8; 4] = [0; 4];
-        self.send_command_byte(ServerCommand::CmdGetFlags)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdGetFlags)?;
-
-        Ok(u32::from_le_bytes(buf))
+        self.with_retry(ServerCommand::CmdGetFlags, |client| {
+            let mut buf: [u8; 4] = [0; 4];
+            client.send_command_byte(ServerCommand::CmdGetFlags)?;
+            client.recv_buf(&mut buf)?;
+            client.read_result_code(ServerCommand::CmdGetFlags)?;
+
+            let flags = u32::from_le_bytes(buf);
+            client.mode = Some(ExecutionMode::from_flags(flags));
+            Ok(flags)
+        })
     }
 
     pub fn storeall(&mut self) -> Result<bool, CpuClientError> {
@@ -1272,78 +1998,105 @@ impl CpuClient {
     }
 
     pub fn set_random_seed(&mut self, seed: u32) -> Result<bool, CpuClientError> {
-        let buf: [u8; 4] = seed.to_le_bytes();
-        self.send_command_byte(ServerCommand::CmdSetRandomSeed)?;
-        self.send_buf(&buf)?;
-        self.read_result_code(ServerCommand::CmdSetRandomSeed)
+        self.with_retry(ServerCommand::CmdSetRandomSeed, |client| {
+            let buf: [u8; 4] = seed.to_le_bytes();
+            client.send_command_byte(ServerCommand::CmdSetRandomSeed)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdSetRandomSeed)
+        })
     }
 
     pub fn set_memory(&mut self, address: u32, data_buf: &[u8]) -> Result<bool, CpuClientError> {
+        self.with_retry(ServerCommand::CmdSetMemory, |client| {
+            log::trace!(
+                "set_memory(): uploading {} bytes to address 0x{:08X}",
+                data_buf.len(),
+                address
+            );
+            let mut buf: [u8; 4] = address.to_le_bytes();
+            let data_buf_len = data_buf.len() as u32;
+            if data_buf_len == 0 {
+                return Err(CpuClientError::BadParameter("Data buffer cannot be empty".to_string()));
+            }
+            client.send_command_byte(ServerCommand::CmdSetMemory)?;
+            // Send address
+            client.send_buf(&mut buf)?;
+            // Send size
+            buf = data_buf_len.to_le_bytes();
+            client.send_buf(&mut buf)?;
+            // Send data
+            client.send_buf(data_buf)?;
+            client.read_result_code(ServerCommand::CmdSetMemory)
+        })
+    }
+
+    /// Upload every range staged in `batch` with a single command. An empty batch is not sent.
+    /// Servers without [Capabilities::MEMORY_RANGES] are sent one [CpuClient::set_memory] per range.
+    pub fn set_memory_ranges(&mut self, batch: &MemoryBatch) -> Result<bool, CpuClientError> {
+        if batch.is_empty() {
+            return Ok(true);
+        }
+        if !self.supports(Capabilities::MEMORY_RANGES) {
+            for (address, data) in batch.ranges() {
+                self.set_memory(*address, data)?;
+            }
+            return Ok(true);
+        }
+        let range_ct = u16::try_from(batch.ranges().len())
+            .map_err(|_| CpuClientError::BadParameter(format!("Too many ranges: {}", batch.ranges().len())))?;
         log::trace!(
-            "set_memory(): uploading {} bytes to address 0x{:08X}",
-            data_buf.len(),
-            address
+            "set_memory_ranges(): uploading {} bytes in {} ranges",
+            batch.byte_len(),
+            range_ct
         );
-        let mut buf: [u8; 4] = address.to_le_bytes();
-        let data_buf_len = data_buf.len() as u32;
-        if data_buf_len == 0 {
-            return Err(CpuClientError::BadParameter("Data buffer cannot be empty".to_string()));
+
+        let mut buf = Vec::with_capacity(2 + batch.ranges().len() * 8 + batch.byte_len());
+        buf.extend_from_slice(&range_ct.to_le_bytes());
+        for (address, data) in batch.ranges() {
+            buf.extend_from_slice(&address.to_le_bytes());
+            buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            buf.extend_from_slice(data);
         }
-        self.send_command_byte(ServerCommand::CmdSetMemory)?;
-        // Send address
-        self.send_buf(&mut buf)?;
-        // Send size
-        buf = data_buf_len.to_le_bytes();
-        self.send_buf(&mut buf)?;
-        // Send data
-        self.send_buf(data_buf)?;
-        self.read_result_code(ServerCommand::CmdSetMemory)
+        self.with_retry(ServerCommand::CmdSetMemoryRanges, |client| {
+            client.send_command_byte(ServerCommand::CmdSetMemoryRanges)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdSetMemoryRanges)
+        })
     }
 
+    /// Download the cycle log. Logs sent in the encoded format, when
+    /// [ServerFlags::ENCODE_CYCLE_LOG] is set, are decoded transparently.
     pub fn get_cycle_states(&mut self) -> Result<Vec<ServerCycleState>, CpuClientError> {
-        let mut param_buf: [u8; 8] = [0; 8];
-
-        self.send_command_byte(ServerCommand::CmdGetCycleStates)?;
-        // We are guaranteed to have at least 8 bytes in the buffer, a count and a size
-        self.recv_buf(&mut param_buf)?;
-        let cycle_count = u32::from_le_bytes([param_buf[0], param_buf[1], param_buf[2], param_buf[3]]);
-        let data_size = u32::from_le_bytes([param_buf[4], param_buf[5], param_buf[6], param_buf[7]]);
-
-        if cycle_count == 0 {
-            return Ok(Vec::new());
-        }
-
-        let struct_size = data_size / cycle_count;
-        let mut receive_buf = vec![0; data_size as usize];
-        self.recv_buf(&mut receive_buf)?;
+        self.with_retry(ServerCommand::CmdGetCycleStates, |client| {
+            let mut param_buf: [u8; 8] = [0; 8];
+
+            client.send_command_byte(ServerCommand::CmdGetCycleStates)?;
+            // We are guaranteed to have at least 8 bytes in the buffer, a count and a size
+            client.recv_buf(&mut param_buf)?;
+            let count_word = u32::from_le_bytes([param_buf[0], param_buf[1], param_buf[2], param_buf[3]]);
+            let data_size = u32::from_le_bytes([param_buf[4], param_buf[5], param_buf[6], param_buf[7]]);
+            let encoded = count_word & CYCLE_LOG_ENCODED_BIT != 0;
+            let cycle_count = count_word & !CYCLE_LOG_ENCODED_BIT;
+
+            if cycle_count == 0 {
+                return Ok(Vec::new());
+            }
 
-        let mut cycles: Vec<ServerCycleState> = Vec::with_capacity(cycle_count as usize);
+            let mut receive_buf = vec![0; data_size as usize];
+            client.recv_buf(&mut receive_buf)?;
 
-        for i in 0..cycle_count {
-            let offset = (i * struct_size) as usize;
-            if offset + struct_size as usize > receive_buf.len() {
-                return Err(CpuClientError::ReadFailure);
-            }
-            let cycle_data = &receive_buf[offset..offset + struct_size as usize];
-            if cycle_data.len() < 12 {
-                return Err(CpuClientError::ReadFailure);
-            }
-            let cycle_state = ServerCycleState {
-                program_state: ProgramState::Execute,
-                address_bus: u32::from_le_bytes([cycle_data[0], cycle_data[1], cycle_data[2], cycle_data[3]]),
-                data_bus: u16::from_le_bytes([cycle_data[4], cycle_data[5]]),
-                cpu_state_bits: cycle_data[6],
-                cpu_status_bits: cycle_data[7],
-                bus_control_bits: cycle_data[8],
-                bus_command_bits: cycle_data[9],
-                pins: u16::from_le_bytes([cycle_data[10], cycle_data[11]]), // Skip pins [10][11]
+            let mut cycles = match encoded {
+                true => decode_cycle_log(cycle_count as usize, &receive_buf)?,
+                false => decode_raw_cycle_log(cycle_count as usize, (data_size / cycle_count) as usize, &receive_buf)?,
             };
-            cycles.push(cycle_state);
-        }
+            for cycle_state in &mut cycles {
+                cycle_state.apply_bus_mode(client.bus_mode);
+            }
 
-        self.read_result_code(ServerCommand::CmdGetCycleStates)?;
+            client.read_result_code(ServerCommand::CmdGetCycleStates)?;
 
-        Ok(cycles)
+            Ok(cycles)
+        })
     }
 
     pub fn set_memory_strategy(
@@ -1352,14 +2105,16 @@ impl CpuClient {
         start: u32,
         end: u32,
     ) -> Result<bool, CpuClientError> {
-        let mut buf: [u8; 9] = [0; 9];
-        buf[0] = strategy as u8;
-        buf[1..5].copy_from_slice(&start.to_le_bytes());
-        buf[5..9].copy_from_slice(&end.to_le_bytes());
-
-        self.send_command_byte(ServerCommand::CmdSetMemoryStrategy)?;
-        self.send_buf(&buf)?;
-        self.read_result_code(ServerCommand::CmdSetMemoryStrategy)
+        self.with_retry(ServerCommand::CmdSetMemoryStrategy, |client| {
+            let mut buf: [u8; 9] = [0; 9];
+            buf[0] = strategy as u8;
+            buf[1..5].copy_from_slice(&start.to_le_bytes());
+            buf[5..9].copy_from_slice(&end.to_le_bytes());
+
+            client.send_command_byte(ServerCommand::CmdSetMemoryStrategy)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdSetMemoryStrategy)
+        })
     }
 
     /// Request a block of memory from the server.
@@ -1421,28 +2176,116 @@ impl CpuClient {
     }
 
     pub fn enable_debug(&mut self, enable: bool) -> Result<(), CpuClientError> {
-        let mut buf: [u8; 1] = [0; 1];
-        buf[0] = if enable { 1 } else { 0 };
-        self.send_command_byte(ServerCommand::CmdEnableDebug)?;
-        self.send_buf(&buf)?;
-        self.read_result_code(ServerCommand::CmdEnableDebug)?;
-        Ok(())
+        self.with_retry(ServerCommand::CmdEnableDebug, |client| {
+            let mut buf: [u8; 1] = [0; 1];
+            buf[0] = if enable { 1 } else { 0 };
+            client.send_command_byte(ServerCommand::CmdEnableDebug)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdEnableDebug)?;
+            Ok(())
+        })
     }
 
+    /// Read the server status. Servers without [Capabilities::STATE_SEQUENCE] report no sequence
+    /// number, so `state_seq` is always 0 for them.
     pub fn server_status(&mut self) -> Result<ServerStatus, CpuClientError> {
-        // 1 + 8 + 4 = 13 bytes total
-        let mut buf: [u8; 13] = [0; 13];
-        self.send_command_byte(ServerCommand::CmdGetServerStatus)?;
-        self.recv_buf(&mut buf)?;
-        self.read_result_code(ServerCommand::CmdGetServerStatus)?;
-
-        let state = ProgramState::try_from(buf[0])?;
-        let cycle_ct = u64::from_le_bytes([buf[1], buf[2], buf[3], buf[4], buf[5], buf[6], buf[7], buf[8]]);
-        let address_latch = u32::from_le_bytes([buf[9], buf[10], buf[11], buf[12]]);
-        Ok(ServerStatus {
-            state,
-            cycle_ct,
-            address_latch,
+        self.with_retry(ServerCommand::CmdGetServerStatus, |client| {
+            // 1 + 8 + 4 + 4 = 17 bytes total
+            let mut buf: [u8; 17] = [0; 17];
+            let len = match client.supports(Capabilities::STATE_SEQUENCE) {
+                true => buf.len(),
+                false => 13,
+            };
+            client.send_command_byte(ServerCommand::CmdGetServerStatus)?;
+            client.recv_buf(&mut buf[..len])?;
+            client.read_result_code(ServerCommand::CmdGetServerStatus)?;
+
+            let state = ProgramState::try_from(buf[0])?;
+            client.program_state = Some(state);
+            let cycle_ct = u64::from_le_bytes([buf[1], buf[2], buf[3], buf[4], buf[5], buf[6], buf[7], buf[8]]);
+            let address_latch = u32::from_le_bytes([buf[9], buf[10], buf[11], buf[12]]);
+            let state_seq = u32::from_le_bytes([buf[13], buf[14], buf[15], buf[16]]);
+            Ok(ServerStatus {
+                state,
+                cycle_ct,
+                address_latch,
+                state_seq,
+            })
+        })
+    }
+
+    /// Poll the server status until the server is in `state`, and return the status that showed
+    /// it. See [CpuClient::wait_for_states].
+    pub fn wait_for_state(&mut self, state: ProgramState, timeout: Duration) -> Result<ServerStatus, CpuClientError> {
+        self.wait_for_states(&[state], timeout, STATE_POLL_INTERVAL)
+    }
+
+    /// Poll the server status every `interval` until the server is in one of `states`, and return
+    /// the status that showed it. Transitions that happened between polls are logged, as a state
+    /// the caller waits for may have come and gone unseen. Fails with
+    /// [CpuClientError::StateTimeout] if no wanted state is seen within `timeout`.
+    pub fn wait_for_states(
+        &mut self,
+        states: &[ProgramState],
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<ServerStatus, CpuClientError> {
+        let start = Instant::now();
+        let mut last = self.server_status()?;
+        loop {
+            if states.contains(&last.state) {
+                return Ok(last);
+            }
+            if start.elapsed() >= timeout {
+                return Err(CpuClientError::StateTimeout(last.state));
+            }
+            std::thread::sleep(interval);
+
+            let status = self.server_status()?;
+            let missed = status.missed_transitions(&last);
+            if missed > 0 {
+                log::debug!(
+                    "Missed {} state transition(s) between {:?} and {:?}",
+                    missed,
+                    last.state,
+                    status.state
+                );
+            }
+            last = status;
+        }
+    }
+
+    /// Return the cycles and time the server spent in each state since the CPU was last reset. The
+    /// state the server is in is counted up to the time of the request. States the server has not
+    /// entered are included with zero counts.
+    pub fn get_state_budget(&mut self) -> Result<Vec<ServerStateBudget>, CpuClientError> {
+        self.with_retry(ServerCommand::CmdGetStateBudget, |client| {
+            let mut count_buf: [u8; 1] = [0; 1];
+            client.send_command_byte(ServerCommand::CmdGetStateBudget)?;
+            client.recv_buf(&mut count_buf)?;
+
+            let mut buf = vec![0; count_buf[0] as usize * 8];
+            if !buf.is_empty() {
+                client.recv_buf(&mut buf)?;
+            }
+            client.read_result_code(ServerCommand::CmdGetStateBudget)?;
+
+            let budget =
+                buf.chunks_exact(8)
+                    .enumerate()
+                    .filter_map(|(i, entry)| {
+                        // Skip states this client doesn't know about.
+                        let state = ProgramState::try_from(i as u8).ok()?;
+                        Some(ServerStateBudget {
+                            state,
+                            cycles: u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                            time: Duration::from_micros(
+                                u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]) as u64
+                            ),
+                        })
+                    })
+                    .collect();
+            Ok(budget)
         })
     }
 
@@ -1452,12 +2295,40 @@ impl CpuClient {
     }
 
     pub fn set_program_bounds(&mut self, start: u32, end: u32) -> Result<bool, CpuClientError> {
-        let mut buf: [u8; 8] = [0; 8];
-        buf[0..4].copy_from_slice(&start.to_le_bytes());
-        buf[4..8].copy_from_slice(&end.to_le_bytes());
+        self.with_retry(ServerCommand::CmdSetProgramBounds, |client| {
+            let mut buf: [u8; 8] = [0; 8];
+            buf[0..4].copy_from_slice(&start.to_le_bytes());
+            buf[4..8].copy_from_slice(&end.to_le_bytes());
+
+            client.send_command_byte(ServerCommand::CmdSetProgramBounds)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdSetProgramBounds)
+        })
+    }
 
-        self.send_command_byte(ServerCommand::CmdSetProgramBounds)?;
-        self.send_buf(&buf)?;
-        self.read_result_code(ServerCommand::CmdSetProgramBounds)
+    pub fn set_interrupt_trigger(&mut self, trigger: &InterruptTrigger) -> Result<bool, CpuClientError> {
+        self.with_retry(ServerCommand::CmdSetInterruptTrigger, |client| {
+            let mut buf: [u8; 7] = [0; 7];
+            buf[0] = trigger.line as u8;
+            buf[1] = trigger.mode as u8;
+            buf[2] = trigger.vector;
+            buf[3..7].copy_from_slice(&trigger.offset.to_le_bytes());
+
+            client.send_command_byte(ServerCommand::CmdSetInterruptTrigger)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdSetInterruptTrigger)
+        })
+    }
+
+    /// Arm an [EndCondition] for automatic execution, or disarm it with `None`. Like the interrupt
+    /// trigger, the condition stays armed across resets until replaced.
+    pub fn set_end_condition(&mut self, condition: Option<EndCondition>) -> Result<bool, CpuClientError> {
+        self.with_retry(ServerCommand::CmdSetEndCondition, |client| {
+            let buf = EndCondition::to_bytes(condition);
+
+            client.send_command_byte(ServerCommand::CmdSetEndCondition)?;
+            client.send_buf(&buf)?;
+            client.read_result_code(ServerCommand::CmdSetEndCondition)
+        })
     }
 }
@@ -0,0 +1,105 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Host-side staging of memory writes.
+//!
+//! Every [CpuClient::set_memory](crate::CpuClient::set_memory) call pays the full latency of a
+//! command round trip, which dominates the cost of the handful of small writes made to set up a
+//! test. A [MemoryBatch] collects those writes on the host, merging ones that overlap or touch, so
+//! they can be sent to the server in a single [CpuClient::set_memory_ranges](crate::CpuClient::set_memory_ranges)
+//! command.
+
+/// A set of memory writes staged on the host.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBatch {
+    /// Staged ranges as (address, data), sorted by address. No two ranges overlap or touch.
+    ranges: Vec<(u32, Vec<u8>)>,
+    writes: usize,
+}
+
+impl MemoryBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage `data` to be written at `address`. A write that overlaps or touches a staged range is
+    /// merged into it, and where they overlap the later write wins, as it would on the server.
+    pub fn stage(&mut self, address: u32, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        self.writes += 1;
+
+        let mut start = address as u64;
+        let mut end = start + data.len() as u64;
+
+        // Pull out every staged range that overlaps or touches the new write.
+        let mut merged = Vec::new();
+        let mut i = 0;
+        while i < self.ranges.len() {
+            let (r_addr, r_data) = &self.ranges[i];
+            let r_start = *r_addr as u64;
+            let r_end = r_start + r_data.len() as u64;
+            if r_start <= end && start <= r_end {
+                start = start.min(r_start);
+                end = end.max(r_end);
+                merged.push(self.ranges.remove(i));
+            }
+            else {
+                i += 1;
+            }
+        }
+
+        let mut buf = vec![0; (end - start) as usize];
+        for (r_addr, r_data) in merged.iter().chain(std::iter::once(&(address, data.to_vec()))) {
+            let offset = (*r_addr as u64 - start) as usize;
+            buf[offset..offset + r_data.len()].copy_from_slice(r_data);
+        }
+        let insert_at = self.ranges.partition_point(|(r_addr, _)| (*r_addr as u64) < start);
+        self.ranges.insert(insert_at, (start as u32, buf));
+    }
+
+    /// Return the staged ranges as (address, data), sorted by address.
+    pub fn ranges(&self) -> &[(u32, Vec<u8>)] {
+        &self.ranges
+    }
+
+    /// Return the number of calls to [MemoryBatch::stage] since the batch was last cleared.
+    pub fn writes(&self) -> usize {
+        self.writes
+    }
+
+    /// Return the total number of staged bytes.
+    pub fn byte_len(&self) -> usize {
+        self.ranges.iter().map(|(_, data)| data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+        self.writes = 0;
+    }
+}
@@ -0,0 +1,200 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Accounting of where the cycles and time of a run went.
+//!
+//! A run passes through a fixed sequence of phases: the jump to the load segment, the register
+//! load program, the preload program, the user program, the finalize program and the register
+//! store. Only the user program is the instruction under test; everything else is overhead of the
+//! protocol and firmware. A [PhaseBudget] records the cycles and time spent in each phase, so the
+//! overhead can be measured and compared between firmware versions and settings.
+
+use std::{fmt::Display, time::Duration};
+
+use crate::{ProgramState, ServerStateBudget};
+
+/// A phase of a run, as counted by [PhaseBudget].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RunPhase {
+    JumpVector,
+    Load,
+    Preload,
+    Program,
+    Finalize,
+    Store,
+}
+
+impl RunPhase {
+    pub const ALL: [RunPhase; 6] = [
+        RunPhase::JumpVector,
+        RunPhase::Load,
+        RunPhase::Preload,
+        RunPhase::Program,
+        RunPhase::Finalize,
+        RunPhase::Store,
+    ];
+
+    /// Return the phase a server state is counted in, or None for states outside of a run, such
+    /// as [ProgramState::Reset] or [ProgramState::StoreDone].
+    pub fn from_server_state(state: ProgramState) -> Option<RunPhase> {
+        match state {
+            ProgramState::CpuSetup | ProgramState::JumpVector => Some(RunPhase::JumpVector),
+            ProgramState::Load | ProgramState::LoadSmm => Some(RunPhase::Load),
+            ProgramState::EmuEnter | ProgramState::Prefetch => Some(RunPhase::Preload),
+            ProgramState::Execute => Some(RunPhase::Program),
+            ProgramState::ExecuteFinalize | ProgramState::EmuExit => Some(RunPhase::Finalize),
+            ProgramState::Store | ProgramState::StoreAll => Some(RunPhase::Store),
+            _ => None,
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Display for RunPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RunPhase::JumpVector => "JumpVector",
+            RunPhase::Load => "Load",
+            RunPhase::Preload => "Preload",
+            RunPhase::Program => "Program",
+            RunPhase::Finalize => "Finalize",
+            RunPhase::Store => "Store",
+        };
+        f.pad(name)
+    }
+}
+
+/// The cycles and time spent in one [RunPhase].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseCost {
+    pub cycles: u64,
+    pub time:   Duration,
+}
+
+impl PhaseCost {
+    pub fn add(&mut self, other: PhaseCost) {
+        self.cycles += other.cycles;
+        self.time += other.time;
+    }
+}
+
+/// The cycles and time spent in each [RunPhase] of one or more runs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseBudget {
+    costs: [PhaseCost; RunPhase::ALL.len()],
+}
+
+impl PhaseBudget {
+    /// Build a budget from the per-state accounting returned by
+    /// [crate::CpuClient::get_state_budget].
+    pub fn from_server(states: &[ServerStateBudget]) -> Self {
+        let mut budget = PhaseBudget::default();
+        for state in states {
+            if let Some(phase) = RunPhase::from_server_state(state.state) {
+                budget.add(phase, state.cycles as u64, state.time);
+            }
+        }
+        budget
+    }
+
+    pub fn add(&mut self, phase: RunPhase, cycles: u64, time: Duration) {
+        self.costs[phase.index()].add(PhaseCost { cycles, time });
+    }
+
+    pub fn get(&self, phase: RunPhase) -> PhaseCost {
+        self.costs[phase.index()]
+    }
+
+    /// Replace the cost of `phase` with its cost in `other`.
+    pub fn set_from(&mut self, other: &PhaseBudget, phase: RunPhase) {
+        self.costs[phase.index()] = other.get(phase);
+    }
+
+    /// Add every phase of `other` to this budget, such as to total the runs of a test set.
+    pub fn merge(&mut self, other: &PhaseBudget) {
+        for (cost, other) in self.costs.iter_mut().zip(other.costs) {
+            cost.add(other);
+        }
+    }
+
+    pub fn total(&self) -> PhaseCost {
+        let mut total = PhaseCost::default();
+        for cost in self.costs {
+            total.add(cost);
+        }
+        total
+    }
+
+    /// Return the cost of every phase but [RunPhase::Program].
+    pub fn overhead(&self) -> PhaseCost {
+        let mut overhead = self.total();
+        let program = self.get(RunPhase::Program);
+        overhead.cycles -= program.cycles;
+        overhead.time -= program.time;
+        overhead
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RunPhase, PhaseCost)> + '_ {
+        RunPhase::ALL.iter().map(|phase| (*phase, self.get(*phase)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == PhaseCost::default()
+    }
+}
+
+impl Display for PhaseBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.total();
+        let percent = |part: u64| {
+            if total.cycles == 0 {
+                0.0
+            }
+            else {
+                part as f64 * 100.0 / total.cycles as f64
+            }
+        };
+        for (phase, cost) in self.iter() {
+            writeln!(
+                f,
+                "{:<10} {:>12} cycles ({:>5.1}%) {:>12.2?}",
+                phase,
+                cost.cycles,
+                percent(cost.cycles),
+                cost.time
+            )?;
+        }
+        let overhead = self.overhead();
+        write!(
+            f,
+            "{:<10} {:>12} cycles ({:>5.1}%) {:>12.2?}",
+            "Overhead",
+            overhead.cycles,
+            percent(overhead.cycles),
+            overhead.time
+        )
+    }
+}
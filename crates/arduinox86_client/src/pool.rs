@@ -0,0 +1,96 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! A pool of servers, for spreading long jobs such as test generation over several boards.
+//!
+//! A [CpuClient] is tied to the thread that opened it, so the pool holds the address of each
+//! server rather than open connections. Workers take a board's [TransportUri] and open their own
+//! connection with [CpuClientPool::connect].
+
+use crate::{CpuClient, CpuClientError, DiscoveryOptions, TransportUri};
+
+/// The servers found on the host, in the order they were probed.
+#[derive(Clone, Debug)]
+pub struct CpuClientPool {
+    uris:    Vec<TransportUri>,
+    options: DiscoveryOptions,
+}
+
+impl CpuClientPool {
+    /// Probe every serial port that passes the filter in `options`, and keep the ones with a
+    /// server listening. Fails if no server is found.
+    pub fn discover(options: &DiscoveryOptions) -> Result<CpuClientPool, CpuClientError> {
+        let ports = serialport::available_ports().map_err(|e| {
+            log::warn!("Didn't find any serial ports: {:?}", e);
+            CpuClientError::EnumerationError
+        })?;
+
+        let mut uris = Vec::new();
+        for port in ports {
+            if !options.filter.matches(&port) {
+                log::trace!("Skipping filtered port: {}", port.port_name);
+                continue;
+            }
+            let name = port.port_name.clone();
+            println!("Trying port: {}", name);
+            // The port is closed again when the transport is dropped; workers reopen it by name.
+            if CpuClient::try_port(port, options).is_some() {
+                uris.push(TransportUri::Serial(name));
+            }
+        }
+
+        if uris.is_empty() {
+            return Err(CpuClientError::DiscoveryError);
+        }
+        Ok(CpuClientPool::from_uris(uris, options))
+    }
+
+    /// Make a pool of known servers, without probing them.
+    pub fn from_uris(uris: Vec<TransportUri>, options: &DiscoveryOptions) -> CpuClientPool {
+        CpuClientPool {
+            uris,
+            options: options.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.uris.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uris.is_empty()
+    }
+
+    pub fn uris(&self) -> &[TransportUri] {
+        &self.uris
+    }
+
+    /// Open a connection to board `index`.
+    pub fn connect(&self, index: usize) -> Result<CpuClient, CpuClientError> {
+        let uri = self
+            .uris
+            .get(index)
+            .ok_or_else(|| CpuClientError::BadParameter(format!("No board {} in a pool of {}", index, self.len())))?;
+        CpuClient::connect(uri, &self.options)
+    }
+}
@@ -0,0 +1,118 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+use std::{fmt::Display, str::FromStr};
+
+use serialport::{SerialPortInfo, SerialPortType};
+
+/// A USB vendor and product ID pair. A missing product ID matches any product from the vendor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: Option<u16>,
+}
+
+impl UsbId {
+    pub fn matches(&self, vid: u16, pid: u16) -> bool {
+        self.vid == vid && self.pid.map_or(true, |p| p == pid)
+    }
+}
+
+impl FromStr for UsbId {
+    type Err = String;
+
+    /// Parse a `VID:PID` pair in hex, e.g. `2341:0043`. The PID may be `*` or omitted to match
+    /// any product.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_hex = |h: &str| u16::from_str_radix(h.trim(), 16).map_err(|e| format!("Bad USB ID '{}': {}", s, e));
+        match s.split_once(':') {
+            Some((vid, "*")) => Ok(UsbId {
+                vid: parse_hex(vid)?,
+                pid: None,
+            }),
+            Some((vid, pid)) => Ok(UsbId {
+                vid: parse_hex(vid)?,
+                pid: Some(parse_hex(pid)?),
+            }),
+            None => Ok(UsbId {
+                vid: parse_hex(s)?,
+                pid: None,
+            }),
+        }
+    }
+}
+
+impl Display for UsbId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "{:04X}:{:04X}", self.vid, pid),
+            None => write!(f, "{:04X}:*", self.vid),
+        }
+    }
+}
+
+/// [PortFilter] narrows the set of serial ports probed during discovery.
+///
+/// A port is a candidate if it is not on the deny list, is on the allow list (when one is given),
+/// and is a USB port matching one of `usb_ids` (when any are given). Port name patterns match
+/// exactly, or by prefix if they end in `*` (e.g. `/dev/ttyACM*`).
+#[derive(Clone, Debug, Default)]
+pub struct PortFilter {
+    pub allow:   Vec<String>,
+    pub deny:    Vec<String>,
+    pub usb_ids: Vec<UsbId>,
+}
+
+impl PortFilter {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty() && self.usb_ids.is_empty()
+    }
+
+    pub fn matches(&self, port: &SerialPortInfo) -> bool {
+        if self.deny.iter().any(|p| name_matches(p, &port.port_name)) {
+            return false;
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|p| name_matches(p, &port.port_name)) {
+            return false;
+        }
+        if !self.usb_ids.is_empty() {
+            return match &port.port_type {
+                SerialPortType::UsbPort(info) => self.usb_ids.iter().any(|id| id.matches(info.vid, info.pid)),
+                _ => false,
+            };
+        }
+        true
+    }
+
+    /// Return the ports from `ports` that pass the filter.
+    pub fn apply(&self, ports: Vec<SerialPortInfo>) -> Vec<SerialPortInfo> {
+        ports.into_iter().filter(|p| self.matches(p)).collect()
+    }
+}
+
+fn name_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
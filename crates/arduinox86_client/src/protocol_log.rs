@@ -0,0 +1,167 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! A bounded record of recent protocol traffic.
+//!
+//! When enabled with [CpuClient::enable_protocol_log](crate::CpuClient::enable_protocol_log), the
+//! client notes every command it sends, every result code and the size and leading bytes of every
+//! buffer sent or received. Only the most recent entries are kept, so the log can stay on for a
+//! whole session and still show what led up to a failure.
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::ServerCommand;
+
+/// Default number of entries kept in a [ProtocolLog].
+pub const PROTOCOL_LOG_LEN: usize = 1024;
+/// Number of bytes of each buffer kept in the log.
+const PREVIEW_LEN: usize = 16;
+
+#[derive(Clone, Debug)]
+pub enum ProtocolEvent {
+    /// A command byte was sent.
+    Command(ServerCommand),
+    /// A result code was read for a command. None if the read failed.
+    Result(ServerCommand, Option<u8>),
+    /// A buffer was sent, with its total length and its first bytes.
+    Sent(usize, Vec<u8>),
+    /// A buffer was received, with its total length and its first bytes. None if the read failed.
+    Received(Option<(usize, Vec<u8>)>),
+    /// The transport was reopened after a failed read, under the given name.
+    Reconnected(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ProtocolLogEntry {
+    /// Time since the log was started.
+    pub at:    Duration,
+    pub event: ProtocolEvent,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProtocolLog {
+    start:    Instant,
+    capacity: usize,
+    entries:  VecDeque<ProtocolLogEntry>,
+}
+
+impl ProtocolLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            start:    Instant::now(),
+            capacity: capacity.max(1),
+            entries:  VecDeque::with_capacity(capacity.max(1)),
+        }
+    }
+
+    pub fn push(&mut self, event: ProtocolEvent) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(ProtocolLogEntry {
+            at: self.start.elapsed(),
+            event,
+        });
+    }
+
+    /// Record a sent buffer, keeping only its first bytes.
+    pub fn push_sent(&mut self, buf: &[u8]) {
+        self.push(ProtocolEvent::Sent(buf.len(), preview(buf)));
+    }
+
+    /// Record a received buffer, keeping only its first bytes.
+    pub fn push_received(&mut self, buf: Option<&[u8]>) {
+        self.push(ProtocolEvent::Received(buf.map(|buf| (buf.len(), preview(buf)))));
+    }
+
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &ProtocolLogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for ProtocolLog {
+    fn default() -> Self {
+        Self::new(PROTOCOL_LOG_LEN)
+    }
+}
+
+fn preview(buf: &[u8]) -> Vec<u8> {
+    buf[..buf.len().min(PREVIEW_LEN)].to_vec()
+}
+
+fn write_bytes(f: &mut std::fmt::Formatter<'_>, len: usize, bytes: &[u8]) -> std::fmt::Result {
+    write!(f, "{} bytes:", len)?;
+    for byte in bytes {
+        write!(f, " {:02X}", byte)?;
+    }
+    if len > bytes.len() {
+        write!(f, " ...")?;
+    }
+    Ok(())
+}
+
+impl Display for ProtocolLogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>10.6} ", self.at.as_secs_f64())?;
+        match &self.event {
+            ProtocolEvent::Command(command) => write!(f, "> {:?}", command),
+            ProtocolEvent::Result(command, Some(code)) => write!(f, "< {:?} result {:02X}", command, code),
+            ProtocolEvent::Result(command, None) => write!(f, "< {:?} result read failed", command),
+            ProtocolEvent::Sent(len, bytes) => {
+                write!(f, "> ")?;
+                write_bytes(f, *len, bytes)
+            }
+            ProtocolEvent::Received(Some((len, bytes))) => {
+                write!(f, "< ")?;
+                write_bytes(f, *len, bytes)
+            }
+            ProtocolEvent::Received(None) => write!(f, "< read failed"),
+            ProtocolEvent::Reconnected(name) => write!(f, "reconnected to {}", name),
+        }
+    }
+}
+
+impl Display for ProtocolLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Recovering from a dropped connection.
+//!
+//! A USB hiccup can take a serial port away in the middle of a long session. With a [RetryPolicy]
+//! in its [DiscoveryOptions](crate::DiscoveryOptions), a [CpuClient](crate::CpuClient) whose
+//! command fails with a read error reopens its transport, checks that the server still speaks the
+//! same protocol version, and sends the command again if doing so is safe. The server may have
+//! been reset in the meantime, so every reconnect is kept as a [Reconnected] event for the caller
+//! to collect with [CpuClient::take_reconnects](crate::CpuClient::take_reconnects), and reads of
+//! server state fail rather than return what a reset server reports.
+
+use std::fmt::Display;
+
+use crate::ServerCommand;
+
+/// [RetryPolicy] controls how a [CpuClient](crate::CpuClient) recovers from a failed read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of times to reconnect and resend a failed command. 0 disables reconnecting.
+    pub retries: u32,
+    /// Number of times to try reopening the transport on each reconnect.
+    pub reopen_attempts: u32,
+    /// Milliseconds to wait before each attempt to reopen the transport, to give a USB device
+    /// time to enumerate again.
+    pub reopen_delay: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            reopen_attempts: 5,
+            reopen_delay: 1000,
+        }
+    }
+}
+
+/// A [Reconnected] event records that the client reopened its transport after a command failed.
+#[derive(Clone, Debug)]
+pub struct Reconnected {
+    /// The command whose failure caused the reconnect.
+    pub command:   ServerCommand,
+    /// Name of the transport the client reconnected through. A serial port may come back under a
+    /// different name.
+    pub transport: String,
+    /// Whether the command was sent again. Commands that are not
+    /// [retryable](ServerCommand::is_retryable) fail with
+    /// [CpuClientError::Reconnected](crate::CpuClientError::Reconnected) instead.
+    pub retried:   bool,
+}
+
+impl Display for Reconnected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Reconnected to {} after {:?} failed; the command was {}",
+            self.transport,
+            self.command,
+            if self.retried { "sent again" } else { "not retried" }
+        )
+    }
+}
+
+impl ServerCommand {
+    /// Whether this command is sent again after a reconnect. Commands that set server state leave
+    /// it as a single send would, and the board's version, CPU and capabilities survive a reset.
+    /// Reads of server state are not retried, as the reconnect may have reset the server; they
+    /// fail with [CpuClientError::Reconnected](crate::CpuClientError::Reconnected) instead.
+    pub fn is_retryable(&self) -> bool {
+        use ServerCommand::*;
+        matches!(
+            self,
+            CmdVersion
+                | CmdCpuType
+                | CmdGetCapabilities
+                | CmdSetFlags
+                | CmdSetRandomSeed
+                | CmdSetMemory
+                | CmdEnableDebug
+                | CmdSetMemoryStrategy
+                | CmdSetProgramBounds
+                | CmdSetInterruptTrigger
+                | CmdSetMemoryRanges
+                | CmdSetEndCondition
+        )
+    }
+}
@@ -21,6 +21,7 @@
     DEALINGS IN THE SOFTWARE.
 */
 
+pub mod register_buffer;
 pub mod register_printer;
 pub mod register_traits;
 pub mod registers_common;
@@ -28,7 +29,10 @@ pub mod registers_v1;
 pub mod registers_v2;
 pub mod registers_v3;
 
+pub use register_buffer::RegisterBuffer;
 pub use register_traits::{Registers16, Registers32};
+#[cfg(feature = "use_moo")]
+pub use register_traits::{moo_registers16_init, moo_registers32_init};
 pub use registers_common::RemoteCpuRegisters;
 pub use registers_v1::RemoteCpuRegistersV1;
 pub use registers_v2::{RemoteCpuRegistersV2, SegmentDescriptorV1};
@@ -0,0 +1,80 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Encoding and decoding of register sets to and from the byte layouts exchanged with the
+//! ArduinoX86 server. All register buffer (de)serialization should go through [RegisterBuffer]
+//! so that there is exactly one definition of each layout.
+
+use std::io::{Cursor, Write};
+
+use binrw::{BinRead, BinWrite};
+
+use crate::{RemoteCpuRegistersV1, RemoteCpuRegistersV2, RemoteCpuRegistersV3A, RemoteCpuRegistersV3B};
+
+/// A register set with a fixed-size little-endian wire layout.
+pub trait RegisterBuffer: Sized {
+    /// The size of the encoded register set, in bytes.
+    const BUFFER_LEN: usize;
+
+    /// Write the register set to `writer` in its wire layout.
+    fn encode<W: Write>(&self, writer: &mut W) -> std::io::Result<()>;
+
+    /// Read a register set from the start of `buf`. Trailing bytes are ignored.
+    fn decode(buf: &[u8]) -> Result<Self, &'static str>;
+
+    /// Encode the register set to a new buffer of [RegisterBuffer::BUFFER_LEN] bytes.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::BUFFER_LEN);
+        self.encode(&mut buf).expect("Writing to a Vec cannot fail");
+        buf
+    }
+}
+
+macro_rules! impl_register_buffer {
+    ($ty:ty, $len:expr, $err:literal) => {
+        impl RegisterBuffer for $ty {
+            const BUFFER_LEN: usize = $len;
+
+            fn encode<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+                let mut cursor = Cursor::new(Vec::with_capacity(Self::BUFFER_LEN));
+                self.write_le(&mut cursor)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+                debug_assert_eq!(cursor.get_ref().len(), Self::BUFFER_LEN);
+                writer.write_all(cursor.get_ref())
+            }
+
+            fn decode(buf: &[u8]) -> Result<Self, &'static str> {
+                if buf.len() < Self::BUFFER_LEN {
+                    return Err($err);
+                }
+                let mut cursor = Cursor::new(&buf[..Self::BUFFER_LEN]);
+                Self::read_le(&mut cursor).map_err(|_| $err)
+            }
+        }
+    };
+}
+
+impl_register_buffer!(RemoteCpuRegistersV1, 28, "Buffer too small for RemoteCpuRegistersV1");
+impl_register_buffer!(RemoteCpuRegistersV2, 102, "Buffer too small for RemoteCpuRegistersV2");
+impl_register_buffer!(RemoteCpuRegistersV3A, 204, "Buffer too small for RemoteCpuRegistersV3A");
+impl_register_buffer!(RemoteCpuRegistersV3B, 208, "Buffer too small for RemoteCpuRegistersV3B");
@@ -26,6 +26,8 @@ use crate::{
     RemoteCpuRegisters,
     RemoteCpuRegistersV1,
     RemoteCpuRegistersV2,
+    RemoteCpuRegistersV3A,
+    RemoteCpuRegistersV3B,
     ServerCpuType,
 };
 use std::fmt::Display;
@@ -42,33 +44,165 @@ macro_rules! flag_chr {
     };
 }
 
-pub struct RegisterPrinter<'a> {
-    pub regs: &'a RemoteCpuRegisters,
-    pub final_regs: Option<&'a RemoteCpuRegisters>,
+/// Suppress the X0-X9 internal registers when printing V2 registers.
+pub const PRINT_NO_XREGS: u32 = 0x0001;
+/// Suppress the task register when printing V2 registers.
+pub const PRINT_NO_TR: u32 = 0x0002;
+/// Suppress the LDT register when printing V2 registers.
+pub const PRINT_NO_LDT: u32 = 0x0004;
+
+/// A register set that can be printed as text, either on its own or as a delta against a final
+/// register state. Changed registers are marked with a `*` in delta output.
+pub trait RegisterPrinter {
+    fn fmt_regs(&self, f: &mut std::fmt::Formatter<'_>, cpu_type: ServerCpuType, options: u32) -> std::fmt::Result;
+
+    fn fmt_regs_delta(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        final_regs: &Self,
+        cpu_type: ServerCpuType,
+        options: u32,
+    ) -> std::fmt::Result;
+
+    /// Return a [RegisterDisplay] that prints this register set.
+    fn display(&self, cpu_type: ServerCpuType) -> RegisterDisplay<'_, Self>
+    where
+        Self: Sized,
+    {
+        RegisterDisplay {
+            regs: self,
+            final_regs: None,
+            cpu_type,
+            options: 0,
+        }
+    }
+
+    /// Return a [RegisterDisplay] that prints `final_regs`, marking registers that differ from this
+    /// register set.
+    fn display_delta<'a>(&'a self, final_regs: &'a Self, cpu_type: ServerCpuType) -> RegisterDisplay<'a, Self>
+    where
+        Self: Sized,
+    {
+        RegisterDisplay {
+            regs: self,
+            final_regs: Some(final_regs),
+            cpu_type,
+            options: 0,
+        }
+    }
+}
+
+/// Adapts a [RegisterPrinter] to [Display].
+pub struct RegisterDisplay<'a, R: RegisterPrinter = RemoteCpuRegisters> {
+    pub regs: &'a R,
+    pub final_regs: Option<&'a R>,
     pub cpu_type: ServerCpuType,
     pub options: u32,
 }
 
-impl RegisterPrinter<'_> {
-    pub const OPTION_NO_XREGS: u32 = 0x0001;
-    pub const NO_TR: u32 = 0x0002;
-    pub const NO_LDT: u32 = 0x0004;
+impl<R: RegisterPrinter> RegisterDisplay<'_, R> {
+    pub fn with_options(mut self, options: u32) -> Self {
+        self.options = options;
+        self
+    }
 }
 
-impl Display for RegisterPrinter<'_> {
+impl<R: RegisterPrinter> Display for RegisterDisplay<'_, R> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match (&self.regs, &self.final_regs) {
-            (RemoteCpuRegisters::V1(regs), None) => fmt_regs_v1(f, regs, self.cpu_type),
-            (RemoteCpuRegisters::V2(regs), None) => fmt_regs_v2(f, regs, self.cpu_type, self.options),
-            (RemoteCpuRegisters::V3(regs), None) => fmt_regs_v3(f, regs, self.cpu_type),
-            (RemoteCpuRegisters::V1(regs), Some(RemoteCpuRegisters::V1(final_regs))) => {
-                fmt_regs_v1_delta(f, regs, final_regs, self.cpu_type)
+        match self.final_regs {
+            Some(final_regs) => self.regs.fmt_regs_delta(f, final_regs, self.cpu_type, self.options),
+            None => self.regs.fmt_regs(f, self.cpu_type, self.options),
+        }
+    }
+}
+
+impl RegisterPrinter for RemoteCpuRegistersV1 {
+    fn fmt_regs(&self, f: &mut std::fmt::Formatter<'_>, cpu_type: ServerCpuType, _options: u32) -> std::fmt::Result {
+        fmt_regs_v1(f, self, cpu_type)
+    }
+
+    fn fmt_regs_delta(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        final_regs: &Self,
+        cpu_type: ServerCpuType,
+        _options: u32,
+    ) -> std::fmt::Result {
+        fmt_regs_v1_delta(f, self, final_regs, cpu_type)
+    }
+}
+
+impl RegisterPrinter for RemoteCpuRegistersV2 {
+    fn fmt_regs(&self, f: &mut std::fmt::Formatter<'_>, cpu_type: ServerCpuType, options: u32) -> std::fmt::Result {
+        fmt_regs_v2(f, self, cpu_type, options)
+    }
+
+    fn fmt_regs_delta(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        final_regs: &Self,
+        cpu_type: ServerCpuType,
+        options: u32,
+    ) -> std::fmt::Result {
+        fmt_regs_v2_delta(f, self, final_regs, cpu_type, options)
+    }
+}
+
+macro_rules! impl_register_printer_v3 {
+    ($($ty:ty),*) => {
+        $(
+            impl RegisterPrinter for $ty {
+                fn fmt_regs(
+                    &self,
+                    f: &mut std::fmt::Formatter<'_>,
+                    cpu_type: ServerCpuType,
+                    _options: u32,
+                ) -> std::fmt::Result {
+                    fmt_regs_v3(f, self, cpu_type)
+                }
+
+                fn fmt_regs_delta(
+                    &self,
+                    f: &mut std::fmt::Formatter<'_>,
+                    final_regs: &Self,
+                    _cpu_type: ServerCpuType,
+                    options: u32,
+                ) -> std::fmt::Result {
+                    fmt_regs_v3_delta(f, self, final_regs, options)
+                }
+            }
+        )*
+    };
+}
+
+impl_register_printer_v3!(RemoteCpuRegistersV3, RemoteCpuRegistersV3A, RemoteCpuRegistersV3B);
+
+impl RegisterPrinter for RemoteCpuRegisters {
+    fn fmt_regs(&self, f: &mut std::fmt::Formatter<'_>, cpu_type: ServerCpuType, options: u32) -> std::fmt::Result {
+        match self {
+            RemoteCpuRegisters::V1(regs) => regs.fmt_regs(f, cpu_type, options),
+            RemoteCpuRegisters::V2(regs) => regs.fmt_regs(f, cpu_type, options),
+            RemoteCpuRegisters::V3(regs) => regs.fmt_regs(f, cpu_type, options),
+        }
+    }
+
+    /// Mismatched register set versions print nothing.
+    fn fmt_regs_delta(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        final_regs: &Self,
+        cpu_type: ServerCpuType,
+        options: u32,
+    ) -> std::fmt::Result {
+        match (self, final_regs) {
+            (RemoteCpuRegisters::V1(regs), RemoteCpuRegisters::V1(final_regs)) => {
+                regs.fmt_regs_delta(f, final_regs, cpu_type, options)
             }
-            (RemoteCpuRegisters::V2(regs), Some(RemoteCpuRegisters::V2(final_regs))) => {
-                fmt_regs_v2_delta(f, regs, final_regs, self.cpu_type, self.options)
+            (RemoteCpuRegisters::V2(regs), RemoteCpuRegisters::V2(final_regs)) => {
+                regs.fmt_regs_delta(f, final_regs, cpu_type, options)
             }
-            (RemoteCpuRegisters::V3(regs), Some(RemoteCpuRegisters::V3(final_regs))) => {
-                fmt_regs_v3_delta(f, regs, final_regs, self.options)
+            (RemoteCpuRegisters::V3(regs), RemoteCpuRegisters::V3(final_regs)) => {
+                regs.fmt_regs_delta(f, final_regs, cpu_type, options)
             }
             _ => Ok(()),
         }
@@ -114,7 +248,7 @@ pub fn fmt_regs_v2(
     cpu_type: ServerCpuType,
     options: u32,
 ) -> std::fmt::Result {
-    if options & RegisterPrinter::OPTION_NO_XREGS == 0 {
+    if options & PRINT_NO_XREGS == 0 {
         write!(
             f,
             "X0: {:04X} X1: {:04X} X2: {:04X} X3: {:04X} X4: {:04X}\n\
@@ -127,20 +261,20 @@ pub fn fmt_regs_v2(
 
     write!(f, "MSW: {:04X}\n", regs.msw)?;
 
-    if options & RegisterPrinter::NO_TR == 0 {
+    if options & PRINT_NO_TR == 0 {
         write!(f, " TR: {:04X}", regs.tr)?;
     }
 
-    if options & RegisterPrinter::NO_LDT == 0 {
+    if options & PRINT_NO_LDT == 0 {
         write!(f, " LDT: {:04X}\n", regs.ldt)?;
     }
 
     fmt_regs_v1(f, &v1_regs, cpu_type)
 }
 
-pub fn fmt_regs_v3(
+pub fn fmt_regs_v3<R: Registers32>(
     fmt: &mut std::fmt::Formatter<'_>,
-    regs: &RemoteCpuRegistersV3,
+    regs: &R,
     cpu_type: ServerCpuType,
 ) -> std::fmt::Result {
     let reg_str = format!(
@@ -307,7 +441,7 @@ pub fn fmt_regs_v2_delta(
     cpu_type: ServerCpuType,
     options: u32,
 ) -> std::fmt::Result {
-    if options & RegisterPrinter::OPTION_NO_XREGS == 0 {
+    if options & PRINT_NO_XREGS == 0 {
         let x0_diff = initial.x0 != _final.x0;
         let x1_diff = initial.x1 != _final.x1;
         let x2_diff = initial.x2 != _final.x2;
@@ -400,10 +534,10 @@ pub fn fmt_regs_v2_delta(
     fmt_flags_v1(fmt, _final.flags, cpu_type)
 }
 
-pub fn fmt_regs_v3_delta(
+pub fn fmt_regs_v3_delta<R: Registers32>(
     fmt: &mut std::fmt::Formatter<'_>,
-    initial: &RemoteCpuRegistersV3,
-    regs: &RemoteCpuRegistersV3,
+    initial: &R,
+    regs: &R,
     _options: u32,
 ) -> std::fmt::Result {
     let cr0_diff = initial.cr0() != regs.cr0();
@@ -21,6 +21,11 @@
     DEALINGS IN THE SOFTWARE.
 */
 
+use crate::{RemoteCpuRegistersV1, RemoteCpuRegistersV2};
+
+#[cfg(feature = "use_moo")]
+use moo::prelude::{MooRegisters16Init, MooRegisters32Init};
+
 pub trait Registers16 {
     fn ax(&self) -> u16;
     fn bx(&self) -> u16;
@@ -67,6 +72,24 @@ pub trait Registers16 {
     fn set_ss(&mut self, value: u16);
     fn set_ip(&mut self, value: u16);
     fn set_flags(&mut self, value: u16);
+
+    /// Return the base address of the code segment. Register sets with segment descriptors
+    /// override this to return the descriptor base.
+    fn cs_base(&self) -> u32 {
+        (self.cs() as u32) << 4
+    }
+    /// Return the base address of the stack segment.
+    fn ss_base(&self) -> u32 {
+        (self.ss() as u32) << 4
+    }
+    /// Return the linear address of CS:IP.
+    fn code_address(&self) -> u32 {
+        self.cs_base().wrapping_add(self.ip() as u32)
+    }
+    /// Return the linear address of SS:SP.
+    fn stack_address(&self) -> u32 {
+        self.ss_base().wrapping_add(self.sp() as u32)
+    }
 }
 
 pub trait Registers32 {
@@ -141,4 +164,217 @@ pub trait Registers32 {
     fn set_eflags(&mut self, value: u32);
 
     fn normalize_descriptors(&mut self);
+
+    /// Return the base address of the code segment from its descriptor cache.
+    fn cs_base(&self) -> u32;
+    /// Return the base address of the stack segment from its descriptor cache.
+    fn ss_base(&self) -> u32;
+    /// Return the linear address of CS:EIP.
+    fn code_address(&self) -> u32 {
+        self.cs_base().wrapping_add(self.eip())
+    }
+    /// Return the linear address of SS:ESP.
+    fn stack_address(&self) -> u32 {
+        self.ss_base().wrapping_add(self.esp())
+    }
+}
+
+macro_rules! impl_registers16 {
+    ($ty:ty $(, $base:ident)?) => {
+        impl Registers16 for $ty {
+            fn ax(&self) -> u16 {
+                self.ax
+            }
+            fn bx(&self) -> u16 {
+                self.bx
+            }
+            fn cx(&self) -> u16 {
+                self.cx
+            }
+            fn dx(&self) -> u16 {
+                self.dx
+            }
+            fn sp(&self) -> u16 {
+                self.sp
+            }
+            fn bp(&self) -> u16 {
+                self.bp
+            }
+            fn si(&self) -> u16 {
+                self.si
+            }
+            fn di(&self) -> u16 {
+                self.di
+            }
+            fn cs(&self) -> u16 {
+                self.cs
+            }
+            fn ds(&self) -> u16 {
+                self.ds
+            }
+            fn es(&self) -> u16 {
+                self.es
+            }
+            fn ss(&self) -> u16 {
+                self.ss
+            }
+            fn ip(&self) -> u16 {
+                self.ip
+            }
+            fn flags(&self) -> u16 {
+                self.flags
+            }
+
+            fn ax_mut(&mut self) -> &mut u16 {
+                &mut self.ax
+            }
+            fn bx_mut(&mut self) -> &mut u16 {
+                &mut self.bx
+            }
+            fn cx_mut(&mut self) -> &mut u16 {
+                &mut self.cx
+            }
+            fn dx_mut(&mut self) -> &mut u16 {
+                &mut self.dx
+            }
+            fn sp_mut(&mut self) -> &mut u16 {
+                &mut self.sp
+            }
+            fn bp_mut(&mut self) -> &mut u16 {
+                &mut self.bp
+            }
+            fn si_mut(&mut self) -> &mut u16 {
+                &mut self.si
+            }
+            fn di_mut(&mut self) -> &mut u16 {
+                &mut self.di
+            }
+            fn cs_mut(&mut self) -> &mut u16 {
+                &mut self.cs
+            }
+            fn ds_mut(&mut self) -> &mut u16 {
+                &mut self.ds
+            }
+            fn es_mut(&mut self) -> &mut u16 {
+                &mut self.es
+            }
+            fn ss_mut(&mut self) -> &mut u16 {
+                &mut self.ss
+            }
+            fn ip_mut(&mut self) -> &mut u16 {
+                &mut self.ip
+            }
+            fn flags_mut(&mut self) -> &mut u16 {
+                &mut self.flags
+            }
+
+            fn set_ax(&mut self, value: u16) {
+                self.ax = value;
+            }
+            fn set_bx(&mut self, value: u16) {
+                self.bx = value;
+            }
+            fn set_cx(&mut self, value: u16) {
+                self.cx = value;
+            }
+            fn set_dx(&mut self, value: u16) {
+                self.dx = value;
+            }
+            fn set_sp(&mut self, value: u16) {
+                self.sp = value;
+            }
+            fn set_bp(&mut self, value: u16) {
+                self.bp = value;
+            }
+            fn set_si(&mut self, value: u16) {
+                self.si = value;
+            }
+            fn set_di(&mut self, value: u16) {
+                self.di = value;
+            }
+            fn set_cs(&mut self, value: u16) {
+                self.cs = value;
+            }
+            fn set_ds(&mut self, value: u16) {
+                self.ds = value;
+            }
+            fn set_es(&mut self, value: u16) {
+                self.es = value;
+            }
+            fn set_ss(&mut self, value: u16) {
+                self.ss = value;
+            }
+            fn set_ip(&mut self, value: u16) {
+                self.ip = value;
+            }
+            fn set_flags(&mut self, value: u16) {
+                self.flags = value;
+            }
+
+            $(
+                impl_registers16!(@$base);
+            )?
+        }
+    };
+    // Segment bases come from the descriptor cache rather than the selector.
+    (@descriptors) => {
+        fn cs_base(&self) -> u32 {
+            self.cs_desc.base_address()
+        }
+        fn ss_base(&self) -> u32 {
+            self.ss_desc.base_address()
+        }
+    };
+}
+
+impl_registers16!(RemoteCpuRegistersV1);
+impl_registers16!(RemoteCpuRegistersV2, descriptors);
+
+/// Build a [MooRegisters16Init] from any 16-bit register set.
+#[cfg(feature = "use_moo")]
+pub fn moo_registers16_init<R: Registers16 + ?Sized>(regs: &R) -> MooRegisters16Init {
+    MooRegisters16Init {
+        ax:    regs.ax(),
+        bx:    regs.bx(),
+        cx:    regs.cx(),
+        dx:    regs.dx(),
+        cs:    regs.cs(),
+        ss:    regs.ss(),
+        ds:    regs.ds(),
+        es:    regs.es(),
+        sp:    regs.sp(),
+        bp:    regs.bp(),
+        si:    regs.si(),
+        di:    regs.di(),
+        ip:    regs.ip(),
+        flags: regs.flags(),
+    }
+}
+
+/// Build a [MooRegisters32Init] from any 32-bit register set. CR3 is not part of [Registers32]
+/// as only the SMM register set provides it, so it is passed separately.
+#[cfg(feature = "use_moo")]
+pub fn moo_registers32_init<R: Registers32 + ?Sized>(regs: &R, cr3: u32) -> MooRegisters32Init {
+    MooRegisters32Init {
+        cr0: regs.cr0(),
+        cr3,
+        eax: regs.eax(),
+        ebx: regs.ebx(),
+        ecx: regs.ecx(),
+        edx: regs.edx(),
+        esp: regs.esp(),
+        ebp: regs.ebp(),
+        esi: regs.esi(),
+        edi: regs.edi(),
+        eip: regs.eip(),
+        dr6: regs.dr6(),
+        dr7: regs.dr7(),
+        eflags: regs.eflags(),
+        cs: regs.cs() as u32,
+        ds: regs.ds() as u32,
+        es: regs.es() as u32,
+        fs: regs.fs() as u32,
+        gs: regs.gs() as u32,
+        ss: regs.ss() as u32,
+    }
 }
@@ -21,8 +21,8 @@
     DEALINGS IN THE SOFTWARE.
 */
 
-use crate::registers::register_traits::Registers32;
-use binrw::BinWrite;
+use crate::registers::{register_buffer::RegisterBuffer, register_traits::Registers32};
+use rand_distr::Beta;
 use std::io::{Seek, Write};
 
 // #[cfg(feature = "use_moo")]
@@ -145,35 +145,40 @@ impl RemoteCpuRegisters {
         }
     }
 
-    pub fn write<WS: Write + Seek>(&self, writer: &mut WS) -> std::io::Result<()> {
-        let mut buf = vec![0u8; 204];
-
+    /// Randomize the registers with the shared register fuzzer, keeping the register set type.
+    /// Register sets without their own fuzzer are randomized as the next larger set and
+    /// converted back.
+    pub fn randomize(
+        &mut self,
+        opts: &RandomizeOpts,
+        rand: &mut rand::rngs::StdRng,
+        beta: &mut Beta<f64>,
+        inject_values: &[u32],
+    ) {
         match self {
             RemoteCpuRegisters::V1(regs) => {
-                regs.write_buf(&mut buf);
-                writer.write_all(&buf[0..28])
+                let mut regs_v2 = RemoteCpuRegistersV2::default();
+                regs_v2.randomize(opts, rand, beta, inject_values);
+                *regs = RemoteCpuRegistersV1::from(&regs_v2);
             }
-            // RemoteCpuRegisters::V2(regs) => {
-            //     regs.write_buf(&mut buf);
-            //     writer.write_all(&buf[0..102])
-            // }
-            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::A(regs)) => regs.write_le(writer).map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to write RemoteCpuRegistersV3A: {}", e),
-                )
-            }),
-            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::B(regs)) => regs.write_le(writer).map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to write RemoteCpuRegistersV3B: {}", e),
-                )
-            }),
-            _ => {
-                unimplemented!("Need V2 write_buf() implementation");
+            RemoteCpuRegisters::V2(regs) => regs.randomize(opts, rand, beta, inject_values),
+            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::A(regs)) => regs.randomize(opts, rand, beta, inject_values),
+            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::B(regs)) => {
+                let mut regs_a = RemoteCpuRegistersV3A::default();
+                regs_a.randomize(opts, rand, beta, inject_values);
+                *regs = RemoteCpuRegistersV3B::from(&regs_a);
             }
         }
     }
+
+    pub fn write<WS: Write + Seek>(&self, writer: &mut WS) -> std::io::Result<()> {
+        match self {
+            RemoteCpuRegisters::V1(regs) => regs.encode(writer),
+            RemoteCpuRegisters::V2(regs) => regs.encode(writer),
+            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::A(regs)) => regs.encode(writer),
+            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::B(regs)) => regs.encode(writer),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
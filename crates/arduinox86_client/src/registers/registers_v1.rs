@@ -20,11 +20,13 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
-use crate::RemoteCpuRegistersV2;
+use crate::{registers::register_buffer::RegisterBuffer, RemoteCpuRegistersV2};
 use binrw::binrw;
 
 #[cfg(feature = "use_moo")]
-use moo::{prelude::MooRegisters16Init, types::MooRegisters16};
+use crate::registers::register_traits::moo_registers16_init;
+#[cfg(feature = "use_moo")]
+use moo::types::MooRegisters16;
 
 #[binrw]
 #[brw(little)]
@@ -34,16 +36,16 @@ pub struct RemoteCpuRegistersV1 {
     pub bx:    u16,
     pub cx:    u16,
     pub dx:    u16,
+    pub ip:    u16,
+    pub cs:    u16,
+    pub flags: u16,
     pub ss:    u16,
+    pub sp:    u16,
     pub ds:    u16,
     pub es:    u16,
-    pub sp:    u16,
     pub bp:    u16,
     pub si:    u16,
     pub di:    u16,
-    pub cs:    u16,
-    pub ip:    u16,
-    pub flags: u16,
 }
 
 impl RemoteCpuRegistersV1 {
@@ -70,48 +72,8 @@ impl RemoteCpuRegistersV1 {
     }
 
     pub fn write_buf(&self, buf: &mut [u8]) {
-        // AX, BX, CX, DX, SS, SP, FLAGS, IP, CS, DS, ES, BP, SI, DI
-        buf[0] = (self.ax & 0xFF) as u8;
-        buf[1] = ((self.ax >> 8) & 0xFF) as u8;
-
-        buf[2] = (self.bx & 0xFF) as u8;
-        buf[3] = ((self.bx >> 8) & 0xFF) as u8;
-
-        buf[4] = (self.cx & 0xFF) as u8;
-        buf[5] = ((self.cx >> 8) & 0xFF) as u8;
-
-        buf[6] = (self.dx & 0xFF) as u8;
-        buf[7] = ((self.dx >> 8) & 0xFF) as u8;
-
-        buf[8] = (self.ip & 0xFF) as u8;
-        buf[9] = ((self.ip >> 8) & 0xFF) as u8;
-
-        buf[10] = (self.cs & 0xFF) as u8;
-        buf[11] = ((self.cs >> 8) & 0xFF) as u8;
-
-        buf[12] = (self.flags & 0xFF) as u8;
-        buf[13] = ((self.flags >> 8) & 0xFF) as u8;
-
-        buf[14] = (self.ss & 0xFF) as u8;
-        buf[15] = ((self.ss >> 8) & 0xFF) as u8;
-
-        buf[16] = (self.sp & 0xFF) as u8;
-        buf[17] = ((self.sp >> 8) & 0xFF) as u8;
-
-        buf[18] = (self.ds & 0xFF) as u8;
-        buf[19] = ((self.ds >> 8) & 0xFF) as u8;
-
-        buf[20] = (self.es & 0xFF) as u8;
-        buf[21] = ((self.es >> 8) & 0xFF) as u8;
-
-        buf[22] = (self.bp & 0xFF) as u8;
-        buf[23] = ((self.bp >> 8) & 0xFF) as u8;
-
-        buf[24] = (self.si & 0xFF) as u8;
-        buf[25] = ((self.si >> 8) & 0xFF) as u8;
-
-        buf[26] = (self.di & 0xFF) as u8;
-        buf[27] = ((self.di >> 8) & 0xFF) as u8;
+        // AX, BX, CX, DX, IP, CS, FLAGS, SS, SP, DS, ES, BP, SI, DI
+        buf[..Self::BUFFER_LEN].copy_from_slice(&self.to_bytes());
     }
 
     pub fn calculate_code_address(&self) -> u32 {
@@ -143,42 +105,12 @@ impl From<&RemoteCpuRegistersV2> for RemoteCpuRegistersV1 {
 
 impl From<&[u8; 28]> for RemoteCpuRegistersV1 {
     fn from(buf: &[u8; 28]) -> Self {
-        RemoteCpuRegistersV1 {
-            ax:    buf[0] as u16 | ((buf[1] as u16) << 8),
-            bx:    buf[2] as u16 | ((buf[3] as u16) << 8),
-            cx:    buf[4] as u16 | ((buf[5] as u16) << 8),
-            dx:    buf[6] as u16 | ((buf[7] as u16) << 8),
-            ip:    buf[8] as u16 | ((buf[9] as u16) << 8),
-            cs:    buf[10] as u16 | ((buf[11] as u16) << 8),
-            flags: buf[12] as u16 | ((buf[13] as u16) << 8),
-            ss:    buf[14] as u16 | ((buf[15] as u16) << 8),
-            sp:    buf[16] as u16 | ((buf[17] as u16) << 8),
-            ds:    buf[18] as u16 | ((buf[19] as u16) << 8),
-            es:    buf[20] as u16 | ((buf[21] as u16) << 8),
-            bp:    buf[22] as u16 | ((buf[23] as u16) << 8),
-            si:    buf[24] as u16 | ((buf[25] as u16) << 8),
-            di:    buf[26] as u16 | ((buf[27] as u16) << 8),
-        }
+        RemoteCpuRegistersV1::decode(buf).unwrap()
     }
 }
 impl From<&[u8]> for RemoteCpuRegistersV1 {
     fn from(buf: &[u8]) -> Self {
-        RemoteCpuRegistersV1 {
-            ax:    buf[0] as u16 | ((buf[1] as u16) << 8),
-            bx:    buf[2] as u16 | ((buf[3] as u16) << 8),
-            cx:    buf[4] as u16 | ((buf[5] as u16) << 8),
-            dx:    buf[6] as u16 | ((buf[7] as u16) << 8),
-            ip:    buf[8] as u16 | ((buf[9] as u16) << 8),
-            cs:    buf[10] as u16 | ((buf[11] as u16) << 8),
-            flags: buf[12] as u16 | ((buf[13] as u16) << 8),
-            ss:    buf[14] as u16 | ((buf[15] as u16) << 8),
-            sp:    buf[16] as u16 | ((buf[17] as u16) << 8),
-            ds:    buf[18] as u16 | ((buf[19] as u16) << 8),
-            es:    buf[20] as u16 | ((buf[21] as u16) << 8),
-            bp:    buf[22] as u16 | ((buf[23] as u16) << 8),
-            si:    buf[24] as u16 | ((buf[25] as u16) << 8),
-            di:    buf[26] as u16 | ((buf[27] as u16) << 8),
-        }
+        RemoteCpuRegistersV1::decode(buf).expect("Buffer too small for RemoteCpuRegistersV1")
     }
 }
 
@@ -192,22 +124,6 @@ impl From<RemoteCpuRegistersV1> for MooRegisters16 {
 #[cfg(feature = "use_moo")]
 impl From<&RemoteCpuRegistersV1> for MooRegisters16 {
     fn from(remote: &RemoteCpuRegistersV1) -> Self {
-        (&MooRegisters16Init {
-            ax:    remote.ax,
-            bx:    remote.bx,
-            cx:    remote.cx,
-            dx:    remote.dx,
-            cs:    remote.cs,
-            ss:    remote.ss,
-            ds:    remote.ds,
-            es:    remote.es,
-            sp:    remote.sp,
-            bp:    remote.bp,
-            si:    remote.si,
-            di:    remote.di,
-            ip:    remote.ip,
-            flags: remote.flags,
-        })
-            .into()
+        (&moo_registers16_init(remote)).into()
     }
 }
@@ -23,17 +23,19 @@
 
 use std::io::Write;
 
-#[cfg(feature = "use_iced")]
 use crate::registers_common::RandomizeOpts;
 
 use rand::Rng;
 use rand_distr::{Beta, Distribution};
 
-use binrw::{binrw, BinRead, BinReaderExt, BinWrite};
+use crate::{registers::register_buffer::RegisterBuffer, Segment};
+use binrw::{binrw, BinRead, BinWrite};
 use modular_bitfield::{bitfield, prelude::*};
 
 #[cfg(feature = "use_moo")]
-use moo::{prelude::MooRegisters16Init, types::MooRegisters16};
+use crate::registers::register_traits::moo_registers16_init;
+#[cfg(feature = "use_moo")]
+use moo::types::MooRegisters16;
 
 #[bitfield]
 #[derive(Default, Debug)]
@@ -71,11 +73,80 @@ impl Default for SegmentDescriptorV1 {
 }
 
 impl SegmentDescriptorV1 {
+    /// Access rights of a present, writable data segment.
+    pub const ACCESS_DATA: u8 = 0x93;
+    /// Access rights of a present, readable code segment.
+    pub const ACCESS_CODE: u8 = 0x9B;
+    /// Access rights of a present LDT.
+    pub const ACCESS_LDT: u8 = 0x82;
+    /// Access rights of a present, available 286 TSS.
+    pub const ACCESS_TSS: u8 = 0x81;
+    /// The highest base address the 286's 24-bit address bus can reach.
+    pub const MAX_BASE: u32 = 0xFF_FFFF;
+
+    /// Build a descriptor cache entry. Fails if `base` doesn't fit in 24 bits.
+    pub fn try_new(base: u32, limit: u16, access: u8) -> Result<Self, &'static str> {
+        let mut desc = SegmentDescriptorV1::default().with_limit(limit);
+        desc.set_base(base)?;
+        desc.set_access_rights(access);
+        Ok(desc)
+    }
+
     pub fn to_buffer<W: Write>(&self, buffer: &mut W) -> std::io::Result<()> {
         let bytes = self.clone().into_bytes();
         buffer.write_all(&bytes)?;
         Ok(())
     }
+
+    pub fn base(&self) -> u32 {
+        self.base_address()
+    }
+
+    /// Set the base address. Fails if `base` doesn't fit in 24 bits.
+    pub fn set_base(&mut self, base: u32) -> Result<(), &'static str> {
+        if base > Self::MAX_BASE {
+            return Err("Segment base does not fit in 24 bits");
+        }
+        self.set_base_address(base);
+        Ok(())
+    }
+
+    /// Return the access rights byte: P, DPL, S and type, as laid out in a descriptor table entry.
+    pub fn access_rights(&self) -> u8 {
+        (self.p() << 7) | (self.dpl() << 5) | (self.s() << 4) | self.d_type()
+    }
+
+    pub fn set_access_rights(&mut self, access: u8) {
+        self.set_d_type(access & 0x0F);
+        self.set_s((access >> 4) & 0x01);
+        self.set_dpl((access >> 5) & 0x03);
+        self.set_p(access >> 7);
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.p() != 0
+    }
+
+    /// Return true for a code segment: S set and type bit 3 set.
+    pub fn is_code(&self) -> bool {
+        self.s() != 0 && self.d_type() & 0x08 != 0
+    }
+
+    /// Return true for a data segment: S set and type bit 3 clear.
+    pub fn is_data(&self) -> bool {
+        self.s() != 0 && self.d_type() & 0x08 == 0
+    }
+
+    /// Return true if the segment can be read: any data segment, or a code segment with the
+    /// readable bit set.
+    pub fn is_readable(&self) -> bool {
+        self.is_data() || (self.is_code() && self.d_type() & 0x02 != 0)
+    }
+
+    /// Return true for a data segment with the writable bit set.
+    pub fn is_writable(&self) -> bool {
+        self.is_data() && self.d_type() & 0x02 != 0
+    }
 }
 
 /// [RemoteCpuRegistersV2] is the full set of registers for the Intel 80286.
@@ -135,70 +206,7 @@ impl From<[u8; 102]> for RemoteCpuRegistersV2 {
 }
 
 fn parse_v2(buf: &[u8]) -> Result<RemoteCpuRegistersV2, &'static str> {
-    if buf.len() < 102 {
-        return Err("Buffer too small for RemoteCpuRegistersV2");
-    }
-
-    let mut new_regs = RemoteCpuRegistersV2::default();
-    let mut cursor = std::io::Cursor::new(buf);
-
-    new_regs.x0 = cursor.read_le().unwrap(); // 800
-    new_regs.x1 = cursor.read_le().unwrap(); // 802
-    new_regs.x2 = cursor.read_le().unwrap(); // 804
-
-    new_regs.msw = cursor.read_le().unwrap(); // 806
-
-    new_regs.x3 = cursor.read_le().unwrap(); // 808
-    new_regs.x4 = cursor.read_le().unwrap(); // 80A
-    new_regs.x5 = cursor.read_le().unwrap(); // 80C
-    new_regs.x6 = cursor.read_le().unwrap(); // 80E
-    new_regs.x7 = cursor.read_le().unwrap(); // 810
-    new_regs.x8 = cursor.read_le().unwrap(); // 812
-    new_regs.x9 = cursor.read_le().unwrap(); // 814
-
-    new_regs.tr = cursor.read_le().unwrap(); // 816
-    new_regs.flags = cursor.read_le().unwrap(); // 818
-    new_regs.ip = cursor.read_le().unwrap(); // 81A
-    new_regs.ldt = cursor.read_le().unwrap(); // 81C
-
-    new_regs.ds = cursor.read_le().unwrap(); // 81E
-    new_regs.ss = cursor.read_le().unwrap(); // 820
-    new_regs.cs = cursor.read_le().unwrap(); // 822
-    new_regs.es = cursor.read_le().unwrap(); // 824
-
-    new_regs.di = cursor.read_le().unwrap(); // 826
-    new_regs.si = cursor.read_le().unwrap(); // 828
-    new_regs.bp = cursor.read_le().unwrap(); // 82A
-    new_regs.sp = cursor.read_le().unwrap(); // 82C
-
-    new_regs.bx = cursor.read_le().unwrap(); // 82E
-    new_regs.dx = cursor.read_le().unwrap(); // 830
-    new_regs.cx = cursor.read_le().unwrap(); // 832
-    new_regs.ax = cursor.read_le().unwrap(); // 834
-
-    let idx = cursor.position();
-    let desc_slice = &cursor.into_inner()[idx as usize..idx as usize + 48];
-
-    new_regs.es_desc = read_descriptor(desc_slice, 0);
-    new_regs.cs_desc = read_descriptor(desc_slice, 1);
-    new_regs.ss_desc = read_descriptor(desc_slice, 2);
-    new_regs.ds_desc = read_descriptor(desc_slice, 3);
-    new_regs.gdt_desc = read_descriptor(desc_slice, 4);
-    new_regs.ldt_desc = read_descriptor(desc_slice, 5);
-    new_regs.idt_desc = read_descriptor(desc_slice, 6);
-    new_regs.tss_desc = read_descriptor(desc_slice, 7);
-
-    Ok(new_regs)
-}
-
-fn read_descriptor(slice: &[u8], index: usize) -> SegmentDescriptorV1 {
-    // each descriptor is 6 bytes
-    let start = index * 6;
-    let end = start + 6;
-    let bytes: [u8; 6] = slice[start..end]
-        .try_into()
-        .expect("desc_slice must be at least 6*8=48 bytes");
-    SegmentDescriptorV1::from_bytes(bytes)
+    RemoteCpuRegistersV2::decode(buf)
 }
 
 impl Default for RemoteCpuRegistersV2 {
@@ -246,45 +254,10 @@ impl Default for RemoteCpuRegistersV2 {
 impl RemoteCpuRegistersV2 {
     pub const FLAGS_RESERVED_SET: u16 = 0x0002; // Reserved bit in flags register, always set to 1.
     pub const FLAGS_RESERVED_MASK: u16 = 0xFFD7; // Reserved bit in flags register, always cleared to 0.
+    pub const MSW_PE: u16 = 0x0001; // Protection enable bit of the MSW.
 
     pub fn to_buffer<W: Write>(&self, buffer: &mut W) {
-        buffer.write_all(&self.x0.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x1.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x2.to_le_bytes()).unwrap();
-        buffer.write_all(&self.msw.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x3.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x4.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x5.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x6.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x7.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x8.to_le_bytes()).unwrap();
-        buffer.write_all(&self.x9.to_le_bytes()).unwrap();
-        buffer.write_all(&self.tr.to_le_bytes()).unwrap();
-        buffer.write_all(&self.flags.to_le_bytes()).unwrap();
-        buffer.write_all(&self.ip.to_le_bytes()).unwrap();
-        buffer.write_all(&self.ldt.to_le_bytes()).unwrap();
-        buffer.write_all(&self.ds.to_le_bytes()).unwrap();
-        buffer.write_all(&self.ss.to_le_bytes()).unwrap();
-        buffer.write_all(&self.cs.to_le_bytes()).unwrap();
-        buffer.write_all(&self.es.to_le_bytes()).unwrap();
-        buffer.write_all(&self.di.to_le_bytes()).unwrap();
-        buffer.write_all(&self.si.to_le_bytes()).unwrap();
-        buffer.write_all(&self.bp.to_le_bytes()).unwrap();
-        buffer.write_all(&self.sp.to_le_bytes()).unwrap();
-        buffer.write_all(&self.bx.to_le_bytes()).unwrap();
-        buffer.write_all(&self.dx.to_le_bytes()).unwrap();
-        buffer.write_all(&self.cx.to_le_bytes()).unwrap();
-        buffer.write_all(&self.ax.to_le_bytes()).unwrap();
-
-        // Write segment descriptors
-        self.es_desc.to_buffer(buffer).expect("Failed to write es_desc");
-        self.cs_desc.to_buffer(buffer).expect("Failed to write cs_desc");
-        self.ss_desc.to_buffer(buffer).expect("Failed to write ss_desc");
-        self.ds_desc.to_buffer(buffer).expect("Failed to write ds_desc");
-        self.gdt_desc.to_buffer(buffer).expect("Failed to write gdt_desc");
-        self.ldt_desc.to_buffer(buffer).expect("Failed to write ldt_desc");
-        self.idt_desc.to_buffer(buffer).expect("Failed to write idt_desc");
-        self.tss_desc.to_buffer(buffer).expect("Failed to write tss_desc");
+        self.encode(buffer).expect("Failed to write RemoteCpuRegistersV2");
     }
 
     pub fn rewind_ip(&mut self, adjust: u16) {
@@ -345,7 +318,6 @@ impl RemoteCpuRegistersV2 {
         }
     }
 
-    #[cfg(feature = "use_iced")]
     #[rustfmt::skip]
     pub fn randomize(&mut self, opts: &RandomizeOpts, rand: &mut rand::rngs::StdRng, beta: &mut Beta<f64>, inject_values: &[u32]) {
         *self = RemoteCpuRegistersV2::default(); // Reset all registers to default values
@@ -451,6 +423,137 @@ impl RemoteCpuRegistersV2 {
         // Calculate the code address based on CS descriptor base and IP
         self.cs_desc.base_address() + (self.ip as u32)
     }
+
+    /// Return the descriptor cache entry of `segment`.
+    pub fn descriptor(&self, segment: Segment) -> &SegmentDescriptorV1 {
+        match segment {
+            Segment::ES => &self.es_desc,
+            Segment::SS => &self.ss_desc,
+            Segment::CS => &self.cs_desc,
+            Segment::DS => &self.ds_desc,
+        }
+    }
+
+    pub fn descriptor_mut(&mut self, segment: Segment) -> &mut SegmentDescriptorV1 {
+        match segment {
+            Segment::ES => &mut self.es_desc,
+            Segment::SS => &mut self.ss_desc,
+            Segment::CS => &mut self.cs_desc,
+            Segment::DS => &mut self.ds_desc,
+        }
+    }
+
+    /// Return true if the MSW has protected mode enabled.
+    pub fn protected_mode(&self) -> bool {
+        self.msw & Self::MSW_PE != 0
+    }
+
+    /// Load `segment` with `selector` and put `desc` straight into its descriptor cache, as LOADALL
+    /// does, so no descriptor table is needed. In protected mode the descriptor must be one the CPU
+    /// would load into that register: a code segment for CS, a writable data segment for SS, and a
+    /// readable segment for DS and ES unless the selector is null. CS and SS must be present.
+    pub fn load_segment(
+        &mut self,
+        segment: Segment,
+        selector: u16,
+        desc: SegmentDescriptorV1,
+    ) -> Result<(), &'static str> {
+        if self.protected_mode() {
+            let null = selector & 0xFFFC == 0;
+            match segment {
+                Segment::CS if !desc.is_code() => return Err("CS must be loaded with a code segment"),
+                Segment::SS if !desc.is_writable() => return Err("SS must be loaded with a writable data segment"),
+                Segment::CS | Segment::SS if !desc.is_present() => return Err("CS and SS must be present"),
+                Segment::DS | Segment::ES if !null && !desc.is_readable() => {
+                    return Err("DS and ES must be loaded with a readable segment")
+                }
+                _ => {}
+            }
+        }
+        match segment {
+            Segment::ES => self.es = selector,
+            Segment::SS => self.ss = selector,
+            Segment::CS => self.cs = selector,
+            Segment::DS => self.ds = selector,
+        }
+        *self.descriptor_mut(segment) = desc;
+        Ok(())
+    }
+
+    /// Set the base and limit of the GDT. Fails if `base` doesn't fit in 24 bits.
+    pub fn set_gdt(&mut self, base: u32, limit: u16) -> Result<(), &'static str> {
+        self.gdt_desc.set_base(base)?;
+        self.gdt_desc.set_limit(limit);
+        Ok(())
+    }
+
+    /// Set the base and limit of the IDT. Fails if `base` doesn't fit in 24 bits.
+    pub fn set_idt(&mut self, base: u32, limit: u16) -> Result<(), &'static str> {
+        self.idt_desc.set_base(base)?;
+        self.idt_desc.set_limit(limit);
+        Ok(())
+    }
+
+    /// Load the LDT register with `selector` and its descriptor. Fails unless `desc` is an LDT
+    /// descriptor.
+    pub fn load_ldt(&mut self, selector: u16, desc: SegmentDescriptorV1) -> Result<(), &'static str> {
+        if desc.s() != 0 || desc.d_type() != 0x02 {
+            return Err("LDTR must be loaded with an LDT descriptor");
+        }
+        self.ldt = selector;
+        self.ldt_desc = desc;
+        Ok(())
+    }
+
+    /// Load the task register with `selector` and its descriptor. Fails unless `desc` is a 286
+    /// TSS descriptor, available or busy.
+    pub fn load_task_register(&mut self, selector: u16, desc: SegmentDescriptorV1) -> Result<(), &'static str> {
+        if desc.s() != 0 || !matches!(desc.d_type(), 0x01 | 0x03) {
+            return Err("TR must be loaded with a 286 TSS descriptor");
+        }
+        self.tr = selector;
+        self.tss_desc = desc;
+        Ok(())
+    }
+
+    /// Switch the register set to protected mode without a descriptor table. PE is set in the MSW,
+    /// and the segment descriptor caches keep their bases and limits but become a code segment for
+    /// CS and writable data segments for the rest, at the privilege level of the RPL of CS. The
+    /// RPLs of SS, DS and ES are set to match.
+    pub fn enter_protected_mode(&mut self) {
+        self.msw |= Self::MSW_PE;
+        let cpl = self.cs & 0x03;
+
+        for segment in [Segment::ES, Segment::SS, Segment::CS, Segment::DS] {
+            let access = match segment {
+                Segment::CS => SegmentDescriptorV1::ACCESS_CODE,
+                _ => SegmentDescriptorV1::ACCESS_DATA,
+            };
+            let desc = self.descriptor_mut(segment);
+            desc.set_access_rights(access);
+            desc.set_dpl(cpl as u8);
+        }
+        self.ss = (self.ss & !0x03) | cpl;
+        self.ds = (self.ds & !0x03) | cpl;
+        self.es = (self.es & !0x03) | cpl;
+    }
+
+    /// Set up unreal mode. PE stays clear, but each segment descriptor cache is given its real mode
+    /// base plus `high_base`, wrapped to 24 bits, so the segments reach memory beyond 1MB. The 286's
+    /// limits are only 16 bits, so they stay at FFFF.
+    pub fn enter_unreal_mode(&mut self, high_base: u32) {
+        self.msw &= !Self::MSW_PE;
+        for (selector, desc) in [
+            (self.es, &mut self.es_desc),
+            (self.ss, &mut self.ss_desc),
+            (self.cs, &mut self.cs_desc),
+            (self.ds, &mut self.ds_desc),
+        ] {
+            let base = ((selector as u32) << 4).wrapping_add(high_base) & SegmentDescriptorV1::MAX_BASE;
+            desc.set_base_address(base);
+            desc.set_limit(0xFFFF);
+        }
+    }
 }
 
 #[cfg(feature = "use_moo")]
@@ -463,22 +566,6 @@ impl From<RemoteCpuRegistersV2> for MooRegisters16 {
 #[cfg(feature = "use_moo")]
 impl From<&RemoteCpuRegistersV2> for MooRegisters16 {
     fn from(remote: &RemoteCpuRegistersV2) -> Self {
-        (&MooRegisters16Init {
-            ax:    remote.ax,
-            bx:    remote.bx,
-            cx:    remote.cx,
-            dx:    remote.dx,
-            cs:    remote.cs,
-            ss:    remote.ss,
-            ds:    remote.ds,
-            es:    remote.es,
-            sp:    remote.sp,
-            bp:    remote.bp,
-            si:    remote.si,
-            di:    remote.di,
-            ip:    remote.ip,
-            flags: remote.flags,
-        })
-            .into()
+        (&moo_registers16_init(remote)).into()
     }
 }
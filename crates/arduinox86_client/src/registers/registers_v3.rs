@@ -26,10 +26,13 @@ use std::io::{Seek, Write};
 use crate::registers_common::RandomizeOpts;
 
 use crate::registers_common::SegmentSize;
-use binrw::{binrw, BinRead, BinReaderExt, BinResult, BinWrite};
+use crate::registers::register_buffer::RegisterBuffer;
+use binrw::{binrw, BinRead, BinResult, BinWrite};
 
 #[cfg(feature = "use_moo")]
-use moo::{prelude::MooRegisters32Init, types::MooRegisters32};
+use crate::registers::register_traits::moo_registers32_init;
+#[cfg(feature = "use_moo")]
+use moo::types::MooRegisters32;
 use rand::Rng;
 use rand_distr::{Beta, Distribution};
 
@@ -238,6 +241,13 @@ macro_rules! impl_registers32 {
                 self.cs_desc.address = (self.cs as u32) << 4;
                 self.es_desc.address = (self.es as u32) << 4;
             }
+
+            fn cs_base(&self) -> u32 {
+                self.cs_desc.base_address()
+            }
+            fn ss_base(&self) -> u32 {
+                self.ss_desc.base_address()
+            }
         }
     };
 }
@@ -364,6 +374,8 @@ impl Registers32 for RemoteCpuRegistersV3 {
             RemoteCpuRegistersV3::B(regs) => regs.normalize_descriptors(),
         }
     }
+    enum_get!(cs_base -> u32, cs_base);
+    enum_get!(ss_base -> u32, ss_base);
 }
 
 impl Default for RemoteCpuRegistersV3 {
@@ -484,6 +496,14 @@ impl SegmentDescriptorV2 {
         self.access = SegmentDescriptorV2AccessWord::from_bytes(access.to_le_bytes());
         self
     }
+    /// Set the default operand and address size (the D bit) of the segment.
+    pub fn with_segment_size(mut self, size: SegmentSize) -> Self {
+        self.access.set_size(match size {
+            SegmentSize::Sixteen => 0,
+            SegmentSize::ThirtyTwo => 1,
+        });
+        self
+    }
 
     pub fn from_slice(slice: &[u8], index: usize) -> Self {
         read_descriptor_v2(slice, index)
@@ -636,6 +656,7 @@ impl RemoteCpuRegistersV3A {
     pub const DEFAULT_CS: u16 = 0x1000;
 
     pub const FLAGS_RESERVED_CR0: u32 = 0x7FFE_FFF0; // Reserved bits in CR0
+    pub const CR0_PE: u32 = 0x0000_0001; // Protection enable bit of CR0
     pub const FLAGS_RESERVED_SET: u32 = 0xFFFC_0002; // Reserved bit 1 set
     pub const FLAGS_RESERVED_MASK: u32 = 0xFFFF_7FD7;
     pub const FLAG_CARRY: u32 = 0b0000_0000_0000_0001;
@@ -868,6 +889,25 @@ impl RemoteCpuRegistersV3A {
     pub fn calculate_code_address(&self) -> u32 {
         self.cs_desc.address + self.eip
     }
+
+    /// Set up unreal mode. PE stays clear, but each segment descriptor cache is given its real mode
+    /// base plus `high_base` and a 4GB limit, so the segments reach memory beyond 1MB and accept
+    /// 32-bit offsets.
+    pub fn enter_unreal_mode(&mut self, high_base: u32) {
+        self.cr0 &= !Self::CR0_PE;
+        for (selector, desc) in [
+            (self.es, &mut self.es_desc),
+            (self.cs, &mut self.cs_desc),
+            (self.ss, &mut self.ss_desc),
+            (self.ds, &mut self.ds_desc),
+            (self.fs, &mut self.fs_desc),
+            (self.gs, &mut self.gs_desc),
+        ] {
+            desc.address = ((selector as u32) << 4).wrapping_add(high_base);
+            desc.limit = 0xFFFF_FFFF;
+            desc.access.set_granularity(1);
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for RemoteCpuRegistersV3A {
@@ -883,61 +923,8 @@ impl From<[u8; 204]> for RemoteCpuRegistersV3A {
     }
 }
 
-#[rustfmt::skip]
 fn parse_v3a(buf: &[u8]) -> Result<RemoteCpuRegistersV3A, &'static str> {
-    if buf.len() < 204 {
-        return Err("Buffer too small for RemoteCpuRegistersV3");
-    }
-
-    let mut new_regs = RemoteCpuRegistersV3A::default();
-    let mut cursor = std::io::Cursor::new(buf);
-
-    new_regs.cr0 = cursor.read_le().unwrap();       // +00
-    new_regs.eflags = cursor.read_le().unwrap();    // +04
-    new_regs.eip = cursor.read_le().unwrap();       // +08
-    new_regs.edi = cursor.read_le().unwrap();       // +0C
-    new_regs.esi = cursor.read_le().unwrap();       // +10
-    new_regs.ebp = cursor.read_le().unwrap();       // +14
-    new_regs.esp = cursor.read_le().unwrap();       // +18
-    new_regs.ebx = cursor.read_le().unwrap();       // +1C
-    new_regs.edx = cursor.read_le().unwrap();       // +20
-    new_regs.ecx = cursor.read_le().unwrap();       // +24
-    new_regs.eax = cursor.read_le().unwrap();       // +28
-
-    new_regs.dr6 = cursor.read_le().unwrap();       // +2C
-    new_regs.dr7 = cursor.read_le().unwrap();       // +30
-
-    new_regs.tr         = cursor.read_le().unwrap();
-    new_regs.tr_pad     = cursor.read_le().unwrap();
-    new_regs.ldt        = cursor.read_le().unwrap();
-    new_regs.ldt_pad    = cursor.read_le().unwrap();
-    new_regs.gs         = cursor.read_le().unwrap();
-    new_regs.gs_pad     = cursor.read_le().unwrap();
-    new_regs.fs         = cursor.read_le().unwrap();
-    new_regs.fs_pad     = cursor.read_le().unwrap();
-    new_regs.ds         = cursor.read_le().unwrap();
-    new_regs.ds_pad     = cursor.read_le().unwrap();
-    new_regs.ss         = cursor.read_le().unwrap();
-    new_regs.ss_pad     = cursor.read_le().unwrap();
-    new_regs.cs         = cursor.read_le().unwrap();
-    new_regs.cs_pad     = cursor.read_le().unwrap();
-    new_regs.es         = cursor.read_le().unwrap();
-    new_regs.es_pad     = cursor.read_le().unwrap();
-
-    let idx = cursor.position();
-    let desc_slice = &cursor.into_inner()[idx as usize..idx as usize + 120];
-
-    new_regs.tss_desc = read_descriptor_v2(desc_slice, 0);
-    new_regs.idt_desc = read_descriptor_v2(desc_slice, 1);
-    new_regs.gdt_desc = read_descriptor_v2(desc_slice, 2);
-    new_regs.ldt_desc = read_descriptor_v2(desc_slice, 3);
-    new_regs.gs_desc = read_descriptor_v2(desc_slice, 4);
-    new_regs.fs_desc = read_descriptor_v2(desc_slice, 5);
-    new_regs.ds_desc = read_descriptor_v2(desc_slice, 6);
-    new_regs.ss_desc = read_descriptor_v2(desc_slice, 7);
-    new_regs.cs_desc = read_descriptor_v2(desc_slice, 8);
-    new_regs.es_desc = read_descriptor_v2(desc_slice, 9);
-    Ok(new_regs)
+    RemoteCpuRegistersV3A::decode(buf)
 }
 
 /// [RemoteCpuRegistersV3] is the LOADALL structure for the Intel 386.
@@ -1129,62 +1116,8 @@ impl From<[u8; 208]> for RemoteCpuRegistersV3B {
     }
 }
 
-#[rustfmt::skip]
 fn parse_v3b(buf: &[u8]) -> Result<RemoteCpuRegistersV3B, &'static str> {
-    if buf.len() < 208 {
-        return Err("Buffer too small for RemoteCpuRegistersV3B");
-    }
-
-    let mut new_regs = RemoteCpuRegistersV3B::default();
-    let mut cursor = std::io::Cursor::new(buf);
-
-    new_regs.cr0 = cursor.read_le().unwrap();
-    new_regs.cr3 = cursor.read_le().unwrap();
-    new_regs.eflags = cursor.read_le().unwrap();
-    new_regs.eip = cursor.read_le().unwrap();
-    new_regs.edi = cursor.read_le().unwrap();
-    new_regs.esi = cursor.read_le().unwrap();
-    new_regs.ebp = cursor.read_le().unwrap();
-    new_regs.esp = cursor.read_le().unwrap();
-    new_regs.ebx = cursor.read_le().unwrap();
-    new_regs.edx = cursor.read_le().unwrap();
-    new_regs.ecx = cursor.read_le().unwrap();
-    new_regs.eax = cursor.read_le().unwrap();
-
-    new_regs.dr6 = cursor.read_le().unwrap();
-    new_regs.dr7 = cursor.read_le().unwrap();
-
-    new_regs.tr         = cursor.read_le().unwrap();
-    new_regs.tr_pad     = cursor.read_le().unwrap();
-    new_regs.ldt        = cursor.read_le().unwrap();
-    new_regs.ldt_pad    = cursor.read_le().unwrap();
-    new_regs.gs         = cursor.read_le().unwrap();
-    new_regs.gs_pad     = cursor.read_le().unwrap();
-    new_regs.fs         = cursor.read_le().unwrap();
-    new_regs.fs_pad     = cursor.read_le().unwrap();
-    new_regs.ds         = cursor.read_le().unwrap();
-    new_regs.ds_pad     = cursor.read_le().unwrap();
-    new_regs.ss         = cursor.read_le().unwrap();
-    new_regs.ss_pad     = cursor.read_le().unwrap();
-    new_regs.cs         = cursor.read_le().unwrap();
-    new_regs.cs_pad     = cursor.read_le().unwrap();
-    new_regs.es         = cursor.read_le().unwrap();
-    new_regs.es_pad     = cursor.read_le().unwrap();
-
-    let idx = cursor.position();
-    let desc_slice = &cursor.into_inner()[idx as usize..idx as usize + 120];
-
-    new_regs.tss_desc = read_descriptor_v2(desc_slice, 0);
-    new_regs.idt_desc = read_descriptor_v2(desc_slice, 1);
-    new_regs.gdt_desc = read_descriptor_v2(desc_slice, 2);
-    new_regs.ldt_desc = read_descriptor_v2(desc_slice, 3);
-    new_regs.gs_desc = read_descriptor_v2(desc_slice, 4);
-    new_regs.fs_desc = read_descriptor_v2(desc_slice, 5);
-    new_regs.ds_desc = read_descriptor_v2(desc_slice, 6);
-    new_regs.ss_desc = read_descriptor_v2(desc_slice, 7);
-    new_regs.cs_desc = read_descriptor_v2(desc_slice, 8);
-    new_regs.es_desc = read_descriptor_v2(desc_slice, 9);
-    Ok(new_regs)
+    RemoteCpuRegistersV3B::decode(buf)
 }
 
 impl_registers32!(RemoteCpuRegistersV3A);
@@ -1200,29 +1133,7 @@ impl From<RemoteCpuRegistersV3A> for MooRegisters32 {
 #[cfg(feature = "use_moo")]
 impl From<&RemoteCpuRegistersV3A> for MooRegisters32 {
     fn from(regs: &RemoteCpuRegistersV3A) -> MooRegisters32 {
-        (&(MooRegisters32Init {
-            cr0: regs.cr0,
-            cr3: 0,
-            eax: regs.eax,
-            ebx: regs.ebx,
-            ecx: regs.ecx,
-            edx: regs.edx,
-            esp: regs.esp,
-            ebp: regs.ebp,
-            esi: regs.esi,
-            edi: regs.edi,
-            eip: regs.eip,
-            dr6: regs.dr6,
-            dr7: regs.dr7,
-            eflags: regs.eflags,
-            cs: regs.cs as u32,
-            ds: regs.ds as u32,
-            es: regs.es as u32,
-            fs: regs.fs as u32,
-            gs: regs.gs as u32,
-            ss: regs.ss as u32,
-        }))
-            .into()
+        (&moo_registers32_init(regs, 0)).into()
     }
 }
 
@@ -1236,28 +1147,6 @@ impl From<RemoteCpuRegistersV3B> for MooRegisters32 {
 #[cfg(feature = "use_moo")]
 impl From<&RemoteCpuRegistersV3B> for MooRegisters32 {
     fn from(regs: &RemoteCpuRegistersV3B) -> MooRegisters32 {
-        (&(MooRegisters32Init {
-            cr0: regs.cr0,
-            cr3: regs.cr3,
-            eax: regs.eax,
-            ebx: regs.ebx,
-            ecx: regs.ecx,
-            edx: regs.edx,
-            esp: regs.esp,
-            ebp: regs.ebp,
-            esi: regs.esi,
-            edi: regs.edi,
-            eip: regs.eip,
-            dr6: regs.dr6,
-            dr7: regs.dr7,
-            eflags: regs.eflags,
-            cs: regs.cs as u32,
-            ds: regs.ds as u32,
-            es: regs.es as u32,
-            fs: regs.fs as u32,
-            gs: regs.gs as u32,
-            ss: regs.ss as u32,
-        }))
-            .into()
+        (&moo_registers32_init(regs, regs.cr3)).into()
     }
 }
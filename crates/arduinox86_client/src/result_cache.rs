@@ -0,0 +1,216 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! A host-side cache of run results, for workflows that run the same program over and over.
+//!
+//! Results are content-addressed by a [RunKey]: a hash of everything that determines the outcome
+//! of a run, such as the program bytes and where they are mounted, the initial registers and the
+//! memory strategy. A tool that finds a result for an unchanged key can skip running it on the
+//! hardware. The cache itself only stores values; persisting it is left to the tool, which can
+//! save [RunKey]s in their hex form.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use crate::MemoryStrategy;
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// The content address of a run. Build one with [RunKey::builder].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RunKey(u64);
+
+impl RunKey {
+    pub fn builder() -> RunKeyBuilder {
+        RunKeyBuilder::default()
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for RunKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for RunKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s.trim(), 16)
+            .map(RunKey)
+            .map_err(|e| format!("Bad run key '{}': {}", s, e))
+    }
+}
+
+/// Hashes the inputs of a run into a [RunKey].
+///
+/// The hash is 64-bit FNV-1a, so keys are stable across builds and platforms and can be saved
+/// between sessions. Each input is tagged and length-prefixed, so the same bytes given as a
+/// different kind of input, or split differently, produce a different key.
+#[derive(Copy, Clone, Debug)]
+pub struct RunKeyBuilder {
+    hash: u64,
+}
+
+impl Default for RunKeyBuilder {
+    fn default() -> Self {
+        Self { hash: FNV_OFFSET_BASIS }
+    }
+}
+
+impl RunKeyBuilder {
+    /// Add program bytes mounted at `address`. Call once per mounted segment.
+    pub fn program(self, address: u32, bytes: &[u8]) -> Self {
+        self.section(b'P', &address.to_le_bytes()).bytes(bytes)
+    }
+
+    /// Add the encoded initial register set.
+    pub fn registers(self, bytes: &[u8]) -> Self {
+        self.section(b'R', bytes)
+    }
+
+    /// Add initial RAM contents beyond the program, as address and value pairs.
+    pub fn ram(self, entries: &[(u32, u8)]) -> Self {
+        entries
+            .iter()
+            .fold(self.section(b'M', &[]), |builder, (address, value)| {
+                builder.bytes(&address.to_le_bytes()).bytes(&[*value])
+            })
+    }
+
+    /// Add the strategy used to fill memory the run reads but doesn't load.
+    pub fn memory_strategy(self, strategy: MemoryStrategy) -> Self {
+        let strategy = match strategy {
+            MemoryStrategy::Random => 0,
+            MemoryStrategy::Zero => 1,
+            MemoryStrategy::Ones => 2,
+        };
+        self.section(b'S', &[strategy])
+    }
+
+    /// Add any other input that changes the result, such as the CPU type or the run options.
+    pub fn extra(self, bytes: &[u8]) -> Self {
+        self.section(b'X', bytes)
+    }
+
+    pub fn build(self) -> RunKey {
+        RunKey(self.hash)
+    }
+
+    fn section(self, tag: u8, bytes: &[u8]) -> Self {
+        self.bytes(&[tag])
+            .bytes(&(bytes.len() as u32).to_le_bytes())
+            .bytes(bytes)
+    }
+
+    fn bytes(mut self, bytes: &[u8]) -> Self {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+        self
+    }
+}
+
+/// An in-memory map of [RunKey]s to run results that counts its hits and misses.
+#[derive(Clone, Debug)]
+pub struct ResultCache<V> {
+    entries: HashMap<RunKey, V>,
+    hits:    usize,
+    misses:  usize,
+}
+
+impl<V> Default for ResultCache<V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            hits:    0,
+            misses:  0,
+        }
+    }
+}
+
+impl<V> ResultCache<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up the result for `key`, counting a hit or a miss.
+    pub fn get(&mut self, key: &RunKey) -> Option<&V> {
+        match self.entries.get(key) {
+            Some(value) => {
+                self.hits += 1;
+                Some(value)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: RunKey, value: V) {
+        self.entries.insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: &RunKey) -> Option<V> {
+        self.entries.remove(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&RunKey, &V)> {
+        self.entries.iter()
+    }
+}
+
+impl<V> FromIterator<(RunKey, V)> for ResultCache<V> {
+    fn from_iter<I: IntoIterator<Item = (RunKey, V)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,286 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+//! Reconstruct approximate machine state at any point in a cycle log.
+//!
+//! A [Timeline] replays the bus transfers in a cycle log to recover what can be known about the
+//! machine at a given cycle: the memory seen on the bus so far, the instruction queue (on CPUs
+//! that report queue status) and the registers at either end of the log. This is meant for
+//! scrubbing through an execution, not for emulation; memory that was never on the bus is unknown.
+
+use std::collections::BTreeMap;
+
+use crate::{get_queue_op, BusState, DataWidth, QueueOp, RemoteCpuRegisters, ServerCpuType, ServerCycleState};
+
+/// Snapshot interval, in cycles. Seeking replays at most this many cycles.
+const CHECKPOINT_INTERVAL: usize = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransferKind {
+    CodeRead,
+    MemRead,
+    MemWrite,
+    IoRead,
+    IoWrite,
+}
+
+/// A completed bus transfer.
+#[derive(Copy, Clone, Debug)]
+pub struct BusTransfer {
+    /// The cycle the transfer completed on.
+    pub cycle:   usize,
+    pub kind:    TransferKind,
+    pub address: u32,
+    pub data:    u16,
+    pub width:   DataWidth,
+}
+
+impl BusTransfer {
+    /// Return the bytes transferred, with their addresses.
+    pub fn bytes(&self) -> Vec<(u32, u8)> {
+        match self.width {
+            DataWidth::Invalid => Vec::new(),
+            DataWidth::EightLow => vec![(self.address, self.data as u8)],
+            DataWidth::EightHigh => vec![(self.address, (self.data >> 8) as u8)],
+            DataWidth::Sixteen => vec![
+                (self.address, self.data as u8),
+                (self.address.wrapping_add(1), (self.data >> 8) as u8),
+            ],
+        }
+    }
+}
+
+/// Machine state reconstructed at a cycle.
+#[derive(Clone, Debug)]
+pub struct TimelineState {
+    /// The cycle this state was reconstructed at, after the cycle has executed.
+    pub cycle: usize,
+    pub address_latch: u32,
+    /// Every memory byte seen on the bus so far. Reads reveal memory and writes change it.
+    pub memory: BTreeMap<u32, u8>,
+    /// The instruction queue, if the CPU reports queue status.
+    pub queue: Option<Vec<u8>>,
+    /// Registers are only known at the first and last cycles.
+    pub registers: Option<RemoteCpuRegisters>,
+    /// The number of bus transfers completed so far.
+    pub transfer_ct: usize,
+}
+
+/// A cycle log with the bus transfers decoded, for seeking to any cycle.
+pub struct Timeline {
+    cpu_type: ServerCpuType,
+    cycles: Vec<ServerCycleState>,
+    transfers: Vec<BusTransfer>,
+    initial_regs: Option<RemoteCpuRegisters>,
+    final_regs: Option<RemoteCpuRegisters>,
+    checkpoints: Vec<TimelineState>,
+}
+
+impl Timeline {
+    pub fn new(cpu_type: ServerCpuType, cycles: Vec<ServerCycleState>) -> Self {
+        let transfers = decode_transfers(cpu_type, &cycles);
+        let mut timeline = Timeline {
+            cpu_type,
+            cycles,
+            transfers,
+            initial_regs: None,
+            final_regs: None,
+            checkpoints: Vec::new(),
+        };
+        timeline.build_checkpoints();
+        timeline
+    }
+
+    /// Set the registers loaded before the first cycle and stored after the last one.
+    pub fn with_registers(mut self, initial: Option<RemoteCpuRegisters>, final_: Option<RemoteCpuRegisters>) -> Self {
+        self.initial_regs = initial;
+        self.final_regs = final_;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.cycles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cycles.is_empty()
+    }
+
+    pub fn cycles(&self) -> &[ServerCycleState] {
+        &self.cycles
+    }
+
+    pub fn transfers(&self) -> &[BusTransfer] {
+        &self.transfers
+    }
+
+    /// Return the last transfer completed at or before `cycle`.
+    pub fn last_transfer(&self, cycle: usize) -> Option<&BusTransfer> {
+        let idx = self.transfers.partition_point(|t| t.cycle <= cycle);
+        idx.checked_sub(1).map(|idx| &self.transfers[idx])
+    }
+
+    /// Reconstruct the machine state after `cycle` has executed. Seeking past the end of the log
+    /// returns the state at the last cycle.
+    pub fn seek(&self, cycle: usize) -> TimelineState {
+        let cycle = cycle.min(self.cycles.len().saturating_sub(1));
+        let mut state = self.checkpoints[cycle / CHECKPOINT_INTERVAL].clone();
+        while state.cycle < cycle {
+            self.step(&mut state);
+        }
+        state.registers = self.registers_at(cycle);
+        state
+    }
+
+    fn registers_at(&self, cycle: usize) -> Option<RemoteCpuRegisters> {
+        if cycle + 1 == self.cycles.len() && self.final_regs.is_some() {
+            self.final_regs.clone()
+        }
+        else if cycle == 0 {
+            self.initial_regs.clone()
+        }
+        else {
+            None
+        }
+    }
+
+    /// The state before any cycle has executed, advanced onto cycle 0.
+    fn first_state(&self) -> TimelineState {
+        let mut state = TimelineState {
+            cycle: 0,
+            address_latch: 0,
+            memory: BTreeMap::new(),
+            queue: self.cpu_type.has_segment_status().then(Vec::new),
+            registers: None,
+            transfer_ct: 0,
+        };
+        self.apply(&mut state);
+        state
+    }
+
+    fn build_checkpoints(&mut self) {
+        self.checkpoints.clear();
+        let mut state = self.first_state();
+        self.checkpoints.push(state.clone());
+        while state.cycle + 1 < self.cycles.len() {
+            self.step(&mut state);
+            if state.cycle % CHECKPOINT_INTERVAL == 0 {
+                self.checkpoints.push(state.clone());
+            }
+        }
+    }
+
+    fn step(&self, state: &mut TimelineState) {
+        state.cycle += 1;
+        self.apply(state);
+    }
+
+    /// Apply the effects of cycle `state.cycle`.
+    fn apply(&self, state: &mut TimelineState) {
+        let Some(cycle) = self.cycles.get(state.cycle)
+        else {
+            return;
+        };
+        if cycle.ale() {
+            state.address_latch = cycle.address_bus;
+        }
+
+        while let Some(transfer) = self.transfers.get(state.transfer_ct) {
+            if transfer.cycle > state.cycle {
+                break;
+            }
+            match transfer.kind {
+                TransferKind::CodeRead | TransferKind::MemRead | TransferKind::MemWrite => {
+                    state.memory.extend(transfer.bytes());
+                }
+                TransferKind::IoRead | TransferKind::IoWrite => {}
+            }
+            if let (TransferKind::CodeRead, Some(queue)) = (transfer.kind, &mut state.queue) {
+                queue.extend(transfer.bytes().iter().map(|(_, byte)| byte));
+            }
+            state.transfer_ct += 1;
+        }
+
+        if let Some(queue) = &mut state.queue {
+            match get_queue_op!(cycle.cpu_status_bits) {
+                QueueOp::First | QueueOp::Subsequent => {
+                    if !queue.is_empty() {
+                        queue.remove(0);
+                    }
+                }
+                QueueOp::Flush => queue.clear(),
+                QueueOp::Idle => {}
+            }
+        }
+    }
+}
+
+/// Decode the completed bus transfers in a cycle log. A transfer completes on the last cycle of a
+/// run of transferring cycles, which is when the data bus holds the final value.
+pub fn decode_transfers(cpu_type: ServerCpuType, cycles: &[ServerCycleState]) -> Vec<BusTransfer> {
+    let mut transfers = Vec::new();
+    let mut address_latch = 0;
+    let mut latched_status = BusState::PASV;
+    let mut pending: Option<BusTransfer> = None;
+
+    for (i, cycle) in cycles.iter().enumerate() {
+        if cycle.ale() {
+            address_latch = cycle.address_bus;
+            latched_status = cpu_type.decode_status(cycle.cpu_status_bits);
+        }
+
+        if !cycle.is_transferring(cpu_type) {
+            transfers.extend(pending.take());
+            continue;
+        }
+
+        let kind = if cycle.is_reading_mem() {
+            match latched_status {
+                BusState::CODE => TransferKind::CodeRead,
+                _ => TransferKind::MemRead,
+            }
+        }
+        else if cycle.is_writing_mem() {
+            TransferKind::MemWrite
+        }
+        else if cycle.is_reading_io() {
+            TransferKind::IoRead
+        }
+        else {
+            TransferKind::IoWrite
+        };
+
+        // A new address latched mid-transfer (e.g. back-to-back 386 cycles) starts a new transfer.
+        if pending.is_some_and(|p| p.address != address_latch || p.kind != kind) {
+            transfers.extend(pending.take());
+        }
+        pending = Some(BusTransfer {
+            cycle: i,
+            kind,
+            address: address_latch,
+            data: cycle.data_bus,
+            width: cycle.data_width(cpu_type, address_latch),
+        });
+    }
+    transfers.extend(pending);
+    transfers
+}
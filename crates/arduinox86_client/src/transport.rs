@@ -0,0 +1,382 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Transports that carry the server protocol.
+//!
+//! A [CpuClient](crate::CpuClient) talks to its server through a [Transport]. Besides a local
+//! serial port, a server can be reached over TCP, such as a board bridged to the network with
+//! ser2net or a software mock server, or over a Unix domain socket. A [MockTransport] answers
+//! commands in-process, for tests that need to script what the server replies.
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    str::FromStr,
+    time::Duration,
+};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::DiscoveryOptions;
+
+/// A byte stream to an `ArduinoX86` server.
+pub trait Transport: Read + Write + Send {
+    /// Discard anything received but not yet read.
+    fn clear_input(&mut self) -> io::Result<()>;
+    /// Discard anything written but not yet sent, if the transport buffers it.
+    fn clear_output(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    /// Name of the transport for log messages.
+    fn name(&self) -> &str;
+}
+
+/// Selects a [Transport] by URI: `serial:COM3`, `tcp://192.168.1.50:5555` or
+/// `unix:/tmp/ardx86.sock`. A string without a scheme is taken as a serial port name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransportUri {
+    Serial(String),
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for TransportUri {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (uri, rest) = if let Some(addr) = s.strip_prefix("tcp://") {
+            (TransportUri::Tcp(addr.to_string()), addr)
+        }
+        else if let Some(path) = s.strip_prefix("unix:") {
+            // Accept both unix:/path and unix:///path.
+            let path = path.strip_prefix("//").unwrap_or(path);
+            (unix_uri(path)?, path)
+        }
+        else if let Some(name) = s.strip_prefix("serial:") {
+            (TransportUri::Serial(name.to_string()), name)
+        }
+        else {
+            (TransportUri::Serial(s.to_string()), s)
+        };
+
+        if rest.is_empty() {
+            return Err(format!("Transport URI has no address: {s}"));
+        }
+        Ok(uri)
+    }
+}
+
+#[cfg(unix)]
+fn unix_uri(path: &str) -> Result<TransportUri, String> {
+    Ok(TransportUri::Unix(PathBuf::from(path)))
+}
+
+#[cfg(not(unix))]
+fn unix_uri(_path: &str) -> Result<TransportUri, String> {
+    Err("Unix sockets are not supported on this platform".to_string())
+}
+
+impl Display for TransportUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportUri::Serial(name) => write!(f, "serial:{name}"),
+            TransportUri::Tcp(addr) => write!(f, "tcp://{addr}"),
+            #[cfg(unix)]
+            TransportUri::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Read timeout for a transport opened with the given options.
+fn read_timeout(options: &DiscoveryOptions) -> Duration {
+    Duration::from_millis(options.timeout + options.timeout_margin)
+}
+
+/// Read and discard whatever a socket has buffered, without blocking.
+fn drain_socket<S: Read>(socket: &mut S, set_nonblocking: impl Fn(&S, bool) -> io::Result<()>) -> io::Result<()> {
+    let mut buf = [0u8; 256];
+    set_nonblocking(socket, true)?;
+    let result = loop {
+        match socket.read(&mut buf) {
+            // A closed connection has nothing left to drain; the next read reports it.
+            Ok(0) => break Ok(()),
+            Ok(_) => continue,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e),
+        }
+    };
+    set_nonblocking(socket, false)?;
+    result
+}
+
+/// A [Transport] over a local serial port.
+pub struct SerialTransport {
+    port: Box<dyn SerialPort>,
+    name: String,
+}
+
+impl SerialTransport {
+    /// Wrap a serial port that is already open.
+    pub fn new(port: Box<dyn SerialPort>) -> Self {
+        let name = port.name().unwrap_or_else(|| "serial".to_string());
+        Self { port, name }
+    }
+
+    /// Open the named serial port with the settings in `options`.
+    pub fn open(port_name: &str, options: &DiscoveryOptions) -> Result<Self, serialport::Error> {
+        let flow_control = if options.software_flow_control {
+            serialport::FlowControl::Software
+        }
+        else {
+            serialport::FlowControl::None
+        };
+        let port = serialport::new(port_name, options.baud_rate)
+            .dtr_on_open(true)
+            .timeout(read_timeout(options))
+            .stop_bits(serialport::StopBits::One)
+            .data_bits(serialport::DataBits::Eight)
+            .parity(serialport::Parity::None)
+            .flow_control(flow_control)
+            .open()?;
+
+        Ok(Self {
+            port,
+            name: port_name.to_string(),
+        })
+    }
+}
+
+impl Read for SerialTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for SerialTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl Transport for SerialTransport {
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.port.clear(ClearBuffer::Input)?;
+        // Some drivers keep bytes that arrived while clearing.
+        let mut flush_buf = [0u8; 100];
+        if self.port.bytes_to_read()? > 0 {
+            _ = self.port.read(&mut flush_buf)?;
+        }
+        Ok(())
+    }
+
+    fn clear_output(&mut self) -> io::Result<()> {
+        Ok(self.port.clear(ClearBuffer::Output)?)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A [Transport] over a TCP connection, for a board behind a serial-to-network bridge or a
+/// software server.
+pub struct TcpTransport {
+    stream: TcpStream,
+    name:   String,
+}
+
+impl TcpTransport {
+    /// Connect to `addr`, given as `host:port`.
+    pub fn connect(addr: &str, options: &DiscoveryOptions) -> io::Result<Self> {
+        let timeout = read_timeout(options);
+        let mut last_error = io::Error::new(ErrorKind::NotFound, format!("No addresses found for {addr}"));
+        for socket_addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&socket_addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    // Commands are single bytes; don't let Nagle hold them back.
+                    stream.set_nodelay(true)?;
+                    return Ok(Self {
+                        stream,
+                        name: format!("tcp://{addr}"),
+                    });
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl Read for TcpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Transport for TcpTransport {
+    fn clear_input(&mut self) -> io::Result<()> {
+        drain_socket(&mut self.stream, TcpStream::set_nonblocking)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A [Transport] over a Unix domain socket.
+#[cfg(unix)]
+pub struct UnixTransport {
+    stream: UnixStream,
+    name:   String,
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    /// Connect to the socket at `path`.
+    pub fn connect(path: &std::path::Path, options: &DiscoveryOptions) -> io::Result<Self> {
+        let timeout = read_timeout(options);
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(Self {
+            stream,
+            name: format!("unix:{}", path.display()),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Read for UnixTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+#[cfg(unix)]
+impl Write for UnixTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixTransport {
+    fn clear_input(&mut self) -> io::Result<()> {
+        drain_socket(&mut self.stream, UnixStream::set_nonblocking)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Builds the reply to a request. It receives every byte written since the last reply was built,
+/// which is a command byte followed by any payload the client sent with it.
+pub type MockHandler = Box<dyn FnMut(&[u8]) -> Vec<u8> + Send>;
+
+/// An in-process [Transport] that answers each request with a handler instead of a server.
+///
+/// The handler runs when the client first reads after writing, so commands that send a payload
+/// see it in full. A read with no reply pending times out, as it would on a silent server.
+pub struct MockTransport {
+    handler: MockHandler,
+    request: Vec<u8>,
+    replies: VecDeque<u8>,
+}
+
+impl MockTransport {
+    pub fn new(handler: impl FnMut(&[u8]) -> Vec<u8> + Send + 'static) -> Self {
+        Self {
+            handler: Box::new(handler),
+            request: Vec::new(),
+            replies: VecDeque::new(),
+        }
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.request.is_empty() {
+            let reply = (self.handler)(&self.request);
+            self.replies.extend(reply);
+            self.request.clear();
+        }
+        if self.replies.is_empty() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Mock server sent no reply"));
+        }
+        let n = buf.len().min(self.replies.len());
+        for (dst, src) in buf.iter_mut().zip(self.replies.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.request.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.replies.clear();
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+}
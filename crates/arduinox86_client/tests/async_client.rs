@@ -0,0 +1,14 @@
+#![cfg(feature = "tokio")]
+use arduinox86_client::*;
+
+#[tokio::test]
+async fn test_spawn_returns_connect_error() {
+    let result = AsyncCpuClient::spawn(|| Err(CpuClientError::DiscoveryError)).await;
+    assert!(matches!(result, Err(CpuClientError::DiscoveryError)));
+}
+
+#[tokio::test]
+async fn test_spawn_reports_panicked_worker() {
+    let result = AsyncCpuClient::spawn(|| panic!("connect failed")).await;
+    assert!(matches!(result, Err(CpuClientError::WorkerStopped)));
+}
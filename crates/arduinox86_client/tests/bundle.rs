@@ -0,0 +1,94 @@
+#![cfg(feature = "bundle")]
+use arduinox86_client::*;
+
+fn cycle(cycle: u32, ale: bool, bus_state: &str, reading: bool, data_bus: u16) -> BundleCycle {
+    BundleCycle {
+        cycle,
+        ale,
+        address_latch: 0x1234,
+        bus_state: bus_state.to_string(),
+        data_bus,
+        reading,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_bus_ops_one_per_transfer() {
+    let trace = [
+        cycle(0, true, "MEMR", false, 0),
+        cycle(1, false, "PASV", true, 0x00AA),
+        // Wait state, the data is only valid on the last cycle of the transfer.
+        cycle(2, false, "PASV", true, 0x00BB),
+        cycle(3, false, "PASV", false, 0),
+        cycle(4, true, "CODE", false, 0),
+        cycle(5, false, "PASV", true, 0x0090),
+    ];
+
+    let ops = bus_ops(&trace);
+    assert_eq!(ops.len(), 2);
+    assert_eq!((ops[0].cycle, ops[0].kind.as_str(), ops[0].data), (2, "MEMR", 0x00BB));
+    assert_eq!((ops[1].cycle, ops[1].kind.as_str(), ops[1].data), (5, "CODE", 0x0090));
+}
+
+#[test]
+fn test_registers_by_name() {
+    let regs = RemoteCpuRegisters::V1(RemoteCpuRegistersV1 {
+        ax: 0x1234,
+        ip: 0x0100,
+        ..Default::default()
+    });
+    let named = BundleRegisters::from(&regs);
+    assert_eq!(named.set, "V1");
+    assert_eq!(named.registers["ax"], 0x1234);
+    assert_eq!(named.registers["ip"], 0x0100);
+}
+
+#[test]
+fn test_write_and_read_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut bundle = ExperimentBundle::new(BundleEnvironment::new("test", "1.0", ServerCpuType::Intel8088, false));
+    bundle.programs.push(BundleProgram {
+        name:    "program.bin".to_string(),
+        address: 0xF0100,
+        data:    vec![0x90, 0xF4],
+    });
+    bundle.initial_regs = Some(RemoteCpuRegisters::default());
+
+    let written = bundle.write(dir.path()).unwrap();
+    assert_eq!(
+        written.files,
+        ["program_0.bin", "initial_regs.bin", "initial_regs.json"]
+    );
+    assert!(dir.path().join("program_0.bin").exists());
+
+    let manifest = BundleManifest::read(dir.path()).unwrap();
+    assert_eq!(manifest, written);
+    assert_eq!(manifest.format_version, BUNDLE_FORMAT_VERSION);
+    assert_eq!(manifest.programs[0].address, 0xF0100);
+    assert_eq!(manifest.environment.cpu_type, "Intel8088");
+
+    // A second write must not mix two runs in one directory.
+    assert!(bundle.write(dir.path()).is_err());
+}
+
+#[test]
+fn test_bookmarks_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(read_bookmarks(dir.path()).unwrap().is_empty());
+
+    let mut bundle = ExperimentBundle::new(BundleEnvironment::new("test", "1.0", ServerCpuType::Intel8088, false));
+    bundle.bookmarks = vec![
+        BundleBookmark {
+            cycle: 12,
+            note:  "First MEMR".to_string(),
+        },
+        BundleBookmark {
+            cycle: 40,
+            note:  String::new(),
+        },
+    ];
+    let written = bundle.write(dir.path()).unwrap();
+    assert_eq!(written.files, [BUNDLE_BOOKMARKS_FILENAME]);
+    assert_eq!(read_bookmarks(dir.path()).unwrap(), bundle.bookmarks);
+}
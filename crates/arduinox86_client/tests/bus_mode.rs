@@ -0,0 +1,57 @@
+use arduinox86_client::*;
+
+// 8088 bus status values (S0-S2).
+const INTA: u8 = 0;
+const MEMR: u8 = 5;
+
+fn min_mode_cycle(t_state: u8, status: u8, pins: u16) -> ServerCycleState {
+    ServerCycleState {
+        program_state: ProgramState::Execute,
+        cpu_state_bits: t_state,
+        cpu_status_bits: status,
+        bus_control_bits: 0,
+        // A minimum mode capture has no 8288, so the command byte is noise. BHE is inactive.
+        bus_command_bits: 0x55 | ServerCycleState::COMMAND_BHE_BIT,
+        address_bus: 0,
+        data_bus: 0,
+        pins,
+    }
+}
+
+#[test]
+fn test_min_mode_commands() {
+    let mut state = min_mode_cycle(2, MEMR, ServerCycleState::PIN_RD);
+    state.apply_bus_mode(BusMode::Minimum);
+    assert!(state.is_reading_mem());
+    assert!(!state.is_reading_io());
+    assert!(!state.is_writing());
+    assert!(!state.bhe());
+
+    let mut state = min_mode_cycle(3, MEMR, ServerCycleState::PIN_WR | ServerCycleState::PIN_IO);
+    state.apply_bus_mode(BusMode::Minimum);
+    assert!(state.is_writing_io());
+    assert_eq!(state.bus_command_bits & ServerCycleState::COMMAND_AIOWC_BIT, 0);
+    assert!(!state.is_writing_mem());
+    assert!(!state.is_reading());
+
+    // INTA is decoded from the status, but only once the 8288 would assert commands.
+    let state = min_mode_cycle(1, INTA, 0);
+    assert_ne!(state.min_mode_command_bits() & ServerCycleState::COMMAND_INTA_BIT, 0);
+    let state = min_mode_cycle(2, INTA, 0);
+    assert_eq!(state.min_mode_command_bits() & ServerCycleState::COMMAND_INTA_BIT, 0);
+}
+
+#[test]
+fn test_max_mode_keeps_commands() {
+    let mut state = min_mode_cycle(2, MEMR, ServerCycleState::PIN_RD);
+    state.apply_bus_mode(BusMode::Maximum);
+    assert_eq!(state.bus_command_bits, 0x55 | ServerCycleState::COMMAND_BHE_BIT);
+}
+
+#[test]
+fn test_parse_bus_mode() {
+    assert_eq!("min".parse::<BusMode>().unwrap(), BusMode::Minimum);
+    assert_eq!("Maximum".parse::<BusMode>().unwrap(), BusMode::Maximum);
+    assert!("medium".parse::<BusMode>().is_err());
+    assert_eq!(BusMode::default(), BusMode::Maximum);
+}
@@ -0,0 +1,125 @@
+use arduinox86_client::*;
+
+// 8086 bus status values (S0-S2).
+const INTA: u8 = 0;
+const HALT: u8 = 3;
+const MEMR: u8 = 5;
+const MEMW: u8 = 6;
+const PASV: u8 = 7;
+
+// Command bits are active low.
+const IDLE: u8 = 0xFF;
+const MRDC: u8 = !ServerCycleState::COMMAND_MRDC_BIT;
+const MWTC: u8 = !ServerCycleState::COMMAND_MWTC_BIT;
+const INTA_CMD: u8 = !ServerCycleState::COMMAND_INTA_BIT;
+
+fn cycle(t_state: u8, status: u8, command: u8, bhe: bool, address: u32, data: u16) -> ServerCycleState {
+    let bhe_bit = if bhe { 0 } else { ServerCycleState::COMMAND_BHE_BIT };
+    ServerCycleState {
+        program_state: ProgramState::Execute,
+        cpu_state_bits: t_state,
+        cpu_status_bits: status,
+        bus_control_bits: (t_state == 1) as u8,
+        bus_command_bits: (command & !ServerCycleState::COMMAND_BHE_BIT) | bhe_bit,
+        address_bus: address,
+        data_bus: data,
+        pins: 0,
+    }
+}
+
+/// A four-cycle bus cycle with the data on the bus in T2 and T3, settling on `data` in T3.
+fn bus_cycle(status: u8, command: u8, bhe: bool, address: u32, data: u16) -> Vec<ServerCycleState> {
+    vec![
+        cycle(1, status, IDLE, bhe, address, 0),
+        cycle(2, PASV, command, bhe, address, 0),
+        cycle(3, PASV, command, bhe, address, data),
+        cycle(4, PASV, IDLE, bhe, address, 0),
+    ]
+}
+
+#[test]
+fn test_word_read_16_bit_bus() {
+    let states = bus_cycle(MEMR, MRDC, true, 0x1000, 0xBEEF);
+    let ops = collect_bus_ops(ServerCpuType::Intel8086, &states);
+
+    assert_eq!(
+        ops,
+        vec![BusOp {
+            cycle: 2,
+            bus_state: BusState::MEMR,
+            address: 0x1000,
+            data: 0xBEEF,
+            width: DataWidth::Sixteen,
+        }]
+    );
+    assert_eq!(ops[0].bytes(), vec![(0x1000, 0xEF), (0x1001, 0xBE)]);
+}
+
+#[test]
+fn test_read_8_bit_bus() {
+    // The 8088 has no BHE; whatever the pin reads, only the low half of the bus carries data.
+    let states = bus_cycle(MEMR, MRDC, true, 0x1001, 0xBEEF);
+    let ops = collect_bus_ops(ServerCpuType::Intel8088, &states);
+
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].width, DataWidth::EightLow);
+    assert_eq!(ops[0].bytes(), vec![(0x1001, 0xEF)]);
+}
+
+#[test]
+fn test_odd_address_bhe() {
+    // A byte at an odd address moves on the high half of the bus, with BHE asserted.
+    let states = bus_cycle(MEMW, MWTC, true, 0x1001, 0xAB00);
+    let ops = collect_bus_ops(ServerCpuType::Intel8086, &states);
+
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].bus_state, BusState::MEMW);
+    assert_eq!(ops[0].width, DataWidth::EightHigh);
+    assert_eq!(ops[0].bytes(), vec![(0x1001, 0xAB)]);
+
+    // A byte at an even address moves on the low half, with BHE inactive.
+    let states = bus_cycle(MEMW, MWTC, false, 0x1000, 0x00CD);
+    let ops = collect_bus_ops(ServerCpuType::Intel8086, &states);
+    assert_eq!(ops[0].width, DataWidth::EightLow);
+    assert_eq!(ops[0].bytes(), vec![(0x1000, 0xCD)]);
+
+    // An odd address without BHE selects neither half.
+    let states = bus_cycle(MEMR, MRDC, false, 0x1001, 0xABCD);
+    let ops = collect_bus_ops(ServerCpuType::Intel8086, &states);
+    assert_eq!(ops[0].width, DataWidth::Invalid);
+    assert!(ops[0].bytes().is_empty());
+}
+
+#[test]
+fn test_inta_and_halt() {
+    let mut states = vec![
+        cycle(1, HALT, IDLE, false, 0x0002, 0),
+        cycle(2, PASV, IDLE, false, 0x0002, 0),
+    ];
+    states.extend(bus_cycle(INTA, INTA_CMD, false, 0, 0x0008));
+    let ops = collect_bus_ops(ServerCpuType::Intel8088, &states);
+
+    // The halt cycle moves no data, so only the vector read is a transfer.
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].cycle, 4);
+    assert_eq!(ops[0].bus_state, BusState::INTA);
+    assert_eq!(ops[0].data, 0x0008);
+}
+
+#[test]
+fn test_collector_partial_bus_cycle() {
+    // A trace starting partway through a bus cycle uses the state it was created with.
+    let mut collector = BusOpCollector::new(BusState::CODE, 0x2000, DataWidth::Sixteen);
+    collector.cycle(0, Some(0x9090));
+    collector.cycle(1, None);
+    collector.latch(BusState::MEMR, 0x3000, DataWidth::EightLow);
+    collector.cycle(2, Some(0x0012));
+    let ops = collector.finish();
+
+    assert_eq!(ops.len(), 2);
+    assert_eq!((ops[0].bus_state, ops[0].address), (BusState::CODE, 0x2000));
+    assert_eq!(
+        (ops[1].bus_state, ops[1].cycle, ops[1].data),
+        (BusState::MEMR, 2, 0x0012)
+    );
+}
@@ -0,0 +1,139 @@
+use arduinox86_client::*;
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+/// Answer like a server speaking protocol version 3, which predates the capabilities query, the
+/// memory range upload and the state sequence number. Returns the commands received.
+fn old_server(mut stream: TcpStream) -> Vec<u8> {
+    let mut commands = Vec::new();
+    let mut cmd = [0u8; 1];
+    while stream.read_exact(&mut cmd).is_ok() {
+        commands.push(cmd[0]);
+        let reply: Vec<u8> = match cmd[0] {
+            c if c == ServerCommand::CmdVersion as u8 => [b"ardx86 ".as_slice(), &[3]].concat(),
+            c if c == ServerCommand::CmdSetMemory as u8 => {
+                let mut params = [0u8; 8];
+                stream.read_exact(&mut params).unwrap();
+                let mut data = vec![0u8; u32::from_le_bytes(params[4..8].try_into().unwrap()) as usize];
+                stream.read_exact(&mut data).unwrap();
+                vec![0x01]
+            }
+            // A version 3 status has no state sequence number.
+            c if c == ServerCommand::CmdGetServerStatus as u8 => [[0x09].as_slice(), &[0; 12], &[0x01]].concat(),
+            _ => vec![0x00],
+        };
+        if stream.write_all(&reply).is_err() {
+            break;
+        }
+    }
+    commands
+}
+
+#[test]
+fn test_capabilities_for_version() {
+    assert_eq!(Capabilities::for_version(2), Capabilities(0));
+    let v3 = Capabilities::for_version(3);
+    assert!(v3.contains(Capabilities::CYCLE_LOG | Capabilities::MEMORY_STRATEGY));
+    assert!(!v3.contains(Capabilities::INTERRUPT_TRIGGER));
+
+    let v4 = Capabilities::for_version(4);
+    assert!(v4.contains(Capabilities::INTERRUPT_TRIGGER));
+    assert!(!v4.contains(Capabilities::STATE_SEQUENCE));
+
+    let v7 = Capabilities::for_version(7);
+    assert!(v7.contains(Capabilities::STATE_BUDGET | Capabilities::MEMORY_RANGES));
+    assert!(!v7.contains(Capabilities::END_CONDITION));
+    let v8 = Capabilities::for_version(8);
+    assert!(v8.contains(Capabilities::END_CONDITION));
+    assert!(!v8.contains(Capabilities::CYCLE_LOG_ENCODING));
+    assert!(Capabilities::for_version(9).contains(Capabilities::CYCLE_LOG_ENCODING));
+}
+
+#[test]
+fn test_capabilities_missing() {
+    // Everything a version 9 server can report.
+    let full = Capabilities(0x1FF);
+    assert_eq!(full.missing(REQUIRED_PROTOCOL_VER), Capabilities(0));
+    // Boards without the cycle log leave out its encoding too.
+    let no_log = Capabilities(full.0 & !(Capabilities::CYCLE_LOG | Capabilities::CYCLE_LOG_ENCODING));
+    assert_eq!(
+        no_log.missing(REQUIRED_PROTOCOL_VER),
+        Capabilities(Capabilities::CYCLE_LOG | Capabilities::CYCLE_LOG_ENCODING)
+    );
+    assert_eq!(Capabilities::for_version(8).missing(8), Capabilities(0));
+}
+
+#[test]
+fn test_current_server_capabilities() {
+    let transport = MockTransport::new(|request| match request[0] {
+        0x01 => [SERVER_BANNER, &[REQUIRED_PROTOCOL_VER]].concat(),
+        0x2D => [&0x1FFu32.to_le_bytes()[..], &[0x01]].concat(),
+        _ => vec![0x01],
+    });
+    let client = CpuClient::with_transport(Box::new(transport), &DiscoveryOptions::default()).unwrap();
+    assert_eq!(client.protocol_version(), REQUIRED_PROTOCOL_VER);
+    assert!(client.capabilities().contains(Capabilities::CYCLE_LOG_ENCODING));
+    assert_eq!(client.capabilities().missing(REQUIRED_PROTOCOL_VER), Capabilities(0));
+}
+
+#[test]
+fn test_capabilities_display() {
+    assert_eq!(Capabilities(0).to_string(), "none");
+    assert_eq!(
+        Capabilities(Capabilities::CYCLE_LOG | Capabilities::END_CONDITION).to_string(),
+        "cycle log, end condition"
+    );
+}
+
+#[test]
+fn test_command_capability() {
+    assert_eq!(ServerCommand::CmdGetCycleStates.capability(), Capabilities::CYCLE_LOG);
+    assert_eq!(
+        ServerCommand::CmdSetEndCondition.capability(),
+        Capabilities::END_CONDITION
+    );
+    assert_eq!(ServerCommand::CmdCycle.capability(), 0);
+    assert_eq!(ServerCommand::CmdGetCapabilities.capability(), 0);
+}
+
+#[test]
+fn test_old_server_degrades() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || old_server(listener.accept().unwrap().0));
+
+    let mut client = CpuClient::init(Some(format!("tcp://{addr}")), Some(1000)).unwrap();
+    assert_eq!(client.protocol_version(), 3);
+    assert_eq!(client.capabilities(), Capabilities::for_version(3));
+
+    // Unsupported commands fail without being sent.
+    assert!(matches!(
+        client.set_end_condition(None),
+        Err(CpuClientError::Unsupported(ServerCommand::CmdSetEndCondition))
+    ));
+
+    // Memory ranges fall back to one write per range.
+    let mut batch = MemoryBatch::new();
+    batch.stage(0x1000, &[0x90, 0x90]);
+    batch.stage(0x2000, &[0xF4]);
+    assert!(client.set_memory_ranges(&batch).unwrap());
+
+    let status = client.server_status().unwrap();
+    assert_eq!(status.state, ProgramState::Execute);
+    assert_eq!(status.state_seq, 0);
+
+    drop(client);
+    let commands = server.join().unwrap();
+    assert_eq!(
+        commands,
+        vec![
+            ServerCommand::CmdVersion as u8,
+            ServerCommand::CmdSetMemory as u8,
+            ServerCommand::CmdSetMemory as u8,
+            ServerCommand::CmdGetServerStatus as u8,
+        ]
+    );
+}
@@ -0,0 +1,78 @@
+use arduinox86_client::*;
+
+#[test]
+fn test_strict_policy() {
+    let policy = ComparisonPreset::Strict;
+    assert!(!policy.register_matches("FLAGS", 0xF002, 0xF012, 0x0010));
+    assert!(policy.compare_address(Some(0x12), 0x12));
+    assert!(policy.cycles_match(10, 10));
+    assert!(!policy.cycles_match(10, 11));
+}
+
+#[test]
+fn test_emulator_friendly_policy() {
+    let policy = ComparisonPreset::EmulatorFriendly;
+    // AF is undefined, so only the difference in CF counts.
+    assert!(policy.register_matches("FLAGS", 0xF002, 0xF012, 0x0010));
+    assert!(!policy.register_matches("FLAGS", 0xF002, 0xF003, 0x0010));
+    assert!(!policy.register_matches("AX", 0x0010, 0x0000, 0x0010));
+    assert!(policy.cycles_match(10, 50));
+
+    let mismatches = policy.register_mismatches(&[("AX", 1, 1), ("BX", 1, 2), ("FLAGS", 0x02, 0x12)], 0x0010);
+    assert_eq!(
+        mismatches,
+        vec![RegisterMismatch {
+            register: "BX",
+            expected: 1,
+            actual:   2,
+        }]
+    );
+}
+
+#[test]
+fn test_registers_only_policy() {
+    let policy = ComparisonPreset::RegistersOnly;
+    assert_eq!(policy.memory_scope(), MemoryScope::None);
+    assert!(!policy.compare_address(None, 0x12));
+    assert!(policy.cycles_match(10, 50));
+}
+
+/// A custom policy that ignores SP and only checks written memory.
+struct IgnoreStack;
+
+impl ComparisonPolicy for IgnoreStack {
+    fn name(&self) -> &str {
+        "ignore_stack"
+    }
+
+    fn compare_register(&self, register: &str) -> bool {
+        register != "SP"
+    }
+
+    fn memory_scope(&self) -> MemoryScope {
+        MemoryScope::Written
+    }
+}
+
+#[test]
+fn test_custom_policy() {
+    let policy = IgnoreStack;
+    assert!(policy.register_matches("SP", 0xFFFE, 0xFFFC, 0));
+    assert!(!policy.register_matches("BP", 0xFFFE, 0xFFFC, 0));
+    assert!(!policy.compare_address(Some(0x12), 0x12));
+    assert!(policy.compare_address(Some(0x12), 0x34));
+    assert!(policy.compare_address(None, 0x12));
+}
+
+#[test]
+fn test_parse_preset() {
+    assert_eq!("strict".parse::<ComparisonPreset>().unwrap(), ComparisonPreset::Strict);
+    assert_eq!(
+        "emulator-friendly".parse::<ComparisonPreset>().unwrap(),
+        ComparisonPreset::EmulatorFriendly
+    );
+    for preset in ComparisonPreset::ALL {
+        assert_eq!(preset.to_string().parse::<ComparisonPreset>().unwrap(), preset);
+    }
+    assert!("lenient".parse::<ComparisonPreset>().is_err());
+}
@@ -0,0 +1,120 @@
+use arduinox86_client::*;
+
+fn cycle(address: u32, data: u16, t_state: u8, status: u8) -> ServerCycleState {
+    ServerCycleState {
+        program_state: ProgramState::Execute,
+        cpu_state_bits: t_state,
+        cpu_status_bits: status,
+        bus_control_bits: (t_state == 1) as u8,
+        bus_command_bits: 0xFF,
+        address_bus: address,
+        data_bus: data,
+        pins: 0,
+    }
+}
+
+/// Code fetches from consecutive words, with a run of idle cycles and a jump.
+fn fetch_log() -> Vec<ServerCycleState> {
+    let mut states = Vec::new();
+    for i in 0..8u32 {
+        for t in 1..=4 {
+            states.push(cycle(0xFFFF0 + i * 2, 0x9090, t, 4));
+        }
+    }
+    states.extend(std::iter::repeat(cycle(0xFFFFE, 0x9090, 0, 7)).take(300));
+    states.push(cycle(0x01000, 0xF4F4, 1, 4));
+    states
+}
+
+fn fields(states: &[ServerCycleState]) -> Vec<(u32, u16, u8, u8, u8, u8, u16)> {
+    states
+        .iter()
+        .map(|s| {
+            (
+                s.address_bus,
+                s.data_bus,
+                s.cpu_state_bits,
+                s.cpu_status_bits,
+                s.bus_control_bits,
+                s.bus_command_bits,
+                s.pins,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_round_trip() {
+    let states = fetch_log();
+    let encoded = encode_cycle_log(&states);
+    let decoded = decode_cycle_log(states.len(), &encoded).unwrap();
+    assert_eq!(fields(&decoded), fields(&states));
+
+    // Far smaller than the raw log.
+    assert!(encoded.len() * 4 < states.len() * CYCLE_RECORD_LEN);
+}
+
+#[test]
+fn test_encoding_bytes() {
+    let states = [
+        cycle(0x1000, 0, 1, 4),
+        cycle(0x1000, 0, 2, 4),
+        cycle(0x1000, 0, 2, 4),
+        cycle(0x1002, 0, 2, 4),
+        cycle(0x0FF0, 0, 2, 4),
+    ];
+    assert_eq!(
+        encode_cycle_log(&states),
+        vec![
+            // The first cycle against an all-zero cycle.
+            FIELD_ADDRESS | FIELD_STATE | FIELD_STATUS | FIELD_CONTROL | FIELD_COMMAND,
+            0x00,
+            0x10,
+            0x00,
+            0x00,
+            1,
+            4,
+            1,
+            0xFF,
+            // T2 drops ALE.
+            FIELD_STATE | FIELD_CONTROL,
+            2,
+            0,
+            // A repeat.
+            0,
+            1,
+            // Small steps are deltas, either way.
+            FIELD_ADDRESS_DELTA,
+            2,
+            FIELD_ADDRESS_DELTA,
+            0xEE,
+        ]
+    );
+}
+
+#[test]
+fn test_decode_rejects_bad_logs() {
+    let states = fetch_log();
+    let encoded = encode_cycle_log(&states);
+
+    // Truncated, with bytes left over, or with a run past the count.
+    assert!(decode_cycle_log(states.len(), &encoded[..encoded.len() - 1]).is_err());
+    assert!(decode_cycle_log(states.len() - 1, &encoded).is_err());
+    assert!(decode_cycle_log(1, &[0, 2]).is_err());
+    assert!(decode_cycle_log(1, &[0, 0]).is_err());
+    // Both a full address and a delta.
+    assert!(decode_cycle_log(1, &[FIELD_ADDRESS | FIELD_ADDRESS_DELTA, 0, 0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_decode_raw() {
+    let raw = [
+        0x34, 0x12, 0x00, 0x00, 0xCD, 0xAB, 3, 5, 0, 0xFE, 0x01, 0x00, 0xAA, 0xAA,
+    ];
+    // Records longer than the known fields are allowed, for servers that add fields.
+    let states = decode_raw_cycle_log(1, 14, &raw).unwrap();
+    assert_eq!(fields(&states), vec![(0x1234, 0xABCD, 3, 5, 0, 0xFE, 1)]);
+
+    assert!(decode_raw_cycle_log(2, 14, &raw).is_err());
+    assert!(decode_raw_cycle_log(1, 8, &raw).is_err());
+}
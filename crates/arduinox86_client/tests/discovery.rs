@@ -0,0 +1,62 @@
+use arduinox86_client::*;
+
+#[test]
+fn test_find_banner() {
+    assert_eq!(find_banner(b"ardx86 \x03\n"), Some(3));
+    assert_eq!(find_banner(b"ardX86 \x03"), Some(3));
+}
+
+#[test]
+fn test_find_banner_leading_garbage() {
+    // Bootloader noise and a partial line from before the reset.
+    assert_eq!(find_banner(b"\x00\xFF\xF0ard\r\nardx86 \x03\n"), Some(3));
+}
+
+#[test]
+fn test_find_banner_incomplete() {
+    assert_eq!(find_banner(b""), None);
+    assert_eq!(find_banner(b"ardx8"), None);
+    assert_eq!(find_banner(b"\x00\x00ardx86 "), None);
+    assert_eq!(find_banner(b"arduino "), None);
+}
+
+#[test]
+fn test_usb_id_parse() {
+    assert_eq!(
+        "2341:0043".parse::<UsbId>(),
+        Ok(UsbId {
+            vid: 0x2341,
+            pid: Some(0x0043),
+        })
+    );
+    assert_eq!("2341:*".parse::<UsbId>(), Ok(UsbId { vid: 0x2341, pid: None }));
+    assert_eq!("2341".parse::<UsbId>(), Ok(UsbId { vid: 0x2341, pid: None }));
+    assert!("arduino".parse::<UsbId>().is_err());
+    assert!(UsbId { vid: 0x2341, pid: None }.matches(0x2341, 0x8036));
+}
+
+#[test]
+fn test_port_filter_names() {
+    let port = |name: &str| serialport::SerialPortInfo {
+        port_name: name.to_string(),
+        port_type: serialport::SerialPortType::BluetoothPort,
+    };
+    let filter = PortFilter {
+        allow:   vec!["/dev/ttyACM*".to_string(), "/dev/ttyS0".to_string()],
+        deny:    vec!["/dev/ttyACM1".to_string()],
+        usb_ids: Vec::new(),
+    };
+
+    assert!(filter.matches(&port("/dev/ttyACM0")));
+    assert!(filter.matches(&port("/dev/ttyS0")));
+    assert!(!filter.matches(&port("/dev/ttyACM1")));
+    assert!(!filter.matches(&port("/dev/ttyS1")));
+    assert!(PortFilter::default().matches(&port("/dev/rfcomm0")));
+
+    // A USB ID filter excludes anything that isn't a matching USB port.
+    let usb_only = PortFilter {
+        usb_ids: vec![UsbId { vid: 0x2341, pid: None }],
+        ..Default::default()
+    };
+    assert!(!usb_only.matches(&port("/dev/rfcomm0")));
+}
@@ -0,0 +1,28 @@
+use arduinox86_client::EndCondition;
+
+#[test]
+fn test_end_condition_encoding() {
+    assert_eq!(EndCondition::to_bytes(None), [0, 0, 0, 0, 0]);
+    assert_eq!(
+        EndCondition::to_bytes(Some(EndCondition::Iret(0x21))),
+        [1, 0x21, 0, 0, 0]
+    );
+    assert_eq!(
+        EndCondition::to_bytes(Some(EndCondition::PortWrite(0x03F8))),
+        [2, 0xF8, 0x03, 0, 0]
+    );
+    assert_eq!(
+        EndCondition::to_bytes(Some(EndCondition::Fetch(0xF0123))),
+        [3, 0x23, 0x01, 0x0F, 0]
+    );
+    assert_eq!(
+        EndCondition::to_bytes(Some(EndCondition::Cycles(1000))),
+        [4, 0xE8, 0x03, 0, 0]
+    );
+}
+
+#[test]
+fn test_end_condition_display() {
+    assert_eq!(EndCondition::Iret(0x21).to_string(), "IRET from INT 21h");
+    assert_eq!(EndCondition::Fetch(0xF0123).to_string(), "fetch from F0123h");
+}
@@ -0,0 +1,88 @@
+use std::sync::{Arc, Mutex};
+
+use arduinox86_client::*;
+
+#[test]
+fn test_execution_mode_from_flags() {
+    assert_eq!(ExecutionMode::from_flags(0), ExecutionMode::Manual);
+    assert_eq!(
+        ExecutionMode::from_flags(ServerFlags::USE_SMM | ServerFlags::HALT_AFTER_JUMP),
+        ExecutionMode::Manual
+    );
+    assert_eq!(
+        ExecutionMode::from_flags(ServerFlags::EXECUTE_AUTOMATIC | ServerFlags::USE_SDRAM_BACKEND),
+        ExecutionMode::Automatic
+    );
+}
+
+#[test]
+fn test_mode_conflict_message() {
+    let err = CpuClientError::ModeConflict {
+        requested: ExecutionMode::Manual,
+        active:    ExecutionMode::Automatic,
+    };
+    assert_eq!(
+        err.to_string(),
+        "Cannot use manual execution while the server is in automatic mode."
+    );
+}
+
+/// A mock server that accepts any flags and records every command byte it receives.
+fn flag_server(commands: Arc<Mutex<Vec<u8>>>) -> MockTransport {
+    MockTransport::new(move |request| {
+        commands.lock().unwrap().push(request[0]);
+        match request[0] {
+            0x01 => [SERVER_BANNER, &[REQUIRED_PROTOCOL_VER]].concat(),
+            0x2D => {
+                let caps = Capabilities::for_version(REQUIRED_PROTOCOL_VER).0;
+                [&caps.to_le_bytes()[..], &[0x01]].concat()
+            }
+            _ => vec![0x01],
+        }
+    })
+}
+
+#[test]
+fn test_cycle_refused_in_automatic_mode() {
+    let commands = Arc::new(Mutex::new(Vec::new()));
+    let transport = flag_server(commands.clone());
+    let mut client = CpuClient::with_transport(Box::new(transport), &DiscoveryOptions::default()).unwrap();
+
+    client.set_flags(ServerFlags::EXECUTE_AUTOMATIC).unwrap();
+    assert_eq!(client.execution_mode(), Some(ExecutionMode::Automatic));
+    assert!(matches!(
+        client.cycle(),
+        Err(CpuClientError::ModeConflict {
+            requested: ExecutionMode::Manual,
+            active:    ExecutionMode::Automatic,
+        })
+    ));
+    assert!(!commands.lock().unwrap().contains(&(ServerCommand::CmdCycle as u8)));
+
+    // Back in manual mode the cycle goes through to the server.
+    client.set_flags(0).unwrap();
+    assert!(client.cycle().unwrap());
+    assert!(commands.lock().unwrap().contains(&(ServerCommand::CmdCycle as u8)));
+}
+
+#[test]
+fn test_missed_transitions() {
+    let previous = ServerStatus {
+        state_seq: 10,
+        ..Default::default()
+    };
+    let next = |state_seq| ServerStatus {
+        state_seq,
+        ..Default::default()
+    };
+    assert_eq!(next(10).missed_transitions(&previous), 0);
+    assert_eq!(next(11).missed_transitions(&previous), 0);
+    assert_eq!(next(14).missed_transitions(&previous), 3);
+
+    // The counter wraps on long-running servers.
+    let previous = ServerStatus {
+        state_seq: u32::MAX,
+        ..Default::default()
+    };
+    assert_eq!(next(1).missed_transitions(&previous), 1);
+}
@@ -0,0 +1,37 @@
+use arduinox86_client::MemoryBatch;
+
+#[test]
+fn test_batch_keeps_disjoint_ranges_sorted() {
+    let mut batch = MemoryBatch::new();
+    batch.stage(0x1000, &[1, 2]);
+    batch.stage(0x0010, &[3]);
+    batch.stage(0x2000, &[]);
+
+    assert_eq!(batch.ranges(), &[(0x0010, vec![3]), (0x1000, vec![1, 2])]);
+    assert_eq!(batch.writes(), 2);
+    assert_eq!(batch.byte_len(), 3);
+}
+
+#[test]
+fn test_batch_merges_overlapping_and_adjacent_writes() {
+    let mut batch = MemoryBatch::new();
+    batch.stage(0x100, &[1, 2, 3, 4]);
+    // Adjacent write is appended.
+    batch.stage(0x104, &[5]);
+    // Overlapping write replaces the bytes it covers.
+    batch.stage(0x0FF, &[9, 9]);
+    // A write spanning two ranges joins them.
+    batch.stage(0x200, &[7]);
+    batch.stage(0x105, &vec![0; 0xFB]);
+
+    assert_eq!(batch.ranges().len(), 1);
+    let (address, data) = &batch.ranges()[0];
+    assert_eq!(*address, 0x0FF);
+    assert_eq!(&data[0..6], &[9, 9, 2, 3, 4, 5]);
+    assert_eq!(data.len(), 0x102);
+    assert_eq!(data[0x101], 7);
+
+    batch.clear();
+    assert!(batch.is_empty());
+    assert_eq!(batch.writes(), 0);
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use arduinox86_client::*;
+
+fn state(state: ProgramState, cycles: u32, micros: u64) -> ServerStateBudget {
+    ServerStateBudget {
+        state,
+        cycles,
+        time: Duration::from_micros(micros),
+    }
+}
+
+#[test]
+fn test_from_server() {
+    let states = [
+        state(ProgramState::Reset, 50, 500),
+        state(ProgramState::JumpVector, 10, 100),
+        state(ProgramState::Load, 200, 2000),
+        state(ProgramState::Prefetch, 20, 200),
+        state(ProgramState::Execute, 30, 300),
+        state(ProgramState::ExecuteFinalize, 40, 400),
+        state(ProgramState::Store, 5, 50),
+        state(ProgramState::StoreDone, 7, 70),
+    ];
+    let budget = PhaseBudget::from_server(&states);
+
+    assert_eq!(budget.get(RunPhase::JumpVector).cycles, 10);
+    assert_eq!(budget.get(RunPhase::Load).cycles, 200);
+    assert_eq!(budget.get(RunPhase::Preload).cycles, 20);
+    assert_eq!(budget.get(RunPhase::Program).cycles, 30);
+    assert_eq!(budget.get(RunPhase::Finalize).cycles, 40);
+    assert_eq!(budget.get(RunPhase::Store).time, Duration::from_micros(50));
+
+    // Reset and StoreDone are outside of the run.
+    assert_eq!(budget.total().cycles, 305);
+    assert_eq!(budget.overhead().cycles, 275);
+}
+
+#[test]
+fn test_merge() {
+    let mut total = PhaseBudget::default();
+    assert!(total.is_empty());
+
+    let mut run = PhaseBudget::default();
+    run.add(RunPhase::Program, 12, Duration::from_millis(1));
+    run.add(RunPhase::Load, 100, Duration::from_millis(2));
+    total.merge(&run);
+    total.merge(&run);
+
+    assert_eq!(total.get(RunPhase::Program).cycles, 24);
+    assert_eq!(total.get(RunPhase::Load).time, Duration::from_millis(4));
+    assert_eq!(total.overhead().cycles, 200);
+    assert_eq!(total.to_string().lines().count(), RunPhase::ALL.len() + 1);
+}
@@ -0,0 +1,53 @@
+use arduinox86_client::*;
+
+#[test]
+fn test_pin_writes_blocked_during_load_and_store() {
+    for state in [
+        ProgramState::CpuId,
+        ProgramState::CpuSetup,
+        ProgramState::JumpVector,
+        ProgramState::Load,
+        ProgramState::LoadSmm,
+        ProgramState::Store,
+        ProgramState::StoreAll,
+    ] {
+        assert!(!state.allows_pin_writes(), "{:?} should block pin writes", state);
+    }
+}
+
+#[test]
+fn test_pin_writes_allowed_around_execution() {
+    for state in [
+        ProgramState::Reset,
+        ProgramState::LoadDone,
+        ProgramState::EmuEnter,
+        ProgramState::Prefetch,
+        ProgramState::Execute,
+        ProgramState::ExecuteFinalize,
+        ProgramState::ExecuteDone,
+        ProgramState::EmuExit,
+        ProgramState::StoreDone,
+        ProgramState::Done,
+    ] {
+        assert!(state.allows_pin_writes(), "{:?} should allow pin writes", state);
+    }
+}
+
+#[test]
+fn test_state_changing_commands() {
+    assert!(ServerCommand::CmdLoad.may_change_state());
+    assert!(ServerCommand::CmdCycle.may_change_state());
+    assert!(ServerCommand::CmdStore.may_change_state());
+    assert!(!ServerCommand::CmdWritePin.may_change_state());
+    assert!(!ServerCommand::CmdReadPin.may_change_state());
+    assert!(!ServerCommand::CmdGetProgramState.may_change_state());
+}
+
+#[test]
+fn test_blocked_error_names_pin_and_state() {
+    let err = CpuClientError::PinWriteBlocked {
+        pin:   CpuPin::NMI,
+        state: ProgramState::Load,
+    };
+    assert_eq!(err.to_string(), "Refusing to write NMI while the server is in Load.");
+}
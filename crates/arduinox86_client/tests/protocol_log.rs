@@ -0,0 +1,29 @@
+use arduinox86_client::*;
+
+#[test]
+fn test_protocol_log_keeps_latest_entries() {
+    let mut log = ProtocolLog::new(2);
+    log.push(ProtocolEvent::Command(ServerCommand::CmdReset));
+    log.push(ProtocolEvent::Command(ServerCommand::CmdLoad));
+    log.push(ProtocolEvent::Result(ServerCommand::CmdLoad, Some(0x01)));
+
+    let events: Vec<_> = log.entries().map(|entry| &entry.event).collect();
+    assert_eq!(log.len(), 2);
+    assert!(matches!(events[0], ProtocolEvent::Command(ServerCommand::CmdLoad)));
+    assert!(matches!(
+        events[1],
+        ProtocolEvent::Result(ServerCommand::CmdLoad, Some(0x01))
+    ));
+}
+
+#[test]
+fn test_protocol_log_previews_buffers() {
+    let mut log = ProtocolLog::new(4);
+    log.push_sent(&[0xAA; 40]);
+    log.push_received(None);
+
+    let text = log.to_string();
+    let lines: Vec<_> = text.lines().collect();
+    assert!(lines[0].ends_with(&format!("> 40 bytes:{} ...", " AA".repeat(16))));
+    assert!(lines[1].ends_with("< read failed"));
+}
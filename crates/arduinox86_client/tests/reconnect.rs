@@ -0,0 +1,185 @@
+use arduinox86_client::*;
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+/// Answer as a server speaking protocol version `version`, and hang up without replying when
+/// `hang_up_on` is received. Returns the commands received.
+fn server(mut stream: TcpStream, version: u8, hang_up_on: Option<ServerCommand>) -> Vec<u8> {
+    let mut commands = Vec::new();
+    let mut cmd = [0u8; 1];
+    while stream.read_exact(&mut cmd).is_ok() {
+        if hang_up_on.is_some_and(|hang_up_on| cmd[0] == hang_up_on as u8) {
+            break;
+        }
+        commands.push(cmd[0]);
+        let reply = match cmd[0] {
+            c if c == ServerCommand::CmdVersion as u8 => [b"ardx86 ".as_slice(), &[version]].concat(),
+            c if c == ServerCommand::CmdGetCapabilities as u8 => vec![0xFF, 0x01, 0x00, 0x00, 0x01],
+            c if c == ServerCommand::CmdGetFlags as u8 => vec![0x82, 0x00, 0x00, 0x00, 0x01],
+            c if c == ServerCommand::CmdSetFlags as u8 => {
+                let mut flags = [0u8; 4];
+                stream.read_exact(&mut flags).unwrap();
+                vec![0x01]
+            }
+            _ => vec![0x01],
+        };
+        if stream.write_all(&reply).is_err() {
+            break;
+        }
+    }
+    commands
+}
+
+/// Serve two connections in turn: the first hangs up on `hang_up_on`, and the second answers with
+/// protocol version `version`. Returns the commands the second received.
+fn spawn_servers(hang_up_on: ServerCommand, version: u8) -> (TransportUri, thread::JoinHandle<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = TransportUri::Tcp(listener.local_addr().unwrap().to_string());
+    let handle = thread::spawn(move || {
+        server(listener.accept().unwrap().0, REQUIRED_PROTOCOL_VER, Some(hang_up_on));
+        server(listener.accept().unwrap().0, version, None)
+    });
+    (uri, handle)
+}
+
+fn retrying_options() -> DiscoveryOptions {
+    DiscoveryOptions {
+        retry: RetryPolicy {
+            retries: 1,
+            reopen_attempts: 2,
+            reopen_delay: 0,
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_command_retryable() {
+    assert!(ServerCommand::CmdSetMemory.is_retryable());
+    assert!(ServerCommand::CmdSetFlags.is_retryable());
+    assert!(ServerCommand::CmdCpuType.is_retryable());
+    assert!(!ServerCommand::CmdCycle.is_retryable());
+    assert!(!ServerCommand::CmdReset.is_retryable());
+    // A reset server answers reads with its reset state, so they aren't retried.
+    assert!(!ServerCommand::CmdGetFlags.is_retryable());
+    assert!(!ServerCommand::CmdGetCycleState.is_retryable());
+    assert!(!ServerCommand::CmdGetCycleStates.is_retryable());
+    assert!(!ServerCommand::CmdReadMemory.is_retryable());
+    assert!(!ServerCommand::CmdGetProgramState.is_retryable());
+    assert!(!ServerCommand::CmdQueueBytes.is_retryable());
+}
+
+#[test]
+fn test_retryable_command_retried() {
+    let (uri, servers) = spawn_servers(ServerCommand::CmdSetFlags, REQUIRED_PROTOCOL_VER);
+    let mut client = CpuClient::connect(&uri, &retrying_options()).unwrap();
+    client.enable_protocol_log(64);
+
+    assert!(client.set_flags(0x82).unwrap());
+
+    let reconnects = client.take_reconnects();
+    assert_eq!(reconnects.len(), 1);
+    assert!(matches!(reconnects[0].command, ServerCommand::CmdSetFlags));
+    assert!(reconnects[0].retried);
+    assert!(client.take_reconnects().is_empty());
+    assert!(client
+        .protocol_log()
+        .unwrap()
+        .entries()
+        .any(|entry| matches!(entry.event, ProtocolEvent::Reconnected(_))));
+
+    drop(client);
+    let second = servers.join().unwrap();
+    assert_eq!(
+        second,
+        vec![
+            ServerCommand::CmdVersion as u8,
+            ServerCommand::CmdGetCapabilities as u8,
+            ServerCommand::CmdSetFlags as u8,
+        ]
+    );
+}
+
+#[test]
+fn test_state_read_not_retried() {
+    let (uri, servers) = spawn_servers(ServerCommand::CmdGetFlags, REQUIRED_PROTOCOL_VER);
+    let mut client = CpuClient::connect(&uri, &retrying_options()).unwrap();
+
+    assert!(matches!(
+        client.get_flags(),
+        Err(CpuClientError::Reconnected(ServerCommand::CmdGetFlags))
+    ));
+    let reconnects = client.take_reconnects();
+    assert_eq!(reconnects.len(), 1);
+    assert!(!reconnects[0].retried);
+
+    // Once the caller has restored the server, reads go through on the new connection.
+    assert!(client.set_flags(0x82).unwrap());
+    assert_eq!(client.get_flags().unwrap(), 0x82);
+
+    drop(client);
+    let second = servers.join().unwrap();
+    assert_eq!(
+        second,
+        vec![
+            ServerCommand::CmdVersion as u8,
+            ServerCommand::CmdGetCapabilities as u8,
+            ServerCommand::CmdSetFlags as u8,
+            ServerCommand::CmdGetFlags as u8,
+        ]
+    );
+}
+
+#[test]
+fn test_state_changing_command_not_retried() {
+    let (uri, servers) = spawn_servers(ServerCommand::CmdReset, REQUIRED_PROTOCOL_VER);
+    let mut client = CpuClient::connect(&uri, &retrying_options()).unwrap();
+
+    assert!(matches!(
+        client.reset_cpu(),
+        Err(CpuClientError::Reconnected(ServerCommand::CmdReset))
+    ));
+    let reconnects = client.take_reconnects();
+    assert_eq!(reconnects.len(), 1);
+    assert!(!reconnects[0].retried);
+
+    // The client is usable again on the new connection.
+    assert_eq!(client.get_flags().unwrap(), 0x82);
+
+    drop(client);
+    let second = servers.join().unwrap();
+    assert!(!second.contains(&(ServerCommand::CmdReset as u8)));
+}
+
+#[test]
+fn test_no_reconnect_by_default() {
+    let (uri, servers) = spawn_servers(ServerCommand::CmdGetFlags, REQUIRED_PROTOCOL_VER);
+    let mut client = CpuClient::connect(&uri, &DiscoveryOptions::default()).unwrap();
+
+    assert!(matches!(client.get_flags(), Err(CpuClientError::ReadFailure)));
+    assert!(client.take_reconnects().is_empty());
+
+    // Let the second server finish.
+    drop(client);
+    _ = TcpStream::connect(uri.to_string().trim_start_matches("tcp://"));
+    servers.join().unwrap();
+}
+
+#[test]
+fn test_version_change_rejected() {
+    let (uri, servers) = spawn_servers(ServerCommand::CmdGetFlags, REQUIRED_PROTOCOL_VER - 1);
+    let mut client = CpuClient::connect(&uri, &retrying_options()).unwrap();
+
+    assert!(matches!(
+        client.get_flags(),
+        Err(CpuClientError::VersionChanged { expected, found })
+            if expected == REQUIRED_PROTOCOL_VER && found == REQUIRED_PROTOCOL_VER - 1
+    ));
+    assert!(client.take_reconnects().is_empty());
+
+    drop(client);
+    servers.join().unwrap();
+}
@@ -0,0 +1,129 @@
+use arduinox86_client::*;
+use proptest::prelude::*;
+
+/// Write `value` as a little-endian word at `offset`.
+fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[test]
+fn test_v1_layout() {
+    // Layout of registers1_t in the server firmware:
+    // AX, BX, CX, DX, IP, CS, FLAGS, SS, SP, DS, ES, BP, SI, DI
+    let mut buf = [0u8; 28];
+    for i in 0..14 {
+        put_u16(&mut buf, i * 2, 0x1100 * (i as u16 + 1));
+    }
+
+    let regs = RemoteCpuRegistersV1::decode(&buf).unwrap();
+    assert_eq!(regs.ax, 0x1100);
+    assert_eq!(regs.dx, 0x4400);
+    assert_eq!(regs.ip, 0x5500);
+    assert_eq!(regs.cs, 0x6600);
+    assert_eq!(regs.flags, 0x7700);
+    assert_eq!(regs.ss, 0x8800);
+    assert_eq!(regs.sp, 0x9900);
+    assert_eq!(regs.di, 0xEE00);
+
+    // The legacy conversions must agree with the shared implementation.
+    assert_eq!(RemoteCpuRegistersV1::from(&buf).to_bytes(), buf);
+    let mut written = [0u8; 28];
+    regs.write_buf(&mut written);
+    assert_eq!(written, buf);
+}
+
+#[test]
+fn test_v2_layout() {
+    // 286 LOADALL image starting at 0x800.
+    let mut buf = [0u8; 102];
+    put_u16(&mut buf, 0x06, 0xFFF1); // MSW
+    put_u16(&mut buf, 0x18, 0x0202); // FLAGS
+    put_u16(&mut buf, 0x1A, 0x1234); // IP
+    put_u16(&mut buf, 0x22, 0xF000); // CS
+    put_u16(&mut buf, 0x34, 0xABCD); // AX
+    // CS descriptor cache, second of the eight 6-byte descriptors.
+    buf[0x36 + 6..0x36 + 9].copy_from_slice(&[0x00, 0x00, 0x0F]);
+
+    let regs = RemoteCpuRegistersV2::decode(&buf).unwrap();
+    assert_eq!(regs.msw, 0xFFF1);
+    assert_eq!(regs.flags, 0x0202);
+    assert_eq!(regs.ip, 0x1234);
+    assert_eq!(regs.cs, 0xF000);
+    assert_eq!(regs.ax, 0xABCD);
+    assert_eq!(regs.cs_desc.base_address(), 0xF0000);
+}
+
+#[test]
+fn test_v3_layout() {
+    let mut buf = [0u8; 208];
+    put_u32(&mut buf, 0x08, 0x0000_0100); // EIP
+    put_u32(&mut buf, 0x28, 0xDEAD_BEEF); // EAX
+    put_u16(&mut buf, 0x4C, 0x1000); // CS
+    put_u32(&mut buf, 0x54 + 8 * 12 + 4, 0x0001_0000); // CS descriptor base
+
+    let regs = RemoteCpuRegistersV3A::decode(&buf).unwrap();
+    assert_eq!(regs.eip, 0x100);
+    assert_eq!(regs.eax, 0xDEAD_BEEF);
+    assert_eq!(regs.cs, 0x1000);
+    assert_eq!(regs.cs_desc.base_address(), 0x10000);
+
+    // The SMM layout has CR3 inserted after CR0, shifting everything by 4 bytes.
+    let mut buf_b = [0u8; 208];
+    buf_b[8..].copy_from_slice(&buf[4..204]);
+    put_u32(&mut buf_b, 0x04, 0x1234_5678);
+    let regs_b = RemoteCpuRegistersV3B::decode(&buf_b).unwrap();
+    assert_eq!(regs_b.cr3, 0x1234_5678);
+    assert_eq!(regs_b.eax, regs.eax);
+    assert_eq!(regs_b.cs_desc.base_address(), 0x10000);
+}
+
+#[test]
+fn test_short_buffers() {
+    assert!(RemoteCpuRegistersV1::decode(&[0; 27]).is_err());
+    assert!(RemoteCpuRegistersV2::decode(&[0; 101]).is_err());
+    assert!(RemoteCpuRegistersV3A::decode(&[0; 203]).is_err());
+    assert!(RemoteCpuRegistersV3B::decode(&[0; 207]).is_err());
+    assert!(RemoteCpuRegisters::try_from(&[0u8; 100][..]).is_err());
+}
+
+proptest! {
+    #[test]
+    fn roundtrip_v1(buf in prop::collection::vec(any::<u8>(), 28)) {
+        let regs = RemoteCpuRegistersV1::decode(&buf).unwrap();
+        prop_assert_eq!(regs.to_bytes(), buf);
+    }
+
+    #[test]
+    fn roundtrip_v2(buf in prop::collection::vec(any::<u8>(), 102)) {
+        let regs = RemoteCpuRegistersV2::decode(&buf).unwrap();
+        prop_assert_eq!(regs.to_bytes(), buf.clone());
+        let mut written = Vec::new();
+        regs.to_buffer(&mut written);
+        prop_assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn roundtrip_v3a(buf in prop::collection::vec(any::<u8>(), 204)) {
+        let regs = RemoteCpuRegistersV3A::decode(&buf).unwrap();
+        prop_assert_eq!(regs.to_bytes(), buf);
+    }
+
+    #[test]
+    fn roundtrip_v3b(buf in prop::collection::vec(any::<u8>(), 208)) {
+        let regs = RemoteCpuRegistersV3B::decode(&buf).unwrap();
+        prop_assert_eq!(regs.to_bytes(), buf);
+    }
+
+    #[test]
+    fn roundtrip_remote_registers(len in prop::sample::select(vec![28usize, 102, 204, 208]), seed in any::<u8>()) {
+        let buf: Vec<u8> = (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect();
+        let regs = RemoteCpuRegisters::try_from(buf.as_slice()).unwrap();
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        regs.write(&mut cursor).unwrap();
+        prop_assert_eq!(cursor.into_inner(), buf);
+    }
+}
@@ -0,0 +1,184 @@
+use arduinox86_client::*;
+
+#[test]
+fn test_registers16_v1() {
+    let mut regs = RemoteCpuRegistersV1 {
+        ax:    0x1234,
+        bx:    0,
+        cx:    0,
+        dx:    0,
+        ss:    0x2000,
+        ds:    0,
+        es:    0,
+        sp:    0xFFFE,
+        bp:    0,
+        si:    0,
+        di:    0,
+        cs:    0xF000,
+        ip:    0x0100,
+        flags: 0xF002,
+    };
+
+    assert_eq!(Registers16::ax(&regs), 0x1234);
+    assert_eq!(regs.cs_base(), 0xF0000);
+    assert_eq!(regs.code_address(), 0xF0100);
+    assert_eq!(regs.code_address(), regs.calculate_code_address());
+    assert_eq!(regs.stack_address(), 0x2FFFE);
+
+    regs.set_cx(0x5555);
+    *regs.dx_mut() = 0xAAAA;
+    assert_eq!(regs.cx, 0x5555);
+    assert_eq!(regs.dx, 0xAAAA);
+}
+
+#[test]
+fn test_registers16_v2_descriptors() {
+    let mut regs = RemoteCpuRegistersV2::default();
+    regs.set_cs(0x1000);
+    regs.set_ip(0x0010);
+    regs.set_ss(0x0000);
+    regs.set_sp(0x0100);
+
+    // Segment bases come from the descriptor cache, not the selector.
+    regs.cs_desc.set_base_address(0x123400);
+    assert_eq!(regs.cs_base(), 0x123400);
+    assert_eq!(regs.code_address(), 0x123410);
+    assert_eq!(regs.code_address(), regs.calculate_code_address());
+
+    regs.normalize_descriptors();
+    assert_eq!(regs.cs_base(), 0x10000);
+    assert_eq!(regs.stack_address(), 0x100);
+}
+
+#[test]
+fn test_descriptor_v1_fields() {
+    let mut desc = SegmentDescriptorV1::try_new(0x123456, 0x0FFF, SegmentDescriptorV1::ACCESS_DATA).unwrap();
+    assert_eq!(desc.base(), 0x123456);
+    assert_eq!(desc.limit(), 0x0FFF);
+    assert_eq!(desc.access_rights(), 0x93);
+    assert!(desc.is_present() && desc.is_data() && desc.is_writable());
+    assert!(!desc.is_code());
+
+    // The access rights land in byte 3 of the LOADALL cache entry.
+    assert_eq!(desc.into_bytes(), [0x56, 0x34, 0x12, 0x93, 0xFF, 0x0F]);
+
+    desc.set_access_rights(0x98); // Execute-only code
+    assert!(desc.is_code() && !desc.is_readable());
+
+    assert!(desc.set_base(0x1000000).is_err());
+    assert!(SegmentDescriptorV1::try_new(0x1000000, 0xFFFF, SegmentDescriptorV1::ACCESS_CODE).is_err());
+}
+
+#[test]
+fn test_registers_v2_protected_mode() {
+    let mut regs = RemoteCpuRegistersV2::default();
+    regs.set_cs(0x0008);
+    regs.normalize_descriptors();
+    regs.enter_protected_mode();
+    assert!(regs.protected_mode());
+    assert!(regs.descriptor(Segment::CS).is_code());
+    assert!(regs.descriptor(Segment::SS).is_writable());
+    assert_eq!(regs.cs_base(), 0x80);
+
+    let code = SegmentDescriptorV1::try_new(0x020000, 0xFFFF, SegmentDescriptorV1::ACCESS_CODE).unwrap();
+    let data = SegmentDescriptorV1::try_new(0x030000, 0x7FFF, SegmentDescriptorV1::ACCESS_DATA).unwrap();
+    regs.load_segment(Segment::CS, 0x0010, code).unwrap();
+    regs.load_segment(Segment::SS, 0x0018, data).unwrap();
+    assert_eq!(regs.cs, 0x0010);
+    assert_eq!(regs.cs_base(), 0x020000);
+    assert_eq!(regs.ss_desc.limit(), 0x7FFF);
+
+    // Descriptors the CPU would refuse are rejected.
+    assert!(regs.load_segment(Segment::CS, 0x0018, data).is_err());
+    assert!(regs.load_segment(Segment::SS, 0x0010, code).is_err());
+    let mut absent = data;
+    absent.set_p(0);
+    assert!(regs.load_segment(Segment::SS, 0x0018, absent).is_err());
+    // A null selector may hold anything.
+    regs.load_segment(Segment::DS, 0x0000, absent).unwrap();
+
+    let ldt = SegmentDescriptorV1::try_new(0x001000, 0x00FF, SegmentDescriptorV1::ACCESS_LDT).unwrap();
+    let tss = SegmentDescriptorV1::try_new(0x002000, 0x002B, SegmentDescriptorV1::ACCESS_TSS).unwrap();
+    regs.load_ldt(0x0020, ldt).unwrap();
+    regs.load_task_register(0x0028, tss).unwrap();
+    assert!(regs.load_ldt(0x0028, tss).is_err());
+    assert!(regs.load_task_register(0x0020, ldt).is_err());
+    regs.set_gdt(0x000800, 0x002F).unwrap();
+    assert!(regs.set_idt(0x1000000, 0x03FF).is_err());
+
+    // The edited state survives the LOADALL buffer round trip.
+    let decoded = RemoteCpuRegistersV2::decode(&regs.to_bytes()).unwrap();
+    assert_eq!(decoded.to_bytes(), regs.to_bytes());
+    assert_eq!(
+        decoded.descriptor(Segment::CS).access_rights(),
+        SegmentDescriptorV1::ACCESS_CODE
+    );
+    assert_eq!(decoded.gdt_desc.base(), 0x000800);
+}
+
+#[test]
+fn test_registers_v2_unreal_mode() {
+    let mut regs = RemoteCpuRegistersV2::default();
+    regs.set_cs(0xF000);
+    regs.set_ip(0x0100);
+    regs.normalize_descriptors();
+    regs.enter_protected_mode();
+    regs.enter_unreal_mode(0x300000);
+
+    assert!(!regs.protected_mode());
+    assert_eq!(regs.cs_base(), 0x3F0000);
+    assert_eq!(regs.code_address(), 0x3F0100);
+    assert_eq!(regs.descriptor(Segment::CS).limit(), 0xFFFF);
+
+    // Bases wrap at the top of the 24-bit address space.
+    regs.enter_unreal_mode(0xFF0000);
+    assert_eq!(regs.cs_base(), 0x0E0000);
+}
+
+#[test]
+fn test_registers32_v3() {
+    let mut regs = RemoteCpuRegistersV3A::default();
+    regs.set_cs(0x1000);
+    regs.set_eip(0x0020);
+    regs.set_ss(0x3000);
+    regs.set_esp(0x0200);
+    regs.normalize_descriptors();
+
+    assert_eq!(Registers32::cs_base(&regs), 0x10000);
+    assert_eq!(Registers32::code_address(&regs), 0x10020);
+    assert_eq!(Registers32::code_address(&regs), regs.calculate_code_address());
+    assert_eq!(Registers32::stack_address(&regs), 0x30200);
+
+    let v3 = RemoteCpuRegistersV3::A(regs);
+    assert_eq!(v3.cs_base(), 0x10000);
+    assert_eq!(Registers32::code_address(&v3), 0x10020);
+}
+
+#[test]
+fn test_randomize_keeps_register_set_type() {
+    use arduinox86_client::registers_common::RandomizeOpts;
+    use rand::SeedableRng;
+
+    let opts = RandomizeOpts {
+        randomize_flags: true,
+        clear_trap_flag: true,
+        randomize_general: true,
+        sp_max_value: 0xFFFF,
+        ..Default::default()
+    };
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1234);
+    let mut beta = rand_distr::Beta::new(0.65, 0.65).unwrap();
+
+    let mut regs = RemoteCpuRegisters::V1(RemoteCpuRegistersV1::default());
+    regs.randomize(&opts, &mut rng, &mut beta, &[]);
+    let RemoteCpuRegisters::V1(regs_v1) = &regs
+    else {
+        panic!("Expected a V1 register set");
+    };
+    assert_eq!(regs_v1.flags & RemoteCpuRegistersV1::FLAG_TRAP, 0);
+    assert_ne!(regs_v1.flags & RemoteCpuRegistersV1::FLAG_RESERVED1, 0);
+
+    let mut regs = RemoteCpuRegisters::V3(RemoteCpuRegistersV3::B(RemoteCpuRegistersV3B::default()));
+    regs.randomize(&opts, &mut rng, &mut beta, &[]);
+    assert!(matches!(regs, RemoteCpuRegisters::V3(RemoteCpuRegistersV3::B(_))));
+}
@@ -0,0 +1,60 @@
+use arduinox86_client::*;
+
+const PROGRAM: [u8; 3] = [0x8A, 0x04, 0xF4];
+
+fn key(address: u32, program: &[u8], registers: &[u8]) -> RunKey {
+    RunKey::builder()
+        .program(address, program)
+        .registers(registers)
+        .memory_strategy(MemoryStrategy::Zero)
+        .build()
+}
+
+#[test]
+fn test_run_key_is_deterministic() {
+    assert_eq!(key(0x100, &PROGRAM, &[1, 2, 3]), key(0x100, &PROGRAM, &[1, 2, 3]));
+}
+
+#[test]
+fn test_run_key_covers_every_input() {
+    let base = key(0x100, &PROGRAM, &[1, 2, 3]);
+    assert_ne!(base, key(0x101, &PROGRAM, &[1, 2, 3]));
+    assert_ne!(base, key(0x100, &PROGRAM[..2], &[1, 2, 3]));
+    assert_ne!(base, key(0x100, &PROGRAM, &[1, 2, 4]));
+
+    let random = RunKey::builder()
+        .program(0x100, &PROGRAM)
+        .registers(&[1, 2, 3])
+        .memory_strategy(MemoryStrategy::Random)
+        .build();
+    assert_ne!(base, random);
+}
+
+#[test]
+fn test_run_key_sections_do_not_run_together() {
+    // Moving a byte from the program to the registers must not give the same key.
+    let a = RunKey::builder().program(0, &[1, 2]).registers(&[3]).build();
+    let b = RunKey::builder().program(0, &[1]).registers(&[2, 3]).build();
+    assert_ne!(a, b);
+}
+
+#[test]
+fn test_run_key_round_trips_as_hex() {
+    let key = key(0x100, &PROGRAM, &[1, 2, 3]);
+    assert_eq!(key.to_string().parse::<RunKey>(), Ok(key));
+    assert!("not hex".parse::<RunKey>().is_err());
+}
+
+#[test]
+fn test_result_cache_counts_hits_and_misses() {
+    let mut cache = ResultCache::new();
+    let key = key(0x100, &PROGRAM, &[1, 2, 3]);
+
+    assert_eq!(cache.get(&key), None);
+    cache.insert(key, "passed");
+    assert_eq!(cache.get(&key), Some(&"passed"));
+
+    assert_eq!(cache.hits(), 1);
+    assert_eq!(cache.misses(), 1);
+    assert_eq!(cache.len(), 1);
+}
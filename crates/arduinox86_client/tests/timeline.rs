@@ -0,0 +1,107 @@
+use arduinox86_client::*;
+
+// 8088 bus status values (S0-S2).
+const CODE: u8 = 4;
+const MEMR: u8 = 5;
+const MEMW: u8 = 6;
+const PASV: u8 = 7;
+
+// Queue status, in bits 6-7 of the status byte.
+const Q_FIRST: u8 = 0b0100_0000;
+const Q_FLUSH: u8 = 0b1000_0000;
+
+fn cycle(ale: bool, address: u32, status: u8, command: u8, data: u16) -> ServerCycleState {
+    ServerCycleState {
+        program_state: ProgramState::Execute,
+        cpu_state_bits: 0,
+        cpu_status_bits: status,
+        bus_control_bits: ale as u8,
+        // Commands are active low.
+        bus_command_bits: !command,
+        address_bus: address,
+        data_bus: data,
+        pins: 0,
+    }
+}
+
+/// A four cycle 8088 bus cycle of type `status`.
+fn bus_cycle(address: u32, status: u8, data: u16) -> Vec<ServerCycleState> {
+    let command = match status {
+        MEMW => ServerCycleState::COMMAND_MWTC_BIT,
+        _ => ServerCycleState::COMMAND_MRDC_BIT,
+    };
+    vec![
+        cycle(true, address, status, 0, 0),
+        cycle(false, address, status, command, data),
+        cycle(false, address, PASV, command, data),
+        cycle(false, address, PASV, 0, data),
+    ]
+}
+
+#[test]
+fn test_decode_transfers() {
+    let mut cycles = bus_cycle(0x100, CODE, 0x90);
+    cycles.extend(bus_cycle(0x2000, MEMR, 0x12));
+    cycles.extend(bus_cycle(0x2001, MEMW, 0x34));
+
+    let transfers = decode_transfers(ServerCpuType::Intel8088, &cycles);
+    let summary: Vec<_> = transfers.iter().map(|t| (t.cycle, t.kind, t.address, t.data)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (2, TransferKind::CodeRead, 0x100, 0x90),
+            (6, TransferKind::MemRead, 0x2000, 0x12),
+            (10, TransferKind::MemWrite, 0x2001, 0x34),
+        ]
+    );
+}
+
+#[test]
+fn test_seek_memory() {
+    let mut cycles = bus_cycle(0x2000, MEMR, 0x12);
+    cycles.extend(bus_cycle(0x2000, MEMW, 0x56));
+    let timeline = Timeline::new(ServerCpuType::Intel8088, cycles);
+
+    // Nothing is known before the read completes.
+    assert!(timeline.seek(1).memory.is_empty());
+    assert_eq!(timeline.seek(2).memory.get(&0x2000), Some(&0x12));
+    // The write replaces the value read.
+    assert_eq!(timeline.seek(6).memory.get(&0x2000), Some(&0x56));
+    // Seeking backwards restores the earlier value.
+    assert_eq!(timeline.seek(3).memory.get(&0x2000), Some(&0x12));
+    assert_eq!(timeline.last_transfer(5).map(|t| t.kind), Some(TransferKind::MemRead));
+}
+
+#[test]
+fn test_seek_queue() {
+    let mut cycles = bus_cycle(0x100, CODE, 0xB0);
+    cycles.extend(bus_cycle(0x101, CODE, 0x01));
+    // Take the first byte, then flush.
+    cycles[7].cpu_status_bits |= Q_FIRST;
+    cycles.push(cycle(false, 0, PASV | Q_FLUSH, 0, 0));
+    let timeline = Timeline::new(ServerCpuType::Intel8088, cycles);
+
+    assert_eq!(timeline.seek(2).queue, Some(vec![0xB0]));
+    assert_eq!(timeline.seek(6).queue, Some(vec![0xB0, 0x01]));
+    assert_eq!(timeline.seek(7).queue, Some(vec![0x01]));
+    assert_eq!(timeline.seek(8).queue, Some(vec![]));
+
+    // No queue status on the 286.
+    assert_eq!(Timeline::new(ServerCpuType::Intel80286, Vec::new()).seek(0).queue, None);
+}
+
+#[test]
+fn test_seek_across_checkpoints() {
+    let mut cycles = Vec::new();
+    for i in 0..200u32 {
+        cycles.extend(bus_cycle(0x3000 + i, MEMW, i as u16));
+    }
+    let timeline = Timeline::new(ServerCpuType::Intel8088, cycles);
+
+    let state = timeline.seek(799);
+    assert_eq!(state.transfer_ct, 200);
+    assert_eq!(state.memory.len(), 200);
+    assert_eq!(state.memory.get(&(0x3000 + 150)), Some(&150));
+    assert_eq!(timeline.seek(10_000).cycle, 799);
+    assert_eq!(timeline.seek(600).transfer_ct, 150);
+}
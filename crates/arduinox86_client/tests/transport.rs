@@ -0,0 +1,92 @@
+use arduinox86_client::*;
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+/// Answer the version, capabilities and CPU type queries like a server with an 8086.
+fn mock_server(mut stream: impl Read + Write) {
+    let mut cmd = [0u8; 1];
+    while stream.read_exact(&mut cmd).is_ok() {
+        let reply: Vec<u8> = match cmd[0] {
+            c if c == ServerCommand::CmdVersion as u8 => [b"ardx86 ".as_slice(), &[REQUIRED_PROTOCOL_VER]].concat(),
+            c if c == ServerCommand::CmdGetCapabilities as u8 => vec![0xFF, 0x00, 0x00, 0x00, 0x01],
+            c if c == ServerCommand::CmdCpuType as u8 => vec![0x02, 0x01],
+            _ => vec![0x00],
+        };
+        if stream.write_all(&reply).is_err() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_transport_uri_parse() {
+    assert_eq!(
+        "tcp://192.168.1.50:5555".parse::<TransportUri>(),
+        Ok(TransportUri::Tcp("192.168.1.50:5555".to_string()))
+    );
+    assert_eq!(
+        "serial:COM3".parse::<TransportUri>(),
+        Ok(TransportUri::Serial("COM3".to_string()))
+    );
+    assert_eq!(
+        "/dev/ttyACM0".parse::<TransportUri>(),
+        Ok(TransportUri::Serial("/dev/ttyACM0".to_string()))
+    );
+    assert!("tcp://".parse::<TransportUri>().is_err());
+    assert!("serial:".parse::<TransportUri>().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_transport_uri_parse_unix() {
+    let expected = Ok(TransportUri::Unix("/tmp/ardx86.sock".into()));
+    assert_eq!("unix:/tmp/ardx86.sock".parse::<TransportUri>(), expected);
+    assert_eq!("unix:///tmp/ardx86.sock".parse::<TransportUri>(), expected);
+}
+
+#[test]
+fn test_tcp_transport() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || mock_server(listener.accept().unwrap().0));
+
+    let mut client = CpuClient::init(Some(format!("tcp://{addr}")), Some(1000)).unwrap();
+    assert!(matches!(client.cpu_type(), Ok((ServerCpuType::Intel8086, false))));
+
+    drop(client);
+    server.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_transport() {
+    use std::os::unix::net::UnixListener;
+
+    let path = std::env::temp_dir().join(format!("ardx86_test_{}.sock", std::process::id()));
+    _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let server = thread::spawn(move || mock_server(listener.accept().unwrap().0));
+
+    let uri = TransportUri::Unix(path.clone());
+    let mut client = CpuClient::connect(&uri, &DiscoveryOptions::default()).unwrap();
+    assert!(matches!(client.cpu_type(), Ok((ServerCpuType::Intel8086, false))));
+
+    drop(client);
+    server.join().unwrap();
+    _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_tcp_transport_no_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // Accept and hang up without answering the version query.
+    let server = thread::spawn(move || drop(listener.accept().unwrap()));
+
+    let result = CpuClient::init(Some(format!("tcp://{addr}")), Some(200));
+    assert!(matches!(result, Err(CpuClientError::DiscoveryError)));
+    server.join().unwrap();
+}
@@ -0,0 +1,185 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! The address-space map shared by the host memory model, the test generator and the GUI.
+//!
+//! An [AddressMap] lists the regions of the address space that are not ordinary RAM, such as a
+//! BIOS ROM or a video buffer, so every tool treats them the same way:
+//!
+//! ```toml
+//! [[region]]
+//! kind = "rom"
+//! start = 0xF0000
+//! end = 0xFFFFF
+//! name = "BIOS"
+//! ```
+//!
+//! Region bounds are inclusive. Where regions overlap, the one listed last wins. Addresses outside
+//! every region are of the `default` kind, which is `ram` unless set.
+//!
+//! The map is read from `--address-map`, then `ARDX86_ADDRESS_MAP`, then `./address_map.toml`.
+//! Without one, the whole address space is RAM.
+
+use std::{env, fmt, fs, ops::RangeInclusive, path::Path, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::ConfigError;
+
+/// Name of the address map file looked for in the working directory.
+pub const ADDRESS_MAP_FILE: &str = "address_map.toml";
+/// Environment variable naming the address map file.
+pub const ADDRESS_MAP_ENV: &str = "ARDX86_ADDRESS_MAP";
+
+/// What occupies a [Region] of the address space.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionKind {
+    /// Ordinary memory. Test code is only placed in RAM.
+    #[default]
+    Ram,
+    /// Read-only memory. Writes are discarded.
+    Rom,
+    /// Device registers. Accesses are reported, as the host does not emulate the device.
+    Mmio,
+    /// Memory that should never be accessed. Reads see an open bus and writes are discarded.
+    Excluded,
+    /// Memory a program may write freely, such as a stack or data area. Writes here are never out
+    /// of bounds.
+    Scratch,
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionKind::Ram => write!(f, "ram"),
+            RegionKind::Rom => write!(f, "rom"),
+            RegionKind::Mmio => write!(f, "mmio"),
+            RegionKind::Excluded => write!(f, "excluded"),
+            RegionKind::Scratch => write!(f, "scratch"),
+        }
+    }
+}
+
+impl RegionKind {
+    /// Return true if writes to this kind of region reach memory.
+    pub fn is_writable(&self) -> bool {
+        matches!(self, RegionKind::Ram | RegionKind::Mmio | RegionKind::Scratch)
+    }
+}
+
+/// A range of physical addresses, `start` to `end` inclusive.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Region {
+    pub kind:  RegionKind,
+    pub start: u32,
+    pub end:   u32,
+    /// Free-form label shown by the GUI.
+    #[serde(default)]
+    pub name:  String,
+}
+
+impl Region {
+    pub fn contains(&self, address: u32) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+
+    pub fn range(&self) -> RangeInclusive<u32> {
+        self.start..=self.end
+    }
+}
+
+/// [AddressMap] is the contents of `address_map.toml`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AddressMap {
+    /// Kind of the addresses outside every region.
+    pub default: RegionKind,
+    #[serde(rename = "region")]
+    pub regions: Vec<Region>,
+}
+
+impl FromStr for AddressMap {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, "address map")
+    }
+}
+
+impl AddressMap {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.display().to_string(), e))?;
+        Self::parse(&text, &path.display().to_string())
+    }
+
+    /// Find and load the address map: `path` if given, then the file named by
+    /// `ARDX86_ADDRESS_MAP`, then `address_map.toml` in the working directory. A file that was
+    /// named explicitly must exist; otherwise an all-RAM map is returned when none is found.
+    pub fn find(path: Option<&Path>) -> Result<Self, ConfigError> {
+        if let Some(path) = path {
+            return Self::load(path);
+        }
+        if let Ok(path) = env::var(ADDRESS_MAP_ENV) {
+            return Self::load(Path::new(&path));
+        }
+        let path = Path::new(ADDRESS_MAP_FILE);
+        if path.exists() {
+            log::debug!("Using address map {}", path.display());
+            return Self::load(path);
+        }
+        Ok(Self::default())
+    }
+
+    fn parse(text: &str, source: &str) -> Result<Self, ConfigError> {
+        let map: AddressMap = toml::from_str(text).map_err(|e| ConfigError::Parse(source.to_string(), e))?;
+        if let Some(region) = map.regions.iter().find(|region| region.start > region.end) {
+            return Err(ConfigError::BadValue(
+                "region",
+                format!("{} region '{}' ends before it starts", region.kind, region.name),
+            ));
+        }
+        Ok(map)
+    }
+
+    /// Return the region containing `address`, if any.
+    pub fn region_at(&self, address: u32) -> Option<&Region> {
+        self.regions.iter().rev().find(|region| region.contains(address))
+    }
+
+    /// Return the kind of memory at `address`.
+    pub fn kind_at(&self, address: u32) -> RegionKind {
+        self.region_at(address).map_or(self.default, |region| region.kind)
+    }
+
+    /// Return the smallest range covering every region of `kind`, or None if the map has no such
+    /// region.
+    pub fn span(&self, kind: RegionKind) -> Option<RangeInclusive<u32>> {
+        let mut regions = self.regions.iter().filter(|region| region.kind == kind);
+        let first = regions.next()?;
+        let (start, end) = regions.fold((first.start, first.end), |(start, end), region| {
+            (start.min(region.start), end.max(region.end))
+        });
+        Some(start..=end)
+    }
+}
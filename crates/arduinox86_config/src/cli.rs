@@ -0,0 +1,165 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! The command-line layer of the connection settings, shared by every tool that talks to a server.
+
+use std::path::PathBuf;
+
+use arduinox86_client::{BusMode, CpuClient, CpuClientPool, UsbId};
+
+use crate::{resolve, AddressMap, ConfigError, ConnectionSettings, DevicesFile};
+
+/// Options for finding and opening a connection to an `ArduinoX86` server. The flags are
+/// global, so they may be given before or after a subcommand. Any flag given overrides the
+/// devices file and the environment.
+#[derive(clap::Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct ConnectionArgs {
+    /// Device to use, by its label in the devices file
+    #[arg(long, global = true)]
+    pub device: Option<String>,
+
+    /// Path to the devices file. Defaults to $ARDX86_DEVICES, then ./devices.toml
+    #[arg(long, value_name = "FILE", global = true)]
+    pub devices: Option<PathBuf>,
+
+    /// Path to the address map. Defaults to $ARDX86_ADDRESS_MAP, then ./address_map.toml
+    #[arg(long, value_name = "FILE", global = true)]
+    pub address_map: Option<PathBuf>,
+
+    /// Serial port of the server, or a transport URI such as tcp://host:port. Every port is probed
+    /// if not given
+    #[arg(long, global = true)]
+    pub com_port: Option<String>,
+
+    /// Baud rate to open the port with. Not needed for the Due's native USB port
+    #[arg(long, global = true)]
+    pub baud: Option<u32>,
+
+    /// Serial read timeout in milliseconds. Each tool has its own default
+    #[arg(long, global = true)]
+    pub timeout: Option<u64>,
+
+    /// Milliseconds to wait after opening the port before querying the server. Use this for
+    /// boards that reset when the port is opened
+    #[arg(long, global = true)]
+    pub settle_delay: Option<u64>,
+
+    /// Number of times to send the version query before giving up on a port
+    #[arg(long, global = true)]
+    pub banner_attempts: Option<u32>,
+
+    /// Number of times to retry discovery when no server is found
+    #[arg(long, global = true)]
+    pub retries: Option<u32>,
+
+    /// Number of times to reconnect and resend a command that fails with a read error, such as
+    /// after a USB hiccup. Reconnecting is off by default
+    #[arg(long, global = true)]
+    pub command_retries: Option<u32>,
+
+    /// Number of times to try reopening the port on each reconnect
+    #[arg(long, global = true)]
+    pub reopen_attempts: Option<u32>,
+
+    /// Milliseconds to wait before each attempt to reopen the port, giving a USB device time to
+    /// enumerate again
+    #[arg(long, global = true)]
+    pub reopen_delay: Option<u64>,
+
+    /// Microseconds to wait before sending each command. Use this for half-duplex links such as
+    /// RS-485, where the server's transceiver needs time to release the line
+    #[arg(long, global = true)]
+    pub turnaround_delay: Option<u64>,
+
+    /// Use XON/XOFF software flow control, for adapters that handle it in the link
+    #[arg(long, global = true)]
+    pub software_flow_control: bool,
+
+    /// Milliseconds added to the read timeout, for long cables or networked serial links
+    #[arg(long, global = true)]
+    pub timeout_margin: Option<u64>,
+
+    /// How the CPU's bus is wired: maximum (through an 8288) or minimum, where the 8288 command
+    /// bits are synthesized from the RD, WR and IO/M pins
+    #[arg(long, global = true)]
+    pub bus_mode: Option<BusMode>,
+
+    /// Only probe ports with these names when no com port is given. A trailing '*' matches by
+    /// prefix, e.g. /dev/ttyACM*
+    #[arg(long = "port-allow", global = true)]
+    pub port_allow: Vec<String>,
+
+    /// Never probe ports with these names
+    #[arg(long = "port-deny", global = true)]
+    pub port_deny: Vec<String>,
+
+    /// Only probe USB ports with these VID:PID pairs (hex), e.g. 2341:0043 or 2341:*
+    #[arg(long = "usb-id", global = true)]
+    pub usb_id: Vec<UsbId>,
+}
+
+impl ConnectionArgs {
+    /// The settings given on the command line alone.
+    pub fn overrides(&self) -> ConnectionSettings {
+        ConnectionSettings {
+            port: self.com_port.clone(),
+            baud: self.baud,
+            timeout: self.timeout,
+            settle_delay: self.settle_delay,
+            banner_attempts: self.banner_attempts,
+            retries: self.retries,
+            command_retries: self.command_retries,
+            reopen_attempts: self.reopen_attempts,
+            reopen_delay: self.reopen_delay,
+            turnaround_delay: self.turnaround_delay,
+            software_flow_control: self.software_flow_control.then_some(true),
+            timeout_margin: self.timeout_margin,
+            bus_mode: self.bus_mode,
+            allow: self.port_allow.clone(),
+            deny: self.port_deny.clone(),
+            usb_ids: self.usb_id.clone(),
+        }
+    }
+
+    /// Resolve the settings from the devices file, the environment and the flags.
+    pub fn settings(&self) -> Result<ConnectionSettings, ConfigError> {
+        let file = DevicesFile::find(self.devices.as_deref())?;
+        resolve(&file, self.device.as_deref(), &self.overrides())
+    }
+
+    /// Load the address map named on the command line, or the default one.
+    pub fn address_map(&self) -> Result<AddressMap, ConfigError> {
+        AddressMap::find(self.address_map.as_deref())
+    }
+
+    /// Open a connection to the server, using `default_timeout` if no layer sets a timeout.
+    pub fn connect(&self, default_timeout: u64) -> Result<CpuClient, ConfigError> {
+        self.settings()?.connect(default_timeout)
+    }
+
+    /// Find every server to spread work over, using `default_timeout` if no layer sets a timeout.
+    pub fn pool(&self, default_timeout: u64) -> Result<CpuClientPool, ConfigError> {
+        self.settings()?.pool(default_timeout)
+    }
+}
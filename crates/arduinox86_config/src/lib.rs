@@ -0,0 +1,390 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Connection and device settings shared by every ArduinoX86 frontend.
+//!
+//! Settings are layered, and each layer only overrides the values it sets:
+//!
+//! 1. The `[defaults]` table of `devices.toml`.
+//! 2. The `[[device]]` entry selected by label, with `--device` or `ARDX86_DEVICE`.
+//! 3. `ARDX86_*` environment variables.
+//! 4. Command-line flags, see [ConnectionArgs].
+//!
+//! The devices file is read from `--devices`, then `ARDX86_DEVICES`, then `./devices.toml`. It is
+//! optional; without one, each tool falls back to its own defaults.
+//!
+//! The [AddressMap], which describes the RAM, ROM and device regions of the address space, is
+//! found the same way from `--address-map`.
+
+mod address_map;
+mod cli;
+
+use std::{env, fmt::Display, fs, path::Path, str::FromStr};
+
+use arduinox86_client::{
+    BusMode,
+    CpuClient,
+    CpuClientError,
+    CpuClientPool,
+    DiscoveryOptions,
+    PortFilter,
+    RetryPolicy,
+    TransportUri,
+    UsbId,
+};
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+pub use address_map::{AddressMap, Region, RegionKind, ADDRESS_MAP_ENV, ADDRESS_MAP_FILE};
+pub use cli::ConnectionArgs;
+
+/// Name of the devices file looked for in the working directory.
+pub const DEVICES_FILE: &str = "devices.toml";
+/// Environment variable naming the devices file.
+pub const DEVICES_ENV: &str = "ARDX86_DEVICES";
+/// Environment variable selecting a device by label.
+pub const DEVICE_ENV: &str = "ARDX86_DEVICE";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    Parse(String, toml::de::Error),
+    #[error("No device labeled '{0}' in the devices file")]
+    UnknownDevice(String),
+    #[error("Bad value for {0}: {1}")]
+    BadValue(&'static str, String),
+    #[error(transparent)]
+    Client(#[from] CpuClientError),
+}
+
+/// [ConnectionSettings] describes how to find and open a connection to a server. Every field is
+/// optional so settings can be layered; a value left unset falls through to the layer below.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    /// Serial port or transport URI of the server. Every serial port is probed if not set.
+    pub port: Option<String>,
+    /// Baud rate to open the port with.
+    pub baud: Option<u32>,
+    /// Serial read timeout in milliseconds.
+    pub timeout: Option<u64>,
+    /// Milliseconds to wait after opening a port before querying it.
+    pub settle_delay: Option<u64>,
+    /// Number of times to send the version query before giving up on a port.
+    pub banner_attempts: Option<u32>,
+    /// Number of times to retry discovery when no server is found.
+    pub retries: Option<u32>,
+    /// Number of times to reconnect and resend a command that fails with a read error.
+    pub command_retries: Option<u32>,
+    /// Number of times to try reopening the port on each reconnect.
+    pub reopen_attempts: Option<u32>,
+    /// Milliseconds to wait before each attempt to reopen the port.
+    pub reopen_delay: Option<u64>,
+    /// Microseconds to wait before sending each command, for half-duplex links.
+    pub turnaround_delay: Option<u64>,
+    /// Use XON/XOFF software flow control.
+    pub software_flow_control: Option<bool>,
+    /// Milliseconds added to the read timeout, for links with extra latency.
+    pub timeout_margin: Option<u64>,
+    /// How the CPU's bus is wired, `maximum` or `minimum`.
+    #[serde(deserialize_with = "deserialize_bus_mode")]
+    pub bus_mode: Option<BusMode>,
+    /// Port names to probe. A trailing '*' matches by prefix.
+    pub allow: Vec<String>,
+    /// Port names never to probe.
+    pub deny: Vec<String>,
+    /// USB VID:PID pairs to probe.
+    #[serde(deserialize_with = "deserialize_usb_ids")]
+    pub usb_ids: Vec<UsbId>,
+}
+
+impl ConnectionSettings {
+    /// Override the values in `self` with those set in `other`.
+    pub fn merge(&mut self, other: &ConnectionSettings) {
+        fn pick<T: Clone>(base: &mut Option<T>, over: &Option<T>) {
+            if over.is_some() {
+                base.clone_from(over);
+            }
+        }
+        fn pick_list<T: Clone>(base: &mut Vec<T>, over: &[T]) {
+            if !over.is_empty() {
+                *base = over.to_vec();
+            }
+        }
+
+        pick(&mut self.port, &other.port);
+        pick(&mut self.baud, &other.baud);
+        pick(&mut self.timeout, &other.timeout);
+        pick(&mut self.settle_delay, &other.settle_delay);
+        pick(&mut self.banner_attempts, &other.banner_attempts);
+        pick(&mut self.retries, &other.retries);
+        pick(&mut self.command_retries, &other.command_retries);
+        pick(&mut self.reopen_attempts, &other.reopen_attempts);
+        pick(&mut self.reopen_delay, &other.reopen_delay);
+        pick(&mut self.turnaround_delay, &other.turnaround_delay);
+        pick(&mut self.software_flow_control, &other.software_flow_control);
+        pick(&mut self.timeout_margin, &other.timeout_margin);
+        pick(&mut self.bus_mode, &other.bus_mode);
+        pick_list(&mut self.allow, &other.allow);
+        pick_list(&mut self.deny, &other.deny);
+        pick_list(&mut self.usb_ids, &other.usb_ids);
+    }
+
+    /// Read settings from the `ARDX86_*` environment variables. List values are comma separated.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(ConnectionSettings {
+            port: env::var("ARDX86_PORT").ok(),
+            baud: env_value("ARDX86_BAUD")?,
+            timeout: env_value("ARDX86_TIMEOUT")?,
+            settle_delay: env_value("ARDX86_SETTLE_DELAY")?,
+            banner_attempts: env_value("ARDX86_BANNER_ATTEMPTS")?,
+            retries: env_value("ARDX86_RETRIES")?,
+            command_retries: env_value("ARDX86_COMMAND_RETRIES")?,
+            reopen_attempts: env_value("ARDX86_REOPEN_ATTEMPTS")?,
+            reopen_delay: env_value("ARDX86_REOPEN_DELAY")?,
+            turnaround_delay: env_value("ARDX86_TURNAROUND_DELAY")?,
+            software_flow_control: env_value("ARDX86_SOFTWARE_FLOW_CONTROL")?,
+            timeout_margin: env_value("ARDX86_TIMEOUT_MARGIN")?,
+            bus_mode: env_value("ARDX86_BUS_MODE")?,
+            allow: env_list("ARDX86_PORT_ALLOW"),
+            deny: env_list("ARDX86_PORT_DENY"),
+            usb_ids: env_list("ARDX86_USB_IDS")
+                .iter()
+                .map(|id| id.parse().map_err(|e| ConfigError::BadValue("ARDX86_USB_IDS", e)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn port_filter(&self) -> PortFilter {
+        PortFilter {
+            allow:   self.allow.clone(),
+            deny:    self.deny.clone(),
+            usb_ids: self.usb_ids.clone(),
+        }
+    }
+
+    /// Build discovery options, using `default_timeout` if no timeout is set.
+    pub fn discovery_options(&self, default_timeout: u64) -> DiscoveryOptions {
+        let defaults = DiscoveryOptions::default();
+        DiscoveryOptions {
+            timeout: self.timeout.unwrap_or(default_timeout),
+            baud_rate: self.baud.unwrap_or(defaults.baud_rate),
+            settle_delay: self.settle_delay.unwrap_or(defaults.settle_delay),
+            banner_attempts: self.banner_attempts.unwrap_or(defaults.banner_attempts),
+            filter: self.port_filter(),
+            turnaround_delay: self.turnaround_delay.unwrap_or(defaults.turnaround_delay),
+            software_flow_control: self.software_flow_control.unwrap_or(defaults.software_flow_control),
+            timeout_margin: self.timeout_margin.unwrap_or(defaults.timeout_margin),
+            bus_mode: self.bus_mode.unwrap_or(defaults.bus_mode),
+            retry: RetryPolicy {
+                retries: self.command_retries.unwrap_or(defaults.retry.retries),
+                reopen_attempts: self.reopen_attempts.unwrap_or(defaults.retry.reopen_attempts),
+                reopen_delay: self.reopen_delay.unwrap_or(defaults.retry.reopen_delay),
+            },
+        }
+    }
+
+    /// Open a connection to the server, retrying discovery up to `retries` times.
+    pub fn connect(&self, default_timeout: u64) -> Result<CpuClient, ConfigError> {
+        let options = self.discovery_options(default_timeout);
+        let retries = self.retries.unwrap_or(0);
+        let mut attempt = 0;
+        loop {
+            match CpuClient::init_with(self.port.clone(), &options) {
+                Ok(client) => return Ok(client),
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    log::warn!("Connection failed: {} Retrying ({}/{})...", e, attempt, retries);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Find every server to spread work over. The port may list several ports or transport URIs,
+    /// separated by commas; otherwise every serial port that passes the filter is probed.
+    pub fn pool(&self, default_timeout: u64) -> Result<CpuClientPool, ConfigError> {
+        let options = self.discovery_options(default_timeout);
+        match &self.port {
+            Some(ports) => {
+                let uris = ports
+                    .split(',')
+                    .map(|port| port.trim().parse::<TransportUri>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| ConfigError::BadValue("port", e))?;
+                Ok(CpuClientPool::from_uris(uris, &options))
+            }
+            None => Ok(CpuClientPool::discover(&options)?),
+        }
+    }
+}
+
+/// A bench device described in the devices file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Device {
+    /// Name used to select the device, e.g. with `--device`.
+    pub label: String,
+    /// Free-form notes, such as the CPU fitted to the board.
+    #[serde(default)]
+    pub description: String,
+    #[serde(flatten)]
+    pub settings: ConnectionSettings,
+}
+
+/// [DevicesFile] is the contents of `devices.toml`, which describes the lab setup once for every
+/// tool.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DevicesFile {
+    /// Settings applied to every device.
+    pub defaults: ConnectionSettings,
+    #[serde(rename = "device")]
+    pub devices:  Vec<Device>,
+}
+
+impl FromStr for DevicesFile {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+impl DevicesFile {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.display().to_string(), e))?;
+        text.parse()
+            .map_err(|e| ConfigError::Parse(path.display().to_string(), e))
+    }
+
+    /// Find and load the devices file: `path` if given, then the file named by `ARDX86_DEVICES`,
+    /// then `devices.toml` in the working directory. A file that was named explicitly must exist;
+    /// otherwise an empty file is returned when none is found.
+    pub fn find(path: Option<&Path>) -> Result<Self, ConfigError> {
+        if let Some(path) = path {
+            return Self::load(path);
+        }
+        if let Ok(path) = env::var(DEVICES_ENV) {
+            return Self::load(Path::new(&path));
+        }
+        let path = Path::new(DEVICES_FILE);
+        if path.exists() {
+            log::debug!("Using devices file {}", path.display());
+            return Self::load(path);
+        }
+        Ok(Self::default())
+    }
+
+    pub fn device(&self, label: &str) -> Option<&Device> {
+        self.devices.iter().find(|d| d.label == label)
+    }
+
+    /// Return the label of the device configured on `port`, if any.
+    pub fn label_for_port(&self, port: &str) -> Option<&str> {
+        self.devices
+            .iter()
+            .find(|d| d.settings.port.as_deref() == Some(port))
+            .map(|d| d.label.as_str())
+    }
+
+    /// Return the file's settings for the device labeled `label`, or just the defaults if no
+    /// label is given.
+    pub fn settings(&self, label: Option<&str>) -> Result<ConnectionSettings, ConfigError> {
+        let mut settings = self.defaults.clone();
+        if let Some(label) = label {
+            let device = self
+                .device(label)
+                .ok_or_else(|| ConfigError::UnknownDevice(label.to_string()))?;
+            settings.merge(&device.settings);
+        }
+        Ok(settings)
+    }
+}
+
+/// Resolve the full set of layered settings: the devices file, then the environment, then
+/// `overrides`. The device is selected by `label`, or by `ARDX86_DEVICE` if no label is given.
+pub fn resolve(
+    file: &DevicesFile,
+    label: Option<&str>,
+    overrides: &ConnectionSettings,
+) -> Result<ConnectionSettings, ConfigError> {
+    let env_label = env::var(DEVICE_ENV).ok();
+    let label = label.or(env_label.as_deref());
+    if let Some(label) = label {
+        log::debug!("Using settings for device '{}'", label);
+    }
+
+    let mut settings = file.settings(label)?;
+    settings.merge(&ConnectionSettings::from_env()?);
+    settings.merge(overrides);
+    Ok(settings)
+}
+
+fn env_value<T>(name: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| ConfigError::BadValue(name, format!("'{}': {}", value, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn deserialize_bus_mode<'de, D>(deserializer: D) -> Result<Option<BusMode>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|mode| mode.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_usb_ids<'de, D>(deserializer: D) -> Result<Vec<UsbId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|id| id.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
@@ -0,0 +1,82 @@
+use arduinox86_config::*;
+
+const ADDRESS_MAP: &str = r#"
+[[region]]
+kind = "scratch"
+start = 0x00400
+end = 0x07FFF
+name = "Stack"
+
+[[region]]
+kind = "mmio"
+start = 0xB8000
+end = 0xBFFFF
+name = "CGA"
+
+[[region]]
+kind = "rom"
+start = 0xF0000
+end = 0xFFFFF
+name = "BIOS"
+
+[[region]]
+kind = "excluded"
+start = 0xFFFF0
+end = 0xFFFFF
+"#;
+
+#[test]
+fn test_region_kinds() {
+    let map: AddressMap = ADDRESS_MAP.parse().unwrap();
+    assert_eq!(map.regions.len(), 4);
+
+    assert_eq!(map.kind_at(0x00000), RegionKind::Ram);
+    assert_eq!(map.kind_at(0x00400), RegionKind::Scratch);
+    assert_eq!(map.kind_at(0x07FFF), RegionKind::Scratch);
+    assert_eq!(map.kind_at(0x08000), RegionKind::Ram);
+    assert_eq!(map.kind_at(0xB8000), RegionKind::Mmio);
+    assert_eq!(map.kind_at(0xF0000), RegionKind::Rom);
+    assert_eq!(map.region_at(0xF0000).unwrap().name, "BIOS");
+
+    // The region listed last wins.
+    assert_eq!(map.kind_at(0xFFFF0), RegionKind::Excluded);
+
+    assert_eq!(map.span(RegionKind::Rom), Some(0xF0000..=0xFFFFF));
+    assert_eq!(map.span(RegionKind::Ram), None);
+}
+
+#[test]
+fn test_default_kind() {
+    assert_eq!(AddressMap::default().kind_at(0xFFFFF), RegionKind::Ram);
+
+    let map: AddressMap = r#"
+default = "excluded"
+
+[[region]]
+kind = "ram"
+start = 0x00000
+end = 0x9FFFF
+
+[[region]]
+kind = "ram"
+start = 0x100000
+end = 0x1FFFFF
+"#
+    .parse()
+    .unwrap();
+    assert_eq!(map.kind_at(0x9FFFF), RegionKind::Ram);
+    assert_eq!(map.kind_at(0xA0000), RegionKind::Excluded);
+    assert_eq!(map.span(RegionKind::Ram), Some(0x00000..=0x1FFFFF));
+}
+
+#[test]
+fn test_bad_region() {
+    let result = r#"
+[[region]]
+kind = "rom"
+start = 0xFFFFF
+end = 0xF0000
+"#
+    .parse::<AddressMap>();
+    assert!(matches!(result, Err(ConfigError::BadValue(..))));
+}
@@ -0,0 +1,137 @@
+use arduinox86_client::{BusMode, TransportUri, UsbId};
+use arduinox86_config::*;
+
+const DEVICES: &str = r#"
+[defaults]
+timeout = 2000
+retries = 2
+usb_ids = ["2341:*"]
+
+[[device]]
+label = "bench-8088"
+description = "Shield v1.1 with an Intel 8088"
+port = "/dev/ttyACM0"
+settle_delay = 1500
+
+[[device]]
+label = "bench-386"
+port = "/dev/ttyACM1"
+timeout = 5000
+usb_ids = ["2341:003e"]
+
+[[device]]
+label = "rs485-v20"
+description = "V20 shield on a 30m RS-485 link"
+port = "/dev/ttyUSB0"
+baud = 115200
+turnaround_delay = 500
+software_flow_control = true
+timeout_margin = 250
+bus_mode = "minimum"
+"#;
+
+#[test]
+fn test_device_settings() {
+    let file: DevicesFile = DEVICES.parse().unwrap();
+    assert_eq!(file.devices.len(), 3);
+
+    let settings = file.settings(Some("bench-8088")).unwrap();
+    assert_eq!(settings.port.as_deref(), Some("/dev/ttyACM0"));
+    assert_eq!(settings.timeout, Some(2000));
+    assert_eq!(settings.settle_delay, Some(1500));
+    assert_eq!(settings.retries, Some(2));
+    assert_eq!(settings.usb_ids, vec![UsbId { vid: 0x2341, pid: None }]);
+
+    // Device values override the defaults, lists included.
+    let settings = file.settings(Some("bench-386")).unwrap();
+    assert_eq!(settings.timeout, Some(5000));
+    assert_eq!(
+        settings.usb_ids,
+        vec![UsbId {
+            vid: 0x2341,
+            pid: Some(0x003E),
+        }]
+    );
+
+    assert_eq!(file.settings(None).unwrap(), file.defaults);
+    assert!(matches!(
+        file.settings(Some("bench-v30")),
+        Err(ConfigError::UnknownDevice(_))
+    ));
+    assert_eq!(file.label_for_port("/dev/ttyACM1"), Some("bench-386"));
+    assert_eq!(file.label_for_port("/dev/ttyS0"), None);
+}
+
+#[test]
+fn test_merge_only_overrides_set_values() {
+    let file: DevicesFile = DEVICES.parse().unwrap();
+    let mut settings = file.settings(Some("bench-8088")).unwrap();
+    settings.merge(&ConnectionSettings {
+        timeout: Some(100),
+        deny: vec!["/dev/ttyACM1".to_string()],
+        ..Default::default()
+    });
+
+    assert_eq!(settings.port.as_deref(), Some("/dev/ttyACM0"));
+    assert_eq!(settings.timeout, Some(100));
+    assert_eq!(settings.deny, vec!["/dev/ttyACM1".to_string()]);
+
+    let options = settings.discovery_options(1000);
+    assert_eq!(options.timeout, 100);
+    assert_eq!(options.settle_delay, 1500);
+    assert_eq!(options.banner_attempts, 1);
+    assert_eq!(ConnectionSettings::default().discovery_options(1000).timeout, 1000);
+}
+
+#[test]
+fn test_link_settings() {
+    let file: DevicesFile = DEVICES.parse().unwrap();
+    let options = file.settings(Some("rs485-v20")).unwrap().discovery_options(1000);
+    assert_eq!(options.baud_rate, 115200);
+    assert_eq!(options.timeout, 2000);
+    assert_eq!(options.turnaround_delay, 500);
+    assert!(options.software_flow_control);
+    assert_eq!(options.timeout_margin, 250);
+    assert_eq!(options.bus_mode, BusMode::Minimum);
+
+    // Links without these settings behave as a direct USB connection.
+    let options = file.settings(Some("bench-8088")).unwrap().discovery_options(1000);
+    assert_eq!(options.turnaround_delay, 0);
+    assert!(!options.software_flow_control);
+    assert_eq!(options.timeout_margin, 0);
+    assert_eq!(options.bus_mode, BusMode::Maximum);
+}
+
+#[test]
+fn test_bad_bus_mode() {
+    assert!("[defaults]\nbus_mode = \"medium\"".parse::<DevicesFile>().is_err());
+}
+
+#[test]
+fn test_bad_usb_id() {
+    assert!("[defaults]\nusb_ids = [\"arduino\"]".parse::<DevicesFile>().is_err());
+}
+
+#[test]
+fn test_pool_ports() {
+    let settings = ConnectionSettings {
+        port: Some("/dev/ttyACM0, tcp://bench:5000,serial:COM4".to_string()),
+        ..Default::default()
+    };
+    let pool = settings.pool(1000).unwrap();
+    assert_eq!(
+        pool.uris(),
+        [
+            TransportUri::Serial("/dev/ttyACM0".to_string()),
+            TransportUri::Tcp("bench:5000".to_string()),
+            TransportUri::Serial("COM4".to_string()),
+        ]
+    );
+    assert!(pool.connect(3).is_err());
+
+    let settings = ConnectionSettings {
+        port: Some("/dev/ttyACM0,".to_string()),
+        ..Default::default()
+    };
+    assert!(settings.pool(1000).is_err());
+}
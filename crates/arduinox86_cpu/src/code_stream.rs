@@ -23,11 +23,12 @@
 
 use std::collections::VecDeque;
 
-use crate::{opcodes::OPCODE_NOP, queue::QueueDataType};
+use crate::queue::QueueDataType;
 use arduinox86_client::CpuWidth;
 
 pub struct CodeStream {
     width: CpuWidth,
+    fill:  u8,
     bytes: VecDeque<(u8, QueueDataType)>,
 }
 
@@ -43,16 +44,31 @@ impl CodeStreamValue {
             CodeStreamValue::Word(val, _, _) => *val,
         }
     }
+
+    /// Return whether the low and high bytes of the value are fill rather than program bytes.
+    pub fn fill_bytes(&self) -> [bool; 2] {
+        match self {
+            CodeStreamValue::Byte(_, dtype) => [*dtype == QueueDataType::Fill, false],
+            CodeStreamValue::Word(_, dtype0, dtype1) => {
+                [*dtype0 == QueueDataType::Fill, *dtype1 == QueueDataType::Fill]
+            }
+        }
+    }
 }
 
 impl CodeStream {
-    pub fn new(width: CpuWidth) -> Self {
+    pub fn new(width: CpuWidth, fill: u8) -> Self {
         Self {
             width,
+            fill,
             bytes: Default::default(),
         }
     }
 
+    pub fn set_fill(&mut self, fill: u8) {
+        self.fill = fill;
+    }
+
     pub fn push_byte(&mut self, data: u8, data_type: QueueDataType) {
         self.bytes.push_back((data, data_type))
     }
@@ -79,18 +95,18 @@ impl CodeStream {
     }
 
     /// Pop a value of the appropriate width from the code stream deque and return it, along with
-    /// a tuple of data types. Overflows are filled with NOPs set to type Fill.
+    /// a tuple of data types. Overflows are filled with the fill byte set to type Fill.
     pub fn pop_data_bus(&mut self) -> CodeStreamValue {
         match self.width {
             CpuWidth::Eight => {
-                let byte0_val = self.bytes.pop_front().unwrap_or((OPCODE_NOP, QueueDataType::Fill));
+                let byte0_val = self.bytes.pop_front().unwrap_or((self.fill, QueueDataType::Fill));
                 let bus_value = byte0_val.0 as u16;
 
                 CodeStreamValue::Byte(bus_value, byte0_val.1)
             }
             CpuWidth::Sixteen => {
-                let byte0_val = self.bytes.pop_front().unwrap_or((OPCODE_NOP, QueueDataType::Fill));
-                let byte1_val = self.bytes.pop_front().unwrap_or((OPCODE_NOP, QueueDataType::Fill));
+                let byte0_val = self.bytes.pop_front().unwrap_or((self.fill, QueueDataType::Fill));
+                let byte1_val = self.bytes.pop_front().unwrap_or((self.fill, QueueDataType::Fill));
                 let bus_value = (byte0_val.0 as u16) | ((byte1_val.0 as u16) << 8);
 
                 CodeStreamValue::Word(bus_value, byte0_val.1, byte1_val.1)
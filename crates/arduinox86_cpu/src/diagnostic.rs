@@ -0,0 +1,148 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Structured diagnostics raised by [crate::RemoteCpu] during a run.
+//!
+//! Conditions like a desynchronized bus or an unhandled bus state used to only reach the log.
+//! They are now also recorded as [Diagnostic]s, so front ends can list them and scripts can fail
+//! a run on the categories they care about.
+
+use std::{fmt, str::FromStr};
+
+/// How serious a [Diagnostic] is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for DiagnosticSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticSeverity::Info => write!(f, "info"),
+            DiagnosticSeverity::Warning => write!(f, "warning"),
+            DiagnosticSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// What a [Diagnostic] is about.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticCategory {
+    /// The host's view of the bus cycle no longer matches the CPU, such as ALE outside of T1.
+    Desync,
+    /// The CPU fetched past the end of the user program.
+    ProgramBounds,
+    /// The program wrote outside its bounds. Only raised when an [crate::OobWritePolicy] other
+    /// than `Allow` is set.
+    OobWrite,
+    /// The CPU transferred memory data with BHE inactive and A0 odd.
+    InvalidWidth,
+    /// The host drove the data bus while the CPU was driving it.
+    BusContention,
+    /// The CPU read the data bus with nothing driving it.
+    BusFloat,
+    /// The CPU entered a bus state the host does not service.
+    UnhandledBusState,
+    /// The run hit the cycle limit.
+    CycleLimit,
+    /// A command to the CPU server failed.
+    Server,
+    /// The program accessed a ROM, MMIO or excluded region of the address map.
+    AddressMap,
+    /// An instruction began at a different address than the length of the previous instruction
+    /// predicts, pointing to a missed flush or a host queue out of step with the CPU.
+    QueueFetch,
+    /// The FPU state could not be saved.
+    Fpu,
+}
+
+impl DiagnosticCategory {
+    /// The name used for this category on the command line and in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagnosticCategory::Desync => "desync",
+            DiagnosticCategory::ProgramBounds => "program_bounds",
+            DiagnosticCategory::OobWrite => "oob_write",
+            DiagnosticCategory::InvalidWidth => "invalid_width",
+            DiagnosticCategory::BusContention => "contention",
+            DiagnosticCategory::BusFloat => "float",
+            DiagnosticCategory::UnhandledBusState => "bus_state",
+            DiagnosticCategory::CycleLimit => "cycle_limit",
+            DiagnosticCategory::Server => "server",
+            DiagnosticCategory::AddressMap => "address_map",
+            DiagnosticCategory::QueueFetch => "queue_fetch",
+            DiagnosticCategory::Fpu => "fpu",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for DiagnosticCategory {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "desync" => Ok(DiagnosticCategory::Desync),
+            "program_bounds" => Ok(DiagnosticCategory::ProgramBounds),
+            "oob_write" => Ok(DiagnosticCategory::OobWrite),
+            "invalid_width" => Ok(DiagnosticCategory::InvalidWidth),
+            "contention" => Ok(DiagnosticCategory::BusContention),
+            "float" => Ok(DiagnosticCategory::BusFloat),
+            "bus_state" => Ok(DiagnosticCategory::UnhandledBusState),
+            "cycle_limit" => Ok(DiagnosticCategory::CycleLimit),
+            "server" => Ok(DiagnosticCategory::Server),
+            "address_map" => Ok(DiagnosticCategory::AddressMap),
+            "queue_fetch" => Ok(DiagnosticCategory::QueueFetch),
+            "fpu" => Ok(DiagnosticCategory::Fpu),
+            _ => Err("Bad value for DiagnosticCategory".to_string()),
+        }
+    }
+}
+
+/// A condition noticed during a run, recorded on the cycle it occurred.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    pub cycle:    u32,
+    pub category: DiagnosticCategory,
+    pub message:  String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: cycle {}: [{}] {}",
+            self.severity, self.cycle, self.category, self.message
+        )
+    }
+}
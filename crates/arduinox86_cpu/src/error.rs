@@ -0,0 +1,63 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! The error type returned by [crate::RemoteCpu].
+
+use arduinox86_client::{CpuClientError, ProgramState, ServerCpuType};
+use thiserror::Error;
+
+/// [RemoteCpuError] represents the ways creating a [crate::RemoteCpu] or a [crate::RemoteCpu::run] can
+/// fail.
+#[derive(Error, Debug)]
+pub enum RemoteCpuError {
+    #[error("{0}")]
+    Client(#[from] CpuClientError),
+    #[error("Out-of-bounds write to [{address:05X}] on cycle {cycle}")]
+    OutOfBoundsWrite { address: u32, cycle: u32 },
+    #[error("Bus transfer with invalid data width at [{address:05X}] on cycle {cycle}")]
+    InvalidWidth { address: u32, cycle: u32 },
+    #[error("Data bus contention at [{address:05X}] on cycle {cycle}")]
+    BusContention { address: u32, cycle: u32 },
+    #[error("Read from a floating data bus at [{address:05X}] on cycle {cycle}")]
+    BusFloat { address: u32, cycle: u32 },
+    #[error("Instruction fetched from queue at [{address:05X}], expected [{expected:05X}], on cycle {cycle}")]
+    QueueFetch { address: u32, expected: u32, cycle: u32 },
+    #[error("WAIT at [{address:05X}] was still waiting on the FPU on cycle {cycle}")]
+    FpuWait { address: u32, cycle: u32 },
+    #[error("CPU server is in shutdown or error state: {0:?}")]
+    ServerState(ProgramState),
+    #[error("Invalid program state: {0:?}")]
+    InvalidState(ProgramState),
+    #[error("8080 emulation was requested, but {0:?} does not support it")]
+    NoEmulation(ServerCpuType),
+    #[error("Prefetching was requested, but there is no prefetch program for {0:?}")]
+    NoPrefetch(ServerCpuType),
+    #[error("CPU server did not set the 8080 emulation flag")]
+    EmulationFlag,
+    #[error("Instruction stepping needs queue status, which {0:?} does not provide")]
+    NoQueueStatus(ServerCpuType),
+    #[error("No stepped run is in progress")]
+    NotStepping,
+    #[error("Snapshot could not be restored: {0}")]
+    Snapshot(String),
+}
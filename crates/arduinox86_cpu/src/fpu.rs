@@ -0,0 +1,141 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! 8087 support for host-stepped runs.
+//!
+//! The 8087 watches the CPU's queue status and decodes the ESC instructions (D8-DF) itself. For an
+//! ESC with a memory operand the CPU makes one read of the operand, whose address the 8087 latches;
+//! any further transfers are made by the 8087 as bus master. WAIT stalls the CPU until the 8087
+//! releases BUSY, which is wired to the CPU's TEST pin.
+//!
+//! With [crate::RunOptions::fpu_save] set, [crate::RemoteCpu::run] appends [FPU_SAVE_PGM] to the
+//! program. Its `FNSAVE` makes the 8087 write its state as a 94-byte block, which the host captures
+//! from the bus into an [FpuState] instead of writing it to memory. Like `FNINIT`, `FNSAVE` leaves
+//! the 8087 reinitialized.
+
+use std::fmt::{self, Display};
+
+/// The size of the state block written by `FNSAVE` in real mode.
+pub const FPU_STATE_LEN: usize = 94;
+
+/// `FNSAVE CS:[0000]` followed by `FWAIT`, so the run doesn't end until the block is written.
+pub const FPU_SAVE_PGM: [u8; 6] = [0x2E, 0xDD, 0x36, 0x00, 0x00, 0x9B];
+
+pub const OPCODE_WAIT: u8 = 0x9B;
+
+/// Return true if `opcode` is one of the ESC opcodes, D8-DF, decoded by the FPU.
+#[inline]
+pub fn is_esc_opcode(opcode: u8) -> bool {
+    (0xD8..=0xDF).contains(&opcode)
+}
+
+/// The 8087 state as saved by `FNSAVE` in real mode.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FpuState {
+    pub control: u16,
+    pub status: u16,
+    pub tag: u16,
+    /// The 20-bit address of the last FPU instruction.
+    pub instruction_pointer: u32,
+    /// The low 11 bits of the last FPU instruction: the low 3 bits of the ESC opcode, then the
+    /// ModR/M byte.
+    pub opcode: u16,
+    /// The 20-bit address of the last FPU memory operand.
+    pub operand_pointer: u32,
+    /// The register stack from ST(0) to ST(7), as 80-bit values in memory byte order.
+    pub stack: [[u8; 10]; 8],
+}
+
+impl FpuState {
+    /// Parse a state block written by `FNSAVE`. Returns None if `bytes` is shorter than
+    /// [FPU_STATE_LEN].
+    pub fn from_bytes(bytes: &[u8]) -> Option<FpuState> {
+        if bytes.len() < FPU_STATE_LEN {
+            return None;
+        }
+        let word = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+
+        let mut stack = [[0; 10]; 8];
+        for (i, register) in stack.iter_mut().enumerate() {
+            register.copy_from_slice(&bytes[14 + i * 10..24 + i * 10]);
+        }
+        Some(FpuState {
+            control: word(0),
+            status: word(2),
+            tag: word(4),
+            instruction_pointer: word(6) as u32 | ((word(8) as u32 & 0xF000) << 4),
+            opcode: word(8) & 0x07FF,
+            operand_pointer: word(10) as u32 | ((word(12) as u32 & 0xF000) << 4),
+            stack,
+        })
+    }
+
+    /// Return the state as the block `FRSTOR` loads.
+    pub fn to_bytes(&self) -> [u8; FPU_STATE_LEN] {
+        let mut bytes = [0; FPU_STATE_LEN];
+        let words = [
+            self.control,
+            self.status,
+            self.tag,
+            self.instruction_pointer as u16,
+            ((self.instruction_pointer >> 4) as u16 & 0xF000) | (self.opcode & 0x07FF),
+            self.operand_pointer as u16,
+            (self.operand_pointer >> 4) as u16 & 0xF000,
+        ];
+        for (i, word) in words.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&word.to_le_bytes());
+        }
+        for (i, register) in self.stack.iter().enumerate() {
+            bytes[14 + i * 10..24 + i * 10].copy_from_slice(register);
+        }
+        bytes
+    }
+
+    /// Return the physical register at the top of the stack, from the status word.
+    pub fn top(&self) -> u8 {
+        ((self.status >> 11) & 0x07) as u8
+    }
+}
+
+impl Display for FpuState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "CW: {:04X} SW: {:04X} TW: {:04X} TOP: {}",
+            self.control,
+            self.status,
+            self.tag,
+            self.top()
+        )?;
+        writeln!(
+            f,
+            "IP: {:05X} OP: {:03X} OPERAND: {:05X}",
+            self.instruction_pointer, self.opcode, self.operand_pointer
+        )?;
+        for (i, register) in self.stack.iter().enumerate() {
+            let hex: String = register.iter().rev().map(|b| format!("{:02X}", b)).collect();
+            writeln!(f, "ST({}): {}", i, hex)?;
+        }
+        Ok(())
+    }
+}
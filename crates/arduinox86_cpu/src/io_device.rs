@@ -0,0 +1,229 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Device models attached to I/O ports.
+//!
+//! An [IoDevice] answers the CPU's port reads and receives its port writes in host-stepped runs,
+//! so a test can exercise code that talks to a PIC, PIT or DMA page register without the real
+//! chip. Devices are registered over a range of ports with [RemoteCpu::add_io_device]. Every
+//! transfer to a device is recorded as an [IoAccess] and commented in the cycle trace. Port
+//! accesses no device claims are not served, as before.
+
+use std::ops::Range;
+
+use arduinox86_client::DataWidth;
+
+use crate::RemoteCpu;
+
+/// Port the host watches for writes that raise INTR.
+const INTR_TRIGGER_PORT: u16 = 0x00FF;
+
+/// A model of a device on the I/O bus. Ports are passed as the full 16-bit port address.
+pub trait IoDevice {
+    /// Return a short name for the device, for the cycle trace.
+    fn name(&self) -> &str {
+        "device"
+    }
+
+    fn read_u8(&mut self, port: u16) -> u8;
+
+    fn write_u8(&mut self, port: u16, value: u8);
+
+    /// Read a word from `port` and `port + 1`. Defaults to two byte reads.
+    fn read_u16(&mut self, port: u16) -> u16 {
+        u16::from_le_bytes([self.read_u8(port), self.read_u8(port.wrapping_add(1))])
+    }
+
+    /// Write a word to `port` and `port + 1`. Defaults to two byte writes.
+    fn write_u16(&mut self, port: u16, value: u16) {
+        let [lo, hi] = value.to_le_bytes();
+        self.write_u8(port, lo);
+        self.write_u8(port.wrapping_add(1), hi);
+    }
+}
+
+/// A port transfer served by an [IoDevice].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IoAccess {
+    pub cycle: u32,
+    pub port:  u16,
+    /// The byte or word transferred. Byte transfers on the high half of the bus are shifted down.
+    pub value: u16,
+    pub word:  bool,
+    pub write: bool,
+}
+
+/// A bank of read/write latches, one byte per port, such as the DMA page registers. Reads return
+/// the last value written, or the initial value.
+#[derive(Clone, Debug)]
+pub struct LatchDevice {
+    name:    String,
+    base:    u16,
+    latches: Vec<u8>,
+}
+
+impl LatchDevice {
+    pub fn new(name: impl Into<String>, ports: Range<u16>, initial: u8) -> Self {
+        Self {
+            name:    name.into(),
+            base:    ports.start,
+            latches: vec![initial; ports.len()],
+        }
+    }
+
+    /// Return the value latched for `port`, if the device covers it.
+    pub fn latch(&self, port: u16) -> Option<u8> {
+        self.latches.get(port.wrapping_sub(self.base) as usize).copied()
+    }
+}
+
+impl IoDevice for LatchDevice {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read_u8(&mut self, port: u16) -> u8 {
+        self.latch(port).unwrap_or(0xFF)
+    }
+
+    fn write_u8(&mut self, port: u16, value: u8) {
+        if let Some(latch) = self.latches.get_mut(port.wrapping_sub(self.base) as usize) {
+            *latch = value;
+        }
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Attach `device` to the ports in `ports`. Fails if the range is empty, overlaps a device
+    /// already attached, or includes port FF, which is reserved for the INTR trigger.
+    pub fn add_io_device(&mut self, ports: Range<u16>, device: Box<dyn IoDevice>) -> Result<(), String> {
+        if ports.is_empty() {
+            return Err("I/O device port range is empty.".to_string());
+        }
+        if ports.contains(&INTR_TRIGGER_PORT) {
+            return Err(format!(
+                "Port {:04X} is reserved for the INTR trigger.",
+                INTR_TRIGGER_PORT
+            ));
+        }
+        if let Some((range, other)) = self
+            .io_devices
+            .iter()
+            .find(|(range, _)| range.start < ports.end && ports.start < range.end)
+        {
+            return Err(format!(
+                "Ports {:04X}-{:04X} overlap {} at {:04X}-{:04X}.",
+                ports.start,
+                ports.end - 1,
+                other.name(),
+                range.start,
+                range.end - 1
+            ));
+        }
+        self.io_devices.push((ports, device));
+        Ok(())
+    }
+
+    /// Detach all I/O devices.
+    pub fn clear_io_devices(&mut self) {
+        self.io_devices.clear();
+    }
+
+    /// Return the port transfers served by I/O devices during the last run, in order.
+    pub fn io_accesses(&self) -> &[IoAccess] {
+        &self.io_accesses
+    }
+
+    /// Serve an I/O read from the device at the latched port, if any. Returns true if a device
+    /// answered and the data bus was set.
+    pub(crate) fn io_device_read(&mut self) -> bool {
+        let port = self.address_latch as u16;
+        let Some((_, device)) = self.io_devices.iter_mut().find(|(range, _)| range.contains(&port))
+        else {
+            return false;
+        };
+        let (value, word) = match self.data_width {
+            DataWidth::EightLow => (device.read_u8(port) as u16, false),
+            DataWidth::EightHigh => (device.read_u8(port) as u16, false),
+            DataWidth::Sixteen => (device.read_u16(port), true),
+            DataWidth::Invalid => return false,
+        };
+        self.data_bus = match self.data_width {
+            DataWidth::EightHigh => value << 8,
+            _ => value,
+        };
+        self.cycle_comment = Some(format!(
+            "IO: {} read [{:04X}] -> {}",
+            device.name(),
+            port,
+            format_io_value(value, word)
+        ));
+        self.io_accesses.push(IoAccess {
+            cycle: self.cycle_num,
+            port,
+            value,
+            word,
+            write: false,
+        });
+        true
+    }
+
+    /// Pass an I/O write on the data bus to the device at the latched port, if any.
+    pub(crate) fn io_device_write(&mut self) {
+        let port = self.address_latch as u16;
+        let Some((_, device)) = self.io_devices.iter_mut().find(|(range, _)| range.contains(&port))
+        else {
+            return;
+        };
+        let (value, word) = match self.data_width {
+            DataWidth::EightLow => (self.data_bus & 0xFF, false),
+            DataWidth::EightHigh => (self.data_bus >> 8, false),
+            DataWidth::Sixteen => (self.data_bus, true),
+            DataWidth::Invalid => return,
+        };
+        match word {
+            true => device.write_u16(port, value),
+            false => device.write_u8(port, value as u8),
+        }
+        self.cycle_comment = Some(format!(
+            "IO: {} write [{:04X}] <- {}",
+            device.name(),
+            port,
+            format_io_value(value, word)
+        ));
+        self.io_accesses.push(IoAccess {
+            cycle: self.cycle_num,
+            port,
+            value,
+            word,
+            write: true,
+        });
+    }
+}
+
+fn format_io_value(value: u16, word: bool) -> String {
+    match word {
+        true => format!("{:04X}", value),
+        false => format!("{:02X}", value),
+    }
+}
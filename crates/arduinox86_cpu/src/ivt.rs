@@ -0,0 +1,209 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Interrupt vector table setup.
+//!
+//! By default every vector points at a two-byte stub of IRET and NOP in the ISR segment, so a
+//! software interrupt returns immediately. [IvtBuilder] replaces individual stubs, moves the ISR
+//! segment, or points vectors at ISR programs of any length, which are placed after the stub table
+//! in the same segment.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    opcodes::{OPCODE_IRET, OPCODE_NOP},
+    RemoteCpu,
+};
+
+/// Default segment holding the ISR stubs and programs.
+pub const DEFAULT_ISR_SEGMENT: u16 = 0xF800;
+/// Length of each vector's stub in the stub table.
+pub const ISR_STUB_LEN: usize = 2;
+/// Length of the stub table at the start of the ISR segment.
+const STUB_TABLE_LEN: usize = 256 * ISR_STUB_LEN;
+/// End of the interrupt vector table itself.
+const IVT_END: u32 = 256 * 4;
+
+/// Builds an [Ivt]. See [RemoteCpu::ivt_builder].
+#[derive(Clone, Debug)]
+pub struct IvtBuilder {
+    segment: u16,
+    stubs:   BTreeMap<u8, [u8; ISR_STUB_LEN]>,
+    isrs:    BTreeMap<u8, Vec<u8>>,
+}
+
+impl Default for IvtBuilder {
+    fn default() -> Self {
+        Self {
+            segment: DEFAULT_ISR_SEGMENT,
+            stubs:   BTreeMap::new(),
+            isrs:    BTreeMap::new(),
+        }
+    }
+}
+
+impl IvtBuilder {
+    /// Place the stub table and ISR programs in `segment` instead of [DEFAULT_ISR_SEGMENT].
+    pub fn segment(mut self, segment: u16) -> Self {
+        self.segment = segment;
+        self
+    }
+
+    /// Replace the IRET, NOP stub of `vector` with `stub`.
+    pub fn stub(mut self, vector: u8, stub: [u8; ISR_STUB_LEN]) -> Self {
+        self.stubs.insert(vector, stub);
+        self
+    }
+
+    /// Point `vector` at `program` instead of its stub. The program is responsible for returning,
+    /// usually with an IRET.
+    pub fn isr(mut self, vector: u8, program: impl Into<Vec<u8>>) -> Self {
+        self.isrs.insert(vector, program.into());
+        self
+    }
+
+    /// Lay out the stub table and ISR programs. Fails if an ISR program is empty, if the programs
+    /// don't fit in the segment, or if the segment overlaps the vector table.
+    pub fn build(self) -> Result<Ivt, String> {
+        let mut image = Vec::with_capacity(STUB_TABLE_LEN);
+        for vector in 0..=255u8 {
+            image.extend_from_slice(self.stubs.get(&vector).unwrap_or(&[OPCODE_IRET, OPCODE_NOP]));
+        }
+
+        let mut vectors: [u16; 256] = std::array::from_fn(|v| (v * ISR_STUB_LEN) as u16);
+        let mut isrs = Vec::with_capacity(self.isrs.len());
+        for (vector, program) in self.isrs {
+            if program.is_empty() {
+                return Err(format!("ISR program for vector {:02X} is empty.", vector));
+            }
+            let start = image.len();
+            let end = start + program.len();
+            if end > 0x10000 {
+                return Err(format!(
+                    "ISR program for vector {:02X} ends at offset {:X}, past the end of segment {:04X}.",
+                    vector, end, self.segment
+                ));
+            }
+            image.extend_from_slice(&program);
+            vectors[vector as usize] = start as u16;
+            isrs.push((vector, start..end));
+        }
+
+        let base = RemoteCpu::calc_linear_address(self.segment, 0);
+        if base < IVT_END || base as usize + image.len() > 0x10_0000 {
+            return Err(format!(
+                "ISR segment {:04X} must not overlap the vector table or wrap past 1MB.",
+                self.segment
+            ));
+        }
+
+        Ok(Ivt {
+            segment: self.segment,
+            vectors,
+            isrs,
+            image,
+        })
+    }
+}
+
+/// A laid-out interrupt vector table: where each vector points, and the contents of the ISR
+/// segment from offset 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ivt {
+    segment: u16,
+    vectors: [u16; 256],
+    isrs:    Vec<(u8, Range<usize>)>,
+    image:   Vec<u8>,
+}
+
+impl Default for Ivt {
+    fn default() -> Self {
+        IvtBuilder::default().build().expect("Default IVT is valid")
+    }
+}
+
+impl Ivt {
+    /// Return the segment holding the stubs and ISR programs.
+    pub fn segment(&self) -> u16 {
+        self.segment
+    }
+
+    /// Return the segment and offset `vector` points to.
+    pub fn vector(&self, vector: u8) -> (u16, u16) {
+        (self.segment, self.vectors[vector as usize])
+    }
+
+    /// Return the contents of the ISR segment, starting at offset 0.
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Return the linear address range covered by the stubs and ISR programs.
+    pub fn range(&self) -> Range<u32> {
+        let base = RemoteCpu::calc_linear_address(self.segment, 0);
+        base..base + self.image.len() as u32
+    }
+
+    /// Return the vector whose stub or ISR program contains the linear `address`, if any.
+    pub fn vector_at(&self, address: u32) -> Option<u8> {
+        if !self.range().contains(&address) {
+            return None;
+        }
+        let offset = (address - self.range().start) as usize;
+        if offset < STUB_TABLE_LEN {
+            return Some((offset / ISR_STUB_LEN) as u8);
+        }
+        self.isrs
+            .iter()
+            .find(|(_, range)| range.contains(&offset))
+            .map(|(vector, _)| *vector)
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Return a builder for a custom interrupt vector table, to be installed with
+    /// [RemoteCpu::install_ivt].
+    pub fn ivt_builder() -> IvtBuilder {
+        IvtBuilder::default()
+    }
+
+    /// Write the vector table and ISR segment for `ivt` into memory. Fetches from the ISR segment
+    /// are then served as ISR bytes. In 8080 emulation mode the BRKEM vector is still pointed at
+    /// the emulation segment.
+    pub fn install_ivt(&mut self, ivt: Ivt) {
+        for vector in 0..=255u8 {
+            let (segment, offset) = ivt.vector(vector);
+            let table_offset = vector as usize * 4;
+            self.write_u16(table_offset, offset);
+            self.write_u16(table_offset + 2, segment);
+        }
+
+        let base = ivt.range().start as usize;
+        self.memory[base..base + ivt.image().len()].copy_from_slice(ivt.image());
+        self.ivt = ivt;
+
+        if self.do_emu8080 && self.cpu_type.has_8080_emulation() {
+            self.setup_emulation_ivt();
+        }
+    }
+}
@@ -21,27 +21,78 @@
     DEALINGS IN THE SOFTWARE.
 */
 
+//! Drive a CPU attached to an ArduinoX86 server one bus cycle at a time.
+//!
+//! [RemoteCpu] is the entry point. Mount a program and its registers, then [RemoteCpu::run] it
+//! with a set of [RunOptions]. A run returns a [RunResult] carrying the final registers along with
+//! everything recorded along the way, including an optional per-cycle trace of [CycleRecord]s,
+//! or a [RemoteCpuError]. The types re-exported here are the supported interface. The cycle
+//! stepping internals are private to the crate and may change between releases.
+
 #![allow(dead_code, unused_variables)]
 
 mod queue;
 #[macro_use]
 pub(crate) mod opcodes;
 mod code_stream;
+mod diagnostic;
+mod error;
+mod fpu;
+mod io_device;
+mod ivt;
+mod memory_hook;
+mod pcb;
 mod remote_program;
-
-use std::str::FromStr;
+mod run_result;
+mod sections;
+mod snapshot;
+mod validator;
+#[cfg(feature = "use_moo")]
+mod replay;
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 // Re-export the client module for convenience
 pub use arduinox86_client;
 use arduinox86_client::*;
+use arduinox86_config::{AddressMap, RegionKind};
 
 use code_stream::CodeStream;
+use fpu::OPCODE_WAIT;
 use opcodes::*;
 use queue::*;
 use remote_program::RemoteProgram;
 
-pub use arduinox86_client::{RemoteCpuRegisters, RemoteCpuRegistersV1, RemoteCpuRegistersV2};
-pub use queue::QueueDataType;
+pub use arduinox86_client::{BusOp, RemoteCpuRegisters, RemoteCpuRegistersV1, RemoteCpuRegistersV2};
+pub use diagnostic::{Diagnostic, DiagnosticCategory, DiagnosticSeverity};
+pub use error::RemoteCpuError;
+pub use fpu::{is_esc_opcode, FpuState, FPU_SAVE_PGM, FPU_STATE_LEN};
+pub use io_device::{IoAccess, IoDevice, LatchDevice};
+pub use ivt::{Ivt, IvtBuilder, DEFAULT_ISR_SEGMENT, ISR_STUB_LEN};
+pub use memory_hook::{MemoryHook, MemoryHookAccess, WatchHook};
+pub use opcodes::{get_opcode_str, is_group_op, DecodeArch};
+pub use pcb::{PeripheralControlBlock, PCB_RELOCATION_RESET, PCB_SIZE};
+pub use queue::{QueueDataType, QueueProvenance};
+pub use run_result::{bus_ops, CycleRecord, InstructionStepResult, RunResult};
+pub use sections::{ProgramSection, TRAMPOLINE_LEN};
+pub use snapshot::CpuSnapshot;
+pub use validator::{
+    CycleMismatch,
+    EmulatorValidator,
+    RamMismatch,
+    TraceCycle,
+    ValidateOptions,
+    ValidationCase,
+    ValidationReport,
+    ValidationRun,
+};
+#[cfg(feature = "use_moo")]
+pub use replay::{RegisterMismatch, ReplayOptions, ReplayResult};
 
 pub const WAIT_STATES: u32 = 0;
 
@@ -72,7 +123,6 @@ const ADDRESS_SPACE: usize = 0x10_0000;
 const ADDRESS_SPACE_MASK: usize = 0x0F_FFFF;
 
 const IO_FINALIZE_ADDR: u32 = 0x00FF;
-const ISR_SEGMENT: u16 = 0xF800;
 
 const I8080_EMULATION_SEGMENT: u16 = 0x1000;
 const BRKEM_INT: u8 = 0xFF;
@@ -80,6 +130,7 @@ const BRKEM_INT: u8 = 0xFF;
 static NULL_PRELOAD_PGM: [u8; 0] = [];
 static INTEL808X_PRELOAD_PGM: [u8; 4] = [0xAA, 0xAA, 0xAA, 0xAA]; // (4x stosb)
 static NECVX0_PRELOAD_PGM: [u8; 2] = [0x63, 0xC0]; // (undefined, no side effects)
+static NOP_PRELOAD_PGM: [u8; 4] = [0x90, 0x90, 0x90, 0x90]; // (4x nop)
 
 static INTEL_PREFIXES: [u8; 8] = [0x26, 0x2E, 0x36, 0x3E, 0xF0, 0xF1, 0xF2, 0xF3];
 static NEC_PREFIXES: [u8; 10] = [0x26, 0x2E, 0x36, 0x3E, 0xF0, 0xF1, 0xF2, 0xF3, 0x64, 0x65];
@@ -90,14 +141,56 @@ macro_rules! cycle_comment {
     }};
 }
 
+/// The state of a host-stepped run kept between cycles, so a run can be advanced a step at a time.
+struct SteppedRun {
+    start_cycle: u32,
+    start_instruction: u32,
+    trace: Vec<CycleRecord>,
+    phase_budget: PhaseBudget,
+    /// The instruction started by the last step, if any.
+    instruction: Option<InstructionStart>,
+}
+
+/// The first cycle of an instruction being stepped.
+struct InstructionStart {
+    record:    CycleRecord,
+    address:   u32,
+    /// The state of the bus cycle underway when the instruction started.
+    bus_state: BusState,
+}
+
+/// Options for a [RemoteCpu::run].
 #[derive(Copy, Clone, Debug)]
 pub struct RunOptions {
+    /// Let the server execute the program, instead of stepping it from the host.
     pub automatic: bool,
+    /// Read out registers in SMM, on CPUs that support it.
     pub use_smm: bool,
     pub cycle_limit: Option<u32>,
     pub wait_states: Option<u32>,
     pub print_opts: PrintOptions,
+    /// Milliseconds between program state polls during an automatic run.
     pub polling_sleep: u32,
+    pub oob_write_policy: OobWritePolicy,
+    pub invalid_width_policy: InvalidWidthPolicy,
+    pub bus_contention_policy: BusContentionPolicy,
+    pub bus_fault: Option<BusFault>,
+    /// Record a [CycleRecord] per cycle into [RunResult::trace]. Host-stepped runs only.
+    pub trace: bool,
+    /// End the run with an error when an instruction is fetched from the queue at an address other
+    /// than the one predicted from the previous instruction. Such fetches are always recorded as
+    /// [DiagnosticCategory::QueueFetch] diagnostics. Only applies to CPUs with queue status.
+    pub strict_queue: bool,
+    /// Run [FPU_SAVE_PGM] after the program and return the FPU state in [RunResult::fpu_state].
+    /// Host-stepped runs with an FPU only.
+    pub fpu_save: bool,
+    /// End the run with an error when a WAIT instruction waits on the FPU for longer than this
+    /// many cycles.
+    pub fpu_wait_limit: Option<u32>,
+    /// End the program when this condition is met, in addition to running past the end of the
+    /// program. The run finalizes at the next code fetch after the condition. Fetch addresses must be
+    /// aligned to the bus width, and IRET conditions need queue status on host-stepped runs.
+    pub end_condition: Option<EndCondition>,
 }
 
 impl Default for RunOptions {
@@ -109,6 +202,325 @@ impl Default for RunOptions {
             wait_states: None,
             print_opts: PrintOptions::default(),
             polling_sleep: 10, // Default sleep time for polling
+            oob_write_policy: OobWritePolicy::default(),
+            invalid_width_policy: InvalidWidthPolicy::default(),
+            bus_contention_policy: BusContentionPolicy::default(),
+            bus_fault: None,
+            trace: false,
+            strict_queue: false,
+            fpu_save: false,
+            fpu_wait_limit: None,
+            end_condition: None,
+        }
+    }
+}
+
+/// What to do when a program writes to memory outside its bounds. The program bounds are the
+/// mounted program region plus any regions added with [RemoteCpu::add_writable_region].
+/// Only applies to host-stepped runs, as automatic runs write to the server's memory.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OobWritePolicy {
+    /// Perform the write silently.
+    #[default]
+    Allow,
+    /// Perform the write and record it for the post-run report.
+    Warn,
+    /// Record the write, discard it, and end the run with an error.
+    Fault,
+}
+
+impl FromStr for OobWritePolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "allow" => Ok(OobWritePolicy::Allow),
+            "warn" => Ok(OobWritePolicy::Warn),
+            "fault" => Ok(OobWritePolicy::Fault),
+            _ => Err("Bad value for OobWritePolicy".to_string()),
+        }
+    }
+}
+
+/// What to do when the CPU transfers memory data with BHE inactive and A0 odd, a combination
+/// that enables neither half of the data bus. This points to either a hardware fault or CPU
+/// behavior worth capturing, so such cycles are always counted in [RunResult::invalid_width_cycles].
+/// Only applies to host-stepped runs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InvalidWidthPolicy {
+    /// Record a diagnostic and continue. Reads are served 0 and writes are discarded.
+    #[default]
+    Record,
+    /// Treat the cycle as an open bus: reads are served FFFF and writes are discarded.
+    OpenBus,
+    /// Record a diagnostic and end the run with an error.
+    Fault,
+}
+
+impl FromStr for InvalidWidthPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "record" => Ok(InvalidWidthPolicy::Record),
+            "open_bus" => Ok(InvalidWidthPolicy::OpenBus),
+            "fault" => Ok(InvalidWidthPolicy::Fault),
+            _ => Err("Bad value for InvalidWidthPolicy".to_string()),
+        }
+    }
+}
+
+/// What to do when the data bus is driven from both sides at once, or read with nobody driving it.
+/// Contention is the host driving the bus while the CPU is also driving it: during the address
+/// phase, or during a write. A float is a read strobe with neither the host nor the server driving
+/// a value. Both are counted in [RunResult::contention_cycles] and [RunResult::float_cycles].
+/// Only applies to host-stepped runs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusContentionPolicy {
+    /// Record a diagnostic and continue.
+    #[default]
+    Record,
+    /// Record a diagnostic and end the run with an error. The host does not drive the bus on a
+    /// cycle that would contend.
+    Fault,
+}
+
+impl FromStr for BusContentionPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "record" => Ok(BusContentionPolicy::Record),
+            "fault" => Ok(BusContentionPolicy::Fault),
+            _ => Err("Bad value for BusContentionPolicy".to_string()),
+        }
+    }
+}
+
+/// A byte written outside the program bounds.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OobWrite {
+    pub cycle: u32,
+    pub address: u32,
+    pub value: u8,
+}
+
+/// The bus reads a [BusFault] can target.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusFaultTarget {
+    /// Any code fetch or memory read.
+    #[default]
+    Any,
+    /// Code fetches only.
+    Code,
+    /// Memory reads only.
+    MemRead,
+}
+
+impl FromStr for BusFaultTarget {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "any" => Ok(BusFaultTarget::Any),
+            "code" => Ok(BusFaultTarget::Code),
+            "memr" => Ok(BusFaultTarget::MemRead),
+            _ => Err("Bad value for BusFaultTarget".to_string()),
+        }
+    }
+}
+
+/// A deliberate corruption of a single bus read, simulating a single event upset. The `nth`
+/// matching read of the user program (counting from 1) is served with its data XORed by
+/// `xor_mask`. Only applies to host-stepped runs, as automatic runs are served by the server.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BusFault {
+    pub target: BusFaultTarget,
+    pub nth: u32,
+    pub xor_mask: u16,
+}
+
+/// A [BusFault] that was injected during a run.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InjectedFault {
+    pub cycle: u32,
+    pub bus_state: BusState,
+    pub address: u32,
+    pub original: u16,
+    pub injected: u16,
+}
+
+/// The program run ahead of the user program to fill the prefetch queue. Each program has
+/// different side effects, which are undone by adjusting the registers on load.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PreloadProgram {
+    /// The default program for the detected CPU: STOSB on Intel, the undefined opcode on NEC.
+    #[default]
+    Auto,
+    /// No preload. The user program starts with an empty queue.
+    None,
+    /// 4x STOSB. DI is adjusted on load so it ends at its initial value.
+    Stosb,
+    /// 4x NOP. No side effects, but leaves less of the user program in the queue.
+    Nop,
+    /// Undefined opcode 63 C0, which has no side effects. NEC V20/V30 only.
+    NecUndefined,
+}
+
+impl FromStr for PreloadProgram {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(PreloadProgram::Auto),
+            "none" => Ok(PreloadProgram::None),
+            "stosb" => Ok(PreloadProgram::Stosb),
+            "nop" => Ok(PreloadProgram::Nop),
+            "nec" => Ok(PreloadProgram::NecUndefined),
+            _ => Err("Bad value for PreloadProgram".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for PreloadProgram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreloadProgram::Auto => write!(f, "auto"),
+            PreloadProgram::None => write!(f, "none"),
+            PreloadProgram::Stosb => write!(f, "stosb"),
+            PreloadProgram::Nop => write!(f, "nop"),
+            PreloadProgram::NecUndefined => write!(f, "nec"),
+        }
+    }
+}
+
+impl PreloadProgram {
+    /// Return the preload programs that are valid for the specified CPU, for callers that want to
+    /// pick one per test file.
+    pub fn choices(cpu_type: ServerCpuType) -> &'static [PreloadProgram] {
+        match cpu_type {
+            ServerCpuType::Intel8088 | ServerCpuType::Intel8086 => {
+                &[PreloadProgram::None, PreloadProgram::Stosb, PreloadProgram::Nop]
+            }
+            ServerCpuType::NecV20 | ServerCpuType::NecV30 => &[
+                PreloadProgram::None,
+                PreloadProgram::Stosb,
+                PreloadProgram::Nop,
+                PreloadProgram::NecUndefined,
+            ],
+            _ => &[PreloadProgram::None],
+        }
+    }
+
+    /// Resolve [PreloadProgram::Auto] to the default program for the specified CPU, and check
+    /// that any other program is valid for it.
+    pub fn resolve(self, cpu_type: ServerCpuType) -> Result<PreloadProgram, String> {
+        let resolved = match (self, cpu_type) {
+            (PreloadProgram::Auto, ServerCpuType::Intel8088 | ServerCpuType::Intel8086) => PreloadProgram::Stosb,
+            (PreloadProgram::Auto, ServerCpuType::NecV20 | ServerCpuType::NecV30) => PreloadProgram::NecUndefined,
+            _ => self,
+        };
+
+        if PreloadProgram::choices(cpu_type).contains(&resolved) {
+            Ok(resolved)
+        }
+        else {
+            Err(format!("Preload program '{}' is not supported on {:?}", self, cpu_type))
+        }
+    }
+
+    fn bytes(&self) -> &'static [u8] {
+        match self {
+            PreloadProgram::Auto | PreloadProgram::None => &NULL_PRELOAD_PGM,
+            PreloadProgram::Stosb => &INTEL808X_PRELOAD_PGM,
+            PreloadProgram::Nop => &NOP_PRELOAD_PGM,
+            PreloadProgram::NecUndefined => &NECVX0_PRELOAD_PGM,
+        }
+    }
+
+    /// Number of string operations the program performs, and so how far it moves DI.
+    fn di_steps(&self) -> u16 {
+        match self {
+            PreloadProgram::Stosb => INTEL808X_PRELOAD_PGM.len() as u16,
+            _ => 0,
+        }
+    }
+
+    fn program(&self, width: CpuWidth, fill_byte: u8) -> Option<RemoteProgram> {
+        match self {
+            PreloadProgram::Auto | PreloadProgram::None => None,
+            _ => Some(RemoteProgram::new(self.bytes(), fill_byte, width)),
+        }
+    }
+}
+
+/// The byte fed to the CPU in place of code it fetches but should not run: the high byte lane
+/// past the end of a program on a 16-bit bus, or the padding that aligns a preload program.
+///
+/// A fill never spans more than one byte lane, as the store program takes over once the CPU
+/// fetches past the program, so only single-byte instructions make sense here.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Filler {
+    /// The NOP for the current mode: 90, or 00 in 8080 emulation mode.
+    #[default]
+    Auto,
+    /// NOP (90). Not valid in 8080 emulation mode, where 90 is SUB B.
+    Nop,
+    /// 8080 NOP (00). Only valid in 8080 emulation mode, where it is the only side-effect free
+    /// single-byte opcode.
+    Nop80,
+    /// An arbitrary byte, for experiments. The caller is responsible for its side effects.
+    Byte(u8),
+}
+
+impl FromStr for Filler {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Filler::Auto),
+            "nop" => Ok(Filler::Nop),
+            "nop80" => Ok(Filler::Nop80),
+            other => u8::from_str_radix(other.trim_start_matches("0x"), 16)
+                .map(Filler::Byte)
+                .map_err(|_| "Bad value for Filler".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Filler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filler::Auto => write!(f, "auto"),
+            Filler::Nop => write!(f, "nop"),
+            Filler::Nop80 => write!(f, "nop80"),
+            Filler::Byte(byte) => write!(f, "{:02X}", byte),
+        }
+    }
+}
+
+impl Filler {
+    /// Resolve the filler to the byte to feed, and check that it is valid for the mode the CPU
+    /// runs the program in.
+    pub fn resolve(self, emu8080: bool) -> Result<u8, String> {
+        match (self, emu8080) {
+            (Filler::Auto, false) | (Filler::Nop, false) => Ok(OPCODE_NOP),
+            (Filler::Auto, true) | (Filler::Nop80, true) => Ok(OPCODE_NOP80),
+            (Filler::Byte(byte), _) => Ok(byte),
+            (Filler::Nop, true) => Err("Filler 'nop' is not a NOP in 8080 emulation mode".to_string()),
+            (Filler::Nop80, false) => Err("Filler 'nop80' is only valid in 8080 emulation mode".to_string()),
         }
     }
 }
@@ -167,15 +579,37 @@ impl Default for PrintOptions {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum RunState {
     #[default]
     Init,
     Preload,
+    EmuEnter,
     Program,
+    EmuExit,
     Finalize,
 }
 
+impl RunState {
+    /// Return the [RunPhase] a cycle in this state is counted in. Entering and exiting 8080
+    /// emulation are counted with the preload and finalize programs they accompany.
+    pub fn phase(&self) -> RunPhase {
+        match self {
+            RunState::Init | RunState::Preload | RunState::EmuEnter => RunPhase::Preload,
+            RunState::Program => RunPhase::Program,
+            RunState::EmuExit | RunState::Finalize => RunPhase::Finalize,
+        }
+    }
+}
+
+/// A transition between [RunState]s, recorded on the cycle it occurred.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RunStateChange {
+    pub cycle: u32,
+    pub from:  RunState,
+    pub to:    RunState,
+}
+
 pub struct RemoteCpu<'a> {
     cpu_type: ServerCpuType,
     run_opts: RunOptions,
@@ -185,17 +619,23 @@ pub struct RemoteCpu<'a> {
     regs: RemoteCpuRegisters,
     memory: Vec<u8>,
     pc: usize,
-    start_addr: usize,
-    end_addr: usize,
+    // Address ranges holding program code. Most programs have a single range; sectioned programs
+    // mounted with [RemoteCpu::mount_sections] have one per section.
+    program_ranges: Vec<std::ops::Range<usize>>,
     program_state: ProgramState,
     run_state: RunState,
+    run_state_changes: Vec<RunStateChange>,
+    in_emulation: bool,
 
     do_prefetch: bool,
     do_emu8080:  bool,
 
     active_pgm: Option<&'a RemoteProgram>,
+    preload: PreloadProgram,
     preload_pgm: Option<RemoteProgram>,
     code_stream: CodeStream,
+    filler: Filler,
+    fill_byte: u8,
     program_end_offset: u16,
 
     address_bus: u32,
@@ -212,6 +652,7 @@ pub struct RemoteCpu<'a> {
     instruction_num: u32,
     mcycle_state: BusState,
     t_state: TState,
+    pins: u16,
 
     nready_states: u32,
 
@@ -219,9 +660,16 @@ pub struct RemoteCpu<'a> {
     queue: InstructionQueue,
     queue_byte: u8,
     queue_type: QueueDataType,
+    queue_provenance: QueueProvenance,
+    // Bus lanes (low, high) of the last preload fetch that were fed fill bytes.
+    preload_fill: [bool; 2],
     queue_first_fetch: bool,
     queue_fetch_n: u8,
     queue_fetch_addr: u32,
+    // Address of the first byte of the last instruction popped from the queue since the last flush.
+    queue_instruction_addr: Option<u32>,
+    // Set to (address, expected) when a mismatched first byte should end the run.
+    queue_fault: Option<(u32, u32)>,
     queue_len_at_finalize: u8,
     opcode: u8,
     finalize: bool,
@@ -233,13 +681,72 @@ pub struct RemoteCpu<'a> {
     halted:  bool,
     halt_ct: u32,
 
+    smc_writes: u32,
+
+    writable_regions: Vec<std::ops::Range<u32>>,
+    address_map: AddressMap,
+    oob_writes: Vec<OobWrite>,
+    oob_fault: bool,
+    // Set once the run's end condition is met. Later code fetches are treated as out of bounds.
+    end_reached: bool,
+    end_start_cycle: u32,
+
+    invalid_width_cycles: u32,
+    invalid_width_fault:  bool,
+
+    contention_cycles: u32,
+    float_cycles: u32,
+    // Set when something drove the data bus this cycle: the host, or the server's store program.
+    bus_driven: bool,
+    contention_fault: bool,
+    float_fault: bool,
+
+    bus_fault_reads: u32,
+    injected_fault:  Option<InjectedFault>,
+
+    diagnostics: Vec<Diagnostic>,
+
+    // The interrupt vector table last installed.
+    ivt: Ivt,
+
+    // Device models attached to I/O ports, and the transfers they served this run.
+    io_devices: Vec<(std::ops::Range<u16>, Box<dyn IoDevice>)>,
+    io_accesses: Vec<IoAccess>,
+    io_write_served: bool,
+
+    // Hooks over ranges of memory, and the bytes they saw this run.
+    memory_hooks: Vec<(std::ops::Range<u32>, Box<dyn MemoryHook>)>,
+    memory_hook_accesses: Vec<MemoryHookAccess>,
+
+    // The peripheral control block of an 80186 or 80188, whether it claimed a transfer this cycle,
+    // and whether it claimed the memory write of the current bus cycle.
+    pcb: Option<PeripheralControlBlock>,
+    pcb_cycle: bool,
+    pcb_write_claimed: bool,
+
+    // The FNSAVE program appended to the program for this run, and the bytes it replaced.
+    fpu_stub: Option<(std::ops::Range<usize>, Vec<u8>)>,
+    // Set once the FNSAVE program starts executing. Memory writes from then on are the state block.
+    fpu_saving: bool,
+    fpu_save_bytes: BTreeMap<u32, u8>,
+    // Set when the host served a memory transfer for the FPU this cycle.
+    fpu_cycle: bool,
+    // Set once the CPU has made its operand read for the current ESC instruction.
+    esc_operand_read: bool,
+    // The cycle and address of the WAIT instruction currently executing, if any.
+    wait_start: Option<(u32, u32)>,
+
     wait_state_opt: u32,
     intr_on_cycle: u32,
     intr_after: u32,
     nmi_on_cycle: u32,
+
+    stepped_run: Option<SteppedRun>,
 }
 
 impl RemoteCpu<'_> {
+    /// Connect to the CPU behind `client`. Fails if the CPU type can't be read, or if 8080
+    /// emulation or prefetching is requested for a CPU that doesn't support it.
     pub fn new(
         mut client: CpuClient,
         do_prefetch: bool,
@@ -248,7 +755,7 @@ impl RemoteCpu<'_> {
         intr_on: u32,
         intr_after: u32,
         nmi_on: u32,
-    ) -> RemoteCpu<'static> {
+    ) -> Result<RemoteCpu<'static>, RemoteCpuError> {
         // Determine CPU type/width
 
         let (server_cpu_type, width, have_fpu) = match client.cpu_type() {
@@ -259,7 +766,7 @@ impl RemoteCpu<'_> {
             }
             Err(e) => {
                 log::error!("Failed to get CPU type!");
-                std::process::exit(1);
+                return Err(e.into());
             }
         };
 
@@ -284,7 +791,7 @@ impl RemoteCpu<'_> {
         if do_emu8080 {
             if !server_cpu_type.has_8080_emulation() {
                 log::error!("Emulation mode requested but detected CPU type does not support it.");
-                std::process::exit(1);
+                return Err(RemoteCpuError::NoEmulation(server_cpu_type));
             }
             else {
                 match client.set_flags(ServerFlags::EMU_8080) {
@@ -293,29 +800,28 @@ impl RemoteCpu<'_> {
                     }
                     Err(e) => {
                         log::error!("Failed to enable emulation mode: {}", e);
-                        std::process::exit(1);
+                        return Err(e.into());
                     }
                 }
             }
         }
 
+        let fill_byte = Filler::Auto.resolve(do_emu8080).unwrap_or(OPCODE_NOP);
+        let mut preload = PreloadProgram::None;
         let mut preload_pgm = None;
 
         if do_prefetch {
             if server_cpu_type.can_prefetch() {
                 log::trace!("Using prefetch program for {:?}", server_cpu_type);
-                preload_pgm = match server_cpu_type {
-                    ServerCpuType::Intel8088 | ServerCpuType::Intel8086 => {
-                        Some(RemoteProgram::new(&INTEL808X_PRELOAD_PGM, OPCODE_NOP, width))
-                    }
-                    ServerCpuType::NecV20 | ServerCpuType::NecV30 => {
-                        Some(RemoteProgram::new(&NECVX0_PRELOAD_PGM, OPCODE_NOP, width))
+                match PreloadProgram::Auto.resolve(server_cpu_type) {
+                    Ok(resolved) => {
+                        preload = resolved;
+                        preload_pgm = resolved.program(width, fill_byte);
                     }
-                    _ => {
+                    Err(_) => {
                         log::error!("Unsupported CPU type for prefetch: {:?}", server_cpu_type);
-                        None
                     }
-                };
+                }
 
                 if let Some(ref program) = preload_pgm {
                     log::trace!("Size of prefetch program: {}", program.len());
@@ -323,7 +829,7 @@ impl RemoteCpu<'_> {
             }
             else {
                 log::error!("Prefetch option chosen but no prefetch program for specified CPU.");
-                std::process::exit(1);
+                return Err(RemoteCpuError::NoPrefetch(server_cpu_type));
             }
         }
 
@@ -334,16 +840,16 @@ impl RemoteCpu<'_> {
                 }
                 Err(e) => {
                     log::error!("Failed to set emulation mode flag!");
-                    std::process::exit(1);
+                    return Err(e.into());
                 }
                 _ => {
                     log::error!("Failed to set emulation mode flag!");
-                    std::process::exit(1);
+                    return Err(RemoteCpuError::EmulationFlag);
                 }
             };
         }
 
-        RemoteCpu {
+        Ok(RemoteCpu {
             cpu_type: server_cpu_type,
             run_opts: RunOptions::default(),
             have_fpu,
@@ -352,17 +858,21 @@ impl RemoteCpu<'_> {
             regs: Default::default(),
             memory: vec![0; ADDRESS_SPACE],
             pc: 0,
-            start_addr: 0,
-            end_addr: 0,
+            program_ranges: Vec::new(),
             program_state: ProgramState::Reset,
             run_state: RunState::Init,
+            run_state_changes: Vec::new(),
+            in_emulation: false,
 
             do_prefetch,
             do_emu8080,
 
             active_pgm: None,
+            preload,
             preload_pgm,
-            code_stream: CodeStream::new(width),
+            code_stream: CodeStream::new(width, fill_byte),
+            filler: Filler::Auto,
+            fill_byte,
             program_end_offset: 0,
 
             address_bus: 0,
@@ -378,15 +888,20 @@ impl RemoteCpu<'_> {
             instruction_num: 0,
             mcycle_state: BusState::PASV,
             t_state: TState::T1,
+            pins: 0,
             nready_states: 0,
 
             have_queue_status,
             queue: InstructionQueue::new(width, !have_queue_status),
             queue_byte: 0,
             queue_type: QueueDataType::Program,
+            queue_provenance: QueueProvenance::Fill,
+            preload_fill: [false; 2],
             queue_first_fetch: true,
             queue_fetch_n: 0,
             queue_fetch_addr: 0,
+            queue_instruction_addr: None,
+            queue_fault: None,
             queue_len_at_finalize: 0,
             opcode: 0,
             finalize: false,
@@ -395,20 +910,59 @@ impl RemoteCpu<'_> {
             nmi: false,
             halted: false,
             halt_ct: 0,
+            smc_writes: 0,
+            writable_regions: Vec::new(),
+            address_map: AddressMap::default(),
+            oob_writes: Vec::new(),
+            oob_fault: false,
+            end_reached: false,
+            end_start_cycle: 0,
+            invalid_width_cycles: 0,
+            invalid_width_fault: false,
+            contention_cycles: 0,
+            float_cycles: 0,
+            bus_driven: false,
+            contention_fault: false,
+            float_fault: false,
+            bus_fault_reads: 0,
+            injected_fault: None,
+            diagnostics: Vec::new(),
+            ivt: Ivt::default(),
+            io_devices: Vec::new(),
+            io_accesses: Vec::new(),
+            io_write_served: false,
+            memory_hooks: Vec::new(),
+            memory_hook_accesses: Vec::new(),
+            pcb: matches!(
+                server_cpu_type,
+                ServerCpuType::Intel80186(_) | ServerCpuType::Intel80188(_)
+            )
+            .then(PeripheralControlBlock::default),
+            pcb_cycle: false,
+            pcb_write_claimed: false,
+            fpu_stub: None,
+            fpu_saving: false,
+            fpu_save_bytes: BTreeMap::new(),
+            fpu_cycle: false,
+            esc_operand_read: false,
+            wait_start: None,
             wait_state_opt,
             intr_on_cycle: intr_on,
             intr_after,
             nmi_on_cycle: nmi_on,
-        }
+            stepped_run: None,
+        })
     }
 
     pub fn reset(&mut self) {
         log::trace!("Resetting!");
         self.program_state = ProgramState::Reset;
         self.run_state = RunState::default();
+        self.run_state_changes.clear();
+        self.in_emulation = false;
 
         self.preload_pgm.as_mut().map(|p| p.reset());
-        self.code_stream = CodeStream::new(self.width);
+        self.code_stream = CodeStream::new(self.width, self.fill_byte);
         self.program_end_offset = 0;
         self.address_bus = 0;
         self.address_latch = 0;
@@ -425,13 +979,49 @@ impl RemoteCpu<'_> {
         self.queue = InstructionQueue::new(self.width, !self.have_queue_status);
         self.queue_byte = 0;
         self.queue_type = QueueDataType::Program;
+        self.queue_provenance = QueueProvenance::Fill;
+        self.preload_fill = [false; 2];
         self.queue_first_fetch = true;
         self.queue_fetch_n = 0;
         self.queue_fetch_addr = 0;
+        self.queue_instruction_addr = None;
+        self.queue_fault = None;
         self.queue_len_at_finalize = 0;
         self.opcode = 0;
         self.finalize = false;
         self.do_nmi = false;
+        self.smc_writes = 0;
+        self.oob_writes.clear();
+        self.oob_fault = false;
+        // A reset abandons any stepped run.
+        self.stepped_run = None;
+        self.unmount_fpu_save();
+        self.end_reached = false;
+        self.end_start_cycle = 0;
+        self.invalid_width_cycles = 0;
+        self.invalid_width_fault = false;
+        self.contention_cycles = 0;
+        self.float_cycles = 0;
+        self.bus_driven = false;
+        self.contention_fault = false;
+        self.float_fault = false;
+        self.bus_fault_reads = 0;
+        self.injected_fault = None;
+        self.diagnostics.clear();
+        self.io_accesses.clear();
+        self.memory_hook_accesses.clear();
+        self.io_write_served = false;
+        // Reset moves the peripheral control block back to its default location.
+        if let Some(pcb) = self.pcb.as_mut() {
+            *pcb = PeripheralControlBlock::default();
+        }
+        self.pcb_cycle = false;
+        self.pcb_write_claimed = false;
+        self.fpu_saving = false;
+        self.fpu_save_bytes.clear();
+        self.fpu_cycle = false;
+        self.esc_operand_read = false;
+        self.wait_start = None;
     }
 
     pub fn set_pc(&mut self, cs: u16, ip: u16) {
@@ -448,6 +1038,43 @@ impl RemoteCpu<'_> {
         self.have_fpu
     }
 
+    /// Return the preload program in use. Never [PreloadProgram::Auto].
+    pub fn preload(&self) -> PreloadProgram {
+        self.preload
+    }
+
+    /// Select the preload program. Takes effect on the next register load, which is where the
+    /// registers are adjusted for the program's side effects.
+    pub fn set_preload(&mut self, preload: PreloadProgram) -> Result<(), String> {
+        let resolved = preload.resolve(self.cpu_type)?;
+        self.preload = resolved;
+        self.preload_pgm = resolved.program(self.width, self.fill_byte);
+        self.do_prefetch = self.preload_pgm.is_some();
+        Ok(())
+    }
+
+    /// Return the filler in use, as selected. See [RemoteCpu::fill_byte] for the byte it
+    /// resolved to.
+    pub fn filler(&self) -> Filler {
+        self.filler
+    }
+
+    /// Return the byte fed in place of code the CPU should not run.
+    pub fn fill_byte(&self) -> u8 {
+        self.fill_byte
+    }
+
+    /// Select the filler. Fails if it is not valid for the CPU's execution mode.
+    pub fn set_filler(&mut self, filler: Filler) -> Result<(), String> {
+        self.fill_byte = filler.resolve(self.do_emu8080)?;
+        self.filler = filler;
+        self.code_stream.set_fill(self.fill_byte);
+        if let Some(program) = &mut self.preload_pgm {
+            program.set_fill(self.fill_byte);
+        }
+        Ok(())
+    }
+
     pub fn pc(&self) -> usize {
         self.pc
     }
@@ -476,49 +1103,480 @@ impl RemoteCpu<'_> {
             *dst = *src;
         }
 
-        // Update end address past sizeof program
-        self.start_addr = location;
-        self.end_addr = location + src_size;
+        // Update end address past sizeof program
+        self.set_program_bounds(location, location + src_size);
+
+        log::debug!(
+            "Program mounted! Start addr: [{:05X}] end addr: [{:05X}]",
+            location,
+            location + src_size
+        );
+        Ok(true)
+    }
+
+    /// Set the program bounds to the single range `start..end`, replacing any previous ranges.
+    pub fn set_program_bounds(&mut self, start: usize, end: usize) {
+        self.program_ranges.clear();
+        self.program_ranges.push(start..end);
+    }
+
+    /// Add the range `start..end` to the program bounds, for programs split across several
+    /// regions of memory. Program byte indices count through the ranges in the order they were
+    /// added.
+    pub fn add_program_bounds(&mut self, start: usize, end: usize) {
+        self.program_ranges.push(start..end);
+    }
+
+    /// Return the address ranges holding program code.
+    pub fn program_ranges(&self) -> &[std::ops::Range<usize>] {
+        &self.program_ranges
+    }
+
+    /// Return the program range containing `addr`, if any.
+    pub(crate) fn program_range(&self, addr: usize) -> Option<&std::ops::Range<usize>> {
+        self.program_ranges.iter().find(|range| range.contains(&addr))
+    }
+
+    /// Return the index of the program byte at `addr`, counting through the program ranges in
+    /// order.
+    pub(crate) fn program_byte_index(&self, addr: usize) -> Option<usize> {
+        let mut index = 0;
+        for range in &self.program_ranges {
+            if range.contains(&addr) {
+                return Some(index + addr - range.start);
+            }
+            index += range.len();
+        }
+        None
+    }
+
+    /// Write a block of bytes, such as a data table, at `address`. Unlike [RemoteCpu::mount_bin],
+    /// this does not change the program bounds. Addresses wrap at the end of the address space.
+    pub fn load_data(&mut self, automatic: bool, address: u32, data: &[u8]) -> Result<(), String> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if automatic {
+            self.client.set_memory(address, data).map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        for (i, byte) in data.iter().enumerate() {
+            self.memory[(address as usize + i) & ADDRESS_SPACE_MASK] = *byte;
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes at `address`: from the server in automatic mode, where the program ran from
+    /// the server's memory, and from the host's memory otherwise. Addresses wrap at the end of the
+    /// address space.
+    pub fn read_data(&mut self, automatic: bool, address: u32, len: usize) -> Result<Vec<u8>, String> {
+        if automatic {
+            let mut data = Vec::with_capacity(len);
+            self.client
+                .read_memory(address, len as u32, &mut data)
+                .map_err(|e| e.to_string())?;
+            return Ok(data);
+        }
+        Ok((0..len)
+            .map(|i| self.memory[(address as usize + i) & ADDRESS_SPACE_MASK])
+            .collect())
+    }
+
+    /// Write scattered bytes given as `(address, value)` pairs, the same shape as MOO RAM entries.
+    /// Runs of consecutive addresses are uploaded together in automatic mode.
+    pub fn load_ram_entries(&mut self, automatic: bool, entries: &[(u32, u8)]) -> Result<(), String> {
+        let mut run_start = 0;
+        let mut run: Vec<u8> = Vec::new();
+        for &(address, value) in entries {
+            if !run.is_empty() && address != run_start + run.len() as u32 {
+                self.load_data(automatic, run_start, &run)?;
+                run.clear();
+            }
+            if run.is_empty() {
+                run_start = address;
+            }
+            run.push(value);
+        }
+        self.load_data(automatic, run_start, &run)
+    }
+
+    /// Set up a stack image at SS:SP so that `values` are popped in order: `values[0]` is at SS:SP,
+    /// `values[1]` at SS:SP+2, and so on. The stack pointer wraps within the segment.
+    pub fn load_stack(&mut self, automatic: bool, ss: u16, sp: u16, values: &[u16]) -> Result<(), String> {
+        let mut entries = Vec::with_capacity(values.len() * 2);
+        let mut offset = sp;
+        for value in values {
+            let [lo, hi] = value.to_le_bytes();
+            entries.push((RemoteCpu::calc_linear_address(ss, offset), lo));
+            entries.push((RemoteCpu::calc_linear_address(ss, offset.wrapping_add(1)), hi));
+            offset = offset.wrapping_add(2);
+        }
+        self.load_ram_entries(automatic, &entries)
+    }
+
+    /// Allow writes to `len` bytes at `start` without them being treated as out of bounds, such as
+    /// for a stack or data area. Regions persist across resets.
+    pub fn add_writable_region(&mut self, start: u32, len: u32) {
+        self.writable_regions.push(start..start.saturating_add(len));
+    }
+
+    pub fn clear_writable_regions(&mut self) {
+        self.writable_regions.clear();
+    }
+
+    /// Set the address map applied to host-stepped memory accesses. Writes to scratch regions are
+    /// never out of bounds, writes to ROM are discarded, and accesses to MMIO and excluded regions
+    /// are raised as diagnostics. The map persists across resets.
+    pub fn set_address_map(&mut self, map: AddressMap) {
+        self.address_map = map;
+    }
+
+    pub fn address_map(&self) -> &AddressMap {
+        &self.address_map
+    }
+
+    /// Return true if a byte write to `address` is within the program bounds.
+    pub(crate) fn write_in_bounds(&self, address: u32) -> bool {
+        self.program_range(address as usize).is_some()
+            || self.writable_regions.iter().any(|region| region.contains(&address))
+            || self.address_map.kind_at(address) == RegionKind::Scratch
+    }
+
+    /// Return the out-of-bounds writes recorded since the last reset.
+    pub fn oob_writes(&self) -> &[OobWrite] {
+        &self.oob_writes
+    }
+
+    /// Print a report of the out-of-bounds writes recorded since the last reset.
+    pub fn print_oob_report(&self) {
+        if self.oob_writes.is_empty() {
+            return;
+        }
+        println!("Program made {} out-of-bounds write(s):", self.oob_writes.len());
+        for write in &self.oob_writes {
+            let ivt_note = if write.address < 0x400 { " (IVT)" } else { "" };
+            println!(
+                "  cycle {:>6}: [{:05X}] <- {:02X}{}",
+                write.cycle, write.address, write.value, ivt_note
+            );
+        }
+    }
+
+    /// Return the bus fault injected during the last run, if any.
+    pub fn injected_fault(&self) -> Option<InjectedFault> {
+        self.injected_fault
+    }
+
+    /// Corrupt the data bus if this read is the one selected by [RunOptions::bus_fault].
+    fn inject_bus_fault(&mut self) {
+        let Some(fault) = self.run_opts.bus_fault
+        else {
+            return;
+        };
+        if self.injected_fault.is_some() || self.run_state != RunState::Program {
+            return;
+        }
+
+        let matches = match fault.target {
+            BusFaultTarget::Any => matches!(self.mcycle_state, BusState::CODE | BusState::MEMR),
+            BusFaultTarget::Code => self.mcycle_state == BusState::CODE,
+            BusFaultTarget::MemRead => self.mcycle_state == BusState::MEMR,
+        };
+        if !matches {
+            return;
+        }
+
+        self.bus_fault_reads += 1;
+        if self.bus_fault_reads == fault.nth {
+            let original = self.data_bus;
+            self.data_bus ^= fault.xor_mask;
+            self.injected_fault = Some(InjectedFault {
+                cycle: self.cycle_num,
+                bus_state: self.mcycle_state,
+                address: self.address_latch,
+                original,
+                injected: self.data_bus,
+            });
+            cycle_comment!(
+                self,
+                "FAULT: {:?} [{:05X}] {:04X} served as {:04X}",
+                self.mcycle_state,
+                self.address_latch,
+                original,
+                self.data_bus
+            );
+        }
+    }
+
+    /// Check the bytes of a memory write of the current data width against the program bounds,
+    /// recording any that fall outside. Returns false if the write should be discarded.
+    fn check_write_bounds(&mut self, address: u32, data: u16) -> bool {
+        if self.run_opts.oob_write_policy == OobWritePolicy::Allow {
+            return true;
+        }
+
+        let bytes = data.to_le_bytes();
+        let written: &[(u32, u8)] = match self.data_width {
+            DataWidth::EightLow => &[(address, bytes[0])],
+            DataWidth::EightHigh => &[(address, bytes[1])],
+            DataWidth::Sixteen => &[(address, bytes[0]), (address.wrapping_add(1), bytes[1])],
+            _ => &[],
+        };
+
+        let mut in_bounds = true;
+        for &(byte_address, value) in written {
+            let byte_address = byte_address & ADDRESS_SPACE_MASK as u32;
+            if !self.write_in_bounds(byte_address) {
+                in_bounds = false;
+                self.oob_writes.push(OobWrite {
+                    cycle: self.cycle_num,
+                    address: byte_address,
+                    value,
+                });
+            }
+        }
+
+        if in_bounds {
+            return true;
+        }
+        cycle_comment!(self, "OOB: write to [{:05X}] is outside program bounds", address);
+        let severity = match self.run_opts.oob_write_policy {
+            OobWritePolicy::Fault => DiagnosticSeverity::Error,
+            _ => DiagnosticSeverity::Warning,
+        };
+        self.diagnostic(
+            severity,
+            DiagnosticCategory::OobWrite,
+            format!("Write to [{:05X}] is outside program bounds.", address),
+        );
+        if self.run_opts.oob_write_policy == OobWritePolicy::Fault {
+            self.oob_fault = true;
+            return false;
+        }
+        true
+    }
+
+    /// Check a memory read at `address` against the address map. Returns false if the read should
+    /// see an open bus instead of memory.
+    fn check_map_read(&mut self, address: u32) -> bool {
+        match self.address_map.kind_at(address) {
+            RegionKind::Mmio => {
+                self.diagnostic(
+                    DiagnosticSeverity::Info,
+                    DiagnosticCategory::AddressMap,
+                    format!("Read from MMIO region at [{:05X}].", address),
+                );
+                true
+            }
+            RegionKind::Excluded => {
+                cycle_comment!(self, "MAP: read from excluded region at [{:05X}]", address);
+                self.diagnostic(
+                    DiagnosticSeverity::Error,
+                    DiagnosticCategory::AddressMap,
+                    format!("Read from excluded region at [{:05X}].", address),
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Check a memory write at `address` against the address map. Returns false if the write
+    /// should be discarded.
+    fn check_map_write(&mut self, address: u32) -> bool {
+        match self.address_map.kind_at(address) {
+            RegionKind::Rom => {
+                cycle_comment!(self, "MAP: write to ROM at [{:05X}] discarded", address);
+                self.diagnostic(
+                    DiagnosticSeverity::Warning,
+                    DiagnosticCategory::AddressMap,
+                    format!("Write to ROM at [{:05X}] discarded.", address),
+                );
+                false
+            }
+            RegionKind::Mmio => {
+                self.diagnostic(
+                    DiagnosticSeverity::Info,
+                    DiagnosticCategory::AddressMap,
+                    format!("Write to MMIO region at [{:05X}].", address),
+                );
+                true
+            }
+            RegionKind::Excluded => {
+                cycle_comment!(self, "MAP: write to excluded region at [{:05X}] discarded", address);
+                self.diagnostic(
+                    DiagnosticSeverity::Error,
+                    DiagnosticCategory::AddressMap,
+                    format!("Write to excluded region at [{:05X}] discarded.", address),
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Count a memory transfer made with an invalid data width, and apply the
+    /// [InvalidWidthPolicy].
+    fn invalid_width(&mut self) {
+        self.invalid_width_cycles += 1;
+        cycle_comment!(
+            self,
+            "Invalid width: BHE inactive with odd A0 at [{:05X}]",
+            self.address_latch
+        );
+
+        let severity = match self.run_opts.invalid_width_policy {
+            InvalidWidthPolicy::Record => DiagnosticSeverity::Warning,
+            InvalidWidthPolicy::OpenBus => DiagnosticSeverity::Info,
+            InvalidWidthPolicy::Fault => {
+                self.invalid_width_fault = true;
+                DiagnosticSeverity::Error
+            }
+        };
+        self.diagnostic(
+            severity,
+            DiagnosticCategory::InvalidWidth,
+            format!(
+                "Bus transfer at [{:05X}] with BHE inactive and A0 odd.",
+                self.address_latch
+            ),
+        );
+    }
+
+    /// Return true if the CPU is driving the data bus this cycle: with an address on a multiplexed
+    /// bus while ALE is active, or with data during a write.
+    fn cpu_driving_bus(&self) -> bool {
+        let write_bits = ServerCycleState::COMMAND_MWTC_BIT
+            | ServerCycleState::COMMAND_AMWC_BIT
+            | ServerCycleState::COMMAND_IOWC_BIT
+            | ServerCycleState::COMMAND_AIOWC_BIT;
+        let address_phase = self.cpu_type.has_multiplexed_bus() && (self.pins & ServerCycleState::PIN_ALE != 0);
+        address_phase || (self.command_status & write_bits) != write_bits
+    }
+
+    /// Put `data_bus` on the bus for the CPU to read, unless the CPU is driving it and the
+    /// [BusContentionPolicy] says to hold off.
+    fn drive_data_bus(&mut self) -> Result<(), RemoteCpuError> {
+        if self.cpu_driving_bus() && !self.bus_contention() {
+            return Ok(());
+        }
+        self.client.write_data_bus(self.data_bus)?;
+        self.bus_driven = true;
+        Ok(())
+    }
+
+    /// Count a cycle where the host is about to drive the bus while the CPU drives it, and apply
+    /// the [BusContentionPolicy]. Returns false if the host should not drive the bus.
+    fn bus_contention(&mut self) -> bool {
+        self.contention_cycles += 1;
+        cycle_comment!(self, "Contention: host drove the bus against the CPU");
+
+        let severity = match self.run_opts.bus_contention_policy {
+            BusContentionPolicy::Record => DiagnosticSeverity::Warning,
+            BusContentionPolicy::Fault => {
+                self.contention_fault = true;
+                DiagnosticSeverity::Error
+            }
+        };
+        self.diagnostic(
+            severity,
+            DiagnosticCategory::BusContention,
+            format!(
+                "Host drove the data bus at [{:05X}] while the CPU was driving it.",
+                self.address_latch
+            ),
+        );
+        !self.contention_fault
+    }
+
+    /// Count a read cycle with nothing driving the bus, and apply the [BusContentionPolicy].
+    fn bus_float(&mut self) {
+        self.float_cycles += 1;
+        cycle_comment!(self, "Float: read with nothing driving the bus");
 
-        log::debug!(
-            "Program mounted! Start addr: [{:05X}] end addr: [{:05X}]",
-            self.start_addr,
-            self.end_addr
+        let severity = match self.run_opts.bus_contention_policy {
+            BusContentionPolicy::Record => DiagnosticSeverity::Warning,
+            BusContentionPolicy::Fault => {
+                self.float_fault = true;
+                DiagnosticSeverity::Error
+            }
+        };
+        self.diagnostic(
+            severity,
+            DiagnosticCategory::BusFloat,
+            format!("Read at [{:05X}] with nothing driving the data bus.", self.address_latch),
         );
-        Ok(true)
     }
 
-    pub fn set_program_bounds(&mut self, start: usize, end: usize) {
-        self.start_addr = start;
-        self.end_addr = end;
+    /// Check the address of the first byte of an instruction just popped from the queue against the
+    /// address the previous instruction predicts: its first byte plus the number of bytes popped for
+    /// it. A flush clears the prediction, as the next instruction starts at a jump target.
+    fn check_queue_fetch(&mut self) {
+        if let Some(start) = self.queue_instruction_addr {
+            let expected = start.wrapping_add(self.queue_fetch_n as u32 + 1);
+            if self.queue_fetch_addr != expected {
+                cycle_comment!(
+                    self,
+                    "Queue: instruction at [{:05X}], expected [{:05X}]",
+                    self.queue_fetch_addr,
+                    expected
+                );
+                let severity = if self.run_opts.strict_queue {
+                    self.queue_fault = Some((self.queue_fetch_addr, expected));
+                    DiagnosticSeverity::Error
+                }
+                else {
+                    DiagnosticSeverity::Warning
+                };
+                self.diagnostic(
+                    severity,
+                    DiagnosticCategory::QueueFetch,
+                    format!(
+                        "Queue fetch at [{:05X}], expected [{:05X}] from {} byte(s) at [{:05X}].",
+                        self.queue_fetch_addr,
+                        expected,
+                        self.queue_fetch_n as u32 + 1,
+                        start
+                    ),
+                );
+            }
+        }
+        self.queue_instruction_addr = Some(self.queue_fetch_addr);
+    }
+
+    /// Record a diagnostic on the current cycle, and log it.
+    fn diagnostic(&mut self, severity: DiagnosticSeverity, category: DiagnosticCategory, message: String) {
+        match severity {
+            DiagnosticSeverity::Info => log::info!("{}", message),
+            DiagnosticSeverity::Warning => log::warn!("{}", message),
+            DiagnosticSeverity::Error => log::error!("{}", message),
+        }
+        self.diagnostics.push(Diagnostic {
+            severity,
+            cycle: self.cycle_num,
+            category,
+            message,
+        });
+    }
+
+    /// Return the diagnostics raised during the last run, in order.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
 
     /// Set up the virtual memory space's Interrupt Vector Table
     pub fn setup_ivt(&mut self) {
         // Populate the IVR with pointers to two-byte ISRs that simply contain an IRET and a NOP for alignment.
-        for i in 0..256 {
-            // Calculate address of ISR for each IVT entry
-            let table_offset: usize = i * 4;
-
-            // Write offset first
-            self.write_u16(table_offset, (table_offset / 2) as u16);
-            // Write segment next
-            self.write_u16(table_offset + 2, ISR_SEGMENT);
-
-            // Write ISR routine
-            let isr_address = RemoteCpu::calc_linear_address(ISR_SEGMENT, (table_offset / 2) as u16);
-
-            self.memory[isr_address as usize] = OPCODE_IRET;
-            self.memory[(isr_address + 1) as usize] = OPCODE_NOP;
-        }
+        self.install_ivt(Ivt::default());
+    }
 
-        if self.do_emu8080 && self.cpu_type.has_8080_emulation() {
-            self.setup_emulation_ivt();
-        }
+    /// Return the interrupt vector table last installed.
+    pub fn ivt(&self) -> &Ivt {
+        &self.ivt
     }
 
     /// Set up the IVT entry for i8080 emulation mode.
-    pub fn setup_emulation_ivt(&mut self) {
+    pub(crate) fn setup_emulation_ivt(&mut self) {
         let table_offset = BRKEM_INT as usize * 4;
 
         // Write offset first
@@ -528,16 +1586,8 @@ impl RemoteCpu<'_> {
     }
 
     /// Return true if this address is an ISR
-    pub fn is_isr_address(&self, address: u32) -> bool {
-        let isr_start = RemoteCpu::calc_linear_address(ISR_SEGMENT, 0);
-        let isr_end = RemoteCpu::calc_linear_address(ISR_SEGMENT, 256 * 4);
-
-        if address >= isr_start && address < isr_end {
-            true
-        }
-        else {
-            false
-        }
+    pub(crate) fn is_isr_address(&self, address: u32) -> bool {
+        self.ivt.range().contains(&address)
     }
 
     pub fn write_u8(&mut self, address: usize, byte: u8) {
@@ -632,20 +1682,18 @@ impl RemoteCpu<'_> {
                         .ip
                         .wrapping_sub((preload_pgm.len() + preload_pgm.get_fill_ct()) as u16);
 
-                    if preload_pgm.len() > 0 {
-                        if self.cpu_type.is_intel() {
-                            log::trace!("Adjusting registers for 8088 prefetch...");
-                            // (4x stosb)
+                    let di_steps = self.preload.di_steps();
+                    if di_steps > 0 {
+                        log::trace!("Adjusting registers for {} prefetch...", self.preload);
 
-                            // Adjust DI. This depends on the state of the Direction flag.
-                            if regs.flags & CPU_FLAG_DIRECTION == 0 {
-                                // Direction forward. Decrement DI.
-                                regs.di = regs.di.wrapping_sub(4);
-                            }
-                            else {
-                                // Direction backwards. Increment DI.
-                                regs.di = regs.di.wrapping_add(4);
-                            }
+                        // Adjust DI. This depends on the state of the Direction flag.
+                        if regs.flags & CPU_FLAG_DIRECTION == 0 {
+                            // Direction forward. Decrement DI.
+                            regs.di = regs.di.wrapping_sub(di_steps);
+                        }
+                        else {
+                            // Direction backwards. Increment DI.
+                            regs.di = regs.di.wrapping_add(di_steps);
                         }
                     }
                 }
@@ -670,35 +1718,7 @@ impl RemoteCpu<'_> {
     pub fn load_registers_from_struct(&mut self, regs: &RemoteCpuRegistersV1) -> bool {
         self.reset(); // CPU is reset on register load
 
-        let mut reg_data: [u8; 28] = [0; 28];
-        reg_data[0] = (regs.ax & 0xFF) as u8;
-        reg_data[1] = (regs.ax >> 8) as u8;
-        reg_data[2] = (regs.bx & 0xFF) as u8;
-        reg_data[3] = (regs.bx >> 8) as u8;
-        reg_data[4] = (regs.cx & 0xFF) as u8;
-        reg_data[5] = (regs.cx >> 8) as u8;
-        reg_data[6] = (regs.dx & 0xFF) as u8;
-        reg_data[7] = (regs.dx >> 8) as u8;
-        reg_data[8] = (regs.ss & 0xFF) as u8;
-        reg_data[9] = (regs.ss >> 8) as u8;
-        reg_data[10] = (regs.sp & 0xFF) as u8;
-        reg_data[11] = (regs.sp >> 8) as u8;
-        reg_data[12] = (regs.flags & 0xFF) as u8;
-        reg_data[13] = (regs.flags >> 8) as u8;
-        reg_data[14] = (regs.ip & 0xFF) as u8;
-        reg_data[15] = (regs.ip >> 8) as u8;
-        reg_data[16] = (regs.cs & 0xFF) as u8;
-        reg_data[17] = (regs.cs >> 8) as u8;
-        reg_data[18] = (regs.ds & 0xFF) as u8;
-        reg_data[19] = (regs.ds >> 8) as u8;
-        reg_data[20] = (regs.es & 0xFF) as u8;
-        reg_data[21] = (regs.es >> 8) as u8;
-        reg_data[22] = (regs.bp & 0xFF) as u8;
-        reg_data[23] = (regs.bp >> 8) as u8;
-        reg_data[24] = (regs.si & 0xFF) as u8;
-        reg_data[25] = (regs.si >> 8) as u8;
-        reg_data[26] = (regs.di & 0xFF) as u8;
-        reg_data[27] = (regs.di >> 8) as u8;
+        let reg_data = regs.to_bytes();
 
         match self
             .client
@@ -709,8 +1729,8 @@ impl RemoteCpu<'_> {
         }
     }
 
-    pub fn update_state(&mut self, cycle: bool) -> Result<(), String> {
-        let cycle_state = self.client.get_cycle_state(cycle).map_err(|e| e.to_string())?;
+    pub(crate) fn update_state(&mut self, cycle: bool) -> Result<(), RemoteCpuError> {
+        let cycle_state = self.client.get_cycle_state(cycle)?;
 
         self.program_state = cycle_state.program_state;
         self.status = cycle_state.cpu_status_bits;
@@ -724,10 +1744,11 @@ impl RemoteCpu<'_> {
 
         self.address_bus = cycle_state.address_bus;
         self.data_bus = cycle_state.data_bus;
+        self.pins = cycle_state.pins;
 
         // Unpack T-cycle from cpu_state.
-        self.t_state =
-            TState::try_from(cycle_state.cpu_state_bits & 0x0F).map_err(|e| format!("Invalid T-state: {}", e))?;
+        self.t_state = TState::try_from(cycle_state.cpu_state_bits & 0x0F)
+            .map_err(|_| CpuClientError::BadValue(ServerCommand::CmdGetCycleState))?;
 
         // BHE pin is packed into 8288 command status byte. Use it to set the
         // data bus width now.
@@ -755,16 +1776,97 @@ impl RemoteCpu<'_> {
     }
 
     /// Return true if the current address latch is within execution bounds.
-    pub fn address_in_bounds(&self) -> bool {
-        let addr = self.address_latch as usize;
-        self.is_isr_address(self.address_latch) || ((addr >= self.start_addr) && (addr < self.end_addr))
+    pub(crate) fn address_in_bounds(&self) -> bool {
+        self.is_isr_address(self.address_latch) || self.program_range(self.address_latch as usize).is_some()
+    }
+
+    /// Whether a program code fetch at the latched address should be served from the program.
+    /// Once the run's end condition is met, every fetch is out of bounds.
+    fn fetch_in_bounds(&self) -> bool {
+        !self.end_reached && self.address_in_bounds()
+    }
+
+    /// Check the run's [EndCondition] against the current cycle.
+    fn check_end_condition(&mut self) {
+        if self.end_reached {
+            return;
+        }
+        let reached = match self.run_opts.end_condition {
+            None => false,
+            Some(EndCondition::Iret(vector)) => {
+                self.have_queue_status
+                    && get_queue_op!(self.status) == QueueOp::First
+                    && self.queue_byte == OPCODE_IRET
+                    && self.ivt.vector_at(self.queue_fetch_addr) == Some(vector)
+            }
+            Some(EndCondition::PortWrite(port)) => {
+                (self.command_status & ServerCycleState::COMMAND_IOWC_BIT) == 0
+                    && (self.address_latch & 0xFFFF) == port as u32
+            }
+            Some(EndCondition::Fetch(address)) => {
+                self.t_state == TState::T1 && self.mcycle_state == BusState::CODE && self.address_latch == address
+            }
+            Some(EndCondition::Cycles(cycles)) => self.cycle_num.wrapping_sub(self.end_start_cycle) >= cycles,
+        };
+        if reached {
+            cycle_comment!(self, "End condition met: {}", self.run_opts.end_condition.unwrap());
+            self.end_reached = true;
+        }
+    }
+
+    /// Return where the byte fetched from `addr` came from, given the data type of the fetch.
+    pub(crate) fn fetch_provenance(&self, dtype: QueueDataType, addr: u32) -> QueueProvenance {
+        match dtype {
+            QueueDataType::Preload => QueueProvenance::Preload,
+            QueueDataType::EmuEnter => QueueProvenance::EmuEnter,
+            QueueDataType::Fill => QueueProvenance::Fill,
+            QueueDataType::Finalize => QueueProvenance::OutOfBounds,
+            QueueDataType::Program if self.is_isr_address(addr) => QueueProvenance::IsrBody,
+            QueueDataType::Program => match self.program_byte_index(addr as usize) {
+                Some(index) => QueueProvenance::ProgramByteIndex(index),
+                None => QueueProvenance::OutOfBounds,
+            },
+        }
+    }
+
+    /// Push the code fetch on the data bus into the queue.
+    fn queue_push(&mut self, dtype: QueueDataType) {
+        let addr = self.address_latch;
+        let mut provenance = [
+            self.fetch_provenance(dtype, addr),
+            self.fetch_provenance(dtype, addr.wrapping_add(1)),
+        ];
+        if dtype == QueueDataType::Preload {
+            // A byte fetch at an odd address arrives on the high lane.
+            let fill = match self.data_width {
+                DataWidth::EightHigh => [self.preload_fill[1], false],
+                _ => self.preload_fill,
+            };
+            for (provenance, fill) in provenance.iter_mut().zip(fill) {
+                if fill {
+                    *provenance = QueueProvenance::PreloadFill;
+                }
+            }
+            self.preload_fill = [false; 2];
+        }
+        self.queue.push(self.data_bus, self.data_width, dtype, addr, provenance);
     }
 
-    pub fn in_preload(&self) -> bool {
+    /// Return the provenance of the byte most recently read from the queue.
+    pub fn queue_provenance(&self) -> QueueProvenance {
+        self.queue_provenance
+    }
+
+    /// Return the bytes in the queue, oldest first, with the provenance of each.
+    pub fn queue_entries(&self) -> Vec<(u8, QueueProvenance)> {
+        self.queue.entries()
+    }
+
+    pub(crate) fn in_preload(&self) -> bool {
         matches!(self.run_state, RunState::Preload)
     }
 
-    pub fn fetch_from_memory(&mut self, address: u32, end_address: u32) -> u16 {
+    pub(crate) fn fetch_from_memory(&mut self, address: u32, end_address: u32) -> u16 {
         let data = self.read_memory(address);
 
         match self.data_width {
@@ -772,9 +1874,10 @@ impl RemoteCpu<'_> {
             DataWidth::Sixteen => {
                 // Did we read past end_address?
                 if address >= (end_address.wrapping_sub(1)) {
-                    // Replace high byte with NOP.
+                    // Replace high byte with the filler.
                     self.program_end_offset += 1;
-                    (data & 0xFF) | ((self.nop() as u16) << 8)
+                    cycle_comment!(self, "Fill {:02X} past end of program", self.fill_byte);
+                    (data & 0xFF) | ((self.fill_byte as u16) << 8)
                 }
                 else {
                     data
@@ -786,7 +1889,7 @@ impl RemoteCpu<'_> {
 
     /// Return a NOP instruction for the current emulation mode.
     #[inline]
-    pub fn nop(&self) -> u8 {
+    pub(crate) fn nop(&self) -> u8 {
         if self.do_emu8080 {
             OPCODE_NOP80
         }
@@ -806,16 +1909,16 @@ impl RemoteCpu<'_> {
                 self.memory[self.address_latch as usize],
                 self.memory[((self.address_latch + 1) as usize) & ADDRESS_SPACE_MASK],
             ]),
-            _ => {
-                log::error!("read_memory(): Invalid data width!");
-                0
-            }
+            DataWidth::Invalid => match self.run_opts.invalid_width_policy {
+                InvalidWidthPolicy::OpenBus => 0xFFFF,
+                _ => 0,
+            },
         }
     }
 
     // Write a data bus value to memory
     // This function is size-aware. For an 8-bit write, the upper byte is ignored.
-    pub fn write_memory(&mut self, address: u32, data: u16) {
+    pub(crate) fn write_memory(&mut self, address: u32, data: u16) {
         let mem_idx = address as usize & ADDRESS_SPACE_MASK;
         match self.data_width {
             DataWidth::EightLow => {
@@ -829,20 +1932,164 @@ impl RemoteCpu<'_> {
                 self.memory[mem_idx] = bytes[0];
                 self.memory[(mem_idx + 1) & ADDRESS_SPACE_MASK] = bytes[1];
             }
-            _ => {
-                log::error!("write_memory(): Invalid data width!");
+            DataWidth::Invalid => {
+                // Neither half of the bus is driven, so there is nothing to write.
+            }
+        }
+    }
+
+    /// Return true if a memory write at `address` of the current data width lands on a byte that is
+    /// already in the prefetch queue.
+    fn write_hits_queue(&self, address: u32) -> bool {
+        match self.data_width {
+            DataWidth::Sixteen => self.queue.contains_addr(address) || self.queue.contains_addr(address + 1),
+            _ => self.queue.contains_addr(address),
+        }
+    }
+
+    /// Track the FPU state for the instruction just fetched from the queue.
+    fn fpu_instruction_start(&mut self) {
+        self.esc_operand_read = false;
+        self.wait_start = (self.opcode == OPCODE_WAIT).then_some((self.cycle_num, self.queue_fetch_addr));
+
+        let stub_start = self.fpu_stub.as_ref().map(|(stub, _)| stub.start as u32);
+        if !self.fpu_saving && stub_start == Some(self.queue_fetch_addr) {
+            self.fpu_saving = true;
+            cycle_comment!(self, "FPU: saving state");
+        }
+    }
+
+    /// Flag a memory transfer made by the FPU. During an ESC instruction the CPU makes one read of
+    /// the operand; any other transfer during an ESC or WAIT instruction is made by the FPU.
+    fn note_fpu_transfer(&mut self, read: bool) {
+        if !self.have_fpu {
+            return;
+        }
+        if is_esc_opcode(self.opcode) && read && !self.esc_operand_read {
+            self.esc_operand_read = true;
+        }
+        else if is_esc_opcode(self.opcode) || self.opcode == OPCODE_WAIT {
+            self.fpu_cycle = true;
+        }
+    }
+
+    /// Record a write of the FNSAVE state block.
+    fn capture_fpu_save(&mut self, address: u32, data: u16) {
+        let bytes = data.to_le_bytes();
+        match self.data_width {
+            DataWidth::EightLow => {
+                self.fpu_save_bytes.insert(address, bytes[0]);
+            }
+            DataWidth::EightHigh => {
+                self.fpu_save_bytes.insert(address, bytes[1]);
+            }
+            DataWidth::Sixteen => {
+                self.fpu_save_bytes.insert(address, bytes[0]);
+                self.fpu_save_bytes.insert(address.wrapping_add(1), bytes[1]);
+            }
+            DataWidth::Invalid => {}
+        }
+    }
+
+    /// Append [FPU_SAVE_PGM] to the end of the program for this run, if an FPU is present.
+    fn mount_fpu_save(&mut self) {
+        if !self.have_fpu {
+            self.diagnostic(
+                DiagnosticSeverity::Warning,
+                DiagnosticCategory::Fpu,
+                "FPU state requested, but no FPU was detected.".to_string(),
+            );
+            return;
+        }
+        let Some(range) = self.program_ranges.last_mut()
+        else {
+            return;
+        };
+        let stub = range.end..range.end + FPU_SAVE_PGM.len();
+        if stub.end > self.memory.len() {
+            return;
+        }
+        range.end = stub.end;
+        let saved = self.memory[stub.clone()].to_vec();
+        self.memory[stub.clone()].copy_from_slice(&FPU_SAVE_PGM);
+        self.fpu_stub = Some((stub, saved));
+    }
+
+    /// Remove the FNSAVE program appended by [RemoteCpu::mount_fpu_save], restoring the memory it
+    /// replaced.
+    fn unmount_fpu_save(&mut self) {
+        if let Some((stub, saved)) = self.fpu_stub.take() {
+            self.memory[stub.clone()].copy_from_slice(&saved);
+            if let Some(range) = self.program_ranges.last_mut() {
+                range.end = stub.start;
             }
         }
     }
 
-    pub fn cycle(&mut self) -> bool {
+    /// Return the FPU state captured during the run, if the FNSAVE program ran.
+    fn fpu_state(&mut self) -> Option<FpuState> {
+        self.fpu_stub.as_ref()?;
+        if !self.fpu_saving {
+            self.diagnostic(
+                DiagnosticSeverity::Warning,
+                DiagnosticCategory::Fpu,
+                "The FNSAVE program did not run, as the program did not end by running off its end.".to_string(),
+            );
+            return None;
+        }
+        let first = self.fpu_save_bytes.keys().next().copied().unwrap_or_default();
+        let last = self.fpu_save_bytes.keys().next_back().copied().unwrap_or_default();
+        if self.fpu_save_bytes.len() != FPU_STATE_LEN || (last - first) as usize != FPU_STATE_LEN - 1 {
+            self.diagnostic(
+                DiagnosticSeverity::Warning,
+                DiagnosticCategory::Fpu,
+                format!(
+                    "Captured {} bytes of the FPU state, expected {}.",
+                    self.fpu_save_bytes.len(),
+                    FPU_STATE_LEN
+                ),
+            );
+            return None;
+        }
+        let bytes: Vec<u8> = self.fpu_save_bytes.values().copied().collect();
+        FpuState::from_bytes(&bytes)
+    }
+
+    /// Return the number of self-modifying writes seen since the last reset - writes that landed
+    /// on bytes already in the prefetch queue.
+    pub fn smc_write_count(&self) -> u32 {
+        self.smc_writes
+    }
+
+    /// Return the number of memory transfer cycles made with an invalid data width since the last
+    /// reset. See [InvalidWidthPolicy].
+    pub fn invalid_width_count(&self) -> u32 {
+        self.invalid_width_cycles
+    }
+
+    /// Return the number of cycles with bus contention and with a floating bus since the last
+    /// reset. See [BusContentionPolicy].
+    pub fn bus_contention_counts(&self) -> (u32, u32) {
+        (self.contention_cycles, self.float_cycles)
+    }
+
+    pub(crate) fn cycle(&mut self) -> Result<(), RemoteCpuError> {
+        let last_program_state = self.program_state;
         match self.update_state(true) {
             Ok(_) => {}
             Err(e) => {
-                log::warn!("Failed to cycle CPU!");
+                self.diagnostic(
+                    DiagnosticSeverity::Warning,
+                    DiagnosticCategory::Server,
+                    format!("Failed to cycle CPU: {}", e),
+                );
             }
         }
 
+        if self.program_state == ProgramState::EmuEnter && last_program_state != ProgramState::EmuEnter {
+            self.set_run_state(RunState::EmuEnter);
+        }
+
         match self.t_state {
             TState::Ti => {
                 // Nothing to do on idle states.
@@ -857,9 +2104,7 @@ impl RemoteCpu<'_> {
                 if self.wait_state_opt > 0 {
                     self.nready_states = self.wait_state_opt;
                     //log::debug!("Deasserting READY to emulate wait states...");
-                    self.client
-                        .write_pin(CpuPin::READY, false)
-                        .expect("Failed to write READY pin!");
+                    self.client.write_pin(CpuPin::READY, false)?;
                 }
             }
             TState::T3 => {
@@ -868,9 +2113,7 @@ impl RemoteCpu<'_> {
 
                     if self.nready_states == 0 {
                         // Reassert READY line
-                        self.client
-                            .write_pin(CpuPin::READY, true)
-                            .expect("Failed to write READY pin!");
+                        self.client.write_pin(CpuPin::READY, true)?;
                     }
                 }
             }
@@ -880,9 +2123,7 @@ impl RemoteCpu<'_> {
 
                     if self.nready_states == 0 {
                         // Reassert READY line
-                        self.client
-                            .write_pin(CpuPin::READY, true)
-                            .expect("Failed to write READY pin!");
+                        self.client.write_pin(CpuPin::READY, true)?;
                     }
                 }
             }
@@ -891,26 +2132,20 @@ impl RemoteCpu<'_> {
                     // We completed a code fetch, so add to prefetch queue
 
                     match self.run_state {
+                        _ if self.program_state == ProgramState::EmuEnter => {
+                            // The server is feeding the CPU the BRKEM program.
+                            self.queue_push(QueueDataType::EmuEnter);
+                        }
                         RunState::Preload => {
                             if self.have_preload_pgm() {
                                 // Preload program is being fetched.
-                                self.queue.push(
-                                    self.data_bus,
-                                    self.data_width,
-                                    QueueDataType::Preload,
-                                    self.address_latch,
-                                );
+                                self.queue_push(QueueDataType::Preload);
                             }
                             else if self.address_in_bounds() {
                                 log::trace!("Preload: program pushed to queue: {}", self.data_bus_str());
                                 // We are in preloading state, but have exhausted preload program. Mark the next byte to be put
                                 // in queue to signal start of main program.
-                                self.queue.push(
-                                    self.data_bus,
-                                    self.data_width,
-                                    QueueDataType::Program,
-                                    self.address_latch,
-                                );
+                                self.queue_push(QueueDataType::Program);
                             }
                             else {
                                 log::trace!(
@@ -920,18 +2155,12 @@ impl RemoteCpu<'_> {
                                 );
                                 // We are in preloading state, but have exhausted preload program. Mark the next byte to be put
                                 // in queue to signal start of main program.
-                                self.queue.push(
-                                    self.data_bus,
-                                    self.data_width,
-                                    QueueDataType::Finalize,
-                                    self.address_latch,
-                                );
+                                self.queue_push(QueueDataType::Finalize);
                             }
                         }
-                        _ if self.address_in_bounds() => {
+                        _ if self.fetch_in_bounds() => {
                             // Normal fetch within program boundaries
-                            self.queue
-                                .push(self.data_bus, self.data_width, self.data_type, self.address_latch);
+                            self.queue_push(self.data_type);
                         }
                         _ => {
                             // We have fetched past the end of the current program, so push a flagged NOP into the queue.
@@ -941,12 +2170,7 @@ impl RemoteCpu<'_> {
                                 self.address_latch
                             );
                             // If we did not enter emulation, then we can immediately move to finalize.
-                            self.queue.push(
-                                self.data_bus,
-                                self.data_width,
-                                QueueDataType::Finalize,
-                                self.address_latch,
-                            );
+                            self.queue_push(QueueDataType::Finalize);
                         }
                     }
                 }
@@ -955,43 +2179,69 @@ impl RemoteCpu<'_> {
 
         if self.program_state == ProgramState::ExecuteDone {
             self.cycle_num += 1;
-            return true;
+            return Ok(());
         }
 
         if self.ale() {
             if self.t_state != TState::T1 {
-                log::warn!("ALE on non-T1 cycle state! CPU desynchronized.");
+                self.diagnostic(
+                    DiagnosticSeverity::Warning,
+                    DiagnosticCategory::Desync,
+                    "ALE on non-T1 cycle state! CPU desynchronized.".to_string(),
+                );
             }
 
-            let addr = self.client.read_address().expect("Failed to get address bus!");
+            let addr = self.client.read_address()?;
             self.address_bus = addr;
             self.address_latch = addr;
+            self.io_write_served = false;
+            self.pcb_write_claimed = false;
         }
         else {
-            self.address_bus = self.client.read_address().expect("Failed to get address bus!");
+            self.address_bus = self.client.read_address()?;
         }
         //log::trace!("state: {:?}", self.program_state);
 
         // Do reads & writes if we are in execute state.
         if self.program_state == ProgramState::Execute {
+            self.bus_driven = false;
+            self.fpu_cycle = false;
+            self.pcb_cycle = false;
             if let BusState::HALT = self.cpu_type.decode_status(self.status) {
                 cycle_comment!(self, "CPU halted!");
                 self.halted = true;
             }
 
+            let mem_read =
+                ((self.command_status & ServerCycleState::COMMAND_MRDC_BIT) == 0) && (self.t_state == TState::T2);
+            let mem_write = (self.command_status & ServerCycleState::COMMAND_MWTC_BIT) == 0;
+            if (mem_read || mem_write) && matches!(self.data_width, DataWidth::Invalid) {
+                self.invalid_width();
+            }
+
             // MRDC status is active-low.
-            if ((self.command_status & ServerCycleState::COMMAND_MRDC_BIT) == 0) && (self.t_state == TState::T2) {
+            if mem_read {
                 let mut write_store = false;
                 let a0 = self.a0();
+                let pcb_read = self.mcycle_state == BusState::MEMR && self.pcb_access(true, false);
 
                 match self.mcycle_state {
+                    BusState::MEMR if pcb_read => {
+                        // The CPU reads its peripheral control block internally.
+                    }
                     BusState::MEMR => {
                         // CPU is reading data from bus. Provide value from memory.
                         log::trace!("Reading memory at address: [{:05X}]", self.address_latch);
-                        self.data_bus = self.read_memory(self.address_latch);
-                        self.client
-                            .write_data_bus(self.data_bus)
-                            .expect("Failed to write data bus.");
+                        self.note_fpu_transfer(true);
+                        self.data_bus = if self.check_map_read(self.address_latch) {
+                            self.read_memory(self.address_latch)
+                        }
+                        else {
+                            0xFFFF
+                        };
+                        self.memory_hook_read();
+                        self.inject_bus_fault();
+                        self.drive_data_bus()?;
                     }
                     BusState::CODE => {
                         // CPU is reading code from bus. Provide value from memory if we are not past the
@@ -1004,6 +2254,7 @@ impl RemoteCpu<'_> {
                                 program.read_program(a0, &mut self.code_stream, QueueDataType::Program);
                                 if !self.code_stream.is_empty() {
                                     let value = self.code_stream.pop_data_bus();
+                                    self.preload_fill = value.fill_bytes();
                                     log::trace!("Writing [Preload] program: [{:0X}]", value.bus_value());
                                     self.data_bus = value.bus_value();
                                     true
@@ -1016,13 +2267,19 @@ impl RemoteCpu<'_> {
                         };
 
                         if !bus_written {
-                            if self.address_in_bounds() {
-                                // Within program range.
-                                let value = self.fetch_from_memory(self.address_latch, self.end_addr as u32);
+                            if self.fetch_in_bounds() {
+                                // Within program range. Only the last range is followed by fill bytes; earlier
+                                // ranges end in a trampoline to the next one.
+                                let end_addr = self
+                                    .program_ranges
+                                    .last()
+                                    .filter(|range| range.contains(&(self.address_latch as usize)))
+                                    .map_or(ADDRESS_SPACE, |range| range.end);
+                                let value = self.fetch_from_memory(self.address_latch, end_addr as u32);
                                 log::trace!(
                                     "Reading [User] program: [{:0X}] end_addr: [{:05X}]",
                                     value,
-                                    self.end_addr
+                                    end_addr
                                 );
                                 self.data_bus = value;
                             }
@@ -1037,51 +2294,111 @@ impl RemoteCpu<'_> {
                         if write_store {
                             // Execute prefetch_store command instead of writing to the data bus ourselves.
                             log::trace!("Writing cpu_server store program byte to bus");
-                            self.client
-                                .prefetch_store()
-                                .expect("Failed to execute CmdPrefetchStore");
+                            self.client.prefetch_store()?;
+                            self.bus_driven = true;
                         }
                         else {
                             if !self.address_in_bounds() {
-                                log::warn!("Writing user program out of bounds. CPU desynchronized.");
+                                self.diagnostic(
+                                    DiagnosticSeverity::Warning,
+                                    DiagnosticCategory::ProgramBounds,
+                                    "Writing user program out of bounds. CPU desynchronized.".to_string(),
+                                );
                             }
+                            self.inject_bus_fault();
                             log::trace!("Writing [User] program word to bus: [{:04X}]", self.data_bus);
-                            self.client
-                                .write_data_bus(self.data_bus)
-                                .expect("Failed to write data bus.");
+                            self.drive_data_bus()?;
                         }
                     }
                     _ => {
                         // Handle other states?
-                        log::warn!("Unhandled bus state: {:?}", self.mcycle_state);
+                        self.diagnostic(
+                            DiagnosticSeverity::Warning,
+                            DiagnosticCategory::UnhandledBusState,
+                            format!("Unhandled bus state: {:?}", self.mcycle_state),
+                        );
                     }
                 }
             }
 
+            // IORC status is active-low. Only ports with a device attached are served.
+            let io_read =
+                ((self.command_status & ServerCycleState::COMMAND_IORC_BIT) == 0) && (self.t_state == TState::T2);
+            if io_read && !self.pcb_access(false, false) && self.io_device_read() {
+                self.drive_data_bus()?;
+            }
+
             // MWTC status is active-low.
-            if (self.command_status & ServerCycleState::COMMAND_MWTC_BIT) == 0 {
+            if mem_write {
                 // CPU is writing to memory. Get data bus from CPU and write to host memory.
-                self.data_bus = self.client.read_data_bus().expect("Failed to read data bus.");
+                self.data_bus = self.client.read_data_bus()?;
+                self.note_fpu_transfer(false);
+
+                if self.fpu_saving {
+                    // The FPU is writing its state block. Capture it rather than write it to memory.
+                    self.capture_fpu_save(self.address_latch, self.data_bus);
+                }
+                else if self.pcb_write_claimed {
+                    // The block took this write on its first cycle. It may have moved since, so it
+                    // is not looked up again.
+                    self.pcb_cycle = true;
+                }
+                else if self.pcb_access(true, true) {
+                    // The CPU writes its peripheral control block internally.
+                    self.pcb_write_claimed = true;
+                }
+                else {
+                    if self.check_map_write(self.address_latch)
+                        && self.check_write_bounds(self.address_latch, self.data_bus)
+                        && self.memory_hook_write()
+                    {
+                        self.write_memory(self.address_latch, self.data_bus);
+                    }
 
-                self.write_memory(self.address_latch, self.data_bus);
+                    // The CPU will execute whatever it already prefetched, not what we just wrote.
+                    if self.write_hits_queue(self.address_latch) {
+                        self.smc_writes += 1;
+                        cycle_comment!(
+                            self,
+                            "SMC: write to [{:05X}] hits prefetched code, queue holds stale bytes",
+                            self.address_latch
+                        );
+                    }
+                }
             }
 
             // IOWC status is active-low.
             if (self.command_status & ServerCycleState::COMMAND_IOWC_BIT) == 0 {
                 // CPU is writing to IO address.
 
-                self.data_bus = self.client.read_data_bus().expect("Failed to read data bus.");
+                self.data_bus = self.client.read_data_bus()?;
 
                 // Check if this is our special port address
                 if self.address_latch == 0x000FF {
                     cycle_comment!(self, "IO write to INTR trigger!");
 
                     // Set INTR line high
-                    self.client
-                        .write_pin(CpuPin::INTR, true)
-                        .expect("Failed to set INTR line high.");
+                    self.client.write_pin(CpuPin::INTR, true)?;
                     self.intr = true;
                 }
+                else if !self.io_write_served {
+                    // The write command spans several cycles; pass the write on once.
+                    self.io_write_served = true;
+                    if !self.pcb_access(false, true) {
+                        self.io_device_write();
+                    }
+                }
+            }
+
+            // Reads are served on T2. A read strobe with nothing driving the bus leaves the CPU to
+            // sample whatever the bus floats to.
+            let read_bits = ServerCycleState::COMMAND_MRDC_BIT | ServerCycleState::COMMAND_IORC_BIT;
+            if (self.command_status & read_bits) != read_bits
+                && self.t_state == TState::T2
+                && !self.bus_driven
+                && !self.pcb_cycle
+            {
+                self.bus_float();
             }
         }
 
@@ -1092,19 +2409,19 @@ impl RemoteCpu<'_> {
             match q_op {
                 QueueOp::First | QueueOp::Subsequent => {
                     // We fetched a byte from the queue last cycle
-                    (self.queue_byte, self.queue_type, self.queue_fetch_addr) = self.queue.pop();
+                    (self.queue_byte, self.queue_type, self.queue_fetch_addr, self.queue_provenance) = self.queue.pop();
                     if q_op == QueueOp::First {
                         // First byte of instruction fetched.
+                        self.check_queue_fetch();
                         self.queue_first_fetch = true;
                         self.queue_fetch_n = 0;
                         self.opcode = self.queue_byte;
+                        self.fpu_instruction_start();
 
                         // Was NMI triggered?
                         if self.do_nmi {
                             cycle_comment!(self, "Setting NMI pin high...");
-                            self.client
-                                .write_pin(CpuPin::NMI, true)
-                                .expect("Failed to write NMI pin!");
+                            self.client.write_pin(CpuPin::NMI, true)?;
                             self.do_nmi = false;
                         }
 
@@ -1119,20 +2436,18 @@ impl RemoteCpu<'_> {
 
                         // Finalize execution if this queue byte was flagged as final
                         if self.queue_type == QueueDataType::Finalize {
-                            self.finalize();
+                            self.finalize()?;
                         }
 
                         // Handle INTR instruction trigger
-                        if !is_group_op(self.queue_byte) {
+                        if !is_group_op(self.queue_byte, self.decode_arch()) {
                             self.instruction_num += 1;
 
                             if self.instruction_num == self.intr_after {
                                 cycle_comment!(self, "Setting INTR high after instruction #{}", self.intr_after);
 
                                 // Set INTR line high
-                                self.client
-                                    .write_pin(CpuPin::INTR, true)
-                                    .expect("Failed to set INTR line high.");
+                                self.client.write_pin(CpuPin::INTR, true)?;
                                 self.intr = true;
                             }
                         }
@@ -1145,6 +2460,7 @@ impl RemoteCpu<'_> {
                 QueueOp::Flush => {
                     // Queue was flushed last cycle
                     self.queue.flush();
+                    self.queue_instruction_addr = None;
                 }
                 _ => {}
             }
@@ -1167,6 +2483,9 @@ impl RemoteCpu<'_> {
         //         self.do_nmi = false;
         //     }
         // }
+        if self.run_state == RunState::Program {
+            self.check_end_condition();
+        }
         self.cycle_num += 1;
 
         // Do cycle-based INTR trigger
@@ -1174,9 +2493,7 @@ impl RemoteCpu<'_> {
             cycle_comment!(self, "Setting INTR high after cycle #{}", self.intr_on_cycle);
 
             // Set INTR line high
-            self.client
-                .write_pin(CpuPin::INTR, true)
-                .expect("Failed to set INTR line high.");
+            self.client.write_pin(CpuPin::INTR, true)?;
             self.intr = true;
         }
 
@@ -1185,14 +2502,16 @@ impl RemoteCpu<'_> {
             cycle_comment!(self, "Setting NMI high after cycle #{}", self.intr_on_cycle);
 
             // Set INTR line high
-            self.client
-                .write_pin(CpuPin::NMI, true)
-                .expect("Failed to set NMI line high.");
+            self.client.write_pin(CpuPin::NMI, true)?;
             self.nmi = true;
         }
 
         if self.cycle_num > CYCLE_LIMIT {
-            log::warn!("Hit cycle limit!");
+            self.diagnostic(
+                DiagnosticSeverity::Warning,
+                DiagnosticCategory::CycleLimit,
+                "Hit cycle limit!".to_string(),
+            );
             match self.client.finalize() {
                 Ok(_) => {
                     log::trace!("Finalized execution!");
@@ -1202,34 +2521,115 @@ impl RemoteCpu<'_> {
                 }
             }
         }
-        true
+        Ok(())
     }
 
-    pub fn finalize(&mut self) {
+    pub(crate) fn finalize(&mut self) -> Result<(), RemoteCpuError> {
         // Save the current queue length - we have to rewind the IP returned by store by this much.
         self.queue_len_at_finalize = self.queue.len() as u8;
-        self.run_state = RunState::Finalize;
+        if self.in_emulation {
+            // The server runs the RETEM program as part of finalizing, so we won't see its cycles.
+            self.set_run_state(RunState::EmuExit);
+            self.in_emulation = false;
+        }
+        self.set_run_state(RunState::Finalize);
         log::trace!("Finalizing execution with {} bytes in queue.", self.queue.len());
         cycle_comment!(self, "Finalizing execution!");
-        self.client.finalize().expect("Failed to finalize!");
+        self.client.finalize()?;
+        Ok(())
+    }
+
+    /// Change the run state, recording the transition.
+    fn set_run_state(&mut self, state: RunState) {
+        if state == self.run_state {
+            return;
+        }
+        log::trace!("Entering [{:?}] run state on cycle {}", state, self.cycle_num);
+        match state {
+            RunState::EmuEnter => cycle_comment!(self, "Entering 8080 emulation"),
+            RunState::EmuExit => cycle_comment!(self, "Exiting 8080 emulation"),
+            _ => {}
+        }
+        self.run_state_changes.push(RunStateChange {
+            cycle: self.cycle_num,
+            from:  self.run_state,
+            to:    state,
+        });
+        self.run_state = state;
+    }
+
+    /// Return the run state transitions of the last run, in order.
+    pub fn run_state_changes(&self) -> &[RunStateChange] {
+        &self.run_state_changes
+    }
+
+    /// Return whether the CPU is executing in 8080 emulation mode.
+    pub fn in_emulation(&self) -> bool {
+        self.in_emulation
+    }
+
+    /// Return the instruction set to decode mnemonics in for the cycle trace.
+    pub(crate) fn decode_arch(&self) -> DecodeArch {
+        if let ServerCpuType::Intel80186(_) | ServerCpuType::Intel80188(_) = self.cpu_type {
+            DecodeArch::Intel80186
+        }
+        else if self.cpu_type.is_intel() {
+            DecodeArch::Intel8088
+        }
+        else {
+            match self.run_state {
+                RunState::Preload | RunState::Program if self.in_emulation => DecodeArch::Intel8080,
+                _ => DecodeArch::NecV20,
+            }
+        }
     }
 
-    pub fn advance_run_state_on_queue_read(&mut self) {
+    pub(crate) fn advance_run_state_on_queue_read(&mut self) {
         match self.run_state {
             RunState::Preload => {
                 if self.queue_type == QueueDataType::Program {
                     log::trace!("Ending preload, entering main Program!");
-                    self.run_state = RunState::Program;
+                    self.set_run_state(RunState::Program);
+                }
+            }
+            RunState::EmuEnter if self.queue_type != QueueDataType::EmuEnter => {
+                // The first byte past the BRKEM program is the first 8080 instruction.
+                self.in_emulation = true;
+                if self.queue_type == QueueDataType::Preload {
+                    log::warn!("Preload program will execute in 8080 emulation mode.");
+                    self.set_run_state(RunState::Preload);
                 }
-                if self.queue_type == QueueDataType::EmuEnter {
-                    panic!("Can't preload into emulation mode!");
+                else {
+                    self.set_run_state(RunState::Program);
                 }
             }
             _ => {}
         }
     }
 
-    pub fn print_cpu_state(&self) {
+    /// Return the bus and queue state of the current cycle.
+    pub fn cycle_record(&self) -> CycleRecord {
+        let q_op = get_queue_op!(self.status);
+        CycleRecord {
+            cycle: self.cycle_num,
+            run_state: self.run_state,
+            ale: self.ale(),
+            address_latch: self.address_latch,
+            address_bus: self.address_bus,
+            bus_state: self.cpu_type.decode_status(self.status),
+            t_state: self.t_state,
+            data_bus: self.data_bus,
+            data_width: transfer_width(self.width, self.address_latch, self.bhe()),
+            reading: is_reading!(self.command_status),
+            writing: is_writing!(self.command_status),
+            queue_op: q_op,
+            queue_byte: matches!(q_op, QueueOp::First | QueueOp::Subsequent).then_some(self.queue_byte),
+            fpu: self.fpu_cycle,
+            comment: self.cycle_comment.clone(),
+        }
+    }
+
+    pub(crate) fn print_cpu_state(&self) {
         println!("{}", self.get_cpu_state_str())
     }
 
@@ -1247,7 +2647,7 @@ impl RemoteCpu<'_> {
         self.address_latch & 0x1 != 0
     }
 
-    pub fn get_cpu_state_str(&self) -> String {
+    pub(crate) fn get_cpu_state_str(&self) -> String {
         let ale_str = match self.ale() {
             true => "A:",
             false => "  ",
@@ -1342,22 +2742,11 @@ impl RemoteCpu<'_> {
         // Handle queue activity
         let mut q_read_str = "       |".to_string();
 
-        let decode_arch = if self.cpu_type.is_intel() {
-            DecodeArch::Intel8088
-        }
-        else {
-            match self.run_state {
-                RunState::Program if self.do_emu8080 => DecodeArch::Intel8080,
-                _ => DecodeArch::Intel8088,
-            }
-        };
+        let decode_arch = self.decode_arch();
 
         if q_op == QueueOp::First {
             // First byte of opcode read from queue. Decode it to opcode or group specifier
-            if self.queue_byte == OPCODE_IRET {
-                let iret_addr = self.queue_fetch_addr;
-                let isr_base_addr = RemoteCpu::calc_linear_address(ISR_SEGMENT, 0);
-                let isr_number = (iret_addr.wrapping_sub(isr_base_addr)) / 2;
+            if let (OPCODE_IRET, Some(isr_number)) = (self.queue_byte, self.ivt.vector_at(self.queue_fetch_addr)) {
                 q_read_str = format!(
                     "q-> {:02X} | {} @ [{:05X}] ISR:{:02X}",
                     self.queue_byte,
@@ -1376,8 +2765,8 @@ impl RemoteCpu<'_> {
             }
         }
         else if q_op == QueueOp::Subsequent {
-            if is_group_op(self.opcode) && self.queue_fetch_n == 1 {
-                // Modrm was just fetched for a group opcode, so display the mnemonic now
+            if is_group_op(self.opcode, decode_arch) && self.queue_fetch_n == 1 {
+                // Modrm or second opcode byte was just fetched for a group opcode, so display the mnemonic now
                 q_read_str = format!(
                     "q-> {:02X} | {}",
                     self.queue_byte,
@@ -1431,7 +2820,7 @@ impl RemoteCpu<'_> {
     }
 
     /// Return whether we are inside the preload program.
-    pub fn have_preload_pgm(&self) -> bool {
+    pub(crate) fn have_preload_pgm(&self) -> bool {
         if let Some(program) = &self.preload_pgm {
             !program.is_finished()
         }
@@ -1440,13 +2829,13 @@ impl RemoteCpu<'_> {
         }
     }
 
-    pub fn print_run_state(&self, print_opts: &PrintOptions) {
+    pub(crate) fn print_run_state(&self, print_opts: &PrintOptions) {
         //log::trace!("print_run_state: {:?}", self.run_state);
         match self.run_state {
             RunState::Preload if print_opts.print_preload => {
                 self.print_cpu_state();
             }
-            RunState::Program if print_opts.print_pgm => {
+            RunState::EmuEnter | RunState::Program | RunState::EmuExit if print_opts.print_pgm => {
                 self.print_cpu_state();
             }
             RunState::Finalize if print_opts.print_finalize => {
@@ -1456,106 +2845,425 @@ impl RemoteCpu<'_> {
         }
     }
 
-    /// Run the CPU for the specified number of cycles.
-    pub fn run(&mut self, run_options: &RunOptions) -> Result<RemoteCpuRegisters, String> {
+    /// Run the mounted program to completion.
+    pub fn run(&mut self, run_options: &RunOptions) -> Result<RunResult, RemoteCpuError> {
         self.run_opts = run_options.clone();
 
         if self.run_opts.automatic {
-            return self.run_automatic().map_err(|e| e.to_string());
+            let registers = self.run_automatic()?;
+            self.regs = registers.clone();
+            return Ok(RunResult {
+                registers,
+                cycles: 0,
+                instructions: 0,
+                run_state_changes: Vec::new(),
+                oob_writes: Vec::new(),
+                smc_writes: 0,
+                invalid_width_cycles: 0,
+                contention_cycles: 0,
+                float_cycles: 0,
+                injected_fault: None,
+                diagnostics: Vec::new(),
+                fill_byte: self.fill_byte,
+                fill_ct: 0,
+                trace: Vec::new(),
+                phase_budget: self.server_phase_budget(),
+                fpu_state: None,
+                io_accesses: Vec::new(),
+                memory_hook_accesses: Vec::new(),
+            });
         }
+
+        if run_options.fpu_save {
+            self.mount_fpu_save();
+        }
+        let result = self.run_stepped();
+        self.unmount_fpu_save();
+        result
+    }
+
+    fn run_stepped(&mut self) -> Result<RunResult, RemoteCpuError> {
+        let run = self.begin_stepped()?;
+        self.complete_stepped(run)
+    }
+
+    /// Cycle a host-stepped run until the program is done, then store its result.
+    fn complete_stepped(&mut self, mut run: SteppedRun) -> Result<RunResult, RemoteCpuError> {
+        while self.program_state != ProgramState::ExecuteDone {
+            self.step_cycle(&mut run)?;
+        }
+        self.finish_stepped(run)
+    }
+
+    /// Set up a host-stepped run with the current [RunOptions], up to the first cycle of the program.
+    fn begin_stepped(&mut self) -> Result<SteppedRun, RemoteCpuError> {
+        // Cycling the CPU while the server runs it automatically would desync the run.
+        self.client.require_mode(ExecutionMode::Manual)?;
         if self.run_opts.use_smm {
             log::debug!("Using SMM for register readout.");
-            let mut flags = self.client.get_flags().map_err(|e| e.to_string())?;
-            self.client
-                .set_flags(flags | ServerFlags::USE_SMM)
-                .map_err(|e| e.to_string())?;
+            let mut flags = self.client.get_flags()?;
+            self.client.set_flags(flags | ServerFlags::USE_SMM)?;
         }
 
+        let run = SteppedRun {
+            start_cycle: self.cycle_num,
+            start_instruction: self.instruction_num,
+            trace: Vec::new(),
+            phase_budget: PhaseBudget::default(),
+            instruction: None,
+        };
+        self.end_reached = false;
+        self.end_start_cycle = run.start_cycle;
+        self.run_state_changes.clear();
+        self.in_emulation = false;
+        self.bus_fault_reads = 0;
+        self.injected_fault = None;
+        self.diagnostics.clear();
+        self.io_accesses.clear();
+        self.memory_hook_accesses.clear();
+        self.io_write_served = false;
+        // Reset moves the peripheral control block back to its default location.
+        if let Some(pcb) = self.pcb.as_mut() {
+            *pcb = PeripheralControlBlock::default();
+        }
+        self.pcb_cycle = false;
+        self.pcb_write_claimed = false;
+        self.fpu_saving = false;
+        self.fpu_save_bytes.clear();
+        self.wait_start = None;
         if let Some(preload_pgm) = &mut self.preload_pgm {
             preload_pgm.reset();
-            log::trace!("Entering [Preload] run state");
-            self.run_state = RunState::Preload;
+            self.set_run_state(RunState::Preload);
         }
         else {
-            log::trace!("Entering [Program] run state");
-            self.run_state = RunState::Program;
+            self.set_run_state(RunState::Program);
         }
 
-        self.update_state(false).map_err(|_| "Failed to update state")?;
+        self.update_state(false)?;
 
         // ALE should be active at start of execution
         if !self.ale() {
-            log::warn!("Execution is not starting on T1.");
+            self.diagnostic(
+                DiagnosticSeverity::Warning,
+                DiagnosticCategory::Desync,
+                "Execution is not starting on T1.".to_string(),
+            );
         }
         else {
             self.address_latch = self.address_bus;
             self.mcycle_state = self.cpu_type.decode_status(self.status);
         }
 
-        self.print_run_state(&run_options.print_opts);
+        self.print_run_state(&self.run_opts.print_opts);
 
-        while self.program_state != ProgramState::ExecuteDone {
-            match self.program_state {
-                ProgramState::Execute => {
-                    self.cycle();
-                    self.print_run_state(&run_options.print_opts);
-                    self.cycle_comment = None;
+        Ok(run)
+    }
+
+    /// Run a single cycle of a host-stepped run and return its record. Fails if the cycle faulted
+    /// under the run's policies.
+    fn step_cycle(&mut self, run: &mut SteppedRun) -> Result<CycleRecord, RemoteCpuError> {
+        let phase = self.run_state.phase();
+        let cycle_start = Instant::now();
+        let finalizing = match self.program_state {
+            ProgramState::Execute | ProgramState::EmuEnter => false,
+            ProgramState::ExecuteFinalize => true,
+            _ => {
+                log::error!("Invalid program state: {:?}!", self.program_state);
+                return Err(RemoteCpuError::InvalidState(self.program_state));
+            }
+        };
+
+        self.cycle()?;
+        run.phase_budget.add(phase, 1, cycle_start.elapsed());
+        if !finalizing {
+            self.print_run_state(&self.run_opts.print_opts);
+        }
+        let record = self.cycle_record();
+        if self.run_opts.trace {
+            run.trace.push(record.clone());
+        }
+        self.cycle_comment = None;
+        if finalizing {
+            return Ok(record);
+        }
+
+        if self.oob_fault {
+            let write = self.oob_writes.last().copied().unwrap_or_default();
+            return Err(RemoteCpuError::OutOfBoundsWrite {
+                address: write.address,
+                cycle:   write.cycle,
+            });
+        }
+        if self.invalid_width_fault {
+            return Err(RemoteCpuError::InvalidWidth {
+                address: self.address_latch,
+                cycle:   self.cycle_num,
+            });
+        }
+        if self.contention_fault {
+            return Err(RemoteCpuError::BusContention {
+                address: self.address_latch,
+                cycle:   self.cycle_num,
+            });
+        }
+        if self.float_fault {
+            return Err(RemoteCpuError::BusFloat {
+                address: self.address_latch,
+                cycle:   self.cycle_num,
+            });
+        }
+        if let Some((address, expected)) = self.queue_fault {
+            return Err(RemoteCpuError::QueueFetch {
+                address,
+                expected,
+                cycle: self.cycle_num,
+            });
+        }
+        if let (Some(limit), Some((start, address))) = (self.run_opts.fpu_wait_limit, self.wait_start) {
+            if self.cycle_num.wrapping_sub(start) > limit {
+                return Err(RemoteCpuError::FpuWait {
+                    address,
+                    cycle: self.cycle_num,
+                });
+            }
+        }
+        Ok(record)
+    }
+
+    /// Store the registers at the end of a host-stepped run and gather its [RunResult].
+    fn finish_stepped(&mut self, run: SteppedRun) -> Result<RunResult, RemoteCpuError> {
+        let SteppedRun {
+            start_cycle,
+            start_instruction,
+            trace,
+            mut phase_budget,
+            ..
+        } = run;
+
+        // Program finalized!
+        log::trace!("Program finalized! Run store now.");
+        let store_start = Instant::now();
+        let mut regs = self.store()?;
+        // The FNSAVE program ran on from the end of the program, so rewind IP past it as well.
+        let fpu_save_len = if self.fpu_saving { FPU_SAVE_PGM.len() as u16 } else { 0 };
+        regs.rewind_ip(self.program_end_offset + fpu_save_len);
+        self.regs = regs.clone();
+        let fpu_state = self.fpu_state();
+
+        // The server runs the jump, load and store by itself; the host counted everything else.
+        let server_budget = self.server_phase_budget();
+        phase_budget.set_from(&server_budget, RunPhase::JumpVector);
+        phase_budget.set_from(&server_budget, RunPhase::Load);
+        phase_budget.add(RunPhase::Store, server_budget.get(RunPhase::Store).cycles, store_start.elapsed());
+
+        Ok(RunResult {
+            registers: regs,
+            cycles: self.cycle_num.wrapping_sub(start_cycle),
+            instructions: self.instruction_num.wrapping_sub(start_instruction),
+            run_state_changes: self.run_state_changes.clone(),
+            oob_writes: self.oob_writes.clone(),
+            smc_writes: self.smc_writes,
+            invalid_width_cycles: self.invalid_width_cycles,
+            contention_cycles: self.contention_cycles,
+            float_cycles: self.float_cycles,
+            injected_fault: self.injected_fault,
+            diagnostics: self.diagnostics.clone(),
+            fill_byte: self.fill_byte,
+            fill_ct: self.program_end_offset,
+            trace,
+            phase_budget,
+            fpu_state,
+            io_accesses: self.io_accesses.clone(),
+            memory_hook_accesses: self.memory_hook_accesses.clone(),
+        })
+    }
+
+    /// Start a host-stepped run of the mounted program, to be advanced an instruction at a time with
+    /// [RemoteCpu::step_instruction] and completed with [RemoteCpu::finish_steps]. `run_options` is
+    /// used as given, except that the run is always host-stepped. Instruction boundaries are found
+    /// from the queue status, so CPUs without it can't be stepped.
+    pub fn begin_steps(&mut self, run_options: &RunOptions) -> Result<(), RemoteCpuError> {
+        if !self.have_queue_status {
+            return Err(RemoteCpuError::NoQueueStatus(self.cpu_type));
+        }
+        self.run_opts = RunOptions {
+            automatic: false,
+            ..*run_options
+        };
+        if self.run_opts.fpu_save {
+            self.mount_fpu_save();
+        }
+        match self.begin_stepped() {
+            Ok(run) => {
+                self.stepped_run = Some(run);
+                Ok(())
+            }
+            Err(e) => {
+                self.unmount_fpu_save();
+                Err(e)
+            }
+        }
+    }
+
+    /// Run the next instruction of a run started with [RemoteCpu::begin_steps], from the queue read
+    /// of its first byte up to the queue read of the first byte of the next instruction. The first
+    /// step also runs the preload program, if any. Prefixes are read from the queue like opcodes, so
+    /// each prefix is a step of its own.
+    ///
+    /// Once a step returns with [InstructionStepResult::finished] set, the program is done and
+    /// further steps fail with [RemoteCpuError::InvalidState]. A step that fails ends the run.
+    pub fn step_instruction(&mut self) -> Result<InstructionStepResult, RemoteCpuError> {
+        let mut run = self.stepped_run.take().ok_or(RemoteCpuError::NotStepping)?;
+        match self.step_to_next_instruction(&mut run) {
+            Ok(step) => {
+                self.stepped_run = Some(run);
+                Ok(step)
+            }
+            Err(e) => {
+                self.unmount_fpu_save();
+                Err(e)
+            }
+        }
+    }
+
+    /// Run what remains of a run started with [RemoteCpu::begin_steps] and return its result, as
+    /// [RemoteCpu::run] would.
+    pub fn finish_steps(&mut self) -> Result<RunResult, RemoteCpuError> {
+        let run = self.stepped_run.take().ok_or(RemoteCpuError::NotStepping)?;
+        let result = self.complete_stepped(run);
+        self.unmount_fpu_save();
+        result
+    }
+
+    fn step_to_next_instruction(&mut self, run: &mut SteppedRun) -> Result<InstructionStepResult, RemoteCpuError> {
+        let executing = |state| matches!(state, ProgramState::Execute | ProgramState::EmuEnter);
+
+        // Run up to the first program instruction, if no step has reached it yet.
+        let start = match run.instruction.take() {
+            Some(start) => start,
+            None => loop {
+                if !executing(self.program_state) {
+                    return Err(RemoteCpuError::InvalidState(self.program_state));
                 }
-                ProgramState::ExecuteFinalize => {
-                    self.cycle();
+                let record = self.step_cycle(run)?;
+                if record.queue_op == QueueOp::First && self.run_state == RunState::Program {
+                    break self.instruction_start(record);
                 }
-                _ => {
-                    log::error!("Invalid program state: {:?}!", self.program_state);
-                    panic!("Invalid program state!");
+            },
+        };
+
+        let mut trace = vec![start.record];
+        let mut finished = true;
+        while executing(self.program_state) {
+            let record = self.step_cycle(run)?;
+            if record.queue_op == QueueOp::First {
+                // Any first byte read outside the program, such as the byte flagged to finalize
+                // the run, ends it.
+                if self.run_state == RunState::Program && executing(self.program_state) {
+                    run.instruction = Some(self.instruction_start(record));
+                    finished = false;
                 }
+                break;
             }
+            trace.push(record);
+        }
+
+        let bytes: Vec<u8> = trace.iter().filter_map(|record| record.queue_byte).collect();
+        let decode_arch = self.decode_arch();
+        let opcode = bytes[0];
+        let modrm = bytes.len() > 1 && is_group_op(opcode, decode_arch);
+        Ok(InstructionStepResult {
+            address: start.address,
+            opcode,
+            mnemonic: opcodes::get_opcode_str(opcode, bytes.get(1).copied().unwrap_or(0), modrm, decode_arch),
+            bytes,
+            cycles: trace.len() as u32,
+            bus_ops: bus_ops(&trace, start.bus_state),
+            trace,
+            finished,
+        })
+    }
 
-            //log::trace!("Program state: {:?}", self.program_state);
+    /// Note the instruction whose first byte was read from the queue on the cycle of `record`.
+    fn instruction_start(&self, record: CycleRecord) -> InstructionStart {
+        InstructionStart {
+            record,
+            address: self.queue_fetch_addr,
+            bus_state: self.mcycle_state,
         }
+    }
 
-        // Program finalized!
-        log::trace!("Program finalized! Run store now.");
-        let mut regs = self.store().map_err(|e| e.to_string())?;
-        regs.rewind_ip(self.program_end_offset);
+    /// Run the program from the host and return a [CycleRecord] for every cycle, for callers that
+    /// analyze or serialize the cycles rather than read the printed cycle log. `run_options` is
+    /// used as given, except that the run is always host-stepped, since the server does not report
+    /// the cycles of an automatic run. Use [RemoteCpu::run] with [RunOptions::trace] set to get the
+    /// trace along with the final registers.
+    pub fn run_traced(&mut self, run_options: &RunOptions) -> Result<Vec<CycleRecord>, RemoteCpuError> {
+        let run_options = RunOptions {
+            automatic: false,
+            trace: true,
+            ..*run_options
+        };
+        Ok(self.run(&run_options)?.trace)
+    }
 
-        Ok(regs)
+    /// Return the server's accounting of the cycles and time spent in each phase since the last
+    /// register load. A failure to read it is logged and returns an empty budget, as it shouldn't
+    /// fail the run it describes. Servers that don't keep a budget also return an empty one.
+    fn server_phase_budget(&mut self) -> PhaseBudget {
+        if !self.client.supports(Capabilities::STATE_BUDGET) {
+            return PhaseBudget::default();
+        }
+        match self.client.get_state_budget() {
+            Ok(states) => PhaseBudget::from_server(&states),
+            Err(e) => {
+                log::warn!("Failed to read the server state budget: {}", e);
+                PhaseBudget::default()
+            }
+        }
     }
 
-    fn run_automatic(&mut self) -> Result<RemoteCpuRegisters, String> {
+    fn run_automatic(&mut self) -> Result<RemoteCpuRegisters, RemoteCpuError> {
         // Run the CPU in automatic mode.
         log::trace!("Running CPU in automatic mode...");
+        // Keep HALT_AFTER_JUMP if the caller set it, as flow control programs need it to stop.
+        let halt_after_jump = self.client.get_flags()? & ServerFlags::HALT_AFTER_JUMP;
         self.client
-            .set_flags(ServerFlags::EXECUTE_AUTOMATIC | ServerFlags::USE_SDRAM_BACKEND)
-            .map_err(|e| e.to_string())?;
+            .set_flags(ServerFlags::EXECUTE_AUTOMATIC | ServerFlags::USE_SDRAM_BACKEND | halt_after_jump)?;
 
         if self.run_opts.use_smm {
             log::debug!("Using SMM for register readout.");
-            let mut flags = self.client.get_flags().map_err(|e| e.to_string())?;
-            self.client
-                .set_flags(flags | ServerFlags::USE_SMM)
-                .map_err(|e| e.to_string())?;
+            let mut flags = self.client.get_flags()?;
+            self.client.set_flags(flags | ServerFlags::USE_SMM)?;
+        }
+        // The server keeps the end condition across runs, so always replace it. Servers without end
+        // conditions have none to disarm, and refuse to arm one.
+        if self.run_opts.end_condition.is_some() || self.client.supports(Capabilities::END_CONDITION) {
+            self.client.set_end_condition(self.run_opts.end_condition)?;
         }
 
         // Reset the CPU state
         use ProgramState::*;
-        let mut state = self.client.get_program_state().map_err(|e| e.to_string())?;
-        while !matches!(state, StoreDone | StoreDoneSmm | Shutdown | Error) {
-            // Sleep for a little bit so we're not spamming the Arduino.
-            std::thread::sleep(std::time::Duration::from_millis(self.run_opts.polling_sleep.into()));
-            state = self.client.get_program_state().map_err(|e| e.to_string())?;
-            log::debug!("Program state: {:?}", state);
-        }
+        let state = self
+            .client
+            .wait_for_states(
+                &[StoreDone, StoreDoneSmm, Shutdown, Error],
+                Duration::MAX,
+                Duration::from_millis(self.run_opts.polling_sleep.into()),
+            )?
+            .state;
+        log::debug!("Program state: {:?}", state);
 
         if matches!(state, Shutdown | Error) {
             log::error!("CPU server is in shutdown or error state: {:?}", state);
-            return Err(format!("CPU server is in shutdown or error state: {:?}", state));
+            return Err(RemoteCpuError::ServerState(state));
         }
 
-        Ok(self.store().map_err(|e| e.to_string())?)
+        Ok(self.store()?)
     }
 
     /// Command the CPU server to store registers, and return them as a [RemoteCpuRegisters] enum
-    pub fn store(&mut self) -> Result<RemoteCpuRegisters, CpuClientError> {
+    pub(crate) fn store(&mut self) -> Result<RemoteCpuRegisters, CpuClientError> {
         let mut buf_v1: [u8; 28] = [0; 28];
 
         match self.cpu_type {
@@ -1592,12 +3300,6 @@ impl RemoteCpu<'_> {
 
     pub fn print_reg_buf(reg_buf: &[u8], cpu_type: ServerCpuType) {
         let regs = RemoteCpuRegisters::try_from(reg_buf).expect("Failed to convert register buffer");
-        let printer = RegisterPrinter {
-            regs: &regs,
-            final_regs: None,
-            cpu_type,
-            options: 0,
-        };
-        println!("{}", printer);
+        println!("{}", regs.display(cpu_type));
     }
 }
@@ -0,0 +1,219 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Read and write hooks over ranges of the host's memory.
+//!
+//! A [MemoryHook] sees every byte the CPU reads or writes in its range during host-stepped runs,
+//! and may serve a read itself or keep a write out of memory. This covers device-backed memory
+//! such as video RAM, and watching for accesses outside the areas a program is expected to touch.
+//! Hooks are registered with [RemoteCpu::add_memory_hook]. Code fetches are not hooked. Every byte
+//! a hook sees is recorded as a [MemoryHookAccess] and commented in the cycle trace.
+
+use std::ops::Range;
+
+use arduinox86_client::DataWidth;
+
+use crate::{RemoteCpu, ADDRESS_SPACE, ADDRESS_SPACE_MASK};
+
+/// A hook on a range of memory. Addresses are passed as 20-bit physical addresses.
+pub trait MemoryHook {
+    /// Return a short name for the hook, for the cycle trace.
+    fn name(&self) -> &str {
+        "hook"
+    }
+
+    /// Called for each byte the CPU reads at `address`, with the byte memory holds. Return a byte
+    /// to serve it instead. Defaults to serving memory.
+    fn read(&mut self, _address: u32, _value: u8) -> Option<u8> {
+        None
+    }
+
+    /// Called for each byte the CPU writes at `address`. Return false to keep the byte out of
+    /// memory. Defaults to letting the write through.
+    fn write(&mut self, _address: u32, _value: u8) -> bool {
+        true
+    }
+}
+
+/// A byte of a memory transfer seen by a [MemoryHook].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryHookAccess {
+    pub cycle:   u32,
+    pub address: u32,
+    /// The byte read or written. For a served read, the byte the hook returned.
+    pub value:   u8,
+    pub write:   bool,
+    /// Set if a hook served the read or kept the write out of memory.
+    pub handled: bool,
+}
+
+/// A hook that only watches its range. Useful to catch a program reading or writing memory it
+/// should not touch.
+#[derive(Clone, Debug)]
+pub struct WatchHook {
+    name: String,
+}
+
+impl WatchHook {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+impl MemoryHook for WatchHook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Register `hook` over the addresses in `range`. Hooks may overlap; they are called in the
+    /// order they were added. Fails if the range is empty or extends past the 1MB address space.
+    pub fn add_memory_hook(&mut self, range: Range<u32>, hook: Box<dyn MemoryHook>) -> Result<(), String> {
+        if range.is_empty() {
+            return Err("Memory hook range is empty.".to_string());
+        }
+        if range.end > ADDRESS_SPACE as u32 {
+            return Err(format!(
+                "Memory hook range {:05X}-{:05X} extends past the end of the address space.",
+                range.start,
+                range.end - 1
+            ));
+        }
+        self.memory_hooks.push((range, hook));
+        Ok(())
+    }
+
+    /// Remove all memory hooks.
+    pub fn clear_memory_hooks(&mut self) {
+        self.memory_hooks.clear();
+    }
+
+    /// Return the bytes seen by memory hooks during the last run, in order.
+    pub fn memory_hook_accesses(&self) -> &[MemoryHookAccess] {
+        &self.memory_hook_accesses
+    }
+
+    /// Pass the memory read on the data bus through the hooks covering each byte, letting the
+    /// first hook that returns a value serve that byte.
+    pub(crate) fn memory_hook_read(&mut self) {
+        if self.memory_hooks.is_empty() {
+            return;
+        }
+        let mut notes = Vec::new();
+        for &(offset, shift) in bus_bytes(self.data_width) {
+            let address = (self.address_latch + offset) & ADDRESS_SPACE_MASK as u32;
+            let mut value = (self.data_bus >> shift) as u8;
+            let mut handled = false;
+            let mut seen = false;
+            for (range, hook) in self.memory_hooks.iter_mut() {
+                if !range.contains(&address) {
+                    continue;
+                }
+                seen = true;
+                if let Some(served) = hook.read(address, value) {
+                    if !handled {
+                        handled = true;
+                        value = served;
+                        notes.push(format!("{} read [{:05X}] -> {:02X}", hook.name(), address, value));
+                    }
+                }
+            }
+            if !seen {
+                continue;
+            }
+            if handled {
+                self.data_bus = (self.data_bus & !(0xFF << shift)) | ((value as u16) << shift);
+            }
+            self.memory_hook_accesses.push(MemoryHookAccess {
+                cycle: self.cycle_num,
+                address,
+                value,
+                write: false,
+                handled,
+            });
+        }
+        if !notes.is_empty() {
+            self.cycle_comment = Some(format!("HOOK: {}", notes.join(", ")));
+        }
+    }
+
+    /// Pass the memory write on the data bus through the hooks covering each byte. Returns true if
+    /// every byte may be written to memory. Otherwise, writes the bytes no hook refused and returns
+    /// false.
+    pub(crate) fn memory_hook_write(&mut self) -> bool {
+        if self.memory_hooks.is_empty() {
+            return true;
+        }
+        let mut notes = Vec::new();
+        let mut allowed = Vec::new();
+        let mut all_allowed = true;
+        for &(offset, shift) in bus_bytes(self.data_width) {
+            let address = (self.address_latch + offset) & ADDRESS_SPACE_MASK as u32;
+            let value = (self.data_bus >> shift) as u8;
+            let mut write = true;
+            let mut seen = false;
+            for (range, hook) in self.memory_hooks.iter_mut() {
+                if range.contains(&address) {
+                    seen = true;
+                    if !hook.write(address, value) {
+                        write = false;
+                        notes.push(format!("{} kept [{:05X}] <- {:02X}", hook.name(), address, value));
+                    }
+                }
+            }
+            if seen {
+                self.memory_hook_accesses.push(MemoryHookAccess {
+                    cycle: self.cycle_num,
+                    address,
+                    value,
+                    write: true,
+                    handled: !write,
+                });
+            }
+            match write {
+                true => allowed.push((address, value)),
+                false => all_allowed = false,
+            }
+        }
+        if !notes.is_empty() {
+            self.cycle_comment = Some(format!("HOOK: {}", notes.join(", ")));
+        }
+        if !all_allowed {
+            for (address, value) in allowed {
+                self.memory[address as usize] = value;
+            }
+        }
+        all_allowed
+    }
+}
+
+/// Return the (address offset, bus shift) of each byte a transfer of `width` carries.
+fn bus_bytes(width: DataWidth) -> &'static [(u32, u32)] {
+    match width {
+        DataWidth::EightLow => &[(0, 0)],
+        DataWidth::EightHigh => &[(0, 8)],
+        DataWidth::Sixteen => &[(0, 0), (1, 8)],
+        DataWidth::Invalid => &[],
+    }
+}
@@ -23,19 +23,27 @@
 
 pub const OPCODE_IRET: u8 = 0xCF;
 pub const OPCODE_NOP: u8 = 0x90;
+pub const OPCODE_HALT: u8 = 0xF4;
 pub const OPCODE_NOPS: u16 = 0x9090;
 pub const OPCODE_NOP80: u8 = 0x00; // NOP for 8080
 pub const OPCODE_NOPS80: u16 = 0x0000; // NOP for 8080
 pub const OPCODE_NMI_TRIGGER: u8 = 0xF1; // Undefined opcode to use as NMI trigger
+pub const OPCODE_NEC_EXTENDED: u8 = 0x0F; // Prefix of the NEC V20/V30 extended opcodes
 
 /*
 #define MODRM_OP(M) (((M & 0b00111000) >> 3) & 0x07)
 #define IS_GRP_OP(O) ((OPCODE_REFS[O] >= GRP1) && (OPCODE_REFS[O] <= GRP2B))
 */
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DecodeArch {
     Intel8088,
     Intel8080,
+    // The 80186 and 80188, which add PUSHA/POPA, BOUND, ENTER/LEAVE, immediate forms of PUSH, IMUL and the
+    // shifts, and the INS/OUTS string instructions.
+    Intel80186,
+    // Native mode of the NEC V20 and V30, with the 80186 additions and the 0x0F extended opcodes.
+    NecV20,
 }
 
 macro_rules! modrm_op {
@@ -51,47 +59,116 @@ pub fn is_prefix(op1: u8) -> bool {
     }
 }
 
-pub fn is_group_op(op1: u8) -> bool {
-    (OPCODE_REFS[op1 as usize] >= 105) && (OPCODE_REFS[op1 as usize] <= 110)
+// Return true if the mnemonic of the opcode depends on the byte after it: the reg field of the
+// modrm for group opcodes, or the second opcode byte of NEC extended opcodes.
+pub fn is_group_op(op1: u8, decode_arch: DecodeArch) -> bool {
+    match decode_arch {
+        DecodeArch::Intel8080 => false,
+        DecodeArch::Intel80186 if matches!(op1, 0xC0 | 0xC1) => true,
+        DecodeArch::NecV20 if matches!(op1, OPCODE_NEC_EXTENDED | 0xC0 | 0xC1) => true,
+        _ => (OPCODE_REFS[op1 as usize] >= GRP1) && (OPCODE_REFS[op1 as usize] <= GRP2B),
+    }
 }
 
 // Return the mnemonic string for the specified opcode. If the opcode is a group
-// opcode, op2 should be specified and modrm set to true.
+// opcode, op2 should be specified and modrm set to true. For NEC extended opcodes,
+// op2 is the second opcode byte.
 pub fn get_opcode_str(op1: u8, op2: u8, modrm: bool, decode_arch: DecodeArch) -> &'static str {
-    let op_idx: usize = match decode_arch {
-        DecodeArch::Intel8088 => OPCODE_REFS[op1 as usize],
-        DecodeArch::Intel8080 => OPCODE_8080_REFS[op1 as usize],
-    };
-
-    if !modrm {
-        // Just return primary opcode
-        match decode_arch {
-            DecodeArch::Intel8088 => {
-                return OPCODE_STRS[op_idx];
-            }
-            DecodeArch::Intel8080 => {
-                return OPCODE_8080_STRS[op_idx];
+    match decode_arch {
+        DecodeArch::Intel8080 => return OPCODE_8080_STRS[OPCODE_8080_REFS[op1 as usize]],
+        DecodeArch::Intel80186 => {
+            if let Some(mnemonic) = get_186_opcode_str(op1, op2, modrm) {
+                return mnemonic;
             }
         }
-    } else {
-        // modrm is in use, check if this is a group instruction...
-        if is_group_op(op1) {
-            // Lookup opcode group
-            let grp_idx: usize = modrm_op!(op2);
-
-            match OPCODE_REFS[op1 as usize] {
-                GRP1 => OPCODE_STRS_GRP1[grp_idx],
-                GRP2A => OPCODE_STRS_GRP2A[grp_idx],
-                GRP2B => OPCODE_STRS_GRP2B[grp_idx],
-                GRP3 => OPCODE_STRS_GRP3[grp_idx],
-                GRP4 => OPCODE_STRS_GRP4[grp_idx],
-                GRP5 => OPCODE_STRS_GRP5[grp_idx],
-                _ => "ERROR",
+        DecodeArch::NecV20 => {
+            if let Some(mnemonic) = get_nec_opcode_str(op1, op2, modrm) {
+                return mnemonic;
             }
-        } else {
-            // Not a group instruction, just return as normal
-            OPCODE_STRS[op_idx]
         }
+        DecodeArch::Intel8088 => {}
+    }
+
+    let op_idx: usize = OPCODE_REFS[op1 as usize];
+    if modrm && is_group_op(op1, DecodeArch::Intel8088) {
+        // modrm is in use and this is a group instruction, so lookup opcode group
+        let grp_idx: usize = modrm_op!(op2);
+
+        match op_idx {
+            GRP1 => OPCODE_STRS_GRP1[grp_idx],
+            GRP2A => OPCODE_STRS_GRP2A[grp_idx],
+            GRP2B => OPCODE_STRS_GRP2B[grp_idx],
+            GRP3 => OPCODE_STRS_GRP3[grp_idx],
+            GRP4 => OPCODE_STRS_GRP4[grp_idx],
+            GRP5 => OPCODE_STRS_GRP5[grp_idx],
+            _ => "ERROR",
+        }
+    } else {
+        // Not a group instruction, just return as normal
+        OPCODE_STRS[op_idx]
+    }
+}
+
+// Return the mnemonic of an opcode the 80186 decodes differently from the 8088, or None if it
+// decodes it the same way. Opcodes that are undefined on the 80186 raise the invalid opcode trap.
+fn get_186_opcode_str(op1: u8, op2: u8, modrm: bool) -> Option<&'static str> {
+    let mnemonic = match op1 {
+        0x0F | 0x63..=0x67 => "INVAL",
+        0x60 => "PUSHA",
+        0x61 => "POPA",
+        0x62 => "BOUND",
+        0x68 | 0x6A => "PUSH",
+        0x69 | 0x6B => "IMUL",
+        0x6C => "INSB",
+        0x6D => "INSW",
+        0x6E => "OUTSB",
+        0x6F => "OUTSW",
+        // Shifts and rotates by an immediate count
+        0xC0 | 0xC1 if modrm => OPCODE_STRS_GRP2A[modrm_op!(op2)],
+        0xC0 | 0xC1 => "GRP2A",
+        0xC8 => "ENTER",
+        0xC9 => "LEAVE",
+        _ => return None,
+    };
+    Some(mnemonic)
+}
+
+// Return the mnemonic of an opcode the NEC V20 and V30 decode differently from the 8088,
+// or None if they decode it the same way. Intel mnemonics are used where there is one.
+fn get_nec_opcode_str(op1: u8, op2: u8, modrm: bool) -> Option<&'static str> {
+    let mnemonic = match op1 {
+        OPCODE_NEC_EXTENDED if modrm => get_nec_extended_str(op2),
+        OPCODE_NEC_EXTENDED => "EXT0F",
+        0x64 => "REPNC",
+        0x65 => "REPC",
+        0x66 | 0x67 => "FPO2",
+        // The rest of the 80186 additions
+        _ => return get_186_opcode_str(op1, op2, modrm),
+    };
+    Some(mnemonic)
+}
+
+// Return the mnemonic of the NEC extended opcode 0x0F op2.
+fn get_nec_extended_str(op2: u8) -> &'static str {
+    match op2 {
+        // Single bit operations, with the bit number in CL or an immediate byte
+        0x10 | 0x11 | 0x18 | 0x19 => "TEST1",
+        0x12 | 0x13 | 0x1A | 0x1B => "CLR1",
+        0x14 | 0x15 | 0x1C | 0x1D => "SET1",
+        0x16 | 0x17 | 0x1E | 0x1F => "NOT1",
+        // Packed BCD string operations
+        0x20 => "ADD4S",
+        0x22 => "SUB4S",
+        0x26 => "CMP4S",
+        // Nibble rotates
+        0x28 => "ROL4",
+        0x2A => "ROR4",
+        // Bit field insertion and extraction
+        0x31 | 0x39 => "INS",
+        0x33 | 0x3B => "EXT",
+        // Break to 8080 emulation mode
+        0xFF => "BRKEM",
+        _ => "INVAL",
     }
 }
 
@@ -0,0 +1,136 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! The peripheral control block of the 80186 and 80188.
+//!
+//! The 186 integrates its interrupt controller, timers, DMA channels and chip select unit, and
+//! maps their registers into a 256 byte block of I/O or memory space. The block is at FF00 in I/O
+//! space after reset, and moves when the CPU writes its relocation register at offset FE.
+//! Transfers to the block still run bus cycles, but reads are answered inside the CPU, so the host
+//! neither serves them nor counts them as floating. Each transfer is commented in the cycle trace.
+
+use arduinox86_client::DataWidth;
+
+use crate::RemoteCpu;
+
+/// Size of the peripheral control block, in bytes.
+pub const PCB_SIZE: u32 = 0x100;
+/// Offset of the relocation register within the peripheral control block.
+pub const PCB_RELOCATION_OFFSET: u32 = 0xFE;
+/// Value of the relocation register after reset: the block is at FF00 in I/O space.
+pub const PCB_RELOCATION_RESET: u16 = 0x20FF;
+
+const RELOCATION_BASE_MASK: u16 = 0x0FFF;
+const RELOCATION_MEMORY_BIT: u16 = 0x1000;
+
+/// The location of the peripheral control block, as set by its relocation register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PeripheralControlBlock {
+    relocation: u16,
+}
+
+impl Default for PeripheralControlBlock {
+    fn default() -> Self {
+        Self {
+            relocation: PCB_RELOCATION_RESET,
+        }
+    }
+}
+
+impl PeripheralControlBlock {
+    /// Return the value of the relocation register.
+    pub fn relocation(&self) -> u16 {
+        self.relocation
+    }
+
+    /// Return the address of the start of the block.
+    pub fn base(&self) -> u32 {
+        ((self.relocation & RELOCATION_BASE_MASK) as u32) << 8
+    }
+
+    /// Return true if the block is mapped into memory space rather than I/O space.
+    pub fn in_memory(&self) -> bool {
+        self.relocation & RELOCATION_MEMORY_BIT != 0
+    }
+
+    /// Return the offset of `address` within the block, if the block is mapped into the space
+    /// being accessed and covers the address.
+    pub fn offset(&self, address: u32, memory: bool) -> Option<u32> {
+        let base = self.base();
+        (memory == self.in_memory() && address >= base && address < base + PCB_SIZE).then(|| address - base)
+    }
+
+    /// Apply a write of `value` at `offset`. Only the relocation register is modelled; byte writes
+    /// replace the half of it they address.
+    pub fn write(&mut self, offset: u32, value: u16, word: bool) {
+        let [lo, hi] = self.relocation.to_le_bytes();
+        self.relocation = match (offset, word) {
+            (PCB_RELOCATION_OFFSET, true) => value,
+            (PCB_RELOCATION_OFFSET, false) => u16::from_le_bytes([value as u8, hi]),
+            (o, false) if o == PCB_RELOCATION_OFFSET + 1 => u16::from_le_bytes([lo, value as u8]),
+            _ => return,
+        };
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Return the peripheral control block, if the CPU is an 80186 or 80188.
+    pub fn pcb(&self) -> Option<&PeripheralControlBlock> {
+        self.pcb.as_ref()
+    }
+
+    /// Check the transfer at the latched address against the peripheral control block. Returns
+    /// true if the block claims it, in which case the host must not serve it. Writes to the
+    /// relocation register move the block.
+    pub(crate) fn pcb_access(&mut self, memory: bool, write: bool) -> bool {
+        let address = match memory {
+            true => self.address_latch,
+            false => self.address_latch & 0xFFFF,
+        };
+        let Some(offset) = self.pcb.as_ref().and_then(|pcb| pcb.offset(address, memory))
+        else {
+            return false;
+        };
+        self.pcb_cycle = true;
+
+        if write {
+            let (value, word) = match self.data_width {
+                DataWidth::EightLow => (self.data_bus & 0xFF, false),
+                DataWidth::EightHigh => (self.data_bus >> 8, false),
+                DataWidth::Sixteen => (self.data_bus, true),
+                DataWidth::Invalid => return true,
+            };
+            if let Some(pcb) = self.pcb.as_mut() {
+                pcb.write(offset, value, word);
+            }
+            self.cycle_comment = Some(match word {
+                true => format!("PCB: write [{:02X}] <- {:04X}", offset, value),
+                false => format!("PCB: write [{:02X}] <- {:02X}", offset, value),
+            });
+        }
+        else {
+            self.cycle_comment = Some(format!("PCB: read [{:02X}]", offset));
+        }
+        true
+    }
+}
@@ -21,9 +21,11 @@
     DEALINGS IN THE SOFTWARE.
 */
 
+use std::fmt;
+
 use arduinox86_client::{CpuWidth, DataWidth};
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum QueueDataType {
     Preload,
     EmuEnter,
@@ -32,11 +34,56 @@ pub enum QueueDataType {
     Fill,
 }
 
+/// Where a byte in the queue came from. This is finer grained than [QueueDataType], which only
+/// marks the bytes that change the run state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QueueProvenance {
+    /// A byte of the preload program.
+    Preload,
+    /// Padding fed in place of a preload program byte, to align the program or fill out a word.
+    PreloadFill,
+    /// A byte of the BRKEM program that enters 8080 emulation.
+    EmuEnter,
+    /// The byte at this index into the program.
+    ProgramByteIndex(usize),
+    /// A byte of an interrupt service routine.
+    IsrBody,
+    /// A byte fetched past the end of the program.
+    OutOfBounds,
+    /// Padding fed when there was no code to fetch.
+    Fill,
+}
+
+impl QueueProvenance {
+    /// Return the index into the program of a program byte.
+    pub fn program_index(&self) -> Option<usize> {
+        match self {
+            QueueProvenance::ProgramByteIndex(index) => Some(*index),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for QueueProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueProvenance::Preload => write!(f, "preload"),
+            QueueProvenance::PreloadFill => write!(f, "preload fill"),
+            QueueProvenance::EmuEnter => write!(f, "emu enter"),
+            QueueProvenance::ProgramByteIndex(index) => write!(f, "program[{}]", index),
+            QueueProvenance::IsrBody => write!(f, "isr"),
+            QueueProvenance::OutOfBounds => write!(f, "out of bounds"),
+            QueueProvenance::Fill => write!(f, "fill"),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct QueueEntry {
     opcode: u8,
-    dtype:  QueueDataType,
-    addr:   u32,
+    dtype: QueueDataType,
+    addr: u32,
+    provenance: QueueProvenance,
 }
 
 pub struct InstructionQueue {
@@ -60,8 +107,9 @@ impl InstructionQueue {
             q: vec![
                 QueueEntry {
                     opcode: 0,
-                    dtype:  QueueDataType::Program,
-                    addr:   0,
+                    dtype: QueueDataType::Program,
+                    addr: 0,
+                    provenance: QueueProvenance::Fill,
                 };
                 width.queue_size()
             ],
@@ -81,7 +129,16 @@ impl InstructionQueue {
         self.len() + usize::from(self.width) <= self.size
     }
 
-    pub fn push(&mut self, data: u16, width: DataWidth, dtype: QueueDataType, addr: u32) {
+    /// Push a fetch into the queue. `provenance` holds the provenance of the byte at `addr`, then
+    /// of the byte at `addr + 1` for a word fetch.
+    pub fn push(
+        &mut self,
+        data: u16,
+        width: DataWidth,
+        dtype: QueueDataType,
+        addr: u32,
+        provenance: [QueueProvenance; 2],
+    ) {
         if self.has_room() {
             match width {
                 DataWidth::EightHigh => {
@@ -89,6 +146,7 @@ impl InstructionQueue {
                         opcode: (data >> 8) as u8,
                         dtype,
                         addr,
+                        provenance: provenance[0],
                     };
                     self.front = (self.front + 1) % self.size;
                     self.len += 1;
@@ -98,12 +156,14 @@ impl InstructionQueue {
                         opcode: data as u8,
                         dtype,
                         addr,
+                        provenance: provenance[0],
                     };
                     self.front = (self.front + 1) % self.size;
                     self.q[self.front] = QueueEntry {
                         opcode: (data >> 8) as u8,
                         dtype,
-                        addr,
+                        addr: addr.wrapping_add(1),
+                        provenance: provenance[1],
                     };
                     self.front = (self.front + 1) % self.size;
                     self.len += 2;
@@ -120,7 +180,7 @@ impl InstructionQueue {
         }
     }
 
-    pub fn pop(&mut self) -> (u8, QueueDataType, u32) {
+    pub fn pop(&mut self) -> (u8, QueueDataType, u32, QueueProvenance) {
         if self.len > 0 {
             let q_entry = self.q[self.back];
             //let dt = self.dt[self.back];
@@ -128,16 +188,31 @@ impl InstructionQueue {
             self.back = (self.back + 1) % self.size;
             self.len -= 1;
 
-            (q_entry.opcode, q_entry.dtype, q_entry.addr)
+            (q_entry.opcode, q_entry.dtype, q_entry.addr, q_entry.provenance)
         }
         else {
             if !self.silent {
                 log::error!("Queue underrun!");
             }
-            (0, QueueDataType::Program, 0)
+            (0, QueueDataType::Program, 0, QueueProvenance::Fill)
         }
     }
 
+    /// Return true if any byte currently in the queue was fetched from `addr`.
+    pub fn contains_addr(&self, addr: u32) -> bool {
+        (0..self.len).any(|i| self.q[(self.back + i) % self.size].addr == addr)
+    }
+
+    /// Return the bytes in the queue, oldest first, with the provenance of each.
+    pub fn entries(&self) -> Vec<(u8, QueueProvenance)> {
+        (0..self.len)
+            .map(|i| {
+                let entry = self.q[(self.back + i) % self.size];
+                (entry.opcode, entry.provenance)
+            })
+            .collect()
+    }
+
     pub fn flush(&mut self) {
         self.len = 0;
         self.back = 0;
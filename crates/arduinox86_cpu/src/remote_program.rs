@@ -23,7 +23,7 @@
 
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
-use crate::{code_stream::CodeStream, opcodes::OPCODE_NOP, queue::QueueDataType};
+use crate::{code_stream::CodeStream, queue::QueueDataType};
 use arduinox86_client::CpuWidth;
 
 pub struct RemoteProgram {
@@ -105,7 +105,7 @@ impl RemoteProgram {
                     // We must have at least 1 byte in the program to do this
                     if self.program_remaining() == 1 {
                         if self.bytes.position() == 0 {
-                            stream.push_byte(OPCODE_NOP, QueueDataType::Fill);
+                            stream.push_byte(self.fill_byte, QueueDataType::Fill);
                         }
                         else {
                             // Seek backwards 1
@@ -0,0 +1,311 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Replay of MOO tests through [RemoteCpu].
+//!
+//! A replay loads a test's initial registers and RAM, runs the instruction to completion and
+//! compares the final state against the test's under a [ComparisonPolicy]. Only tests with 16-bit
+//! register sets are supported. Tests that raise an exception must be replayed in automatic mode,
+//! where the server halts at the handler the same way it did when the test was generated.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+pub use arduinox86_client::RegisterMismatch;
+use arduinox86_client::{
+    Capabilities,
+    ComparisonPolicy,
+    ComparisonPreset,
+    RegisterBuffer,
+    Registers16,
+    RemoteCpuRegisters,
+    RemoteCpuRegistersV1,
+    RemoteCpuRegistersV2,
+    ServerCpuType,
+};
+use moo::{
+    prelude::MooTest,
+    types::{MooRegisters, MooRegisters16},
+};
+
+use crate::{opcodes::OPCODE_HALT, PrintOptions, RamMismatch, RemoteCpu, RunOptions};
+
+/// Options controlling a replay.
+#[derive(Copy, Clone, Debug)]
+pub struct ReplayOptions {
+    /// Run in automatic mode on the server instead of stepping the CPU from the host.
+    pub automatic: bool,
+    /// Flags bits the test leaves undefined. Policies that treat undefined flags as wildcards
+    /// skip them.
+    pub undefined_flags: u16,
+    pub cycle_limit: Option<u32>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            automatic: false,
+            undefined_flags: 0,
+            cycle_limit: Some(10_000),
+        }
+    }
+}
+
+/// The result of replaying a test. A replay passes if it has no mismatches.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayResult {
+    pub name: String,
+    pub registers: Vec<RegisterMismatch>,
+    pub ram: Vec<RamMismatch>,
+    /// Expected and actual cycle counts, if cycles were compared and differ by more than the
+    /// policy's tolerance. Cycles are only compared in automatic mode, where the server captures
+    /// the same cycle log the test was generated from.
+    pub cycles: Option<(usize, usize)>,
+}
+
+impl ReplayResult {
+    pub fn passed(&self) -> bool {
+        self.registers.is_empty() && self.ram.is_empty() && self.cycles.is_none()
+    }
+}
+
+impl Display for ReplayResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.passed() {
+            return write!(f, "{}: passed", self.name);
+        }
+        writeln!(f, "{}: failed", self.name)?;
+        for mismatch in &self.registers {
+            writeln!(
+                f,
+                "  {}: expected {:04X} got {:04X}",
+                mismatch.register, mismatch.expected, mismatch.actual
+            )?;
+        }
+        for mismatch in &self.ram {
+            writeln!(
+                f,
+                "  [{:05X}]: expected {:02X} got {:02X}",
+                mismatch.address, mismatch.expected, mismatch.actual
+            )?;
+        }
+        if let Some((expected, actual)) = self.cycles {
+            writeln!(f, "  cycles: expected {} got {}", expected, actual)?;
+        }
+        Ok(())
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Replay a MOO test with default options and the [ComparisonPreset::Strict] policy. See
+    /// [RemoteCpu::replay_with].
+    pub fn replay(&mut self, test: &MooTest) -> Result<ReplayResult, String> {
+        self.replay_with(test, &ReplayOptions::default(), &ComparisonPreset::Strict)
+    }
+
+    /// Load a MOO test's initial state, run it and compare the result against its final state
+    /// under `policy`. Returns an error if the test could not be run at all; mismatches are
+    /// reported in the [ReplayResult].
+    pub fn replay_with(
+        &mut self,
+        test: &MooTest,
+        options: &ReplayOptions,
+        policy: &dyn ComparisonPolicy,
+    ) -> Result<ReplayResult, String> {
+        let MooRegisters::Sixteen(initial) = test.initial_regs()
+        else {
+            return Err("Replay only supports 16-bit register sets".to_string());
+        };
+        let MooRegisters::Sixteen(expected) = test.final_regs()
+        else {
+            return Err("Replay only supports 16-bit register sets".to_string());
+        };
+
+        if test.exception().is_some() && !options.automatic {
+            return Err("Tests that raise an exception can only be replayed in automatic mode".to_string());
+        }
+
+        self.reset();
+
+        // Mount the instruction at CS:IP. In automatic mode the server needs a HALT to stop on.
+        let code_address = RemoteCpu::calc_linear_address(initial.cs, initial.ip);
+        let mut code = test.bytes().to_vec();
+        if options.automatic {
+            code.push(OPCODE_HALT);
+        }
+        self.mount_bin(options.automatic, &code, code_address as usize)?;
+        if options.automatic && self.client.supports(Capabilities::PROGRAM_BOUNDS) {
+            self.client
+                .set_program_bounds(code_address, code_address + test.bytes().len() as u32)
+                .map_err(|e| e.to_string())?;
+        }
+        else {
+            self.setup_ivt();
+        }
+
+        // The test's RAM includes the instruction bytes and any IVT entries it reads, so load it
+        // after the IVT so it takes precedence.
+        let initial_ram: Vec<(u32, u8)> = test
+            .initial_mem_state()
+            .entries
+            .iter()
+            .map(|entry| (entry.address, entry.value))
+            .collect();
+        self.load_ram_entries(options.automatic, &initial_ram)?;
+
+        let reg_buf = match self.cpu_type {
+            ServerCpuType::Intel80286 => {
+                let mut regs = RemoteCpuRegistersV2 {
+                    ax: initial.ax,
+                    bx: initial.bx,
+                    cx: initial.cx,
+                    dx: initial.dx,
+                    sp: initial.sp,
+                    bp: initial.bp,
+                    si: initial.si,
+                    di: initial.di,
+                    cs: initial.cs,
+                    ds: initial.ds,
+                    es: initial.es,
+                    ss: initial.ss,
+                    ip: initial.ip,
+                    flags: initial.flags,
+                    ..Default::default()
+                };
+                regs.normalize_descriptors();
+                regs.to_bytes()
+            }
+            _ => RemoteCpuRegistersV1 {
+                ax:    initial.ax,
+                bx:    initial.bx,
+                cx:    initial.cx,
+                dx:    initial.dx,
+                ip:    initial.ip,
+                cs:    initial.cs,
+                flags: initial.flags,
+                ss:    initial.ss,
+                sp:    initial.sp,
+                ds:    initial.ds,
+                es:    initial.es,
+                bp:    initial.bp,
+                si:    initial.si,
+                di:    initial.di,
+            }
+            .to_bytes(),
+        };
+        if !self.load_registers_from_buf(&reg_buf) {
+            return Err(format!("Register setup failed: {}", self.get_last_error()));
+        }
+
+        let run_options = RunOptions {
+            automatic: options.automatic,
+            cycle_limit: options.cycle_limit,
+            print_opts: PrintOptions {
+                print_pgm: false,
+                print_preload: false,
+                print_finalize: false,
+            },
+            ..Default::default()
+        };
+        let final_regs = self.run(&run_options).map_err(|e| e.to_string())?.registers;
+
+        let mut result = ReplayResult {
+            name: test.name().to_string(),
+            registers: compare_registers16(policy, expected, &final_regs, options.undefined_flags)?,
+            ..Default::default()
+        };
+
+        let initial_ram: BTreeMap<u32, u8> = initial_ram.into_iter().collect();
+        for entry in &test.final_mem_state().entries {
+            if !policy.compare_address(initial_ram.get(&entry.address).copied(), entry.value) {
+                continue;
+            }
+            let actual = self.read_ram_byte(options.automatic, entry.address)?;
+            if actual != entry.value {
+                result.ram.push(RamMismatch {
+                    address: entry.address,
+                    expected: entry.value,
+                    actual,
+                });
+            }
+        }
+
+        // Servers without a cycle log can't count cycles, so the count goes unchecked.
+        if policy.cycle_tolerance().is_some() && options.automatic && self.client.supports(Capabilities::CYCLE_LOG) {
+            let actual = self.client.get_cycle_states().map_err(|e| e.to_string())?.len();
+            let expected = test.cycles().len();
+            if !policy.cycles_match(expected, actual) {
+                result.cycles = Some((expected, actual));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn read_ram_byte(&mut self, automatic: bool, address: u32) -> Result<u8, String> {
+        if automatic {
+            let mut buf = Vec::with_capacity(1);
+            self.client
+                .read_memory(address, 1, &mut buf)
+                .map_err(|e| e.to_string())?;
+            buf.first()
+                .copied()
+                .ok_or_else(|| format!("No data read from [{:05X}]", address))
+        }
+        else {
+            Ok(self.memory[address as usize & crate::ADDRESS_SPACE_MASK])
+        }
+    }
+}
+
+fn compare_registers16(
+    policy: &dyn ComparisonPolicy,
+    expected: &MooRegisters16,
+    actual: &RemoteCpuRegisters,
+    undefined_flags: u16,
+) -> Result<Vec<RegisterMismatch>, String> {
+    let actual: &dyn Registers16 = match actual {
+        RemoteCpuRegisters::V1(regs) => regs,
+        RemoteCpuRegisters::V2(regs) => regs,
+        RemoteCpuRegisters::V3(_) => return Err("CPU returned a 32-bit register set".to_string()),
+    };
+
+    let pairs = [
+        ("AX", expected.ax, actual.ax()),
+        ("BX", expected.bx, actual.bx()),
+        ("CX", expected.cx, actual.cx()),
+        ("DX", expected.dx, actual.dx()),
+        ("SP", expected.sp, actual.sp()),
+        ("BP", expected.bp, actual.bp()),
+        ("SI", expected.si, actual.si()),
+        ("DI", expected.di, actual.di()),
+        ("CS", expected.cs, actual.cs()),
+        ("DS", expected.ds, actual.ds()),
+        ("ES", expected.es, actual.es()),
+        ("SS", expected.ss, actual.ss()),
+        ("IP", expected.ip, actual.ip()),
+        ("FLAGS", expected.flags, actual.flags()),
+    ]
+    .map(|(register, expected, actual)| (register, expected as u32, actual as u32));
+
+    Ok(policy.register_mismatches(&pairs, undefined_flags as u32))
+}
@@ -0,0 +1,140 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! What a [crate::RemoteCpu] run produced.
+//!
+//! A [RunResult] gathers everything a run records besides the final registers, so callers don't
+//! have to query the CPU afterwards or scrape the printed cycle log. Host-stepped runs can also
+//! record a [CycleRecord] per cycle, the structured form of the printed cycle log.
+
+use arduinox86_client::{BusOp, BusOpCollector, BusState, DataWidth, PhaseBudget, QueueOp, RemoteCpuRegisters, TState};
+
+use crate::{Diagnostic, FpuState, InjectedFault, IoAccess, MemoryHookAccess, OobWrite, RunState, RunStateChange};
+
+/// The result of a completed [crate::RemoteCpu::run].
+///
+/// Automatic runs are executed by the server, so only `registers` and `phase_budget` are filled in
+/// for them.
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    /// Final register state, with IP rewound to the end of the user program.
+    pub registers: RemoteCpuRegisters,
+    /// Cycles executed, including the preload and finalize programs.
+    pub cycles: u32,
+    /// Instructions executed, as counted by first-byte queue reads.
+    pub instructions: u32,
+    pub run_state_changes: Vec<RunStateChange>,
+    pub oob_writes: Vec<OobWrite>,
+    /// Writes to code already in the prefetch queue.
+    pub smc_writes: u32,
+    /// Memory transfer cycles with BHE inactive and A0 odd. See [crate::InvalidWidthPolicy].
+    pub invalid_width_cycles: u32,
+    /// Cycles where the host drove the data bus while the CPU was driving it, and read cycles with
+    /// nothing driving it. See [crate::BusContentionPolicy].
+    pub contention_cycles: u32,
+    pub float_cycles: u32,
+    pub injected_fault: Option<InjectedFault>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The byte fed in place of code past the end of the program, and how many were fed.
+    pub fill_byte: u8,
+    pub fill_ct: u16,
+    /// One record per cycle, if [crate::RunOptions::trace] was set.
+    pub trace: Vec<CycleRecord>,
+    /// Cycles and time spent in each phase of the run. The jump, load and store phases are counted
+    /// by the server. In host-stepped runs the other phases are counted by the host, and their
+    /// time includes the serial round trips of every cycle.
+    pub phase_budget: PhaseBudget,
+    /// The FPU state after the program, if [crate::RunOptions::fpu_save] was set and the state was
+    /// captured.
+    pub fpu_state: Option<FpuState>,
+    /// Port transfers served by the attached [crate::IoDevice]s.
+    pub io_accesses: Vec<IoAccess>,
+    /// Bytes of memory transfers seen by the registered [crate::MemoryHook]s.
+    pub memory_hook_accesses: Vec<MemoryHookAccess>,
+}
+
+/// The bus and queue state of the CPU on a single cycle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CycleRecord {
+    pub cycle: u32,
+    pub run_state: RunState,
+    pub ale: bool,
+    pub address_latch: u32,
+    pub address_bus: u32,
+    pub bus_state: BusState,
+    pub t_state: TState,
+    /// Data bus contents. Only meaningful when `reading` or `writing` is set.
+    pub data_bus: u16,
+    /// The half of the data bus the current bus cycle uses.
+    pub data_width: DataWidth,
+    pub reading: bool,
+    pub writing: bool,
+    pub queue_op: QueueOp,
+    /// The byte read from the queue, if `queue_op` is a queue read.
+    pub queue_byte: Option<u8>,
+    /// Set when the host served a memory transfer for the FPU on this cycle.
+    pub fpu: bool,
+    /// What the host did on this cycle, such as feeding fill bytes or raising an interrupt, as shown
+    /// at the end of the line in the printed cycle log.
+    pub comment: Option<String>,
+}
+
+/// Collect the bus transfers in `trace`, one per bus cycle. `bus_state` is the state of a bus
+/// cycle already underway when the trace starts.
+pub fn bus_ops(trace: &[CycleRecord], bus_state: BusState) -> Vec<BusOp> {
+    let (address, width) = trace
+        .first()
+        .map(|record| (record.address_latch, record.data_width))
+        .unwrap_or((0, DataWidth::Invalid));
+    let mut collector = BusOpCollector::new(bus_state, address, width);
+
+    for record in trace {
+        if record.ale {
+            collector.latch(record.bus_state, record.address_latch, record.data_width);
+        }
+        collector.cycle(
+            record.cycle,
+            (record.reading || record.writing).then_some(record.data_bus),
+        );
+    }
+    collector.finish()
+}
+
+/// One instruction executed by [crate::RemoteCpu::step_instruction], from the cycle its first
+/// byte was read from the queue up to the first byte of the next instruction.
+#[derive(Clone, Debug)]
+pub struct InstructionStepResult {
+    /// Address the first byte of the instruction was fetched from.
+    pub address:  u32,
+    pub opcode:   u8,
+    /// The bytes read from the queue for this instruction, starting with the opcode.
+    pub bytes:    Vec<u8>,
+    pub mnemonic: &'static str,
+    pub cycles:   u32,
+    pub bus_ops:  Vec<BusOp>,
+    /// One record per cycle of the instruction.
+    pub trace:    Vec<CycleRecord>,
+    /// Set when the program ended after this instruction. The run is completed with
+    /// [crate::RemoteCpu::finish_steps].
+    pub finished: bool,
+}
@@ -0,0 +1,130 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Loading of programs split across several segments.
+//!
+//! Each section is written at its own segment:offset and, except for the last, is followed by a
+//! far jump to the start of the next section. Execution starts at the first section and falls
+//! through the trampolines in order, so the sections run as one program. Every section is added
+//! to the program bounds, so fetches from any of them are served as program bytes.
+
+use std::ops::Range;
+
+use arduinox86_client::ServerFlags;
+
+use crate::RemoteCpu;
+
+/// Far jump, `JMP ptr16:16`.
+const OPCODE_JMPF: u8 = 0xEA;
+/// Length of the far jump written after each section but the last.
+pub const TRAMPOLINE_LEN: usize = 5;
+
+/// A block of code to be mounted at `segment:offset`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramSection {
+    pub segment: u16,
+    pub offset:  u16,
+    pub bytes:   Vec<u8>,
+}
+
+impl ProgramSection {
+    pub fn new(segment: u16, offset: u16, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            segment,
+            offset,
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Return the linear address of the start of the section.
+    pub fn address(&self) -> u32 {
+        RemoteCpu::calc_linear_address(self.segment, self.offset)
+    }
+
+    /// Return the far jump to the start of this section.
+    pub fn trampoline(&self) -> [u8; TRAMPOLINE_LEN] {
+        let [off_lo, off_hi] = self.offset.to_le_bytes();
+        let [seg_lo, seg_hi] = self.segment.to_le_bytes();
+        [OPCODE_JMPF, off_lo, off_hi, seg_lo, seg_hi]
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Mount a program made of several sections. Each section but the last is followed by a far
+    /// jump to the next section, then enough fill bytes to cover the prefetches made before the
+    /// jump flushes the queue. The program bounds are replaced by one range per section, in order.
+    /// Sections may not overlap once their trampolines and padding are included.
+    pub fn mount_sections(&mut self, automatic: bool, sections: &[ProgramSection]) -> Result<bool, String> {
+        if sections.is_empty() {
+            return Err("No program sections to mount.".to_string());
+        }
+
+        // The queue may be full and a fetch in flight when the far jump executes.
+        let padding = self.width.queue_size() + 2;
+
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(sections.len());
+        let mut images = Vec::with_capacity(sections.len());
+        for (i, section) in sections.iter().enumerate() {
+            let mut image = section.bytes.clone();
+            if let Some(next) = sections.get(i + 1) {
+                image.extend_from_slice(&next.trampoline());
+                image.resize(image.len() + padding, self.fill_byte);
+            }
+            let start = section.address() as usize;
+            let range = start..start + image.len();
+            if range.end > self.memory.len() {
+                return Err(format!(
+                    "Section {} at [{:04X}:{:04X}] would exceed memory bounds of [{}] bytes.",
+                    i,
+                    section.segment,
+                    section.offset,
+                    self.memory.len()
+                ));
+            }
+            if let Some(j) = ranges
+                .iter()
+                .position(|other| range.start < other.end && other.start < range.end)
+            {
+                return Err(format!(
+                    "Section {} at [{:04X}:{:04X}] overlaps section {}.",
+                    i, section.segment, section.offset, j
+                ));
+            }
+            ranges.push(range);
+            images.push(image);
+        }
+
+        if automatic {
+            self.client
+                .set_flags(ServerFlags::EXECUTE_AUTOMATIC | ServerFlags::USE_SDRAM_BACKEND)
+                .map_err(|e| e.to_string())?;
+        }
+
+        for (range, image) in ranges.iter().zip(&images) {
+            self.load_data(automatic, range.start as u32, image)?;
+            log::debug!("Section mounted at [{:05X}]-[{:05X}]", range.start, range.end);
+        }
+        self.program_ranges = ranges;
+        Ok(true)
+    }
+}
@@ -0,0 +1,229 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Checkpoints of a [RemoteCpu] between runs.
+//!
+//! A [CpuSnapshot] holds what is needed to put the CPU back where a run left it: the registers
+//! stored by the last run, the memory image, the program ranges and the run counters. It is taken
+//! with [RemoteCpu::snapshot], saved with [CpuSnapshot::to_bytes], and put back with
+//! [RemoteCpu::restore], which loads the registers into the physical CPU with the Load command.
+//! The CPU is reset by a register load, so the host's queue model starts out empty after a
+//! restore, as it does after every load, and is not part of the snapshot.
+
+use std::io::Cursor;
+
+use arduinox86_client::RemoteCpuRegisters;
+
+use crate::{RemoteCpu, RemoteCpuError, RunState, ADDRESS_SPACE};
+
+const SNAPSHOT_MAGIC: &[u8; 6] = b"AXSNAP";
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// The state of a [RemoteCpu] between runs.
+#[derive(Clone, Debug)]
+pub struct CpuSnapshot {
+    /// The registers stored by the last run, or last loaded.
+    pub registers: RemoteCpuRegisters,
+    /// The full 1MB memory image.
+    pub memory: Vec<u8>,
+    pub program_ranges: Vec<std::ops::Range<usize>>,
+    pub cycles: u32,
+    pub instructions: u32,
+    pub run_state: RunState,
+}
+
+impl CpuSnapshot {
+    /// Serialize the snapshot to a little-endian binary blob.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut regs = Cursor::new(Vec::new());
+        self.registers.write(&mut regs).expect("Writing to a Vec cannot fail");
+        let regs = regs.into_inner();
+
+        let mut buf = Vec::with_capacity(self.memory.len() + regs.len() + 64);
+        buf.extend_from_slice(SNAPSHOT_MAGIC);
+        buf.push(SNAPSHOT_VERSION);
+        buf.extend_from_slice(&self.cycles.to_le_bytes());
+        buf.extend_from_slice(&self.instructions.to_le_bytes());
+        buf.push(run_state_to_u8(self.run_state));
+        buf.extend_from_slice(&(self.program_ranges.len() as u16).to_le_bytes());
+        for range in &self.program_ranges {
+            buf.extend_from_slice(&(range.start as u32).to_le_bytes());
+            buf.extend_from_slice(&(range.end as u32).to_le_bytes());
+        }
+        buf.extend_from_slice(&(regs.len() as u16).to_le_bytes());
+        buf.extend_from_slice(&regs);
+        buf.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.memory);
+        buf
+    }
+
+    /// Parse a snapshot written by [CpuSnapshot::to_bytes].
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, String> {
+        let mut reader = SnapshotReader { buf, pos: 0 };
+        if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err("Not a CPU snapshot.".to_string());
+        }
+        let version = reader.u8()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(format!("Unsupported snapshot version {}.", version));
+        }
+        let cycles = reader.u32()?;
+        let instructions = reader.u32()?;
+        let run_state = run_state_from_u8(reader.u8()?)?;
+        let range_ct = reader.u16()?;
+        let mut program_ranges = Vec::with_capacity(range_ct as usize);
+        for _ in 0..range_ct {
+            let start = reader.u32()? as usize;
+            let end = reader.u32()? as usize;
+            program_ranges.push(start..end);
+        }
+        let regs_len = reader.u16()? as usize;
+        let registers = RemoteCpuRegisters::try_from(reader.take(regs_len)?)?;
+        let memory_len = reader.u32()? as usize;
+        if memory_len != ADDRESS_SPACE {
+            return Err(format!(
+                "Snapshot memory image is {} bytes, expected {}.",
+                memory_len, ADDRESS_SPACE
+            ));
+        }
+        let memory = reader.take(memory_len)?.to_vec();
+        Ok(Self {
+            registers,
+            memory,
+            program_ranges,
+            cycles,
+            instructions,
+            run_state,
+        })
+    }
+}
+
+struct SnapshotReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| "Snapshot is truncated.".to_string())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn run_state_to_u8(state: RunState) -> u8 {
+    match state {
+        RunState::Init => 0,
+        RunState::Preload => 1,
+        RunState::EmuEnter => 2,
+        RunState::Program => 3,
+        RunState::EmuExit => 4,
+        RunState::Finalize => 5,
+    }
+}
+
+fn run_state_from_u8(value: u8) -> Result<RunState, String> {
+    match value {
+        0 => Ok(RunState::Init),
+        1 => Ok(RunState::Preload),
+        2 => Ok(RunState::EmuEnter),
+        3 => Ok(RunState::Program),
+        4 => Ok(RunState::EmuExit),
+        5 => Ok(RunState::Finalize),
+        _ => Err(format!("Bad run state {} in snapshot.", value)),
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Take a snapshot of the CPU between runs. In automatic mode memory lives on the server, so
+    /// the image is read back from it; otherwise the host's memory is copied.
+    pub fn snapshot(&mut self, automatic: bool) -> Result<CpuSnapshot, RemoteCpuError> {
+        let memory = match automatic {
+            true => {
+                let mut memory = Vec::with_capacity(ADDRESS_SPACE);
+                self.client.read_memory(0, ADDRESS_SPACE as u32, &mut memory)?;
+                memory
+            }
+            false => self.memory.clone(),
+        };
+        Ok(CpuSnapshot {
+            registers: self.regs.clone(),
+            memory,
+            program_ranges: self.program_ranges.clone(),
+            cycles: self.cycle_num,
+            instructions: self.instruction_num,
+            run_state: self.run_state,
+        })
+    }
+
+    /// Put the CPU back in the state of `snapshot`: upload the memory image, load the registers
+    /// into the CPU, and restore the program ranges and run counters. Fails if the memory image is
+    /// not the full address space.
+    pub fn restore(&mut self, snapshot: &CpuSnapshot, automatic: bool) -> Result<(), RemoteCpuError> {
+        if snapshot.memory.len() != ADDRESS_SPACE {
+            return Err(RemoteCpuError::Snapshot(format!(
+                "Snapshot memory image is {} bytes, expected {}.",
+                snapshot.memory.len(),
+                ADDRESS_SPACE
+            )));
+        }
+        if automatic {
+            self.client.set_memory(0, &snapshot.memory)?;
+        }
+        else {
+            self.memory.copy_from_slice(&snapshot.memory);
+        }
+
+        let mut regs = Cursor::new(Vec::new());
+        snapshot
+            .registers
+            .write(&mut regs)
+            .map_err(|e| RemoteCpuError::Snapshot(e.to_string()))?;
+        if !self.load_registers_from_buf(regs.get_ref()) {
+            return Err(RemoteCpuError::Snapshot(
+                "CPU server did not accept the snapshot registers".to_string(),
+            ));
+        }
+
+        self.program_ranges = snapshot.program_ranges.clone();
+        self.cycle_num = snapshot.cycles;
+        self.instruction_num = snapshot.instructions;
+        self.run_state = snapshot.run_state;
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Live validation of an emulator against the CPU.
+//!
+//! An [EmulatorValidator] runs a single instruction on an emulator and reports the bus cycles it
+//! ran and the state it finished in. [RemoteCpu::validate_emulator] runs the same instruction from
+//! the same state on the hardware and compares the two under a [ComparisonPolicy], cycle by cycle,
+//! producing a [ValidationReport]. It is the live counterpart of validating against a test set:
+//! the hardware produces the expected result on the spot, for states no test set covers.
+//!
+//! The hardware run is host-stepped, so the cycle trace can be captured. Only 16-bit register sets
+//! are supported.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use arduinox86_client::{BusState, ComparisonPolicy, RegisterMismatch, Registers16, RemoteCpuRegisters, TState};
+
+use crate::{CycleRecord, PrintOptions, RemoteCpu, RunOptions, RunState};
+
+/// A RAM byte whose final value didn't match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RamMismatch {
+    pub address:  u32,
+    pub expected: u8,
+    pub actual:   u8,
+}
+
+/// The state an instruction starts from.
+#[derive(Clone, Debug)]
+pub struct ValidationCase {
+    pub name: String,
+    pub registers: RemoteCpuRegisters,
+    /// The instruction bytes, mounted at CS:IP.
+    pub instruction: Vec<u8>,
+    /// RAM to load before the run, as (address, value) pairs. Loaded after the instruction, so it
+    /// takes precedence.
+    pub memory: Vec<(u32, u8)>,
+}
+
+/// One bus cycle, as recorded on the hardware or reported by an emulator.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TraceCycle {
+    pub ale: bool,
+    /// The latched address of the bus cycle.
+    pub address: u32,
+    pub bus_state: BusState,
+    pub t_state: TState,
+    /// The data bus contents, on cycles where data was transferred.
+    pub data: Option<u16>,
+}
+
+impl From<&CycleRecord> for TraceCycle {
+    fn from(record: &CycleRecord) -> Self {
+        TraceCycle {
+            ale: record.ale,
+            address: record.address_latch,
+            bus_state: record.bus_state,
+            t_state: record.t_state,
+            data: (record.reading || record.writing).then_some(record.data_bus),
+        }
+    }
+}
+
+impl Display for TraceCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{:05X}] {} {:?}",
+            if self.ale { "A:" } else { "  " },
+            self.address,
+            self.bus_state,
+            self.t_state
+        )?;
+        if let Some(data) = self.data {
+            write!(f, " {:04X}", data)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of running a [ValidationCase], on the hardware or an emulator.
+#[derive(Clone, Debug)]
+pub struct ValidationRun {
+    pub registers: RemoteCpuRegisters,
+    /// The bus cycles of the instruction, from its first cycle to the last.
+    pub cycles:    Vec<TraceCycle>,
+    /// Final RAM, as (address, value) pairs. An emulator reports at least the bytes it wrote.
+    pub memory:    Vec<(u32, u8)>,
+}
+
+/// An emulator that can be validated against the CPU.
+pub trait EmulatorValidator {
+    /// Return the emulator's name, for reports.
+    fn name(&self) -> &str;
+
+    /// Set up the emulator in the case's initial state, run the instruction and report the bus
+    /// cycles and final state.
+    fn run(&mut self, case: &ValidationCase) -> Result<ValidationRun, String>;
+}
+
+/// Options controlling a validation.
+#[derive(Copy, Clone, Debug)]
+pub struct ValidateOptions {
+    /// Flags bits the instruction leaves undefined. Policies that treat undefined flags as
+    /// wildcards skip them.
+    pub undefined_flags: u16,
+    pub cycle_limit: Option<u32>,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        Self {
+            undefined_flags: 0,
+            cycle_limit: Some(10_000),
+        }
+    }
+}
+
+/// The first cycle where the hardware and emulator traces differ. A side that ran out of cycles
+/// is None.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CycleMismatch {
+    pub index:    usize,
+    pub hardware: Option<TraceCycle>,
+    pub emulator: Option<TraceCycle>,
+}
+
+/// The differences between the hardware and an emulator running the same case. The hardware's
+/// results are the expected ones.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    pub name: String,
+    pub emulator: String,
+    pub registers: Vec<RegisterMismatch>,
+    pub ram: Vec<RamMismatch>,
+    /// Hardware and emulator cycle counts, if they differ by more than the policy's tolerance.
+    pub cycles: Option<(usize, usize)>,
+    /// The first differing cycle, if cycles are compared.
+    pub first_cycle_mismatch: Option<CycleMismatch>,
+}
+
+impl ValidationReport {
+    /// Compare an emulator's run of `case` against the hardware's under `policy`.
+    pub fn new(
+        policy: &dyn ComparisonPolicy,
+        case: &ValidationCase,
+        hardware: &ValidationRun,
+        emulator: &ValidationRun,
+        undefined_flags: u16,
+    ) -> Result<Self, String> {
+        let mut report = ValidationReport {
+            name: case.name.clone(),
+            ..Default::default()
+        };
+
+        let pairs = register_pairs16(&hardware.registers, &emulator.registers)?;
+        report.registers = policy.register_mismatches(&pairs, undefined_flags as u32);
+
+        // A byte the emulator doesn't report is taken to be unchanged from the initial RAM.
+        let initial: BTreeMap<u32, u8> = case.memory.iter().copied().collect();
+        let emulator_ram: BTreeMap<u32, u8> = emulator.memory.iter().copied().collect();
+        for &(address, expected) in &hardware.memory {
+            let initial_value = initial.get(&address).copied();
+            if !policy.compare_address(initial_value, expected) {
+                continue;
+            }
+            let actual = emulator_ram.get(&address).copied().or(initial_value);
+            if let Some(actual) = actual.filter(|&actual| actual != expected) {
+                report.ram.push(RamMismatch {
+                    address,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        if policy.cycle_tolerance().is_some() {
+            let (expected, actual) = (hardware.cycles.len(), emulator.cycles.len());
+            if !policy.cycles_match(expected, actual) {
+                report.cycles = Some((expected, actual));
+            }
+            report.first_cycle_mismatch = (0..expected.max(actual))
+                .map(|index| CycleMismatch {
+                    index,
+                    hardware: hardware.cycles.get(index).copied(),
+                    emulator: emulator.cycles.get(index).copied(),
+                })
+                .find(|mismatch| mismatch.hardware != mismatch.emulator);
+        }
+        Ok(report)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.registers.is_empty() && self.ram.is_empty() && self.cycles.is_none() && self.first_cycle_mismatch.is_none()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.passed() {
+            return write!(f, "{} ({}): passed", self.name, self.emulator);
+        }
+        writeln!(f, "{} ({}): failed", self.name, self.emulator)?;
+        for mismatch in &self.registers {
+            writeln!(
+                f,
+                "  {}: hardware {:04X} emulator {:04X}",
+                mismatch.register, mismatch.expected, mismatch.actual
+            )?;
+        }
+        for mismatch in &self.ram {
+            writeln!(
+                f,
+                "  [{:05X}]: hardware {:02X} emulator {:02X}",
+                mismatch.address, mismatch.expected, mismatch.actual
+            )?;
+        }
+        if let Some((expected, actual)) = self.cycles {
+            writeln!(f, "  cycles: hardware {} emulator {}", expected, actual)?;
+        }
+        if let Some(mismatch) = &self.first_cycle_mismatch {
+            let cycle = |cycle: Option<TraceCycle>| cycle.map_or("(none)".to_string(), |cycle| cycle.to_string());
+            writeln!(f, "  first difference on cycle {}:", mismatch.index)?;
+            writeln!(f, "    hardware: {}", cycle(mismatch.hardware))?;
+            writeln!(f, "    emulator: {}", cycle(mismatch.emulator))?;
+        }
+        Ok(())
+    }
+}
+
+impl RemoteCpu<'_> {
+    /// Run a case on the emulator and on the hardware, and compare the results under `policy`.
+    /// Returns an error if either side could not run the case at all; differences are reported in
+    /// the [ValidationReport].
+    pub fn validate_emulator(
+        &mut self,
+        emulator: &mut dyn EmulatorValidator,
+        case: &ValidationCase,
+        options: &ValidateOptions,
+        policy: &dyn ComparisonPolicy,
+    ) -> Result<ValidationReport, String> {
+        let emulated = emulator.run(case)?;
+        let hardware = self.run_validation_case(case, &emulated, options)?;
+
+        let mut report = ValidationReport::new(policy, case, &hardware, &emulated, options.undefined_flags)?;
+        report.emulator = emulator.name().to_string();
+        Ok(report)
+    }
+
+    /// Run a case on the hardware. RAM is read back at every address the case loaded or the
+    /// emulator reported.
+    fn run_validation_case(
+        &mut self,
+        case: &ValidationCase,
+        emulated: &ValidationRun,
+        options: &ValidateOptions,
+    ) -> Result<ValidationRun, String> {
+        let code_address = registers16(&case.registers)?.code_address();
+
+        self.reset();
+        self.mount_bin(false, &case.instruction, code_address as usize)?;
+        self.setup_ivt();
+        self.load_ram_entries(false, &case.memory)?;
+
+        let mut reg_buf = std::io::Cursor::new(Vec::new());
+        case.registers.write(&mut reg_buf).map_err(|e| e.to_string())?;
+        if !self.load_registers_from_buf(reg_buf.get_ref()) {
+            return Err(format!("Register setup failed: {}", self.get_last_error()));
+        }
+
+        let run_options = RunOptions {
+            cycle_limit: options.cycle_limit,
+            print_opts: PrintOptions {
+                print_pgm: false,
+                print_preload: false,
+                print_finalize: false,
+            },
+            trace: true,
+            ..Default::default()
+        };
+        let result = self.run(&run_options).map_err(|e| e.to_string())?;
+
+        let mut addresses: Vec<u32> = case
+            .memory
+            .iter()
+            .chain(&emulated.memory)
+            .map(|&(address, _)| address)
+            .collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        let memory = addresses
+            .into_iter()
+            .map(|address| Ok((address, self.read_data(false, address, 1)?[0])))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(ValidationRun {
+            registers: result.registers,
+            cycles: result
+                .trace
+                .iter()
+                .filter(|record| record.run_state == RunState::Program)
+                .map(TraceCycle::from)
+                .collect(),
+            memory,
+        })
+    }
+}
+
+fn registers16(regs: &RemoteCpuRegisters) -> Result<&dyn Registers16, String> {
+    match regs {
+        RemoteCpuRegisters::V1(regs) => Ok(regs),
+        RemoteCpuRegisters::V2(regs) => Ok(regs),
+        RemoteCpuRegisters::V3(_) => Err("Validation only supports 16-bit register sets".to_string()),
+    }
+}
+
+/// Return `(register, expected, actual)` triples for two 16-bit register sets.
+fn register_pairs16(
+    expected: &RemoteCpuRegisters,
+    actual: &RemoteCpuRegisters,
+) -> Result<[(&'static str, u32, u32); 14], String> {
+    let (e, a) = (registers16(expected)?, registers16(actual)?);
+
+    Ok([
+        ("AX", e.ax(), a.ax()),
+        ("BX", e.bx(), a.bx()),
+        ("CX", e.cx(), a.cx()),
+        ("DX", e.dx(), a.dx()),
+        ("SP", e.sp(), a.sp()),
+        ("BP", e.bp(), a.bp()),
+        ("SI", e.si(), a.si()),
+        ("DI", e.di(), a.di()),
+        ("CS", e.cs(), a.cs()),
+        ("DS", e.ds(), a.ds()),
+        ("ES", e.es(), a.es()),
+        ("SS", e.ss(), a.ss()),
+        ("IP", e.ip(), a.ip()),
+        ("FLAGS", e.flags(), a.flags()),
+    ]
+    .map(|(register, expected, actual)| (register, expected as u32, actual as u32)))
+}
@@ -36,7 +36,7 @@ fn test_aaa() {
     };
 
     // Create a remote cpu instance using the cpu_client which should now be connected.
-    let mut cpu = RemoteCpu::new(cpu_client, false, false, 0, 0, 0, 0);
+    let mut cpu = RemoteCpu::new(cpu_client, false, false, 0, 0, 0, 0).expect("Failed to set up the CPU");
 
     let cf = true;
 
@@ -0,0 +1,95 @@
+mod common;
+
+use arduinox86_cpu::{
+    arduinox86_client::ServerCycleState,
+    BusContentionPolicy,
+    DiagnosticCategory,
+    RemoteCpuError,
+    RunOptions,
+};
+use common::{bus_cycle, scripted_cpu, IOR, MEMR};
+
+/// A memory read during which the CPU also asserts the advanced write strobe, so it drives the bus
+/// while the host serves the read.
+fn contended_read() -> Vec<common::ScriptedCycle> {
+    let strobe = ServerCycleState::COMMAND_MRDC_BIT | ServerCycleState::COMMAND_AMWC_BIT;
+    bus_cycle(MEMR, 0x1000, strobe, true)
+}
+
+/// An I/O read from a port with no device attached, so nothing drives the bus.
+fn floating_read() -> Vec<common::ScriptedCycle> {
+    bus_cycle(IOR, 0x60, ServerCycleState::COMMAND_IORC_BIT, false)
+}
+
+fn run_options(bus_contention_policy: BusContentionPolicy) -> RunOptions {
+    RunOptions {
+        bus_contention_policy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_from_str() {
+    assert_eq!("record".parse::<BusContentionPolicy>(), Ok(BusContentionPolicy::Record));
+    assert_eq!("Fault".parse::<BusContentionPolicy>(), Ok(BusContentionPolicy::Fault));
+    assert!("ignore".parse::<BusContentionPolicy>().is_err());
+}
+
+#[test]
+fn test_default_records() {
+    assert_eq!(RunOptions::default().bus_contention_policy, BusContentionPolicy::Record);
+    assert_eq!(
+        "contention".parse::<DiagnosticCategory>(),
+        Ok(DiagnosticCategory::BusContention)
+    );
+    assert_eq!("float".parse::<DiagnosticCategory>(), Ok(DiagnosticCategory::BusFloat));
+    assert_eq!(DiagnosticCategory::BusFloat.to_string(), "float");
+}
+
+#[test]
+fn test_contention_recorded() {
+    let (mut cpu, log) = scripted_cpu(contended_read());
+    let result = cpu.run(&run_options(BusContentionPolicy::Record)).unwrap();
+
+    assert_eq!((result.contention_cycles, result.float_cycles), (1, 0));
+    assert!(result
+        .diagnostics
+        .iter()
+        .any(|d| d.category == DiagnosticCategory::BusContention));
+    // The host still serves the read.
+    assert_eq!(log.lock().unwrap().driven.len(), 1);
+}
+
+#[test]
+fn test_contention_fault() {
+    let (mut cpu, log) = scripted_cpu(contended_read());
+    let result = cpu.run(&run_options(BusContentionPolicy::Fault));
+
+    assert!(matches!(
+        result,
+        Err(RemoteCpuError::BusContention { address: 0x1000, .. })
+    ));
+    // The host holds off rather than drive against the CPU.
+    assert!(log.lock().unwrap().driven.is_empty());
+}
+
+#[test]
+fn test_float_recorded() {
+    let (mut cpu, log) = scripted_cpu(floating_read());
+    let result = cpu.run(&run_options(BusContentionPolicy::Record)).unwrap();
+
+    assert_eq!((result.contention_cycles, result.float_cycles), (0, 1));
+    assert!(result
+        .diagnostics
+        .iter()
+        .any(|d| d.category == DiagnosticCategory::BusFloat));
+    assert!(log.lock().unwrap().driven.is_empty());
+}
+
+#[test]
+fn test_float_fault() {
+    let (mut cpu, _) = scripted_cpu(floating_read());
+    let result = cpu.run(&run_options(BusContentionPolicy::Fault));
+
+    assert!(matches!(result, Err(RemoteCpuError::BusFloat { address: 0x60, .. })));
+}
@@ -0,0 +1,154 @@
+//! A scripted 8086 server for driving [RemoteCpu] runs without hardware.
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use arduinox86_cpu::{arduinox86_client::*, RemoteCpu};
+
+/// Bus status (S0-S2) of a code fetch, memory read and write, I/O read and write and a passive bus.
+pub const CODE: u8 = 4;
+pub const MEMR: u8 = 5;
+pub const MEMW: u8 = 6;
+pub const IOR: u8 = 1;
+pub const IOW: u8 = 2;
+pub const PASV: u8 = 7;
+
+/// Queue status (QS0-QS1), in the high bits of the status byte.
+pub const QUEUE_FIRST: u8 = 0b01 << 6;
+pub const QUEUE_SUBSEQUENT: u8 = 0b11 << 6;
+
+/// Command strobes and BHE are active-low, so this is a cycle with none of them asserted.
+pub const NO_COMMAND: u8 = 0xFF;
+
+/// One cycle of the script, as the server reports it.
+#[derive(Copy, Clone, Debug)]
+pub struct ScriptedCycle {
+    pub t_state: TState,
+    pub status: u8,
+    pub ale: bool,
+    pub command: u8,
+    pub address: u32,
+    /// The value the CPU drives onto the data bus, if it is writing.
+    pub data: u16,
+}
+
+impl ScriptedCycle {
+    /// An idle cycle, with the queue status `queue`.
+    pub fn idle(queue: u8) -> Self {
+        Self {
+            t_state: TState::Ti,
+            status: PASV | queue,
+            ale: false,
+            command: NO_COMMAND,
+            address: 0,
+            data: 0,
+        }
+    }
+}
+
+/// The four T-states of a bus cycle of type `status` at `address`. `strobe` is the active-low
+/// command bits asserted from T2, and `bhe` whether BHE is asserted throughout.
+pub fn bus_cycle(status: u8, address: u32, strobe: u8, bhe: bool) -> Vec<ScriptedCycle> {
+    let bhe_bits = match bhe {
+        true => ServerCycleState::COMMAND_BHE_BIT,
+        false => 0,
+    };
+    let cycle = |t_state, ale, strobe: u8| ScriptedCycle {
+        t_state,
+        status,
+        ale,
+        command: NO_COMMAND & !bhe_bits & !strobe,
+        address,
+        data: 0,
+    };
+    vec![
+        cycle(TState::T1, true, 0),
+        cycle(TState::T2, false, strobe),
+        cycle(TState::T3, false, strobe),
+        cycle(TState::T4, false, 0),
+    ]
+}
+
+/// What the server saw during a run.
+#[derive(Default)]
+pub struct ServerLog {
+    /// Values the host drove onto the data bus, with the script index of the cycle.
+    pub driven: Vec<(usize, u16)>,
+    /// The register set type and registers of the last Load command.
+    pub loaded: Option<(u8, Vec<u8>)>,
+}
+
+/// The reply to a cycle state request: the cycle, or the program done once the script has ended.
+fn cycle_state(cycle: Option<ScriptedCycle>) -> Vec<u8> {
+    let state = match cycle {
+        Some(_) => ProgramState::Execute,
+        None => ProgramState::ExecuteDone,
+    };
+    let cycle = cycle.unwrap_or(ScriptedCycle::idle(0));
+    let mut reply = vec![
+        state as u8,
+        cycle.t_state as u8,
+        cycle.status,
+        cycle.ale as u8,
+        cycle.command,
+    ];
+    reply.extend_from_slice(&cycle.address.to_le_bytes());
+    reply.extend_from_slice(&cycle.data.to_le_bytes());
+    reply
+}
+
+/// Play back `script` one cycle per cycle request, then report the program done. `cpu_type` is the
+/// server's reply to the CPU type command.
+fn scripted_server(script: Vec<ScriptedCycle>, cpu_type: u8, log: Arc<Mutex<ServerLog>>) -> MockTransport {
+    let mut index = 0;
+    MockTransport::new(move |request| {
+        let current = script.get(index).copied();
+        let program_state = match current {
+            Some(_) => ProgramState::Execute,
+            None => ProgramState::ExecuteDone,
+        };
+        let ok = |reply: &[u8]| [reply, &[0x01]].concat();
+        match request[0] {
+            0x01 => [SERVER_BANNER, &[REQUIRED_PROTOCOL_VER]].concat(),
+            0x2D => ok(&Capabilities::for_version(REQUIRED_PROTOCOL_VER).0.to_le_bytes()),
+            0x18 => ok(&[cpu_type]),
+            0x14 if request.get(1) == Some(&1) => {
+                index += 1;
+                ok(&cycle_state(script.get(index).copied()))
+            }
+            0x14 => ok(&cycle_state(current)),
+            0x17 => ok(&current.map_or(0, |cycle| cycle.address).to_le_bytes()[..3]),
+            0x0A => {
+                let data = u16::from_le_bytes([request[1], request[2]]);
+                log.lock().unwrap().driven.push((index, data));
+                ok(&[])
+            }
+            0x03 => {
+                log.lock().unwrap().loaded = Some((request[1], request[2..].to_vec()));
+                ok(&[])
+            }
+            0x09 => ok(&current.map_or(0, |cycle| cycle.data).to_le_bytes()),
+            0x12 => ok(&[program_state as u8]),
+            0x23 => ok(&[0; 4]),
+            // A V1 register set.
+            0x0D => [&[0x00][..], &[0; 28], &[0x01]].concat(),
+            // Set flags, write pin, finalize and the store program.
+            0x19 | 0x10 | 0x0B | 0x16 => ok(&[]),
+            _ => vec![0x00],
+        }
+    })
+}
+
+/// Connect a [RemoteCpu] to an 8086 server, without an FPU, that plays back `script`.
+pub fn scripted_cpu(script: Vec<ScriptedCycle>) -> (RemoteCpu<'static>, Arc<Mutex<ServerLog>>) {
+    scripted_cpu_type(script, 0x02)
+}
+
+/// Connect a [RemoteCpu] to a server that plays back `script` and reports the CPU type `cpu_type`.
+pub fn scripted_cpu_type(script: Vec<ScriptedCycle>, cpu_type: u8) -> (RemoteCpu<'static>, Arc<Mutex<ServerLog>>) {
+    let log = Arc::new(Mutex::new(ServerLog::default()));
+    let transport = scripted_server(script, cpu_type, log.clone());
+    let client = CpuClient::with_transport(Box::new(transport), &DiscoveryOptions::default()).unwrap();
+    let cpu = RemoteCpu::new(client, false, false, 0, 0, 0, 0).unwrap();
+    (cpu, log)
+}
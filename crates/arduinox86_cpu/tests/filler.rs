@@ -0,0 +1,55 @@
+mod common;
+
+use arduinox86_cpu::{arduinox86_client::ServerCycleState, Filler, RunOptions};
+use common::{bus_cycle, scripted_cpu, CODE};
+
+/// Two word fetches from a three-byte program, the second of which runs past its end.
+fn fetch_past_end() -> Vec<common::ScriptedCycle> {
+    let mut script = bus_cycle(CODE, 0x100, ServerCycleState::COMMAND_MRDC_BIT, true);
+    script.extend(bus_cycle(CODE, 0x102, ServerCycleState::COMMAND_MRDC_BIT, true));
+    script
+}
+
+#[test]
+fn test_resolve() {
+    assert_eq!(Filler::Auto.resolve(false), Ok(0x90));
+    assert_eq!(Filler::Auto.resolve(true), Ok(0x00));
+    assert_eq!(Filler::Nop80.resolve(true), Ok(0x00));
+    assert_eq!(Filler::Byte(0xF4).resolve(false), Ok(0xF4));
+    assert!(Filler::Nop.resolve(true).is_err());
+    assert!(Filler::Nop80.resolve(false).is_err());
+}
+
+#[test]
+fn test_from_str() {
+    assert_eq!("auto".parse::<Filler>(), Ok(Filler::Auto));
+    assert_eq!("NOP80".parse::<Filler>(), Ok(Filler::Nop80));
+    assert_eq!("0xf4".parse::<Filler>(), Ok(Filler::Byte(0xF4)));
+    assert_eq!("cc".parse::<Filler>(), Ok(Filler::Byte(0xCC)));
+    assert!("sub".parse::<Filler>().is_err());
+    assert_eq!(Filler::Byte(0xCC).to_string(), "CC");
+}
+
+#[test]
+fn test_fill_past_end() {
+    let (mut cpu, log) = scripted_cpu(fetch_past_end());
+    cpu.mount_bin(false, &[0x90, 0x90, 0x90], 0x100).unwrap();
+    let result = cpu.run(&RunOptions::default()).unwrap();
+
+    // The high byte of the second fetch lies past the program.
+    assert_eq!(log.lock().unwrap().driven, [(1, 0x9090), (5, 0x9090)]);
+    assert_eq!(result.fill_byte, 0x90);
+    assert_eq!(result.fill_ct, 1);
+}
+
+#[test]
+fn test_fill_selected_byte() {
+    let (mut cpu, log) = scripted_cpu(fetch_past_end());
+    cpu.mount_bin(false, &[0x90, 0x90, 0x90], 0x100).unwrap();
+    cpu.set_filler(Filler::Byte(0xF4)).unwrap();
+    let result = cpu.run(&RunOptions::default()).unwrap();
+
+    assert_eq!(log.lock().unwrap().driven, [(1, 0x9090), (5, 0xF490)]);
+    assert_eq!(result.fill_byte, 0xF4);
+    assert_eq!(result.fill_ct, 1);
+}
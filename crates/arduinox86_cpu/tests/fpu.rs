@@ -0,0 +1,47 @@
+use arduinox86_cpu::{is_esc_opcode, DiagnosticCategory, FpuState, RunOptions, FPU_STATE_LEN};
+
+fn fnsave_block() -> Vec<u8> {
+    let mut bytes = vec![0; FPU_STATE_LEN];
+    // Control, status (TOP = 7) and tag words.
+    bytes[0..6].copy_from_slice(&[0x7F, 0x03, 0x00, 0x38, 0xFF, 0x3F]);
+    // Instruction pointer F0123 with opcode 0x1E8 (D9 E8, FLD1).
+    bytes[6..10].copy_from_slice(&[0x23, 0x01, 0xE8, 0xF1]);
+    // Operand pointer 4ABCD.
+    bytes[10..14].copy_from_slice(&[0xCD, 0xAB, 0x00, 0x40]);
+    // ST(0) = 1.0
+    bytes[14..24].copy_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0x80, 0xFF, 0x3F]);
+    bytes
+}
+
+#[test]
+fn test_parse_fnsave_block() {
+    let state = FpuState::from_bytes(&fnsave_block()).unwrap();
+    assert_eq!(state.control, 0x037F);
+    assert_eq!(state.status, 0x3800);
+    assert_eq!(state.top(), 7);
+    assert_eq!(state.tag, 0x3FFF);
+    assert_eq!(state.instruction_pointer, 0xF0123);
+    assert_eq!(state.opcode, 0x1E8);
+    assert_eq!(state.operand_pointer, 0x4ABCD);
+    assert_eq!(state.stack[0], [0, 0, 0, 0, 0, 0, 0, 0x80, 0xFF, 0x3F]);
+    assert!(state.to_string().contains("ST(0): 3FFF8000000000000000"));
+
+    assert_eq!(state.to_bytes().to_vec(), fnsave_block());
+    assert!(FpuState::from_bytes(&fnsave_block()[..FPU_STATE_LEN - 1]).is_none());
+}
+
+#[test]
+fn test_esc_opcodes() {
+    assert!(is_esc_opcode(0xD8));
+    assert!(is_esc_opcode(0xDF));
+    assert!(!is_esc_opcode(0x9B));
+    assert!(!is_esc_opcode(0xE0));
+}
+
+#[test]
+fn test_fpu_options() {
+    let options = RunOptions::default();
+    assert!(!options.fpu_save);
+    assert_eq!(options.fpu_wait_limit, None);
+    assert_eq!("fpu".parse::<DiagnosticCategory>().unwrap(), DiagnosticCategory::Fpu);
+}
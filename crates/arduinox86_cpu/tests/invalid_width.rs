@@ -0,0 +1,90 @@
+mod common;
+
+use arduinox86_cpu::{
+    arduinox86_client::ServerCycleState,
+    DiagnosticCategory,
+    DiagnosticSeverity,
+    InvalidWidthPolicy,
+    RemoteCpuError,
+    RunOptions,
+};
+use common::{bus_cycle, scripted_cpu, MEMR};
+
+/// A memory read at an odd address with BHE inactive, which no data lane can carry.
+fn odd_read() -> Vec<common::ScriptedCycle> {
+    bus_cycle(MEMR, 0x1001, ServerCycleState::COMMAND_MRDC_BIT, false)
+}
+
+fn run_options(invalid_width_policy: InvalidWidthPolicy) -> RunOptions {
+    RunOptions {
+        invalid_width_policy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_from_str() {
+    assert_eq!("record".parse::<InvalidWidthPolicy>(), Ok(InvalidWidthPolicy::Record));
+    assert_eq!(
+        "Open_Bus".parse::<InvalidWidthPolicy>(),
+        Ok(InvalidWidthPolicy::OpenBus)
+    );
+    assert_eq!("fault".parse::<InvalidWidthPolicy>(), Ok(InvalidWidthPolicy::Fault));
+    assert!("ignore".parse::<InvalidWidthPolicy>().is_err());
+}
+
+#[test]
+fn test_default_records() {
+    assert_eq!(RunOptions::default().invalid_width_policy, InvalidWidthPolicy::Record);
+    assert_eq!(
+        "invalid_width".parse::<DiagnosticCategory>(),
+        Ok(DiagnosticCategory::InvalidWidth)
+    );
+    assert_eq!(DiagnosticCategory::InvalidWidth.to_string(), "invalid_width");
+}
+
+#[test]
+fn test_record() {
+    let (mut cpu, log) = scripted_cpu(odd_read());
+    let result = cpu.run(&run_options(InvalidWidthPolicy::Record)).unwrap();
+
+    assert_eq!(result.invalid_width_cycles, 1);
+    let diagnostic = result
+        .diagnostics
+        .iter()
+        .find(|d| d.category == DiagnosticCategory::InvalidWidth)
+        .unwrap();
+    assert_eq!(diagnostic.severity, DiagnosticSeverity::Warning);
+    assert!(diagnostic.message.contains("[01001]"));
+    assert_eq!(log.lock().unwrap().driven, [(1, 0x0000)]);
+}
+
+#[test]
+fn test_open_bus() {
+    let (mut cpu, log) = scripted_cpu(odd_read());
+    let result = cpu.run(&run_options(InvalidWidthPolicy::OpenBus)).unwrap();
+
+    assert_eq!(result.invalid_width_cycles, 1);
+    assert_eq!(log.lock().unwrap().driven, [(1, 0xFFFF)]);
+}
+
+#[test]
+fn test_fault() {
+    let (mut cpu, _) = scripted_cpu(odd_read());
+    let result = cpu.run(&run_options(InvalidWidthPolicy::Fault));
+
+    assert!(matches!(
+        result,
+        Err(RemoteCpuError::InvalidWidth { address: 0x1001, .. })
+    ));
+    assert_eq!(cpu.invalid_width_count(), 1);
+}
+
+#[test]
+fn test_even_read_is_valid() {
+    let script = bus_cycle(MEMR, 0x1000, ServerCycleState::COMMAND_MRDC_BIT, false);
+    let (mut cpu, _) = scripted_cpu(script);
+    let result = cpu.run(&run_options(InvalidWidthPolicy::Fault)).unwrap();
+
+    assert_eq!(result.invalid_width_cycles, 0);
+}
@@ -0,0 +1,27 @@
+use arduinox86_cpu::{IoDevice, LatchDevice};
+
+#[test]
+fn test_latch_device() {
+    // DMA page registers.
+    let mut pages = LatchDevice::new("dma_page", 0x80..0x90, 0x00);
+    assert_eq!(pages.name(), "dma_page");
+    pages.write_u8(0x87, 0x12);
+    assert_eq!(pages.read_u8(0x87), 0x12);
+    assert_eq!(pages.latch(0x87), Some(0x12));
+    assert_eq!(pages.read_u8(0x80), 0x00);
+
+    // Ports outside the device read as open bus and ignore writes.
+    pages.write_u8(0x90, 0x34);
+    assert_eq!(pages.latch(0x90), None);
+    assert_eq!(pages.read_u8(0x90), 0xFF);
+}
+
+#[test]
+fn test_word_access_defaults() {
+    let mut latches = LatchDevice::new("latches", 0x40..0x44, 0xFF);
+    latches.write_u16(0x42, 0xBEEF);
+    assert_eq!(latches.latch(0x42), Some(0xEF));
+    assert_eq!(latches.latch(0x43), Some(0xBE));
+    assert_eq!(latches.read_u16(0x42), 0xBEEF);
+    assert_eq!(latches.read_u16(0x40), 0xFFFF);
+}
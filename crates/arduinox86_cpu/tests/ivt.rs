@@ -0,0 +1,40 @@
+use arduinox86_cpu::{Ivt, RemoteCpu, DEFAULT_ISR_SEGMENT, ISR_STUB_LEN};
+
+#[test]
+fn test_default_ivt() {
+    let ivt = Ivt::default();
+    assert_eq!(ivt.segment(), DEFAULT_ISR_SEGMENT);
+    assert_eq!(ivt.vector(0x21), (DEFAULT_ISR_SEGMENT, 0x42));
+    assert_eq!(ivt.image().len(), 256 * ISR_STUB_LEN);
+    assert_eq!(&ivt.image()[0x42..0x44], &[0xCF, 0x90]);
+    assert_eq!(ivt.range(), 0xF8000..0xF8200);
+    assert_eq!(ivt.vector_at(0xF8043), Some(0x21));
+    assert_eq!(ivt.vector_at(0xF8200), None);
+}
+
+#[test]
+fn test_custom_isrs() {
+    // POP AX, PUSH AX, IRET
+    let ivt = RemoteCpu::ivt_builder()
+        .segment(0xE000)
+        .stub(0x03, [0xF4, 0x90])
+        .isr(0x21, [0x58, 0x50, 0xCF])
+        .isr(0x10, [0xCF])
+        .build()
+        .unwrap();
+
+    assert_eq!(&ivt.image()[0x06..0x08], &[0xF4, 0x90]);
+    // ISR programs follow the stub table in vector order.
+    assert_eq!(ivt.vector(0x10), (0xE000, 0x200));
+    assert_eq!(ivt.vector(0x21), (0xE000, 0x201));
+    assert_eq!(&ivt.image()[0x201..], &[0x58, 0x50, 0xCF]);
+    assert_eq!(ivt.range(), 0xE0000..0xE0204);
+    assert_eq!(ivt.vector_at(0xE0203), Some(0x21));
+}
+
+#[test]
+fn test_invalid_ivt() {
+    assert!(RemoteCpu::ivt_builder().isr(0x21, []).build().is_err());
+    assert!(RemoteCpu::ivt_builder().segment(0x0000).build().is_err());
+    assert!(RemoteCpu::ivt_builder().isr(0x21, vec![0xCF; 0x10000]).build().is_err());
+}
@@ -0,0 +1,40 @@
+use arduinox86_cpu::{MemoryHook, WatchHook};
+
+/// Serves reads from its own buffer and absorbs writes, like a video RAM window.
+struct VideoRam {
+    base:  u32,
+    bytes: Vec<u8>,
+}
+
+impl MemoryHook for VideoRam {
+    fn read(&mut self, address: u32, _value: u8) -> Option<u8> {
+        self.bytes.get((address - self.base) as usize).copied()
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> bool {
+        if let Some(byte) = self.bytes.get_mut((address - self.base) as usize) {
+            *byte = value;
+        }
+        false
+    }
+}
+
+#[test]
+fn test_watch_hook_passes_through() {
+    let mut watch = WatchHook::new("ivt");
+    assert_eq!(watch.name(), "ivt");
+    assert_eq!(watch.read(0x00010, 0x12), None);
+    assert!(watch.write(0x00010, 0x34));
+}
+
+#[test]
+fn test_device_backed_hook() {
+    let mut vram = VideoRam {
+        base:  0xB8000,
+        bytes: vec![0x20; 0x10],
+    };
+    assert_eq!(vram.name(), "hook");
+    assert!(!vram.write(0xB8002, 0x41));
+    assert_eq!(vram.read(0xB8002, 0x00), Some(0x41));
+    assert_eq!(vram.read(0xB8003, 0x00), Some(0x20));
+}
@@ -0,0 +1,67 @@
+use arduinox86_cpu::{get_opcode_str, is_group_op, DecodeArch};
+
+#[test]
+fn test_nec_extended_opcodes() {
+    let nec = |op2| get_opcode_str(0x0F, op2, true, DecodeArch::NecV20);
+    assert_eq!(nec(0x10), "TEST1");
+    assert_eq!(nec(0x19), "TEST1");
+    assert_eq!(nec(0x20), "ADD4S");
+    assert_eq!(nec(0x28), "ROL4");
+    assert_eq!(nec(0xFF), "BRKEM");
+    assert_eq!(nec(0x21), "INVAL");
+    // Without the second byte the extended opcode can't be named.
+    assert_eq!(get_opcode_str(0x0F, 0x20, false, DecodeArch::NecV20), "EXT0F");
+    // 0x0F is POP CS on the 8088 and undefined on the 80186.
+    assert_eq!(get_opcode_str(0x0F, 0x20, true, DecodeArch::Intel8088), "POP");
+    assert_eq!(get_opcode_str(0x0F, 0x20, true, DecodeArch::Intel80186), "INVAL");
+}
+
+#[test]
+fn test_immediate_shifts() {
+    // The reg field of the modrm selects the operation.
+    for arch in [DecodeArch::Intel80186, DecodeArch::NecV20] {
+        assert_eq!(get_opcode_str(0xC0, 0b00_000_000, true, arch), "ROL");
+        assert_eq!(get_opcode_str(0xC1, 0b11_101_001, true, arch), "SHR");
+        assert_eq!(get_opcode_str(0xC1, 0b00_111_000, true, arch), "SAR");
+        assert_eq!(get_opcode_str(0xC0, 0, false, arch), "GRP2A");
+    }
+    // On the 8088 they alias RETN.
+    assert_eq!(get_opcode_str(0xC0, 0b00_101_000, true, DecodeArch::Intel8088), "RETN");
+}
+
+#[test]
+fn test_63_to_67() {
+    for op1 in 0x63..=0x67 {
+        assert_eq!(get_opcode_str(op1, 0, false, DecodeArch::Intel80186), "INVAL");
+    }
+    assert_eq!(get_opcode_str(0x63, 0, false, DecodeArch::NecV20), "INVAL");
+    assert_eq!(get_opcode_str(0x64, 0, false, DecodeArch::NecV20), "REPNC");
+    assert_eq!(get_opcode_str(0x65, 0, false, DecodeArch::NecV20), "REPC");
+    assert_eq!(get_opcode_str(0x66, 0, false, DecodeArch::NecV20), "FPO2");
+    assert_eq!(get_opcode_str(0x67, 0, false, DecodeArch::NecV20), "FPO2");
+}
+
+#[test]
+fn test_186_additions() {
+    for arch in [DecodeArch::Intel80186, DecodeArch::NecV20] {
+        assert_eq!(get_opcode_str(0x60, 0, false, arch), "PUSHA");
+        assert_eq!(get_opcode_str(0x62, 0, false, arch), "BOUND");
+        assert_eq!(get_opcode_str(0x6B, 0, false, arch), "IMUL");
+        assert_eq!(get_opcode_str(0xC8, 0, false, arch), "ENTER");
+    }
+}
+
+#[test]
+fn test_is_group_op() {
+    assert!(is_group_op(0x80, DecodeArch::Intel8088));
+    assert!(!is_group_op(0xC0, DecodeArch::Intel8088));
+    assert!(is_group_op(0xC0, DecodeArch::Intel80186));
+    assert!(!is_group_op(0x0F, DecodeArch::Intel80186));
+    assert!(is_group_op(0x0F, DecodeArch::NecV20));
+    assert!(is_group_op(0xC1, DecodeArch::NecV20));
+    assert!(is_group_op(0xFE, DecodeArch::NecV20));
+    // 8080 opcodes have no modrm, including the ones that are groups on the 8088.
+    for op1 in [0x0F, 0x80, 0xC0, 0xFE, 0xFF] {
+        assert!(!is_group_op(op1, DecodeArch::Intel8080));
+    }
+}
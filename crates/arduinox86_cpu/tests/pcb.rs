@@ -0,0 +1,74 @@
+mod common;
+
+use arduinox86_cpu::{
+    arduinox86_client::ServerCycleState,
+    PeripheralControlBlock,
+    RunOptions,
+    WatchHook,
+    PCB_RELOCATION_RESET,
+};
+use common::{bus_cycle, scripted_cpu_type, ScriptedCycle, IOW, MEMW};
+
+/// An 80186 without queue status.
+const CPU_80186: u8 = 0x06;
+
+#[test]
+fn test_pcb_reset_location() {
+    let pcb = PeripheralControlBlock::default();
+    assert_eq!(pcb.relocation(), PCB_RELOCATION_RESET);
+    assert_eq!(pcb.base(), 0xFF00);
+    assert!(!pcb.in_memory());
+    assert_eq!(pcb.offset(0xFF22, false), Some(0x22));
+    assert_eq!(pcb.offset(0xFEFF, false), None);
+    // The block is in I/O space, so memory at the same address is not claimed.
+    assert_eq!(pcb.offset(0xFF22, true), None);
+}
+
+#[test]
+fn test_pcb_relocation() {
+    let mut pcb = PeripheralControlBlock::default();
+
+    // Move the block to E000:0000 in memory space.
+    pcb.write(0xFE, 0x1E00, true);
+    assert!(pcb.in_memory());
+    assert_eq!(pcb.base(), 0xE0000);
+    assert_eq!(pcb.offset(0xE00FE, true), Some(0xFE));
+    assert_eq!(pcb.offset(0xFF00, false), None);
+
+    // Byte writes replace the half of the register they address.
+    pcb.write(0xFF, 0x00, false);
+    assert_eq!(pcb.relocation(), 0x0000);
+    pcb.write(0xFE, 0x10, false);
+    assert_eq!(pcb.base(), 0x1000);
+    assert!(!pcb.in_memory());
+
+    // Writes to other registers leave the block where it is.
+    pcb.write(0x22, 0x8000, true);
+    assert_eq!(pcb.relocation(), 0x0010);
+}
+
+/// A write of `data` to `address` that the CPU drives for the whole bus cycle.
+fn write_cycle(status: u8, address: u32, strobe: u8, data: u16) -> Vec<ScriptedCycle> {
+    bus_cycle(status, address, strobe, true)
+        .into_iter()
+        .map(|cycle| ScriptedCycle { data, ..cycle })
+        .collect()
+}
+
+#[test]
+fn test_pcb_relocated_by_memory_write() {
+    // Move the block to E000:0000 in memory space, then from there to D000:0000.
+    let mut script = write_cycle(IOW, 0xFFFE, ServerCycleState::COMMAND_IOWC_BIT, 0x1E00);
+    script.extend(write_cycle(MEMW, 0xE00FE, ServerCycleState::COMMAND_MWTC_BIT, 0x1D00));
+    let (mut cpu, _) = scripted_cpu_type(script, CPU_80186);
+    cpu.mount_bin(false, &[0x90; 0x200], 0x100).unwrap();
+    cpu.add_memory_hook(0xE0000..0xE0100, Box::new(WatchHook::new("old pcb")))
+        .unwrap();
+    cpu.run(&RunOptions::default()).unwrap();
+
+    let pcb = cpu.pcb().unwrap();
+    assert_eq!(pcb.base(), 0xD0000);
+    // The write strobe stays asserted after the block has moved, but the block already took the
+    // write, so it doesn't reach memory at the old address.
+    assert!(cpu.memory_hook_accesses().is_empty());
+}
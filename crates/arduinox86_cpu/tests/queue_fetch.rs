@@ -0,0 +1,74 @@
+mod common;
+
+use arduinox86_cpu::{arduinox86_client::ServerCycleState, DiagnosticCategory, RemoteCpuError, RunOptions, RunResult};
+use common::{bus_cycle, scripted_cpu, ScriptedCycle, CODE, QUEUE_FIRST};
+
+/// Fetch a word at each of `fetches` without a flush in between, then read three one-byte
+/// instructions from the queue.
+fn fetch_and_run(fetches: [u32; 2]) -> Vec<ScriptedCycle> {
+    let mut script: Vec<ScriptedCycle> = fetches
+        .iter()
+        .flat_map(|&address| bus_cycle(CODE, address, ServerCycleState::COMMAND_MRDC_BIT, true))
+        .collect();
+    script.extend([ScriptedCycle::idle(QUEUE_FIRST); 3]);
+    script
+}
+
+fn run(script: Vec<ScriptedCycle>, strict_queue: bool) -> Result<RunResult, RemoteCpuError> {
+    let (mut cpu, _) = scripted_cpu(script);
+    // NOPs, so each byte is an instruction of its own.
+    cpu.mount_bin(false, &[0x90; 0x200], 0x100).unwrap();
+    cpu.run(&RunOptions {
+        strict_queue,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_queue_fetch_category() {
+    assert_eq!(
+        "queue_fetch".parse::<DiagnosticCategory>(),
+        Ok(DiagnosticCategory::QueueFetch)
+    );
+    assert_eq!(DiagnosticCategory::QueueFetch.to_string(), "queue_fetch");
+}
+
+#[test]
+fn test_default_not_strict() {
+    assert!(!RunOptions::default().strict_queue);
+}
+
+#[test]
+fn test_sequential_fetch() {
+    let result = run(fetch_and_run([0x100, 0x102]), true).unwrap();
+    assert!(!result
+        .diagnostics
+        .iter()
+        .any(|d| d.category == DiagnosticCategory::QueueFetch));
+}
+
+#[test]
+fn test_missed_flush_recorded() {
+    // The queue holds 0x100, 0x101, 0x200 - the jump to 0x200 was never flushed.
+    let result = run(fetch_and_run([0x100, 0x200]), false).unwrap();
+    let diagnostics: Vec<_> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.category == DiagnosticCategory::QueueFetch)
+        .collect();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.contains("at [00200], expected [00102]"));
+}
+
+#[test]
+fn test_missed_flush_strict() {
+    let result = run(fetch_and_run([0x100, 0x200]), true);
+    assert!(matches!(
+        result,
+        Err(RemoteCpuError::QueueFetch {
+            address: 0x200,
+            expected: 0x102,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,16 @@
+use arduinox86_cpu::QueueProvenance;
+
+#[test]
+fn test_program_index() {
+    assert_eq!(QueueProvenance::ProgramByteIndex(3).program_index(), Some(3));
+    assert_eq!(QueueProvenance::PreloadFill.program_index(), None);
+    assert_eq!(QueueProvenance::OutOfBounds.program_index(), None);
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(QueueProvenance::ProgramByteIndex(12).to_string(), "program[12]");
+    assert_eq!(QueueProvenance::PreloadFill.to_string(), "preload fill");
+    assert_eq!(QueueProvenance::IsrBody.to_string(), "isr");
+    assert_eq!(QueueProvenance::OutOfBounds.to_string(), "out of bounds");
+}
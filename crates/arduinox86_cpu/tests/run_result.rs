@@ -0,0 +1,80 @@
+use arduinox86_cpu::{
+    arduinox86_client::{BusState, CpuClientError, DataWidth, QueueOp, TState},
+    bus_ops,
+    BusOp,
+    CycleRecord,
+    RemoteCpuError,
+    RunOptions,
+    RunState,
+};
+
+fn record(cycle: u32, ale: bool, bus_state: BusState, data: Option<u16>) -> CycleRecord {
+    CycleRecord {
+        cycle,
+        run_state: RunState::Program,
+        ale,
+        address_latch: 0x100 + cycle,
+        address_bus: 0x100 + cycle,
+        bus_state,
+        t_state: TState::T1,
+        data_bus: data.unwrap_or(0),
+        data_width: DataWidth::Sixteen,
+        reading: data.is_some(),
+        writing: false,
+        queue_op: QueueOp::Idle,
+        queue_byte: None,
+        fpu: false,
+        comment: None,
+    }
+}
+
+#[test]
+fn test_trace_off_by_default() {
+    assert!(!RunOptions::default().trace);
+}
+
+#[test]
+fn test_error_display() {
+    let err = RemoteCpuError::OutOfBoundsWrite {
+        address: 0xFFFF,
+        cycle:   12,
+    };
+    assert_eq!(err.to_string(), "Out-of-bounds write to [0FFFF] on cycle 12");
+
+    let err = RemoteCpuError::from(CpuClientError::ReadTimeout);
+    assert!(matches!(err, RemoteCpuError::Client(CpuClientError::ReadTimeout)));
+    assert_eq!(err.to_string(), "Response timeout waiting for command.");
+}
+
+#[test]
+fn test_bus_ops_one_per_transfer() {
+    let trace = vec![
+        // The tail of a code fetch that started before the trace.
+        record(0, false, BusState::PASV, Some(0x1234)),
+        record(1, true, BusState::MEMR, None),
+        record(2, false, BusState::MEMR, Some(0x00AA)),
+        // Data held over a wait state is one transfer, with the data of its last cycle.
+        record(3, false, BusState::PASV, Some(0x00BB)),
+        record(4, false, BusState::PASV, None),
+    ];
+
+    assert_eq!(
+        bus_ops(&trace, BusState::CODE),
+        vec![
+            BusOp {
+                cycle: 0,
+                bus_state: BusState::CODE,
+                address: 0x100,
+                data: 0x1234,
+                width: DataWidth::Sixteen,
+            },
+            BusOp {
+                cycle: 3,
+                bus_state: BusState::MEMR,
+                address: 0x101,
+                data: 0x00BB,
+                width: DataWidth::Sixteen,
+            },
+        ]
+    );
+}
@@ -0,0 +1,19 @@
+use arduinox86_cpu::{ProgramSection, TRAMPOLINE_LEN};
+
+#[test]
+fn test_section_address() {
+    let section = ProgramSection::new(0x1234, 0x0010, [0x90]);
+    assert_eq!(section.address(), 0x12350);
+
+    // Linear addresses wrap at 1MB.
+    let section = ProgramSection::new(0xFFFF, 0x0020, [0x90]);
+    assert_eq!(section.address(), 0x00010);
+}
+
+#[test]
+fn test_trampoline() {
+    let section = ProgramSection::new(0xF000, 0x0100, [0xF4]);
+    let trampoline = section.trampoline();
+    assert_eq!(trampoline.len(), TRAMPOLINE_LEN);
+    assert_eq!(trampoline, [0xEA, 0x00, 0x01, 0x00, 0xF0]);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use arduinox86_cpu::{arduinox86_client::*, CpuSnapshot, RemoteCpuError, RunState};
+use common::scripted_cpu;
+
+fn snapshot() -> CpuSnapshot {
+    let mut memory = vec![0; 0x10_0000];
+    memory[0x1000] = 0x90;
+    memory[0xFFFFF] = 0xEA;
+    CpuSnapshot {
+        registers: RemoteCpuRegisters::V1(RemoteCpuRegistersV1 {
+            ax: 0x1234,
+            cs: 0xF000,
+            ip: 0xFFF0,
+            ..Default::default()
+        }),
+        memory,
+        program_ranges: vec![0x1000..0x1010],
+        cycles: 1234,
+        instructions: 56,
+        run_state: RunState::Finalize,
+    }
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let original = snapshot();
+    let restored = CpuSnapshot::from_bytes(&original.to_bytes()).unwrap();
+
+    assert_eq!(restored.registers.ax(), 0x1234);
+    assert_eq!(restored.registers.code_address(), 0xFFFF0);
+    assert_eq!(restored.memory, original.memory);
+    assert_eq!(restored.program_ranges, vec![0x1000..0x1010]);
+    assert_eq!(restored.cycles, 1234);
+    assert_eq!(restored.instructions, 56);
+    assert_eq!(restored.run_state, RunState::Finalize);
+}
+
+#[test]
+fn test_snapshot_rejects_bad_blobs() {
+    let bytes = snapshot().to_bytes();
+    assert!(CpuSnapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert!(CpuSnapshot::from_bytes(&bad_magic).is_err());
+
+    let mut bad_version = bytes;
+    bad_version[6] = 0xFF;
+    assert!(CpuSnapshot::from_bytes(&bad_version).is_err());
+}
+
+#[test]
+fn test_restore() {
+    let (mut cpu, log) = scripted_cpu(Vec::new());
+    cpu.restore(&snapshot(), false).unwrap();
+
+    let (set_type, regs) = log.lock().unwrap().loaded.clone().unwrap();
+    assert_eq!(set_type, u8::from(RegisterSetType::Intel8088));
+    assert_eq!(RemoteCpuRegistersV1::from(regs.as_slice()).ax, 0x1234);
+
+    let restored = cpu.snapshot(false).unwrap();
+    assert_eq!(restored.memory[0x1000], 0x90);
+    assert_eq!(restored.memory[0xFFFFF], 0xEA);
+    assert_eq!(restored.program_ranges, vec![0x1000..0x1010]);
+    assert_eq!(restored.cycles, 1234);
+    assert_eq!(restored.instructions, 56);
+    assert_eq!(restored.run_state, RunState::Finalize);
+}
+
+#[test]
+fn test_restore_rejects_short_memory() {
+    let (mut cpu, log) = scripted_cpu(Vec::new());
+    let mut short = snapshot();
+    short.memory.truncate(0x1000);
+
+    assert!(matches!(cpu.restore(&short, false), Err(RemoteCpuError::Snapshot(_))));
+    assert!(log.lock().unwrap().loaded.is_none());
+}
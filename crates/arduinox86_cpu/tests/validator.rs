@@ -0,0 +1,123 @@
+use arduinox86_cpu::{
+    arduinox86_client::{BusState, ComparisonPreset, TState},
+    RamMismatch,
+    RemoteCpuRegisters,
+    RemoteCpuRegistersV1,
+    TraceCycle,
+    ValidationCase,
+    ValidationReport,
+    ValidationRun,
+};
+
+const CF: u16 = 0x0001;
+const AF: u16 = 0x0010;
+
+fn regs(ax: u16, flags: u16) -> RemoteCpuRegisters {
+    RemoteCpuRegisters::V1(RemoteCpuRegistersV1 {
+        ax,
+        flags,
+        ..Default::default()
+    })
+}
+
+fn cycle(address: u32, bus_state: BusState, data: Option<u16>) -> TraceCycle {
+    TraceCycle {
+        ale: false,
+        address,
+        bus_state,
+        t_state: TState::T3,
+        data,
+    }
+}
+
+fn case() -> ValidationCase {
+    // MOV [0500], AL
+    ValidationCase {
+        name: "mov".to_string(),
+        registers: regs(0x0012, 0),
+        instruction: vec![0xA2, 0x00, 0x05],
+        memory: vec![(0x500, 0x00), (0x501, 0x00)],
+    }
+}
+
+fn hardware() -> ValidationRun {
+    ValidationRun {
+        registers: regs(0x0012, AF),
+        cycles:    vec![
+            cycle(0x00000, BusState::CODE, Some(0x00A2)),
+            cycle(0x00500, BusState::MEMW, Some(0x0012)),
+        ],
+        memory:    vec![(0x500, 0x12), (0x501, 0x00)],
+    }
+}
+
+#[test]
+fn test_identical_runs_pass() {
+    let report = ValidationReport::new(&ComparisonPreset::Strict, &case(), &hardware(), &hardware(), 0).unwrap();
+    assert!(report.passed());
+}
+
+#[test]
+fn test_mismatches_are_reported() {
+    let mut emulator = hardware();
+    emulator.registers = regs(0x0013, AF);
+    // The emulator only reports the byte it wrote, and wrote the wrong value.
+    emulator.memory = vec![(0x500, 0x13)];
+    emulator.cycles[1] = cycle(0x00500, BusState::MEMW, Some(0x0013));
+    emulator.cycles.push(cycle(0x00000, BusState::PASV, None));
+
+    let report = ValidationReport::new(&ComparisonPreset::Strict, &case(), &hardware(), &emulator, 0).unwrap();
+    assert!(!report.passed());
+
+    assert_eq!(report.registers.len(), 1);
+    assert_eq!(report.registers[0].register, "AX");
+    assert_eq!(
+        report.ram,
+        vec![RamMismatch {
+            address:  0x500,
+            expected: 0x12,
+            actual:   0x13,
+        }]
+    );
+    assert_eq!(report.cycles, Some((2, 3)));
+
+    let first = report.first_cycle_mismatch.unwrap();
+    assert_eq!(first.index, 1);
+    assert_eq!(first.hardware.unwrap().data, Some(0x0012));
+    assert_eq!(first.emulator.unwrap().data, Some(0x0013));
+
+    let text = report.to_string();
+    assert!(text.contains("AX: hardware 0012 emulator 0013"));
+    assert!(text.contains("[00500]: hardware 12 emulator 13"));
+    assert!(text.contains("first difference on cycle 1:"));
+}
+
+#[test]
+fn test_policy_applies() {
+    let mut emulator = hardware();
+    emulator.registers = regs(0x0012, 0);
+    emulator.cycles.clear();
+
+    // Strict compares every flag and the cycle trace.
+    let report = ValidationReport::new(&ComparisonPreset::Strict, &case(), &hardware(), &emulator, AF).unwrap();
+    assert_eq!(report.registers.len(), 1);
+    assert_eq!(report.first_cycle_mismatch.map(|m| m.index), Some(0));
+
+    // EmulatorFriendly skips undefined flags and cycles.
+    let report =
+        ValidationReport::new(&ComparisonPreset::EmulatorFriendly, &case(), &hardware(), &emulator, AF).unwrap();
+    assert!(report.passed());
+
+    // A defined flag still counts.
+    emulator.registers = regs(0x0012, AF | CF);
+    let report =
+        ValidationReport::new(&ComparisonPreset::EmulatorFriendly, &case(), &hardware(), &emulator, AF).unwrap();
+    assert_eq!(report.registers[0].register, "FLAGS");
+}
+
+#[test]
+fn test_32bit_registers_rejected() {
+    let mut emulator = hardware();
+    emulator.registers = RemoteCpuRegisters::V3(Default::default());
+    assert!(ValidationReport::new(&ComparisonPreset::Strict, &case(), &hardware(), &emulator, 0).is_err());
+}
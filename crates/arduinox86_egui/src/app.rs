@@ -30,21 +30,53 @@ use std::{
 };
 
 use crate::{
-    assembler::Assembler,
+    assembler::{Assembler, Severity},
+    breakpoints::BreakpointSet,
     client::ClientContext,
     config::ConfigFile,
     enums::{BinaryBlobType, ClientControlState, CpuStateType, MountAddress, ScheduleType},
     events::{FrontendThreadEvent, GuiEvent, GuiEventQueue},
+    flight_recorder::{export_flight_record, FlightRecorder},
+    health::{HealthEventKind, HealthMonitor, MONITORED_PINS},
+    patch::{Patch, PatchHistory},
+    project::{Project, PROJECT_EXTENSION, PROJECT_FLAGS},
+    register_fuzzer::RegisterFuzzer,
     resource_manager::ResourceManager,
     scheduler::Scheduler,
     serial_manager::SerialManager,
     structs::{BinaryBlob, ScheduledEvent},
     style::custom_style,
     window_manager::WindowManager,
-    windows::{ClientWindow, MemoryViewer, RegisterWindow},
+    windows::{
+        disassembly_window::last_fetch_address,
+        BreakpointWindow,
+        ClientWindow,
+        CycleTraceWindow,
+        DisassemblyWindow,
+        HealthWindow,
+        MemoryViewer,
+        PatchWindow,
+        RegisterWindow,
+    },
 };
 use anyhow::{bail, Result};
-use arduinox86_client::{ProgramState, RegisterSetType, RemoteCpuRegisters, ServerFlags, ServerStatus};
+use arduinox86_client::{
+    BinWrite,
+    BundleBookmark,
+    BundleCycle,
+    BundleEnvironment,
+    BundleProgram,
+    ExperimentBundle,
+    ProgramState,
+    RegisterPrinter,
+    RegisterSetType,
+    RemoteCpuRegisters,
+    ResultCache,
+    RunKey,
+    ServerFlags,
+    ServerStatus,
+};
+use arduinox86_config::{AddressMap, ConnectionSettings, DevicesFile};
 use clap::Parser;
 use egui::{
     containers::menu::{MenuButton, MenuConfig},
@@ -61,6 +93,8 @@ pub const LONG_NOTIFICATION_TIME: Option<Duration> = Some(Duration::from_secs(8)
 
 pub const SERVER_UPDATE_RATE: u64 = 1;
 pub const SERVER_UPDATE_MS: u64 = 1000 / SERVER_UPDATE_RATE;
+// Cycles stepped per frame while stepping to a breakpoint, each a round trip to the server.
+pub const AUTO_STEP_CYCLES: usize = 32;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -68,6 +102,10 @@ struct Cli {
     /// Path to the TOML config file
     #[arg(long, value_name = "FILE", default_value = "./cfg/arduinox86_gui.toml")]
     config_file: PathBuf,
+
+    /// Always run programs on the hardware, even if the same program already ran from the same registers
+    #[arg(long)]
+    no_cache: bool,
 }
 
 #[derive(Default)]
@@ -75,17 +113,37 @@ pub struct TransientAppState {
     ctx_init: bool,
     app_init: bool,
     config: ConfigFile,
+    // The config file as loaded, for flight records.
+    config_text: String,
+    connection: ConnectionSettings,
     serial_manager: SerialManager,
     resource_manager: ResourceManager,
     last_program_state: Option<ProgramState>,
+    last_state_seq: Option<u32>,
     selected_serial_port: usize,
 
+    // Final registers of earlier runs, keyed by program and initial registers.
+    no_cache: bool,
+    result_cache: ResultCache<RemoteCpuRegisters>,
+    pending_run_key: Option<RunKey>,
+
     client_ctx: Option<ClientContext>,
     client_window: ClientWindow,
+    cycle_trace_window: CycleTraceWindow,
     window_manager: WindowManager,
     initial_register_window: RegisterWindow,
     final_register_window: RegisterWindow,
+    register_fuzzer: RegisterFuzzer,
     memory_viewer_window: MemoryViewer,
+    disassembly_window: DisassemblyWindow,
+    health_window: HealthWindow,
+    health: HealthMonitor,
+    patch_window: PatchWindow,
+    patches: PatchHistory,
+    breakpoint_window: BreakpointWindow,
+    breakpoints: BreakpointSet,
+    auto_step: bool,
+    flight_recorder: FlightRecorder,
     scheduler: Scheduler,
     event_queue: GuiEventQueue,
     error_msg: Option<String>,
@@ -209,6 +267,34 @@ impl App {
             }
         };
 
+        // Load the shared devices file and resolve connection settings.
+        let devices = match DevicesFile::find(config.devices_file.as_deref()) {
+            Ok(devices) => devices,
+            Err(e) => {
+                log::error!("Failed to load devices file: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let connection = match config.connection(&devices) {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::error!("Bad connection settings: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let address_map = match AddressMap::find(config.address_map.as_deref()) {
+            Ok(address_map) => address_map,
+            Err(e) => {
+                log::error!("Failed to load address map: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let port_labels = devices
+            .devices
+            .iter()
+            .filter_map(|d| Some((d.settings.port.clone()?, d.label.clone())))
+            .collect();
+
         // Create directories if they don't exist.
         if let Err(e) = fs::create_dir_all(&config.assembly_output_path) {
             log::error!(
@@ -229,6 +315,9 @@ impl App {
                 ps: restored_app.gs.syntax_set.clone(),
                 ts: syntect::highlighting::ThemeSet::load_defaults(),
             };
+            restored_app.ts.config_text = config_text;
+            restored_app.ts.no_cache = cli.no_cache;
+            restored_app.ts.memory_viewer_window.set_address_map(address_map);
 
             return restored_app;
         }
@@ -252,7 +341,7 @@ impl App {
         }
         log::debug!("Found {} original syntaxes in GuiState::SyntaxSet", syntaxes_found);
 
-        let new_app = App {
+        let mut new_app = App {
             gs: GuiState {
                 toasts: Toasts::new().with_anchor(egui_notify::Anchor::BottomRight),
                 syntax_set: syntax_set.clone(),
@@ -262,12 +351,16 @@ impl App {
                 },
             },
             ts: TransientAppState {
+                serial_manager: SerialManager::new(connection.port_filter(), port_labels),
                 config,
-                serial_manager: SerialManager::new(),
+                config_text,
+                connection,
+                no_cache: cli.no_cache,
                 ..Default::default()
             },
             ..Default::default()
         };
+        new_app.ts.memory_viewer_window.set_address_map(address_map);
 
         let mut syntaxes_found = 0;
         for syntax in new_app.gs.syntax_set.syntaxes() {
@@ -380,6 +473,26 @@ impl eframe::App for App {
                                     }
                                 }
                             });
+
+                            ui.separator();
+
+                            if ui.button("Open Project...").clicked() {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("ArduinoX86 Projects", &[PROJECT_EXTENSION])
+                                    .pick_file()
+                                {
+                                    self.ts.event_queue.push(GuiEvent::OpenProject { path });
+                                }
+                            }
+                            if ui.button("Save Project...").clicked() {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("ArduinoX86 Projects", &[PROJECT_EXTENSION])
+                                    .set_file_name(format!("session.{}", PROJECT_EXTENSION))
+                                    .save_file()
+                                {
+                                    self.ts.event_queue.push(GuiEvent::SaveProject { path });
+                                }
+                            }
                         }
                     }
 
@@ -404,6 +517,14 @@ impl eframe::App for App {
                             });
                         }
                     });
+
+                ui.menu_button("Windows", |ui| {
+                    ui.checkbox(self.ts.health_window.open_mut(), "Hardware Health");
+                    ui.checkbox(self.ts.patch_window.open_mut(), "Instruction Patching");
+                    ui.checkbox(self.ts.cycle_trace_window.open_mut(), "Cycle Trace");
+                    ui.checkbox(self.ts.breakpoint_window.open_mut(), "Breakpoints");
+                    ui.checkbox(self.ts.disassembly_window.open_mut(), "Disassembly");
+                });
                 ui.add_space(16.0);
             });
         });
@@ -436,9 +557,15 @@ impl eframe::App for App {
                     .clicked()
                 {
                     // Do clicky stuff
-                    match ClientContext::new(self.ts.selected_serial_port, &mut self.ts.serial_manager) {
+                    match ClientContext::new(
+                        self.ts.selected_serial_port,
+                        &mut self.ts.serial_manager,
+                        &self.ts.connection,
+                    ) {
                         Ok(client_ctx) => {
                             self.ts.error_msg = None;
+                            self.ts.health.reset();
+                            self.ts.flight_recorder.reset();
                             self.ts.client_window.init(&client_ctx);
                             self.ts.client_ctx = Some(client_ctx);
 
@@ -470,6 +597,26 @@ impl eframe::App for App {
             });
         });
 
+        if *self.ts.health_window.open() {
+            self.ts
+                .health_window
+                .show(ctx, &mut self.ts.health, &mut self.ts.event_queue);
+        }
+        if *self.ts.patch_window.open() {
+            let can_run = self.ts.client_ctx.is_some() && self.ts.client_window.registers().0.is_some();
+            self.ts
+                .patch_window
+                .show(ctx, &mut self.ts.patches, can_run, &mut self.ts.event_queue);
+        }
+        if *self.ts.breakpoint_window.open() {
+            self.ts.breakpoint_window.show(ctx, &mut self.ts.breakpoints);
+        }
+        if *self.ts.cycle_trace_window.open() {
+            self.ts
+                .cycle_trace_window
+                .show(ctx, self.ts.client_window.bookmarks(), &mut self.ts.event_queue);
+        }
+
         // Render floating windows.
         if let Some(client_ctx) = &mut self.ts.client_ctx {
             self.ts
@@ -529,8 +676,20 @@ impl eframe::App for App {
             self.ts
                 .memory_viewer_window
                 .show(ctx, client_ctx, &mut self.ts.event_queue);
+            if *self.ts.disassembly_window.open() {
+                let fetch_address = last_fetch_address(client_ctx.cpu_type, self.ts.client_window.cycles());
+                self.ts.disassembly_window.show(
+                    ctx,
+                    client_ctx.code_address(),
+                    fetch_address,
+                    &mut self.ts.event_queue,
+                );
+            }
 
             self.ts.scheduler.run(&mut self.ts.event_queue);
+            if self.ts.auto_step {
+                self.ts.event_queue.push(GuiEvent::StepToBreakpoint);
+            }
 
             if update_state {
                 client_ctx.set_initial_state(&new_state);
@@ -540,6 +699,10 @@ impl eframe::App for App {
         // Handle events.
         self.handle_events(ctx);
 
+        if let Some(err_msg) = &self.ts.error_msg {
+            self.ts.flight_recorder.record_error(err_msg);
+        }
+
         ctx.request_repaint();
     }
 
@@ -558,7 +721,27 @@ impl App {
                 match event {
                     GuiEvent::ResetState => {
                         self.ts.last_program_state = None;
+                        self.ts.last_state_seq = None;
+                        self.ts.pending_run_key = None;
+                        self.ts.auto_step = false;
+                        client_ctx.reset_stepper();
+                        self.ts.disassembly_window.invalidate();
                         *self.ts.final_register_window.open_mut() = false;
+                        self.ts.final_register_window.set_note(None);
+                    }
+                    GuiEvent::RandomizeRegisters => match &client_ctx.initial_state().regs {
+                        RemoteCpuRegisters::V3(regs) => {
+                            let new_regs = RemoteCpuRegisters::V3(self.ts.register_fuzzer.randomize(regs));
+                            self.ts.initial_register_window.set_regs(&new_regs, None);
+                        }
+                        regs => {
+                            log::warn!("Can't randomize register type: {}", RegisterSetType::from(regs));
+                        }
+                    },
+                    GuiEvent::RestoreRegisters { index } => {
+                        if let Some(regs) = self.ts.initial_register_window.history(index).cloned() {
+                            self.ts.initial_register_window.set_regs(&regs, None);
+                        }
                     }
                     GuiEvent::LoadRegisters => {
                         let program_state = client_ctx.program_state();
@@ -578,14 +761,16 @@ impl App {
                                     .error(format!("Failed to clear automatic execution flag: {}", e))
                                     .duration(LONG_NOTIFICATION_TIME);
                                 log::error!("Failed to clear automatic execution flag: {}", e);
+                                self.ts.health.record(
+                                    HealthEventKind::CommandError,
+                                    format!("Failed to clear automatic execution flag: {}", e),
+                                );
                             }
                         }
 
                         let mut initial_state = client_ctx.initial_state().clone();
                         initial_state.regs.normalize();
 
-                        let mut buf = Cursor::new(Vec::<u8>::new());
-
                         let mut regs = &initial_state.regs;
                         let regs_b;
 
@@ -601,18 +786,10 @@ impl App {
                             }
                         }
 
-                        regs.write(&mut buf).unwrap_or_else(|e| {
-                            log::error!("Failed to write registers: {}", e);
-                        });
-
-                        log::debug!("Wrote {} bytes of register data to buffer.", buf.get_ref().len());
-
-                        match client_ctx
-                            .client
-                            .load_registers_from_buf(RegisterSetType::from(regs), buf.get_ref())
-                        {
+                        match client_ctx.client.restore_registers_snapshot(regs) {
                             Ok(_) => {
                                 log::debug!("Registers loaded successfully.");
+                                self.ts.initial_register_window.remember(&initial_state.regs);
                                 self.gs
                                     .toasts
                                     .success("Registers loaded successfully!")
@@ -624,13 +801,18 @@ impl App {
                                     .toasts
                                     .error(format!("Failed to load registers: {}", e))
                                     .duration(LONG_NOTIFICATION_TIME);
-                                self.ts.error_msg = Some(format!("Failed to load registers: {}", e));
+                                let msg = format!("Failed to load registers: {}", e);
+                                self.ts.health.record(HealthEventKind::CommandError, &msg);
+                                self.ts.error_msg = Some(msg);
                             }
                         }
 
                         match self.ts.client_window.push_cycle(client_ctx, false) {
                             Ok(_) => {
                                 log::debug!("Pushed CPU cycle successfully.");
+                                self.ts
+                                    .cycle_trace_window
+                                    .set_cycles(client_ctx.cpu_type, self.ts.client_window.cycles());
                             }
                             Err(e) => {
                                 log::error!("Failed to push CPU cycle: {}", e);
@@ -638,7 +820,9 @@ impl App {
                                     .toasts
                                     .error(format!("Failed to push CPU cycle: {}", e))
                                     .duration(LONG_NOTIFICATION_TIME);
-                                self.ts.error_msg = Some(format!("Failed to push CPU cycle: {}", e));
+                                let msg = format!("Failed to push CPU cycle: {}", e);
+                                self.ts.health.record(HealthEventKind::CommandError, &msg);
+                                self.ts.error_msg = Some(msg);
                             }
                         }
                     }
@@ -656,7 +840,9 @@ impl App {
                                 .toasts
                                 .error(format!("Failed to erase memory: {}", e))
                                 .duration(LONG_NOTIFICATION_TIME);
-                            self.ts.error_msg = Some(format!("Failed to erase memory: {}", e));
+                            let msg = format!("Failed to erase memory: {}", e);
+                            self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            self.ts.error_msg = Some(msg);
                         }
                     },
                     GuiEvent::ReadMemory { address, size } => match client_ctx.read_memory(address, size) {
@@ -670,7 +856,20 @@ impl App {
                         }
                         Err(e) => {
                             log::error!("Failed to read memory: {}", e);
-                            self.ts.error_msg = Some(format!("Failed to read memory: {}", e));
+                            let msg = format!("Failed to read memory: {}", e);
+                            self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            self.ts.error_msg = Some(msg);
+                        }
+                    },
+                    GuiEvent::ReadDisassembly { address, size } => match client_ctx.code_snapshot(address, size) {
+                        Ok(snapshot) => {
+                            self.ts.disassembly_window.set_snapshot(snapshot);
+                        }
+                        Err(e) => {
+                            log::error!("Failed to read memory for disassembly: {}", e);
+                            let msg = format!("Failed to read memory for disassembly: {}", e);
+                            self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            self.ts.error_msg = Some(msg);
                         }
                     },
                     GuiEvent::UploadBlob {
@@ -705,7 +904,9 @@ impl App {
                                     .duration(LONG_NOTIFICATION_TIME);
 
                                 log::error!("Failed to load binary blob: {}", e);
-                                self.ts.error_msg = Some(format!("Failed to load binary blob: {}", e));
+                                let msg = format!("Failed to load binary blob: {}", e);
+                                self.ts.health.record(HealthEventKind::CommandError, &msg);
+                                self.ts.error_msg = Some(msg);
                                 return;
                             }
                             else {
@@ -729,44 +930,79 @@ impl App {
                             self.ts.error_msg = Some(format!("Blob {} not found for upload.", blob_name));
                         }
                     }
-                    GuiEvent::RunProgram => {
-                        // Load the binary resources into memory.
-                        for blob in self.ts.resource_manager.blobs() {
-                            let resolved_mount_address = match blob.mount_address {
-                                MountAddress::FlatAddress(addr) => addr,
-                                MountAddress::CsIp => self
-                                    .ts
-                                    .initial_register_window
-                                    .regs(RegisterSetType::Intel386)
-                                    .code_address(),
-                            };
-
-                            log::debug!(
-                                "Loading binary blob: {} at address {:08x}",
-                                blob.name,
-                                resolved_mount_address
-                            );
-
-                            if let Err(e) = client_ctx.client.set_memory(resolved_mount_address, &blob.data) {
-                                self.gs
-                                    .toasts
-                                    .error(format!("Failed to load binary blob: {}", e))
-                                    .duration(LONG_NOTIFICATION_TIME);
+                    GuiEvent::RunProgram { upload_blobs } => {
+                        // If this program already ran from these registers, show the cached result
+                        // instead of running it again.
+                        let run_key = if self.ts.no_cache {
+                            None
+                        }
+                        else {
+                            let code_address = self
+                                .ts
+                                .initial_register_window
+                                .regs(RegisterSetType::Intel386)
+                                .code_address();
+                            program_run_key(self.ts.resource_manager.blobs(), code_address, client_ctx)
+                        };
+                        if let Some(final_regs) = run_key.and_then(|key| self.ts.result_cache.get(&key).cloned()) {
+                            let state = client_ctx.initial_state();
+                            self.ts.final_register_window.set_regs(&state.regs, Some(&final_regs));
+                            self.ts
+                                .final_register_window
+                                .set_note(Some("Cached result; the program was not run again.".to_string()));
+                            *self.ts.final_register_window.open_mut() = true;
+                            self.ts
+                                .client_window
+                                .set_registers(Some(state.regs.clone()), Some(final_regs));
+                            self.gs
+                                .toasts
+                                .info("Program unchanged, showing the cached result.")
+                                .duration(NORMAL_NOTIFICATION_TIME);
+                            continue;
+                        }
+                        self.ts.pending_run_key = None;
+
+                        // Load the binary resources into memory, unless only a patch changed since the last run.
+                        if upload_blobs {
+                            for blob in self.ts.resource_manager.blobs() {
+                                let resolved_mount_address = match blob.mount_address {
+                                    MountAddress::FlatAddress(addr) => addr,
+                                    MountAddress::CsIp => self
+                                        .ts
+                                        .initial_register_window
+                                        .regs(RegisterSetType::Intel386)
+                                        .code_address(),
+                                };
 
-                                log::error!("Failed to load binary blob: {}", e);
-                                self.ts.error_msg = Some(format!("Failed to load binary blob: {}", e));
-                                return;
-                            }
-                            else {
-                                self.gs
-                                    .toasts
-                                    .success(format!("Binary blob: {} loaded successfully!", blob.name))
-                                    .duration(NORMAL_NOTIFICATION_TIME);
                                 log::debug!(
-                                    "Binary blob: {} loaded successfully at address {:#x}",
+                                    "Loading binary blob: {} at address {:08x}",
                                     blob.name,
                                     resolved_mount_address
                                 );
+
+                                if let Err(e) = client_ctx.client.set_memory(resolved_mount_address, &blob.data) {
+                                    self.gs
+                                        .toasts
+                                        .error(format!("Failed to load binary blob: {}", e))
+                                        .duration(LONG_NOTIFICATION_TIME);
+
+                                    log::error!("Failed to load binary blob: {}", e);
+                                    let msg = format!("Failed to load binary blob: {}", e);
+                                    self.ts.health.record(HealthEventKind::CommandError, &msg);
+                                    self.ts.error_msg = Some(msg);
+                                    return;
+                                }
+                                else {
+                                    self.gs
+                                        .toasts
+                                        .success(format!("Binary blob: {} loaded successfully!", blob.name))
+                                        .duration(NORMAL_NOTIFICATION_TIME);
+                                    log::debug!(
+                                        "Binary blob: {} loaded successfully at address {:#x}",
+                                        blob.name,
+                                        resolved_mount_address
+                                    );
+                                }
                             }
                         }
 
@@ -784,6 +1020,10 @@ impl App {
                                     .error(format!("Failed to set automatic execution flag: {}", e))
                                     .duration(LONG_NOTIFICATION_TIME);
                                 log::error!("Failed to set automatic execution flag: {}", e);
+                                self.ts.health.record(
+                                    HealthEventKind::CommandError,
+                                    format!("Failed to set automatic execution flag: {}", e),
+                                );
                             }
                         }
 
@@ -791,23 +1031,15 @@ impl App {
                         let mut initial_state = client_ctx.initial_state().clone();
                         initial_state.regs.normalize();
 
-                        let mut buf = Cursor::new(Vec::<u8>::new());
-
-                        initial_state
-                            .regs
-                            .write(&mut buf)
-                            .expect("Failed to write registers to buffer");
-
-                        if let Err(e) = client_ctx
-                            .client
-                            .load_registers_from_buf(RegisterSetType::from(&initial_state.regs), buf.get_ref())
-                        {
+                        if let Err(e) = client_ctx.client.restore_registers_snapshot(&initial_state.regs) {
                             self.gs
                                 .toasts
                                 .error(format!("Failed to load registers: {}", e))
                                 .duration(LONG_NOTIFICATION_TIME);
                             log::error!("Failed to load registers: {}", e);
-                            self.ts.error_msg = Some(format!("Failed to load registers: {}", e));
+                            let msg = format!("Failed to load registers: {}", e);
+                            self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            self.ts.error_msg = Some(msg);
                         }
                         else {
                             self.gs
@@ -815,6 +1047,9 @@ impl App {
                                 .success("Registers loaded successfully!")
                                 .duration(NORMAL_NOTIFICATION_TIME);
                             log::debug!("Registers loaded successfully.");
+                            self.ts.initial_register_window.remember(&initial_state.regs);
+                            // The final registers are cached when the run completes.
+                            self.ts.pending_run_key = run_key;
                         }
                     }
                     GuiEvent::AssembleProgram { program_name } => {
@@ -844,6 +1079,7 @@ impl App {
                                             .success(format!("Assembly successful for {}!", program_name))
                                             .duration(NORMAL_NOTIFICATION_TIME);
                                         code_editor.set_assembler_output(assembler.stdout());
+                                        code_editor.set_messages(assembler.messages());
                                     }
                                     Err(e) => {
                                         let stderr = assembler.stderr();
@@ -853,12 +1089,24 @@ impl App {
                                             e,
                                             stderr
                                         );
-                                        self.gs
-                                            .toasts
-                                            .error(format!("Assembly failed: {}", e))
-                                            .duration(LONG_NOTIFICATION_TIME);
+                                        let error_count = assembler
+                                            .messages()
+                                            .iter()
+                                            .filter(|m| m.severity == Severity::Error)
+                                            .count();
+                                        let toast_msg = if error_count > 0 {
+                                            format!(
+                                                "Assembly failed with {} error(s); see the code editor.",
+                                                error_count
+                                            )
+                                        }
+                                        else {
+                                            format!("Assembly failed: {}", e)
+                                        };
+                                        self.gs.toasts.error(toast_msg).duration(LONG_NOTIFICATION_TIME);
 
                                         code_editor.set_assembler_output(assembler.stderr());
+                                        code_editor.set_messages(assembler.messages());
                                     }
                                 },
                                 Err(e) => {
@@ -898,28 +1146,69 @@ impl App {
                     }
                     GuiEvent::PollStatus => {
                         // Get the server status. This event is scheduled automatically.
-                        match client_ctx.client.server_status() {
+                        // A single failed poll is retried; if the retry fails too, the client and
+                        // server have most likely lost track of each other.
+                        let status = match client_ctx.client.server_status() {
+                            Ok(status) => Ok(status),
+                            Err(e) => {
+                                log::warn!("Failed to get server status, retrying: {}", e);
+                                self.ts
+                                    .health
+                                    .record(HealthEventKind::Retry, format!("Status poll failed: {}", e));
+                                client_ctx.client.server_status()
+                            }
+                        };
+
+                        match status {
                             Ok(status) => {
                                 log::debug!("Server status: {:?}", status);
+                                self.ts.health.update_status(&status, self.ts.last_program_state);
                                 self.ts.client_window.set_server_status(client_ctx, status);
 
+                                if *self.ts.health_window.open() {
+                                    for pin in MONITORED_PINS {
+                                        let level = client_ctx.client.read_pin(pin).ok();
+                                        self.ts.health.update_pin(pin, level);
+                                    }
+                                }
+                                if self.ts.health.take_firmware_query() {
+                                    new_events.push(GuiEvent::QueryLastError);
+                                }
+
+                                // A program can run to completion between polls, leaving the state
+                                // unchanged; the sequence number still tells us it moved.
                                 if self.ts.last_program_state.is_none()
                                     || (Some(status.state) != self.ts.last_program_state)
+                                    || (Some(status.state_seq) != self.ts.last_state_seq)
                                 {
                                     log::info!("Program state changed: {:?}", status.state);
+                                    self.ts.flight_recorder.record_state(status.state);
 
                                     match status.state {
                                         ProgramState::StoreDone | ProgramState::StoreDoneSmm => {
+                                            // The client window fetched the cycles on the state change.
+                                            self.ts
+                                                .cycle_trace_window
+                                                .set_cycles(client_ctx.cpu_type, self.ts.client_window.cycles());
+
                                             // Get the register file.
 
-                                            match client_ctx.client.store_registers() {
+                                            match client_ctx.client.save_registers_snapshot() {
                                                 Ok(final_regs) => {
+                                                    if let Some(key) = self.ts.pending_run_key.take() {
+                                                        self.ts.result_cache.insert(key, final_regs.clone());
+                                                    }
                                                     let state = client_ctx.initial_state();
 
                                                     self.ts
                                                         .final_register_window
                                                         .set_regs(&state.regs, Some(&final_regs));
+                                                    self.ts.final_register_window.set_note(None);
                                                     *self.ts.final_register_window.open_mut() = true;
+                                                    self.ts.client_window.set_registers(
+                                                        Some(state.regs.clone()),
+                                                        Some(final_regs.clone()),
+                                                    );
                                                     log::debug!(
                                                         "Registers updated after program completion: {:?}",
                                                         final_regs
@@ -934,8 +1223,9 @@ impl App {
                                                         .toasts
                                                         .error(format!("Failed to get final registers: {}", e))
                                                         .duration(LONG_NOTIFICATION_TIME);
-                                                    self.ts.error_msg =
-                                                        Some(format!("Failed to get final registers: {}", e));
+                                                    let msg = format!("Failed to get final registers: {}", e);
+                                                    self.ts.health.record(HealthEventKind::CommandError, &msg);
+                                                    self.ts.error_msg = Some(msg);
                                                 }
                                             }
                                         }
@@ -945,17 +1235,33 @@ impl App {
                                     }
 
                                     self.ts.last_program_state = Some(status.state);
+                                    self.ts.last_state_seq = Some(status.state_seq);
                                 }
                             }
                             Err(e) => {
                                 log::error!("Failed to get server status: {}", e);
-                                self.ts.error_msg = Some(format!("Failed to get server status: {}", e));
+                                let msg = format!("Failed to get server status: {}", e);
+                                self.ts.health.record(HealthEventKind::Desync, &msg);
+                                self.ts.error_msg = Some(msg);
                             }
                         }
                     }
+                    GuiEvent::QueryLastError => match client_ctx.client.get_last_error() {
+                        Ok(err) => {
+                            log::debug!("Last firmware error: {}", err);
+                            self.ts.health.set_firmware_error(&err);
+                        }
+                        Err(e) => {
+                            log::error!("Failed to get last firmware error: {}", e);
+                            self.ts
+                                .health
+                                .record(HealthEventKind::Desync, format!("Failed to get last error: {}", e));
+                        }
+                    },
                     GuiEvent::ClearCycleLog => match client_ctx.client.clear_cycle_log() {
                         Ok(_) => {
                             log::debug!("Cycle log cleared successfully.");
+                            self.ts.cycle_trace_window.clear();
                             self.gs
                                 .toasts
                                 .success("Cycle log cleared successfully!")
@@ -967,7 +1273,9 @@ impl App {
                                 .toasts
                                 .error(format!("Failed to clear cycle log: {}", e))
                                 .duration(LONG_NOTIFICATION_TIME);
-                            self.ts.error_msg = Some(format!("Failed to clear cycle log: {}", e));
+                            let msg = format!("Failed to clear cycle log: {}", e);
+                            self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            self.ts.error_msg = Some(msg);
                         }
                     },
                     GuiEvent::ToggleRefreshMemory {
@@ -1004,6 +1312,388 @@ impl App {
                         let new_event = self.ts.memory_viewer_window.make_refresh_event();
                         new_events.push(new_event);
                     }
+                    GuiEvent::ExportFlightRecord { path } => {
+                        let (initial_regs, final_regs) = self.ts.client_window.registers();
+                        let mut registers = String::new();
+                        for (name, regs) in [("Initial", initial_regs), ("Final", final_regs)] {
+                            if let Some(regs) = regs {
+                                registers.push_str(&format!(
+                                    "{} registers:\n{}\n",
+                                    name,
+                                    regs.display(client_ctx.cpu_type)
+                                ));
+                            }
+                        }
+                        let health: String = self
+                            .ts
+                            .health
+                            .events()
+                            .map(|event| {
+                                format!(
+                                    "{:>10.3} {} (x{}): {}\n",
+                                    event.at.as_secs_f64(),
+                                    event.kind,
+                                    event.repeats,
+                                    event.message
+                                )
+                            })
+                            .collect();
+                        let config = format!(
+                            "CPU: {:?}\nServer flags: {:08X}\nPort: {}\n\n{:#?}\n\n{}",
+                            client_ctx.cpu_type,
+                            client_ctx.cached_flags(),
+                            client_ctx.port_name,
+                            self.ts.connection,
+                            self.ts.config_text
+                        );
+
+                        let sections = [
+                            ("events.log", self.ts.flight_recorder.to_text()),
+                            ("health.log", health),
+                            (
+                                "protocol.log",
+                                client_ctx
+                                    .client
+                                    .protocol_log()
+                                    .map(|log| log.to_string())
+                                    .unwrap_or_default(),
+                            ),
+                            ("cycles.log", self.ts.client_window.cycle_log()),
+                            ("registers.txt", registers),
+                            ("config.txt", config),
+                        ];
+                        match export_flight_record(&path, &sections) {
+                            Ok(()) => {
+                                self.gs
+                                    .toasts
+                                    .success(format!("Flight record saved to {}", path.display()))
+                                    .duration(NORMAL_NOTIFICATION_TIME);
+                            }
+                            Err(e) => {
+                                log::error!("Failed to export flight record: {:#}", e);
+                                self.gs
+                                    .toasts
+                                    .error(format!("Failed to export flight record: {:#}", e))
+                                    .duration(LONG_NOTIFICATION_TIME);
+                            }
+                        }
+                    }
+                    GuiEvent::ExportExperimentBundle { path } => {
+                        // The second value reported by cpu_type() is the FPU bit.
+                        let mut environment = BundleEnvironment::new(
+                            "arduinox86_egui",
+                            env!("CARGO_PKG_VERSION"),
+                            client_ctx.cpu_type,
+                            client_ctx.queue_status,
+                        );
+                        environment.port = Some(client_ctx.port_name.clone());
+                        environment.server_flags = Some(client_ctx.cached_flags());
+
+                        let mut bundle = ExperimentBundle::new(environment);
+                        let code_address = self
+                            .ts
+                            .initial_register_window
+                            .regs(RegisterSetType::Intel386)
+                            .code_address();
+                        for blob in self.ts.resource_manager.blobs() {
+                            bundle.programs.push(BundleProgram {
+                                name:    blob.name.clone(),
+                                address: match blob.mount_address {
+                                    MountAddress::FlatAddress(addr) => addr,
+                                    MountAddress::CsIp => code_address,
+                                },
+                                data:    blob.data.clone(),
+                            });
+                        }
+                        let (initial_regs, final_regs) = self.ts.client_window.registers();
+                        bundle.initial_regs = initial_regs.cloned();
+                        bundle.final_regs = final_regs.cloned();
+                        bundle.trace =
+                            BundleCycle::from_server_states(self.ts.client_window.cycles(), client_ctx.cpu_type);
+                        bundle.bookmarks = self
+                            .ts
+                            .client_window
+                            .bookmarks()
+                            .iter()
+                            .map(|(cycle, note)| BundleBookmark {
+                                cycle: *cycle as u32,
+                                note:  note.clone(),
+                            })
+                            .collect();
+
+                        match bundle.write(&path) {
+                            Ok(_) => {
+                                self.gs
+                                    .toasts
+                                    .success(format!("Experiment bundle saved to {}", path.display()))
+                                    .duration(NORMAL_NOTIFICATION_TIME);
+                            }
+                            Err(e) => {
+                                log::error!("Failed to export experiment bundle: {}", e);
+                                self.gs
+                                    .toasts
+                                    .error(format!("Failed to export experiment bundle: {}", e))
+                                    .duration(LONG_NOTIFICATION_TIME);
+                            }
+                        }
+                    }
+                    GuiEvent::PatchAndRun { offset, bytes } => {
+                        let code_address = self
+                            .ts
+                            .initial_register_window
+                            .regs(RegisterSetType::Intel386)
+                            .code_address();
+                        let patch = match Patch::locate(
+                            self.ts.resource_manager.blobs(),
+                            code_address,
+                            offset,
+                            &bytes,
+                        ) {
+                            Ok(patch) => patch,
+                            Err(e) => {
+                                self.gs
+                                    .toasts
+                                    .error(format!("Failed to patch program: {}", e))
+                                    .duration(LONG_NOTIFICATION_TIME);
+                                continue;
+                            }
+                        };
+
+                        // Upload only the patched bytes; the rest of the program is already in memory.
+                        if let Err(e) = client_ctx.client.set_memory(patch.address, &patch.new) {
+                            log::error!("Failed to upload patch: {}", e);
+                            let msg = format!("Failed to upload patch: {}", e);
+                            self.gs.toasts.error(msg.clone()).duration(LONG_NOTIFICATION_TIME);
+                            self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            self.ts.error_msg = Some(msg);
+                            continue;
+                        }
+                        if let Err(e) = patch.apply(&mut self.ts.resource_manager) {
+                            log::error!("Failed to patch program: {}", e);
+                            continue;
+                        }
+                        if let (Some(window), Some(blob)) = (
+                            self.ts.window_manager.blob_window_mut(&patch.blob),
+                            self.ts.resource_manager.blob(&patch.blob),
+                        ) {
+                            window.set_data(&blob.data);
+                        }
+
+                        self.gs
+                            .toasts
+                            .success(format!(
+                                "Patched {} byte(s) at {:05X}, running again.",
+                                patch.new.len(),
+                                patch.address
+                            ))
+                            .duration(NORMAL_NOTIFICATION_TIME);
+                        self.ts.patches.push(patch);
+
+                        self.ts.client_window.reset_state();
+                        self.ts.cycle_trace_window.clear();
+                        new_events.push(GuiEvent::ResetState);
+                        new_events.push(GuiEvent::RunProgram { upload_blobs: false });
+                    }
+                    GuiEvent::ExportCycleTrace { path } => {
+                        let csv = self.ts.cycle_trace_window.to_csv(self.ts.client_window.bookmarks());
+                        match fs::write(&path, csv) {
+                            Ok(()) => {
+                                self.gs
+                                    .toasts
+                                    .success(format!("Cycle trace saved to {}", path.display()))
+                                    .duration(NORMAL_NOTIFICATION_TIME);
+                            }
+                            Err(e) => {
+                                log::error!("Failed to export cycle trace: {}", e);
+                                self.gs
+                                    .toasts
+                                    .error(format!("Failed to export cycle trace: {}", e))
+                                    .duration(LONG_NOTIFICATION_TIME);
+                            }
+                        }
+                    }
+                    GuiEvent::ExportPatches { path } => match fs::write(&path, self.ts.patches.to_asm_diff()) {
+                        Ok(()) => {
+                            self.gs
+                                .toasts
+                                .success(format!("Patch history saved to {}", path.display()))
+                                .duration(NORMAL_NOTIFICATION_TIME);
+                        }
+                        Err(e) => {
+                            log::error!("Failed to export patch history: {}", e);
+                            self.gs
+                                .toasts
+                                .error(format!("Failed to export patch history: {}", e))
+                                .duration(LONG_NOTIFICATION_TIME);
+                        }
+                    },
+                    GuiEvent::SaveProject { path } => {
+                        let mut project = Project::new(client_ctx.cached_flags());
+                        for blob in self.ts.resource_manager.blobs() {
+                            project.add_blob(blob);
+                        }
+                        let mut listings: Vec<_> = self
+                            .ts
+                            .window_manager
+                            .code_editors()
+                            .map(|editor| (editor.program_name(), editor.code()))
+                            .collect();
+                        listings.sort_by_key(|(name, _)| *name);
+                        for (name, code) in listings {
+                            project.add_listing(name, code);
+                        }
+
+                        let initial_regs = self.ts.initial_register_window.regs(RegisterSetType::Intel386);
+                        let result = project
+                            .set_initial_regs(&initial_regs)
+                            .and_then(|_| project.to_toml())
+                            .and_then(|text| Ok(fs::write(&path, text)?));
+                        match result {
+                            Ok(()) => {
+                                self.gs
+                                    .toasts
+                                    .success(format!("Project saved to {}", path.display()))
+                                    .duration(NORMAL_NOTIFICATION_TIME);
+                            }
+                            Err(e) => {
+                                log::error!("Failed to save project: {}", e);
+                                self.gs
+                                    .toasts
+                                    .error(format!("Failed to save project: {}", e))
+                                    .duration(LONG_NOTIFICATION_TIME);
+                            }
+                        }
+                    }
+                    GuiEvent::OpenProject { path } => {
+                        // Decode everything before touching the session, so a bad project leaves it as it was.
+                        let loaded = fs::read_to_string(&path)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|text| Project::from_toml(&text))
+                            .and_then(|project| Ok((project.binary_blobs()?, project.initial_regs()?, project)));
+                        let (blobs, initial_regs, project) = match loaded {
+                            Ok(loaded) => loaded,
+                            Err(e) => {
+                                log::error!("Failed to open project {}: {}", path.display(), e);
+                                self.gs
+                                    .toasts
+                                    .error(format!("Failed to open project: {}", e))
+                                    .duration(LONG_NOTIFICATION_TIME);
+                                continue;
+                            }
+                        };
+
+                        self.ts.window_manager.clear();
+                        self.ts.resource_manager.clear();
+                        for blob in blobs {
+                            match self.ts.resource_manager.add_blob(blob) {
+                                Ok(binary_view) => {
+                                    self.ts
+                                        .window_manager
+                                        .add_blob(binary_view.name().to_string(), binary_view);
+                                }
+                                Err(e) => log::warn!("Skipping project blob: {}", e),
+                            }
+                        }
+                        for listing in &project.listings {
+                            self.ts
+                                .window_manager
+                                .new_code_window(&listing.name, Some(listing.code.clone()));
+                        }
+                        if let Some(regs) = initial_regs {
+                            self.ts.initial_register_window.set_regs(&regs, None);
+                        }
+                        match client_ctx.set_flags(PROJECT_FLAGS, project.server_flags) {
+                            Ok(()) => self.ts.client_window.sync_flags(client_ctx),
+                            Err(e) => {
+                                log::error!("Failed to restore project flags: {}", e);
+                                self.ts.health.record(
+                                    HealthEventKind::CommandError,
+                                    format!("Failed to restore project flags: {}", e),
+                                );
+                            }
+                        }
+
+                        self.gs
+                            .toasts
+                            .success(format!("Project loaded from {}", path.display()))
+                            .duration(NORMAL_NOTIFICATION_TIME);
+                    }
+                    GuiEvent::AutoStep { enabled } => {
+                        if enabled && self.ts.breakpoints.is_empty() {
+                            self.gs
+                                .toasts
+                                .info("No breakpoints set, stepping until the program finishes.")
+                                .duration(NORMAL_NOTIFICATION_TIME);
+                        }
+                        self.ts.auto_step = enabled;
+                    }
+                    GuiEvent::StepToBreakpoint => {
+                        if !self.ts.auto_step {
+                            continue;
+                        }
+                        match client_ctx.step_until(&mut self.ts.breakpoints, AUTO_STEP_CYCLES) {
+                            Ok(outcome) => {
+                                self.ts.client_window.push_cycles(outcome.cycles);
+                                self.ts
+                                    .cycle_trace_window
+                                    .set_cycles(client_ctx.cpu_type, self.ts.client_window.cycles());
+
+                                if let Some(index) = outcome.hit {
+                                    self.ts.auto_step = false;
+                                    // The offending cycle is the last one stepped.
+                                    let cycle = self.ts.client_window.cycles().len().saturating_sub(1);
+                                    let mut scrub_events = GuiEventQueue::new();
+                                    self.ts.client_window.highlight_cycle(cycle, &mut scrub_events);
+                                    new_events.append(&mut scrub_events.events);
+                                    let breakpoint = &self.ts.breakpoints.breakpoints()[index];
+                                    let msg = format!(
+                                        "Breakpoint hit: {} at {:05X} on cycle {}",
+                                        breakpoint.kind, breakpoint.address, cycle
+                                    );
+                                    log::info!("{}", msg);
+                                    self.gs.toasts.info(msg).duration(NORMAL_NOTIFICATION_TIME);
+                                }
+                                else if outcome.finished {
+                                    self.ts.auto_step = false;
+                                    self.gs
+                                        .toasts
+                                        .info("Program finished without hitting a breakpoint.")
+                                        .duration(NORMAL_NOTIFICATION_TIME);
+                                }
+                            }
+                            Err(e) => {
+                                self.ts.auto_step = false;
+                                let msg = format!("Failed to step CPU: {}", e);
+                                log::error!("{}", msg);
+                                self.gs.toasts.error(msg.clone()).duration(LONG_NOTIFICATION_TIME);
+                                self.ts.health.record(HealthEventKind::CommandError, &msg);
+                            }
+                        }
+                    }
+                    GuiEvent::ScrubCycle { cycle } => {
+                        let Some(state) = self.ts.client_window.timeline_state(cycle)
+                        else {
+                            continue;
+                        };
+                        self.ts.memory_viewer_window.show_timeline_state(&state);
+
+                        // Registers are only known at the ends of the capture.
+                        match &state.registers {
+                            Some(regs) => {
+                                let initial_state = client_ctx.initial_state();
+                                self.ts.final_register_window.set_regs(&initial_state.regs, Some(regs));
+                                self.ts
+                                    .final_register_window
+                                    .set_note(Some(format!("Registers at cycle {}", cycle)));
+                            }
+                            None => {
+                                self.ts.final_register_window.set_note(Some(format!(
+                                    "Registers unknown at cycle {}; showing the last known set.",
+                                    cycle
+                                )));
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -1030,3 +1720,27 @@ impl App {
         }
     }
 }
+
+/// Build the result cache key for running the loaded blobs from the client's initial registers.
+/// CS:IP-mounted blobs are placed at `code_address`. Returns None if the registers can't be encoded.
+fn program_run_key(blobs: &[BinaryBlob], code_address: u32, client_ctx: &ClientContext) -> Option<RunKey> {
+    let mut regs = client_ctx.initial_state().regs.clone();
+    regs.normalize();
+    let mut reg_buf = Cursor::new(Vec::new());
+    regs.write(&mut reg_buf).ok()?;
+
+    let builder = blobs.iter().fold(RunKey::builder(), |builder, blob| {
+        let address = match blob.mount_address {
+            MountAddress::FlatAddress(addr) => addr,
+            MountAddress::CsIp => code_address,
+        };
+        builder.program(address, &blob.data)
+    });
+    Some(
+        builder
+            .registers(reg_buf.get_ref())
+            .extra(format!("{:?}", client_ctx.cpu_type).as_bytes())
+            .extra(&client_ctx.cached_flags().to_le_bytes())
+            .build(),
+    )
+}
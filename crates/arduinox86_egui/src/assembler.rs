@@ -29,12 +29,64 @@ use std::{
 };
 use tempfile::NamedTempFile;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// An error or warning NASM reported against a line of the source.
+#[derive(Clone, Debug)]
+pub struct AssemblerMessage {
+    /// The 1-based source line, if NASM gave one.
+    pub line: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl AssemblerMessage {
+    /// Parse a single line of NASM output, in the form `file:line: severity: message`. NASM reports no
+    /// column. Lines that aren't errors or warnings return `None`.
+    pub fn parse(text: &str) -> Option<Self> {
+        const SEVERITIES: [(&str, Severity); 4] = [
+            (": error: ", Severity::Error),
+            (": fatal: ", Severity::Error),
+            (": panic: ", Severity::Error),
+            (": warning: ", Severity::Warning),
+        ];
+
+        let (pos, tag, severity) = SEVERITIES
+            .iter()
+            .filter_map(|(tag, severity)| text.find(tag).map(|pos| (pos, *tag, *severity)))
+            .min_by_key(|(pos, _, _)| *pos)?;
+
+        // The location is everything before the tag. Paths may contain colons, so the line
+        // number is whatever follows the last one.
+        let location = &text[..pos];
+        let line = location
+            .rsplit_once(':')
+            .and_then(|(_, line)| line.trim().parse::<usize>().ok());
+
+        Some(AssemblerMessage {
+            line,
+            severity,
+            message: text[pos + tag.len()..].trim().to_string(),
+        })
+    }
+
+    /// Parse all the errors and warnings out of NASM's output.
+    pub fn parse_all(output: &str) -> Vec<Self> {
+        output.lines().filter_map(Self::parse).collect()
+    }
+}
+
 /// A little wrapper around calling `nasm` from Rust.
 pub struct Assembler {
     nasm_path: PathBuf,
     format: String,
     stdout_str: String,
     stderr_str: String,
+    messages: Vec<AssemblerMessage>,
 }
 
 impl Default for Assembler {
@@ -57,6 +109,7 @@ impl Assembler {
             format: format.into(),
             stdout_str: String::new(),
             stderr_str: String::new(),
+            messages: Vec::new(),
         }
     }
 
@@ -101,6 +154,7 @@ impl Assembler {
 
         self.stdout_str = String::from_utf8_lossy(&output.stdout).into_owned();
         self.stderr_str = String::from_utf8_lossy(&output.stderr).into_owned();
+        self.messages = AssemblerMessage::parse_all(&self.stderr_str);
 
         if output.status.success() {
             log::debug!("assemble_str(): nasm call succeeded!");
@@ -136,4 +190,9 @@ impl Assembler {
     pub fn stderr(&self) -> &str {
         &self.stderr_str
     }
+
+    /// Errors and warnings from the last call, with the source line each one refers to.
+    pub fn messages(&self) -> &[AssemblerMessage] {
+        &self.messages
+    }
 }
@@ -0,0 +1,180 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Breakpoints on bus transfers, checked while the GUI single-steps the CPU.
+//!
+//! The [CycleStepper] follows the bus across stepped cycles, remembering the address and bus
+//! status latched at ALE, and reports each transfer once as a [BusAccess]. The [BreakpointSet]
+//! matches these against the user's breakpoints.
+
+use arduinox86_client::{BusState, ServerCpuType, ServerCycleState};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, strum_macros::Display)]
+pub enum BreakpointKind {
+    #[strum(to_string = "Code Fetch")]
+    CodeFetch,
+    #[strum(to_string = "Memory Read")]
+    MemRead,
+    #[strum(to_string = "Memory Write")]
+    MemWrite,
+    #[strum(to_string = "I/O")]
+    Io,
+}
+
+impl BreakpointKind {
+    pub const ALL: [BreakpointKind; 4] = [
+        BreakpointKind::CodeFetch,
+        BreakpointKind::MemRead,
+        BreakpointKind::MemWrite,
+        BreakpointKind::Io,
+    ];
+}
+
+/// A transfer on the bus, as seen by the [CycleStepper].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BusAccess {
+    pub kind:    BreakpointKind,
+    pub address: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub kind:    BreakpointKind,
+    /// Physical address, or port number for I/O breakpoints.
+    pub address: u32,
+    pub enabled: bool,
+    pub hits:    u32,
+}
+
+impl Breakpoint {
+    pub fn new(kind: BreakpointKind, address: u32) -> Self {
+        Self {
+            kind,
+            address,
+            enabled: true,
+            hits: 0,
+        }
+    }
+
+    pub fn matches(&self, access: &BusAccess) -> bool {
+        if !self.enabled || self.kind != access.kind {
+            return false;
+        }
+        match self.kind {
+            // Ports are 16 bits, whatever the upper address lines hold.
+            BreakpointKind::Io => (access.address & 0xFFFF) == (self.address & 0xFFFF),
+            _ => access.address == self.address,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BreakpointSet {
+    breakpoints: Vec<Breakpoint>,
+    last_hit:    Option<usize>,
+}
+
+impl BreakpointSet {
+    pub fn add(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.breakpoints.len() {
+            self.breakpoints.remove(index);
+            self.last_hit = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.last_hit = None;
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn breakpoints_mut(&mut self) -> &mut [Breakpoint] {
+        &mut self.breakpoints
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// Return the index of the breakpoint that stopped execution most recently.
+    pub fn last_hit(&self) -> Option<usize> {
+        self.last_hit
+    }
+
+    /// Return the index of the first enabled breakpoint matching `access`, counting the hit.
+    pub fn check(&mut self, access: &BusAccess) -> Option<usize> {
+        let index = self.breakpoints.iter().position(|bp| bp.matches(access))?;
+        self.breakpoints[index].hits += 1;
+        self.last_hit = Some(index);
+        Some(index)
+    }
+}
+
+/// Follows the bus across single-stepped cycles.
+#[derive(Default)]
+pub struct CycleStepper {
+    address_latch: u32,
+    // Bus status latched at ALE. The older CPUs go passive before the transfer itself.
+    bus_state: Option<BusState>,
+    // Set once the transfer of the current bus cycle has been reported.
+    reported: bool,
+}
+
+impl CycleStepper {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Track `cycle`, returning the transfer it starts, if any. A transfer spanning several
+    /// cycles is reported only on its first.
+    pub fn access(&mut self, arch: ServerCpuType, cycle: &ServerCycleState) -> Option<BusAccess> {
+        if cycle.ale() {
+            self.address_latch = cycle.address_bus;
+            self.bus_state = Some(arch.decode_status(cycle.cpu_status_bits));
+            self.reported = false;
+        }
+        if self.reported || !cycle.is_transferring(arch) {
+            return None;
+        }
+
+        let kind = match self.bus_state? {
+            BusState::CODE => BreakpointKind::CodeFetch,
+            BusState::MEMR => BreakpointKind::MemRead,
+            BusState::MEMW => BreakpointKind::MemWrite,
+            BusState::IOR | BusState::IOW => BreakpointKind::Io,
+            _ => return None,
+        };
+        self.reported = true;
+        Some(BusAccess {
+            kind,
+            address: self.address_latch,
+        })
+    }
+}
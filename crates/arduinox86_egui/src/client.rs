@@ -20,7 +20,10 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
-use crate::serial_manager::SerialManager;
+use crate::{
+    breakpoints::{BreakpointSet, BusAccess, CycleStepper},
+    serial_manager::SerialManager,
+};
 use arduinox86_client::{
     CpuClient,
     ProgramState,
@@ -29,8 +32,12 @@ use arduinox86_client::{
     RemoteCpuRegistersV2,
     RemoteCpuRegistersV3,
     RemoteCpuRegistersV3A,
+    SegmentSize,
     ServerCpuType,
+    ServerCycleState,
+    PROTOCOL_LOG_LEN,
 };
+use arduinox86_config::ConnectionSettings;
 
 use crate::enums::ClientControlState;
 use anyhow::Result;
@@ -40,6 +47,25 @@ pub struct RemoteCpuState {
     pub regs: RemoteCpuRegisters,
 }
 
+/// Memory around the code being run, with the CS:IP and code segment size it was read for.
+#[derive(Clone)]
+pub struct CodeSnapshot {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    pub code_address: u32,
+    pub bitness: u32,
+}
+
+/// The cycles stepped by [ClientContext::step_until] and why it stopped.
+#[derive(Default)]
+pub struct StepOutcome {
+    pub cycles: Vec<ServerCycleState>,
+    /// Index of the breakpoint hit by the last cycle.
+    pub hit: Option<usize>,
+    /// The program left the execute state.
+    pub finished: bool,
+}
+
 pub struct ClientContext {
     pub(crate) port_name: String,
     pub(crate) client_state: ClientControlState,
@@ -51,10 +77,13 @@ pub struct ClientContext {
 
     pub(crate) initial_state: RemoteCpuState,
     pub(crate) memory_vec:    Vec<u8>,
+
+    // Tracks the bus across stepped cycles for breakpoints.
+    stepper: CycleStepper,
 }
 
 impl ClientContext {
-    pub fn new(selected_port: usize, sm: &mut SerialManager) -> Result<Self> {
+    pub fn new(selected_port: usize, sm: &mut SerialManager, connection: &ConnectionSettings) -> Result<Self> {
         // Get port name from selection
         let selected_port = sm
             .ports()
@@ -62,7 +91,13 @@ impl ClientContext {
             .ok_or_else(|| anyhow::anyhow!("Invalid port selection"))?;
 
         let port_name = selected_port.port_name.clone();
-        let mut client = CpuClient::init(Some(port_name.clone()), None)?;
+        let settings = ConnectionSettings {
+            port: Some(port_name.clone()),
+            ..connection.clone()
+        };
+        let mut client = settings.connect(1000)?;
+        // Keep the recent protocol traffic for flight records.
+        client.enable_protocol_log(PROTOCOL_LOG_LEN);
         let (cpu_type, queue_status) = client.cpu_type()?;
 
         // Create the appropriate register state type based on the CPU type.
@@ -89,6 +124,7 @@ impl ClientContext {
             program_state,
             initial_state,
             memory_vec: Vec::with_capacity(u16::MAX as usize),
+            stepper: CycleStepper::default(),
         })
     }
 
@@ -141,6 +177,15 @@ impl ClientContext {
         Ok(state)
     }
 
+    /// Set the flags in `mask` to their state in `flags`, leaving the other flags as they are.
+    pub fn set_flags(&mut self, mask: u32, flags: u32) -> Result<()> {
+        let current = self.client.get_flags()?;
+        let flags = (current & !mask) | (flags & mask);
+        self.client.set_flags(flags)?;
+        self.server_flags = flags;
+        Ok(())
+    }
+
     pub fn cached_flag_state(&self, flag: u32) -> bool {
         self.server_flags & flag != 0
     }
@@ -148,4 +193,69 @@ impl ClientContext {
     pub fn cached_flags(&self) -> u32 {
         self.server_flags
     }
+
+    /// Return the address of CS:IP. Registers are only read back at the end of a run, so while a
+    /// program runs this is where it started.
+    pub fn code_address(&self) -> u32 {
+        self.initial_state.regs.code_address()
+    }
+
+    /// Return the size of the code segment, 16 or 32 bits.
+    pub fn code_bitness(&self) -> u32 {
+        match &self.initial_state.regs {
+            RemoteCpuRegisters::V3(RemoteCpuRegistersV3::A(regs)) => regs.cs_desc.segment_size().into(),
+            _ => SegmentSize::Sixteen.into(),
+        }
+    }
+
+    /// Read `size` bytes of memory at `address` for disassembly.
+    pub fn code_snapshot(&mut self, address: u32, size: u32) -> Result<CodeSnapshot> {
+        let bytes = self.read_memory(address, size)?.to_vec();
+        Ok(CodeSnapshot {
+            address,
+            bytes,
+            code_address: self.code_address(),
+            bitness: self.code_bitness(),
+        })
+    }
+
+    /// Forget the bus state tracked for breakpoints, before a new run.
+    pub fn reset_stepper(&mut self) {
+        self.stepper.reset();
+    }
+
+    /// Step the CPU one cycle, returning the cycle and the transfer it starts, if any.
+    pub fn step_cycle(&mut self) -> Result<(ServerCycleState, Option<BusAccess>)> {
+        let cycle = self.client.get_cycle_state(true)?;
+        self.program_state = cycle.program_state;
+        let access = self.stepper.access(self.cpu_type, &cycle);
+        Ok((cycle, access))
+    }
+
+    /// Step the CPU until a transfer hits one of `breakpoints`, the program leaves the execute
+    /// state or `max_cycles` cycles have run. The cycles stepped are returned even on a hit, the
+    /// offending cycle last.
+    pub fn step_until(&mut self, breakpoints: &mut BreakpointSet, max_cycles: usize) -> Result<StepOutcome> {
+        let mut outcome = StepOutcome::default();
+        for _ in 0..max_cycles {
+            let (cycle, access) = self.step_cycle()?;
+            let state = cycle.program_state;
+            outcome.cycles.push(cycle);
+
+            if let Some(access) = access {
+                outcome.hit = breakpoints.check(&access);
+                if outcome.hit.is_some() {
+                    break;
+                }
+            }
+            if !matches!(
+                state,
+                ProgramState::Prefetch | ProgramState::Execute | ProgramState::ExecuteFinalize
+            ) {
+                outcome.finished = true;
+                break;
+            }
+        }
+        Ok(outcome)
+    }
 }
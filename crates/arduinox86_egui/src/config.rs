@@ -20,10 +20,65 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
+use arduinox86_client::UsbId;
+use arduinox86_config::{resolve, ConfigError, ConnectionSettings, DevicesFile};
 use serde::Deserialize;
 use std::path::PathBuf;
 
 #[derive(Debug, Default, Clone, Deserialize)]
 pub struct ConfigFile {
     pub assembly_output_path: PathBuf,
+    /// Path to the shared devices file. Defaults to $ARDX86_DEVICES, then ./devices.toml.
+    #[serde(default)]
+    pub devices_file: Option<PathBuf>,
+    /// Path to the address map. Defaults to $ARDX86_ADDRESS_MAP, then ./address_map.toml.
+    #[serde(default)]
+    pub address_map: Option<PathBuf>,
+    #[serde(default)]
+    pub serial: SerialConfig,
+}
+
+impl ConfigFile {
+    /// Resolve the connection settings from the devices file, the environment and the `[serial]`
+    /// table, which takes precedence.
+    pub fn connection(&self, devices: &DevicesFile) -> Result<ConnectionSettings, ConfigError> {
+        resolve(devices, self.serial.device.as_deref(), &self.serial.settings())
+    }
+}
+
+/// Restricts which serial ports are offered for connection.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct SerialConfig {
+    /// Device label in the devices file to take connection settings from.
+    pub device:  Option<String>,
+    /// Port names to list. A trailing '*' matches by prefix. Empty lists every port.
+    pub allow:   Vec<String>,
+    /// Port names to hide.
+    pub deny:    Vec<String>,
+    /// USB VID:PID pairs in hex, e.g. "2341:0043". Empty lists every port.
+    pub usb_ids: Vec<String>,
+}
+
+impl SerialConfig {
+    pub fn settings(&self) -> ConnectionSettings {
+        let usb_ids = self
+            .usb_ids
+            .iter()
+            .filter_map(|id| match id.parse::<UsbId>() {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    log::error!("Ignoring serial.usb_ids entry: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        ConnectionSettings {
+            allow: self.allow.clone(),
+            deny: self.deny.clone(),
+            usb_ids,
+            ..Default::default()
+        }
+    }
 }
@@ -20,12 +20,22 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
+use std::collections::BTreeMap;
+
 use crate::{
     events::{GuiEvent, GuiEventQueue},
     widgets::cycle_display::CycleDisplay,
 };
-use arduinox86_client::{ServerCpuType, ServerCycleLogPrinter, ServerCycleState};
-use egui::{Color32, Margin, Response};
+use arduinox86_client::{
+    RemoteCpuRegisters,
+    ServerCpuType,
+    ServerCycleLogPrinter,
+    ServerCycleState,
+    Timeline,
+    TimelineState,
+    TransferKind,
+};
+use egui::{Color32, Margin, Response, Stroke, StrokeKind};
 
 #[derive(Default)]
 pub struct CycleTable {
@@ -33,6 +43,16 @@ pub struct CycleTable {
     cycles: Vec<ServerCycleState>,
     data_bus_str: String,
     address_latch: u32,
+    // Built on demand for the timeline slider, and dropped whenever the cycles change.
+    timeline: Option<Timeline>,
+    scrub_cycle: usize,
+    scrubbing: bool,
+    scroll_to_cursor: bool,
+    initial_regs: Option<RemoteCpuRegisters>,
+    final_regs: Option<RemoteCpuRegisters>,
+    // Notes attached to cycles of the current capture, by cycle index.
+    bookmarks: BTreeMap<usize, String>,
+    bookmark_note: String,
 }
 
 impl CycleTable {
@@ -42,6 +62,14 @@ impl CycleTable {
             cycles: Vec::new(),
             data_bus_str: String::new(),
             address_latch: 0,
+            timeline: None,
+            scrub_cycle: 0,
+            scrubbing: false,
+            scroll_to_cursor: false,
+            initial_regs: None,
+            final_regs: None,
+            bookmarks: BTreeMap::new(),
+            bookmark_note: String::new(),
         }
     }
 
@@ -62,19 +90,71 @@ impl CycleTable {
             self.data_bus_str = format!("{:04X}", last_cycle.data_bus);
         }
         self.cycles = cycles;
+        self.timeline = None;
+        self.scrubbing = false;
+        self.bookmarks.clear();
     }
 
     pub fn push_cycle(&mut self, cycle: ServerCycleState) {
         self.data_bus_str = format!("{:04X}", cycle.data_bus);
         self.cycles.push(cycle);
+        self.timeline = None;
     }
 
     pub fn cycles(&self) -> &[ServerCycleState] {
         &self.cycles
     }
 
+    /// Return the cycles as the text cycle log.
+    pub fn cycle_log(&self) -> String {
+        ServerCycleLogPrinter::new(self.arch, &self.cycles).to_string()
+    }
+
+    /// Return the registers at the first and last cycle, if known.
+    pub fn registers(&self) -> (Option<&RemoteCpuRegisters>, Option<&RemoteCpuRegisters>) {
+        (self.initial_regs.as_ref(), self.final_regs.as_ref())
+    }
+
     pub fn clear(&mut self) {
         self.cycles.clear();
+        self.timeline = None;
+        self.scrubbing = false;
+        self.initial_regs = None;
+        self.final_regs = None;
+        self.bookmarks.clear();
+    }
+
+    /// Return the bookmarked cycles and their notes.
+    pub fn bookmarks(&self) -> &BTreeMap<usize, String> {
+        &self.bookmarks
+    }
+
+    /// Mark `cycle` in the cycle log and scroll to it, as for a breakpoint hit.
+    pub fn highlight(&mut self, cycle: usize, events: &mut GuiEventQueue) {
+        self.jump_to(cycle, events);
+    }
+
+    /// Select `cycle` on the timeline and scroll the cycle log to it.
+    fn jump_to(&mut self, cycle: usize, events: &mut GuiEventQueue) {
+        self.scrub_cycle = cycle;
+        self.scrubbing = true;
+        self.scroll_to_cursor = true;
+        events.push(GuiEvent::ScrubCycle { cycle });
+    }
+
+    /// Set the registers the timeline reports at the first and last cycle.
+    pub fn set_registers(&mut self, initial: Option<RemoteCpuRegisters>, final_: Option<RemoteCpuRegisters>) {
+        self.initial_regs = initial;
+        self.final_regs = final_;
+        self.timeline = None;
+    }
+
+    /// Return the machine state reconstructed at `cycle`, if the timeline has been built.
+    pub fn timeline_state(&self, cycle: usize) -> Option<TimelineState> {
+        self.timeline
+            .as_ref()
+            .filter(|timeline| !timeline.is_empty())
+            .map(|timeline| timeline.seek(cycle))
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui, events: &mut GuiEventQueue) -> Option<Response> {
@@ -99,6 +179,7 @@ impl CycleTable {
         //ui.painter().rect_filled(rect, 0.0, bg);
 
         let mut inner_response = None;
+        let scroll_to_cursor = std::mem::take(&mut self.scroll_to_cursor);
 
         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
             ui.horizontal(|ui| {
@@ -112,7 +193,7 @@ impl CycleTable {
                     .clicked()
                 {
                     events.push(GuiEvent::ClearCycleLog);
-                    self.cycles.clear();
+                    self.clear();
                 }
                 if ui
                     .button(egui::RichText::new(format!("{}", egui_phosphor::regular::CLIPBOARD_TEXT)).size(18.0))
@@ -136,6 +217,8 @@ impl CycleTable {
                     .show(ui, |ui| {
                         ui.set_min_width(ui.available_width());
                         let num_cycles = self.cycles.len();
+                        let cursor_color = ui.visuals().selection.stroke.color;
+                        let bookmark_color = ui.visuals().warn_fg_color;
                         for (i, cycle) in self.cycles.iter().enumerate() {
                             let mut data_str_opt = None;
                             // If is last cycle
@@ -146,11 +229,43 @@ impl CycleTable {
                             let cycle_display =
                                 CycleDisplay::new(self.arch, cycle.clone(), &mut self.address_latch, data_str_opt);
 
-                            inner_response = Some(ui.add(cycle_display));
+                            let row = ui.scope(|ui| ui.add(cycle_display));
+                            inner_response = Some(row.inner);
+
+                            // Mark bookmarked cycles, with the note on hover.
+                            if let Some(note) = self.bookmarks.get(&i) {
+                                let rect = row.response.rect;
+                                ui.painter().rect_filled(
+                                    egui::Rect::from_min_size(rect.min, egui::vec2(3.0, rect.height())),
+                                    0.0,
+                                    bookmark_color,
+                                );
+                                if !note.is_empty() {
+                                    row.response.clone().on_hover_text(note.as_str());
+                                }
+                            }
+
+                            // Mark the cycle selected on the timeline slider.
+                            if self.scrubbing && (i == self.scrub_cycle) {
+                                ui.painter().rect_stroke(
+                                    row.response.rect,
+                                    2.0,
+                                    Stroke::new(1.0, cursor_color),
+                                    StrokeKind::Outside,
+                                );
+                                if scroll_to_cursor {
+                                    ui.scroll_to_rect(row.response.rect, Some(egui::Align::Center));
+                                }
+                            }
                         }
                     });
             });
 
+        egui::CollapsingHeader::new("Timeline").show(ui, |ui| self.show_timeline(ui, events));
+        egui::CollapsingHeader::new(format!("Bookmarks ({})", self.bookmarks.len()))
+            .id_salt("cycle_bookmarks")
+            .show(ui, |ui| self.show_bookmarks(ui, events));
+
         if let Some(resp) = inner_response.as_ref() {
             if resp.changed() {
                 log::debug!("ClientWindow::show(): Response: {:?}", resp);
@@ -159,4 +274,145 @@ impl CycleTable {
 
         inner_response
     }
+
+    /// Show the bookmarks, with controls to bookmark the cycle selected on the timeline and to jump
+    /// between bookmarks.
+    fn show_bookmarks(&mut self, ui: &mut egui::Ui, events: &mut GuiEventQueue) {
+        let cycle = self.scrub_cycle.min(self.cycles.len() - 1);
+
+        ui.horizontal(|ui| {
+            let prev = self.bookmarks.range(..cycle).next_back().map(|(c, _)| *c);
+            let next = self.bookmarks.range(cycle + 1..).next().map(|(c, _)| *c);
+            if ui
+                .add_enabled(
+                    prev.is_some(),
+                    egui::Button::new(egui::RichText::new(egui_phosphor::regular::CARET_LEFT.to_string())),
+                )
+                .on_hover_text("Previous bookmark")
+                .clicked()
+            {
+                if let Some(prev) = prev {
+                    self.jump_to(prev, events);
+                }
+            }
+            if ui
+                .add_enabled(
+                    next.is_some(),
+                    egui::Button::new(egui::RichText::new(egui_phosphor::regular::CARET_RIGHT.to_string())),
+                )
+                .on_hover_text("Next bookmark")
+                .clicked()
+            {
+                if let Some(next) = next {
+                    self.jump_to(next, events);
+                }
+            }
+
+            ui.separator();
+            ui.label(format!("Cycle {}:", cycle));
+            ui.add(egui::TextEdit::singleline(&mut self.bookmark_note).hint_text("Note"));
+            if ui
+                .button(egui::RichText::new(egui_phosphor::regular::BOOKMARK_SIMPLE.to_string()))
+                .on_hover_text("Bookmark the cycle selected on the timeline")
+                .clicked()
+            {
+                self.bookmarks.insert(cycle, std::mem::take(&mut self.bookmark_note));
+            }
+        });
+
+        let mut jump = None;
+        let mut remove = None;
+        egui::ScrollArea::vertical()
+            .id_salt("bookmark_list")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for (cycle, note) in self.bookmarks.iter_mut() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(format!("{:08}", cycle))
+                            .on_hover_text("Go to cycle")
+                            .clicked()
+                        {
+                            jump = Some(*cycle);
+                        }
+                        ui.text_edit_singleline(note);
+                        if ui
+                            .button(egui::RichText::new(egui_phosphor::regular::X.to_string()))
+                            .on_hover_text("Remove bookmark")
+                            .clicked()
+                        {
+                            remove = Some(*cycle);
+                        }
+                    });
+                }
+            });
+
+        if let Some(cycle) = jump {
+            self.jump_to(cycle, events);
+        }
+        if let Some(cycle) = remove {
+            self.bookmarks.remove(&cycle);
+        }
+    }
+
+    /// Show a slider to scrub through the cycle log, with the machine state reconstructed at the
+    /// selected cycle.
+    fn show_timeline(&mut self, ui: &mut egui::Ui, events: &mut GuiEventQueue) {
+        let timeline = self.timeline.get_or_insert_with(|| {
+            Timeline::new(self.arch, self.cycles.clone())
+                .with_registers(self.initial_regs.clone(), self.final_regs.clone())
+        });
+        if timeline.is_empty() {
+            return;
+        }
+
+        let last_cycle = timeline.len() - 1;
+        self.scrub_cycle = self.scrub_cycle.min(last_cycle);
+        if ui
+            .add(egui::Slider::new(&mut self.scrub_cycle, 0..=last_cycle).text("Cycle"))
+            .changed()
+        {
+            self.scrubbing = true;
+            self.scroll_to_cursor = true;
+            events.push(GuiEvent::ScrubCycle {
+                cycle: self.scrub_cycle,
+            });
+        }
+
+        let state = timeline.seek(self.scrub_cycle);
+        ui.label(format!("Address latch: {:08X}", state.address_latch));
+
+        let transfer_str = match timeline.last_transfer(self.scrub_cycle) {
+            Some(transfer) => {
+                let kind = match transfer.kind {
+                    TransferKind::CodeRead => "CODE",
+                    TransferKind::MemRead => "MEMR",
+                    TransferKind::MemWrite => "MEMW",
+                    TransferKind::IoRead => "IOR",
+                    TransferKind::IoWrite => "IOW",
+                };
+                format!(
+                    "{} {:08X} = {:04X} (cycle {})",
+                    kind, transfer.address, transfer.data, transfer.cycle
+                )
+            }
+            None => "None".to_string(),
+        };
+        ui.label(format!("Last transfer: {}", transfer_str));
+
+        if let Some(queue) = &state.queue {
+            let queue_str = queue.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+            ui.label(format!("Queue: [{}]", queue_str));
+        }
+
+        ui.label(format!("Memory seen on the bus: {} bytes", state.memory.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("timeline_memory")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for (address, byte) in &state.memory {
+                    ui.monospace(format!("{:08X}: {:02X}", address, byte));
+                }
+            });
+    }
 }
@@ -25,5 +25,6 @@ pub mod canvas;
 pub mod cycle_table;
 pub mod data_table;
 pub mod data_visualizer;
+pub mod register_diff;
 pub mod registers_v3;
 pub mod tab_group;
@@ -0,0 +1,251 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! A read-only view comparing two register sets of any version. Registers and flag bits that
+//! changed between the initial and final state are highlighted.
+
+use crate::TEXT_COLOR;
+use arduinox86_client::{Registers32, RemoteCpuRegisters};
+use egui::{Color32, TextStyle};
+
+/// Color used for values that differ from the initial register set.
+const DIFF_COLOR: Color32 = Color32::CYAN;
+
+/// A flag field as (label, tooltip, mask). Fields wider than one bit, like IOPL, are shown as a
+/// single value.
+type FlagField = (&'static str, &'static str, u32);
+
+const FLAGS_8086: &[FlagField] = &[
+    ("O", "Overflow", 0x0800),
+    ("D", "Direction", 0x0400),
+    ("I", "Interrupt enable", 0x0200),
+    ("T", "Trap", 0x0100),
+    ("S", "Sign", 0x0080),
+    ("Z", "Zero", 0x0040),
+    ("A", "Auxiliary carry", 0x0010),
+    ("P", "Parity", 0x0004),
+    ("C", "Carry", 0x0001),
+];
+
+const FLAGS_286: &[FlagField] = &[("NT", "Nested task", 0x4000), ("IOPL", "I/O privilege level", 0x3000)];
+
+const FLAGS_386: &[FlagField] = &[("VM", "Virtual 8086 mode", 0x2_0000), ("RF", "Resume", 0x1_0000)];
+
+/// A register compared between the initial and final register sets.
+pub struct RegisterDelta {
+    pub name:    &'static str,
+    pub initial: String,
+    pub last:    String,
+    pub changed: bool,
+}
+
+/// A flag field compared between the initial and final register sets.
+pub struct FlagDelta {
+    pub name: &'static str,
+    pub tip: &'static str,
+    pub initial: u32,
+    pub last: u32,
+}
+
+impl FlagDelta {
+    fn new(field: &FlagField, initial_flags: u32, final_flags: u32) -> Self {
+        let (name, tip, mask) = *field;
+        let shift = mask.trailing_zeros();
+        Self {
+            name,
+            tip,
+            initial: (initial_flags & mask) >> shift,
+            last: (final_flags & mask) >> shift,
+        }
+    }
+
+    pub fn changed(&self) -> bool {
+        self.initial != self.last
+    }
+}
+
+#[derive(Default)]
+pub struct RegisterDiffControl {
+    regs: Vec<RegisterDelta>,
+    flags: Vec<FlagDelta>,
+    changed_only: bool,
+}
+
+impl RegisterDiffControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare two register sets. Returns false, leaving the control empty, if the register sets are
+    /// different versions.
+    pub fn set_regs(&mut self, initial_regs: &RemoteCpuRegisters, final_regs: &RemoteCpuRegisters) -> bool {
+        self.clear();
+        match (initial_regs, final_regs) {
+            (RemoteCpuRegisters::V1(i), RemoteCpuRegisters::V1(f)) => {
+                self.reg16("AX", i.ax, f.ax);
+                self.reg16("BX", i.bx, f.bx);
+                self.reg16("CX", i.cx, f.cx);
+                self.reg16("DX", i.dx, f.dx);
+                self.reg16("SP", i.sp, f.sp);
+                self.reg16("BP", i.bp, f.bp);
+                self.reg16("SI", i.si, f.si);
+                self.reg16("DI", i.di, f.di);
+                self.reg16("CS", i.cs, f.cs);
+                self.reg16("DS", i.ds, f.ds);
+                self.reg16("ES", i.es, f.es);
+                self.reg16("SS", i.ss, f.ss);
+                self.reg16("IP", i.ip, f.ip);
+                self.reg16("FLAGS", i.flags, f.flags);
+                self.push_flags(&[FLAGS_8086], i.flags as u32, f.flags as u32);
+            }
+            (RemoteCpuRegisters::V2(i), RemoteCpuRegisters::V2(f)) => {
+                self.reg16("AX", i.ax, f.ax);
+                self.reg16("BX", i.bx, f.bx);
+                self.reg16("CX", i.cx, f.cx);
+                self.reg16("DX", i.dx, f.dx);
+                self.reg16("SP", i.sp, f.sp);
+                self.reg16("BP", i.bp, f.bp);
+                self.reg16("SI", i.si, f.si);
+                self.reg16("DI", i.di, f.di);
+                self.reg16("CS", i.cs, f.cs);
+                self.reg16("DS", i.ds, f.ds);
+                self.reg16("ES", i.es, f.es);
+                self.reg16("SS", i.ss, f.ss);
+                self.reg16("IP", i.ip, f.ip);
+                self.reg16("FLAGS", i.flags, f.flags);
+                self.reg16("MSW", i.msw, f.msw);
+                self.reg16("TR", i.tr, f.tr);
+                self.reg16("LDT", i.ldt, f.ldt);
+                self.push_flags(&[FLAGS_286, FLAGS_8086], i.flags as u32, f.flags as u32);
+            }
+            (RemoteCpuRegisters::V3(i), RemoteCpuRegisters::V3(f)) => {
+                self.reg32("EAX", i.eax(), f.eax());
+                self.reg32("EBX", i.ebx(), f.ebx());
+                self.reg32("ECX", i.ecx(), f.ecx());
+                self.reg32("EDX", i.edx(), f.edx());
+                self.reg32("ESP", i.esp(), f.esp());
+                self.reg32("EBP", i.ebp(), f.ebp());
+                self.reg32("ESI", i.esi(), f.esi());
+                self.reg32("EDI", i.edi(), f.edi());
+                self.reg16("CS", i.cs(), f.cs());
+                self.reg16("DS", i.ds(), f.ds());
+                self.reg16("ES", i.es(), f.es());
+                self.reg16("FS", i.fs(), f.fs());
+                self.reg16("GS", i.gs(), f.gs());
+                self.reg16("SS", i.ss(), f.ss());
+                self.reg32("EIP", i.eip(), f.eip());
+                self.reg32("EFLAGS", i.eflags(), f.eflags());
+                self.reg32("CR0", i.cr0(), f.cr0());
+                self.reg32("DR6", i.dr6(), f.dr6());
+                self.reg32("DR7", i.dr7(), f.dr7());
+                self.push_flags(&[FLAGS_386, FLAGS_286, FLAGS_8086], i.eflags(), f.eflags());
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.regs.clear();
+        self.flags.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regs.is_empty()
+    }
+
+    fn reg16(&mut self, name: &'static str, initial: u16, last: u16) {
+        self.regs.push(RegisterDelta {
+            name,
+            initial: format!("{:04X}", initial),
+            last: format!("{:04X}", last),
+            changed: initial != last,
+        });
+    }
+
+    fn reg32(&mut self, name: &'static str, initial: u32, last: u32) {
+        self.regs.push(RegisterDelta {
+            name,
+            initial: format!("{:08X}", initial),
+            last: format!("{:08X}", last),
+            changed: initial != last,
+        });
+    }
+
+    fn push_flags(&mut self, fields: &[&[FlagField]], initial_flags: u32, final_flags: u32) {
+        self.flags = fields
+            .iter()
+            .flat_map(|group| group.iter())
+            .map(|field| FlagDelta::new(field, initial_flags, final_flags))
+            .collect();
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.changed_only, "Changed only");
+
+        egui::Grid::new("reg_diff_grid").striped(true).show(ui, |ui| {
+            ui.label("Register");
+            ui.label("Initial");
+            ui.label("Final");
+            ui.end_row();
+
+            for reg in self.regs.iter().filter(|reg| reg.changed || !self.changed_only) {
+                let color = if reg.changed { DIFF_COLOR } else { TEXT_COLOR };
+                ui.label(egui::RichText::new(reg.name).text_style(TextStyle::Monospace));
+                ui.label(egui::RichText::new(&reg.initial).text_style(TextStyle::Monospace));
+                ui.label(
+                    egui::RichText::new(&reg.last)
+                        .text_style(TextStyle::Monospace)
+                        .color(color),
+                );
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+
+        egui::Grid::new("reg_diff_flags").striped(true).show(ui, |ui| {
+            ui.label("");
+            for flag in &self.flags {
+                ui.label(egui::RichText::new(flag.name).text_style(TextStyle::Monospace))
+                    .on_hover_text(flag.tip);
+            }
+            ui.end_row();
+            ui.label("Initial");
+            for flag in &self.flags {
+                ui.label(egui::RichText::new(flag.initial.to_string()).text_style(TextStyle::Monospace));
+            }
+            ui.end_row();
+            ui.label("Final");
+            for flag in &self.flags {
+                let color = if flag.changed() { DIFF_COLOR } else { TEXT_COLOR };
+                ui.label(
+                    egui::RichText::new(flag.last.to_string())
+                        .text_style(TextStyle::Monospace)
+                        .color(color),
+                );
+            }
+            ui.end_row();
+        });
+    }
+}
@@ -25,7 +25,7 @@ use crate::{
     events::GuiEventQueue,
     register_state::RegisterStringStateV3,
 };
-use arduinox86_client::{Registers32, RemoteCpuRegistersV3, RemoteCpuRegistersV3A, ServerCpuType};
+use arduinox86_client::{RegisterPrinter, Registers32, RemoteCpuRegistersV3, RemoteCpuRegistersV3A, ServerCpuType};
 use egui::{Color32, TextBuffer};
 
 const COLUMN_WIDTH: f32 = 150.0;
@@ -62,6 +62,18 @@ impl RegisterControlV3 {
 
     #[rustfmt::skip]
     pub fn show(&mut self, ui: &mut egui::Ui, events: &mut GuiEventQueue) {
+        if ui
+            .button(egui::RichText::new(format!("{}", egui_phosphor::regular::CLIPBOARD_TEXT)).size(18.0))
+            .on_hover_text("Copy")
+            .clicked()
+        {
+            let text = match &self.final_regs {
+                Some(final_regs) => self.regs.display_delta(final_regs, ServerCpuType::Intel80386).to_string(),
+                None => self.regs.display(ServerCpuType::Intel80386).to_string(),
+            };
+            ui.ctx().copy_text(text);
+        }
+
         match &mut self.regs {
             RemoteCpuRegistersV3::A(_) | RemoteCpuRegistersV3::B(_) => {
                 if self.final_regs.is_none() {
@@ -27,27 +27,68 @@ use std::path::PathBuf;
 pub enum GuiEvent {
     ResetState,
     LoadRegisters,
+    RandomizeRegisters,
+    RestoreRegisters {
+        index: usize,
+    },
     EraseMemory,
     ReadMemory {
         address: u32,
         size:    u32,
     },
-    RunProgram,
+    RunProgram {
+        // False when the blobs are already in memory, such as after a patch.
+        upload_blobs: bool,
+    },
     AssembleProgram {
         program_name: String,
     },
     PollStatus,
+    QueryLastError,
     ClearCycleLog,
     ToggleRefreshMemory {
         enabled: bool,
         hertz:   u32,
     },
     RefreshMemory,
+    ReadDisassembly {
+        address: u32,
+        size:    u32,
+    },
+    ScrubCycle {
+        cycle: usize,
+    },
+    AutoStep {
+        enabled: bool,
+    },
+    StepToBreakpoint,
     UploadBlob {
         blob_name: String,
         mount_address: MountAddress,
         size: Option<usize>,
     },
+    ExportFlightRecord {
+        path: PathBuf,
+    },
+    ExportExperimentBundle {
+        path: PathBuf,
+    },
+    ExportCycleTrace {
+        path: PathBuf,
+    },
+    PatchAndRun {
+        offset: u32,
+        bytes:  Vec<u8>,
+    },
+    ExportPatches {
+        path: PathBuf,
+    },
+    SaveProject {
+        path: PathBuf,
+    },
+    OpenProject {
+        path: PathBuf,
+    },
 }
 
 #[derive(Default)]
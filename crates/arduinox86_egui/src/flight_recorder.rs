@@ -0,0 +1,138 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Flight recorder for bug reports.
+//!
+//! The [FlightRecorder] keeps the most recent program state changes and errors of a session. The
+//! client's protocol log holds the matching commands and responses. Exporting a flight record
+//! bundles both with the cycle capture, the register dumps and the configuration in a single zip
+//! file that can be attached to an issue.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use arduinox86_client::ProgramState;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+/// Maximum number of events kept by the recorder.
+pub const FLIGHT_RECORD_LEN: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FlightEventKind {
+    State(ProgramState),
+    Error(String),
+}
+
+pub struct FlightEvent {
+    /// Time since the start of the session.
+    pub at:   Duration,
+    pub kind: FlightEventKind,
+}
+
+pub struct FlightRecorder {
+    start:  Instant,
+    events: VecDeque<FlightEvent>,
+}
+
+impl Default for FlightRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlightRecorder {
+    pub fn new() -> Self {
+        Self {
+            start:  Instant::now(),
+            events: VecDeque::with_capacity(FLIGHT_RECORD_LEN),
+        }
+    }
+
+    /// Start a new session, discarding all history.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn record_state(&mut self, state: ProgramState) {
+        self.push(FlightEventKind::State(state));
+    }
+
+    /// Record an error. An error identical to the last one recorded is skipped, so a message that
+    /// stays on screen is only recorded once.
+    pub fn record_error(&mut self, message: &str) {
+        let last_error = self.events.iter().rev().find_map(|event| match &event.kind {
+            FlightEventKind::Error(last) => Some(last),
+            _ => None,
+        });
+        if last_error.map(String::as_str) != Some(message) {
+            self.push(FlightEventKind::Error(message.to_string()));
+        }
+    }
+
+    fn push(&mut self, kind: FlightEventKind) {
+        if self.events.len() == FLIGHT_RECORD_LEN {
+            self.events.pop_front();
+        }
+        self.events.push_back(FlightEvent {
+            at: self.start.elapsed(),
+            kind,
+        });
+    }
+
+    /// Return the recorded events as text, one per line.
+    pub fn to_text(&self) -> String {
+        self.events
+            .iter()
+            .map(|event| {
+                let what = match &event.kind {
+                    FlightEventKind::State(state) => format!("state {:?}", state),
+                    FlightEventKind::Error(message) => format!("error: {}", message),
+                };
+                format!("{:>10.3} {}\n", event.at.as_secs_f64(), what)
+            })
+            .collect()
+    }
+}
+
+/// Write a flight record to `path` as a zip file holding one text file per `(name, contents)`
+/// section.
+pub fn export_flight_record(path: &Path, sections: &[(&str, String)]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, contents) in sections {
+        zip.start_file(*name, options)
+            .with_context(|| format!("Adding {}", name))?;
+        zip.write_all(contents.as_bytes())
+            .with_context(|| format!("Writing {}", name))?;
+    }
+    zip.finish().context("Finishing flight record")?;
+    Ok(())
+}
@@ -0,0 +1,305 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Session-long record of hardware health.
+//!
+//! The [HealthMonitor] collects the symptoms of a flaky rig - failed commands, retried and lost
+//! status polls, stalled execution, firmware errors and control pin readings - so they can be
+//! reviewed in the health dashboard instead of scrolling past in the log.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use arduinox86_client::{CpuPin, ProgramState, ServerStatus};
+
+/// Maximum number of events kept in the history.
+pub const HEALTH_HISTORY_LEN: usize = 256;
+/// Number of consecutive polls in the Execute state without cycle progress before we report a stall.
+pub const STALL_POLL_COUNT: u32 = 3;
+/// The pins sampled while the dashboard is open.
+pub const MONITORED_PINS: [CpuPin; 4] = [CpuPin::READY, CpuPin::TEST, CpuPin::INTR, CpuPin::NMI];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, strum_macros::Display)]
+pub enum HealthEventKind {
+    #[strum(to_string = "Command error")]
+    CommandError,
+    #[strum(to_string = "Retry")]
+    Retry,
+    #[strum(to_string = "Desync")]
+    Desync,
+    #[strum(to_string = "Stall")]
+    Stall,
+    #[strum(to_string = "Firmware error")]
+    FirmwareError,
+    #[strum(to_string = "Pin fault")]
+    PinFault,
+    #[strum(to_string = "Missed transition")]
+    MissedTransition,
+}
+
+impl HealthEventKind {
+    /// Retries are expected now and then, and short programs finish between polls; everything
+    /// else counts as an error.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, HealthEventKind::Retry | HealthEventKind::MissedTransition)
+    }
+}
+
+pub struct HealthEvent {
+    /// Time since the start of the session.
+    pub at: Duration,
+    pub kind: HealthEventKind,
+    pub message: String,
+    /// Number of identical events folded into this one.
+    pub repeats: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct PinHealth {
+    pub level: Option<bool>,
+    pub reads: u32,
+    pub failures: u32,
+    pub transitions: u32,
+}
+
+pub struct HealthMonitor {
+    start: Instant,
+    events: VecDeque<HealthEvent>,
+    command_errors: u32,
+    retries: u32,
+    desyncs: u32,
+    stalls: u32,
+    pin_faults: u32,
+    missed_transitions: u32,
+    last_firmware_error: Option<String>,
+    firmware_query_pending: bool,
+    pins: [PinHealth; MONITORED_PINS.len()],
+    // Error count for each minute of the session.
+    errors_per_minute: Vec<u32>,
+    last_cycle_ct: Option<u64>,
+    last_state_seq: Option<u32>,
+    stalled_polls: u32,
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: VecDeque::new(),
+            command_errors: 0,
+            retries: 0,
+            desyncs: 0,
+            stalls: 0,
+            pin_faults: 0,
+            missed_transitions: 0,
+            last_firmware_error: None,
+            firmware_query_pending: false,
+            pins: [PinHealth::default(); MONITORED_PINS.len()],
+            errors_per_minute: Vec::new(),
+            last_cycle_ct: None,
+            last_state_seq: None,
+            stalled_polls: 0,
+        }
+    }
+
+    /// Start a new session, discarding all history.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn session_time(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Record a health event. Consecutive identical events are folded together.
+    pub fn record(&mut self, kind: HealthEventKind, message: impl Into<String>) {
+        let message = message.into();
+        let at = self.session_time();
+
+        match kind {
+            HealthEventKind::CommandError => {
+                self.command_errors += 1;
+                // The server keeps a description of the last failure; fetch it on the next poll.
+                self.firmware_query_pending = true;
+            }
+            HealthEventKind::Retry => self.retries += 1,
+            HealthEventKind::Desync => self.desyncs += 1,
+            HealthEventKind::Stall => self.stalls += 1,
+            HealthEventKind::PinFault => self.pin_faults += 1,
+            HealthEventKind::MissedTransition => self.missed_transitions += 1,
+            HealthEventKind::FirmwareError => {}
+        }
+
+        if kind.is_error() {
+            let minute = (at.as_secs() / 60) as usize;
+            if self.errors_per_minute.len() <= minute {
+                self.errors_per_minute.resize(minute + 1, 0);
+            }
+            self.errors_per_minute[minute] += 1;
+        }
+
+        if let Some(last) = self.events.back_mut() {
+            if last.kind == kind && last.message == message {
+                last.at = at;
+                last.repeats += 1;
+                return;
+            }
+        }
+
+        if self.events.len() == HEALTH_HISTORY_LEN {
+            self.events.pop_front();
+        }
+        self.events.push_back(HealthEvent {
+            at,
+            kind,
+            message,
+            repeats: 1,
+        });
+    }
+
+    /// Returns true once after a command error, when the firmware's last error should be fetched.
+    pub fn take_firmware_query(&mut self) -> bool {
+        std::mem::take(&mut self.firmware_query_pending)
+    }
+
+    /// Store the firmware's last error message, recording an event if it changed.
+    pub fn set_firmware_error(&mut self, message: &str) {
+        let message = message.trim();
+        if message.is_empty() || self.last_firmware_error.as_deref() == Some(message) {
+            return;
+        }
+        self.last_firmware_error = Some(message.to_string());
+        self.record(HealthEventKind::FirmwareError, message);
+    }
+
+    /// Track a successful status poll. Execution that stops advancing the cycle count is reported
+    /// once as a stall, and entering the error state is recorded, as are state transitions that
+    /// happened between polls.
+    pub fn update_status(&mut self, status: &ServerStatus, last_state: Option<ProgramState>) {
+        if status.state == ProgramState::Error && last_state != Some(ProgramState::Error) {
+            self.record(HealthEventKind::CommandError, "Server entered the error state");
+        }
+
+        if status.state == ProgramState::Execute && self.last_cycle_ct == Some(status.cycle_ct) {
+            self.stalled_polls += 1;
+            if self.stalled_polls == STALL_POLL_COUNT {
+                self.record(
+                    HealthEventKind::Stall,
+                    format!("No cycle progress at cycle {}", status.cycle_ct),
+                );
+            }
+        }
+        else {
+            self.stalled_polls = 0;
+        }
+        self.last_cycle_ct = Some(status.cycle_ct);
+
+        if let Some(last_seq) = self.last_state_seq {
+            let missed = status.state_seq.wrapping_sub(last_seq).saturating_sub(1);
+            if missed > 0 {
+                self.record(
+                    HealthEventKind::MissedTransition,
+                    format!("{} state transition(s) missed before {:?}", missed, status.state),
+                );
+            }
+        }
+        self.last_state_seq = Some(status.state_seq);
+    }
+
+    /// Track the result of reading one of the [MONITORED_PINS].
+    pub fn update_pin(&mut self, pin: CpuPin, level: Option<bool>) {
+        let Some(idx) = MONITORED_PINS.iter().position(|p| *p == pin)
+        else {
+            return;
+        };
+
+        let stats = &mut self.pins[idx];
+        stats.reads += 1;
+        match level {
+            Some(level) => {
+                if stats.level.is_some_and(|last| last != level) {
+                    stats.transitions += 1;
+                }
+                stats.level = Some(level);
+            }
+            None => {
+                stats.failures += 1;
+                stats.level = None;
+                self.record(HealthEventKind::PinFault, format!("Failed to read {:?} pin", pin));
+            }
+        }
+    }
+
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &HealthEvent> {
+        self.events.iter()
+    }
+
+    pub fn pins(&self) -> impl Iterator<Item = (CpuPin, &PinHealth)> {
+        MONITORED_PINS.iter().copied().zip(self.pins.iter())
+    }
+
+    pub fn errors_per_minute(&self) -> &[u32] {
+        &self.errors_per_minute
+    }
+
+    pub fn last_firmware_error(&self) -> Option<&str> {
+        self.last_firmware_error.as_deref()
+    }
+
+    pub fn command_errors(&self) -> u32 {
+        self.command_errors
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    pub fn desyncs(&self) -> u32 {
+        self.desyncs
+    }
+
+    pub fn stalls(&self) -> u32 {
+        self.stalls
+    }
+
+    pub fn missed_transitions(&self) -> u32 {
+        self.missed_transitions
+    }
+
+    pub fn pin_faults(&self) -> u32 {
+        self.pin_faults
+    }
+
+    /// True if nothing worse than a retry has been seen this session.
+    pub fn is_healthy(&self) -> bool {
+        self.command_errors + self.desyncs + self.stalls + self.pin_faults == 0
+    }
+}
@@ -25,6 +25,7 @@
 mod app;
 mod assembler;
 mod async_exec;
+mod breakpoints;
 mod character_encoding;
 mod client;
 mod config;
@@ -32,7 +33,12 @@ mod controls;
 mod enums;
 mod events;
 mod file_dialogs;
+mod flight_recorder;
+mod health;
+mod patch;
+mod project;
 mod range_check;
+mod register_fuzzer;
 mod register_state;
 mod resource_manager;
 mod scheduler;
@@ -0,0 +1,167 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Byte patches made to mounted programs between runs.
+//!
+//! A [Patch] overwrites bytes of the program blob that covers CS:IP+offset, so a change such as a
+//! different displacement can be tried without editing and reassembling the source. The
+//! [PatchHistory] keeps every patch of the session, and can be exported as an assembly listing
+//! that shows the bytes each patch replaced.
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{enums::MountAddress, resource_manager::ResourceManager, structs::BinaryBlob};
+
+#[derive(Clone, Debug)]
+pub struct Patch {
+    /// Name of the patched blob.
+    pub blob: String,
+    /// Offset of the patch into the blob.
+    pub offset: usize,
+    /// Offset of the patch from CS:IP.
+    pub ip_offset: u32,
+    /// Physical address of the patch.
+    pub address: u32,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl Patch {
+    /// Find the blob that covers `bytes` written at `code_address + ip_offset`, and return the
+    /// patch that would write them. The blob is not changed.
+    pub fn locate(blobs: &[BinaryBlob], code_address: u32, ip_offset: u32, bytes: &[u8]) -> Result<Patch> {
+        if bytes.is_empty() {
+            bail!("No bytes to patch.");
+        }
+        let address = code_address.wrapping_add(ip_offset);
+        for blob in blobs {
+            let mount_address = match blob.mount_address {
+                MountAddress::FlatAddress(addr) => addr,
+                MountAddress::CsIp => code_address,
+            };
+            let Some(offset) = address.checked_sub(mount_address).map(|o| o as usize)
+            else {
+                continue;
+            };
+            if offset + bytes.len() > blob.data.len() {
+                continue;
+            }
+            return Ok(Patch {
+                blob: blob.name.clone(),
+                offset,
+                ip_offset,
+                address,
+                old: blob.data[offset..offset + bytes.len()].to_vec(),
+                new: bytes.to_vec(),
+            });
+        }
+        Err(anyhow!(
+            "No loaded program covers {} byte(s) at {:05X}.",
+            bytes.len(),
+            address
+        ))
+    }
+
+    /// Write the new bytes into the blob.
+    pub fn apply(&self, rm: &mut ResourceManager) -> Result<()> {
+        let blob = rm
+            .blob_mut(&self.blob)
+            .ok_or_else(|| anyhow!("Blob '{}' no longer exists.", self.blob))?;
+        blob.data[self.offset..self.offset + self.new.len()].copy_from_slice(&self.new);
+        Ok(())
+    }
+}
+
+/// The patches made during the session, oldest first.
+#[derive(Default)]
+pub struct PatchHistory {
+    patches: Vec<Patch>,
+}
+
+impl PatchHistory {
+    pub fn push(&mut self, patch: Patch) {
+        self.patches.push(patch);
+    }
+
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.patches.clear();
+    }
+
+    /// Format the history as a commented assembly listing. Each patch shows the bytes it replaced
+    /// on `;-` lines and the bytes it wrote on `;+` lines.
+    pub fn to_asm_diff(&self) -> String {
+        let mut text = format!(
+            "; ArduinoX86 patch history: {} patch(es), oldest first.\n",
+            self.patches.len()
+        );
+        for (i, patch) in self.patches.iter().enumerate() {
+            _ = writeln!(
+                text,
+                "\n; Patch {}: {}+{:04X}h (CS:IP+{:04X}h, address {:05X}h)",
+                i + 1,
+                patch.blob,
+                patch.offset,
+                patch.ip_offset,
+                patch.address
+            );
+            _ = writeln!(text, ";-    db {}", db_list(&patch.old));
+            _ = writeln!(text, ";+    db {}", db_list(&patch.new));
+        }
+        text
+    }
+}
+
+fn db_list(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}h", b))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parse a list of hex bytes such as "90 90", "9090" or "0x90,0x90".
+pub fn parse_hex_bytes(text: &str) -> Result<Vec<u8>> {
+    let digits: String = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|token| token.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect();
+    if !digits.is_ascii() || digits.is_empty() || digits.len() % 2 != 0 {
+        bail!("Expected an even number of hex digits.");
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| anyhow!("Bad hex byte '{}'.", &digits[i..i + 2]))
+        })
+        .collect()
+}
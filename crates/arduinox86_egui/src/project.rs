@@ -0,0 +1,172 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Project files save a GUI session so it can be resumed later. A project (`.ax86proj`) is a TOML
+//! file holding the loaded blobs and their mount addresses, the assembly listings open in code
+//! editors, the initial registers and the server option flags. Binary data is stored as hex.
+
+use crate::{
+    enums::{BinaryBlobType, MountAddress},
+    patch::parse_hex_bytes,
+    structs::BinaryBlob,
+};
+use anyhow::{anyhow, bail, Result};
+use arduinox86_client::{RemoteCpuRegisters, ServerFlags};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+pub const PROJECT_EXTENSION: &str = "ax86proj";
+pub const PROJECT_VERSION: u32 = 1;
+
+/// The server flags that are user options, and so are saved with a project. Flags the GUI sets
+/// itself while running, such as automatic execution, are not.
+pub const PROJECT_FLAGS: u32 = ServerFlags::HALT_AFTER_JUMP
+    | ServerFlags::USE_SDRAM_BACKEND
+    | ServerFlags::USE_SMM
+    | ServerFlags::ENABLE_DEBUG
+    | ServerFlags::ENABLE_CYCLE_LOGGING
+    | ServerFlags::ENABLE_ALE_INTERRUPT
+    | ServerFlags::RESOLVE_BUS_STEP;
+
+/// Bytes per line of hex in a project file.
+const HEX_LINE_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectBlob {
+    pub name: String,
+    pub mount_address: MountAddress,
+    pub blob_type: BinaryBlobType,
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectListing {
+    pub name: String,
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    /// The server flags in [PROJECT_FLAGS].
+    #[serde(default)]
+    pub server_flags: u32,
+    /// The initial registers in the server's register file format, as hex.
+    #[serde(default)]
+    pub initial_regs: Option<String>,
+    #[serde(default)]
+    pub blobs: Vec<ProjectBlob>,
+    #[serde(default)]
+    pub listings: Vec<ProjectListing>,
+}
+
+impl Project {
+    pub fn new(server_flags: u32) -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            server_flags: server_flags & PROJECT_FLAGS,
+            initial_regs: None,
+            blobs: Vec::new(),
+            listings: Vec::new(),
+        }
+    }
+
+    pub fn add_blob(&mut self, blob: &BinaryBlob) {
+        self.blobs.push(ProjectBlob {
+            name: blob.name.clone(),
+            mount_address: blob.mount_address,
+            blob_type: blob.blob_type.clone(),
+            data: to_hex(&blob.data),
+        });
+    }
+
+    pub fn add_listing(&mut self, name: &str, code: &str) {
+        self.listings.push(ProjectListing {
+            name: name.to_string(),
+            code: code.to_string(),
+        });
+    }
+
+    pub fn set_initial_regs(&mut self, regs: &RemoteCpuRegisters) -> Result<()> {
+        let mut reg_buf = Cursor::new(Vec::new());
+        regs.write(&mut reg_buf)?;
+        self.initial_regs = Some(to_hex(reg_buf.get_ref()));
+        Ok(())
+    }
+
+    pub fn initial_regs(&self) -> Result<Option<RemoteCpuRegisters>> {
+        let Some(hex) = &self.initial_regs
+        else {
+            return Ok(None);
+        };
+        let reg_buf = parse_hex_bytes(hex)?;
+        RemoteCpuRegisters::try_from(reg_buf.as_slice())
+            .map(Some)
+            .map_err(|e| anyhow!("Bad initial registers: {}", e))
+    }
+
+    /// Decode the project's blobs.
+    pub fn binary_blobs(&self) -> Result<Vec<BinaryBlob>> {
+        self.blobs
+            .iter()
+            .map(|blob| {
+                let data = if blob.data.trim().is_empty() {
+                    Vec::new()
+                }
+                else {
+                    parse_hex_bytes(&blob.data).map_err(|e| anyhow!("Bad data for blob '{}': {}", blob.name, e))?
+                };
+                Ok(BinaryBlob::new(
+                    blob.name.clone(),
+                    blob.mount_address,
+                    blob.blob_type.clone(),
+                    data,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let project: Project = toml::from_str(text)?;
+        if project.version > PROJECT_VERSION {
+            bail!(
+                "Project version {} is newer than this version of the GUI supports ({}).",
+                project.version,
+                PROJECT_VERSION
+            );
+        }
+        Ok(project)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .chunks(HEX_LINE_LEN)
+        .map(|line| line.iter().map(|b| format!("{:02X}", b)).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
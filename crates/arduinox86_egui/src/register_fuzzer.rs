@@ -0,0 +1,76 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! One-click register randomization for interactive bring-up.
+//!
+//! Uses the same register fuzzer as the test generator, with the generator's default weights, so
+//! registers tried by hand look like the ones in generated tests.
+
+use arduinox86_client::{registers_common::RandomizeOpts, Registers32, RemoteCpuRegisters, RemoteCpuRegistersV3};
+use rand::{rngs::StdRng, SeedableRng};
+use rand_distr::Beta;
+
+pub struct RegisterFuzzer {
+    rng:  StdRng,
+    beta: Beta<f64>,
+    opts: RandomizeOpts,
+}
+
+impl Default for RegisterFuzzer {
+    fn default() -> Self {
+        Self {
+            rng:  StdRng::from_os_rng(),
+            beta: Beta::new(0.65, 0.65).expect("Couldn't create beta function for register randomization"),
+            opts: RandomizeOpts {
+                weight_zero: 0.05,
+                weight_ones: 0.05,
+                weight_sp_odd: 0.1,
+                sp_min_value: 0x0008,
+                sp_max_value: 0xFFFF,
+                sp_use_ss_limit: true,
+                randomize_flags: true,
+                clear_trap_flag: true,
+                clear_interrupt_flag: true,
+                clear_resume_flag: true,
+                randomize_general: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl RegisterFuzzer {
+    /// Return a randomized copy of `regs`. CS and EIP are kept, so the mounted program still runs.
+    pub fn randomize(&mut self, regs: &RemoteCpuRegistersV3) -> RemoteCpuRegistersV3 {
+        let mut new_regs = RemoteCpuRegisters::V3(regs.clone());
+        new_regs.randomize(&self.opts, &mut self.rng, &mut self.beta, &[]);
+
+        let RemoteCpuRegisters::V3(mut new_regs) = new_regs
+        else {
+            unreachable!("Randomizing keeps the register set type");
+        };
+        new_regs.set_cs(regs.cs());
+        new_regs.set_eip(regs.eip());
+        new_regs
+    }
+}
@@ -75,4 +75,8 @@ impl ResourceManager {
     pub fn blobs(&self) -> &[BinaryBlob] {
         &self.blobs
     }
+
+    pub fn clear(&mut self) {
+        self.blobs.clear();
+    }
 }
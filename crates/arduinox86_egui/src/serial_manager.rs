@@ -20,29 +20,41 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
+use std::collections::HashMap;
+
+use arduinox86_client::PortFilter;
 use serialport::{SerialPortInfo, SerialPortType};
 
 #[derive(Default)]
 pub struct SerialManager {
-    ports: Vec<SerialPortInfo>,
+    ports:  Vec<SerialPortInfo>,
+    filter: PortFilter,
+    // Device labels from the devices file, by port name.
+    labels: HashMap<String, String>,
 }
 
 impl SerialManager {
-    pub fn new() -> Self {
+    pub fn new(filter: PortFilter, labels: HashMap<String, String>) -> Self {
         SerialManager {
-            ports: Self::enumerate_and_filter_ports(),
+            ports: Self::enumerate_and_filter_ports(&filter),
+            filter,
+            labels,
         }
     }
 
     pub fn refresh(&mut self) {
-        self.ports = Self::enumerate_and_filter_ports();
+        self.ports = Self::enumerate_and_filter_ports(&self.filter);
     }
 
-    fn enumerate_and_filter_ports() -> Vec<SerialPortInfo> {
+    fn enumerate_and_filter_ports(filter: &PortFilter) -> Vec<SerialPortInfo> {
         serialport::available_ports()
             .unwrap_or_else(|_| vec![])
             .into_iter()
             .filter_map(|p| {
+                if !filter.matches(&p) {
+                    return None;
+                }
+
                 // Filter port names containing "USB-to-Serial"
                 if let SerialPortType::UsbPort(info) = &p.port_type {
                     if let Some(product) = &info.product {
@@ -60,27 +72,37 @@ impl SerialManager {
     pub fn port_display_names(&self) -> Vec<String> {
         self.ports
             .iter()
-            .map(|port| match &port.port_type {
-                SerialPortType::UsbPort(info) => {
-                    format!(
-                        "{} ({})",
-                        port.port_name,
-                        info.product.as_deref().unwrap_or("Unknown USB Device")
-                    )
-                }
-                SerialPortType::BluetoothPort => {
-                    format!("{} (Bluetooth)", port.port_name)
-                }
-                SerialPortType::PciPort => {
-                    format!("{} (PCI)", port.port_name)
-                }
-                SerialPortType::Unknown => {
-                    format!("{} (Unknown)", port.port_name)
+            .map(|port| {
+                let name = Self::port_description(port);
+                match self.labels.get(&port.port_name) {
+                    Some(label) => format!("{}: {}", label, name),
+                    None => name,
                 }
             })
             .collect()
     }
 
+    fn port_description(port: &SerialPortInfo) -> String {
+        match &port.port_type {
+            SerialPortType::UsbPort(info) => {
+                format!(
+                    "{} ({})",
+                    port.port_name,
+                    info.product.as_deref().unwrap_or("Unknown USB Device")
+                )
+            }
+            SerialPortType::BluetoothPort => {
+                format!("{} (Bluetooth)", port.port_name)
+            }
+            SerialPortType::PciPort => {
+                format!("{} (PCI)", port.port_name)
+            }
+            SerialPortType::Unknown => {
+                format!("{} (Unknown)", port.port_name)
+            }
+        }
+    }
+
     pub fn port_names(&self) -> Vec<String> {
         self.ports.iter().map(|port| port.port_name.clone()).collect()
     }
@@ -198,6 +198,20 @@ impl WindowManager {
         })
     }
 
+    /// Iterate over the open code editors.
+    pub fn code_editors(&self) -> impl Iterator<Item = &CodeEditor> {
+        self.code_windows.values().filter_map(|w| match w {
+            Window::CodeEditor { window, .. } => Some(window),
+            _ => None,
+        })
+    }
+
+    /// Close every binary view and code editor.
+    pub fn clear(&mut self) {
+        self.blob_windows.clear();
+        self.code_windows.clear();
+    }
+
     pub fn code_window(&self, name: &str) -> Option<&CodeEditor> {
         self.code_windows.get(name).map(|w| {
             if let Window::CodeEditor { window, .. } = w {
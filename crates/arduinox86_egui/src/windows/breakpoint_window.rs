@@ -0,0 +1,135 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    breakpoints::{Breakpoint, BreakpointKind, BreakpointSet},
+    TEXT_COLOR,
+};
+use egui::{Color32, TextStyle};
+
+pub struct BreakpointWindow {
+    open: bool,
+    kind: BreakpointKind,
+    address_string: String,
+}
+
+impl Default for BreakpointWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            kind: BreakpointKind::CodeFetch,
+            address_string: String::new(),
+        }
+    }
+}
+
+impl BreakpointWindow {
+    pub fn open(&self) -> &bool {
+        &self.open
+    }
+
+    pub fn open_mut(&mut self) -> &mut bool {
+        &mut self.open
+    }
+
+    /// Show the window. Breakpoints are checked while stepping to a breakpoint from the client
+    /// window, not while the CPU runs autonomously.
+    pub fn show(&mut self, e_ctx: &egui::Context, breakpoints: &mut BreakpointSet) {
+        egui::Window::new("Breakpoints")
+            .open(&mut self.open)
+            .default_width(400.0)
+            .default_height(300.0)
+            .show(e_ctx, |ui| {
+                let address = u32::from_str_radix(&self.address_string, 16).ok();
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("breakpoint_kind")
+                        .selected_text(self.kind.to_string())
+                        .show_ui(ui, |ui| {
+                            for kind in BreakpointKind::ALL {
+                                ui.selectable_value(&mut self.kind, kind, kind.to_string());
+                            }
+                        });
+                    ui.label("Address:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.address_string)
+                            .font(TextStyle::Monospace)
+                            .desired_width(80.0)
+                            .hint_text("FFFF0")
+                            .text_color(if address.is_some() { TEXT_COLOR } else { Color32::RED }),
+                    );
+                    if ui
+                        .add_enabled(address.is_some(), egui::Button::new("Add"))
+                        .on_disabled_hover_text("Enter a hex address, or a port for I/O")
+                        .clicked()
+                    {
+                        if let Some(address) = address {
+                            breakpoints.add(Breakpoint::new(self.kind, address));
+                        }
+                    }
+                    if ui
+                        .add_enabled(!breakpoints.is_empty(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        breakpoints.clear();
+                    }
+                });
+
+                ui.separator();
+                let last_hit = breakpoints.last_hit();
+                let hit_color = ui.visuals().warn_fg_color;
+                let mut remove = None;
+                egui::Grid::new("breakpoint_list").striped(true).show(ui, |ui| {
+                    ui.strong("On");
+                    ui.strong("Kind");
+                    ui.strong("Address");
+                    ui.strong("Hits");
+                    ui.end_row();
+                    for (i, breakpoint) in breakpoints.breakpoints_mut().iter_mut().enumerate() {
+                        ui.checkbox(&mut breakpoint.enabled, "");
+                        ui.label(breakpoint.kind.to_string());
+                        let address_str = format!("{:05X}", breakpoint.address);
+                        if last_hit == Some(i) {
+                            ui.colored_label(hit_color, egui::RichText::new(address_str).monospace())
+                                .on_hover_text("Hit last");
+                        }
+                        else {
+                            ui.monospace(address_str);
+                        }
+                        ui.label(breakpoint.hits.to_string());
+                        if ui
+                            .button(egui::RichText::new(egui_phosphor::regular::X.to_string()))
+                            .on_hover_text("Remove breakpoint")
+                            .clicked()
+                        {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = remove {
+                    breakpoints.remove(i);
+                }
+            });
+    }
+}
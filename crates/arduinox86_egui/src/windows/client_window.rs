@@ -20,7 +20,7 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
-use std::time::Instant;
+use std::{collections::BTreeMap, time::Instant};
 
 use crate::{
     client::ClientContext,
@@ -28,7 +28,15 @@ use crate::{
     events::{GuiEvent, GuiEventQueue},
 };
 use anyhow::{anyhow, Result};
-use arduinox86_client::{CpuPin, ProgramState, ServerFlags, ServerStatus};
+use arduinox86_client::{
+    CpuPin,
+    ProgramState,
+    RemoteCpuRegisters,
+    ServerCycleState,
+    ServerFlags,
+    ServerStatus,
+    TimelineState,
+};
 use egui_notify::Toasts;
 
 pub struct ClientWindow {
@@ -90,6 +98,8 @@ impl ClientWindow {
         self.debug_enabled = flags & ServerFlags::ENABLE_DEBUG != 0;
         self.use_smm = flags & ServerFlags::USE_SMM != 0;
         self.halt_after_jump = flags & ServerFlags::HALT_AFTER_JUMP != 0;
+        self.ale_interrupt_enabled = flags & ServerFlags::ENABLE_ALE_INTERRUPT != 0;
+        self.resolve_bus_stepping = flags & ServerFlags::RESOLVE_BUS_STEP != 0;
     }
 
     pub fn set_server_status(&mut self, c_ctx: &mut ClientContext, server_status: ServerStatus) {
@@ -145,8 +155,41 @@ impl ClientWindow {
         self.cycle_table.clear();
     }
 
+    pub fn set_registers(&mut self, initial: Option<RemoteCpuRegisters>, final_: Option<RemoteCpuRegisters>) {
+        self.cycle_table.set_registers(initial, final_);
+    }
+
+    pub fn timeline_state(&self, cycle: usize) -> Option<TimelineState> {
+        self.cycle_table.timeline_state(cycle)
+    }
+
+    /// Return the captured cycle log as text.
+    pub fn cycle_log(&self) -> String {
+        self.cycle_table.cycle_log()
+    }
+
+    /// Return the cycles captured during the last run.
+    pub fn cycles(&self) -> &[ServerCycleState] {
+        self.cycle_table.cycles()
+    }
+
+    /// Return the bookmarked cycles of the captured cycle log and their notes.
+    pub fn bookmarks(&self) -> &BTreeMap<usize, String> {
+        self.cycle_table.bookmarks()
+    }
+
+    /// Return the initial and final registers of the last run.
+    pub fn registers(&self) -> (Option<&RemoteCpuRegisters>, Option<&RemoteCpuRegisters>) {
+        self.cycle_table.registers()
+    }
+
     pub fn push_cycle(&mut self, c_ctx: &mut ClientContext, step: bool) -> Result<()> {
-        let cycle = c_ctx.client.get_cycle_state(step).map_err(|e| {
+        // Stepped cycles go through the client context, so breakpoints see the bus they drive.
+        let cycle = match step {
+            true => c_ctx.step_cycle().map(|(cycle, _)| cycle),
+            false => c_ctx.client.get_cycle_state(false).map_err(Into::into),
+        }
+        .map_err(|e| {
             let err_str = format!("Failed to get cycle state: {}", e);
             log::error!("{}", err_str);
             anyhow!(err_str)
@@ -157,6 +200,18 @@ impl ClientWindow {
         Ok(())
     }
 
+    /// Append cycles stepped elsewhere, such as while running to a breakpoint.
+    pub fn push_cycles(&mut self, cycles: Vec<ServerCycleState>) {
+        for cycle in cycles {
+            self.cycle_table.push_cycle(cycle);
+        }
+    }
+
+    /// Mark `cycle` in the cycle log and scroll to it.
+    pub fn highlight_cycle(&mut self, cycle: usize, events: &mut GuiEventQueue) {
+        self.cycle_table.highlight(cycle, events);
+    }
+
     pub fn show(
         &mut self,
         e_ctx: &egui::Context,
@@ -330,6 +385,30 @@ impl ClientWindow {
                                 }
                             }
                         });
+                        ui.menu_button("Debug", |ui| {
+                            if ui
+                                .button("Export Flight Record...")
+                                .on_hover_text("Save recent commands, states, cycles and registers for a bug report")
+                                .clicked()
+                            {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Zip Files", &["zip"])
+                                    .set_file_name("flight_record.zip")
+                                    .save_file()
+                                {
+                                    events.push(GuiEvent::ExportFlightRecord { path });
+                                }
+                            }
+                            if ui
+                                .button("Export Experiment Bundle...")
+                                .on_hover_text("Save the program, registers, trace and environment of the last run")
+                                .clicked()
+                            {
+                                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                    events.push(GuiEvent::ExportExperimentBundle { path });
+                                }
+                            }
+                        });
                     });
 
                     ui.horizontal(|ui| {
@@ -371,6 +450,17 @@ impl ClientWindow {
                             _ = self.push_cycle(c_ctx, true);
                         }
 
+                        if ui
+                            .button(
+                                egui::RichText::new(format!("{}", egui_phosphor::fill::FAST_FORWARD))
+                                    .size(self.icon_size),
+                            )
+                            .on_hover_text("Step until a breakpoint hits")
+                            .clicked()
+                        {
+                            events.push(GuiEvent::AutoStep { enabled: true });
+                        }
+
                         if ui
                             .button(egui::RichText::new(format!("{}", egui_phosphor::fill::PLAY)).size(self.icon_size))
                             .on_hover_text("Run Autonomously")
@@ -378,7 +468,7 @@ impl ClientWindow {
                         {
                             self.reset_state();
                             events.push(GuiEvent::ResetState);
-                            events.push(GuiEvent::RunProgram);
+                            events.push(GuiEvent::RunProgram { upload_blobs: true });
                         }
 
                         if ui
@@ -389,7 +479,7 @@ impl ClientWindow {
                             .on_hover_text("Pause")
                             .clicked()
                         {
-                            // Do pause
+                            events.push(GuiEvent::AutoStep { enabled: false });
                         }
 
                         if ui
@@ -423,11 +513,14 @@ impl ClientWindow {
                             if let Ok(pin) = c_ctx.client.read_pin(CpuPin::READY) {
                                 if pin {
                                     log::debug!("READY pin is high, setting low");
-                                    c_ctx.client.write_pin(CpuPin::READY, false).ok();
                                 }
                                 else {
                                     log::debug!("READY pin is low, setting high");
-                                    c_ctx.client.write_pin(CpuPin::READY, true).ok();
+                                }
+                                if let Err(e) = c_ctx.client.write_pin(CpuPin::READY, !pin) {
+                                    let pin_str = format!("Failed to toggle READY: {}", e);
+                                    log::error!("{}", pin_str);
+                                    toasts.error(pin_str);
                                 }
                             }
                             else {
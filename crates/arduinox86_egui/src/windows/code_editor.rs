@@ -20,8 +20,13 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
-use crate::events::{GuiEvent, GuiEventQueue};
-use egui::TextBuffer;
+use std::collections::BTreeMap;
+
+use crate::{
+    assembler::{AssemblerMessage, Severity},
+    events::{GuiEvent, GuiEventQueue},
+};
+use egui::{Color32, TextBuffer};
 use egui_extras::syntax_highlighting::SyntectSettings;
 
 pub struct CodeEditor {
@@ -30,8 +35,12 @@ pub struct CodeEditor {
     language: String,
     code: String,
     open: bool,
+    editor_id: egui::Id,
 
     assembler_output: String,
+    // Errors and warnings from the last assembly, shown against their lines.
+    messages: Vec<AssemblerMessage>,
+    goto_line: Option<usize>,
 }
 
 impl CodeEditor {
@@ -42,7 +51,10 @@ impl CodeEditor {
             language: "asm".to_string(),
             code: "hlt\n".to_string(),
             open: false,
+            editor_id: egui::Id::new("code_editor").with(program_name),
             assembler_output: "".to_string(),
+            messages: Vec::new(),
+            goto_line: None,
         }
     }
 
@@ -62,6 +74,11 @@ impl CodeEditor {
         self.assembler_output = output.to_string();
     }
 
+    /// Set the errors and warnings to annotate the code with.
+    pub fn set_messages(&mut self, messages: &[AssemblerMessage]) {
+        self.messages = messages.to_vec();
+    }
+
     pub fn code(&self) -> &str {
         &self.code
     }
@@ -98,6 +115,23 @@ impl CodeEditor {
 
         let theme = egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx(), ui.style());
 
+        // Lines with an error are underlined in the error color, lines with only warnings in the
+        // warning color.
+        let mut marked_lines: BTreeMap<usize, Color32> = BTreeMap::new();
+        for message in &self.messages {
+            if let Some(line) = message.line {
+                let color = Self::severity_color(ui, message.severity);
+                marked_lines
+                    .entry(line)
+                    .and_modify(|c| {
+                        if message.severity == Severity::Error {
+                            *c = color;
+                        }
+                    })
+                    .or_insert(color);
+            }
+        }
+
         let mut layouter = |ui: &egui::Ui, buf: &dyn TextBuffer, wrap_width: f32| {
             let mut layout_job = egui_extras::syntax_highlighting::highlight_with(
                 ui.ctx(),
@@ -108,6 +142,17 @@ impl CodeEditor {
                 syntect_settings,
             );
             layout_job.wrap.max_width = wrap_width;
+            if !marked_lines.is_empty() {
+                let line_starts: Vec<usize> = std::iter::once(0)
+                    .chain(buf.as_str().match_indices('\n').map(|(i, _)| i + 1))
+                    .collect();
+                for section in &mut layout_job.sections {
+                    let line = line_starts.partition_point(|start| *start <= section.byte_range.start);
+                    if let Some(color) = marked_lines.get(&line) {
+                        section.format.underline = egui::Stroke::new(1.5, *color);
+                    }
+                }
+            }
             ui.fonts(|f| f.layout_job(layout_job))
         };
 
@@ -116,19 +161,26 @@ impl CodeEditor {
             max_scroll_height -= 200.0; // Reserve space for assembler output
         }
 
-        egui::ScrollArea::vertical()
-            .max_height(max_scroll_height)
-            .show(ui, |ui| {
-                ui.add(
-                    egui::TextEdit::multiline(&mut self.code)
-                        .font(egui::TextStyle::Monospace) // for cursor height
-                        .code_editor()
-                        .desired_rows(40)
-                        .lock_focus(true)
-                        .desired_width(f32::INFINITY)
-                        .layouter(&mut layouter),
-                );
-            });
+        let mut scroll_area = egui::ScrollArea::vertical().max_height(max_scroll_height);
+        if let Some(line) = self.goto_line.take() {
+            self.move_cursor_to_line(ui.ctx(), line);
+            // Leave a few lines of context above the target line.
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            scroll_area = scroll_area.vertical_scroll_offset(line.saturating_sub(4) as f32 * row_height);
+        }
+
+        scroll_area.show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.code)
+                    .id(self.editor_id)
+                    .font(egui::TextStyle::Monospace) // for cursor height
+                    .code_editor()
+                    .desired_rows(40)
+                    .lock_focus(true)
+                    .desired_width(f32::INFINITY)
+                    .layouter(&mut layouter),
+            );
+        });
 
         if !self.assembler_output.is_empty() {
             ui.separator();
@@ -145,12 +197,35 @@ impl CodeEditor {
                         .clicked()
                     {
                         self.assembler_output = "".to_string();
+                        self.messages.clear();
                     }
                 });
             });
 
             ui.separator();
 
+            if !self.messages.is_empty() {
+                egui::ScrollArea::vertical()
+                    .id_salt("messages")
+                    .max_height(80.0)
+                    .show(ui, |ui| {
+                        for message in &self.messages {
+                            let color = Self::severity_color(ui, message.severity);
+                            let text = match message.line {
+                                Some(line) => format!("Line {}: {}", line, message.message),
+                                None => message.message.clone(),
+                            };
+                            let response = ui.selectable_label(false, egui::RichText::new(text).color(color));
+                            if let Some(line) = message.line {
+                                if response.on_hover_text("Go to line").clicked() {
+                                    self.goto_line = Some(line);
+                                }
+                            }
+                        }
+                    });
+                ui.separator();
+            }
+
             egui::ScrollArea::vertical()
                 .id_salt("error")
                 .max_height(200.0)
@@ -166,4 +241,27 @@ impl CodeEditor {
                 });
         }
     }
+
+    fn severity_color(ui: &egui::Ui, severity: Severity) -> Color32 {
+        match severity {
+            Severity::Error => ui.visuals().error_fg_color,
+            Severity::Warning => ui.visuals().warn_fg_color,
+        }
+    }
+
+    /// Put the editor's cursor at the start of a 1-based line and focus it.
+    fn move_cursor_to_line(&self, ctx: &egui::Context, line: usize) {
+        let index: usize = self
+            .code
+            .split('\n')
+            .take(line.saturating_sub(1))
+            .map(|l| l.chars().count() + 1)
+            .sum();
+        if let Some(mut state) = egui::TextEdit::load_state(ctx, self.editor_id) {
+            let cursor = egui::text::CCursor::new(index);
+            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+            state.store(ctx, self.editor_id);
+        }
+        ctx.memory_mut(|m| m.request_focus(self.editor_id));
+    }
 }
@@ -0,0 +1,325 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+use std::collections::BTreeMap;
+
+use crate::events::{GuiEvent, GuiEventQueue};
+use arduinox86_client::{
+    get_queue_op,
+    BusState,
+    QueueOp,
+    ServerCpuType,
+    ServerCycleState,
+    ServerCycleStatePrinter,
+    TState,
+};
+use egui_extras::{Column, TableBuilder};
+
+const BUS_STATES: [BusState; 8] = [
+    BusState::INTA,
+    BusState::IOR,
+    BusState::IOW,
+    BusState::HALT,
+    BusState::CODE,
+    BusState::MEMR,
+    BusState::MEMW,
+    BusState::PASV,
+];
+
+const COLUMNS: [&str; 7] = [
+    "Cycle",
+    "T-State",
+    "Bus Status",
+    "Address",
+    "Data",
+    "Queue Op",
+    "Comment",
+];
+
+/// One decoded cycle of the trace, formatted once when the cycles are set.
+struct TraceRow {
+    t_state: String,
+    idle: bool,
+    ale: bool,
+    bus_state: BusState,
+    address: String,
+    data: String,
+    queue_op: &'static str,
+    comment: &'static str,
+}
+
+/// A table of the cycles captured during the last run, as fetched by the client window with
+/// `get_cycle_states()`, with filters and CSV export.
+pub struct CycleTraceWindow {
+    open: bool,
+    rows: Vec<TraceRow>,
+    filter: String,
+    // Indexed by the BusState discriminant.
+    show_bus_states: [bool; 8],
+    hide_idle: bool,
+}
+
+impl Default for CycleTraceWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            rows: Vec::new(),
+            filter: String::new(),
+            show_bus_states: [true; 8],
+            hide_idle: false,
+        }
+    }
+}
+
+impl CycleTraceWindow {
+    pub fn open(&self) -> &bool {
+        &self.open
+    }
+
+    pub fn open_mut(&mut self) -> &mut bool {
+        &mut self.open
+    }
+
+    /// Decode `cycles` into the rows of the table.
+    pub fn set_cycles(&mut self, arch: ServerCpuType, cycles: &[ServerCycleState]) {
+        let bus_chr_width = arch.bus_chr_width();
+        let mut address_latch = 0;
+        // The bus state is only valid up to T1 on the older CPUs, so transfers are named after the
+        // state latched with the address.
+        let mut latched_state = BusState::PASV;
+
+        self.rows = cycles
+            .iter()
+            .map(|cycle| {
+                let bus_state = arch.decode_status(cycle.cpu_status_bits);
+                if cycle.ale() {
+                    address_latch = cycle.address_bus;
+                    latched_state = bus_state;
+                }
+
+                let transferring = cycle.is_transferring(arch);
+                let data = match transferring {
+                    true => ServerCycleStatePrinter {
+                        cpu_type: arch,
+                        address_latch,
+                        state: cycle.clone(),
+                    }
+                    .data_bus_str()
+                    .trim()
+                    .to_string(),
+                    false => String::new(),
+                };
+
+                // Only the CPUs with segment status report the queue on the same status lines.
+                let queue_op = match arch.has_segment_status() {
+                    true => match get_queue_op!(cycle.cpu_status_bits) {
+                        QueueOp::Idle => "",
+                        QueueOp::First => "F",
+                        QueueOp::Flush => "E",
+                        QueueOp::Subsequent => "S",
+                    },
+                    false => "",
+                };
+
+                let comment = match (transferring, latched_state) {
+                    (true, BusState::CODE) => "Code fetch",
+                    (true, BusState::MEMR) => "Memory read",
+                    (true, BusState::MEMW) => "Memory write",
+                    (true, BusState::IOR) => "I/O read",
+                    (true, BusState::IOW) => "I/O write",
+                    (true, BusState::INTA) => "Interrupt acknowledge",
+                    (_, _) if bus_state == BusState::HALT => "Halt",
+                    _ => "",
+                };
+
+                let t_state = cycle.t_state();
+                TraceRow {
+                    t_state: arch.tstate_to_string(t_state),
+                    idle: t_state == TState::Ti,
+                    ale: cycle.ale(),
+                    bus_state,
+                    address: format!("{:0width$X}", address_latch, width = bus_chr_width),
+                    data,
+                    queue_op,
+                    comment,
+                }
+            })
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.clear();
+    }
+
+    /// Return the indices of the rows that pass the current filters.
+    fn visible_rows(&self, bookmarks: &BTreeMap<usize, String>) -> Vec<usize> {
+        let filter = self.filter.to_ascii_lowercase();
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|(i, row)| {
+                if !self.show_bus_states[row.bus_state as usize] || (self.hide_idle && row.idle) {
+                    return false;
+                }
+                filter.is_empty()
+                    || row.address.to_ascii_lowercase().contains(&filter)
+                    || row.data.to_ascii_lowercase().contains(&filter)
+                    || row.comment.to_ascii_lowercase().contains(&filter)
+                    || bookmarks
+                        .get(i)
+                        .is_some_and(|note| note.to_ascii_lowercase().contains(&filter))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Return the rows that pass the current filters as CSV, with bookmark notes in the comment
+    /// column.
+    pub fn to_csv(&self, bookmarks: &BTreeMap<usize, String>) -> String {
+        let mut csv = String::from("Cycle,T-State,ALE,Bus Status,Address,Data,Queue Op,Comment\n");
+        for i in self.visible_rows(bookmarks) {
+            let row = &self.rows[i];
+            let comment = Self::comment(row, bookmarks.get(&i));
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},\"{}\"\n",
+                i,
+                row.t_state,
+                if row.ale { "A" } else { "" },
+                row.bus_state.to_string().trim(),
+                row.address,
+                row.data,
+                row.queue_op,
+                comment.replace('"', "\"\"")
+            ));
+        }
+        csv
+    }
+
+    fn comment(row: &TraceRow, note: Option<&String>) -> String {
+        match note {
+            Some(note) if row.comment.is_empty() => note.clone(),
+            Some(note) => format!("{}; {}", row.comment, note),
+            None => row.comment.to_string(),
+        }
+    }
+
+    pub fn show(&mut self, e_ctx: &egui::Context, bookmarks: &BTreeMap<usize, String>, events: &mut GuiEventQueue) {
+        // The window contents need all of self, so the open flag is borrowed from a copy.
+        let mut open = self.open;
+        egui::Window::new("Cycle Trace")
+            .open(&mut open)
+            .default_width(640.0)
+            .default_height(480.0)
+            .show(e_ctx, |ui| self.show_contents(ui, bookmarks, events));
+        self.open = open;
+    }
+
+    fn show_contents(&mut self, ui: &mut egui::Ui, bookmarks: &BTreeMap<usize, String>, events: &mut GuiEventQueue) {
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Address, data or comment"));
+            ui.checkbox(&mut self.hide_idle, "Hide Ti");
+            if ui
+                .add_enabled(!self.rows.is_empty(), egui::Button::new("Export CSV..."))
+                .on_hover_text("Save the cycles that pass the filters")
+                .clicked()
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV Files", &["csv"])
+                    .set_file_name("cycles.csv")
+                    .save_file()
+                {
+                    events.push(GuiEvent::ExportCycleTrace { path });
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            for (state, show) in BUS_STATES.iter().zip(self.show_bus_states.iter_mut()) {
+                ui.checkbox(show, state.to_string().trim());
+            }
+        });
+        ui.separator();
+
+        if self.rows.is_empty() {
+            ui.label("No cycles available");
+            return;
+        }
+
+        let visible = self.visible_rows(bookmarks);
+        ui.label(format!("Showing {} of {} cycles", visible.len(), self.rows.len()));
+
+        let text_height = egui::TextStyle::Body
+            .resolve(ui.style())
+            .size
+            .max(ui.spacing().interact_size.y);
+        let available_height = ui.available_height();
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto().at_least(48.0))
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::remainder())
+            .min_scrolled_height(0.0)
+            .max_scroll_height(available_height)
+            .header(20.0, |mut header| {
+                for title in COLUMNS {
+                    header.col(|ui| {
+                        ui.strong(title);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(text_height, visible.len(), |mut table_row| {
+                    let i = visible[table_row.index()];
+                    let row = &self.rows[i];
+                    table_row.col(|ui| {
+                        ui.monospace(i.to_string());
+                    });
+                    table_row.col(|ui| {
+                        ui.monospace(&row.t_state);
+                    });
+                    table_row.col(|ui| {
+                        let ale_str = if row.ale { "A:" } else { "  " };
+                        ui.monospace(format!("{}{}", ale_str, row.bus_state));
+                    });
+                    table_row.col(|ui| {
+                        ui.monospace(&row.address);
+                    });
+                    table_row.col(|ui| {
+                        ui.monospace(&row.data);
+                    });
+                    table_row.col(|ui| {
+                        ui.monospace(row.queue_op);
+                    });
+                    table_row.col(|ui| {
+                        ui.label(Self::comment(row, bookmarks.get(&i)));
+                    });
+                });
+            });
+    }
+}
@@ -0,0 +1,228 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+use crate::{
+    client::CodeSnapshot,
+    events::{GuiEvent, GuiEventQueue},
+};
+use arduinox86_client::{BusState, ServerCpuType, ServerCycleState};
+use iced_x86::{Decoder, DecoderOptions, Formatter, Instruction, NasmFormatter};
+
+/// Bytes of memory disassembled at a time.
+pub const DISASSEMBLY_BYTES: u32 = 256;
+/// Read a new snapshot once the fetch address comes this close to the end of the current one, so
+/// the instruction being fetched is never cut short.
+const REFETCH_MARGIN: u32 = 16;
+
+struct DisassemblyLine {
+    address: u32,
+    len: u32,
+    bytes: String,
+    text: String,
+}
+
+/// Return the address of the last code fetch in `cycles`, which is where the queue was being
+/// filled from.
+pub fn last_fetch_address(arch: ServerCpuType, cycles: &[ServerCycleState]) -> Option<u32> {
+    cycles
+        .iter()
+        .rev()
+        .find(|cycle| cycle.ale() && arch.decode_status(cycle.cpu_status_bits) == BusState::CODE)
+        .map(|cycle| cycle.address_bus)
+}
+
+/// Disassembles memory from CS:IP, following the code fetches of the cycles stepped so far.
+pub struct DisassemblyWindow {
+    open: bool,
+    follow: bool,
+    snapshot: Option<CodeSnapshot>,
+    lines: Vec<DisassemblyLine>,
+    // Address of a snapshot requested but not yet received, so it is only asked for once.
+    requested: Option<u32>,
+    last_fetch: Option<u32>,
+}
+
+impl Default for DisassemblyWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            follow: true,
+            snapshot: None,
+            lines: Vec::new(),
+            requested: None,
+            last_fetch: None,
+        }
+    }
+}
+
+impl DisassemblyWindow {
+    pub fn open(&self) -> &bool {
+        &self.open
+    }
+
+    pub fn open_mut(&mut self) -> &mut bool {
+        &mut self.open
+    }
+
+    pub fn set_snapshot(&mut self, snapshot: CodeSnapshot) {
+        let mut decoder = Decoder::with_ip(
+            snapshot.bitness,
+            &snapshot.bytes,
+            snapshot.address as u64,
+            DecoderOptions::NONE,
+        );
+        let mut formatter = NasmFormatter::new();
+        formatter.options_mut().set_add_leading_zero_to_hex_numbers(false);
+        let mut instruction = Instruction::default();
+
+        self.lines.clear();
+        while decoder.can_decode() {
+            decoder.decode_out(&mut instruction);
+            let start = (instruction.ip() - snapshot.address as u64) as usize;
+            let bytes = &snapshot.bytes[start..start + instruction.len()];
+            let mut text = String::new();
+            formatter.format(&instruction, &mut text);
+            self.lines.push(DisassemblyLine {
+                address: instruction.ip() as u32,
+                len: instruction.len() as u32,
+                bytes: bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+                text,
+            });
+        }
+
+        self.snapshot = Some(snapshot);
+        self.requested = None;
+    }
+
+    /// Drop the snapshot, so memory is read again before it is shown. Call when the program or
+    /// registers may have changed.
+    pub fn invalidate(&mut self) {
+        self.snapshot = None;
+        self.lines.clear();
+        self.requested = None;
+        self.last_fetch = None;
+    }
+
+    /// Return the address the snapshot should start at. Code can't be disassembled backwards, so
+    /// this is CS:IP while the fetches stay near it, and otherwise the fetch address, which after a
+    /// jump is the start of an instruction.
+    fn wanted_address(&self, code_address: u32, fetch_address: Option<u32>) -> u32 {
+        let Some(fetch) = fetch_address
+        else {
+            return code_address;
+        };
+        if let Some(snapshot) = &self.snapshot {
+            let end = snapshot.address as u64 + snapshot.bytes.len() as u64;
+            if fetch >= snapshot.address && (fetch as u64 + REFETCH_MARGIN as u64) < end {
+                return snapshot.address;
+            }
+        }
+        match fetch.checked_sub(code_address) {
+            Some(offset) if offset + REFETCH_MARGIN < DISASSEMBLY_BYTES => code_address,
+            _ => fetch,
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        e_ctx: &egui::Context,
+        code_address: u32,
+        fetch_address: Option<u32>,
+        events: &mut GuiEventQueue,
+    ) {
+        if self.follow {
+            let wanted = self.wanted_address(code_address, fetch_address);
+            let current = self.snapshot.as_ref().map(|snapshot| snapshot.address);
+            if current != Some(wanted) && self.requested != Some(wanted) {
+                events.push(GuiEvent::ReadDisassembly {
+                    address: wanted,
+                    size:    DISASSEMBLY_BYTES,
+                });
+                self.requested = Some(wanted);
+            }
+        }
+        let scroll_to_fetch = fetch_address != self.last_fetch;
+        self.last_fetch = fetch_address;
+
+        egui::Window::new("Disassembly")
+            .open(&mut self.open)
+            .default_width(480.0)
+            .default_height(400.0)
+            .show(e_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.follow, "Follow CS:IP")
+                        .on_hover_text("Read memory again when the code fetches leave the disassembled range");
+                    if ui
+                        .button(egui::RichText::new(
+                            egui_phosphor::regular::ARROWS_CLOCKWISE.to_string(),
+                        ))
+                        .on_hover_text("Refresh")
+                        .clicked()
+                    {
+                        let address = self.snapshot.as_ref().map_or(code_address, |snapshot| snapshot.address);
+                        events.push(GuiEvent::ReadDisassembly {
+                            address,
+                            size: DISASSEMBLY_BYTES,
+                        });
+                    }
+                    ui.label(format!("CS:IP: {:05X}", code_address));
+                    match fetch_address {
+                        Some(fetch) => ui.label(format!("Fetch: {:05X}", fetch)),
+                        None => ui.label("Fetch: -"),
+                    };
+                });
+                ui.separator();
+
+                if self.lines.is_empty() {
+                    ui.label("No code read");
+                    return;
+                }
+
+                let fetch_color = ui.visuals().selection.bg_fill;
+                let ip_color = ui.visuals().warn_fg_color;
+                egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                    egui::Grid::new("disassembly").striped(true).show(ui, |ui| {
+                        for line in &self.lines {
+                            let fetching = fetch_address
+                                .is_some_and(|fetch| fetch >= line.address && fetch < line.address + line.len);
+
+                            let marker = if line.address == code_address { "CS:IP" } else { "" };
+                            ui.colored_label(ip_color, egui::RichText::new(marker).monospace());
+
+                            let address = egui::RichText::new(format!("{:05X}", line.address)).monospace();
+                            let response = match fetching {
+                                true => ui.label(address.background_color(fetch_color)),
+                                false => ui.label(address),
+                            };
+                            ui.monospace(&line.bytes);
+                            ui.monospace(&line.text);
+                            ui.end_row();
+
+                            if fetching && scroll_to_fetch {
+                                response.scroll_to_me(Some(egui::Align::Center));
+                            }
+                        }
+                    });
+                });
+            });
+    }
+}
@@ -0,0 +1,214 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+use crate::{
+    events::{GuiEvent, GuiEventQueue},
+    health::HealthMonitor,
+};
+use egui::{Color32, Sense, Stroke};
+
+/// Number of session minutes shown in the error history chart.
+const HISTORY_MINUTES: usize = 60;
+const HISTORY_BAR_WIDTH: f32 = 6.0;
+const HISTORY_HEIGHT: f32 = 48.0;
+
+pub struct HealthWindow {
+    open: bool,
+    icon_size: f32,
+}
+
+impl Default for HealthWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            icon_size: 24.0,
+        }
+    }
+}
+
+impl HealthWindow {
+    pub fn open(&self) -> &bool {
+        &self.open
+    }
+
+    pub fn open_mut(&mut self) -> &mut bool {
+        &mut self.open
+    }
+
+    pub fn show(&mut self, e_ctx: &egui::Context, health: &mut HealthMonitor, events: &mut GuiEventQueue) {
+        egui::Window::new("Hardware Health")
+            .open(&mut self.open)
+            .default_width(480.0)
+            .default_height(520.0)
+            .show(e_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(
+                            egui::RichText::new(format!("{}", egui_phosphor::regular::WARNING_CIRCLE))
+                                .size(self.icon_size),
+                        )
+                        .on_hover_text("Query last firmware error")
+                        .clicked()
+                    {
+                        events.push(GuiEvent::QueryLastError);
+                    }
+                    if ui
+                        .button(egui::RichText::new(format!("{}", egui_phosphor::regular::TRASH)).size(self.icon_size))
+                        .on_hover_text("Clear session history")
+                        .clicked()
+                    {
+                        health.reset();
+                    }
+
+                    let elapsed = health.session_time().as_secs();
+                    ui.label(format!(
+                        "Session: {:02}:{:02}:{:02}",
+                        elapsed / 3600,
+                        (elapsed / 60) % 60,
+                        elapsed % 60
+                    ));
+                    if health.is_healthy() {
+                        ui.colored_label(Color32::GREEN, "Healthy");
+                    }
+                    else {
+                        ui.colored_label(Color32::RED, "Errors seen");
+                    }
+                });
+
+                ui.separator();
+                egui::Grid::new("health_counters").striped(true).show(ui, |ui| {
+                    Self::counter_row(ui, "Command errors", health.command_errors());
+                    Self::counter_row(ui, "Retries", health.retries());
+                    Self::counter_row(ui, "Desync events", health.desyncs());
+                    Self::counter_row(ui, "Stalls", health.stalls());
+                    Self::counter_row(ui, "Pin faults", health.pin_faults());
+                    Self::counter_row(ui, "Missed transitions", health.missed_transitions());
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Last firmware error:");
+                    match health.last_firmware_error() {
+                        Some(err) => ui.colored_label(Color32::RED, err),
+                        None => ui.label("None"),
+                    };
+                });
+
+                ui.separator();
+                ui.label("Pins (sampled while this window is open)");
+                egui::Grid::new("health_pins").striped(true).show(ui, |ui| {
+                    ui.strong("Pin");
+                    ui.strong("Level");
+                    ui.strong("Reads");
+                    ui.strong("Failures");
+                    ui.strong("Transitions");
+                    ui.end_row();
+                    for (pin, stats) in health.pins() {
+                        ui.label(format!("{:?}", pin));
+                        match stats.level {
+                            Some(true) => ui.label("High"),
+                            Some(false) => ui.label("Low"),
+                            None => ui.label("-"),
+                        };
+                        ui.label(stats.reads.to_string());
+                        if stats.failures > 0 {
+                            ui.colored_label(Color32::RED, stats.failures.to_string());
+                        }
+                        else {
+                            ui.label("0");
+                        }
+                        ui.label(stats.transitions.to_string());
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.label(format!("Errors per minute (last {} minutes)", HISTORY_MINUTES));
+                Self::show_history(ui, health.errors_per_minute());
+
+                ui.separator();
+                ui.label("Event history");
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("health_events").striped(true).show(ui, |ui| {
+                        for event in health.events().rev() {
+                            let secs = event.at.as_secs();
+                            ui.monospace(format!("{:02}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60));
+                            ui.label(event.kind.to_string());
+                            if event.repeats > 1 {
+                                ui.label(format!("{} (x{})", event.message, event.repeats));
+                            }
+                            else {
+                                ui.label(&event.message);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+    }
+
+    fn counter_row(ui: &mut egui::Ui, label: &str, count: u32) {
+        ui.label(label);
+        if count > 0 {
+            ui.colored_label(Color32::RED, count.to_string());
+        }
+        else {
+            ui.label("0");
+        }
+        ui.end_row();
+    }
+
+    fn show_history(ui: &mut egui::Ui, errors_per_minute: &[u32]) {
+        let minutes = &errors_per_minute[errors_per_minute.len().saturating_sub(HISTORY_MINUTES)..];
+        let peak = minutes.iter().copied().max().unwrap_or(0);
+        let scale = peak.max(1) as f32;
+
+        let size = egui::vec2(HISTORY_MINUTES as f32 * HISTORY_BAR_WIDTH, HISTORY_HEIGHT);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_stroke(
+            rect,
+            0.0,
+            Stroke::new(1.0, Color32::DARK_GRAY),
+            egui::StrokeKind::Inside,
+        );
+
+        for (i, count) in minutes.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let height = (*count as f32 / scale) * (HISTORY_HEIGHT - 2.0);
+            let x = rect.left() + i as f32 * HISTORY_BAR_WIDTH;
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(x + 1.0, rect.bottom() - 1.0 - height),
+                egui::pos2(x + HISTORY_BAR_WIDTH - 1.0, rect.bottom() - 1.0),
+            );
+            painter.rect_filled(bar, 0.0, Color32::RED);
+        }
+
+        response.on_hover_text(format!(
+            "{} errors in the last {} minute(s), at most {} in one minute",
+            minutes.iter().sum::<u32>(),
+            minutes.len(),
+            peak
+        ));
+    }
+}
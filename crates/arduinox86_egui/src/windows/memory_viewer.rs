@@ -22,12 +22,20 @@
 */
 use crate::{
     client::ClientContext,
-    controls::data_table::DataTableWidget,
+    controls::data_table::{DataRange, DataTableWidget},
     events::{GuiEvent, GuiEventQueue},
     TEXT_COLOR,
 };
+use arduinox86_client::TimelineState;
+use arduinox86_config::{AddressMap, RegionKind};
 use egui::{Color32, TextStyle};
 
+const TIMELINE_COLOR: Color32 = Color32::LIGHT_BLUE;
+const ROM_COLOR: Color32 = Color32::GOLD;
+const MMIO_COLOR: Color32 = Color32::LIGHT_RED;
+const EXCLUDED_COLOR: Color32 = Color32::DARK_GRAY;
+const SCRATCH_COLOR: Color32 = Color32::LIGHT_GREEN;
+
 pub struct MemoryViewer {
     pub address_string: String,
     pub address: u32,
@@ -38,6 +46,9 @@ pub struct MemoryViewer {
     pub refresh_rate_string: String,
     pub refresh_rate: u32,
     pub dt: DataTableWidget,
+    // The last memory read from the server, before any timeline bytes were laid over it.
+    downloaded: Vec<u8>,
+    address_map: AddressMap,
 }
 
 impl Default for MemoryViewer {
@@ -52,6 +63,8 @@ impl Default for MemoryViewer {
             refresh_rate_string: "1".to_string(),
             refresh_rate: 1,
             dt: DataTableWidget::default(),
+            downloaded: Vec::new(),
+            address_map: AddressMap::default(),
         }
     }
 }
@@ -62,7 +75,71 @@ impl MemoryViewer {
     }
 
     pub fn set_data(&mut self, data: &[u8]) {
+        self.downloaded = data.to_vec();
         self.dt.set_data(data);
+        self.add_region_ranges();
+    }
+
+    /// Set the address map whose ROM, MMIO, excluded and scratch regions are colored.
+    pub fn set_address_map(&mut self, map: AddressMap) {
+        self.address_map = map;
+    }
+
+    /// Color the bytes of the viewed range that fall in a region of the address map. RAM is left
+    /// uncolored.
+    fn add_region_ranges(&mut self) {
+        let view_end = self.address as u64 + self.dt.data_len() as u64;
+        for region in &self.address_map.regions {
+            let fg_color = match region.kind {
+                RegionKind::Ram => continue,
+                RegionKind::Rom => ROM_COLOR,
+                RegionKind::Mmio => MMIO_COLOR,
+                RegionKind::Excluded => EXCLUDED_COLOR,
+                RegionKind::Scratch => SCRATCH_COLOR,
+            };
+            let start = region.start.max(self.address);
+            let end = (region.end as u64 + 1).min(view_end);
+            if start as u64 >= end {
+                continue;
+            }
+            self.dt.add_range(DataRange {
+                name: format!("{} {}", region.kind, region.name),
+                fg_color,
+                range: (start - self.address) as usize..(end - self.address as u64) as usize,
+            });
+        }
+    }
+
+    /// Lay the bytes seen on the bus up to a timeline cycle over the downloaded memory, and
+    /// highlight them. Bytes outside the viewed range are ignored.
+    pub fn show_timeline_state(&mut self, state: &TimelineState) {
+        let mut data = self.downloaded.clone();
+        let mut runs: Vec<std::ops::Range<usize>> = Vec::new();
+
+        for (&address, &byte) in &state.memory {
+            let Some(offset) = address.checked_sub(self.address).map(|o| o as usize)
+            else {
+                continue;
+            };
+            if offset >= data.len() {
+                continue;
+            }
+            data[offset] = byte;
+            match runs.last_mut() {
+                Some(run) if run.end == offset => run.end += 1,
+                _ => runs.push(offset..offset + 1),
+            }
+        }
+
+        self.dt.set_data(&data);
+        self.add_region_ranges();
+        for range in runs {
+            self.dt.add_range(DataRange {
+                name: format!("Bus at cycle {}", state.cycle),
+                fg_color: TIMELINE_COLOR,
+                range,
+            });
+        }
     }
 
     pub fn make_refresh_event(&self) -> GuiEvent {
@@ -21,13 +21,23 @@
     DEALINGS IN THE SOFTWARE.
 */
 pub mod binary_view;
+pub mod breakpoint_window;
 pub mod client_window;
 pub mod code_editor;
+pub mod cycle_trace_window;
+pub mod disassembly_window;
+pub mod health_window;
 pub mod memory_viewer;
+pub mod patch_window;
 pub mod register_window;
 
 pub use binary_view::BinaryView;
+pub use breakpoint_window::BreakpointWindow;
 pub use client_window::ClientWindow;
 pub use code_editor::CodeEditor;
+pub use cycle_trace_window::CycleTraceWindow;
+pub use disassembly_window::DisassemblyWindow;
+pub use health_window::HealthWindow;
 pub use memory_viewer::MemoryViewer;
+pub use patch_window::PatchWindow;
 pub use register_window::RegisterWindow;
@@ -0,0 +1,153 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    events::{GuiEvent, GuiEventQueue},
+    patch::{parse_hex_bytes, PatchHistory},
+    TEXT_COLOR,
+};
+use egui::{Color32, TextStyle};
+
+pub struct PatchWindow {
+    open: bool,
+    offset_string: String,
+    bytes_string: String,
+}
+
+impl Default for PatchWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            offset_string: "0".to_string(),
+            bytes_string: String::new(),
+        }
+    }
+}
+
+impl PatchWindow {
+    pub fn open(&self) -> &bool {
+        &self.open
+    }
+
+    pub fn open_mut(&mut self) -> &mut bool {
+        &mut self.open
+    }
+
+    /// Show the window. Patching is only offered once a program has run, as only the patched
+    /// bytes are uploaded before running it again.
+    pub fn show(
+        &mut self,
+        e_ctx: &egui::Context,
+        history: &mut PatchHistory,
+        can_run: bool,
+        events: &mut GuiEventQueue,
+    ) {
+        egui::Window::new("Instruction Patching")
+            .open(&mut self.open)
+            .default_width(480.0)
+            .default_height(320.0)
+            .show(e_ctx, |ui| {
+                let offset = u32::from_str_radix(&self.offset_string, 16).ok();
+                let bytes = parse_hex_bytes(&self.bytes_string).ok();
+
+                ui.horizontal(|ui| {
+                    ui.label("CS:IP +");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.offset_string)
+                            .font(TextStyle::Monospace)
+                            .desired_width(50.0)
+                            .text_color(if offset.is_some() { TEXT_COLOR } else { Color32::RED }),
+                    );
+                    ui.label("Bytes:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.bytes_string)
+                            .font(TextStyle::Monospace)
+                            .desired_width(160.0)
+                            .hint_text("90 90")
+                            .text_color(if bytes.is_some() { TEXT_COLOR } else { Color32::RED }),
+                    );
+
+                    let response = ui
+                        .add_enabled(
+                            can_run && offset.is_some() && bytes.is_some(),
+                            egui::Button::new("Patch & Run"),
+                        )
+                        .on_hover_text("Write the bytes into the loaded program, upload them and run it again")
+                        .on_disabled_hover_text("Run the program once, then enter a hex offset and hex bytes");
+                    if response.clicked() {
+                        if let (Some(offset), Some(bytes)) = (offset, bytes) {
+                            events.push(GuiEvent::PatchAndRun { offset, bytes });
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("History ({} patches)", history.patches().len()));
+                    if ui
+                        .add_enabled(!history.is_empty(), egui::Button::new("Export .asm Diff..."))
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Assembly Files", &["asm"])
+                            .set_file_name("patches.asm")
+                            .save_file()
+                        {
+                            events.push(GuiEvent::ExportPatches { path });
+                        }
+                    }
+                    if ui
+                        .add_enabled(!history.is_empty(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        history.clear();
+                    }
+                });
+
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("patch_history").striped(true).show(ui, |ui| {
+                        ui.strong("#");
+                        ui.strong("Program");
+                        ui.strong("CS:IP +");
+                        ui.strong("Address");
+                        ui.strong("Old");
+                        ui.strong("New");
+                        ui.end_row();
+                        for (i, patch) in history.patches().iter().enumerate() {
+                            ui.label((i + 1).to_string());
+                            ui.label(&patch.blob);
+                            ui.monospace(format!("{:04X}", patch.ip_offset));
+                            ui.monospace(format!("{:05X}", patch.address));
+                            ui.monospace(hex_string(&patch.old));
+                            ui.monospace(hex_string(&patch.new));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
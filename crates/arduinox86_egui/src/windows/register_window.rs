@@ -20,15 +20,30 @@
     FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
     DEALINGS IN THE SOFTWARE.
 */
-use crate::{controls::registers_v3::RegisterControlV3, enums::CpuStateType, events::GuiEventQueue};
+use std::collections::VecDeque;
+
+use crate::{
+    controls::{register_diff::RegisterDiffControl, registers_v3::RegisterControlV3},
+    enums::CpuStateType,
+    events::{GuiEvent, GuiEventQueue},
+};
 use arduinox86_client::{RegisterSetType, RemoteCpuRegisters};
 
+/// Number of register sets remembered in the history.
+pub const REGISTER_HISTORY_LEN: usize = 16;
+
 #[derive(Default)]
 pub struct RegisterWindow {
     open: bool,
     pub(crate) reg_type: RegisterSetType,
 
     pub(crate) control_v3: RegisterControlV3,
+    // Comparison against the initial registers, available once final registers are set.
+    diff: RegisterDiffControl,
+    diff_view: bool,
+    note: Option<String>,
+    // Register sets loaded into the CPU, most recent first.
+    history: VecDeque<RemoteCpuRegisters>,
 }
 
 impl RegisterWindow {
@@ -37,6 +52,10 @@ impl RegisterWindow {
             open: false,
             reg_type,
             control_v3: RegisterControlV3::new(),
+            diff: RegisterDiffControl::new(),
+            diff_view: false,
+            note: None,
+            history: VecDeque::new(),
         }
     }
 
@@ -49,6 +68,15 @@ impl RegisterWindow {
     }
 
     pub fn set_regs(&mut self, initial_regs: &RemoteCpuRegisters, final_regs: Option<&RemoteCpuRegisters>) {
+        match final_regs {
+            Some(final_regs) => {
+                if !self.diff.set_regs(initial_regs, final_regs) {
+                    log::warn!("Initial and final register sets are different versions; no diff available.");
+                }
+            }
+            None => self.diff.clear(),
+        }
+
         match (initial_regs, final_regs) {
             (RemoteCpuRegisters::V3(initial_regs_v3), Some(RemoteCpuRegisters::V3(final_regs_v3))) => {
                 self.control_v3.set_regs(initial_regs_v3, Some(final_regs_v3));
@@ -56,12 +84,33 @@ impl RegisterWindow {
             (RemoteCpuRegisters::V3(initial_regs_v3), None) => {
                 self.control_v3.set_regs(initial_regs_v3, None);
             }
+            _ if !self.diff.is_empty() => {
+                // Only the diff view can display this register set.
+            }
             _ => {
                 log::warn!("Unsupported register type for setting.");
             }
         }
     }
 
+    /// Set a note shown above the registers, such as when they don't match the displayed cycle.
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.note = note;
+    }
+
+    /// Remember a register set that was loaded into the CPU.
+    pub fn remember(&mut self, regs: &RemoteCpuRegisters) {
+        if self.history.len() == REGISTER_HISTORY_LEN {
+            self.history.pop_back();
+        }
+        self.history.push_front(regs.clone());
+    }
+
+    /// Return a remembered register set, where 0 is the most recently loaded.
+    pub fn history(&self, index: usize) -> Option<&RemoteCpuRegisters> {
+        self.history.get(index)
+    }
+
     pub fn regs(&self, reg_type: RegisterSetType) -> RemoteCpuRegisters {
         match reg_type {
             RegisterSetType::Intel386 => RemoteCpuRegisters::V3(self.control_v3.regs().clone()),
@@ -82,12 +131,57 @@ impl RegisterWindow {
             egui::Window::new(format!("{} Registers", state_type))
                 .default_width(400.0)
                 .default_height(300.0)
-                .show(e_ctx, |ui| match reg_type {
-                    RegisterSetType::Intel386 => self.control_v3.show(ui, events),
-                    _ => {
+                .show(e_ctx, |ui| {
+                    if let Some(note) = &self.note {
+                        ui.label(note);
+                        ui.separator();
+                    }
+                    if matches!(state_type, CpuStateType::Initial) {
+                        self.show_history(ui, events);
+                        ui.separator();
+                    }
+                    // The 386 control shows final registers with changes colored, but other register
+                    // sets can only be displayed through the diff view.
+                    let has_control = matches!(reg_type, RegisterSetType::Intel386);
+                    if !self.diff.is_empty() && has_control {
+                        ui.checkbox(&mut self.diff_view, "Diff")
+                            .on_hover_text("Compare against the initial registers");
+                    }
+                    if !self.diff.is_empty() && (self.diff_view || !has_control) {
+                        self.diff.show(ui);
+                    }
+                    else if has_control {
+                        self.control_v3.show(ui, events);
+                    }
+                    else {
                         ui.label("Unsupported register type for display.");
                     }
                 });
         }
     }
+
+    fn show_history(&mut self, ui: &mut egui::Ui, events: &mut GuiEventQueue) {
+        ui.horizontal(|ui| {
+            if ui
+                .button(egui::RichText::new(format!("{}", egui_phosphor::regular::DICE_FIVE)).size(18.0))
+                .on_hover_text("Randomize")
+                .clicked()
+            {
+                events.push(GuiEvent::RandomizeRegisters);
+            }
+
+            ui.add_enabled_ui(!self.history.is_empty(), |ui| {
+                egui::ComboBox::from_id_salt("register_history")
+                    .selected_text("Recent")
+                    .show_ui(ui, |ui| {
+                        for (index, regs) in self.history.iter().enumerate() {
+                            let label = format!("{}: code at {:05X}", index + 1, regs.code_address());
+                            if ui.selectable_label(false, label).clicked() {
+                                events.push(GuiEvent::RestoreRegisters { index });
+                            }
+                        }
+                    });
+            });
+        });
+    }
 }
@@ -0,0 +1,161 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! ardx86 brings the ArduinoX86 tools together under one binary. Every subcommand accepts the
+//! same connection and logging options; the standalone `exec_program`, `test_generator` and
+//! `hwci` binaries remain as deprecated wrappers for now.
+
+use std::path::PathBuf;
+
+use arduinox86_config::ConnectionArgs;
+use clap::{Parser, Subcommand};
+use exec_program::{DiagOptions, RunArgs, Suite};
+use hwci::HwciArgs;
+use test_generator::{CoverageArgs, DiffArgs, GenArgs, ImportJsonArgs, InferFlagsArgs, Mode, SampleArgs, TimingsArgs};
+
+#[derive(Parser, Debug)]
+#[command(name = "ardx86", author, version, about)]
+struct Cli {
+    #[command(flatten)]
+    conn: ConnectionArgs,
+
+    /// Log filter in RUST_LOG syntax, e.g. 'debug' or 'arduinox86_cpu=trace'. Overrides RUST_LOG
+    #[arg(long, global = true)]
+    log: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Execute a program binary with a register file
+    Run(RunArgs),
+    /// Generate a test set from a config file
+    Gen(GenArgs),
+    /// Run an existing test set against the CPU
+    Validate(GenArgs),
+    /// Rebuild a test set from its session logs, without the hardware
+    Rebuild(GenArgs),
+    /// Infer undefined flags masks by running each opcode's tests several times
+    InferFlags(InferFlagsArgs),
+    /// Work with MOO test sets
+    Moo {
+        #[command(subcommand)]
+        command: MooCommand,
+    },
+    /// Check the health of a rig
+    Diag {
+        #[command(flatten)]
+        opts: DiagOptions,
+
+        #[command(subcommand)]
+        command: DiagCommand,
+    },
+    /// Run a hardware session script: connect, load registers and memory, run and check the results
+    Script {
+        /// The script file
+        path: PathBuf,
+
+        #[command(flatten)]
+        opts: DiagOptions,
+    },
+    /// Replay a sample of MOO tests on the CPU and write a JUnit XML report
+    Hwci(HwciArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum MooCommand {
+    /// Verify a test set directory against its manifest
+    VerifySet {
+        /// The test set directory
+        dir: PathBuf,
+    },
+    /// Convert legacy JSON test files (8088, V20 and 80186 sets) into MOO files
+    ImportJson(ImportJsonArgs),
+    /// Build a table of cycle counts per opcode, prefix and ModR/M form from a test set
+    Timings(TimingsArgs),
+    /// Print a matrix of test counts per opcode, group extension and prefix, marking cells missing
+    /// from a generator config
+    Coverage(CoverageArgs),
+    /// Write a reduced sample of a test set, keeping a few tests of each kind per test file
+    Sample(SampleArgs),
+    /// Compare two MOO files test by test: final registers, final RAM and cycles side by side
+    Diff(DiffArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum DiagCommand {
+    /// Run a built-in program suite and report pass/fail for each program
+    Suite {
+        #[arg(value_enum, default_value = "torture")]
+        suite: Suite,
+    },
+    /// Walk ones and zeros across the data and address buses and report per-pin health
+    BurnIn {
+        /// Number of passes over every bus line
+        #[arg(long, default_value_t = 10)]
+        passes: u32,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    init_logging(cli.log.as_deref());
+
+    match &cli.command {
+        Command::Run(args) => exec_program::run(&cli.conn, args),
+        Command::Gen(args) => test_generator::run(&cli.conn, args, Mode::Generate)?,
+        Command::Validate(args) => test_generator::run(&cli.conn, args, Mode::Validate)?,
+        Command::Rebuild(args) => test_generator::run(&cli.conn, args, Mode::Rebuild)?,
+        Command::InferFlags(args) => test_generator::infer_flags(&cli.conn, args)?,
+        Command::Moo { command } => match command {
+            MooCommand::VerifySet { dir } => test_generator::verify_set(dir)?,
+            MooCommand::ImportJson(args) => test_generator::import_json(args)?,
+            MooCommand::Timings(args) => test_generator::timings(args)?,
+            MooCommand::Coverage(args) => exit_with(test_generator::coverage(args)?),
+            MooCommand::Sample(args) => test_generator::sample(args)?,
+            MooCommand::Diff(args) => exit_with(test_generator::diff(args)?),
+        },
+        Command::Diag { opts, command } => match command {
+            DiagCommand::Suite { suite } => exit_with(exec_program::run_suite(&cli.conn, *suite, opts)),
+            DiagCommand::BurnIn { passes } => exit_with(exec_program::run_burn_in(&cli.conn, *passes, opts)),
+        },
+        Command::Script { path, opts } => exit_with(exec_program::run_script(&cli.conn, path, opts)),
+        Command::Hwci(args) => hwci::run(&cli.conn, args)?,
+    }
+    Ok(())
+}
+
+/// Set up logging from RUST_LOG, with `--log` taking precedence.
+fn init_logging(filter: Option<&str>) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let Some(filter) = filter {
+        builder.parse_filters(filter);
+    }
+    builder.init();
+}
+
+fn exit_with(passed: bool) -> ! {
+    std::process::exit(if passed { 0 } else { 1 })
+}
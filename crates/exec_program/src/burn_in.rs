@@ -0,0 +1,227 @@
+//! Signal-integrity burn-in.
+//!
+//! Repeatedly runs small programs that walk a single one (and a single zero) across the data bus,
+//! and a single one across the address bus, and checks what the CPU read and wrote against what
+//! the host served. Each failure is charged to the bus line under test, producing a per-pin health
+//! report. This is meant for bringing up a new shield or checking an aging CPU socket, where a bad
+//! contact shows up as a line that is stuck or intermittently wrong.
+
+use arduinox86_client::*;
+use arduinox86_cpu::{arduinox86_client, *};
+
+const CODE_CS: u16 = 0xF000;
+const CODE_IP: u16 = 0x0100;
+const FLAGS: u16 = 0xF002;
+
+// Word the data test reads from, and the word it writes back to and reads again.
+const DATA_SRC: u16 = 0x0500;
+const DATA_DST: u16 = 0x0600;
+
+const ADDRESS_LINES: usize = 20;
+
+// mov ax, [si] / mov [di], ax / mov bx, [di] / hlt
+const DATA_PROGRAM: [u8; 7] = [0x8B, 0x04, 0x89, 0x05, 0x8B, 0x1D, 0xF4];
+// mov al, [si] / hlt
+const ADDRESS_PROGRAM: [u8; 3] = [0x8A, 0x04, 0xF4];
+
+#[derive(Copy, Clone, Default)]
+struct PinStats {
+    tests: u32,
+    read_fails: u32,
+    write_fails: u32,
+}
+
+impl PinStats {
+    fn failed(&self) -> bool {
+        self.read_fails > 0 || self.write_fails > 0
+    }
+}
+
+/// The marker byte stored at the address that has only line `line` set.
+fn address_marker(line: usize) -> u8 {
+    0x80 | line as u8
+}
+
+/// Return DS:SI for a physical address made of a single address line.
+fn address_regs(line: usize) -> (u16, u16) {
+    if line < 16 {
+        (0, 1 << line)
+    }
+    else {
+        (1 << (line - 4), 0)
+    }
+}
+
+fn run_program(
+    cpu: &mut RemoteCpu,
+    code: &[u8],
+    mut regs: RemoteCpuRegistersV1,
+    automatic: bool,
+) -> Result<RemoteCpuRegistersV1, String> {
+    regs.cs = CODE_CS;
+    regs.ip = CODE_IP;
+    regs.flags = FLAGS;
+
+    cpu.mount_bin(automatic, code, regs.calculate_code_address() as usize)?;
+    if !cpu.load_registers_from_buf(&regs.to_bytes()) {
+        return Err(format!("Register setup failed: {}", cpu.get_last_error()));
+    }
+
+    let run_options = RunOptions {
+        automatic,
+        cycle_limit: Some(1_000),
+        print_opts: PrintOptions {
+            print_pgm: false,
+            print_preload: false,
+            print_finalize: false,
+        },
+        ..Default::default()
+    };
+
+    match cpu.run(&run_options).map_err(|e| e.to_string())?.registers {
+        RemoteCpuRegisters::V1(final_regs) => Ok(final_regs),
+        _ => Err("CPU returned an unexpected register set".to_string()),
+    }
+}
+
+/// Read a data pattern, write it back and read it again. Returns the words read before and after
+/// the write.
+fn data_pass(cpu: &mut RemoteCpu, pattern: u16, automatic: bool) -> Result<(u16, u16), String> {
+    cpu.load_data(automatic, DATA_SRC as u32, &pattern.to_le_bytes())?;
+    cpu.load_data(automatic, DATA_DST as u32, &(!pattern).to_le_bytes())?;
+
+    let regs = RemoteCpuRegistersV1 {
+        si: DATA_SRC,
+        di: DATA_DST,
+        ..Default::default()
+    };
+    let final_regs = run_program(cpu, &DATA_PROGRAM, regs, automatic)?;
+    Ok((final_regs.ax, final_regs.bx))
+}
+
+/// Read the marker byte at the address with only `line` set.
+fn address_pass(cpu: &mut RemoteCpu, line: usize, automatic: bool) -> Result<u8, String> {
+    let (ds, si) = address_regs(line);
+    let regs = RemoteCpuRegistersV1 {
+        ds,
+        si,
+        ..Default::default()
+    };
+    let final_regs = run_program(cpu, &ADDRESS_PROGRAM, regs, automatic)?;
+    Ok(final_regs.ax as u8)
+}
+
+/// Run `passes` rounds of bus line tests against the connected CPU and print a per-pin report.
+/// Returns true if every line passed.
+pub fn run_burn_in(cpu: &mut RemoteCpu, passes: u32, automatic: bool) -> bool {
+    match cpu.cpu_type() {
+        ServerCpuType::Intel80286 | ServerCpuType::Intel80386 | ServerCpuType::Undetected => {
+            eprintln!("Burn-in requires an 8086-class CPU, detected {}.", cpu.cpu_type());
+            return false;
+        }
+        _ => {}
+    }
+
+    let data_lines = match CpuWidth::from(cpu.cpu_type()) {
+        CpuWidth::Eight => 8,
+        CpuWidth::Sixteen => 16,
+    };
+    let mut data_stats = vec![PinStats::default(); data_lines];
+    let mut address_stats = [PinStats::default(); ADDRESS_LINES];
+
+    // Every address line gets its own marker, so a read from the wrong line is never a match.
+    for line in 0..ADDRESS_LINES {
+        let (ds, si) = address_regs(line);
+        let address = RemoteCpu::calc_linear_address(ds, si);
+        if let Err(e) = cpu.load_data(automatic, address, &[address_marker(line)]) {
+            eprintln!("Failed to set up address markers: {e}");
+            return false;
+        }
+    }
+
+    for pass in 0..passes {
+        println!("Pass {}/{}...", pass + 1, passes);
+
+        for bit in 0..16 {
+            for pattern in [1u16 << bit, !(1u16 << bit)] {
+                let (read, readback) = match data_pass(cpu, pattern, automatic) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        eprintln!("Data test for pattern {:04X} failed to run: {e}", pattern);
+                        return false;
+                    }
+                };
+                // On an 8-bit bus both bytes of the word travel over the same lines.
+                let stats = &mut data_stats[bit % data_lines];
+                stats.tests += 1;
+                if read != pattern {
+                    log::warn!("Data read: expected {:04X}, read {:04X}", pattern, read);
+                    stats.read_fails += 1;
+                }
+                else if readback != pattern {
+                    log::warn!("Data write: wrote {:04X}, read back {:04X}", pattern, readback);
+                    stats.write_fails += 1;
+                }
+            }
+        }
+
+        for (line, stats) in address_stats.iter_mut().enumerate() {
+            let read = match address_pass(cpu, line, automatic) {
+                Ok(read) => read,
+                Err(e) => {
+                    eprintln!("Address test for A{} failed to run: {e}", line);
+                    return false;
+                }
+            };
+            stats.tests += 1;
+            if read != address_marker(line) {
+                log::warn!(
+                    "Address A{}: expected marker {:02X}, read {:02X}",
+                    line,
+                    address_marker(line),
+                    read
+                );
+                stats.read_fails += 1;
+            }
+        }
+    }
+
+    println!("{:<5} {:>6} {:>10} {:>11}", "Pin", "Tests", "Read fails", "Write fails");
+    let pins = data_stats
+        .iter()
+        .enumerate()
+        .map(|(line, stats)| (format!("D{line}"), stats))
+        .chain(
+            address_stats
+                .iter()
+                .enumerate()
+                .map(|(line, stats)| (format!("A{line}"), stats)),
+        );
+
+    let mut bad_pins = 0;
+    for (name, stats) in pins {
+        let mark = if stats.failed() {
+            bad_pins += 1;
+            "  <-- check"
+        }
+        else {
+            ""
+        };
+        println!(
+            "{:<5} {:>6} {:>10} {:>11}{}",
+            name, stats.tests, stats.read_fails, stats.write_fails, mark
+        );
+    }
+
+    if bad_pins == 0 {
+        println!(
+            "All {} bus lines passed {} pass(es).",
+            data_lines + ADDRESS_LINES,
+            passes
+        );
+    }
+    else {
+        println!("{} bus line(s) failed.", bad_pins);
+    }
+    bad_pins == 0
+}
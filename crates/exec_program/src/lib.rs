@@ -0,0 +1,741 @@
+//! Program execution and rig diagnostics for 8088/8086/V20/V30 CPUs. The `exec_program` binary
+//! and the `run` and `diag` subcommands of `ardx86` are thin wrappers around this crate.
+
+use std::path::PathBuf;
+
+use arduinox86_client::*;
+use arduinox86_config::ConnectionArgs;
+use arduinox86_cpu::{arduinox86_client, *};
+
+mod burn_in;
+mod script;
+mod torture;
+pub use script::{run_script, Check, Script, Statement};
+pub use torture::Suite;
+
+const SCREEN_INIT_TIME: u64 = 3; // Seconds to wait for the screen to initialize.
+
+/// Options for executing a program from disk.
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    #[arg(long, default_value_t = false)]
+    pub storeall: bool,
+
+    // The binary file containing the register data. Produced from an assembly
+    // file 'program_regs.asm'
+    #[arg(long)]
+    pub reg_file: Option<PathBuf>,
+
+    // The binary file containing the code to execute.
+    #[arg(long)]
+    pub bin_file: Option<PathBuf>,
+
+    // Specify the address in memory to mount the bin file. Typically, you would prefer to use
+    // the `automount` option to mount the binary at CS:IP, but this option allows you to mount it
+    // at arbitrary addresses.
+    #[arg(long)]
+    pub mount_addr: Option<String>,
+
+    // Automatically mount the binary file at CS:IP.
+    #[arg(long)]
+    pub automount: bool,
+
+    // Offset the automount address by the specified value.
+    #[arg(long)]
+    pub mount_offset: Option<String>,
+
+    // Specify the number of wait states for every bus transfer.
+    // TODO: Currently no division between memory and IO, should change...
+    #[arg(long, default_value_t = 0)]
+    pub wait_states: u32,
+
+    // Enter 8080 emulation mode. Must have a compatible CPU such as a V20/V30.
+    #[arg(long, default_value_t = false)]
+    pub emu8080: bool,
+
+    // Fill the prefetch queue before executing code.
+    #[arg(long, default_value_t = false)]
+    pub prefetch: bool,
+
+    // Choose the program used to fill the prefetch queue: auto, none, stosb, nop or nec.
+    // Implies --prefetch unless 'none'.
+    #[arg(long)]
+    pub preload: Option<PreloadProgram>,
+
+    // Choose the byte fed past the end of the program: auto, nop, nop80 or a hex byte.
+    #[arg(long)]
+    pub filler: Option<Filler>,
+
+    // Raise the INTR line on N cycles after HLT.
+    #[arg(long, default_value_t = 0)]
+    pub intr_after: u32,
+
+    // Raise the INTR line on the specified cycle #.
+    #[arg(long, default_value_t = 0)]
+    pub intr_on: u32,
+
+    // Raise the NMI line on the specified cycle #.
+    #[arg(long)]
+    pub nmi_on: Option<u32>,
+
+    // Run the CPU for a single instruction.
+    #[arg(long, default_value_t = false)]
+    pub single_step: bool,
+
+    #[arg(long, default_value_t = false)]
+    pub reset_only: bool,
+
+    // Run the CPU in automatic execution mode.
+    #[arg(long)]
+    pub automatic: bool,
+
+    // Enable serial debugging.
+    #[arg(long)]
+    pub serial_debug: bool,
+
+    // What to do when the program writes outside its mounted region: allow, warn or fault.
+    // Warn and fault print a report of the out-of-bounds writes after the run.
+    #[arg(long, default_value = "allow")]
+    pub oob_writes: OobWritePolicy,
+
+    // What to do when the CPU transfers memory data with BHE inactive and A0 odd: record,
+    // open_bus or fault. Such cycles are counted and printed after the run either way.
+    #[arg(long, default_value = "record")]
+    pub invalid_width: InvalidWidthPolicy,
+
+    // What to do when the host drives the data bus while the CPU is driving it, or the CPU reads
+    // the bus with nothing driving it: record or fault. Fault also keeps the host off the bus on
+    // a contending cycle. Such cycles are counted and printed after the run either way.
+    #[arg(long, default_value = "record")]
+    pub bus_contention: BusContentionPolicy,
+
+    // End the run with an error when an instruction is fetched from the queue at an address other
+    // than the previous instruction predicts. Such fetches are reported as queue_fetch
+    // diagnostics either way.
+    #[arg(long)]
+    pub strict_queue: bool,
+
+    // Save the FPU state with FNSAVE after the program and print it. Requires an FPU; not
+    // available in automatic mode.
+    #[arg(long, conflicts_with = "automatic")]
+    pub fpu_save: bool,
+
+    // End the run with an error when a WAIT instruction waits on the FPU for more than this many
+    // cycles.
+    #[arg(long)]
+    pub fpu_wait_limit: Option<u32>,
+
+    // End the program at the next code fetch after a condition, for programs that never run off
+    // their end: iret:VECTOR, port:PORT or fetch:ADDRESS in hex, or cycles:N in decimal.
+    #[arg(long, value_parser = parse_end_condition)]
+    pub end_on: Option<EndCondition>,
+
+    // Additional region the program may write to, as hex START:END (end exclusive).
+    // May be given more than once.
+    #[arg(long, value_parser = parse_region)]
+    pub writable: Vec<(u32, u32)>,
+
+    // Install the binary in FILE as the ISR for a vector, as hex VECTOR:FILE, instead of the
+    // default IRET stub. The ISR must return itself. May be given more than once.
+    #[arg(long, value_parser = parse_isr)]
+    pub isr: Vec<(u8, PathBuf)>,
+
+    // Attach a bank of read/write latches to the ports START:END (hex, end exclusive), such as the
+    // DMA page registers. Reads return the last byte written, or FF. May be given more than once.
+    #[arg(long, value_parser = parse_region, conflicts_with = "automatic")]
+    pub io_latch: Vec<(u32, u32)>,
+
+    // Watch the memory region START:END (hex, end exclusive) and list every byte the program reads
+    // or writes there after the run. May be given more than once.
+    #[arg(long, value_parser = parse_region, conflicts_with = "automatic")]
+    pub watch: Vec<(u32, u32)>,
+
+    // Segment holding the ISR stubs and programs, in hex. Defaults to F800.
+    #[arg(long, value_parser = parse_hex_u16)]
+    pub isr_segment: Option<u16>,
+
+    // Experimental: corrupt a single bus read to simulate a single event upset, as
+    // TARGET:N:MASK. The Nth read of the given target (any, code or memr) is served with its
+    // data XORed by the hex MASK. Not available in automatic mode.
+    #[arg(long, value_parser = parse_bus_fault, conflicts_with = "automatic")]
+    pub inject_fault: Option<BusFault>,
+
+    // Exit with an error if the run raised a diagnostic in any of these categories: desync,
+    // program_bounds, oob_write, invalid_width, contention, float, bus_state, cycle_limit, server,
+    // address_map, queue_fetch or fpu. Comma-separated.
+    #[arg(long, value_delimiter = ',')]
+    pub fail_on: Vec<DiagnosticCategory>,
+
+    // Write an experiment bundle to this directory: the program, initial and final registers,
+    // cycle trace, bus ops, diagnostics and environment, enough to share and reproduce the run.
+    #[arg(long)]
+    pub bundle: Option<PathBuf>,
+}
+
+/// Options shared by the diagnostic runs.
+#[derive(clap::Args, Debug, Default)]
+pub struct DiagOptions {
+    // Run the CPU in automatic execution mode.
+    #[arg(long)]
+    pub automatic: bool,
+
+    // Fill the prefetch queue before executing code.
+    #[arg(long, default_value_t = false)]
+    pub prefetch: bool,
+
+    // Specify the number of wait states for every bus transfer.
+    #[arg(long, default_value_t = 0)]
+    pub wait_states: u32,
+}
+
+/// Connect to the CPU and set it up for a diagnostic run.
+fn diag_cpu(conn: &ConnectionArgs, opts: &DiagOptions) -> RemoteCpu<'static> {
+    let mut cpu_client = connect(conn);
+    init_screen(&mut cpu_client);
+    let cpu = match RemoteCpu::new(cpu_client, opts.prefetch, false, opts.wait_states, 0, 0, 0) {
+        Ok(cpu) => cpu,
+        Err(e) => {
+            eprintln!("Error setting up the CPU: {}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Detected CPU type: {:?}", cpu.cpu_type());
+    cpu
+}
+
+/// Run a built-in program suite against the connected CPU. Returns true if every program passed.
+pub fn run_suite(conn: &ConnectionArgs, suite: Suite, opts: &DiagOptions) -> bool {
+    let mut cpu = diag_cpu(conn, opts);
+    torture::run_suite(&mut cpu, suite, opts.automatic)
+}
+
+/// Run the signal-integrity burn-in against the connected CPU. Returns true if every bus line
+/// passed.
+pub fn run_burn_in(conn: &ConnectionArgs, passes: u32, opts: &DiagOptions) -> bool {
+    let mut cpu = diag_cpu(conn, opts);
+    burn_in::run_burn_in(&mut cpu, passes, opts.automatic)
+}
+
+/// Execute the program and register files given in `args`, printing the final register state.
+pub fn run(conn: &ConnectionArgs, args: &RunArgs) {
+    let (Some(reg_file), Some(bin_file)) = (args.reg_file.clone(), args.bin_file.clone())
+    else {
+        eprintln!("Both --reg-file and --bin-file must be specified.");
+        std::process::exit(1);
+    };
+
+    let reg_bytes = std::fs::read(&reg_file).unwrap_or_else(|e| {
+        eprintln!("Couldn't read register file {:?}: {}", reg_file, e);
+        std::process::exit(1);
+    });
+
+    // Capture initial regs before adjustment.
+    let initial_regs = match RemoteCpuRegisters::try_from(reg_bytes.as_slice()) {
+        Ok(regs) => regs,
+        Err(e) => {
+            eprintln!("Error parsing register binary: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let bin_bytes = std::fs::read(&bin_file).unwrap_or_else(|e| {
+        eprintln!("Couldn't read binary file {:?}: {}", bin_file, e);
+        std::process::exit(1);
+    });
+
+    let mount_addr = if let Some(mount_addr) = &args.mount_addr {
+        let addr = u32::from_str_radix(mount_addr, 16).unwrap_or_else(|e| {
+            eprintln!("Couldn't parse code mount address '{}': {}", mount_addr, e);
+            std::process::exit(1);
+        });
+        println!("Mounting code at address [{:08X}]", addr);
+        addr
+    }
+    else if args.automount {
+        initial_regs.code_address()
+    }
+    else {
+        eprintln!("Either --mount_addr or --automount must be specified.");
+        std::process::exit(1);
+    };
+
+    if (mount_addr as usize) > (0xFFFFFusize - bin_bytes.len()) {
+        eprintln!("Specified mount point out of range.");
+        std::process::exit(1);
+    }
+
+    // Create a cpu_client connection to cpu_server.
+    let mut cpu_client = connect(conn);
+
+    if args.storeall {
+        // Just do STOREALL and exit.
+        if let Err(e) = cpu_client.storeall() {
+            eprintln!("Error executing STOREALL: {e}");
+            std::process::exit(1);
+        }
+        else {
+            println!("STOREALL executed successfully.");
+            return;
+        }
+    }
+
+    if args.nmi_on.is_some() && args.single_step {
+        eprintln!("Cannot use NMI with single step mode!");
+        std::process::exit(1);
+    }
+
+    let nmi_on = if let Some(nmi_cycle) = args.nmi_on {
+        nmi_cycle
+    }
+    else {
+        if args.single_step {
+            // If single step mode is enabled, we can use NMI on cycle 1.
+            1
+        }
+        else {
+            // Otherwise, we don't use NMI.
+            0
+        }
+    };
+
+    init_screen(&mut cpu_client);
+
+    // Create a remote cpu instance using the cpu_client which should now be connected.
+    let mut cpu = match RemoteCpu::new(
+        cpu_client,
+        args.prefetch,
+        args.emu8080,
+        args.wait_states,
+        args.intr_on,
+        args.intr_after,
+        nmi_on,
+    ) {
+        Ok(cpu) => cpu,
+        Err(e) => {
+            eprintln!("Error setting up the CPU: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let cpu_type = cpu.cpu_type();
+    println!("Detected CPU type: {:?}", cpu_type);
+
+    if let Some(preload) = args.preload {
+        if let Err(e) = cpu.set_preload(preload) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    if let Some(filler) = args.filler {
+        if let Err(e) = cpu.set_filler(filler) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        println!("Filler: {} ({:02X})", cpu.filler(), cpu.fill_byte());
+    }
+    if args.prefetch || args.preload.is_some() {
+        println!("Preload program: {}", cpu.preload());
+    }
+
+    let have_fpu = cpu.have_fpu();
+    if have_fpu {
+        println!("Detected FPU!");
+    }
+
+    if args.reset_only {
+        println!("Reset only flag passed, exiting.");
+        return;
+    }
+
+    // Copy the binary to memory
+    log::debug!("Mounting program code at: {:05X}", mount_addr);
+    match cpu.mount_bin(args.automatic, &bin_bytes, mount_addr as usize) {
+        Ok(_) => {
+            log::debug!("Program code mounted successfully.");
+        }
+        Err(e) => {
+            eprintln!("Error mounting program code: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // Set up IVR table
+    let mut ivt_builder = RemoteCpu::ivt_builder();
+    if let Some(segment) = args.isr_segment {
+        ivt_builder = ivt_builder.segment(segment);
+    }
+    for (vector, isr_file) in &args.isr {
+        let isr_bytes = std::fs::read(isr_file).unwrap_or_else(|e| {
+            eprintln!("Couldn't read ISR file {:?}: {}", isr_file, e);
+            std::process::exit(1);
+        });
+        ivt_builder = ivt_builder.isr(*vector, isr_bytes);
+    }
+    match ivt_builder.build() {
+        Ok(ivt) => cpu.install_ivt(ivt),
+        Err(e) => {
+            eprintln!("Error setting up the interrupt vector table: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    for (start, end) in &args.io_latch {
+        if *end > 0xFFFF {
+            eprintln!("I/O latch range {:X}:{:X} must end at or below FFFF.", start, end);
+            std::process::exit(1);
+        }
+        let ports = (*start as u16)..(*end as u16);
+        let device = LatchDevice::new(format!("latch {:04X}", start), ports.clone(), 0xFF);
+        if let Err(e) = cpu.add_io_device(ports, Box::new(device)) {
+            eprintln!("Error attaching I/O latches: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    for (start, end) in &args.watch {
+        let hook = WatchHook::new(format!("watch {:05X}", start));
+        if let Err(e) = cpu.add_memory_hook(*start..*end, Box::new(hook)) {
+            eprintln!("Error adding memory watch: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    for (start, end) in &args.writable {
+        cpu.add_writable_region(*start, end.saturating_sub(*start));
+    }
+    match conn.address_map() {
+        Ok(map) => cpu.set_address_map(map),
+        Err(e) => {
+            eprintln!("Error loading address map: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // Load the registers from binary file
+    let result = cpu.load_registers_from_buf(&reg_bytes);
+    if result {
+        log::trace!("Successfully set up registers!");
+
+        println!("Initial register state:");
+
+        println!("{}", initial_regs.display(cpu_type));
+
+        let print_opts = PrintOptions {
+            print_pgm: true,
+            print_preload: false,
+            print_finalize: false,
+        };
+
+        let run_options = RunOptions {
+            automatic: args.automatic,
+            cycle_limit: Some(10_000),
+            wait_states: None,
+            print_opts,
+            oob_write_policy: args.oob_writes,
+            invalid_width_policy: args.invalid_width,
+            bus_contention_policy: args.bus_contention,
+            bus_fault: args.inject_fault,
+            trace: args.bundle.is_some(),
+            strict_queue: args.strict_queue,
+            fpu_save: args.fpu_save,
+            fpu_wait_limit: args.fpu_wait_limit,
+            end_condition: args.end_on,
+            ..Default::default()
+        };
+
+        match cpu.run(&run_options) {
+            Ok(result) => {
+                println!("Final register state:");
+                println!("{}", initial_regs.display_delta(&result.registers, cpu_type));
+
+                if result.smc_writes > 0 {
+                    println!(
+                        "Program made {} write(s) to code already in the prefetch queue (self-modifying code).",
+                        result.smc_writes
+                    );
+                }
+                if result.invalid_width_cycles > 0 {
+                    println!(
+                        "CPU made {} memory transfer(s) with BHE inactive and A0 odd.",
+                        result.invalid_width_cycles
+                    );
+                }
+                if result.contention_cycles > 0 || result.float_cycles > 0 {
+                    println!(
+                        "Data bus contention on {} cycle(s), floating bus read on {} cycle(s).",
+                        result.contention_cycles, result.float_cycles
+                    );
+                }
+                if result.fill_ct > 0 {
+                    println!(
+                        "Fed {} fill byte(s) ({:02X}) past the end of the program.",
+                        result.fill_ct, result.fill_byte
+                    );
+                }
+                for change in &result.run_state_changes {
+                    if matches!(change.to, RunState::EmuEnter | RunState::EmuExit) {
+                        println!("{:?} on cycle {}", change.to, change.cycle);
+                    }
+                }
+                for access in &result.io_accesses {
+                    println!(
+                        "IO {} [{:04X}] {:0w$X} on cycle {}",
+                        if access.write { "write" } else { "read " },
+                        access.port,
+                        access.value,
+                        access.cycle,
+                        w = if access.word { 4 } else { 2 }
+                    );
+                }
+                for access in &result.memory_hook_accesses {
+                    println!(
+                        "Memory {} [{:05X}] {:02X} on cycle {}",
+                        if access.write { "write" } else { "read " },
+                        access.address,
+                        access.value,
+                        access.cycle
+                    );
+                }
+                if let Some(fpu_state) = &result.fpu_state {
+                    print!("FPU state:\n{}", fpu_state);
+                }
+                if !result.phase_budget.is_empty() {
+                    println!("Cycles and time by run phase:\n{}", result.phase_budget);
+                }
+                cpu.print_oob_report();
+                print_fault_report(args, &cpu);
+                write_bundle(conn, args, &cpu, &bin_bytes, mount_addr, &initial_regs, Some(&result));
+                check_diagnostics(args, &cpu);
+            }
+            Err(e) => {
+                log::error!("Program execution failed: {}", e);
+                cpu.print_oob_report();
+                print_fault_report(args, &cpu);
+                write_bundle(conn, args, &cpu, &bin_bytes, mount_addr, &initial_regs, None);
+                check_diagnostics(args, &cpu);
+            }
+        }
+    }
+    else {
+        log::error!("Register setup failed: {}", cpu.get_last_error());
+    }
+}
+
+/// Parse a hex address range of the form START:END.
+fn parse_region(s: &str) -> Result<(u32, u32), String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected START:END, got '{}'", s))?;
+    let start = u32::from_str_radix(start, 16).map_err(|e| format!("Bad start address '{}': {}", start, e))?;
+    let end = u32::from_str_radix(end, 16).map_err(|e| format!("Bad end address '{}': {}", end, e))?;
+    if end < start {
+        return Err(format!("Region end {:X} is below start {:X}", end, start));
+    }
+    Ok((start, end))
+}
+
+/// Parse an ISR of the form VECTOR:FILE, with the vector in hex.
+fn parse_isr(s: &str) -> Result<(u8, PathBuf), String> {
+    let (vector, file) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected VECTOR:FILE, got '{}'", s))?;
+    let vector = u8::from_str_radix(vector, 16).map_err(|e| format!("Bad vector '{}': {}", vector, e))?;
+    Ok((vector, PathBuf::from(file)))
+}
+
+fn parse_hex_u16(s: &str) -> Result<u16, String> {
+    u16::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|e| format!("Bad hex value '{}': {}", s, e))
+}
+
+fn parse_end_condition(s: &str) -> Result<EndCondition, String> {
+    let (kind, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected KIND:VALUE, got '{}'", s))?;
+    let hex = |value: &str| {
+        u32::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|e| format!("Bad hex value '{}': {}", value, e))
+    };
+    match kind {
+        "iret" => u8::try_from(hex(value)?)
+            .map(EndCondition::Iret)
+            .map_err(|_| format!("Bad vector '{}'", value)),
+        "port" => u16::try_from(hex(value)?)
+            .map(EndCondition::PortWrite)
+            .map_err(|_| format!("Bad port '{}'", value)),
+        "fetch" => hex(value).map(EndCondition::Fetch),
+        "cycles" => value
+            .parse::<u32>()
+            .map(EndCondition::Cycles)
+            .map_err(|e| format!("Bad cycle count '{}': {}", value, e)),
+        _ => Err(format!("Unknown end condition '{}'", kind)),
+    }
+}
+
+fn parse_bus_fault(s: &str) -> Result<BusFault, String> {
+    let mut parts = s.split(':');
+    let (Some(target), Some(nth), Some(mask), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(format!("Expected TARGET:N:MASK, got '{}'", s));
+    };
+    let target = target.parse::<BusFaultTarget>()?;
+    let nth = nth
+        .parse::<u32>()
+        .map_err(|e| format!("Bad read number '{}': {}", nth, e))?;
+    if nth == 0 {
+        return Err("Read numbers start at 1".to_string());
+    }
+    let xor_mask = u16::from_str_radix(mask.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Bad fault mask '{}': {}", mask, e))?;
+    Ok(BusFault { target, nth, xor_mask })
+}
+
+/// Tag the run with the injected fault, so the cycle trace above can be read against it.
+fn print_fault_report(args: &RunArgs, cpu: &RemoteCpu) {
+    let Some(fault) = args.inject_fault
+    else {
+        return;
+    };
+    match cpu.injected_fault() {
+        Some(injected) => println!(
+            "Injected bus fault: cycle {} {:?} [{:05X}] {:04X} served as {:04X}",
+            injected.cycle, injected.bus_state, injected.address, injected.original, injected.injected
+        ),
+        None => println!(
+            "No bus fault injected: program made fewer than {} {:?} read(s).",
+            fault.nth, fault.target
+        ),
+    }
+}
+
+/// Write the experiment bundle requested with --bundle, if any. `result` is None if the run failed.
+fn write_bundle(
+    conn: &ConnectionArgs,
+    args: &RunArgs,
+    cpu: &RemoteCpu,
+    bin_bytes: &[u8],
+    mount_addr: u32,
+    initial_regs: &RemoteCpuRegisters,
+    result: Option<&RunResult>,
+) {
+    let Some(dir) = &args.bundle
+    else {
+        return;
+    };
+
+    let mut environment = BundleEnvironment::new(
+        "exec_program",
+        env!("CARGO_PKG_VERSION"),
+        cpu.cpu_type(),
+        cpu.have_fpu(),
+    );
+    environment.port = conn.com_port.clone();
+    environment.arguments = std::env::args().collect();
+
+    let mut bundle = ExperimentBundle::new(environment);
+    let bin_name = args
+        .bin_file
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    bundle.programs.push(BundleProgram {
+        name:    bin_name,
+        address: mount_addr,
+        data:    bin_bytes.to_vec(),
+    });
+    bundle.initial_regs = Some(initial_regs.clone());
+    if let Some(result) = result {
+        bundle.final_regs = Some(result.registers.clone());
+        bundle.trace = result.trace.iter().map(bundle_cycle).collect();
+    }
+    bundle.diagnostics = cpu
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| BundleDiagnostic {
+            severity: diagnostic.severity.to_string(),
+            cycle:    diagnostic.cycle,
+            category: diagnostic.category.to_string(),
+            message:  diagnostic.message.clone(),
+        })
+        .collect();
+
+    match bundle.write(dir) {
+        Ok(manifest) => println!(
+            "Wrote experiment bundle of {} file(s) to {}",
+            manifest.files.len() + 1,
+            dir.display()
+        ),
+        Err(e) => eprintln!("Failed to write experiment bundle to {}: {}", dir.display(), e),
+    }
+}
+
+fn bundle_cycle(record: &CycleRecord) -> BundleCycle {
+    BundleCycle {
+        cycle: record.cycle,
+        ale: record.ale,
+        address_latch: record.address_latch,
+        address_bus: record.address_bus,
+        bus_state: format!("{:?}", record.bus_state),
+        t_state: format!("{:?}", record.t_state),
+        data_bus: record.data_bus,
+        reading: record.reading,
+        writing: record.writing,
+        queue_op: Some(format!("{:?}", record.queue_op)),
+        queue_byte: record.queue_byte,
+        comment: record.comment.clone(),
+    }
+}
+
+/// Print the diagnostics raised during the run. Exits with an error if any of them is in a
+/// category selected with --fail-on.
+fn check_diagnostics(args: &RunArgs, cpu: &RemoteCpu) {
+    let diagnostics = cpu.diagnostics();
+    if diagnostics.is_empty() {
+        return;
+    }
+    println!("Run raised {} diagnostic(s):", diagnostics.len());
+    for diagnostic in diagnostics {
+        println!("  {}", diagnostic);
+    }
+
+    let failed = diagnostics
+        .iter()
+        .filter(|diagnostic| args.fail_on.contains(&diagnostic.category))
+        .count();
+    if failed > 0 {
+        eprintln!("{} diagnostic(s) in a --fail-on category.", failed);
+        std::process::exit(1);
+    }
+}
+
+/// Open a connection to the CPU server, exiting on failure.
+fn connect(conn: &ConnectionArgs) -> CpuClient {
+    match conn.connect(5000) {
+        Ok(ard_client) => {
+            println!("Opened connection to Arduino_8088 server!");
+            ard_client
+        }
+        Err(e) => {
+            eprintln!("Error connecting to Arduino_8088 server: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn init_screen(cpu_client: &mut CpuClient) {
+    match cpu_client.init_screen() {
+        Ok(screen_present) if screen_present => {
+            println!(
+                "Display screen detected, waiting ({}) seconds for initialization...",
+                SCREEN_INIT_TIME
+            );
+            // Wait for the screen to initialize.
+            std::thread::sleep(std::time::Duration::from_secs(SCREEN_INIT_TIME));
+        }
+        Ok(_) => {
+            println!("No display screen detected.");
+        }
+        Err(e) => {
+            eprintln!("Error initializing screen: {e}");
+        }
+    }
+}
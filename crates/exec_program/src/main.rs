@@ -1,279 +1,54 @@
-use std::path::PathBuf;
+//! Deprecated entry point, kept while scripts move over to `ardx86 run` and `ardx86 diag`.
 
-use arduinox86_client::*;
-use arduinox86_cpu::{arduinox86_client, *};
+use arduinox86_config::ConnectionArgs;
 use clap::Parser;
-
-const SCREEN_INIT_TIME: u64 = 3; // Seconds to wait for the screen to initialize.
+use exec_program::{DiagOptions, RunArgs, Suite};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(long)]
-    com_port: Option<String>,
-
-    #[arg(long, default_value_t = false)]
-    storeall: bool,
-
-    // The binary file containing the register data. Produced from an assembly
-    // file 'program_regs.asm'
-    #[arg(long, required(true))]
-    reg_file: PathBuf,
-
-    // The binary file containing the code to execute.
-    #[arg(long, required(true))]
-    bin_file: PathBuf,
-
-    // Specify the address in memory to mount the bin file. Typically, you would prefer to use
-    // the `automount` option to mount the binary at CS:IP, but this option allows you to mount it
-    // at arbitrary addresses.
-    #[arg(long)]
-    mount_addr: Option<String>,
-
-    // Automatically mount the binary file at CS:IP.
-    #[arg(long)]
-    automount: bool,
-
-    // Offset the automount address by the specified value.
-    #[arg(long)]
-    mount_offset: Option<String>,
-
-    // Specify the number of wait states for every bus transfer.
-    // TODO: Currently no division between memory and IO, should change...
-    #[arg(long, default_value_t = 0)]
-    wait_states: u32,
-
-    // Enter 8080 emulation mode. Must have a compatible CPU such as a V20/V30.
-    #[arg(long, default_value_t = false)]
-    emu8080: bool,
-
-    // Fill the prefetch queue before executing code.
-    #[arg(long, default_value_t = false)]
-    prefetch: bool,
-
-    // Raise the INTR line on N cycles after HLT.
-    #[arg(long, default_value_t = 0)]
-    intr_after: u32,
+    #[command(flatten)]
+    conn: ConnectionArgs,
 
-    // Raise the INTR line on the specified cycle #.
-    #[arg(long, default_value_t = 0)]
-    intr_on: u32,
+    #[command(flatten)]
+    run: RunArgs,
 
-    // Raise the NMI line on the specified cycle #.
-    #[arg(long)]
-    nmi_on: Option<u32>,
+    // Run a built-in program suite against the connected CPU and report pass/fail
+    // instead of executing a program from disk.
+    #[arg(long, value_enum, conflicts_with_all(["reg_file", "bin_file"]))]
+    suite: Option<Suite>,
 
-    // Run the CPU for a single instruction.
-    #[arg(long, default_value_t = false)]
-    single_step: bool,
+    // Run the signal-integrity burn-in: walk ones and zeros across the data and address
+    // buses and print a per-pin health report.
+    #[arg(long, conflicts_with_all(["reg_file", "bin_file", "suite"]))]
+    burn_in: bool,
 
-    #[arg(long, default_value_t = false)]
-    reset_only: bool,
-
-    // Run the CPU in automatic execution mode.
-    #[arg(long)]
-    automatic: bool,
-
-    // Enable serial debugging.
-    #[arg(long)]
-    serial_debug: bool,
+    // Number of passes over every bus line for --burn-in.
+    #[arg(long, default_value_t = 10)]
+    burn_in_passes: u32,
 }
 
 fn main() {
     env_logger::init();
 
     let args = Args::parse();
+    eprintln!("Note: exec_program is deprecated, use `ardx86 run` or `ardx86 diag` instead.");
 
-    // Parse commandline arguments
-    let reg_bytes = std::fs::read(args.reg_file.clone()).unwrap_or_else(|e| {
-        eprintln!("Couldn't read register file {:?}: {}", args.reg_file, e);
-        std::process::exit(1);
-    });
-
-    // Capture initial regs before adjustment.
-    let initial_regs = match RemoteCpuRegisters::try_from(reg_bytes.as_slice()) {
-        Ok(regs) => regs,
-        Err(e) => {
-            eprintln!("Error parsing register binary: {}", e);
-            std::process::exit(1);
-        }
-    };
-
-    let bin_bytes = std::fs::read(args.bin_file.clone()).unwrap_or_else(|e| {
-        eprintln!("Couldn't read binary file {:?}: {}", args.bin_file, e);
-        std::process::exit(1);
-    });
-
-    let mount_addr = if let Some(mount_addr) = &args.mount_addr {
-        let addr = u32::from_str_radix(mount_addr, 16).unwrap_or_else(|e| {
-            eprintln!("Couldn't parse code mount address '{}': {}", mount_addr, e);
-            std::process::exit(1);
-        });
-        println!("Mounting code at address [{:08X}]", addr);
-        addr
-    }
-    else if args.automount {
-        initial_regs.code_address()
-    }
-    else {
-        eprintln!("Either --mount_addr or --automount must be specified.");
-        std::process::exit(1);
-    };
-
-    if (mount_addr as usize) > (0xFFFFFusize - bin_bytes.len()) {
-        eprintln!("Specified mount point out of range.");
-        std::process::exit(1);
-    }
-
-    // Create a cpu_client connection to cpu_server.
-    let mut cpu_client = match CpuClient::init(args.com_port.clone(), Some(5000)) {
-        Ok(ard_client) => {
-            println!("Opened connection to Arduino_8088 server!");
-            ard_client
-        }
-        Err(e) => {
-            eprintln!("Error connecting to Arduino_8088 server: {e}");
-            std::process::exit(1);
-        }
+    let diag_opts = DiagOptions {
+        automatic:   args.run.automatic,
+        prefetch:    args.run.prefetch,
+        wait_states: args.run.wait_states,
     };
 
-    if args.storeall {
-        // Just do STOREALL and exit.
-        if let Err(e) = cpu_client.storeall() {
-            eprintln!("Error executing STOREALL: {e}");
-            std::process::exit(1);
-        }
-        else {
-            println!("STOREALL executed successfully.");
-            return;
-        }
-    }
-
-    if args.nmi_on.is_some() && args.single_step {
-        eprintln!("Cannot use NMI with single step mode!");
-        std::process::exit(1);
-    }
-
-    let nmi_on = if let Some(nmi_cycle) = args.nmi_on {
-        nmi_cycle
-    }
-    else {
-        if args.single_step {
-            // If single step mode is enabled, we can use NMI on cycle 1.
-            1
-        }
-        else {
-            // Otherwise, we don't use NMI.
-            0
-        }
-    };
-
-    match cpu_client.init_screen() {
-        Ok(screen_present) if screen_present => {
-            println!(
-                "Display screen detected, waiting ({}) seconds for initialization...",
-                SCREEN_INIT_TIME
-            );
-            // Wait for the screen to initialize.
-            std::thread::sleep(std::time::Duration::from_secs(SCREEN_INIT_TIME));
-        }
-        Ok(_) => {
-            println!("No display screen detected.");
-        }
-        Err(e) => {
-            eprintln!("Error initializing screen: {e}");
-        }
-    }
-
-    // Create a remote cpu instance using the cpu_client which should now be connected.
-    let mut cpu = RemoteCpu::new(
-        cpu_client,
-        args.prefetch,
-        args.emu8080,
-        args.wait_states,
-        args.intr_on,
-        args.intr_after,
-        nmi_on,
-    );
-
-    let cpu_type = cpu.cpu_type();
-    println!("Detected CPU type: {:?}", cpu_type);
-
-    let have_fpu = cpu.have_fpu();
-    if have_fpu {
-        println!("Detected FPU!");
-    }
-
-    if args.reset_only {
-        println!("Reset only flag passed, exiting.");
-        return;
+    if let Some(suite) = args.suite {
+        let passed = exec_program::run_suite(&args.conn, suite, &diag_opts);
+        std::process::exit(if passed { 0 } else { 1 });
     }
 
-    // Copy the binary to memory
-    log::debug!("Mounting program code at: {:05X}", mount_addr);
-    match cpu.mount_bin(args.automatic, &bin_bytes, mount_addr as usize) {
-        Ok(_) => {
-            log::debug!("Program code mounted successfully.");
-        }
-        Err(e) => {
-            eprintln!("Error mounting program code: {}", e);
-            std::process::exit(1);
-        }
+    if args.burn_in {
+        let passed = exec_program::run_burn_in(&args.conn, args.burn_in_passes, &diag_opts);
+        std::process::exit(if passed { 0 } else { 1 });
     }
 
-    // Set up IVR table
-    cpu.setup_ivt();
-
-    // Load the registers from binary file
-    let result = cpu.load_registers_from_buf(&reg_bytes);
-    if result {
-        log::trace!("Successfully set up registers!");
-
-        println!("Initial register state:");
-
-        println!(
-            "{}",
-            RegisterPrinter {
-                regs: &initial_regs,
-                final_regs: None,
-                cpu_type,
-                options: 0,
-            }
-        );
-
-        let print_opts = PrintOptions {
-            print_pgm: true,
-            print_preload: false,
-            print_finalize: false,
-        };
-
-        let run_options = RunOptions {
-            automatic: args.automatic,
-            cycle_limit: Some(10_000),
-            wait_states: None,
-            print_opts,
-            ..Default::default()
-        };
-
-        match cpu.run(&run_options) {
-            Ok(regs) => {
-                println!("Final register state:");
-                println!(
-                    "{}",
-                    RegisterPrinter {
-                        regs: &initial_regs,
-                        final_regs: Some(&regs),
-                        cpu_type,
-                        options: 0,
-                    }
-                );
-            }
-            Err(e) => {
-                log::error!("Program execution failed: {}", e);
-            }
-        }
-    }
-    else {
-        log::error!("Register setup failed: {}", cpu.get_last_error());
-    }
+    exec_program::run(&args.conn, &args.run);
 }
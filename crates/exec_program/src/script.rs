@@ -0,0 +1,390 @@
+//! Scripted hardware sessions.
+//!
+//! A script is a text file of commands, one per line, that drives the CPU through a session and
+//! checks the results, so a regression scenario can be repeated without writing Rust. Arguments
+//! are separated by whitespace and `#` starts a comment. Addresses, register values and bytes are
+//! hex, with an optional `0x` prefix; cycle and instruction counts are decimal. Paths are relative
+//! to the script.
+//!
+//! | Command                     | Effect                                                           |
+//! |-----------------------------|------------------------------------------------------------------|
+//! | `connect`                   | Connect to the CPU server and detect the CPU.                    |
+//! | `regs FILE`                 | Start from the registers in a register file.                     |
+//! | `reg NAME VALUE`            | Set a register, such as `reg ax 1234`.                           |
+//! | `program FILE [ADDR]`       | Mount a program at ADDR, or at CS:IP.                            |
+//! | `data ADDR FILE`            | Write a file to memory without treating it as code.              |
+//! | `mem ADDR BYTE...`          | Write bytes to memory.                                           |
+//! | `run [LIMIT]`               | Load the registers and run, failing after LIMIT cycles (10000).  |
+//! | `expect NAME VALUE`         | Check a final register, or the `cycles` or `instructions` count. |
+//! | `expect_mem ADDR BYTE...`   | Check bytes in memory after the run.                             |
+//! | `print regs`                | Print the final registers of the last run.                       |
+//! | `print mem ADDR LEN`        | Print LEN bytes of memory.                                       |
+//! | `echo TEXT`                 | Print TEXT.                                                      |
+//!
+//! The whole script is parsed before anything runs, so a typo doesn't waste a session on the
+//! hardware. A failed expectation is reported and the script carries on; any other error stops it.
+
+use std::path::{Path, PathBuf};
+
+use arduinox86_client::*;
+use arduinox86_config::ConnectionArgs;
+use arduinox86_cpu::{arduinox86_client, *};
+
+use crate::{diag_cpu, DiagOptions};
+
+const DEFAULT_CYCLE_LIMIT: u32 = 10_000;
+/// Bytes per line when printing memory.
+const DUMP_LINE_LEN: usize = 16;
+
+/// The registers a script can name. The 286 register set is addressed by the same names.
+const REGISTER_NAMES: [&str; 14] = [
+    "ax", "bx", "cx", "dx", "sp", "bp", "si", "di", "cs", "ds", "es", "ss", "ip", "flags",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    Register(String),
+    Cycles,
+    Instructions,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Statement {
+    Connect,
+    Regs(PathBuf),
+    Reg(String, u16),
+    Program(PathBuf, Option<u32>),
+    Data(u32, PathBuf),
+    Mem(u32, Vec<u8>),
+    Run(u32),
+    Expect(Check, u32),
+    ExpectMem(u32, Vec<u8>),
+    PrintRegs,
+    PrintMem(u32, usize),
+    Echo(String),
+}
+
+impl Statement {
+    /// True if the statement talks to the CPU or depends on its type, and so must come after
+    /// `connect`.
+    fn needs_cpu(&self) -> bool {
+        matches!(
+            self,
+            Statement::Reg(..)
+                | Statement::Program(..)
+                | Statement::Data(..)
+                | Statement::Mem(..)
+                | Statement::Run(..)
+                | Statement::ExpectMem(..)
+                | Statement::PrintMem(..)
+        )
+    }
+}
+
+/// A parsed script: each statement with its 1-based line number.
+#[derive(Debug)]
+pub struct Script {
+    pub statements: Vec<(usize, Statement)>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read script {:?}: {}", path, e))?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Parse a script, resolving paths against `base_dir`.
+    pub fn parse(text: &str, base_dir: &Path) -> Result<Self, String> {
+        let mut statements = Vec::new();
+        let mut connected = false;
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let statement = parse_statement(line, base_dir).map_err(|e| format!("line {}: {}", line_no, e))?;
+            if statement.needs_cpu() && !connected {
+                return Err(format!("line {}: '{}' before 'connect'", line_no, line));
+            }
+            connected |= statement == Statement::Connect;
+            statements.push((line_no, statement));
+        }
+        Ok(Script { statements })
+    }
+}
+
+fn parse_statement(line: &str, base_dir: &Path) -> Result<Statement, String> {
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let args: Vec<&str> = rest.split_whitespace().collect();
+    let path = |arg: &str| base_dir.join(arg);
+
+    let statement = match (command, args.as_slice()) {
+        ("connect", []) => Statement::Connect,
+        ("regs", [file]) => Statement::Regs(path(file)),
+        ("reg", [name, value]) => Statement::Reg(register_name(name)?, parse_hex(value)? as u16),
+        ("program", [file]) => Statement::Program(path(file), None),
+        ("program", [file, address]) => Statement::Program(path(file), Some(parse_hex(address)?)),
+        ("data", [address, file]) => Statement::Data(parse_hex(address)?, path(file)),
+        ("mem", [address, bytes @ ..]) if !bytes.is_empty() => Statement::Mem(parse_hex(address)?, parse_bytes(bytes)?),
+        ("run", []) => Statement::Run(DEFAULT_CYCLE_LIMIT),
+        ("run", [limit]) => Statement::Run(parse_dec(limit)?),
+        ("expect", ["cycles", count]) => Statement::Expect(Check::Cycles, parse_dec(count)?),
+        ("expect", ["instructions", count]) => Statement::Expect(Check::Instructions, parse_dec(count)?),
+        ("expect", [name, value]) => Statement::Expect(Check::Register(register_name(name)?), parse_hex(value)?),
+        ("expect_mem", [address, bytes @ ..]) if !bytes.is_empty() => {
+            Statement::ExpectMem(parse_hex(address)?, parse_bytes(bytes)?)
+        }
+        ("print", ["regs"]) => Statement::PrintRegs,
+        ("print", ["mem", address, len]) => Statement::PrintMem(parse_hex(address)?, parse_hex(len)? as usize),
+        ("echo", _) => Statement::Echo(rest.trim().to_string()),
+        ("connect" | "regs" | "reg" | "program" | "data" | "mem" | "run" | "expect" | "expect_mem" | "print", _) => {
+            return Err(format!("wrong arguments for '{}'", command));
+        }
+        _ => return Err(format!("unknown command '{}'", command)),
+    };
+    Ok(statement)
+}
+
+fn register_name(name: &str) -> Result<String, String> {
+    let name = name.to_lowercase();
+    if REGISTER_NAMES.contains(&name.as_str()) {
+        Ok(name)
+    }
+    else {
+        Err(format!("unknown register '{}'", name))
+    }
+}
+
+fn parse_hex(s: &str) -> Result<u32, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(|e| format!("bad hex value '{}': {}", s, e))
+}
+
+fn parse_dec(s: &str) -> Result<u32, String> {
+    s.parse::<u32>().map_err(|e| format!("bad count '{}': {}", s, e))
+}
+
+fn parse_bytes(args: &[&str]) -> Result<Vec<u8>, String> {
+    args.iter()
+        .map(|arg| match parse_hex(arg)? {
+            byte @ 0..=0xFF => Ok(byte as u8),
+            _ => Err(format!("'{}' is not a byte", arg)),
+        })
+        .collect()
+}
+
+/// Look up a register by one of the [REGISTER_NAMES]. Only the 8086 and 286 register sets can be
+/// named.
+fn register<'a>(regs: &'a mut RemoteCpuRegisters, name: &str) -> Result<&'a mut u16, String> {
+    fn lookup<'a, R: Registers16>(regs: &'a mut R, name: &str) -> Option<&'a mut u16> {
+        Some(match name {
+            "ax" => regs.ax_mut(),
+            "bx" => regs.bx_mut(),
+            "cx" => regs.cx_mut(),
+            "dx" => regs.dx_mut(),
+            "sp" => regs.sp_mut(),
+            "bp" => regs.bp_mut(),
+            "si" => regs.si_mut(),
+            "di" => regs.di_mut(),
+            "cs" => regs.cs_mut(),
+            "ds" => regs.ds_mut(),
+            "es" => regs.es_mut(),
+            "ss" => regs.ss_mut(),
+            "ip" => regs.ip_mut(),
+            "flags" => regs.flags_mut(),
+            _ => return None,
+        })
+    }
+
+    let reg = match regs {
+        RemoteCpuRegisters::V1(regs) => lookup(regs, name),
+        RemoteCpuRegisters::V2(regs) => lookup(regs, name),
+        RemoteCpuRegisters::V3(_) => return Err("the 386 register set can't be named in scripts".to_string()),
+    };
+    reg.ok_or_else(|| format!("unknown register '{}'", name))
+}
+
+/// Runs a [Script] against the CPU, keeping the state that carries from one statement to the next.
+struct ScriptRunner<'a> {
+    conn: &'a ConnectionArgs,
+    opts: &'a DiagOptions,
+    cpu: Option<RemoteCpu<'static>>,
+    regs: Option<RemoteCpuRegisters>,
+    last_run: Option<RunResult>,
+    checks: usize,
+    failures: usize,
+}
+
+impl ScriptRunner<'_> {
+    fn cpu(&mut self) -> Result<&mut RemoteCpu<'static>, String> {
+        self.cpu.as_mut().ok_or_else(|| "not connected".to_string())
+    }
+
+    fn last_run(&self) -> Result<&RunResult, String> {
+        self.last_run
+            .as_ref()
+            .ok_or_else(|| "no program has run yet".to_string())
+    }
+
+    /// The registers to start from, defaulting to the reset state of the detected CPU.
+    fn regs_mut(&mut self) -> Result<&mut RemoteCpuRegisters, String> {
+        if self.regs.is_none() {
+            let regs = match self.cpu()?.cpu_type() {
+                ServerCpuType::Intel80286 => RemoteCpuRegisters::V2(RemoteCpuRegistersV2::default()),
+                ServerCpuType::Intel80386 => {
+                    return Err("load a register file with 'regs' before setting 386 registers".to_string())
+                }
+                _ => RemoteCpuRegisters::V1(RemoteCpuRegistersV1::default()),
+            };
+            self.regs = Some(regs);
+        }
+        Ok(self.regs.as_mut().expect("registers were just set"))
+    }
+
+    fn check(&mut self, line_no: usize, passed: bool, what: String) {
+        self.checks += 1;
+        if !passed {
+            self.failures += 1;
+            println!("[FAIL] line {}: {}", line_no, what);
+        }
+    }
+
+    fn execute(&mut self, line_no: usize, statement: &Statement) -> Result<(), String> {
+        let automatic = self.opts.automatic;
+        match statement {
+            Statement::Connect => {
+                self.cpu = Some(diag_cpu(self.conn, self.opts));
+            }
+            Statement::Regs(file) => {
+                let bytes = std::fs::read(file).map_err(|e| format!("couldn't read {:?}: {}", file, e))?;
+                self.regs = Some(RemoteCpuRegisters::try_from(bytes.as_slice())?);
+            }
+            Statement::Reg(name, value) => {
+                *register(self.regs_mut()?, name)? = *value;
+            }
+            Statement::Program(file, address) => {
+                let bytes = std::fs::read(file).map_err(|e| format!("couldn't read {:?}: {}", file, e))?;
+                let address = match address {
+                    Some(address) => *address,
+                    None => self.regs_mut()?.code_address(),
+                };
+                let cpu = self.cpu()?;
+                cpu.mount_bin(automatic, &bytes, address as usize)?;
+                cpu.setup_ivt();
+            }
+            Statement::Data(address, file) => {
+                let bytes = std::fs::read(file).map_err(|e| format!("couldn't read {:?}: {}", file, e))?;
+                self.cpu()?.load_data(automatic, *address, &bytes)?;
+            }
+            Statement::Mem(address, bytes) => {
+                self.cpu()?.load_data(automatic, *address, bytes)?;
+            }
+            Statement::Run(limit) => {
+                let mut reg_buf = std::io::Cursor::new(Vec::new());
+                self.regs_mut()?.write(&mut reg_buf).map_err(|e| e.to_string())?;
+                let cpu = self.cpu()?;
+                if !cpu.load_registers_from_buf(reg_buf.get_ref()) {
+                    return Err(format!("register setup failed: {}", cpu.get_last_error()));
+                }
+
+                let run_options = RunOptions {
+                    automatic,
+                    cycle_limit: Some(*limit),
+                    print_opts: PrintOptions {
+                        print_pgm: false,
+                        print_preload: false,
+                        print_finalize: false,
+                    },
+                    ..Default::default()
+                };
+                let result = cpu.run(&run_options).map_err(|e| e.to_string())?;
+                println!(
+                    "Line {}: ran {} cycles, {} instructions.",
+                    line_no, result.cycles, result.instructions
+                );
+                self.last_run = Some(result);
+            }
+            Statement::Expect(check, expected) => {
+                let mut final_regs = self.last_run()?.registers.clone();
+                let (actual, what) = match check {
+                    Check::Register(name) => {
+                        let actual = *register(&mut final_regs, name)? as u32;
+                        let what = format!("expected {} {:04X}, got {:04X}", name.to_uppercase(), expected, actual);
+                        (actual, what)
+                    }
+                    Check::Cycles => {
+                        let actual = self.last_run()?.cycles;
+                        (actual, format!("expected {} cycles, got {}", expected, actual))
+                    }
+                    Check::Instructions => {
+                        let actual = self.last_run()?.instructions;
+                        (actual, format!("expected {} instructions, got {}", expected, actual))
+                    }
+                };
+                self.check(line_no, actual == *expected, what);
+            }
+            Statement::ExpectMem(address, expected) => {
+                let actual = self.cpu()?.read_data(automatic, *address, expected.len())?;
+                let what = format!(
+                    "expected [{:05X}] {}, got {}",
+                    address,
+                    hex_bytes(expected),
+                    hex_bytes(&actual)
+                );
+                self.check(line_no, actual == *expected, what);
+            }
+            Statement::PrintRegs => {
+                let cpu_type = self.cpu()?.cpu_type();
+                println!("{}", self.last_run()?.registers.display(cpu_type));
+            }
+            Statement::PrintMem(address, len) => {
+                let bytes = self.cpu()?.read_data(automatic, *address, *len)?;
+                for (i, line) in bytes.chunks(DUMP_LINE_LEN).enumerate() {
+                    println!("[{:05X}] {}", *address as usize + i * DUMP_LINE_LEN, hex_bytes(line));
+                }
+            }
+            Statement::Echo(text) => println!("{}", text),
+        }
+        Ok(())
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// Run a script against the CPU. Returns true if the script ran to the end and every expectation
+/// passed.
+pub fn run_script(conn: &ConnectionArgs, path: &Path, opts: &DiagOptions) -> bool {
+    let script = match Script::load(path) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    let mut runner = ScriptRunner {
+        conn,
+        opts,
+        cpu: None,
+        regs: None,
+        last_run: None,
+        checks: 0,
+        failures: 0,
+    };
+    for (line_no, statement) in &script.statements {
+        if let Err(e) = runner.execute(*line_no, statement) {
+            eprintln!("Script stopped at line {}: {}", line_no, e);
+            return false;
+        }
+    }
+
+    println!(
+        "{}/{} expectations passed.",
+        runner.checks - runner.failures,
+        runner.checks
+    );
+    runner.failures == 0
+}
@@ -0,0 +1,221 @@
+//! Built-in program suites for smoke-testing a rig.
+//!
+//! A suite is a TOML file embedded in the binary. Each program carries its
+//! machine code, initial register overrides and the final register values it
+//! must produce, so a suite can be run without any external files.
+
+use std::collections::BTreeMap;
+
+use arduinox86_client::*;
+use arduinox86_cpu::{arduinox86_client, *};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+const TORTURE_SUITE: &str = include_str!("../torture/suite.toml");
+
+const DEFAULT_CS: u16 = 0xF000;
+const DEFAULT_IP: u16 = 0x0100;
+const DEFAULT_FLAGS: u16 = 0xF002;
+const DEFAULT_SP: u16 = 0xFFFE;
+
+const FLAGS_KEY: &str = "flags";
+const FLAGS_MASK_KEY: &str = "flags_mask";
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum Suite {
+    /// Flag edge cases, prefetch tricks, self-modifying code and stack abuse.
+    Torture,
+}
+
+impl Suite {
+    fn source(&self) -> &'static str {
+        match self {
+            Suite::Torture => TORTURE_SUITE,
+        }
+    }
+
+    pub fn load(&self) -> Result<Vec<SuiteProgram>, String> {
+        let file: SuiteFile = toml::from_str(self.source()).map_err(|e| format!("Bad suite definition: {e}"))?;
+        for program in &file.program {
+            program.validate()?;
+        }
+        Ok(file.program)
+    }
+}
+
+#[derive(Deserialize)]
+struct SuiteFile {
+    program: Vec<SuiteProgram>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SuiteProgram {
+    pub name: String,
+    pub category: String,
+    pub description: String,
+    pub code: String,
+    pub source: String,
+    #[serde(default)]
+    pub init: BTreeMap<String, u16>,
+    pub expect: BTreeMap<String, u16>,
+}
+
+impl SuiteProgram {
+    pub fn id(&self) -> String {
+        format!("{}/{}", self.category, self.name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.code_bytes()?;
+        self.initial_regs()?;
+        for name in self.expect.keys() {
+            if name != FLAGS_MASK_KEY && register(&mut RemoteCpuRegistersV1::default(), name).is_none() {
+                return Err(format!("{}: unknown register '{}' in expect", self.name, name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the hex string in `code` into bytes.
+    pub fn code_bytes(&self) -> Result<Vec<u8>, String> {
+        self.code
+            .split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).map_err(|e| format!("{}: bad code byte '{}': {}", self.name, b, e)))
+            .collect()
+    }
+
+    /// Build the initial register state from the defaults and the program's overrides.
+    pub fn initial_regs(&self) -> Result<RemoteCpuRegistersV1, String> {
+        let mut regs = RemoteCpuRegistersV1 {
+            cs: DEFAULT_CS,
+            ip: DEFAULT_IP,
+            flags: DEFAULT_FLAGS,
+            sp: DEFAULT_SP,
+            ..Default::default()
+        };
+        for (name, value) in &self.init {
+            *register(&mut regs, name).ok_or_else(|| format!("{}: unknown register '{}' in init", self.name, name))? =
+                *value;
+        }
+        Ok(regs)
+    }
+
+    /// Compare final registers against the expected values. Returns a description of each mismatch.
+    pub fn check(&self, final_regs: &RemoteCpuRegistersV1) -> Vec<String> {
+        let mut regs = final_regs.clone();
+        let flags_mask = self.expect.get(FLAGS_MASK_KEY).copied().unwrap_or(0xFFFF);
+        let mut mismatches = Vec::new();
+
+        for (name, expected) in &self.expect {
+            if name == FLAGS_MASK_KEY {
+                continue;
+            }
+            let Some(actual) = register(&mut regs, name).copied()
+            else {
+                continue;
+            };
+            let (expected, actual) = if name == FLAGS_KEY {
+                (expected & flags_mask, actual & flags_mask)
+            }
+            else {
+                (*expected, actual)
+            };
+            if expected != actual {
+                mismatches.push(format!("{}: expected {:04X} got {:04X}", name.to_uppercase(), expected, actual));
+            }
+        }
+        mismatches
+    }
+}
+
+fn register<'a>(regs: &'a mut RemoteCpuRegistersV1, name: &str) -> Option<&'a mut u16> {
+    match name {
+        "ax" => Some(&mut regs.ax),
+        "bx" => Some(&mut regs.bx),
+        "cx" => Some(&mut regs.cx),
+        "dx" => Some(&mut regs.dx),
+        "ip" => Some(&mut regs.ip),
+        "cs" => Some(&mut regs.cs),
+        "flags" => Some(&mut regs.flags),
+        "ss" => Some(&mut regs.ss),
+        "sp" => Some(&mut regs.sp),
+        "ds" => Some(&mut regs.ds),
+        "es" => Some(&mut regs.es),
+        "bp" => Some(&mut regs.bp),
+        "si" => Some(&mut regs.si),
+        "di" => Some(&mut regs.di),
+        _ => None,
+    }
+}
+
+/// Run a single suite program. Returns a list of mismatches, or an error if the program could not be run.
+fn run_program(cpu: &mut RemoteCpu, program: &SuiteProgram, automatic: bool) -> Result<Vec<String>, String> {
+    let code = program.code_bytes()?;
+    let regs = program.initial_regs()?;
+
+    cpu.mount_bin(automatic, &code, regs.calculate_code_address() as usize)?;
+    cpu.setup_ivt();
+    if !cpu.load_registers_from_buf(&regs.to_bytes()) {
+        return Err(format!("Register setup failed: {}", cpu.get_last_error()));
+    }
+
+    let run_options = RunOptions {
+        automatic,
+        cycle_limit: Some(10_000),
+        print_opts: PrintOptions {
+            print_pgm: false,
+            print_preload: false,
+            print_finalize: false,
+        },
+        ..Default::default()
+    };
+
+    match cpu.run(&run_options).map_err(|e| e.to_string())?.registers {
+        RemoteCpuRegisters::V1(final_regs) => Ok(program.check(&final_regs)),
+        _ => Err("CPU returned an unexpected register set".to_string()),
+    }
+}
+
+/// Run every program in the suite against the connected CPU and print a pass/fail report.
+/// Returns true if all programs passed.
+pub fn run_suite(cpu: &mut RemoteCpu, suite: Suite, automatic: bool) -> bool {
+    match cpu.cpu_type() {
+        ServerCpuType::Intel80286 | ServerCpuType::Intel80386 | ServerCpuType::Undetected => {
+            eprintln!("The {:?} suite requires an 8086-class CPU, detected {}.", suite, cpu.cpu_type());
+            return false;
+        }
+        _ => {}
+    }
+
+    let programs = match suite.load() {
+        Ok(programs) => programs,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    let mut passed = 0;
+    for program in &programs {
+        match run_program(cpu, program, automatic) {
+            Ok(mismatches) if mismatches.is_empty() => {
+                passed += 1;
+                println!("[PASS] {:<32} {}", program.id(), program.description);
+            }
+            Ok(mismatches) => {
+                println!("[FAIL] {:<32} {}", program.id(), program.description);
+                for mismatch in mismatches {
+                    println!("         {mismatch}");
+                }
+                log::info!("Source for {}:{}", program.name, program.source);
+            }
+            Err(e) => {
+                println!("[FAIL] {:<32} {}", program.id(), program.description);
+                println!("         error: {e}");
+            }
+        }
+    }
+
+    println!("{}/{} programs passed.", passed, programs.len());
+    passed == programs.len()
+}
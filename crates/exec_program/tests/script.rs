@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use exec_program::*;
+
+const SCRIPT: &str = r#"
+# Add two registers and store the result.
+connect
+regs add.regs
+reg ax 1234
+reg BX 0x0101
+program add.bin
+mem 0x500 AA 55
+run 2000
+
+expect ax 1335     # AX = AX + BX
+expect cycles 42
+expect_mem 500 35 13
+print mem 500 2
+echo done: all checks ran
+"#;
+
+fn parse(text: &str) -> Result<Vec<Statement>, String> {
+    Script::parse(text, Path::new("scripts"))
+        .map(|script| script.statements.into_iter().map(|(_, statement)| statement).collect())
+}
+
+#[test]
+fn test_parse_script() {
+    let script = Script::parse(SCRIPT, Path::new("scripts")).unwrap();
+    let lines: Vec<usize> = script.statements.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![3, 4, 5, 6, 7, 8, 9, 11, 12, 13, 14, 15]);
+
+    let statements = parse(SCRIPT).unwrap();
+    assert_eq!(
+        statements,
+        vec![
+            Statement::Connect,
+            Statement::Regs(PathBuf::from("scripts/add.regs")),
+            Statement::Reg("ax".to_string(), 0x1234),
+            Statement::Reg("bx".to_string(), 0x0101),
+            Statement::Program(PathBuf::from("scripts/add.bin"), None),
+            Statement::Mem(0x500, vec![0xAA, 0x55]),
+            Statement::Run(2000),
+            Statement::Expect(Check::Register("ax".to_string()), 0x1335),
+            Statement::Expect(Check::Cycles, 42),
+            Statement::ExpectMem(0x500, vec![0x35, 0x13]),
+            Statement::PrintMem(0x500, 2),
+            Statement::Echo("done: all checks ran".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_defaults() {
+    let statements = parse("connect\nprogram test.bin F0000\nrun\nprint regs").unwrap();
+    assert_eq!(
+        statements[1],
+        Statement::Program(PathBuf::from("scripts/test.bin"), Some(0xF0000))
+    );
+    assert_eq!(statements[2], Statement::Run(10_000));
+    assert_eq!(statements[3], Statement::PrintRegs);
+}
+
+#[test]
+fn test_parse_errors() {
+    let err = |text: &str| parse(text).unwrap_err();
+
+    assert_eq!(err("connect\nfrobnicate"), "line 2: unknown command 'frobnicate'");
+    assert_eq!(err("connect\nreg ax"), "line 2: wrong arguments for 'reg'");
+    assert_eq!(err("connect\nreg eax 1"), "line 2: unknown register 'eax'");
+    assert_eq!(err("connect\nmem 500 100"), "line 2: '100' is not a byte");
+    assert!(err("connect\nrun lots").starts_with("line 2: bad count 'lots'"));
+    assert!(err("connect\nreg ax xyz").starts_with("line 2: bad hex value 'xyz'"));
+}
+
+#[test]
+fn test_hardware_commands_need_connect() {
+    // A register file can be named before connecting, but setting a register depends on the CPU type.
+    assert!(parse("regs test.regs\nconnect\nrun").is_ok());
+    assert_eq!(parse("reg ax 1").unwrap_err(), "line 1: 'reg ax 1' before 'connect'");
+    assert_eq!(
+        parse("# setup\nmem 500 00").unwrap_err(),
+        "line 2: 'mem 500 00' before 'connect'"
+    );
+    assert_eq!(parse("run").unwrap_err(), "line 1: 'run' before 'connect'");
+}
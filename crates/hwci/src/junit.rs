@@ -0,0 +1,115 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! A minimal JUnit XML report writer, enough for CI systems to show per-test results.
+
+use std::io::Write;
+
+pub struct TestCase {
+    pub classname: String,
+    pub name: String,
+    /// Set if the test ran and produced a mismatch.
+    pub failure: Option<String>,
+    /// Set if the test could not be run.
+    pub error: Option<String>,
+}
+
+pub struct TestSuite {
+    pub name:  String,
+    pub cases: Vec<TestCase>,
+}
+
+impl TestSuite {
+    pub fn failures(&self) -> usize {
+        self.cases.iter().filter(|case| case.failure.is_some()).count()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.cases.iter().filter(|case| case.error.is_some()).count()
+    }
+}
+
+pub fn write_report<W: Write>(writer: &mut W, suites: &[TestSuite]) -> std::io::Result<()> {
+    let tests: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let failures: usize = suites.iter().map(TestSuite::failures).sum();
+    let errors: usize = suites.iter().map(TestSuite::errors).sum();
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<testsuites name="hwci" tests="{}" failures="{}" errors="{}">"#,
+        tests, failures, errors
+    )?;
+    for suite in suites {
+        writeln!(
+            writer,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}">"#,
+            escape(&suite.name),
+            suite.cases.len(),
+            suite.failures(),
+            suite.errors()
+        )?;
+        for case in &suite.cases {
+            write!(
+                writer,
+                r#"    <testcase classname="{}" name="{}""#,
+                escape(&case.classname),
+                escape(&case.name)
+            )?;
+            match (&case.failure, &case.error) {
+                (Some(failure), _) => {
+                    writeln!(writer, ">")?;
+                    writeln!(
+                        writer,
+                        r#"      <failure message="mismatch">{}</failure>"#,
+                        escape(failure)
+                    )?;
+                    writeln!(writer, "    </testcase>")?;
+                }
+                (None, Some(error)) => {
+                    writeln!(writer, ">")?;
+                    writeln!(writer, r#"      <error message="{}"/>"#, escape(error))?;
+                    writeln!(writer, "    </testcase>")?;
+                }
+                (None, None) => writeln!(writer, "/>")?,
+            }
+        }
+        writeln!(writer, "  </testsuite>")?;
+    }
+    writeln!(writer, "</testsuites>")
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,434 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! hwci replays a random sample of MOO tests on the connected CPU and writes a JUnit XML report,
+//! so that firmware and client changes can be gated on a real-hardware regression run. It exits
+//! with a nonzero status if any test mismatches or cannot be run. The `hwci` binary and the
+//! `hwci` subcommand of `ardx86` are thin wrappers around [run].
+
+mod junit;
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use arduinox86_client::{ComparisonPolicy, ComparisonPreset, MemoryScope, ResultCache, RunKey};
+use arduinox86_config::ConnectionArgs;
+use arduinox86_cpu::{DiagnosticCategory, RemoteCpu, ReplayOptions};
+use junit::{TestCase, TestSuite};
+use moo::{
+    prelude::MooTest,
+    types::{MooRamEntry, MooRegisters, MooRegisters16},
+};
+use rand::{seq::index, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+const MANIFEST_FILENAME: &str = "manifest.toml";
+
+#[derive(clap::Args, Debug)]
+pub struct HwciArgs {
+    /// A directory of MOO files, or a set manifest listing them
+    pub tests: PathBuf,
+
+    /// Number of tests to replay, sampled across all files. Replays every test if not given
+    #[arg(long)]
+    pub sample: Option<usize>,
+
+    /// Seed for the sample. A random seed is chosen and printed if not given
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Path to write the JUnit XML report to
+    #[arg(long, default_value = "hwci.xml")]
+    pub junit: PathBuf,
+
+    /// Step the CPU from the host instead of running in automatic mode
+    #[arg(long)]
+    pub host_stepped: bool,
+
+    /// Flags bits to compare, in hex
+    #[arg(long, default_value = "FFFF")]
+    pub flags_mask: String,
+
+    /// Also compare cycle counts (automatic mode only). The same as --policy strict
+    #[arg(long, conflicts_with = "policy")]
+    pub cycles: bool,
+
+    /// Comparison policy: strict, registers_only or emulator_friendly. Defaults to strict with
+    /// --cycles and emulator_friendly otherwise, which compares registers and RAM
+    #[arg(long)]
+    pub policy: Option<ComparisonPreset>,
+
+    /// Fail a test that raises a diagnostic in any of these categories (host-stepped mode only):
+    /// desync, program_bounds, oob_write, contention, float, bus_state, cycle_limit or server
+    #[arg(long, value_delimiter = ',')]
+    pub fail_on: Vec<DiagnosticCategory>,
+
+    /// File of cached results. Tests whose inputs match a cached result are not run again
+    #[arg(long, default_value = "hwci_cache.toml")]
+    pub cache: PathBuf,
+
+    /// Run every sampled test on the hardware, ignoring the cache and leaving it unchanged
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+/// The part of a set manifest hwci needs.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    file: String,
+}
+
+/// The result cache as saved to disk, keyed by [RunKey] in hex.
+#[derive(Default, Deserialize, Serialize)]
+struct CacheFile {
+    #[serde(default)]
+    results: BTreeMap<String, CachedResult>,
+}
+
+/// The outcome of a test that ran. Tests that could not be run are not cached.
+#[derive(Clone, Deserialize, Serialize)]
+struct CachedResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failure: Option<String>,
+}
+
+/// Replay the sampled tests and write the report. Exits with a nonzero status if any test failed.
+pub fn run(conn: &ConnectionArgs, args: &HwciArgs) -> anyhow::Result<()> {
+    let flags_mask =
+        u16::from_str_radix(&args.flags_mask, 16).with_context(|| format!("Bad flags mask: {}", args.flags_mask))?;
+
+    let mut files = Vec::new();
+    for path in test_files(&args.tests)? {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // Read through the generator's MOO reader, which understands its extension chunks.
+        let test_file = test_generator::read_test_file(&path).with_context(|| format!("Reading {}", name))?;
+        files.push((name, test_file));
+    }
+
+    let candidates: Vec<(usize, usize)> = files
+        .iter()
+        .enumerate()
+        .flat_map(|(file_idx, (_, test_file))| (0..test_file.test_ct()).map(move |test_idx| (file_idx, test_idx)))
+        .collect();
+    if candidates.is_empty() {
+        bail!("No tests found in {}", args.tests.display());
+    }
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let sample_size = args.sample.unwrap_or(candidates.len()).min(candidates.len());
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut sample = index::sample(&mut rng, candidates.len(), sample_size).into_vec();
+    sample.sort_unstable();
+    println!(
+        "Replaying {} of {} tests from {} files (seed {})",
+        sample_size,
+        candidates.len(),
+        files.len(),
+        seed
+    );
+
+    let client = conn
+        .connect(5000)
+        .map_err(|e| anyhow::anyhow!("Error connecting to ArduinoX86 server: {}", e))?;
+    let mut cpu = RemoteCpu::new(client, false, false, 0, 0, 0, 0)
+        .map_err(|e| anyhow::anyhow!("Error setting up the CPU: {}", e))?;
+    println!("Detected CPU type: {:?}", cpu.cpu_type());
+
+    let options = ReplayOptions {
+        automatic: !args.host_stepped,
+        ..Default::default()
+    };
+    let policy = HwciPolicy {
+        preset: args.policy.unwrap_or(match args.cycles {
+            true => ComparisonPreset::Strict,
+            false => ComparisonPreset::EmulatorFriendly,
+        }),
+        flags_mask: flags_mask as u32,
+    };
+    println!("Comparing with the {} policy", policy.name());
+
+    let mut cache = if args.no_cache {
+        ResultCache::new()
+    }
+    else {
+        load_cache(&args.cache)?
+    };
+    // Everything besides the test itself that can change a result.
+    let run_context = format!(
+        "{:?} {:?} {:?} {:?}",
+        cpu.cpu_type(),
+        options,
+        policy,
+        args.fail_on.iter().map(DiagnosticCategory::name).collect::<Vec<_>>()
+    );
+
+    let mut suites: Vec<TestSuite> = files
+        .iter()
+        .map(|(name, _)| TestSuite {
+            name:  name.clone(),
+            cases: Vec::new(),
+        })
+        .collect();
+
+    for &candidate in &sample {
+        let (file_idx, test_idx) = candidates[candidate];
+        let (file_name, test_file) = &files[file_idx];
+        let test = &test_file.tests()[test_idx];
+
+        let mut case = TestCase {
+            classname: file_name.clone(),
+            name: format!("{:05} {}", test_idx, test.name()),
+            failure: None,
+            error: None,
+        };
+
+        let key = if args.no_cache {
+            None
+        }
+        else {
+            run_key(test, &run_context)
+        };
+        if let Some(cached) = key.as_ref().and_then(|key| cache.get(key)) {
+            if let Some(failure) = &cached.failure {
+                println!("[FAIL] {} #{} (cached): {}", file_name, test_idx, failure);
+            }
+            case.failure = cached.failure.clone();
+            suites[file_idx].cases.push(case);
+            continue;
+        }
+
+        match cpu.replay_with(test, &options, &policy) {
+            Ok(result) if result.passed() => {
+                log::debug!("{}", result);
+            }
+            Ok(result) => {
+                println!("[FAIL] {} #{}: {}", file_name, test_idx, result);
+                case.failure = Some(result.to_string());
+            }
+            Err(e) => {
+                println!("[ERROR] {} #{}: {}", file_name, test_idx, e);
+                case.error = Some(e);
+            }
+        }
+        if case.error.is_none() {
+            let diagnostics: Vec<String> = cpu
+                .diagnostics()
+                .iter()
+                .filter(|diagnostic| args.fail_on.contains(&diagnostic.category))
+                .map(|diagnostic| diagnostic.to_string())
+                .collect();
+            if !diagnostics.is_empty() {
+                println!("[FAIL] {} #{}: {}", file_name, test_idx, diagnostics.join("; "));
+                let failure = case.failure.get_or_insert_with(String::new);
+                for diagnostic in diagnostics {
+                    if !failure.is_empty() {
+                        failure.push('\n');
+                    }
+                    failure.push_str(&diagnostic);
+                }
+            }
+        }
+        if let (Some(key), None) = (key, &case.error) {
+            cache.insert(
+                key,
+                CachedResult {
+                    failure: case.failure.clone(),
+                },
+            );
+        }
+        suites[file_idx].cases.push(case);
+    }
+
+    if !args.no_cache {
+        if cache.hits() > 0 {
+            println!(
+                "{} of {} results taken from {}",
+                cache.hits(),
+                sample_size,
+                args.cache.display()
+            );
+        }
+        save_cache(&args.cache, &cache)?;
+    }
+
+    // Only report files that had tests sampled.
+    suites.retain(|suite| !suite.cases.is_empty());
+
+    let mut writer =
+        BufWriter::new(File::create(&args.junit).with_context(|| format!("Creating report {}", args.junit.display()))?);
+    junit::write_report(&mut writer, &suites).context("Writing JUnit report")?;
+
+    let failures: usize = suites.iter().map(TestSuite::failures).sum();
+    let errors: usize = suites.iter().map(TestSuite::errors).sum();
+    println!(
+        "{} passed, {} failed, {} errors. Report written to {}",
+        sample_size - failures - errors,
+        failures,
+        errors,
+        args.junit.display()
+    );
+
+    if failures + errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// A preset with the compared flags limited to `--flags-mask`.
+#[derive(Debug)]
+struct HwciPolicy {
+    preset: ComparisonPreset,
+    flags_mask: u32,
+}
+
+impl ComparisonPolicy for HwciPolicy {
+    fn name(&self) -> &str {
+        self.preset.name()
+    }
+
+    fn compare_register(&self, register: &str) -> bool {
+        self.preset.compare_register(register)
+    }
+
+    fn flags_mask(&self) -> u32 {
+        self.preset.flags_mask() & self.flags_mask
+    }
+
+    fn memory_scope(&self) -> MemoryScope {
+        self.preset.memory_scope()
+    }
+
+    fn cycle_tolerance(&self) -> Option<usize> {
+        self.preset.cycle_tolerance()
+    }
+
+    fn undefined_as_wildcard(&self) -> bool {
+        self.preset.undefined_as_wildcard()
+    }
+}
+
+/// Build the cache key for a test from everything that decides its result: the instruction bytes
+/// at CS:IP, the initial registers and RAM, the expected final state and `run_context`. Returns
+/// None for tests hwci can't replay.
+fn run_key(test: &MooTest, run_context: &str) -> Option<RunKey> {
+    let (MooRegisters::Sixteen(initial), MooRegisters::Sixteen(expected)) = (test.initial_regs(), test.final_regs())
+    else {
+        return None;
+    };
+    let ram = |entries: &[MooRamEntry]| -> Vec<(u32, u8)> {
+        entries.iter().map(|entry| (entry.address, entry.value)).collect()
+    };
+
+    Some(
+        RunKey::builder()
+            .program(RemoteCpu::calc_linear_address(initial.cs, initial.ip), test.bytes())
+            .registers(&registers16_bytes(initial))
+            .ram(&ram(&test.initial_mem_state().entries))
+            .extra(&registers16_bytes(expected))
+            .extra(&ram(&test.final_mem_state().entries))
+            .extra(test.name().as_bytes())
+            .extra(run_context.as_bytes())
+            .build(),
+    )
+}
+
+fn registers16_bytes(regs: &MooRegisters16) -> Vec<u8> {
+    [
+        regs.ax, regs.bx, regs.cx, regs.dx, regs.sp, regs.bp, regs.si, regs.di, regs.cs, regs.ds, regs.es, regs.ss,
+        regs.ip, regs.flags,
+    ]
+    .iter()
+    .flat_map(|reg| reg.to_le_bytes())
+    .collect()
+}
+
+/// Load the result cache, or start an empty one if the file doesn't exist yet.
+fn load_cache(path: &Path) -> anyhow::Result<ResultCache<CachedResult>> {
+    if !path.is_file() {
+        return Ok(ResultCache::new());
+    }
+    let text = std::fs::read_to_string(path).with_context(|| format!("Reading cache {}", path.display()))?;
+    let file: CacheFile = toml::from_str(&text).with_context(|| format!("Parsing cache {}", path.display()))?;
+    file.results
+        .into_iter()
+        .map(|(key, result)| Ok((key.parse::<RunKey>().map_err(anyhow::Error::msg)?, result)))
+        .collect()
+}
+
+fn save_cache(path: &Path, cache: &ResultCache<CachedResult>) -> anyhow::Result<()> {
+    let file = CacheFile {
+        results: cache
+            .iter()
+            .map(|(key, result)| (key.to_string(), result.clone()))
+            .collect(),
+    };
+    let text = toml::to_string(&file).context("Serializing cache")?;
+    std::fs::write(path, text).with_context(|| format!("Writing cache {}", path.display()))
+}
+
+/// Resolve the test path to a list of MOO files. A manifest lists them explicitly; a directory
+/// without one is scanned for files with a .MOO extension.
+fn test_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Reading manifest {}", path.display()))?;
+        let manifest: Manifest =
+            toml::from_str(&text).with_context(|| format!("Parsing manifest {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        return Ok(manifest.files.iter().map(|entry| dir.join(&entry.file)).collect());
+    }
+
+    // Prefer the set's own manifest if the directory has one.
+    let manifest_path = path.join(MANIFEST_FILENAME);
+    if manifest_path.is_file() {
+        return test_files(&manifest_path);
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path).with_context(|| format!("Reading directory {}", path.display()))? {
+        let path = entry?.path();
+        if path.is_file()
+            && path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("moo"))
+                .unwrap_or(false)
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -24,19 +24,88 @@ use crate::{
     cpu_common::{BusOp, BusOpType},
     cycles::MyServerCycleState,
     registers::Registers,
+    state::bytes_from_bus_op,
     trace_log,
     Config,
     Opcode,
     TestContext,
 };
-use arduinox86_client::ServerCpuType;
-use iced_x86::{Mnemonic, OpKind};
+use arduinox86_client::{Registers16, ServerCpuType};
+use iced_x86::{Mnemonic, OpKind, Register};
 use moo::types::{MooCpuType, MooException, MooIvtOrder};
+use std::collections::VecDeque;
 
 pub struct BusOps {
     ops: Vec<BusOp>,
 }
 
+/// The memory operand of the instruction under test, resolved against the initial registers.
+struct MemoryOperand {
+    segment: Register,
+    offset: u16,
+    size: u32,
+    linear: u32,
+    limit: u32,
+    expand_down: bool,
+}
+
+impl MemoryOperand {
+    /// Resolve the explicit memory operand of `instruction`. The linear address is taken from the
+    /// segment's descriptor base rather than `segment << 4`, so protected-mode captures are checked
+    /// against the addresses the CPU actually used. Only 16-bit register sets are supported.
+    fn resolve(registers: &Registers, instruction: &iced_x86::Instruction) -> Option<MemoryOperand> {
+        let regs16 = registers.as_registers16()?;
+        let segment = instruction.memory_segment();
+        let base = address_register(regs16, instruction.memory_base())?;
+        let index = address_register(regs16, instruction.memory_index())?;
+        let offset = base
+            .wrapping_add(index)
+            .wrapping_add(instruction.memory_displacement32() as u16);
+
+        Some(MemoryOperand {
+            segment,
+            offset,
+            size: instruction.memory_size().size().max(1) as u32,
+            linear: registers.segment_base(segment)?.wrapping_add(offset as u32) & 0xFF_FFFF,
+            limit: registers.segment_limit(segment).unwrap_or(0xFFFF),
+            expand_down: registers.segment_expand_down(segment),
+        })
+    }
+
+    /// Return the exception the CPU raises if the operand does not fit within its segment limit:
+    /// #SS for the stack segment and #GP for any other.
+    fn limit_exception(&self) -> Option<u8> {
+        let first = self.offset as u32;
+        let last = first + self.size - 1;
+        let in_bounds = if self.expand_down {
+            first > self.limit && last <= 0xFFFF
+        }
+        else {
+            last <= self.limit
+        };
+
+        match (in_bounds, self.segment) {
+            (true, _) => None,
+            (false, Register::SS) => Some(12),
+            (false, _) => Some(13),
+        }
+    }
+}
+
+/// Return the value of a register used in 16-bit effective address calculation.
+fn address_register(regs: &dyn Registers16, register: Register) -> Option<u16> {
+    match register {
+        Register::None => Some(0),
+        Register::BX => Some(regs.bx()),
+        Register::BP => Some(regs.bp()),
+        Register::SI => Some(regs.si()),
+        Register::DI => Some(regs.di()),
+        // XLAT indexes by AL.
+        Register::AL => Some(regs.ax() & 0xFF),
+        _ => None,
+    }
+}
+
 impl From<&[MyServerCycleState]> for BusOps {
     fn from(cycle_states: &[MyServerCycleState]) -> Self {
         let mut bus_ops = Vec::new();
@@ -90,6 +159,9 @@ impl BusOps {
         &self.ops
     }
 
+    /// Check the bus operations against what the instruction's operands require. If the memory
+    /// operand lies outside its segment limit, no access to it should appear and the exception the
+    /// CPU is expected to raise is returned instead.
     pub fn validate(
         &self,
         config: &Config,
@@ -98,10 +170,37 @@ impl BusOps {
         instruction: &iced_x86::Instruction,
         op0: OpKind,
         op1: OpKind,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<Option<u8>> {
         let has_memory_read = self.ops.iter().any(|op| op.op_type == BusOpType::MemRead);
         let has_memory_write = self.ops.iter().any(|op| op.op_type == BusOpType::MemWrite);
 
+        let is_286_esc = matches!(config.test_gen.cpu_type, MooCpuType::Intel80286)
+            && config.test_gen.esc_opcodes.contains(&opcode.into());
+        let memory_operand = if (op0 == OpKind::Memory || op1 == OpKind::Memory)
+            && !matches!(instruction.mnemonic(), Mnemonic::Lea)
+            && !is_286_esc
+        {
+            MemoryOperand::resolve(registers, instruction)
+        }
+        else {
+            None
+        };
+
+        if let Some(operand) = &memory_operand {
+            if let Some(exception) = operand.limit_exception() {
+                if self.accesses(operand.linear) {
+                    return Err(anyhow::anyhow!(
+                        "Memory operand at {:?}:{:04X} exceeds segment limit {:04X}, but was accessed at {:06X}.",
+                        operand.segment,
+                        operand.offset,
+                        operand.limit,
+                        operand.linear
+                    ));
+                }
+                return Ok(Some(exception));
+            }
+        }
+
         match op0 {
             OpKind::Memory => {
                 if !has_memory_read {
@@ -229,7 +328,67 @@ impl BusOps {
             }
         }
 
-        Ok(())
+        if let Some(operand) = &memory_operand {
+            if (has_memory_read || has_memory_write) && !self.accesses(operand.linear) {
+                return Err(anyhow::anyhow!(
+                    "Expected memory operand {:?}:{:04X} at linear address {:06X}, but no access to it found.",
+                    operand.segment,
+                    operand.offset,
+                    operand.linear
+                ));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Return true if any memory read or write covers the byte at `address`.
+    fn accesses(&self, address: u32) -> bool {
+        self.ops
+            .iter()
+            .filter(|op| matches!(op.op_type, BusOpType::MemRead | BusOpType::MemWrite))
+            .any(|op| bytes_from_bus_op(op).iter().any(|(addr, _)| *addr == address))
+    }
+
+    /// Return the memory writes that land on bytes held in the prefetch queue at the time of the
+    /// write. On hardware the CPU executes those stale bytes rather than the values written
+    /// (self-modifying code).
+    ///
+    /// The queue is modelled from the code fetches: it holds at most `queue_size` of the most
+    /// recently fetched bytes, is flushed when a fetch does not follow on from the previous one,
+    /// and never holds the `instr_len` bytes of the instruction under test at `code_address`, as
+    /// they have been consumed by the time it writes.
+    pub fn detect_smc(&self, queue_size: usize, code_address: u32, instr_len: usize) -> Vec<BusOp> {
+        let instr_range = code_address..code_address.wrapping_add(instr_len as u32);
+        let mut queue: VecDeque<u32> = VecDeque::with_capacity(queue_size + 1);
+        let mut smc_writes = Vec::new();
+
+        for bus_op in &self.ops {
+            match bus_op.op_type {
+                BusOpType::CodeRead => {
+                    for (addr, _) in bytes_from_bus_op(bus_op) {
+                        if queue.back().is_some_and(|last| last.wrapping_add(1) != addr) {
+                            // A non-sequential fetch means the queue was flushed.
+                            queue.clear();
+                        }
+                        queue.push_back(addr);
+                        if queue.len() > queue_size {
+                            queue.pop_front();
+                        }
+                    }
+                }
+                BusOpType::MemWrite => {
+                    if bytes_from_bus_op(bus_op)
+                        .iter()
+                        .any(|(addr, _)| !instr_range.contains(addr) && queue.contains(addr))
+                    {
+                        smc_writes.push(*bus_op);
+                    }
+                }
+                _ => {}
+            }
+        }
+        smc_writes
     }
 
     pub fn detect_exception(&self, context: &mut TestContext, cpu_type: ServerCpuType) -> Option<MooException> {
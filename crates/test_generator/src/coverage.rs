@@ -0,0 +1,286 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Audit the coverage of a test set.
+//!
+//! Counts the tests in a directory of MOO files per opcode, group extension and prefix, and prints
+//! the counts as a matrix with one row per test file. Given the generator config the set was made
+//! from, the matrix also lists the files the config would generate and the prefixes it would put
+//! on each opcode, and marks every expected cell with no tests, so gaps can be found before a set
+//! is released.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+
+use crate::{
+    gen_tests::get_group_extension_range,
+    moo_io,
+    timings::{moo_files, stem_opcode},
+    Config,
+    Opcode,
+    TestOpcodeSizePrefix,
+};
+
+/// Column label for tests without a prefix.
+const NO_PREFIX: &str = "none";
+
+/// Operand and address size prefixes select the test file rather than a column.
+const SIZE_PREFIXES: [u8; 2] = [0x66, 0x67];
+
+fn prefix_name(prefix: u8) -> String {
+    match prefix {
+        0x26 => "ES".to_string(),
+        0x2E => "CS".to_string(),
+        0x36 => "SS".to_string(),
+        0x3E => "DS".to_string(),
+        0x64 => "FS".to_string(),
+        0x65 => "GS".to_string(),
+        0xF0 => "LOCK".to_string(),
+        0xF2 => "REPNE".to_string(),
+        0xF3 => "REP".to_string(),
+        _ => format!("{:02X}", prefix),
+    }
+}
+
+/// Test counts for one test file, per prefix. Tests without a prefix are counted under None.
+#[derive(Default)]
+struct CoverageRow {
+    tests:  usize,
+    counts: BTreeMap<Option<u8>, usize>,
+}
+
+/// The test files a generator config produces, with the prefixes it may put on each.
+struct Template {
+    rows: BTreeMap<String, BTreeSet<u8>>,
+    prefixes: BTreeSet<u8>,
+}
+
+impl Template {
+    fn from_config(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        let mut config: Config = toml::from_str(&text).context("Parsing TOML into Config")?;
+        if config.test_gen.exclude_esc_opcodes {
+            config
+                .test_gen
+                .excluded_opcodes
+                .extend(config.test_gen.esc_opcodes.clone());
+        }
+        let test_gen = &config.test_gen;
+
+        let mut rows = BTreeMap::new();
+        let mut prefixes = BTreeSet::new();
+        for opcode_raw in test_gen.opcode_range[0]..=test_gen.opcode_range[1] {
+            let opcode = Opcode::from(opcode_raw);
+            if !test_gen.valid_opcodes.contains(&opcode_raw)
+                || test_gen.excluded_opcodes.contains(&opcode_raw)
+                || opcode.is_prefix(&test_gen.prefixes)
+            {
+                continue;
+            }
+
+            // Mirror the prefix rolls in instruction generation.
+            let mut expected = BTreeSet::new();
+            if test_gen.max_prefixes > 0 && !test_gen.disable_seg_overrides.contains(&opcode_raw) {
+                expected.extend(test_gen.segment_prefixes.iter().copied());
+            }
+            if test_gen.lock_prefix_chance > 0.0 && !test_gen.disable_lock_prefix.contains(&opcode_raw) {
+                expected.insert(test_gen.lock_prefix_opcode);
+            }
+            if test_gen.rep_prefix_chance > 0.0 && test_gen.rep_opcodes.contains(&opcode_raw) {
+                expected.extend(test_gen.rep_prefixes.iter().copied());
+            }
+            prefixes.extend(expected.iter().copied());
+
+            let extensions: Vec<String> = if test_gen.group_opcodes.contains(&opcode_raw) {
+                let (start, end) = get_group_extension_range(&config, opcode);
+                (start..=end).map(|ext| format!(".{:1X}", ext)).collect()
+            }
+            else {
+                vec![String::new()]
+            };
+
+            let mut size_prefixes: Vec<TestOpcodeSizePrefix> = TestOpcodeSizePrefix::iter(
+                test_gen.cpu_type,
+                opcode,
+                &test_gen.disable_operand_size_prefix,
+                &test_gen.disable_address_size_prefix,
+            )
+            .collect();
+            if size_prefixes.is_empty() {
+                size_prefixes.push(TestOpcodeSizePrefix::None);
+            }
+
+            for size_prefix in size_prefixes {
+                for ext in &extensions {
+                    let name = format!("{}{}{}", size_prefix.to_filename_prefix(), opcode, ext);
+                    rows.insert(name, expected.clone());
+                }
+            }
+        }
+        Ok(Template { rows, prefixes })
+    }
+}
+
+/// Count the tests in `path` per prefix.
+fn count_file(path: &Path, opcode: &[u8]) -> anyhow::Result<CoverageRow> {
+    let test_file = moo_io::read_test_file(path)?;
+    let mut row = CoverageRow::default();
+    for test in test_file.tests() {
+        let bytes = test.bytes();
+        let Some(opcode_offset) = bytes.iter().position(|&b| b == opcode[0])
+        else {
+            continue;
+        };
+        row.tests += 1;
+
+        let prefixes: BTreeSet<u8> = bytes[..opcode_offset]
+            .iter()
+            .copied()
+            .filter(|b| !SIZE_PREFIXES.contains(b))
+            .collect();
+        if prefixes.is_empty() {
+            *row.counts.entry(None).or_default() += 1;
+        }
+        for prefix in prefixes {
+            *row.counts.entry(Some(prefix)).or_default() += 1;
+        }
+    }
+    Ok(row)
+}
+
+/// Print the coverage matrix for the test set in `dir`. If `template` names a generator config,
+/// cells the config expects but the set lacks are marked, and the result is false if there are
+/// any. With `missing_only`, only rows with missing cells are printed.
+pub fn coverage(dir: &Path, template: Option<&Path>, missing_only: bool) -> anyhow::Result<bool> {
+    let template = template.map(Template::from_config).transpose()?;
+
+    let files = moo_files(&[dir.to_path_buf()])?;
+    if files.is_empty() && template.is_none() {
+        bail!("No MOO files found in {}", dir.display());
+    }
+
+    let mut rows: BTreeMap<String, CoverageRow> = BTreeMap::new();
+    for path in &files {
+        let stem = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_uppercase();
+        let Some(opcode) = stem_opcode(&stem)
+        else {
+            log::warn!("Can't get an opcode from file name {}, skipping", path.display());
+            continue;
+        };
+        rows.insert(stem, count_file(path, &opcode)?);
+    }
+
+    // Columns are every prefix seen in the set or expected by the template.
+    let mut columns: BTreeSet<u8> = rows
+        .values()
+        .flat_map(|row| row.counts.keys().flatten().copied())
+        .collect();
+    let mut names: BTreeSet<String> = rows.keys().cloned().collect();
+    if let Some(template) = &template {
+        columns.extend(template.prefixes.iter().copied());
+        names.extend(template.rows.keys().cloned());
+    }
+    let columns: Vec<Option<u8>> = std::iter::once(None).chain(columns.into_iter().map(Some)).collect();
+
+    print!("{:<10} {:>6}", "Opcode", "Tests");
+    for column in &columns {
+        print!(" {:>6}", column.map_or_else(|| NO_PREFIX.to_string(), prefix_name));
+    }
+    println!();
+
+    let empty = CoverageRow::default();
+    let mut missing_files = 0;
+    let mut missing_cells = 0;
+    let mut extra_files = 0;
+    for name in &names {
+        let row = rows.get(name).unwrap_or(&empty);
+        let expected = template.as_ref().map(|t| t.rows.get(name));
+
+        let mut line = format!("{:<10} {:>6}", name, row.tests);
+        let mut row_missing = 0;
+        for column in &columns {
+            let count = row.counts.get(column).copied().unwrap_or(0);
+            let is_expected = match (expected, column) {
+                (Some(Some(_)), None) => true,
+                (Some(Some(prefixes)), Some(prefix)) => prefixes.contains(prefix),
+                _ => false,
+            };
+            let cell = if count > 0 {
+                count.to_string()
+            }
+            else if is_expected {
+                row_missing += 1;
+                "MISS".to_string()
+            }
+            else {
+                ".".to_string()
+            };
+            let _ = write!(line, " {:>6}", cell);
+        }
+
+        match expected {
+            Some(None) => {
+                extra_files += 1;
+                line.push_str("  (not in template)");
+            }
+            Some(Some(_)) if !rows.contains_key(name) => {
+                missing_files += 1;
+                line.push_str("  <-- no test file");
+            }
+            _ => {}
+        }
+        missing_cells += row_missing;
+
+        if !missing_only || row_missing > 0 {
+            println!("{}", line);
+        }
+    }
+
+    println!("{} test file(s), {} prefix column(s).", rows.len(), columns.len() - 1);
+    if template.is_none() {
+        return Ok(true);
+    }
+    if extra_files > 0 {
+        println!("{} file(s) are not in the template.", extra_files);
+    }
+    if missing_files == 0 && missing_cells == 0 {
+        println!("Every cell in the template is covered.");
+        Ok(true)
+    }
+    else {
+        println!(
+            "{} file(s) missing, {} cell(s) without tests.",
+            missing_files, missing_cells
+        );
+        Ok(false)
+    }
+}
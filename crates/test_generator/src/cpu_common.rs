@@ -632,9 +632,9 @@ impl TryFrom<BusStatusByte> for BusOpType {
     fn try_from(value: BusStatusByte) -> Result<Self, Self::Error> {
         match value {
             BusStatusByte::V1(v) => match v & 0x7 {
-                0b00 => Ok(BusOpType::CodeRead),
                 0b001 => Ok(BusOpType::IoRead),
                 0b010 => Ok(BusOpType::IoWrite),
+                0b100 => Ok(BusOpType::CodeRead),
                 0b101 => Ok(BusOpType::MemRead),
                 0b110 => Ok(BusOpType::MemWrite),
                 _ => Err(()),
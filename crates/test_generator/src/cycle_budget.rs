@@ -0,0 +1,138 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Per-test cycle budgets.
+//!
+//! Each test gets a rough estimate of how many cycles its instruction should take, scaled up by
+//! the REP count or ENTER nesting level where those apply. A test that runs for longer than a
+//! configured multiple of its estimate is aborted early instead of consuming the full test
+//! timeout, and a test that completes but logs more cycles than its budget is flagged. Both are
+//! recorded in a slow opcode report written alongside the trace logs.
+
+use std::{fmt::Display, fs, path::Path};
+
+use anyhow::Context;
+use iced_x86::{Instruction, Mnemonic};
+use serde::Deserialize;
+
+use crate::{registers::Registers, Opcode};
+
+pub const SLOW_REPORT_FILENAME: &str = "slow_opcodes.txt";
+
+/// Cycles allowed for any instruction, including exception dispatch and bus wait states.
+const BASE_CYCLES: u32 = 100;
+/// Cycles allowed per iteration of a REP-prefixed string instruction.
+const REP_ITERATION_CYCLES: u32 = 16;
+/// Cycles allowed per nesting level of ENTER.
+const ENTER_LEVEL_CYCLES: u32 = 16;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CycleBudget {
+    /// Abort or flag a test that exceeds this multiple of its estimated cycle count.
+    pub multiple: f32,
+    /// Rate at which the server executes cycles, used to convert a cycle budget to a time limit.
+    pub cycles_per_ms: u32,
+    /// Minimum time limit for a test, in milliseconds.
+    pub min_ms: u32,
+}
+
+impl CycleBudget {
+    /// Return the number of cycles a test with the given estimate may take.
+    pub fn cycle_limit(&self, estimate: u32) -> u32 {
+        (estimate as f32 * self.multiple) as u32
+    }
+
+    /// Return the time a test with the given estimate may run for, capped at `timeout`.
+    pub fn time_limit_ms(&self, estimate: u32, timeout: u32) -> u32 {
+        (self.cycle_limit(estimate) / self.cycles_per_ms.max(1))
+            .max(self.min_ms)
+            .min(timeout)
+    }
+}
+
+/// Estimate the number of cycles `instruction` will take with the given initial registers.
+pub fn estimate_cycles(instruction: &Instruction, regs: &Registers) -> u32 {
+    let mut estimate = BASE_CYCLES;
+
+    if instruction.is_string_instruction() && (instruction.has_rep_prefix() || instruction.has_repne_prefix()) {
+        estimate = estimate.saturating_add(regs.ecx().saturating_mul(REP_ITERATION_CYCLES));
+    }
+
+    if instruction.mnemonic() == Mnemonic::Enter {
+        let level = (instruction.immediate8_2nd() & 0x1F) as u32;
+        estimate += level * ENTER_LEVEL_CYCLES;
+    }
+
+    estimate
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlowReason {
+    /// The test ran past its time limit and was abandoned.
+    Aborted { elapsed_ms: u32 },
+    /// The test completed, but logged more cycles than its budget.
+    Exceeded { cycles: u32 },
+}
+
+/// A test that exceeded its cycle budget.
+#[derive(Clone, Debug)]
+pub struct SlowTest {
+    pub opcode: Opcode,
+    pub test_num: usize,
+    pub gen_num: usize,
+    pub instruction: String,
+    pub bytes: Vec<u8>,
+    pub estimate: u32,
+    pub reason: SlowReason,
+}
+
+impl Display for SlowTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let reason = match self.reason {
+            SlowReason::Aborted { elapsed_ms } => format!("aborted after {} ms", elapsed_ms),
+            SlowReason::Exceeded { cycles } => format!("took {} cycles", cycles),
+        };
+        write!(
+            f,
+            "{} test {} gen {}: {} [{}] estimate {} cycles, {}",
+            self.opcode, self.test_num, self.gen_num, self.instruction, bytes, self.estimate, reason
+        )
+    }
+}
+
+/// Write the slow opcode report to `dir`, one test per line.
+pub fn write_slow_report(dir: &Path, slow_tests: &[SlowTest]) -> anyhow::Result<()> {
+    let path = dir.join(SLOW_REPORT_FILENAME);
+    let mut report = String::new();
+    for slow_test in slow_tests {
+        report.push_str(&slow_test.to_string());
+        report.push('\n');
+    }
+    fs::write(&path, report).with_context(|| format!("Writing slow opcode report: {}", path.display()))
+}
@@ -0,0 +1,212 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Cycle-stream invariant checks.
+//!
+//! Every capture is checked against a handful of bus protocol invariants before its test is
+//! accepted. A capture that breaks one of these was almost certainly corrupted on the way in, by a
+//! marginal socket contact or a noisy signal, rather than produced by the instruction under test,
+//! so it is rejected with a [CycleStreamError] instead of being compared against other captures.
+
+use std::{collections::HashMap, fmt::Display};
+
+use arduinox86_client::{CpuWidth, ServerCycleState, TState};
+
+use crate::{
+    cpu_common::{BusOp, BusOpType},
+    cycles::MyServerCycleState,
+    state::bytes_from_bus_op,
+};
+
+/// Memory read, memory write, I/O read, I/O write and interrupt acknowledge strobes. The advanced
+/// and normal write strobes of the same bus are expected to overlap, so they form a single group.
+const COMMAND_STROBE_GROUPS: [u8; 5] = [
+    ServerCycleState::COMMAND_MRDC_BIT,
+    ServerCycleState::COMMAND_AMWC_BIT | ServerCycleState::COMMAND_MWTC_BIT,
+    ServerCycleState::COMMAND_IORC_BIT,
+    ServerCycleState::COMMAND_AIOWC_BIT | ServerCycleState::COMMAND_IOWC_BIT,
+    ServerCycleState::COMMAND_INTA_BIT,
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CycleStreamError {
+    /// ALE was asserted outside of Ts/T1.
+    AleOutsideT1 { cycle: usize, t_state: u8 },
+    /// More than one kind of bus command strobe was active in the same cycle.
+    OverlappingStrobes { cycle: usize, command_bits: u8 },
+    /// A code fetch did not follow the previous fetch of the same burst.
+    FetchOutOfOrder { bus_op: usize, prev_addr: u32, addr: u32 },
+    /// An odd address with BHE inactive, which selects neither half of the data bus.
+    NoByteLane { bus_op: usize, addr: u32 },
+    /// A code fetch returned a byte that differs from the uploaded instruction.
+    CodeMismatch { bus_op: usize, addr: u32, expected: u8, found: u8 },
+}
+
+impl Display for CycleStreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CycleStreamError::AleOutsideT1 { cycle, t_state } => {
+                write!(f, "cycle {}: ALE asserted in T-state {}", cycle, t_state)
+            }
+            CycleStreamError::OverlappingStrobes { cycle, command_bits } => {
+                write!(f, "cycle {}: overlapping command strobes ({:08b})", cycle, command_bits)
+            }
+            CycleStreamError::FetchOutOfOrder {
+                bus_op,
+                prev_addr,
+                addr,
+            } => {
+                write!(
+                    f,
+                    "bus op {}: code fetch from {:06X} does not follow fetch from {:06X}",
+                    bus_op, addr, prev_addr
+                )
+            }
+            CycleStreamError::NoByteLane { bus_op, addr } => {
+                write!(f, "bus op {}: odd address {:06X} with BHE inactive", bus_op, addr)
+            }
+            CycleStreamError::CodeMismatch {
+                bus_op,
+                addr,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "bus op {}: code fetch from {:06X} returned {:02X}, uploaded {:02X}",
+                    bus_op, addr, found, expected
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CycleStreamError {}
+
+/// Check the cycle-level invariants of a capture: ALE timing and command strobe exclusivity.
+pub fn check_cycles(cycles: &[MyServerCycleState]) -> Result<(), CycleStreamError> {
+    for (cycle, my_state) in cycles.iter().enumerate() {
+        let state = ServerCycleState::from(my_state);
+
+        if state.ale() && state.t_state() != TState::T1 {
+            return Err(CycleStreamError::AleOutsideT1 {
+                cycle,
+                t_state: state.cpu_state_bits & 0x07,
+            });
+        }
+
+        // Command strobes are active low.
+        let active = !state.bus_command_bits & !ServerCycleState::COMMAND_BHE_BIT;
+        let groups = COMMAND_STROBE_GROUPS
+            .iter()
+            .filter(|group| active & **group != 0)
+            .count();
+        if groups > 1 {
+            return Err(CycleStreamError::OverlappingStrobes {
+                cycle,
+                command_bits: state.bus_command_bits,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the bus-operation invariants of a capture. `code` is the instruction uploaded at
+/// `cs_base:ip`; every fetch of those bytes must return them unchanged until they are written to.
+/// Fetch ordering is only checked if `check_fetch_order` is set, since a taken branch legitimately
+/// restarts fetching at a lower address. Byte lanes are only checked on a 16-bit bus, as 8-bit
+/// parts have no BHE pin and report another signal in its place.
+///
+/// IP wraps within the code segment, so code near the end of the segment continues at its base,
+/// and a fetch from the segment base after one from its end is in order.
+pub fn check_bus_ops(
+    bus_ops: &[BusOp],
+    cpu_width: CpuWidth,
+    cs_base: u32,
+    ip: u16,
+    code: &[u8],
+    check_fetch_order: bool,
+) -> Result<(), CycleStreamError> {
+    let mut uploaded: HashMap<u32, u8> = code
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| (cs_base.wrapping_add(ip.wrapping_add(i as u16) as u32), *byte))
+        .collect();
+    let mut prev_fetch: Option<u32> = None;
+
+    for (idx, op) in bus_ops.iter().enumerate() {
+        if matches!(cpu_width, CpuWidth::Sixteen) && op.addr & 1 != 0 && !op.bhe {
+            return Err(CycleStreamError::NoByteLane {
+                bus_op: idx,
+                addr:   op.addr,
+            });
+        }
+
+        match op.op_type {
+            BusOpType::CodeRead => {
+                if let Some(prev_addr) = prev_fetch {
+                    if check_fetch_order && op.addr <= prev_addr && !wraps_segment(cs_base, prev_addr, op.addr) {
+                        return Err(CycleStreamError::FetchOutOfOrder {
+                            bus_op: idx,
+                            prev_addr,
+                            addr: op.addr,
+                        });
+                    }
+                }
+                prev_fetch = Some(op.addr);
+
+                for (addr, found) in bytes_from_bus_op(op) {
+                    if let Some(&expected) = uploaded.get(&addr) {
+                        if expected != found {
+                            return Err(CycleStreamError::CodeMismatch {
+                                bus_op: idx,
+                                addr,
+                                expected,
+                                found,
+                            });
+                        }
+                    }
+                }
+            }
+            BusOpType::MemWrite => {
+                // Any other bus cycle ends the fetch burst.
+                prev_fetch = None;
+                // Fetches after a write see the new value, not the uploaded one.
+                for (addr, _) in bytes_from_bus_op(op) {
+                    uploaded.remove(&addr);
+                }
+            }
+            _ => {
+                prev_fetch = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return true if a fetch from `addr` continues a fetch from `prev_addr` at the end of the code
+/// segment by wrapping around to its base. The last fetch of the segment is the word or byte at
+/// offset FFFE or FFFF, depending on the bus width.
+fn wraps_segment(cs_base: u32, prev_addr: u32, addr: u32) -> bool {
+    prev_addr.wrapping_sub(cs_base) >= 0xFFFE && addr == cs_base
+}
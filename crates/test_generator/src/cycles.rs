@@ -27,6 +27,7 @@ use moo::prelude::MooCycleState;
 
 #[derive(Clone, Debug)]
 pub enum MyServerCycleState {
+    State186(ServerCycleState),
     State286(ServerCycleState),
     State386Ex(ServerCycleState),
 }
@@ -34,6 +35,7 @@ pub enum MyServerCycleState {
 impl MyServerCycleState {
     pub fn data_bus(&self) -> u16 {
         match self {
+            MyServerCycleState::State186(state) => state.data_bus,
             MyServerCycleState::State286(state) => state.data_bus,
             MyServerCycleState::State386Ex(state) => state.data_bus,
         }
@@ -43,6 +45,16 @@ impl MyServerCycleState {
 impl From<MyServerCycleState> for MooCycleState {
     fn from(wrapper: MyServerCycleState) -> Self {
         match wrapper {
+            MyServerCycleState::State186(state) => {
+                // The 186 shares the 286's pins and command bits, but its status byte is laid out as
+                // on the 8088: S0-S2 in the low bits, the segment from S3-S4 and the queue status on top.
+                let status = state.cpu_status_bits;
+                let mut moo_state = MooCycleState::from(MyServerCycleState::State286(state));
+                moo_state.bus_state = status & 0x07;
+                moo_state.segment = (status >> 3) & 0x03;
+                moo_state.queue_op = (status >> 6) & 0x03;
+                moo_state
+            }
             MyServerCycleState::State286(state) => {
                 let ale = state.bus_control_bits & 1 != 0;
                 let mut pins0 = 0u8;
@@ -172,6 +184,7 @@ impl From<MyServerCycleState> for MooCycleState {
 impl From<&MyServerCycleState> for ServerCycleState {
     fn from(wrapper: &MyServerCycleState) -> Self {
         match wrapper {
+            MyServerCycleState::State186(state) => state.clone(),
             MyServerCycleState::State286(state) => state.clone(),
             MyServerCycleState::State386Ex(state) => state.clone(),
         }
@@ -183,6 +196,21 @@ impl TryFrom<&MyServerCycleState> for BusOp {
 
     fn try_from(wrapper: &MyServerCycleState) -> Result<Self, Self::Error> {
         match wrapper {
+            MyServerCycleState::State186(state) => {
+                let status_byte = BusStatusByte::V1(state.cpu_status_bits & 0x07);
+                if let Ok(op_type) = BusOpType::try_from(status_byte) {
+                    let bus_op = BusOp {
+                        idx: 0,
+                        op_type,
+                        addr: state.address_bus,
+                        // Only BHE on the 16-bit parts; the 80188 reports RFSH in this bit.
+                        bhe: state.bus_command_bits & 0x80 == 0,
+                        data: state.data_bus,
+                        flags: 0,
+                    };
+                    return Ok(bus_op);
+                }
+            }
             MyServerCycleState::State286(state) => {
                 let status_byte = BusStatusByte::V2(state.cpu_status_bits & 0x0F);
                 //log::trace!("Bus status byte: {:?}", status_byte);
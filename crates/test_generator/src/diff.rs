@@ -0,0 +1,178 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Compare two MOO files test by test.
+//!
+//! Meant for regression-checking a test set regenerated after a firmware change: tests are paired
+//! by index, and for each pair that differs the final registers, the final RAM and the cycles are
+//! compared under a [ComparisonPolicy]. Cycles are printed side by side, one row per cycle index,
+//! with differing rows marked.
+
+use std::path::Path;
+
+use anyhow::Context;
+use arduinox86_client::{ComparisonPolicy, MooTestComparison};
+use moo::prelude::*;
+
+use crate::moo_io;
+
+/// Width of a formatted cycle, for padding the left column.
+const CYCLE_WIDTH: usize = 36;
+
+fn format_cycle(cycle: &MooCycleState) -> String {
+    let ale = match cycle.pins0 & MooCycleState::PIN_ALE != 0 {
+        true => 'A',
+        false => ' ',
+    };
+    format!(
+        "{} {:08X} {:04X} M:{:03b} I:{:03b} B:{:X} T:{:X}",
+        ale, cycle.address_bus, cycle.data_bus, cycle.memory_status, cycle.io_status, cycle.bus_state, cycle.t_state
+    )
+}
+
+/// Differences between two tests with the same index.
+struct TestDiff {
+    bytes: bool,
+    comparison: MooTestComparison,
+    cycles: bool,
+}
+
+impl TestDiff {
+    fn new(policy: &dyn ComparisonPolicy, left: &MooTest, right: &MooTest) -> Self {
+        let comparison = MooTestComparison::new(policy, left, right, 0);
+
+        // With no tolerance for cycle counts, the cycles themselves must match as well.
+        let cycles = comparison.cycles.is_some()
+            || (policy.cycle_tolerance() == Some(0)
+                && left
+                    .cycles()
+                    .iter()
+                    .zip(right.cycles())
+                    .any(|(left, right)| format_cycle(left) != format_cycle(right)));
+
+        TestDiff {
+            bytes: left.bytes() != right.bytes(),
+            comparison,
+            cycles,
+        }
+    }
+
+    fn regs(&self) -> bool {
+        self.comparison.register_width || !self.comparison.registers.is_empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.bytes && !self.regs() && self.comparison.ram.is_empty() && !self.cycles
+    }
+}
+
+fn print_cycles(left: &[MooCycleState], right: &[MooCycleState]) {
+    println!("  {:>5}  {:<w$}  {}", "#", "LEFT", "RIGHT", w = CYCLE_WIDTH);
+    for i in 0..left.len().max(right.len()) {
+        let left_str = left.get(i).map(format_cycle).unwrap_or_default();
+        let right_str = right.get(i).map(format_cycle).unwrap_or_default();
+        let mark = if left_str != right_str { '*' } else { ' ' };
+        println!("{} {:>5}  {:<w$}  {}", mark, i, left_str, right_str, w = CYCLE_WIDTH);
+    }
+}
+
+fn print_diff(index: usize, left: &MooTest, right: &MooTest, diff: &TestDiff) {
+    println!("Test {} ({}):", index, left.name());
+    if diff.bytes {
+        // A different instruction makes everything after it meaningless to compare.
+        println!("  Instruction differs: {:02X?} | {:02X?}", left.bytes(), right.bytes());
+        return;
+    }
+    if diff.comparison.register_width {
+        println!("  Register sets differ in width.");
+    }
+    for mismatch in &diff.comparison.registers {
+        println!(
+            "  {} mismatch: {:04X} != {:04X}",
+            mismatch.register, mismatch.expected, mismatch.actual
+        );
+    }
+    for (address, left_value, right_value) in &diff.comparison.ram {
+        let show = |value: &Option<u8>| value.map(|v| format!("{:02X}", v)).unwrap_or_else(|| "--".to_string());
+        println!("  RAM [{:08X}]: {} | {}", address, show(left_value), show(right_value));
+    }
+    if diff.cycles {
+        println!("  Cycles: {} | {}", left.cycles().len(), right.cycles().len());
+        print_cycles(left.cycles(), right.cycles());
+    }
+    println!();
+}
+
+/// Compare the tests of two MOO files under `policy`, printing the first `max_tests` that differ
+/// (all of them if zero), or only the totals with `summary`. Returns true if the files hold the
+/// same tests.
+pub fn diff(
+    left_path: &Path,
+    right_path: &Path,
+    policy: &dyn ComparisonPolicy,
+    max_tests: usize,
+    summary: bool,
+) -> anyhow::Result<bool> {
+    let left_file = moo_io::read_test_file(left_path).with_context(|| format!("Reading {}", left_path.display()))?;
+    let right_file = moo_io::read_test_file(right_path).with_context(|| format!("Reading {}", right_path.display()))?;
+
+    let left_tests = left_file.tests();
+    let right_tests = right_file.tests();
+    if left_tests.len() != right_tests.len() {
+        println!(
+            "Test counts differ: {} | {}. Comparing the first {}.",
+            left_tests.len(),
+            right_tests.len(),
+            left_tests.len().min(right_tests.len())
+        );
+    }
+
+    let mut differing = 0;
+    let (mut regs_ct, mut ram_ct, mut cycles_ct, mut bytes_ct) = (0, 0, 0, 0);
+    for (index, (left, right)) in left_tests.iter().zip(right_tests.iter()).enumerate() {
+        let diff = TestDiff::new(policy, left, right);
+        if diff.is_empty() {
+            continue;
+        }
+
+        if !summary && (max_tests == 0 || differing < max_tests) {
+            print_diff(index, left, right, &diff);
+        }
+        differing += 1;
+        bytes_ct += diff.bytes as usize;
+        regs_ct += diff.regs() as usize;
+        ram_ct += !diff.comparison.ram.is_empty() as usize;
+        cycles_ct += diff.cycles as usize;
+    }
+
+    println!(
+        "{} of {} tests differ: {} in instruction, {} in registers, {} in RAM, {} in cycles.",
+        differing,
+        left_tests.len().min(right_tests.len()),
+        bytes_ct,
+        regs_ct,
+        ram_ct,
+        cycles_ct
+    );
+    Ok(differing == 0 && left_tests.len() == right_tests.len())
+}
@@ -0,0 +1,174 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Dump generated instruction sequences without the hardware.
+//!
+//! Runs the same instruction and register generation as a test set, including the prefix rolls
+//! and the masking fixups, but writes each sequence to its own `.bin` file instead of running it.
+//! The initial registers go alongside in a `.regs` file, in the load format `exec_program` takes,
+//! and `programs.toml` lists every program with its seeds. This lets the encoding logic be
+//! reviewed and diffed between generator versions, and the programs reused by other tools.
+//!
+//! Each program is the first generation attempt for its test. Generation on hardware may reject
+//! an attempt and generate another, so a published test can differ from its dumped program.
+
+use std::{fs, io::Cursor, path::Path};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{
+    gen_tests::{file_seed, generate_sequence, get_group_extension_range, get_test_count, mask_rep_count},
+    Config,
+    Opcode,
+    QueueStressTarget,
+    TestContext,
+    TestOpcodeSizePrefix,
+};
+
+pub const PROGRAMS_FILENAME: &str = "programs.toml";
+
+/// A dumped program, as listed in the programs manifest.
+#[derive(Serialize)]
+struct ProgramEntry {
+    /// The instruction sequence file.
+    file: String,
+    /// The initial register file.
+    registers: String,
+    /// The test file the program belongs to, and its index in it.
+    test_file: String,
+    test: usize,
+    name: String,
+    // Seeds are written as hex strings, as TOML integers can't hold every u64.
+    file_seed: String,
+    reg_seed: String,
+    instruction_address: u32,
+    /// The queue stress layout applied to the program, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queue_stress: Option<QueueStressTarget>,
+}
+
+#[derive(Serialize)]
+struct ProgramManifest {
+    cpu_type:  String,
+    base_seed: String,
+    #[serde(rename = "program")]
+    programs:  Vec<ProgramEntry>,
+}
+
+/// Generate the instruction sequence and registers for every test the config describes, and
+/// write them to `dir` with a manifest.
+pub fn dump_programs(context: &mut TestContext, config: &Config, dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Creating program directory: {}", dir.display()))?;
+
+    let (opcode_range_start, opcode_range_end) = match config.test_gen.opcode_override {
+        Some(opcode_override) => (opcode_override, opcode_override),
+        None => (config.test_gen.opcode_range[0], config.test_gen.opcode_range[1]),
+    };
+
+    let mut programs = Vec::new();
+    for opcode_raw in opcode_range_start..=opcode_range_end {
+        let opcode = Opcode::from(opcode_raw);
+        if !config.test_gen.valid_opcodes.contains(&opcode_raw)
+            || config.test_gen.excluded_opcodes.contains(&opcode_raw)
+            || opcode.is_prefix(&config.test_gen.prefixes)
+        {
+            continue;
+        }
+
+        let have_group_ext = config.test_gen.group_opcodes.contains(&opcode_raw);
+        let (op_ext_start, op_ext_end) = match have_group_ext {
+            true => get_group_extension_range(config, opcode),
+            false => (0, 0),
+        };
+
+        let mut size_prefixes: Vec<TestOpcodeSizePrefix> = TestOpcodeSizePrefix::iter(
+            config.test_gen.cpu_type,
+            opcode,
+            &config.test_gen.disable_operand_size_prefix,
+            &config.test_gen.disable_address_size_prefix,
+        )
+        .collect();
+        if size_prefixes.is_empty() {
+            size_prefixes.push(TestOpcodeSizePrefix::None);
+        }
+
+        for size_prefix in size_prefixes {
+            context.test_opcode_size_prefix = size_prefix;
+
+            for opcode_ext in op_ext_start..=op_ext_end {
+                let op_ext_str = match have_group_ext {
+                    true => format!(".{:1X}", opcode_ext),
+                    false => String::new(),
+                };
+                let stem = format!("{}{}{}", size_prefix.to_filename_prefix(), opcode, op_ext_str);
+                context.file_seed = file_seed(config, opcode_raw, None, opcode_ext);
+
+                for test_num in 0..get_test_count(config, opcode) {
+                    let (mut test_registers, test_instruction) =
+                        generate_sequence(context, config, opcode, have_group_ext, opcode_ext, test_num, 0)
+                            .with_context(|| format!("Generating test {} of {}", test_num, stem))?;
+                    mask_rep_count(context, config, &test_instruction, &mut test_registers);
+
+                    let bin_name = format!("{}_{:05}.bin", stem, test_num);
+                    let regs_name = format!("{}_{:05}.regs", stem, test_num);
+                    fs::write(dir.join(&bin_name), test_instruction.sequence_bytes())
+                        .with_context(|| format!("Writing {}", bin_name))?;
+
+                    let mut regs_buf = Cursor::new(Vec::new());
+                    test_registers.regs.to_buffer(&mut regs_buf);
+                    fs::write(dir.join(&regs_name), regs_buf.get_ref())
+                        .with_context(|| format!("Writing {}", regs_name))?;
+
+                    programs.push(ProgramEntry {
+                        file: bin_name,
+                        registers: regs_name,
+                        test_file: format!("{}.MOO", stem),
+                        test: test_num,
+                        name: test_instruction.name().to_string(),
+                        file_seed: format!("{:016X}", context.file_seed),
+                        reg_seed: format!("{:016X}", test_registers.reg_seed),
+                        instruction_address: test_registers.instruction_address,
+                        queue_stress: context.queue_stress.take().map(|stress| stress.target),
+                    });
+                }
+                println!("Dumped {} programs for {}", get_test_count(config, opcode), stem);
+            }
+        }
+    }
+
+    let manifest = ProgramManifest {
+        cpu_type: format!("{:?}", config.test_gen.cpu_type),
+        base_seed: format!("{:016X}", config.test_gen.base_seed),
+        programs,
+    };
+    let text = toml::to_string_pretty(&manifest).context("Serializing program manifest")?;
+    let manifest_path = dir.join(PROGRAMS_FILENAME);
+    fs::write(&manifest_path, text).with_context(|| format!("Writing {}", manifest_path.display()))?;
+    println!(
+        "Wrote {} programs and {}",
+        manifest.programs.len(),
+        manifest_path.display()
+    );
+    Ok(())
+}
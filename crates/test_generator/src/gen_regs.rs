@@ -30,9 +30,8 @@ use arduinox86_client::{
     RemoteCpuRegistersV3B,
 };
 use moo::types::{MooCpuType, MooRegisters, MooRegisters16, MooRegisters32};
-use rand::{rngs::StdRng, SeedableRng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rand_distr::Beta;
-use std::ops::Range;
 
 pub struct TestRegisters {
     pub regs: Registers,
@@ -117,10 +116,6 @@ impl TestRegisters {
         let mut rng = StdRng::seed_from_u64(reg_seed);
 
         // Randomize the registers.
-        let instruction_range: Range<u32> = Range {
-            start: config.test_gen.instruction_address_range[0],
-            end:   config.test_gen.instruction_address_range[1],
-        };
         let mut registers_good = false;
         let mut instruction_address = 0;
         let mut initial_regs = Registers::V1(Default::default());
@@ -145,17 +140,41 @@ impl TestRegisters {
                     }
                     random_v3a
                 }
+                MooCpuType::Intel80186 | MooCpuType::Intel80188 => {
+                    let mut random_v1 = Registers::V1(RemoteCpuRegistersV1::default());
+                    randomize_v2(context, config.test_gen.clone(), opcode, &mut rng, &mut random_v1);
+
+                    if config.test_exec.print_initial_regs {
+                        print_regs(&random_v1, config.test_gen.cpu_type.into());
+                    }
+                    random_v1
+                }
                 _ => Registers::V1(RemoteCpuRegistersV1::default()),
             };
 
-            if matches!(config.test_gen.cpu_mode, CpuMode::Real) {
-                // Doing real mode test. Normalize the segment descriptors.
-                initial_regs.normalize_descriptors();
+            match config.test_gen.cpu_mode {
+                CpuMode::Real => {
+                    // Doing real mode test. Normalize the segment descriptors.
+                    initial_regs.normalize_descriptors();
+                }
+                CpuMode::Protected => {
+                    // Start from real mode bases, then make the caches protected mode segments.
+                    initial_regs.normalize_descriptors();
+                    initial_regs.enter_protected_mode();
+                }
+                CpuMode::Unreal => {
+                    // Start from real mode bases, then move every segment up by a whole number of
+                    // megabytes within the address space.
+                    initial_regs.normalize_descriptors();
+                    let megabytes = (config.test_gen.address_mask >> 20).max(1);
+                    initial_regs.enter_unreal_mode(rng.random_range(1..=megabytes) << 20);
+                }
             }
+            initial_regs.set_code_segment_size(context.gen_width.into());
 
             // Check if the instruction is valid with the current registers.
             instruction_address = initial_regs.calculate_code_address() & config.test_gen.address_mask;
-            if instruction_range.contains(&instruction_address) {
+            if context.code_address_allowed(&config.test_gen, instruction_address) {
                 registers_good = true;
             }
         }
@@ -21,18 +21,48 @@
     DEALINGS IN THE SOFTWARE.
 */
 
-use std::{ffi::OsString, io::BufWriter, time::Instant};
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    io::{BufWriter, Cursor},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use super::{Config, Opcode, TestContext, TestOpcodeSizePrefix};
+use super::{
+    Config,
+    InjectLine,
+    InjectMode,
+    InstructionSize,
+    IvtTarget,
+    Opcode,
+    QueueStressTarget,
+    SmcPolicy,
+    TestContext,
+    TestGen,
+    TestOpcodeSizePrefix,
+};
 use crate::{
     bus_ops::BusOps,
     cpu_common::BusOp,
+    cycle_budget::{estimate_cycles, write_slow_report, SlowReason, SlowTest, SLOW_REPORT_FILENAME},
+    cycle_check::{check_bus_ops, check_cycles, CycleStreamError},
     cycles::MyServerCycleState,
-    display::print_regs_v2,
     gen_regs::TestRegisters,
+    golden::{generate_golden_test, load_golden_tests},
     instruction::TestInstruction,
+    manifest::{InterruptRecord, QueueStressRecord, SetAnnotations, SetManifest, VectorRecord, TAG_GOLDEN, TAG_SMC},
+    mini_trace::mini_traces,
+    moo_io::{self, TestTags},
+    moo_io::StoreDump,
+    parallel::WidthShard,
+    progress::{format_seed, progress_filename, FileProgress, FileStatus, RunProgress},
     registers::Registers,
+    session::{Capture, SessionHeader, SessionWriter},
+    shutdown::{recover, write_blocked_report, BlockedOpcode, OpcodeBlocked, ShutdownError, BLOCKED_REPORT_FILENAME},
     state::{final_state_from_ops, initial_state_from_ops},
+    stats::{GenStats, STATS_CSV_FILENAME, STATS_JSON_FILENAME},
 };
 
 use moo::{
@@ -54,11 +84,22 @@ use moo::{
 };
 
 use arduinox86_client::{
+    registers_common::SegmentSize,
     BinWrite,
+    Capabilities,
+    CpuClientError,
     CpuWidth,
+    InterruptTrigger,
+    IrqLine,
+    IrqTriggerMode,
+    MemoryBatch,
     MemoryStrategy,
+    PhaseBudget,
     ProgramState,
+    RegisterBuffer,
+    RegisterPrinter,
     RegisterSetType,
+    RemoteCpuRegistersV1,
     RemoteCpuRegistersV2,
     RemoteCpuRegistersV3B,
     ServerCpuType,
@@ -259,6 +300,7 @@ pub fn write_initial_mem(context: &mut TestContext, initial_mem: &[MooRamEntry])
         mem_vec.push((consecutive_start_address, consecutive_bytes));
     }
 
+    let mut batch = MemoryBatch::new();
     for span in mem_vec {
         log::debug!(
             "Writing initial memory at address {:08X} with {} bytes: {:02X?}",
@@ -266,20 +308,129 @@ pub fn write_initial_mem(context: &mut TestContext, initial_mem: &[MooRamEntry])
             span.1.len(),
             span.1
         );
-        context
-            .client
-            .set_memory(span.0, &span.1)
-            .with_context(|| format!("Writing initial memory at address {:08X}", span.0))?;
+        batch.stage(span.0, &span.1);
     }
+    context
+        .client()
+        .set_memory_ranges(&batch)
+        .with_context(|| format!("Writing {} initial memory ranges", batch.ranges().len()))?;
     Ok(())
 }
 
+/// Time spent setting up each test before its registers are loaded, and how many memory uploads
+/// that took.
+#[derive(Clone, Debug, Default)]
+pub struct SetupStats {
+    pub tests:   usize,
+    pub time:    Duration,
+    /// Memory writes staged for the tests.
+    pub writes:  usize,
+    /// Commands sent to upload the staged writes.
+    pub uploads: usize,
+}
+
+impl SetupStats {
+    pub fn add(&mut self, time: Duration, batch: &MemoryBatch) {
+        self.tests += 1;
+        self.time += time;
+        self.writes += batch.writes();
+        if !batch.is_empty() {
+            self.uploads += 1;
+        }
+    }
+}
+
+impl Display for SetupStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let per_test = self.time.checked_div(self.tests as u32).unwrap_or_default();
+        write!(
+            f,
+            "Test setup: {:.2?} per test, {} memory writes sent in {} uploads ({} round trips saved)",
+            per_test,
+            self.writes,
+            self.uploads,
+            self.writes.saturating_sub(self.uploads)
+        )
+    }
+}
+
+/// Where the tests, traces and session logs for one code segment width are written.
+pub struct WidthOutput {
+    pub width: InstructionSize,
+    pub test_dir: PathBuf,
+    pub trace_dir: PathBuf,
+    pub session_dir: Option<PathBuf>,
+}
+
+impl WidthOutput {
+    /// Create the output directories, refusing a test directory whose manifest records a different
+    /// width so that captures of one width never overwrite the other.
+    pub fn prepare(&self) -> anyhow::Result<()> {
+        for dir in [Some(&self.test_dir), Some(&self.trace_dir), self.session_dir.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            fs::create_dir_all(dir).with_context(|| format!("Creating output directory: {}", dir.display()))?;
+        }
+
+        if let Ok(manifest) = SetManifest::read(&self.test_dir) {
+            // Manifests written before widths were recorded only ever held 16-bit tests.
+            let existing_width = manifest.set.code_width.unwrap_or(16);
+            if existing_width != u32::from(self.width) {
+                bail!(
+                    "{} holds {}-bit tests, refusing to write {}-bit tests into it. Use another test_output_dir, \
+                     or list both widths in gen_widths to write each to its own subdirectory.",
+                    self.test_dir.display(),
+                    existing_width,
+                    u32::from(self.width)
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolve the output directories for each width in `gen_widths`. A single width writes straight to
+/// the configured directories. With more than one, each width gets a subdirectory named by
+/// [InstructionSize::dir_name], since the file names only encode the opcode and size prefixes.
+pub fn width_outputs(test_gen: &TestGen) -> anyhow::Result<Vec<WidthOutput>> {
+    if test_gen.gen_widths.is_empty() {
+        bail!("gen_widths is empty, so there are no tests to generate.");
+    }
+
+    let split = test_gen.gen_widths.len() > 1;
+    let width_dir = |dir: &PathBuf, width: InstructionSize| match split {
+        true => dir.join(width.dir_name()),
+        false => dir.clone(),
+    };
+
+    let mut outputs: Vec<WidthOutput> = Vec::new();
+    for &width in &test_gen.gen_widths {
+        if outputs.iter().any(|output| output.width == width) {
+            bail!("{:?} is listed more than once in gen_widths.", width);
+        }
+        if width == InstructionSize::ThirtyTwo && !matches!(test_gen.cpu_type, MooCpuType::Intel80386Ex) {
+            bail!("32-bit code segments need a 386, but cpu_type is {:?}.", test_gen.cpu_type);
+        }
+        outputs.push(WidthOutput {
+            width,
+            test_dir: width_dir(&test_gen.test_output_dir, width),
+            trace_dir: width_dir(&test_gen.trace_output_dir, width),
+            session_dir: test_gen.session_log_dir.as_ref().map(|dir| width_dir(dir, width)),
+        });
+    }
+    Ok(outputs)
+}
+
 pub fn gen_tests(context: &mut TestContext, config: &Config) -> anyhow::Result<()> {
     let mut opcode_range_start = 0;
     let mut opcode_range_end = 0x0FFF;
 
     context.gen_ct = 0;
     context.gen_start = Instant::now();
+    context.phase_budget = PhaseBudget::default();
+    context.setup_stats = SetupStats::default();
+    context.stats = GenStats::default();
 
     for count_override in &config.test_gen.count_overrides {
         log::debug!(
@@ -308,126 +459,176 @@ pub fn gen_tests(context: &mut TestContext, config: &Config) -> anyhow::Result<(
         bail!("Invalid opcode range specified.");
     }
 
-    // Tell ArduinoX86 to execute instructions automatically.
-    let mut server_flags = ServerFlags::EXECUTE_AUTOMATIC | ServerFlags::ENABLE_CYCLE_LOGGING;
+    set_server_flags(context, config)?;
 
-    if let MooCpuType::Intel80386Ex = config.test_gen.cpu_type {
-        server_flags |= ServerFlags::USE_SMM;
+    // Check every width's output directories up front, so a bad one doesn't stop us partway.
+    let outputs = width_outputs(&config.test_gen)?;
+    for output in &outputs {
+        output.prepare()?;
     }
 
-    context.client.set_flags(server_flags)?;
-    // Set default serial debug state.
-    context.client.enable_debug(config.test_exec.serial_debug_default)?;
+    for output in &outputs {
+        context.gen_width = output.width;
+        println!(
+            "Generating tests with a {}-bit code segment into {}",
+            u32::from(output.width),
+            output.test_dir.display()
+        );
 
-    let prefix_byte: Option<u8> = None;
-    let mut last_opcode = opcode_range_start;
+        // Carry over annotations for files we don't regenerate from the previous manifest, if any.
+        context.annotations = SetManifest::read(&output.test_dir)
+            .map(|manifest| manifest.annotations())
+            .unwrap_or_default();
+        // Pick up the progress of an interrupted run, if we're appending.
+        context.progress = match context.dry_run {
+            true => RunProgress::default(),
+            false => RunProgress::open(
+                &output.test_dir,
+                &progress_filename(context.shard.as_ref()),
+                config.test_gen.append_file,
+            )?,
+        };
 
-    for opcode_raw in opcode_range_start..=opcode_range_end {
-        if !config.test_gen.valid_opcodes.contains(&opcode_raw) {
-            continue;
-        }
+        let prefix_byte: Option<u8> = None;
+        let mut last_opcode = opcode_range_start;
+        let mut next_file_index = 0;
+        let mut generated_files = Vec::new();
 
-        let opcode_u8 = opcode_raw as u8;
-        let opcode = Opcode::from(opcode_raw);
-        for size_prefix in TestOpcodeSizePrefix::iter(
-            config.test_gen.cpu_type,
-            opcode,
-            &config.test_gen.disable_operand_size_prefix,
-            &config.test_gen.disable_address_size_prefix,
-        ) {
-            context.file_gen_ct = 0;
-            context.exceptions.clear();
-            context.test_opcode_size_prefix = size_prefix;
-
-            let mut op_ext_start = 0;
-            let mut op_ext_end = 0;
-            let mut have_group_ext = false;
-            if config.test_gen.group_opcodes.contains(&opcode_raw) {
-                have_group_ext = true;
-                (op_ext_start, op_ext_end) = get_group_extension_range(config, opcode.into());
+        for opcode_raw in opcode_range_start..=opcode_range_end {
+            if !config.test_gen.valid_opcodes.contains(&opcode_raw) {
+                continue;
             }
 
-            for opcode_ext in op_ext_start..=op_ext_end {
-                last_opcode = opcode_raw;
+            let opcode = Opcode::from(opcode_raw);
+            for size_prefix in TestOpcodeSizePrefix::iter(
+                config.test_gen.cpu_type,
+                opcode,
+                &config.test_gen.disable_operand_size_prefix,
+                &config.test_gen.disable_address_size_prefix,
+            ) {
+                context.file_gen_ct = 0;
+                context.test_opcode_size_prefix = size_prefix;
+
+                let mut op_ext_start = 0;
+                let mut op_ext_end = 0;
+                let mut have_group_ext = false;
+                if config.test_gen.group_opcodes.contains(&opcode_raw) {
+                    have_group_ext = true;
+                    (op_ext_start, op_ext_end) = get_group_extension_range(config, opcode.into());
+                }
 
-                // Reset mnemonic hashmap.
-                context.mnemonic_set.clear();
+                for opcode_ext in op_ext_start..=op_ext_end {
+                    last_opcode = opcode_raw;
 
-                if config.test_gen.excluded_opcodes.contains(&opcode_raw) {
-                    log::debug!("Skipping excluded opcode: {}", opcode);
-                    continue;
-                }
+                    // Reset mnemonic hashmap.
+                    context.mnemonic_set.clear();
 
-                if (opcode_raw < 0x100) && config.test_gen.prefixes.contains(&opcode_u8) {
-                    log::debug!("Skipping prefix: {:02X}", opcode_raw);
-                    continue;
-                }
+                    if config.test_gen.excluded_opcodes.contains(&opcode_raw) {
+                        log::debug!("Skipping excluded opcode: {}", opcode);
+                        continue;
+                    }
 
-                let mut op_ext_str = "".to_string();
-                if have_group_ext {
-                    // If this is a group opcode, append the extension.
-                    op_ext_str = format!(".{:1X}", opcode_ext);
-                }
+                    if opcode.is_prefix(&config.test_gen.prefixes) {
+                        log::debug!("Skipping prefix: {:02X}", opcode_raw);
+                        continue;
+                    }
 
-                // Create the output file path.
-                let mut file_path = config.test_gen.test_output_dir.clone();
-                let size_prefix_base = size_prefix.to_filename_prefix();
-                let filename = OsString::from(format!("{}{}{}.MOO", size_prefix_base, opcode, op_ext_str));
-                file_path.push(filename.clone());
+                    // When generation is spread over several boards, each board takes every Nth file.
+                    let file_index = next_file_index;
+                    next_file_index += 1;
+                    if context.shard.as_ref().is_some_and(|shard| !shard.owns(file_index)) {
+                        continue;
+                    }
 
-                // Create the trace file.
-                let trace_filename = OsString::from(format!(
-                    "{}{}{}{}",
-                    size_prefix_base,
-                    opcode,
-                    op_ext_str,
-                    config.test_gen.trace_file_suffix.display()
-                ));
-                let trace_file_path = config.test_gen.trace_output_dir.join(trace_filename);
-                let trace_file = match config.test_gen.append_file {
-                    true => std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(&trace_file_path)
-                        .with_context(|| format!("Opening trace file: {}", trace_file_path.display()))?,
-                    false => std::fs::File::create(&trace_file_path)
-                        .with_context(|| format!("Creating trace file: {}", trace_file_path.display()))?,
-                };
+                    let mut op_ext_str = "".to_string();
+                    if have_group_ext {
+                        // If this is a group opcode, append the extension.
+                        op_ext_str = format!(".{:1X}", opcode_ext);
+                    }
 
-                context.trace_log = BufWriter::new(trace_file);
+                    // Create the output file path.
+                    let mut file_path = output.test_dir.clone();
+                    let size_prefix_base = size_prefix.to_filename_prefix();
+                    let filename = OsString::from(format!("{}{}{}.MOO", size_prefix_base, opcode, op_ext_str));
+                    file_path.push(filename.clone());
 
-                // Create the file seed.
-                let mut file_seed: u64 = opcode_raw as u64;
-                if let Some(prefix_byte) = prefix_byte {
-                    file_seed = file_seed | ((prefix_byte as u64) << 8);
-                }
-                file_seed <<= 3;
-                file_seed |= (opcode_ext & 0x07) as u64;
-                file_seed ^= config.test_gen.base_seed;
-
-                context.file_seed = file_seed;
-                let mut test_start_num = 0;
-
-                let moo_arch = MooCpuType::from(context.client.cpu_type()?.0);
-
-                let mut test_file = MooTestFile::new(config.test_gen.moo_version, moo_arch, config.test_gen.test_count);
-
-                let mut test_metadata = MooFileMetadata::new(
-                    config.test_gen.set_version_major,
-                    config.test_gen.set_version_minor,
-                    config.test_gen.cpu_type.into(),
-                    opcode_raw as u32,
-                )
-                .with_file_seed(context.file_seed);
-
-                // Open the file if append == true
-                if config.test_gen.append_file {
-                    // Open `filename` for reading as a BufReader.
-                    match std::fs::File::open(&file_path) {
-                        Ok(file) => {
+                    // Create the trace file.
+                    let trace_filename = OsString::from(format!(
+                        "{}{}{}{}",
+                        size_prefix_base,
+                        opcode,
+                        op_ext_str,
+                        config.test_gen.trace_file_suffix.display()
+                    ));
+                    let trace_file_path = output.trace_dir.join(trace_filename);
+                    let trace_file = match config.test_gen.append_file {
+                        true => std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(&trace_file_path)
+                            .with_context(|| format!("Opening trace file: {}", trace_file_path.display()))?,
+                        false => std::fs::File::create(&trace_file_path)
+                            .with_context(|| format!("Creating trace file: {}", trace_file_path.display()))?,
+                    };
+
+                    context.trace_log = BufWriter::new(trace_file);
+                    if let Some(shard) = context.shard.clone() {
+                        trace_log!(
+                            context,
+                            "Generated on board {} of {}: {}",
+                            shard.index + 1,
+                            shard.count,
+                            shard.board
+                        );
+                    }
+
+                    // Create the file seed.
+                    context.file_seed = file_seed(config, opcode_raw, prefix_byte, opcode_ext);
+                    let mut test_start_num = 0;
+
+                    let moo_arch = MooCpuType::from(context.client().cpu_type()?.0);
+
+                    let mut test_file =
+                        MooTestFile::new(config.test_gen.moo_version, moo_arch, config.test_gen.test_count);
+                    context.file_store_dumps.clear();
+                    context.exceptions.clear();
+
+                    let mut test_metadata = MooFileMetadata::new(
+                        config.test_gen.set_version_major,
+                        config.test_gen.set_version_minor,
+                        config.test_gen.cpu_type.into(),
+                        opcode.into(),
+                    )
+                    .with_file_seed(context.file_seed);
+
+                    let file_key = filename.to_string_lossy().to_string();
+                    let test_count = get_test_count(config, opcode.into());
+                    let recorded = context.progress.get(&file_key).cloned();
+                    if let Some(recorded) = &recorded {
+                        if recorded.file_seed != format_seed(context.file_seed) {
+                            bail!(
+                                "{} was started with file seed {}, but the config now gives {}. Restore base_seed, or \
+                                 remove the file to regenerate it.",
+                                file_key,
+                                recorded.file_seed,
+                                format_seed(context.file_seed)
+                            );
+                        }
+                        if recorded.status == FileStatus::Complete
+                            && recorded.completed >= test_count
+                            && file_path.exists()
+                        {
+                            println!("Test file {} is complete. Skipping...", file_path.to_string_lossy());
+                            continue;
+                        }
+                    }
+
+                    // Open the file if append == true
+                    if config.test_gen.append_file {
+                        if file_path.exists() {
                             log::debug!("Appending to existing test file: {}", file_path.to_string_lossy());
-                            let mut file_reader = std::io::BufReader::new(file);
-                            test_file = MooTestFile::read(&mut file_reader)?;
+                            // Salvages any complete tests if the file was truncated by a crash.
+                            (test_file, context.file_store_dumps) = moo_io::read_test_file_with_dumps(&file_path)?;
 
                             println!(
                                 "Read {} tests from existing file: {}",
@@ -437,316 +638,718 @@ pub fn gen_tests(context: &mut TestContext, config: &Config) -> anyhow::Result<(
 
                             test_start_num = test_file.test_ct();
                         }
-                        Err(e) => {
-                            if e.kind() == std::io::ErrorKind::NotFound {
-                                // If the file does not exist, we will create it later.
-                                log::debug!(
-                                    "File {} not found, creating new test file.",
-                                    file_path.to_string_lossy()
-                                );
-                            }
-                            else {
-                                return Err(anyhow::anyhow!("Error opening test file: {}", e));
+                        else {
+                            // If the file does not exist, we will create it later.
+                            log::debug!(
+                                "File {} not found, creating new test file.",
+                                file_path.to_string_lossy()
+                            );
+                        }
+                    };
+
+                    if test_start_num >= config.test_gen.test_count {
+                        println!("Test file {} is complete. Skipping...", file_path.to_string_lossy());
+                        continue;
+                    }
+
+                    generated_files.push(file_key.clone());
+                    context.stats.begin_file(&file_key);
+                    if test_start_num == 0 {
+                        // Starting a fresh file, so any previous annotations no longer apply.
+                        context.annotations.remove(&file_key);
+                    }
+                    else if let Some(recorded) = &recorded {
+                        // The manifest is only written once the width is finished, so restore the
+                        // annotations of the tests already in the file from their progress instead.
+                        if recorded.completed < test_start_num {
+                            println!(
+                                "Warning: progress for {} records {} tests, but the file holds {}. Annotations for \
+                                 the unrecorded tests are lost.",
+                                file_key, recorded.completed, test_start_num
+                            );
+                        }
+                        let mut annotations = recorded.annotations_before(test_start_num);
+                        for test in test_file.tests().iter() {
+                            if let Some(exception) = test.exception() {
+                                annotations.count_exception(exception.exception_num);
                             }
                         }
+                        context.annotations.insert(file_key.clone(), annotations);
                     }
-                };
 
-                if test_start_num >= config.test_gen.test_count {
-                    println!("Test file {} is complete. Skipping...", file_path.to_string_lossy());
-                    continue;
-                }
+                    let mut file_progress = FileProgress::new(
+                        &file_key,
+                        opcode_raw,
+                        have_group_ext.then_some(opcode_ext),
+                        context.file_seed,
+                        test_count,
+                    );
+                    file_progress.completed = test_start_num;
+                    file_progress.annotations = context.annotations.get(&file_key).cloned().unwrap_or_default();
+                    context.progress.begin(file_progress)?;
+
+                    // Start a session log for the file, or keep appending to it if we're resuming.
+                    context.session_log = match &output.session_dir {
+                        Some(session_dir) => {
+                            let header = SessionHeader {
+                                file_name: file_key.clone(),
+                                opcode:    opcode_raw,
+                                file_seed: context.file_seed,
+                            };
+                            Some(SessionWriter::open(session_dir, &header, test_start_num > 0)?)
+                        }
+                        None => None,
+                    };
 
-                let test_count = get_test_count(config, opcode.into());
-                for test_num in test_start_num..test_count {
-                    // Create unique instruction and initial register set for each test.
-                    // These should not change regardless of test attempt count.
+                    // Hand-written tests go first, ahead of the random tests.
+                    let golden_tests = match &config.test_gen.golden_dir {
+                        Some(golden_dir) => load_golden_tests(golden_dir, &file_key, output.width)?,
+                        None => Vec::new(),
+                    };
 
-                    let mut test_result = generate_consistent_test(
-                        context,
-                        config,
-                        test_num,
-                        opcode,
-                        have_group_ext,
-                        opcode_ext,
-                        config.test_exec.validate_count as usize,
-                    );
+                    let mut blocked = false;
+                    for test_num in test_start_num..test_count {
+                        // Create unique instruction and initial register set for each test.
+                        // These should not change regardless of test attempt count.
 
-                    if !context.dry_run {
-                        if test_result.is_err() {
-                            let err_msg = format!(
-                                "Failed to generate test for opcode {} at test number {}: {}",
+                        let golden = golden_tests.get(test_num);
+                        let mut test_result = match golden {
+                            Some(golden) => generate_golden_test(
+                                context,
+                                config,
+                                test_num,
                                 opcode,
+                                have_group_ext.then_some(opcode_ext),
+                                golden,
+                                config.test_exec.validate_count as usize,
+                            ),
+                            None => generate_consistent_test(
+                                context,
+                                config,
                                 test_num,
-                                test_result.as_ref().err().unwrap()
-                            );
-                            trace_error!(context, "{}", err_msg);
-                            return Err(anyhow::anyhow!(err_msg));
-                        }
+                                opcode,
+                                have_group_ext,
+                                opcode_ext,
+                                config.test_exec.validate_count as usize,
+                            ),
+                        };
+
+                        if !context.dry_run {
+                            if let Some(opcode_blocked) =
+                                test_result.as_ref().err().and_then(|e| e.downcast_ref::<OpcodeBlocked>())
+                            {
+                                println!("Blocking {}: {}", file_key, opcode_blocked);
+                                trace_error!(context, "Blocking {}: {}", file_key, opcode_blocked);
+                                context.blocked_opcodes.push(BlockedOpcode {
+                                    file_name: file_key.clone(),
+                                    opcode,
+                                    blocked: opcode_blocked.clone(),
+                                });
+                                blocked = true;
+                                break;
+                            }
+                            if test_result.is_err() {
+                                let err_msg = format!(
+                                    "Failed to generate test for opcode {} at test number {}: {}",
+                                    opcode,
+                                    test_num,
+                                    test_result.as_ref().err().unwrap()
+                                );
+                                trace_error!(context, "{}", err_msg);
+                                return Err(anyhow::anyhow!(err_msg));
+                            }
 
-                        // Add the test to the test file.
-                        let test = test_result?;
-                        test_file.add_test(test);
-                        context.file_gen_ct += 1;
-                        context.gen_ct += 1;
+                            // Add the test to the test file.
+                            let test = test_result?;
+                            annotate_test(context, config, &file_key, test_num, &test);
+                            if golden.is_some() {
+                                context
+                                    .annotations
+                                    .entry(file_key.clone())
+                                    .or_default()
+                                    .tag(TAG_GOLDEN, test_num as u32);
+                            }
+                            if let Some(capture) = context.capture.take() {
+                                if let Some(session_log) = &mut context.session_log {
+                                    session_log.write(&Capture {
+                                        test: test_num as u32,
+                                        ..capture
+                                    })?;
+                                }
+                            }
+                            context.stats.add_test(&test);
+                            test_file.add_test(test);
+                            context.file_gen_ct += 1;
+                            context.gen_ct += 1;
+
+                            // Write the tests so far and record them, so an interrupted run resumes from here.
+                            // Mini traces are only written with the finished file.
+                            let interval = config.test_gen.checkpoint_interval;
+                            if interval > 0 && test_file.test_ct() % interval == 0 {
+                                moo_io::write_test_file_with_dumps(
+                                    &test_file,
+                                    &context.file_store_dumps,
+                                    &[],
+                                    &file_tags(context, &file_key),
+                                    config.test_gen.compress_cycles,
+                                    &file_path,
+                                )?;
+                                context.progress.update(
+                                    &file_key,
+                                    test_file.test_ct(),
+                                    FileStatus::InProgress,
+                                    context.annotations.get(&file_key),
+                                )?;
+                            }
+                        }
+                    }
+                    context.stats.end_file(blocked);
+                    if blocked {
+                        // Leave the file as it was at the last checkpoint rather than write a partial set.
+                        context.progress.set_status(&file_key, FileStatus::Blocked)?;
+                        continue;
+                    }
+                    // Test generation is complete.
+
+                    // Log time taken
+                    context.gen_stop = Instant::now();
+                    if config.test_exec.show_gen_time {
+                        let gen_duration = context.gen_stop.duration_since(context.gen_start);
+                        println!(
+                            "Generated {} tests in {:.2?} seconds ({} tests per second)",
+                            context.gen_ct,
+                            gen_duration,
+                            context.gen_ct as f64 / gen_duration.as_secs_f64()
+                        );
+                        println!("Time by run phase:\n{}", context.phase_budget);
+                        println!("{}", context.setup_stats);
                     }
-                }
-                // Test generation is complete.
-
-                // Log time taken
-                context.gen_stop = Instant::now();
-                if config.test_exec.show_gen_time {
-                    let gen_duration = context.gen_stop.duration_since(context.gen_start);
-                    println!(
-                        "Generated {} tests in {:.2?} seconds ({} tests per second)",
-                        context.gen_ct,
-                        gen_duration,
-                        context.gen_ct as f64 / gen_duration.as_secs_f64()
-                    );
-                }
-
-                trace_banner!(context);
-                trace_log!(
-                    context,
-                    "### Test generation complete for opcode {} ({} tests) ###",
-                    opcode_raw,
-                    context.file_gen_ct
-                );
-
-                // Adjust final metadata with count...
-                test_metadata = test_metadata.with_test_count(context.gen_ct as u32);
-                // ... and with the most frequently seen mnemonic (to handle some tests that have invalid forms icedx86 won't decode).
-                if let Some((mnemonic, count)) = context.mnemonic_set.iter().max_by_key(|entry| entry.1) {
-                    let mnemonic_stats = format!("Most frequent mnemonic: {} ({} times)", mnemonic, count);
-                    trace_log!(context, "{}", mnemonic_stats);
-                    log::debug!("{}", mnemonic_stats);
-                    test_metadata = test_metadata.with_mnemonic(mnemonic.to_string());
-                }
-
-                trace_log!(context, "Exceptions seen:");
 
-                let total = context.file_gen_ct * config.test_exec.validate_count as usize;
-                for exception in &context.exceptions {
+                    trace_banner!(context);
                     trace_log!(
                         context,
-                        "{}: {:5}/{:5} ({:.2}%)",
-                        exception.0,
-                        exception.1,
-                        total,
-                        (*exception.1 as f64 / total as f64) * 100.0
+                        "### Test generation complete for opcode {} ({} tests) ###",
+                        opcode_raw,
+                        context.file_gen_ct
                     );
-                }
 
-                trace_banner!(context);
+                    // Adjust final metadata with count...
+                    test_metadata = test_metadata.with_test_count(context.gen_ct as u32);
+                    // ... and with the most frequently seen mnemonic (to handle some tests that have invalid forms icedx86 won't decode).
+                    if let Some((mnemonic, count)) = context.mnemonic_set.iter().max_by_key(|entry| entry.1) {
+                        let mnemonic_stats = format!("Most frequent mnemonic: {} ({} times)", mnemonic, count);
+                        trace_log!(context, "{}", mnemonic_stats);
+                        log::debug!("{}", mnemonic_stats);
+                        test_metadata = test_metadata.with_mnemonic(mnemonic.to_string());
+                    }
 
-                test_file.set_metadata(test_metadata);
+                    trace_log!(context, "Exceptions seen:");
+
+                    let total = context.file_gen_ct * config.test_exec.validate_count as usize;
+                    for exception in &context.exceptions {
+                        trace_log!(
+                            context,
+                            "{}: {:5}/{:5} ({:.2}%)",
+                            exception.0,
+                            exception.1,
+                            total,
+                            (*exception.1 as f64 / total as f64) * 100.0
+                        );
+                    }
+
+                    trace_banner!(context);
 
-                // Open the file as a Writer.
-                log::debug!("Writing test file: {}", file_path.to_string_lossy());
+                    if let Some(file_annotations) = context.annotations.get(&file_key) {
+                        if !file_annotations.exceptions.is_empty() {
+                            let summary = file_annotations
+                                .exceptions
+                                .iter()
+                                .map(|e| format!("#{}: {}", e.vector, e.count))
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            println!(
+                                "Exceptions in {} ({} tests): {}",
+                                file_key,
+                                test_file.test_ct(),
+                                summary
+                            );
+                        }
+                    }
 
-                let file = std::fs::File::create(&file_path)?;
-                let mut writer = BufWriter::new(file);
+                    test_file.set_metadata(test_metadata);
 
-                test_file.write(&mut writer)?;
+                    // Write the file via a temp file and rename, so a crash can't leave a truncated file.
+                    log::debug!("Writing test file: {}", file_path.to_string_lossy());
+                    let traces = match config.test_gen.mini_trace_chunks {
+                        true => mini_traces(&test_file, config.test_gen.cpu_type),
+                        false => Vec::new(),
+                    };
+                    moo_io::write_test_file_with_dumps(
+                        &test_file,
+                        &context.file_store_dumps,
+                        &traces,
+                        &file_tags(context, &file_key),
+                        config.test_gen.compress_cycles,
+                        &file_path,
+                    )?;
+                    context.progress.update(
+                        &file_key,
+                        test_file.test_ct(),
+                        FileStatus::Complete,
+                        context.annotations.get(&file_key),
+                    )?;
+                }
             }
         }
+
+        println!("Test generation complete at terminating opcode: {:02X}", last_opcode);
+
+        if context.shard.is_some() {
+            // The other boards are still writing to this directory, so the coordinator writes the
+            // manifest once they have all finished.
+            context.shard_widths.push(WidthShard {
+                annotations: std::mem::take(&mut context.annotations),
+                files: std::mem::take(&mut generated_files),
+            });
+            continue;
+        }
+
+        let (server_cpu, has_fpu) = context.client().cpu_type()?;
+        write_manifest(config, output, server_cpu, has_fpu, &context.annotations)?;
     }
 
-    println!("Test generation complete at terminating opcode: {:02X}", last_opcode);
+    if context.shard.is_none() {
+        write_reports(
+            &config.test_gen.trace_output_dir,
+            context.cycle_stream_errors,
+            &context.blocked_opcodes,
+            &context.slow_tests,
+            &context.stats,
+        )?;
+    }
 
     Ok(())
 }
 
-fn generate_consistent_test(
+/// Write the set manifest covering all files in the output directory, noting the width.
+pub fn write_manifest(
+    config: &Config,
+    output: &WidthOutput,
+    server_cpu: ServerCpuType,
+    has_fpu: bool,
+    annotations: &SetAnnotations,
+) -> anyhow::Result<()> {
+    let mut manifest = SetManifest::build(
+        &output.test_dir,
+        format!(
+            "{}.{}",
+            config.test_gen.set_version_major, config.test_gen.set_version_minor
+        ),
+        format!("{:?}", config.test_gen.cpu_type),
+        format!("{:?}", server_cpu),
+        has_fpu,
+    )?;
+    manifest.set.code_width = Some(output.width.into());
+    manifest.set.cpu_mode = Some(format!("{:?}", config.test_gen.cpu_mode));
+    manifest.set_annotations(annotations);
+    manifest.write(&output.test_dir)?;
+    println!(
+        "Wrote manifest for {} files ({} tests)",
+        manifest.files.len(),
+        manifest.set.total_tests
+    );
+    for (vector, count) in manifest.exception_histogram() {
+        println!("  exception #{}: {} test(s)", vector, count);
+    }
+    Ok(())
+}
+
+/// Print the run's rejected capture count, and write the blocked opcode, slow test and generation
+/// statistics reports.
+pub fn write_reports(
+    trace_output_dir: &Path,
+    cycle_stream_errors: usize,
+    blocked_opcodes: &[BlockedOpcode],
+    slow_tests: &[SlowTest],
+    stats: &GenStats,
+) -> anyhow::Result<()> {
+    if cycle_stream_errors > 0 {
+        println!(
+            "{} capture(s) were rejected for breaking cycle-stream invariants. Check the CPU socket if this is frequent.",
+            cycle_stream_errors
+        );
+    }
+
+    if !blocked_opcodes.is_empty() {
+        write_blocked_report(trace_output_dir, blocked_opcodes)?;
+        println!(
+            "{} file(s) were skipped for repeated CPU shutdowns, see {}",
+            blocked_opcodes.len(),
+            trace_output_dir.join(BLOCKED_REPORT_FILENAME).display()
+        );
+    }
+
+    if !slow_tests.is_empty() {
+        write_slow_report(trace_output_dir, slow_tests)?;
+        println!(
+            "{} test(s) exceeded their cycle budget, see {}",
+            slow_tests.len(),
+            trace_output_dir.join(SLOW_REPORT_FILENAME).display()
+        );
+    }
+
+    if !stats.files.is_empty() {
+        stats.write(trace_output_dir)?;
+        stats.print_summary();
+        println!(
+            "Statistics for {} file(s) written to {} and {}",
+            stats.files.len(),
+            trace_output_dir.join(STATS_JSON_FILENAME).display(),
+            trace_output_dir.join(STATS_CSV_FILENAME).display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Return the tags recorded for the tests of a file, to be written to its TAGS chunks.
+pub fn file_tags(context: &TestContext, file_key: &str) -> Vec<TestTags> {
+    context
+        .annotations
+        .get(file_key)
+        .map(|annotations| TestTags::from_tag_map(&annotations.tags))
+        .unwrap_or_default()
+}
+
+/// Record the manifest annotations and store dump for an accepted test.
+pub fn annotate_test(context: &mut TestContext, config: &Config, file_key: &str, test_num: usize, test: &MooTest) {
+    if context.smc_detected {
+        context
+            .annotations
+            .entry(file_key.to_string())
+            .or_default()
+            .tag(TAG_SMC, test_num as u32);
+    }
+    if let Some(interrupt) = context.interrupt.take() {
+        context
+            .annotations
+            .entry(file_key.to_string())
+            .or_default()
+            .interrupts
+            .push(InterruptRecord {
+                test: test_num as u32,
+                ..interrupt
+            });
+    }
+    if let Some(vector) = context.vector.take() {
+        context
+            .annotations
+            .entry(file_key.to_string())
+            .or_default()
+            .vectors
+            .push(VectorRecord {
+                test: test_num as u32,
+                ..vector
+            });
+    }
+    if let Some(stress) = context.queue_stress.take() {
+        context
+            .annotations
+            .entry(file_key.to_string())
+            .or_default()
+            .queue_stress
+            .push(QueueStressRecord {
+                test: test_num as u32,
+                ..stress
+            });
+    }
+    if let Some(exception) = test.exception() {
+        context
+            .annotations
+            .entry(file_key.to_string())
+            .or_default()
+            .count_exception(exception.exception_num);
+    }
+    if config.test_gen.store_dump_chunks {
+        if let Some(dump) = context.store_dump.take() {
+            context.file_store_dumps.push(StoreDump {
+                test: test_num as u32,
+                ..dump
+            });
+        }
+    }
+}
+
+/// Generate the registers and instruction sequence for a test, applying the masking fixups for
+/// its effective address registers, branch target and displacement. This touches no hardware.
+pub fn generate_sequence(
     context: &mut TestContext,
     config: &Config,
-    test_num: usize,
     opcode: Opcode,
     have_group_ext: bool,
     opcode_ext: u8,
-    required_matches: usize,
-) -> Result<MooTest, Error> {
-    let mut gen_num = 0;
-    let mut sieved = false;
-    let mut sieve_ct = 0;
+    test_num: usize,
+    gen_num: usize,
+) -> anyhow::Result<(TestRegisters, TestInstruction)> {
+    let mut test_registers = TestRegisters::new(context, config, opcode, test_num, gen_num);
 
-    // Set flow control end condition
+    context.code_segment_size = test_registers.regs.segment_size(iced_x86::Register::CS);
 
-    if config.test_gen.flow_control_opcodes.contains(&opcode.into()) {
-        let flags = context.client.get_flags()?;
-        if flags & ServerFlags::HALT_AFTER_JUMP == 0 {
-            // Enable halt after jump if not already set.
-            context.client.set_flags(flags | ServerFlags::HALT_AFTER_JUMP)?;
-            log::debug!("Enabled HALT_AFTER_JUMP for opcode {}", opcode);
-        }
-    }
-    else {
-        let flags = context.client.get_flags()?;
-        if flags & ServerFlags::HALT_AFTER_JUMP != 0 {
-            // Disable halt after jump if set.
-            context.client.set_flags(flags & !ServerFlags::HALT_AFTER_JUMP)?;
-            log::debug!("Disabled HALT_AFTER_JUMP for opcode {}", opcode);
-        }
-    }
+    //log::trace!("Generating new instruction!");
+    let mut test_instruction = TestInstruction::new(
+        context,
+        &config.test_gen,
+        opcode,
+        have_group_ext.then_some(opcode_ext),
+        &test_registers,
+        test_num,
+        gen_num,
+    )?;
 
-    // We'll attempt to generate a test up to 'max_gen' times before giving up.
-    // If we can't generate a test after that point, something has gone very wrong, like the
-    // ArduinoX86 has crashed, the opcode is invalid, or we hit a major bug.
-    while (sieved && sieve_ct < config.test_exec.max_sieve) || gen_num < config.test_exec.max_gen as usize {
-        // Generate a fresh Register & Instruction pair.
-        let mut test_registers = TestRegisters::new(context, config, opcode, test_num, gen_num);
+    trace_banner!(context);
+    trace_log!(context, "Code segment is size {:?}", context.code_segment_size);
+
+    let mut segments = test_instruction.segments();
+    //segments.sort();
+    //segments.dedup();
 
-        context.code_segment_size = test_registers.regs.segment_size(iced_x86::Register::CS);
+    let mut segment_limit = 0xFFFF_FFFF;
 
-        //log::trace!("Generating new instruction!");
-        let mut test_instruction = TestInstruction::new(
+    for segment in &segments {
+        trace_log!(
             context,
-            &config.test_gen,
-            opcode,
-            have_group_ext.then_some(opcode_ext),
-            &test_registers,
-            test_num,
-            gen_num,
-        )?;
+            "Test instruction accesses segment {:?} with limit {:X}, size {:?}",
+            segment,
+            test_registers.regs.segment_limit(*segment).unwrap_or(0),
+            test_registers.regs.segment_size(*segment)
+        );
 
-        trace_banner!(context);
-        trace_log!(context, "Code segment is size {:?}", context.code_segment_size);
+        segment_limit &= test_registers.regs.segment_limit(*segment).unwrap_or(0xFFFF_FFFF);
+    }
 
-        let mut segments = test_instruction.segments();
-        //segments.sort();
-        //segments.dedup();
+    let mut ea_registers = &test_instruction.ea_registers();
+    for register in ea_registers {
+        trace_log!(context, "Test instruction uses EA register {:?}", register);
+    }
 
-        let mut segment_limit = 0xFFFF_FFFF;
+    let scale_shift = match test_instruction.iced_instruction().memory_index_scale() {
+        8 => 3,
+        4 => 2,
+        2 => 1,
+        _ => 0,
+    };
 
-        for segment in &segments {
-            trace_log!(
-                context,
-                "Test instruction accesses segment {:?} with limit {:X}, size {:?}",
-                segment,
-                test_registers.regs.segment_limit(*segment).unwrap_or(0),
-                test_registers.regs.segment_size(*segment)
-            );
+    if !ea_registers.is_empty() {
+        trace_log!(
+            context,
+            "Masking EA registers: {:?} with limit {:08X}",
+            ea_registers,
+            segment_limit >> scale_shift
+        );
 
-            segment_limit &= test_registers.regs.segment_limit(*segment).unwrap_or(0xFFFF_FFFF);
-        }
+        test_registers.regs.mask_registers32(segments[0], ea_registers);
+    }
 
-        let mut ea_registers = &test_instruction.ea_registers();
-        for register in ea_registers {
-            trace_log!(context, "Test instruction uses EA register {:?}", register);
+    if config.test_gen.flow_control_opcodes.contains(&opcode.into()) {
+        if matches!(test_instruction.iced_instruction().op0_kind(), OpKind::NearBranch32) {
+            segment_limit &= test_registers
+                .regs
+                .segment_limit(iced_x86::Register::CS)
+                .unwrap_or(0xFFFF_FFFF);
+            trace_log!(context, "Test instruction is flow control operation with 32-bit near branch. Masking branch target with segment limit {:08X} (sign-extended)", segment_limit);
+
+            test_instruction.mask_nearbranch32(
+                context,
+                &config.test_gen,
+                test_registers.regs.segment_size(iced_x86::Register::CS).into(),
+                segment_limit,
+            )?;
         }
+    }
 
-        let scale_shift = match test_instruction.iced_instruction().memory_index_scale() {
-            8 => 3,
-            4 => 2,
-            2 => 1,
-            _ => 0,
-        };
+    if let Some(immediate_size) = test_instruction.immediate_size() {
+        trace_log!(
+            context,
+            "Test instruction uses immediate of size {} bytes",
+            immediate_size,
+        );
 
-        if !ea_registers.is_empty() {
-            trace_log!(
-                context,
-                "Masking EA registers: {:?} with limit {:08X}",
-                ea_registers,
-                segment_limit >> scale_shift
-            );
+        // if immediate_size > 2 &&
+        //     trace_log!(
+        //         context,
+        //         "Instruction is a flow control operation with large immediate. Masking immediate with segment limit {:08X} (sign-extended)",
+        //         segment_limit
+        //     )
+        // }
+    }
 
-            test_registers.regs.mask_registers32(segments[0], ea_registers);
-        }
+    // Handle displacement masking.
+    // 32-bit displacements are too large to fully randomize, so we need to mask them to the segment limit.
+    if let Some(displacement_size) = test_instruction.displacement_size() {
+        trace_log!(
+            context,
+            "Test instruction uses displacement of size {} bytes",
+            displacement_size,
+        );
+
+        if displacement_size == 4 {
+            // Handle POP.  POP will touch multiple segments, with the last segment being SS.
+            // This isn't the segment we care about for masking so pop it.
+            if matches!(test_instruction.iced_instruction().mnemonic(), Mnemonic::Pop) && segments.len() > 1 {
+                segments.pop();
+            }
+
+            // Handle 0xFF opcodes
+            if matches!(
+                test_instruction.iced_instruction().mnemonic(),
+                Mnemonic::Call | Mnemonic::Jmp | Mnemonic::Push | Mnemonic::Pop
+            ) && segments.len() > 1
+            {
+                log::debug!("Have multiple segments, keeping first : {:?}", segments);
+                segments = vec![segments[0]];
+            }
 
-        if config.test_gen.flow_control_opcodes.contains(&opcode.into()) {
-            if matches!(test_instruction.iced_instruction().op0_kind(), OpKind::NearBranch32) {
-                segment_limit &= test_registers
-                    .regs
-                    .segment_limit(iced_x86::Register::CS)
-                    .unwrap_or(0xFFFF_FFFF);
-                trace_log!(context, "Test instruction is flow control operation with 32-bit near branch. Masking branch target with segment limit {:08X} (sign-extended)", segment_limit);
+            if segments.len() > 1 {
+                let error_msg = format!(
+                    "Multiple segments found with displacement: {:?} - unexpected condition.",
+                    segments
+                );
+                trace_error!(context, "{}", error_msg);
+                bail!(error_msg);
+            }
+            else if !segments.is_empty() {
+                let segment = segments[0];
+                trace_log!(
+                    context,
+                    "Masking displacement with segment limit {:08X} (sign-extended)",
+                    segment_limit
+                );
 
-                test_instruction.mask_nearbranch32(
+                let scale_shift = match test_instruction.iced_instruction().memory_index_scale() {
+                    8 => 3,
+                    4 => 2,
+                    2 => 1,
+                    _ => 0,
+                };
+                test_instruction.mask_displacement32(
                     context,
                     &config.test_gen,
-                    test_registers.regs.segment_size(iced_x86::Register::CS).into(),
-                    segment_limit,
+                    test_registers.regs.segment_size(segment).into(),
+                    segment_limit >> scale_shift,
                 )?;
             }
+            else if !matches!(test_instruction.iced_instruction().mnemonic(), Mnemonic::Lea) {
+                trace_error!(context, "No segment found with displacement and not LEA");
+                bail!("No segment found with displacement - unexpected condition.");
+            }
         }
+    }
 
-        if let Some(immediate_size) = test_instruction.immediate_size() {
-            trace_log!(
-                context,
-                "Test instruction uses immediate of size {} bytes",
-                immediate_size,
-            );
+    // Lay the test out to exercise the prefetch queue, if configured.
+    let (_, test_seed) = test_rng(context.file_seed, test_num);
+    context.queue_stress = choose_queue_stress(context, config, test_seed, opcode, &test_instruction, &test_registers);
+    if let Some(stress) = &context.queue_stress {
+        trace_log!(context, "Queue stress: {:?} ({} bytes)", stress.target, stress.bytes);
+        apply_queue_stress(config, test_seed, stress, &mut test_instruction, &mut test_registers);
+    }
+
+    Ok((test_registers, test_instruction))
+}
 
-            // if immediate_size > 2 &&
-            //     trace_log!(
-            //         context,
-            //         "Instruction is a flow control operation with large immediate. Masking immediate with segment limit {:08X} (sign-extended)",
-            //         segment_limit
-            //     )
-            // }
+/// Tell ArduinoX86 to execute instructions automatically and log their cycles, and set the
+/// default serial debug state.
+pub fn set_server_flags(context: &mut TestContext, config: &Config) -> anyhow::Result<()> {
+    let mut server_flags = ServerFlags::EXECUTE_AUTOMATIC | ServerFlags::ENABLE_CYCLE_LOGGING;
+
+    if let MooCpuType::Intel80386Ex = config.test_gen.cpu_type {
+        server_flags |= ServerFlags::USE_SMM;
+    }
+    if config.test_exec.encode_cycle_log {
+        if context.client().supports(Capabilities::CYCLE_LOG_ENCODING) {
+            server_flags |= ServerFlags::ENCODE_CYCLE_LOG;
         }
+        else {
+            log::warn!("The server does not support cycle log encoding; cycle logs will be sent raw.");
+        }
+    }
 
-        // Handle displacement masking.
-        // 32-bit displacements are too large to fully randomize, so we need to mask them to the segment limit.
-        if let Some(displacement_size) = test_instruction.displacement_size() {
-            trace_log!(
-                context,
-                "Test instruction uses displacement of size {} bytes",
-                displacement_size,
-            );
+    context.client().set_flags(server_flags)?;
+    context.client().enable_debug(config.test_exec.serial_debug_default)?;
+    Ok(())
+}
 
-            if displacement_size == 4 {
-                // Handle POP.  POP will touch multiple segments, with the last segment being SS.
-                // This isn't the segment we care about for masking so pop it.
-                if matches!(test_instruction.iced_instruction().mnemonic(), Mnemonic::Pop) && segments.len() > 1 {
-                    segments.pop();
-                }
+/// Enable HALT_AFTER_JUMP for flow control opcodes, and disable it for everything else. Also arm the
+/// server end condition of the opcode's termination condition.
+pub fn set_flow_control_halt(context: &mut TestContext, config: &Config, opcode: Opcode) -> anyhow::Result<()> {
+    let is_flow_control = config.test_gen.flow_control_opcodes.contains(&opcode.into());
+    let termination = match config.test_gen.flow_control_termination {
+        Some(termination) if is_flow_control => termination,
+        _ => config.test_gen.termination_condition,
+    };
+    // Servers without end conditions have none to disarm. Arming one was checked at startup.
+    if termination.end_condition().is_some() || context.client().supports(Capabilities::END_CONDITION) {
+        context.client().set_end_condition(termination.end_condition())?;
+    }
 
-                // Handle 0xFF opcodes
-                if matches!(
-                    test_instruction.iced_instruction().mnemonic(),
-                    Mnemonic::Call | Mnemonic::Jmp | Mnemonic::Push | Mnemonic::Pop
-                ) && segments.len() > 1
-                {
-                    log::debug!("Have multiple segments, keeping first : {:?}", segments);
-                    segments = vec![segments[0]];
-                }
+    if is_flow_control {
+        let flags = context.client().get_flags()?;
+        if flags & ServerFlags::HALT_AFTER_JUMP == 0 {
+            // Enable halt after jump if not already set.
+            context.client().set_flags(flags | ServerFlags::HALT_AFTER_JUMP)?;
+            log::debug!("Enabled HALT_AFTER_JUMP for opcode {}", opcode);
+        }
+    }
+    else {
+        let flags = context.client().get_flags()?;
+        if flags & ServerFlags::HALT_AFTER_JUMP != 0 {
+            // Disable halt after jump if set.
+            context.client().set_flags(flags & !ServerFlags::HALT_AFTER_JUMP)?;
+            log::debug!("Disabled HALT_AFTER_JUMP for opcode {}", opcode);
+        }
+    }
+    Ok(())
+}
 
-                if segments.len() > 1 {
-                    let error_msg = format!(
-                        "Multiple segments found with displacement: {:?} - unexpected condition.",
-                        segments
-                    );
-                    trace_error!(context, "{}", error_msg);
-                    bail!(error_msg);
-                }
-                else if !segments.is_empty() {
-                    let segment = segments[0];
-                    trace_log!(
-                        context,
-                        "Masking displacement with segment limit {:08X} (sign-extended)",
-                        segment_limit
-                    );
+/// Restore the server flags and end condition if the client reconnected since the last call, as a
+/// server reset by the reconnect has lost them. Returns true if it reconnected.
+fn resync_after_reconnect(context: &mut TestContext, config: &Config, opcode: Opcode) -> anyhow::Result<bool> {
+    let reconnects = context.client().take_reconnects();
+    if reconnects.is_empty() {
+        return Ok(false);
+    }
+    context.stats.add_reconnects(reconnects.len());
+    for reconnect in &reconnects {
+        trace_error!(context, "{}", reconnect);
+    }
+    set_server_flags(context, config)?;
+    set_flow_control_halt(context, config, opcode)?;
+    Ok(true)
+}
+
+fn generate_consistent_test(
+    context: &mut TestContext,
+    config: &Config,
+    test_num: usize,
+    opcode: Opcode,
+    have_group_ext: bool,
+    opcode_ext: u8,
+    required_matches: usize,
+) -> Result<MooTest, Error> {
+    let mut gen_num = 0;
+    let mut sieved = false;
+    let mut sieve_ct = 0;
+    let mut shutdown_ct = 0;
+
+    // Set flow control end condition
+    set_flow_control_halt(context, config, opcode)?;
 
-                    let scale_shift = match test_instruction.iced_instruction().memory_index_scale() {
-                        8 => 3,
-                        4 => 2,
-                        2 => 1,
-                        _ => 0,
-                    };
-                    test_instruction.mask_displacement32(
-                        context,
-                        &config.test_gen,
-                        test_registers.regs.segment_size(segment).into(),
-                        segment_limit >> scale_shift,
-                    )?;
-                }
-                else if !matches!(test_instruction.iced_instruction().mnemonic(), Mnemonic::Lea) {
-                    trace_error!(context, "No segment found with displacement and not LEA");
-                    bail!("No segment found with displacement - unexpected condition.");
-                }
-            }
-        }
+    // We'll attempt to generate a test up to 'max_gen' times before giving up.
+    // If we can't generate a test after that point, something has gone very wrong, like the
+    // ArduinoX86 has crashed, the opcode is invalid, or we hit a major bug.
+    while (sieved && sieve_ct < config.test_exec.max_sieve) || gen_num < config.test_exec.max_gen as usize {
+        // Generate a fresh Register & Instruction pair.
+        let (mut test_registers, test_instruction) =
+            generate_sequence(context, config, opcode, have_group_ext, opcode_ext, test_num, gen_num)?;
 
         let mut test_attempt_ct = 0;
         let mut prev_test: Option<MooTest> = None;
@@ -767,6 +1370,16 @@ fn generate_consistent_test(
                 &test_instruction,
                 &mut test_registers,
             );
+            context.stats.add_run(&test_result);
+
+            // The server may have been reset by a reconnect during the test, so its result can't be
+            // trusted even if every command succeeded.
+            if resync_after_reconnect(context, config, opcode)? {
+                match_count = 0;
+                prev_test = None;
+                test_attempt_ct += 1;
+                continue;
+            }
 
             match test_result {
                 Ok(test) => {
@@ -830,6 +1443,19 @@ fn generate_consistent_test(
                         }
                     }
 
+                    if context.smc_detected && config.test_gen.smc_policy == SmcPolicy::Reject {
+                        log::debug!("Rejecting self-modifying test for opcode {}", opcode);
+                        trace_log!(
+                            context,
+                            "SMC policy is Reject - rejecting test for opcode {}, gen_num: {}",
+                            opcode,
+                            gen_num
+                        );
+                        match_count = 0;
+                        prev_test = None;
+                        break 'gen;
+                    }
+
                     if let Some(prev) = &prev_test {
                         let comparison = prev.compare(&test);
                         let mut matched = false;
@@ -928,6 +1554,25 @@ fn generate_consistent_test(
                     );
                     match_count = 0;
                     prev_test = None;
+                    if e.downcast_ref::<CycleStreamError>().is_some() {
+                        context.cycle_stream_errors += 1;
+                    }
+                    if let Some(shutdown) = e.downcast_ref::<ShutdownError>() {
+                        shutdown_ct += 1;
+                        if shutdown_ct > config.test_exec.shutdown_retry {
+                            return Err(OpcodeBlocked {
+                                test_num,
+                                shutdowns: shutdown_ct,
+                                last_error: shutdown.last_error.clone(),
+                            }
+                            .into());
+                        }
+                    }
+                    if context.slow_abort {
+                        // Retrying the same instruction would just time out again.
+                        context.slow_abort = false;
+                        break 'gen;
+                    }
                 }
             }
 
@@ -951,6 +1596,27 @@ fn generate_consistent_test(
     Err(anyhow::anyhow!(error_msg).into())
 }
 
+/// Derive the seed for a test file from its opcode, prefix byte and group extension.
+pub fn file_seed(config: &Config, opcode_raw: u16, prefix_byte: Option<u8>, opcode_ext: u8) -> u64 {
+    let mut file_seed: u64 = opcode_raw as u64;
+    if let Some(prefix_byte) = prefix_byte {
+        file_seed = file_seed | ((prefix_byte as u64) << 8);
+    }
+    file_seed <<= 3;
+    file_seed |= (opcode_ext & 0x07) as u64;
+    file_seed ^ config.test_gen.base_seed
+}
+
+/// Return the seed of test `test_num` in a file, and the RNG it was drawn from.
+fn test_rng(file_seed: u64, test_num: usize) -> (rand::rngs::StdRng, u64) {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(file_seed);
+    let mut test_seed: u64 = rng.random();
+    for _ in 0..test_num {
+        test_seed = rng.random();
+    }
+    (rng, test_seed)
+}
+
 pub fn get_test_count(config: &Config, opcode: Opcode) -> usize {
     for ct_override in &config.test_gen.count_overrides {
         let [min, max] = &ct_override.opcode_range[..]
@@ -1096,6 +1762,36 @@ pub fn log_instruction(
     }
 }
 
+/// Mask the count register of an instruction with a REP or REPNE prefix, so string operations
+/// finish in a reasonable time.
+pub fn mask_rep_count(
+    context: &mut TestContext,
+    config: &Config,
+    test_instruction: &TestInstruction,
+    test_registers: &mut TestRegisters,
+) {
+    if test_instruction.iced_instruction().has_rep_prefix() || test_instruction.iced_instruction().has_repne_prefix() {
+        // If the instruction has a REP or REPNE prefix, log it.
+        trace_log!(
+            context,
+            "Instruction {} has REP/REPNE prefix. Masking CX with {:04X}",
+            test_instruction.name(),
+            config.test_gen.rep_cx_mask
+        );
+
+        match context.server_cpu {
+            ServerCpuType::Intel80386 => {
+                let ecx = test_registers.regs.ecx();
+                test_registers.regs.set_ecx(ecx & config.test_gen.rep_cx_mask as u32);
+            }
+            _ => {
+                let cx = test_registers.regs.cx();
+                test_registers.regs.set_cx(cx & config.test_gen.rep_cx_mask);
+            }
+        }
+    }
+}
+
 pub fn generate_test(
     context: &mut TestContext,
     config: &Config,
@@ -1125,44 +1821,37 @@ pub fn generate_test(
 
     // Mask CX register if the instruction has REP/REPNE prefix.
     // ---------------------------------------------------------------------------------------------
-    if test_instruction.iced_instruction().has_rep_prefix() || test_instruction.iced_instruction().has_repne_prefix() {
-        // If the instruction has a REP or REPNE prefix, log it.
-        trace_log!(
-            context,
-            "Instruction {} has REP/REPNE prefix. Masking CX with {:04X}",
-            test_instruction.name(),
-            config.test_gen.rep_cx_mask
-        );
+    mask_rep_count(context, config, test_instruction, test_registers);
 
-        match context.server_cpu {
-            ServerCpuType::Intel80386 => {
-                let ecx = test_registers.regs.ecx();
-                test_registers.regs.set_ecx(ecx & config.test_gen.rep_cx_mask as u32);
-            }
-            _ => {
-                let cx = test_registers.regs.cx();
-                test_registers.regs.set_cx(cx & config.test_gen.rep_cx_mask);
-            }
-        }
-    }
+    // Estimate how long the instruction should take.
+    // ---------------------------------------------------------------------------------------------
+    context.slow_abort = false;
+    context.store_dump = None;
+    let cycle_estimate = estimate_cycles(test_instruction.iced_instruction(), &test_registers.regs);
+    let time_limit = match &config.test_exec.cycle_budget {
+        Some(budget) => budget.time_limit_ms(cycle_estimate, config.test_exec.test_timeout),
+        None => config.test_exec.test_timeout,
+    };
+    trace_log!(
+        context,
+        "Estimated {} cycles, time limit {} ms",
+        cycle_estimate,
+        time_limit
+    );
 
     // Enable serial debug if configured.
     // ---------------------------------------------------------------------------------------------
     if Some(test_num) == config.test_exec.serial_debug_test {
         log::debug!("Enabling serial debug for test number {}", test_num);
-        context.client.enable_debug(true)?;
+        context.client().enable_debug(true)?;
     }
     else {
-        context.client.enable_debug(config.test_exec.serial_debug_default)?;
+        context.client().enable_debug(config.test_exec.serial_debug_default)?;
     }
 
     // Generate test seed.
     // ---------------------------------------------------------------------------------------------
-    let mut rng = rand::rngs::StdRng::seed_from_u64(context.file_seed);
-    let mut test_seed: u64 = rng.random();
-    for _ in 0..test_num {
-        test_seed = rng.random();
-    }
+    let (mut rng, test_seed) = test_rng(context.file_seed, test_num);
 
     let gen_metadata = MooTestGenMetadata {
         seed:   test_seed,
@@ -1171,7 +1860,7 @@ pub fn generate_test(
 
     // Set memory seed.
     // ---------------------------------------------------------------------------------------------
-    context.client.randomize_memory(test_seed as u32)?;
+    context.client().randomize_memory(test_seed as u32)?;
 
     // Determine the memory strategy based on the zero and ff chances.
     // ---------------------------------------------------------------------------------------------
@@ -1192,32 +1881,95 @@ pub fn generate_test(
         MemoryStrategy::Random
     };
 
+    // Memory writes for the test are staged and uploaded in one command once setup is complete.
+    let setup_start = Instant::now();
+    let mut batch = MemoryBatch::new();
+
     // Set memory strategy on the client.
-    context.client.set_memory_strategy(
-        strategy,
-        config.test_gen.mem_strategy_start,
-        config.test_gen.mem_strategy_end,
-    )?;
+    context.memory_strategy = strategy;
+    let (strategy_start, strategy_end) = context.mem_strategy_range(&config.test_gen);
+    if context.client().supports(Capabilities::MEMORY_STRATEGY) {
+        context
+            .client()
+            .set_memory_strategy(strategy, strategy_start, strategy_end)?;
+    }
 
     // Upload the instruction sequence.
-    log::trace!("Uploading instruction sequence...");
-    context
-        .client
-        .set_memory(test_registers.instruction_address, test_instruction.sequence_bytes())?;
+    log::trace!("Staging instruction sequence...");
+    batch.stage(test_registers.instruction_address, test_instruction.sequence_bytes());
 
     let end_address = test_registers.instruction_address + test_instruction.sequence_bytes().len() as u32;
-    context
-        .client
-        .set_program_bounds(test_registers.instruction_address, end_address)?;
+    if context.client().supports(Capabilities::PROGRAM_BOUNDS) {
+        context
+            .client()
+            .set_program_bounds(test_registers.instruction_address, end_address)?;
+    }
 
     // Fix up memory if necessary.
-    adjust_memory(context, test_seed, test_instruction, test_registers)?;
+    adjust_memory(&mut batch, test_seed, test_instruction, test_registers);
+
+    // Redirect the IVT entry of the vector the instruction raises, if configured.
+    // ---------------------------------------------------------------------------------------------
+    context.vector = choose_vector_target(config, test_seed, test_instruction, test_registers);
+    if let Some(vector) = &context.vector {
+        trace_log!(
+            context,
+            "Redirecting vector {:02X} to {:04X}:{:04X} ({:?})",
+            vector.vector,
+            vector.segment,
+            vector.offset,
+            vector.target
+        );
+        let entry = [vector.offset.to_le_bytes(), vector.segment.to_le_bytes()].concat();
+        let entry_address = vector.vector as u32 * 4;
+        batch.stage(entry_address, &entry);
+    }
+
+    log::trace!(
+        "Uploading {} staged memory writes in {} ranges...",
+        batch.writes(),
+        batch.ranges().len()
+    );
+    context.client().set_memory_ranges(&batch)?;
+
+    // Arm or disarm interrupt injection.
+    // ---------------------------------------------------------------------------------------------
+    if config.test_gen.interrupt_injection.is_some() {
+        context.interrupt = choose_interrupt(config, test_seed);
+        let trigger = match &context.interrupt {
+            Some(interrupt) => {
+                trace_log!(
+                    context,
+                    "Injecting {:?} at {:?} offset {} (vector {:02X})",
+                    interrupt.line,
+                    interrupt.mode,
+                    interrupt.offset,
+                    interrupt.vector
+                );
+                InterruptTrigger {
+                    line:   match interrupt.line {
+                        InjectLine::Intr => IrqLine::Intr,
+                        InjectLine::Nmi => IrqLine::Nmi,
+                    },
+                    mode:   match interrupt.mode {
+                        InjectMode::Instruction => IrqTriggerMode::Instruction,
+                        InjectMode::Cycle => IrqTriggerMode::Cycle,
+                    },
+                    vector: interrupt.vector,
+                    offset: interrupt.offset,
+                }
+            }
+            None => InterruptTrigger::default(),
+        };
+        context.client().set_interrupt_trigger(&trigger)?;
+    }
+    context.setup_stats.add(setup_start.elapsed(), &batch);
 
     // Load the registers onto the Arduino.
     // ---------------------------------------------------------------------------------------------
 
     // Determine server program state. If we're in SMM mode we will need to convert to V3B registers.
-    let state = context.client.get_program_state()?;
+    let state = context.client().get_program_state()?;
 
     // Reset cursor before writing to buffer!
     context.load_register_buffer.set_position(0);
@@ -1254,10 +2006,8 @@ pub fn generate_test(
         config.test_exec.load_retry
     );
 
-    if let Err(e) = context
-        .client
-        .load_registers_from_buf(load_type, context.load_register_buffer.get_ref())
-    {
+    let client = context.client.as_mut().expect("No hardware connection");
+    if let Err(e) = client.load_registers_from_buf(load_type, context.load_register_buffer.get_ref()) {
         // If the load fails, retry up to `config.test_exec.load_retry` times.
         while load_attempt_ct < config.test_exec.load_retry {
             load_attempt_ct += 1;
@@ -1266,8 +2016,7 @@ pub fn generate_test(
                 load_attempt_ct,
                 config.test_exec.load_retry
             );
-            if context
-                .client
+            if client
                 .load_registers_from_buf(load_type, context.load_register_buffer.get_ref())
                 .is_ok()
             {
@@ -1281,18 +2030,40 @@ pub fn generate_test(
 
     // Poll program state until finished with execution.
     // ---------------------------------------------------------------------------------------------
-    let mut state = context.client.get_program_state()?;
-    let mut test_timeout = false;
     let start_time = Instant::now();
-    while !matches!(
-        state,
-        ProgramState::StoreDone | ProgramState::StoreDoneSmm | ProgramState::Shutdown | ProgramState::Error
-    ) {
-        // Sleep for a little bit so we're not spamming the Arduino.
-        std::thread::sleep(std::time::Duration::from_millis(config.test_exec.polling_sleep.into()));
-
-        let millis = start_time.elapsed().as_millis() as u32;
-        if millis > config.test_exec.test_timeout {
+    let wait = context.client().wait_for_states(
+        &[
+            ProgramState::StoreDone,
+            ProgramState::StoreDoneSmm,
+            ProgramState::Shutdown,
+            ProgramState::Error,
+        ],
+        Duration::from_millis(time_limit.into()),
+        Duration::from_millis(config.test_exec.polling_sleep.into()),
+    );
+    let state = match wait {
+        Ok(status) => status.state,
+        Err(CpuClientError::StateTimeout(state)) => {
+            let millis = start_time.elapsed().as_millis() as u32;
+            if time_limit < config.test_exec.test_timeout {
+                // Don't let a pathological instruction eat the whole test timeout.
+                let error_str = format!(
+                    "Test exceeded cycle budget after {} ms (estimate {} cycles), program state is: {:?}",
+                    millis, cycle_estimate, state
+                );
+                trace_error!(context, "{}", error_str);
+                context.slow_tests.push(SlowTest {
+                    opcode,
+                    test_num,
+                    gen_num,
+                    instruction: test_instruction.name().to_string(),
+                    bytes: test_instruction.sequence_bytes().to_vec(),
+                    estimate: cycle_estimate,
+                    reason: SlowReason::Aborted { elapsed_ms: millis },
+                });
+                context.slow_abort = true;
+                bail!("{}", error_str);
+            }
             let error_str = format!(
                 "Test timeout reached after {} ms, program state is: {:?}",
                 millis, state
@@ -1300,27 +2071,27 @@ pub fn generate_test(
             trace_error!(context, "{}", error_str);
             bail!("{}", error_str);
         }
-        state = context.client.get_program_state()?;
-    }
+        Err(e) => return Err(e.into()),
+    };
 
     if matches!(state, ProgramState::Error) {
-        log::error!("Error executing instruction: {}", context.client.get_last_error()?);
+        log::error!("Error executing instruction: {}", context.client().get_last_error()?);
 
         context.last_program_state = Some(ProgramState::Error);
         return Err(anyhow::anyhow!(
             "Error executing instruction: {}",
-            context.client.get_last_error()?
+            context.client().get_last_error()?
         ));
     }
 
     if matches!(state, ProgramState::Shutdown) {
-        log::error!("Shutdown executing instruction: {}", context.client.get_last_error()?);
+        let last_error = context.client().get_last_error()?;
+        log::error!("Shutdown executing instruction: {}", last_error);
 
         context.last_program_state = Some(ProgramState::Shutdown);
-        return Err(anyhow::anyhow!(
-            "Shutdown executing instruction: {}",
-            context.client.get_last_error()?
-        ));
+        // Bring the CPU back so the test can be retried.
+        recover(context, config)?;
+        return Err(ShutdownError { last_error }.into());
     }
 
     // Read the registers back from the Arduino.
@@ -1328,31 +2099,115 @@ pub fn generate_test(
     log::trace!("Reading registers back from ArduinoX86...");
     let reg_type = context
         .client
+        .as_mut()
+        .expect("No hardware connection")
         .store_registers_to_buf(&mut context.store_register_buffer)
         .map_err(|e| anyhow::anyhow!("Error reading registers: {}", e))?;
 
+    // Read the cycle states from ArduinoX86.
+    // ---------------------------------------------------------------------------------------------
+    log::trace!("Reading cycle states from ArduinoX86...");
+    let cycle_states = context.client().get_cycle_states()?;
+    log::trace!("Got {} cycle states!", cycle_states.len(),);
+
+    // Account for where the run's cycles went. This is only statistics, so don't fail the test.
+    if context.client().supports(Capabilities::STATE_BUDGET) {
+        match context.client().get_state_budget() {
+            Ok(states) => context.phase_budget.merge(&PhaseBudget::from_server(&states)),
+            Err(e) => log::warn!("Failed to read the server state budget: {}", e),
+        }
+    }
+
+    // Keep everything read from the hardware, so the test can be rebuilt from a session log.
+    let mut initial_regs = Cursor::new(Vec::with_capacity(test_registers.regs.buf_len()));
+    test_registers.regs.to_buffer(&mut initial_regs);
+    let capture = Capture {
+        test: test_num as u32,
+        gen_ct: gen_num as u16,
+        seed: test_seed,
+        instruction_address: test_registers.instruction_address,
+        bytes: test_instruction.sequence_bytes().to_vec(),
+        initial_regs: initial_regs.into_inner(),
+        reg_type,
+        store_regs: context.store_register_buffer.clone(),
+        cycles: cycle_states,
+    };
+
+    let test = build_test(
+        context,
+        config,
+        test_num,
+        gen_num,
+        opcode,
+        test_instruction,
+        test_registers,
+        gen_metadata,
+        &capture,
+    )?;
+    context.capture = Some(capture);
+    Ok(test)
+}
+
+/// Build a test from the hardware I/O in `capture`. This is everything after execution that does
+/// not talk to the hardware, so it is shared by generation and rebuilding from session logs.
+pub fn build_test(
+    context: &mut TestContext,
+    config: &Config,
+    test_num: usize,
+    gen_num: usize,
+    opcode: Opcode,
+    test_instruction: &TestInstruction,
+    test_registers: &TestRegisters,
+    gen_metadata: MooTestGenMetadata,
+    capture: &Capture,
+) -> anyhow::Result<MooTest> {
+    let reg_type = capture.reg_type;
+
+    // Keep the raw dump, so parsing problems can be diagnosed from the test file or trace log.
+    trace_log!(
+        context,
+        "Raw register dump (type {}): {}",
+        reg_type,
+        capture
+            .store_regs
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<String>()
+    );
+    context.store_dump = Some(StoreDump {
+        test: test_num as u32,
+        reg_type,
+        data: capture.store_regs.clone(),
+    });
+
     let final_regs = match reg_type {
         0x0 => {
             // V1 registers
-            unimplemented!()
+            let regs_v1 = RemoteCpuRegistersV1::decode(capture.store_regs.as_slice())
+                .map_err(|e| anyhow::anyhow!("Error parsing V1 registers: {}", e))?;
+
+            if config.test_exec.print_final_regs {
+                println!("{}", regs_v1.display(config.test_gen.cpu_type.into()));
+            }
+            Registers::V1(regs_v1)
         }
         0x1 => {
             // V2 registers
-            let regs_v2 = RemoteCpuRegistersV2::try_from(context.store_register_buffer.as_slice())
+            let regs_v2 = RemoteCpuRegistersV2::try_from(capture.store_regs.as_slice())
                 .map_err(|e| anyhow::anyhow!("Error parsing V2 registers: {}", e))?;
 
             if config.test_exec.print_final_regs {
-                print_regs_v2(&regs_v2, config.test_gen.cpu_type.into());
+                println!("{}", regs_v2.display(config.test_gen.cpu_type.into()));
             }
             Registers::V2(regs_v2)
         }
         0x3 => {
             // V3B registers
-            let regs_v3b = RemoteCpuRegistersV3B::try_from(context.store_register_buffer.as_slice())
+            let regs_v3b = RemoteCpuRegistersV3B::try_from(capture.store_regs.as_slice())
                 .map_err(|e| anyhow::anyhow!("Error parsing V3B registers: {}", e))?;
 
             if config.test_exec.print_final_regs {
-                //print_regs_v3b(&regs_v3b, config.test_gen.cpu_type.into());
+                println!("{}", regs_v3b.display(config.test_gen.cpu_type.into()));
             }
             Registers::V3B(regs_v3b)
         }
@@ -1362,18 +2217,16 @@ pub fn generate_test(
         }
     };
 
-    // Read the cycle states from ArduinoX86.
-    // ---------------------------------------------------------------------------------------------
-    log::trace!("Reading cycle states from ArduinoX86...");
-    let cycle_states = context.client.get_cycle_states()?;
-    log::trace!("Got {} cycle states!", cycle_states.len(),);
+    let cycle_states = &capture.cycles;
+    let cycle_estimate = estimate_cycles(test_instruction.iced_instruction(), &test_registers.regs);
 
     let mut my_cycle_vec = Vec::new();
 
     // Convert cycle states to MooCycleStates.
     let mut moo_cycle_states = Vec::with_capacity(cycle_states.len());
-    for cycle_state in &cycle_states {
+    for cycle_state in cycle_states {
         let my_cycle = match config.test_gen.cpu_type {
+            MooCpuType::Intel80186 | MooCpuType::Intel80188 => MyServerCycleState::State186(cycle_state.clone()),
             MooCpuType::Intel80286 => MyServerCycleState::State286(cycle_state.clone()),
             MooCpuType::Intel80386Ex => MyServerCycleState::State386Ex(cycle_state.clone()),
             _ => unimplemented!(
@@ -1387,13 +2240,61 @@ pub fn generate_test(
 
     log_cycle_states(context, &moo_cycle_states);
 
+    if let Err(e) = check_cycles(&my_cycle_vec) {
+        log::warn!("Cycle stream validation failed: {}", e);
+        trace_error!(context, "Cycle stream validation failed: {}", e);
+        return Err(e.into());
+    }
+
+    if let Some(budget) = &config.test_exec.cycle_budget {
+        let cycle_ct = moo_cycle_states.len() as u32;
+        if cycle_ct > budget.cycle_limit(cycle_estimate) {
+            trace_log!(
+                context,
+                "Test took {} cycles, over budget of {} cycles",
+                cycle_ct,
+                budget.cycle_limit(cycle_estimate)
+            );
+            // The consistency check runs the same test several times, so only report it once.
+            let reported = context
+                .slow_tests
+                .iter()
+                .any(|slow| slow.opcode == opcode && slow.test_num == test_num && slow.gen_num == gen_num);
+            if !reported {
+                context.slow_tests.push(SlowTest {
+                    opcode,
+                    test_num,
+                    gen_num,
+                    instruction: test_instruction.name().to_string(),
+                    bytes: test_instruction.sequence_bytes().to_vec(),
+                    estimate: cycle_estimate,
+                    reason: SlowReason::Exceeded { cycles: cycle_ct },
+                });
+            }
+        }
+    }
+
     // Collect BusOps from cycle states.
     // ---------------------------------------------------------------------------------------------
     let bus_ops = BusOps::from(my_cycle_vec.as_slice());
     log::trace!("Got {} bus operations from cycles", bus_ops.len(),);
     bus_ops.log(context);
 
-    if let Err(e) = bus_ops.validate(
+    if let Err(e) = check_bus_ops(
+        bus_ops.ops(),
+        CpuWidth::from(context.server_cpu),
+        test_registers.regs.cs_base(),
+        test_registers.regs.ip(),
+        test_instruction.sequence_bytes(),
+        // A jump refetches bytes the queue already held, so fetches may go backwards.
+        !config.test_gen.flow_control_opcodes.contains(&opcode.into()) && context.queue_stress.is_none(),
+    ) {
+        log::warn!("Cycle stream validation failed: {}", e);
+        trace_error!(context, "Cycle stream validation failed: {}", e);
+        return Err(e.into());
+    }
+
+    let expected_exception = match bus_ops.validate(
         config,
         &test_registers.regs,
         opcode,
@@ -1401,10 +2302,13 @@ pub fn generate_test(
         test_instruction.op0_kind(),
         test_instruction.op1_kind(),
     ) {
-        log::error!("Bus operation validation failed: {}", e);
-        trace_log!(context, "Bus operation validation failed: {}", e);
-        return Err(e);
-    }
+        Ok(expected_exception) => expected_exception,
+        Err(e) => {
+            log::error!("Bus operation validation failed: {}", e);
+            trace_log!(context, "Bus operation validation failed: {}", e);
+            return Err(e);
+        }
+    };
 
     if let Err(e) = validate_regs(&final_regs) {
         log::error!("Register validation failed: {}", e);
@@ -1452,10 +2356,41 @@ pub fn generate_test(
         trace_log!(context, "Flags on stack at {:06X}", exception.flag_address);
     }
 
+    // A memory operand beyond its segment limit must have raised #GP or #SS.
+    if let Some(expected) = expected_exception {
+        if exception.as_ref().map(|e| e.exception_num) != Some(expected) {
+            log::error!("Memory operand exceeds segment limit, but exception {} was not raised", expected);
+            trace_log!(
+                context,
+                "Memory operand exceeds segment limit, but exception {} was not raised",
+                expected
+            );
+            bail!("Memory operand exceeds segment limit, but exception {} was not raised", expected);
+        }
+    }
+
+    // Detect writes to bytes already fetched as code (self-modifying code).
+    // ---------------------------------------------------------------------------------------------
+    let smc_writes = bus_ops.detect_smc(
+        CpuWidth::from(context.server_cpu).queue_size(),
+        test_registers.regs.calculate_code_address(),
+        test_instruction.sequence_bytes().len(),
+    );
+    for smc_write in &smc_writes {
+        trace_log!(
+            context,
+            "SMC: bus op {} writes {:04X} to {:06X}, which was already fetched as code",
+            smc_write.idx,
+            smc_write.data,
+            smc_write.addr
+        );
+    }
+    context.smc_detected = !smc_writes.is_empty();
+
     // Log final register state.
     // ---------------------------------------------------------------------------------------------
     match config.test_gen.cpu_type {
-        MooCpuType::Intel80286 => {
+        MooCpuType::Intel80186 | MooCpuType::Intel80188 | MooCpuType::Intel80286 => {
             trace_log!(
                 context,
                 "{}",
@@ -1518,12 +2453,194 @@ pub fn generate_test(
     Ok(test)
 }
 
+/// Salt mixed into the test seed so interrupt injection does not disturb the main test RNG.
+const INTERRUPT_SEED_SALT: u64 = 0x1A7E_1A7E_1A7E_1A7E;
+
+/// Decide whether to inject an interrupt into a test, and where. This is derived from the test seed
+/// so that every validation run of a test injects the same interrupt.
+pub fn choose_interrupt(config: &Config, test_seed: u64) -> Option<InterruptRecord> {
+    let injection = config.test_gen.interrupt_injection.as_ref()?;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(test_seed ^ INTERRUPT_SEED_SALT);
+    if rng.random::<f32>() >= injection.chance {
+        return None;
+    }
+
+    let line = if rng.random::<f32>() < injection.nmi_chance {
+        InjectLine::Nmi
+    }
+    else {
+        InjectLine::Intr
+    };
+    let offset = rng.random_range(injection.offset_range[0]..=injection.offset_range[1]);
+
+    Some(InterruptRecord {
+        test: 0,
+        line,
+        mode: injection.mode,
+        offset,
+        vector: injection.vector,
+    })
+}
+
+/// Salt mixed into the test seed so IVT stress does not disturb the main test RNG.
+const IVT_SEED_SALT: u64 = 0x1B7E_5E7D_1B7E_5E7D;
+
+/// Return the vector an instruction raises when it executes or faults, if it is one IVT stress
+/// applies to.
+fn raised_vector(test_instruction: &TestInstruction) -> Option<u8> {
+    let iced_i = test_instruction.iced_instruction();
+    match iced_i.mnemonic() {
+        Mnemonic::Int => Some(iced_i.immediate8()),
+        Mnemonic::Int3 => Some(3),
+        Mnemonic::Into => Some(4),
+        Mnemonic::Div | Mnemonic::Idiv | Mnemonic::Aam => Some(0),
+        _ => None,
+    }
+}
+
+/// Decide whether to redirect the IVT entry of the vector a test raises, and where to. This is
+/// derived from the test seed so that rebuilding a test chooses the same target.
+pub fn choose_vector_target(
+    config: &Config,
+    test_seed: u64,
+    test_instruction: &TestInstruction,
+    test_registers: &TestRegisters,
+) -> Option<VectorRecord> {
+    let stress = config.test_gen.ivt_stress.as_ref()?;
+    let vector = raised_vector(test_instruction)?;
+    if stress.targets.is_empty() {
+        return None;
+    }
+
+    // Never overwrite the instruction itself.
+    let entry_address = vector as u32 * 4;
+    let start = test_registers.instruction_address;
+    let end = start + test_instruction.sequence_bytes().len() as u32;
+    if entry_address < end && start < entry_address + 4 {
+        return None;
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(test_seed ^ IVT_SEED_SALT);
+    if rng.random::<f32>() >= stress.chance {
+        return None;
+    }
+
+    let target = stress.targets[rng.random_range(0..stress.targets.len())];
+    let (segment, offset) = match target {
+        IvtTarget::OddAddress => (rng.random::<u16>(), rng.random::<u16>() | 1),
+        IvtTarget::SegmentTop => (rng.random::<u16>(), 0xFFFF - rng.random_range(0..4u16)),
+        IvtTarget::InsideInstruction => {
+            let len = test_instruction.sequence_bytes().len() as u16;
+            (
+                test_registers.regs.cs(),
+                test_registers.regs.ip().wrapping_add(rng.random_range(0..len)),
+            )
+        }
+    };
+
+    Some(VectorRecord {
+        test: 0,
+        vector,
+        target,
+        segment,
+        offset,
+    })
+}
+
+/// Salt mixed into the test seed so queue stress does not disturb the main test RNG.
+const QUEUE_STRESS_SEED_SALT: u64 = 0x9E0E_F1A5_9E0E_F1A5;
+/// Salt for the filler bytes a `JumpAfter` jump skips over.
+const QUEUE_FILLER_SEED_SALT: u64 = 0xF111_E4B7_F111_E4B7;
+/// The most filler bytes a `JumpAfter` jump skips over.
+const QUEUE_STRESS_MAX_SKIP: u8 = 8;
+
+/// Return the IP and instruction address that put `overhang` bytes of the instruction past the code
+/// segment limit, if a 16-bit IP can reach the limit.
+fn segment_limit_layout(test_registers: &TestRegisters, overhang: u8, address_mask: u32) -> Option<(u16, u32)> {
+    let limit = test_registers.regs.segment_limit(iced_x86::Register::CS)?;
+    let ip = u16::try_from(limit.checked_add(1)?.checked_sub(overhang as u32)?).ok()?;
+    let address = test_registers.regs.cs_base().wrapping_add(ip as u32) & address_mask;
+    Some((ip, address))
+}
+
+/// Decide whether to lay a test out for queue stress, and how. This is derived from the test seed
+/// and the instruction so that rebuilding a test chooses the same layout.
+pub fn choose_queue_stress(
+    context: &TestContext,
+    config: &Config,
+    test_seed: u64,
+    opcode: Opcode,
+    test_instruction: &TestInstruction,
+    test_registers: &TestRegisters,
+) -> Option<QueueStressRecord> {
+    let stress = config.test_gen.queue_stress.as_ref()?;
+    if stress.targets.is_empty() || config.test_gen.flow_control_opcodes.contains(&opcode.into()) {
+        return None;
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(test_seed ^ QUEUE_STRESS_SEED_SALT);
+    if rng.random::<f32>() >= stress.chance {
+        return None;
+    }
+
+    let target = stress.targets[rng.random_range(0..stress.targets.len())];
+    let bytes = match target {
+        QueueStressTarget::JumpAfter => {
+            if !config.test_gen.termination_condition.appends_halt() {
+                return None;
+            }
+            rng.random_range(0..=QUEUE_STRESS_MAX_SKIP)
+        }
+        QueueStressTarget::SegmentLimit => {
+            let len = test_instruction.instr_bytes().len();
+            if len < 2 || !matches!(context.code_segment_size, SegmentSize::Sixteen) {
+                return None;
+            }
+            let overhang = rng.random_range(1..len) as u8;
+            // The whole sequence must still land where test code may be placed.
+            let (_, start) = segment_limit_layout(test_registers, overhang, config.test_gen.address_mask)?;
+            let end = start + test_instruction.sequence_bytes().len() as u32 - 1;
+            if !context.code_address_allowed(&config.test_gen, start)
+                || !context.code_address_allowed(&config.test_gen, end)
+            {
+                return None;
+            }
+            overhang
+        }
+    };
+
+    Some(QueueStressRecord { test: 0, target, bytes })
+}
+
+/// Lay a test out as chosen by [choose_queue_stress].
+pub fn apply_queue_stress(
+    config: &Config,
+    test_seed: u64,
+    stress: &QueueStressRecord,
+    test_instruction: &mut TestInstruction,
+    test_registers: &mut TestRegisters,
+) {
+    match stress.target {
+        QueueStressTarget::JumpAfter => {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(test_seed ^ QUEUE_FILLER_SEED_SALT);
+            let filler = (0..stress.bytes).map(|_| rng.random::<u8>()).collect::<Vec<_>>();
+            test_instruction.append_jump(&filler);
+        }
+        QueueStressTarget::SegmentLimit => {
+            let (ip, address) = segment_limit_layout(test_registers, stress.bytes, config.test_gen.address_mask)
+                .expect("Queue stress layout was checked when it was chosen");
+            test_registers.regs.set_ip(ip);
+            test_registers.instruction_address = address;
+        }
+    }
+}
+
 pub fn adjust_memory(
-    context: &mut TestContext,
+    batch: &mut MemoryBatch,
     test_seed: u64,
     test_instruction: &TestInstruction,
     test_registers: &mut TestRegisters,
-) -> anyhow::Result<()> {
+) {
     // If the instruction is POPF, we need to generate a flag value without the trap flag.
     match test_instruction.iced_instruction().mnemonic() {
         Mnemonic::Popf => {
@@ -1534,7 +2651,7 @@ pub fn adjust_memory(
             // Calculate the stack address.
             let stack_address = test_registers.regs.stack_address();
             // Write the flags to the stack.
-            context.client.set_memory(stack_address, &flags.to_le_bytes())?;
+            batch.stage(stack_address, &flags.to_le_bytes());
         }
         Mnemonic::Iret => {
             // Generate a random flag value without the trap flag.
@@ -1546,11 +2663,10 @@ pub fn adjust_memory(
             stack_address += test_registers.regs.sp().wrapping_add(4) as u32;
 
             // Write the flags to the stack.
-            context.client.set_memory(stack_address, &flags.to_le_bytes())?;
+            batch.stage(stack_address, &flags.to_le_bytes());
         }
         _ => {}
     }
-    Ok(())
 }
 
 pub fn log_bus_ops(context: &mut TestContext, bus_ops: &[BusOp]) {
@@ -0,0 +1,383 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Hand-written "golden" tests.
+//!
+//! Random generation can take a long time to stumble on a known-tricky corner case, if it ever
+//! does. A golden test pins one down: a TOML descriptor giving the instruction (as NASM source or
+//! raw bytes), any registers to set and any memory to fill. Descriptors live in a directory per
+//! test file, named after the file without its extension (e.g. `golden/F6.6/div_by_zero.toml`),
+//! and are run in file name order as the first tests of the file, ahead of the random tests.
+//!
+//! ```toml
+//! asm = "div byte [bx]"
+//!
+//! [regs]
+//! ax = 0x1234
+//! bx = 0x0100
+//!
+//! [[ram]]
+//! address = 0x10100
+//! bytes = [0x00]
+//! ```
+//!
+//! Registers not named keep the random values the generator would have given the test, so a
+//! descriptor only needs to set what makes the case interesting.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, bail, Context};
+use moo::{
+    prelude::*,
+    types::{MooComparison, MooRamEntry},
+};
+use serde::Deserialize;
+
+use crate::{
+    gen_regs::TestRegisters,
+    gen_tests::{generate_test, set_flow_control_halt, write_initial_mem},
+    instruction::TestInstruction,
+    registers::Registers,
+    trace_error,
+    trace_log,
+    Config,
+    CpuMode,
+    InstructionSize,
+    Opcode,
+    TestContext,
+    TestOpcodeSizePrefix,
+};
+
+/// A golden test descriptor, as written.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GoldenDescriptor {
+    /// NASM source for the instruction. A `bits` directive for the code segment is added.
+    #[serde(default)]
+    asm:   Option<String>,
+    /// The raw instruction bytes, for forms NASM won't emit.
+    #[serde(default)]
+    bytes: Option<Vec<u8>>,
+    /// Register values by lowercase name, e.g. `ax`, `eflags` or `ds`.
+    #[serde(default)]
+    regs:  BTreeMap<String, u32>,
+    #[serde(default)]
+    ram:   Vec<GoldenRam>,
+}
+
+/// Bytes to place in memory before the test runs.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GoldenRam {
+    address: u32,
+    bytes:   Vec<u8>,
+}
+
+/// A golden test, assembled and ready to run.
+pub struct GoldenTest {
+    pub name: String,
+    bytes: Vec<u8>,
+    regs: BTreeMap<String, u32>,
+    ram: Vec<MooRamEntry>,
+}
+
+/// Read and assemble the golden tests for a test file, in file name order. A file with no golden
+/// directory has no golden tests.
+pub fn load_golden_tests(dir: &Path, file_key: &str, width: InstructionSize) -> anyhow::Result<Vec<GoldenTest>> {
+    let stem = file_key.strip_suffix(".MOO").unwrap_or(file_key);
+    let test_dir = dir.join(stem);
+    if !test_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = fs::read_dir(&test_dir)
+        .with_context(|| format!("Reading golden test directory: {}", test_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| load_golden_test(path, width).with_context(|| format!("Loading golden test: {}", path.display())))
+        .collect()
+}
+
+fn load_golden_test(path: &Path, width: InstructionSize) -> anyhow::Result<GoldenTest> {
+    let text = fs::read_to_string(path)?;
+    let descriptor: GoldenDescriptor = toml::from_str(&text)?;
+
+    let bytes = match (&descriptor.asm, descriptor.bytes) {
+        (Some(asm), None) => assemble(asm, width)?,
+        (None, Some(bytes)) => bytes,
+        _ => bail!("A golden test needs exactly one of 'asm' or 'bytes'"),
+    };
+    if bytes.is_empty() {
+        bail!("Golden test has no instruction bytes");
+    }
+
+    let mut ram = Vec::new();
+    for span in &descriptor.ram {
+        for (offset, value) in span.bytes.iter().enumerate() {
+            ram.push(MooRamEntry {
+                address: span.address + offset as u32,
+                value:   *value,
+            });
+        }
+    }
+    ram.sort_by_key(|entry| entry.address);
+
+    Ok(GoldenTest {
+        name: path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        bytes,
+        regs: descriptor.regs,
+        ram,
+    })
+}
+
+/// Assemble `asm` with NASM as a flat binary. NASM is found through the `NASM_PATH` environment
+/// variable, or on the path.
+fn assemble(asm: &str, width: InstructionSize) -> anyhow::Result<Vec<u8>> {
+    let nasm_path = env::var_os("NASM_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("nasm"));
+
+    let mut source = tempfile::NamedTempFile::with_suffix(".asm")?;
+    writeln!(source, "bits {}", u32::from(width))?;
+    writeln!(source, "{}", asm)?;
+    source.flush()?;
+    let output = tempfile::NamedTempFile::with_suffix(".bin")?;
+
+    let result = Command::new(&nasm_path)
+        .arg("-f")
+        .arg("bin")
+        .arg("-o")
+        .arg(output.path())
+        .arg(source.path())
+        .output()
+        .with_context(|| format!("Running NASM: {}", nasm_path.display()))?;
+    if !result.status.success() {
+        bail!("NASM failed: {}", String::from_utf8_lossy(&result.stderr).trim());
+    }
+
+    Ok(fs::read(output.path())?)
+}
+
+/// Set a register by name.
+fn set_register(regs: &mut Registers, name: &str, value: u32) -> anyhow::Result<()> {
+    if let Some(regs16) = regs.as_registers16_mut() {
+        let reg = match name {
+            "ax" => regs16.ax_mut(),
+            "bx" => regs16.bx_mut(),
+            "cx" => regs16.cx_mut(),
+            "dx" => regs16.dx_mut(),
+            "sp" => regs16.sp_mut(),
+            "bp" => regs16.bp_mut(),
+            "si" => regs16.si_mut(),
+            "di" => regs16.di_mut(),
+            "cs" => regs16.cs_mut(),
+            "ds" => regs16.ds_mut(),
+            "es" => regs16.es_mut(),
+            "ss" => regs16.ss_mut(),
+            "ip" => regs16.ip_mut(),
+            "flags" => regs16.flags_mut(),
+            _ => bail!("Unknown 16-bit register '{}'", name),
+        };
+        *reg = u16::try_from(value).map_err(|_| anyhow!("Value {:X} is too large for register '{}'", value, name))?;
+    }
+    else if let Some(regs32) = regs.as_registers32_mut() {
+        let segment = match name {
+            "cs" => Some(regs32.cs_mut()),
+            "ds" => Some(regs32.ds_mut()),
+            "es" => Some(regs32.es_mut()),
+            "fs" => Some(regs32.fs_mut()),
+            "gs" => Some(regs32.gs_mut()),
+            "ss" => Some(regs32.ss_mut()),
+            _ => None,
+        };
+        if let Some(segment) = segment {
+            *segment =
+                u16::try_from(value).map_err(|_| anyhow!("Value {:X} is too large for register '{}'", value, name))?;
+            return Ok(());
+        }
+
+        let reg = match name {
+            "eax" => regs32.eax_mut(),
+            "ebx" => regs32.ebx_mut(),
+            "ecx" => regs32.ecx_mut(),
+            "edx" => regs32.edx_mut(),
+            "esp" => regs32.esp_mut(),
+            "ebp" => regs32.ebp_mut(),
+            "esi" => regs32.esi_mut(),
+            "edi" => regs32.edi_mut(),
+            "eip" => regs32.eip_mut(),
+            "eflags" => regs32.eflags_mut(),
+            "cr0" => regs32.cr0_mut(),
+            "dr6" => regs32.dr6_mut(),
+            "dr7" => regs32.dr7_mut(),
+            _ => bail!("Unknown 32-bit register '{}'", name),
+        };
+        *reg = value;
+    }
+    Ok(())
+}
+
+impl GoldenTest {
+    /// Build the initial registers for the test: the random registers the generator would use for
+    /// this test number, with the descriptor's registers applied on top.
+    fn registers(
+        &self,
+        context: &mut TestContext,
+        config: &Config,
+        opcode: Opcode,
+        test_num: usize,
+    ) -> anyhow::Result<TestRegisters> {
+        let mut test_registers = TestRegisters::new(context, config, opcode, test_num, 0);
+        if self.regs.is_empty() {
+            return Ok(test_registers);
+        }
+
+        // In unreal mode, keep the segments as far past their real mode bases as generated.
+        let high_base = test_registers
+            .regs
+            .cs_base()
+            .wrapping_sub((test_registers.regs.cs() as u32) << 4);
+        for (name, value) in &self.regs {
+            set_register(&mut test_registers.regs, &name.to_ascii_lowercase(), *value)?;
+        }
+        // Segment registers may have changed, so rebuild the descriptors in real and protected mode.
+        match config.test_gen.cpu_mode {
+            CpuMode::Real => test_registers.regs.normalize_descriptors(),
+            CpuMode::Protected => {
+                test_registers.regs.normalize_descriptors();
+                test_registers.regs.enter_protected_mode();
+            }
+            CpuMode::Unreal => {
+                test_registers.regs.normalize_descriptors();
+                test_registers.regs.enter_unreal_mode(high_base);
+            }
+        }
+        test_registers.regs.set_code_segment_size(context.gen_width.into());
+
+        test_registers.instruction_address =
+            test_registers.regs.calculate_code_address() & config.test_gen.address_mask;
+        if !context.code_address_allowed(&config.test_gen, test_registers.instruction_address) {
+            bail!(
+                "Golden test '{}' places code at {:08X}, outside the allowed instruction range",
+                self.name,
+                test_registers.instruction_address
+            );
+        }
+        Ok(test_registers)
+    }
+}
+
+/// Run a golden test on the hardware until it gives `required_matches` identical results in a row.
+/// Unlike a random test, a golden test is never swapped for a fresh instruction, so failing to get
+/// a consistent result is an error.
+pub fn generate_golden_test(
+    context: &mut TestContext,
+    config: &Config,
+    test_num: usize,
+    opcode: Opcode,
+    op_ext: Option<u8>,
+    golden: &GoldenTest,
+    required_matches: usize,
+) -> anyhow::Result<MooTest> {
+    set_flow_control_halt(context, config, opcode)?;
+
+    let mut test_registers = golden.registers(context, config, opcode, test_num)?;
+    context.code_segment_size = test_registers.regs.segment_size(iced_x86::Register::CS);
+
+    // The bytes carry any size prefixes, so decode relative to the code segment alone.
+    let test_instruction = TestInstruction::from((
+        TestOpcodeSizePrefix::None.relative_opcode_size(context.code_segment_size),
+        TestOpcodeSizePrefix::None.relative_address_size(context.code_segment_size),
+        golden.bytes.as_slice(),
+    ));
+    trace_log!(
+        context,
+        "Running golden test '{}' as test {}: {}",
+        golden.name,
+        test_num,
+        test_instruction.name()
+    );
+
+    let mut prev_test: Option<MooTest> = None;
+    let mut match_count = 0;
+    for attempt in 0..config.test_exec.test_retry {
+        write_initial_mem(context, &golden.ram)?;
+
+        let result = generate_test(
+            context,
+            config,
+            test_num,
+            0,
+            opcode,
+            op_ext,
+            &test_instruction,
+            &mut test_registers,
+        );
+        context.stats.add_run(&result);
+        match result {
+            Ok(test) => {
+                match &prev_test {
+                    Some(prev) if matches!(prev.compare(&test), MooComparison::Equal) => match_count += 1,
+                    Some(_) => {
+                        trace_error!(context, "Golden test '{}' did not match previous run.", golden.name);
+                        match_count = 0;
+                    }
+                    None => match_count = 0,
+                }
+                if match_count + 1 >= required_matches {
+                    return Ok(test);
+                }
+                prev_test = Some(test);
+            }
+            Err(e) => {
+                trace_error!(
+                    context,
+                    "Failed to run golden test '{}', attempt {}: {}",
+                    golden.name,
+                    attempt + 1,
+                    e
+                );
+                match_count = 0;
+                prev_test = None;
+            }
+        }
+    }
+
+    bail!(
+        "Golden test '{}' did not give a consistent result after {} attempts",
+        golden.name,
+        config.test_exec.test_retry
+    )
+}
@@ -0,0 +1,454 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Infer undefined flags masks by running instructions on the hardware.
+//!
+//! Each opcode and group extension gets a batch of generated tests, and every test in the batch is
+//! run several times with the same registers and memory. A flag bit that comes out differently
+//! between runs of the same test isn't determined by the instruction's inputs, so it is added to
+//! the opcode's undefined flags. The runs alternate direction through the batch, so each repeat of
+//! a test follows a different instruction than the last. Tests with such flags fail the
+//! generator's consistency check until their opcode's mask covers them.
+//!
+//! Undefined flags that the silicon computes deterministically from the inputs look defined to
+//! this pass, so those still need to be marked by hand.
+//!
+//! The masks are written to the `flags` and `flags-mask` keys of the opcode's metadata entry, in
+//! the `[metadata.opcodes]` tables of a generator config or the `opcodes` object of a JSON
+//! metadata file. By default the inferred flags are added to those already marked undefined.
+//! TOML files keep their formatting and comments.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{bail, Context};
+use moo::{prelude::*, types::MooRegisters};
+use serde_json::{Map, Value};
+use toml_edit::{DocumentMut, Item, Table, TableLike};
+
+use crate::{
+    flags::*,
+    gen_tests::{
+        file_seed,
+        generate_sequence,
+        generate_test,
+        get_group_extension_range,
+        set_flow_control_halt,
+        set_server_flags,
+    },
+    trace_error,
+    trace_log,
+    Config,
+    InferFlagsArgs,
+    Opcode,
+    TestContext,
+    TestOpcodeSizePrefix,
+};
+
+/// The flags named in a metadata `flags` string, in order. An undefined flag is written as its
+/// letter and a defined flag as '.'.
+const FLAG_LETTERS: [(char, u16); 8] = [
+    ('o', CPU_FLAG_OVERFLOW),
+    ('d', CPU_FLAG_DIRECTION),
+    ('i', CPU_FLAG_INT_ENABLE),
+    ('s', CPU_FLAG_SIGN),
+    ('z', CPU_FLAG_ZERO),
+    ('a', CPU_FLAG_AUX_CARRY),
+    ('p', CPU_FLAG_PARITY),
+    ('c', CPU_FLAG_CARRY),
+];
+
+/// The undetermined flags seen for an opcode or group extension.
+#[derive(Copy, Clone, Debug, Default)]
+struct FlagsTally {
+    /// Tests that completed at least two runs.
+    tests: usize,
+    undetermined: u16,
+}
+
+/// A metadata entry whose undefined flags changed.
+#[derive(Debug)]
+struct MaskChange {
+    entry: String,
+    old:   u16,
+    new:   u16,
+}
+
+/// Run tests for every opcode the config describes, and write the undefined flags masks they
+/// imply to the metadata file.
+pub fn infer_flags(context: &mut TestContext, config: &Config, args: &InferFlagsArgs) -> anyhow::Result<()> {
+    if args.runs < 2 {
+        bail!("--runs must be at least 2, so there are runs to compare.");
+    }
+    if config.test_gen.gen_widths.is_empty() {
+        bail!("gen_widths is empty, so there are no tests to run.");
+    }
+    set_server_flags(context, config)?;
+
+    let (opcode_range_start, opcode_range_end) = match config.test_gen.opcode_override {
+        Some(opcode_override) => (opcode_override, opcode_override),
+        None => (config.test_gen.opcode_range[0], config.test_gen.opcode_range[1]),
+    };
+
+    // Keyed by opcode and group extension, as masks aren't kept per width or size prefix.
+    let mut tallies: BTreeMap<(u16, Option<u8>), FlagsTally> = BTreeMap::new();
+    for &width in &config.test_gen.gen_widths {
+        context.gen_width = width;
+
+        for opcode_raw in opcode_range_start..=opcode_range_end {
+            let opcode = Opcode::from(opcode_raw);
+            if !config.test_gen.valid_opcodes.contains(&opcode_raw)
+                || config.test_gen.excluded_opcodes.contains(&opcode_raw)
+                || opcode.is_prefix(&config.test_gen.prefixes)
+            {
+                continue;
+            }
+            set_flow_control_halt(context, config, opcode)?;
+
+            let have_group_ext = config.test_gen.group_opcodes.contains(&opcode_raw);
+            let (op_ext_start, op_ext_end) = match have_group_ext {
+                true => get_group_extension_range(config, opcode),
+                false => (0, 0),
+            };
+
+            let mut size_prefixes: Vec<TestOpcodeSizePrefix> = TestOpcodeSizePrefix::iter(
+                config.test_gen.cpu_type,
+                opcode,
+                &config.test_gen.disable_operand_size_prefix,
+                &config.test_gen.disable_address_size_prefix,
+            )
+            .collect();
+            if size_prefixes.is_empty() {
+                size_prefixes.push(TestOpcodeSizePrefix::None);
+            }
+
+            for size_prefix in size_prefixes {
+                context.test_opcode_size_prefix = size_prefix;
+
+                for opcode_ext in op_ext_start..=op_ext_end {
+                    let op_ext = have_group_ext.then_some(opcode_ext);
+                    let batch = run_batch(context, config, opcode, op_ext, args.tests, args.runs)?;
+                    println!(
+                        "{}{}{}: {} tests, undetermined flags {}",
+                        size_prefix.to_filename_prefix(),
+                        opcode,
+                        entry_suffix(op_ext),
+                        batch.tests,
+                        flags_string(batch.undetermined)
+                    );
+
+                    let tally = tallies.entry((opcode_raw, op_ext)).or_default();
+                    tally.tests += batch.tests;
+                    tally.undetermined |= batch.undetermined;
+                }
+            }
+        }
+    }
+
+    for (&(opcode_raw, op_ext), tally) in &tallies {
+        if tally.tests == 0 {
+            println!(
+                "No test of {}{} completed two runs, so its mask is left as it was.",
+                Opcode::from(opcode_raw),
+                entry_suffix(op_ext)
+            );
+        }
+    }
+    let inferred: Vec<(Opcode, Option<u8>, u16)> = tallies
+        .iter()
+        .filter(|(_, tally)| tally.tests > 0)
+        .map(|(&(opcode_raw, op_ext), tally)| (Opcode::from(opcode_raw), op_ext, tally.undetermined))
+        .collect();
+
+    let path = args.metadata.as_deref().unwrap_or(&args.config_file);
+    let text = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let (text, changes) = match is_json(path) {
+        true => update_json(&text, &inferred, args.replace)?,
+        false => update_toml(&text, &inferred, args.replace)?,
+    };
+    for change in &changes {
+        println!(
+            "{}: {} -> {}",
+            change.entry,
+            flags_string(change.old),
+            flags_string(change.new)
+        );
+    }
+
+    if changes.is_empty() {
+        println!("No masks in {} need to change.", path.display());
+    }
+    else if args.dry_run {
+        println!(
+            "Dry run, so the {} changed masks were not written to {}.",
+            changes.len(),
+            path.display()
+        );
+    }
+    else {
+        fs::write(path, text).with_context(|| format!("Writing {}", path.display()))?;
+        println!("Wrote {} changed masks to {}", changes.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Run `tests` tests of `opcode` `runs` times each, and return the flags that differed between
+/// runs of the same test. Runs that fail are logged and left out.
+fn run_batch(
+    context: &mut TestContext,
+    config: &Config,
+    opcode: Opcode,
+    op_ext: Option<u8>,
+    tests: usize,
+    runs: usize,
+) -> anyhow::Result<FlagsTally> {
+    let opcode_ext = op_ext.unwrap_or(0);
+    context.file_seed = file_seed(config, opcode.into(), None, opcode_ext);
+
+    let mut results: Vec<Vec<u16>> = vec![Vec::new(); tests];
+    for run in 0..runs {
+        let order: Vec<usize> = match run % 2 {
+            0 => (0..tests).collect(),
+            _ => (0..tests).rev().collect(),
+        };
+        for test_num in order {
+            // The sequence comes from the test's seed, so each run regenerates the same test.
+            let (mut test_registers, test_instruction) =
+                generate_sequence(context, config, opcode, op_ext.is_some(), opcode_ext, test_num, 0)
+                    .with_context(|| format!("Generating test {} of {}{}", test_num, opcode, entry_suffix(op_ext)))?;
+            match generate_test(
+                context,
+                config,
+                test_num,
+                0,
+                opcode,
+                op_ext,
+                &test_instruction,
+                &mut test_registers,
+            ) {
+                Ok(test) => results[test_num].push(final_flags(&test)),
+                Err(e) => trace_error!(context, "Run {} of test {} failed: {}", run, test_num, e),
+            }
+        }
+    }
+
+    let mut tally = FlagsTally::default();
+    for flags in results.iter().filter(|flags| flags.len() > 1) {
+        tally.tests += 1;
+        tally.undetermined |= flags.iter().fold(0, |acc, &run_flags| acc | (run_flags ^ flags[0]));
+    }
+    trace_log!(
+        context,
+        "### {}{}: {} tests, undetermined flags {:04X} ###",
+        opcode,
+        entry_suffix(op_ext),
+        tally.tests,
+        tally.undetermined
+    );
+    Ok(tally)
+}
+
+/// The low 16 bits of a test's final flags, which are all a mask covers.
+fn final_flags(test: &MooTest) -> u16 {
+    match test.final_regs() {
+        MooRegisters::Sixteen(regs) => regs.flags,
+        MooRegisters::ThirtyTwo(regs) => regs.eflags as u16,
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// The group extension part of an entry's name, as in test file names.
+fn entry_suffix(op_ext: Option<u8>) -> String {
+    op_ext.map(|ext| format!(".{:1X}", ext)).unwrap_or_default()
+}
+
+/// Format undefined flags as a metadata `flags` string.
+fn flags_string(undefined: u16) -> String {
+    FLAG_LETTERS
+        .iter()
+        .map(|&(letter, bit)| match undefined & bit {
+            0 => '.',
+            _ => letter,
+        })
+        .collect()
+}
+
+/// The undefined flags an entry should have, given its current ones and those inferred.
+fn merged_flags(old: u16, undetermined: u16, replace: bool) -> u16 {
+    match replace {
+        true => undetermined,
+        false => old | undetermined,
+    }
+}
+
+/// Return the undefined flags of a `flags-mask` value.
+fn undefined_from_mask(mask: i64) -> u16 {
+    !(mask as u16)
+}
+
+/// Apply inferred flags to the `[metadata.opcodes]` tables of a generator config, returning the
+/// edited text and the entries that changed.
+fn update_toml(
+    text: &str,
+    inferred: &[(Opcode, Option<u8>, u16)],
+    replace: bool,
+) -> anyhow::Result<(String, Vec<MaskChange>)> {
+    let mut doc: DocumentMut = text.parse().context("Parsing TOML")?;
+    let Some(opcodes) = doc
+        .get_mut("metadata")
+        .and_then(|metadata| metadata.get_mut("opcodes"))
+        .and_then(Item::as_table_like_mut)
+    else {
+        bail!("The file has no [metadata.opcodes] table.");
+    };
+
+    let mut changes = Vec::new();
+    for &(opcode, op_ext, undetermined) in inferred {
+        let name = format!("{}{}", opcode, entry_suffix(op_ext));
+        let Some(entry) = opcodes.get_mut(&opcode.to_string()).and_then(Item::as_table_like_mut)
+        else {
+            println!("{} has no metadata entry, so its mask can't be written.", opcode);
+            continue;
+        };
+
+        let opcode_old = toml_undefined(entry);
+        let entry = match op_ext {
+            Some(ext) => toml_ext_entry(entry, ext)?,
+            None => entry,
+        };
+        let old = toml_undefined(entry).or(opcode_old).unwrap_or(0);
+        let new = merged_flags(old, undetermined, replace);
+        // An extension with no mask of its own uses its opcode's, so write it even if they match.
+        if new == old && (op_ext.is_none() || toml_undefined(entry).is_some()) {
+            continue;
+        }
+
+        let mask_key = match entry.contains_key("flags_mask") {
+            true => "flags_mask",
+            false => "flags-mask",
+        };
+        entry.insert("flags", toml_edit::value(flags_string(new)));
+        entry.insert(mask_key, toml_edit::value(!new as i64));
+        changes.push(MaskChange { entry: name, old, new });
+    }
+    Ok((doc.to_string(), changes))
+}
+
+fn toml_undefined(entry: &dyn TableLike) -> Option<u16> {
+    entry
+        .get("flags-mask")
+        .or_else(|| entry.get("flags_mask"))
+        .and_then(Item::as_integer)
+        .map(undefined_from_mask)
+}
+
+/// Return the `reg` entry for a group extension, creating it with its opcode's status and arch if
+/// it doesn't exist.
+fn toml_ext_entry(entry: &mut dyn TableLike, ext: u8) -> anyhow::Result<&mut dyn TableLike> {
+    let mut ext_table = Table::new();
+    for key in ["status", "arch"] {
+        if let Some(item) = entry.get(key) {
+            ext_table.insert(key, item.clone());
+        }
+    }
+    let reg = entry
+        .entry("reg")
+        .or_insert_with(|| {
+            let mut reg = Table::new();
+            reg.set_implicit(true);
+            Item::Table(reg)
+        })
+        .as_table_like_mut()
+        .context("reg is not a table")?;
+    reg.entry(&ext.to_string())
+        .or_insert(Item::Table(ext_table))
+        .as_table_like_mut()
+        .with_context(|| format!("reg.{} is not a table", ext))
+}
+
+/// Apply inferred flags to the `opcodes` object of a JSON metadata file, returning the edited text
+/// and the entries that changed.
+fn update_json(
+    text: &str,
+    inferred: &[(Opcode, Option<u8>, u16)],
+    replace: bool,
+) -> anyhow::Result<(String, Vec<MaskChange>)> {
+    let mut doc: Value = serde_json::from_str(text).context("Parsing JSON")?;
+    let Some(opcodes) = doc.get_mut("opcodes").and_then(Value::as_object_mut)
+    else {
+        bail!("The file has no opcodes object.");
+    };
+
+    let mut changes = Vec::new();
+    for &(opcode, op_ext, undetermined) in inferred {
+        let name = format!("{}{}", opcode, entry_suffix(op_ext));
+        let Some(entry) = opcodes.get_mut(&opcode.to_string()).and_then(Value::as_object_mut)
+        else {
+            println!("{} has no metadata entry, so its mask can't be written.", opcode);
+            continue;
+        };
+
+        let opcode_old = json_undefined(entry);
+        let entry = match op_ext {
+            Some(ext) => json_ext_entry(entry, ext)?,
+            None => entry,
+        };
+        let old = json_undefined(entry).or(opcode_old).unwrap_or(0);
+        let new = merged_flags(old, undetermined, replace);
+        if new == old && (op_ext.is_none() || json_undefined(entry).is_some()) {
+            continue;
+        }
+
+        entry.insert("flags".to_string(), Value::from(flags_string(new)));
+        entry.insert("flags-mask".to_string(), Value::from(!new));
+        changes.push(MaskChange { entry: name, old, new });
+    }
+
+    let mut text = serde_json::to_string_pretty(&doc).context("Serializing JSON")?;
+    text.push('\n');
+    Ok((text, changes))
+}
+
+fn json_undefined(entry: &Map<String, Value>) -> Option<u16> {
+    entry.get("flags-mask").and_then(Value::as_i64).map(undefined_from_mask)
+}
+
+/// Return the `reg` entry for a group extension, creating it with its opcode's status and arch if
+/// it doesn't exist.
+fn json_ext_entry(entry: &mut Map<String, Value>, ext: u8) -> anyhow::Result<&mut Map<String, Value>> {
+    let ext_entry: Map<String, Value> = ["status", "arch"]
+        .iter()
+        .filter_map(|&key| Some((key.to_string(), entry.get(key)?.clone())))
+        .collect();
+    entry
+        .entry("reg")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .context("reg is not an object")?
+        .entry(ext.to_string())
+        .or_insert(Value::Object(ext_entry))
+        .as_object_mut()
+        .with_context(|| format!("reg.{} is not an object", ext))
+}
@@ -21,7 +21,7 @@
     DEALINGS IN THE SOFTWARE.
 */
 
-use std::{collections::VecDeque, ops::Range};
+use std::{collections::VecDeque, io::Write, ops::Range};
 
 use crate::{
     cpu_common::{
@@ -39,9 +39,9 @@ use crate::{
     AddressSize,
     InstructionSize,
     Opcode,
-    TerminationCondition,
     TestContext,
     TestGen,
+    TestOpcodeSizePrefix,
 };
 
 use anyhow::bail;
@@ -55,6 +55,20 @@ use rand::{
 };
 use rand_distr::{Beta, Distribution};
 
+/// The state instruction synthesis depends on besides the generator config.
+pub(crate) struct SynthContext<'a> {
+    pub(crate) trace_log: &'a mut dyn Write,
+    pub(crate) test_opcode_size_prefix: TestOpcodeSizePrefix,
+    pub(crate) code_segment_size: SegmentSize,
+}
+
+/// Derive the seed for an instruction from the file seed, test number and generation attempt.
+pub(crate) fn instruction_seed(file_seed: u64, test_num: usize, gen_number: usize) -> u64 {
+    // Put the gen_number into the top 8 bits of the test seed.
+    // This allows us to generate tests based off the test number and gen count together.
+    file_seed ^ ((test_num as u64) | ((gen_number as u64) << 24))
+}
+
 pub struct TestInstruction {
     name: String,
     operand_size: InstructionSize,
@@ -164,10 +178,24 @@ impl TestInstruction {
         test_num: usize,
         gen_number: usize,
     ) -> anyhow::Result<Self> {
-        // Put the gen_number into the top 8 bits of the test seed.
-        // This allows us to generate tests based off the test number and gen count together.
-        let test_seed = context.file_seed ^ ((test_num as u64) | ((gen_number as u64) << 24));
+        let test_seed = instruction_seed(context.file_seed, test_num, gen_number);
+        let mut synth = SynthContext {
+            trace_log: &mut context.trace_log,
+            test_opcode_size_prefix: context.test_opcode_size_prefix,
+            code_segment_size: context.code_segment_size,
+        };
+        Self::synthesize(&mut synth, config, opcode, opcode_ext, test_seed)
+    }
 
+    /// Generate a new, random instruction from `test_seed`. The same seed, config and context
+    /// always produce the same instruction.
+    pub(crate) fn synthesize(
+        context: &mut SynthContext,
+        config: &TestGen,
+        opcode: Opcode,
+        opcode_ext: Option<u8>,
+        test_seed: u64,
+    ) -> anyhow::Result<Self> {
         // Create a new rng seeded by the base seed XOR test seed for repeatability.
         let mut rng = StdRng::seed_from_u64(test_seed);
 
@@ -178,7 +206,7 @@ impl TestInstruction {
             bail!("Opcode {} is excluded from generation", opcode);
         }
 
-        if !opcode.is_extended() && config.prefixes.contains(&opcode.into()) {
+        if opcode.is_prefix(&config.prefixes) {
             bail!("Opcode {} is a prefix and cannot be generated", opcode);
         }
 
@@ -216,8 +244,8 @@ impl TestInstruction {
 
         // We can do specific filters on modrm values here if needed.
         match config.cpu_type {
-            MooCpuType::Intel80286 | MooCpuType::Intel80386Ex => {
-                // Any modrm is fine for the 80286 as invalid forms will generate a UD exception
+            MooCpuType::Intel80186 | MooCpuType::Intel80188 | MooCpuType::Intel80286 | MooCpuType::Intel80386Ex => {
+                // Any modrm is fine from the 80186 on, as invalid forms will generate a UD exception
                 // instead of freaking out.
             }
             _ => {
@@ -494,7 +522,7 @@ impl TestInstruction {
             );
         }
 
-        if matches!(config.termination_condition, TerminationCondition::Halt) {
+        if config.termination_condition.appends_halt() {
             // Insert a HALT instruction at the end of the sequence.
             if instruction_byte_ct == instruction_bytes.len() {
                 log::trace!("Appending HALT instruction");
@@ -664,7 +692,7 @@ impl TestInstruction {
                 self.modrm_offset,
             );
 
-            if matches!(config.termination_condition, TerminationCondition::Halt) {
+            if config.termination_condition.appends_halt() {
                 // Insert a HALT instruction at the end of the sequence.
                 if instruction_byte_ct == instruction_bytes.len() {
                     log::trace!("Appending HALT instruction");
@@ -858,7 +886,7 @@ impl TestInstruction {
             let instruction_byte_ct = self.iced_i.len();
             let mut sequence_bytes = instruction_byte_ct;
 
-            if matches!(config.termination_condition, TerminationCondition::Halt) {
+            if config.termination_condition.appends_halt() {
                 // Insert a HALT instruction at the end of the sequence.
                 if instruction_byte_ct == instruction_bytes.len() {
                     log::trace!("Appending HALT instruction");
@@ -904,6 +932,20 @@ impl TestInstruction {
         Ok(())
     }
 
+    /// Replace the terminating HALT with a short jump over `filler` to a HALT, so that whatever
+    /// the CPU prefetched past the instruction is flushed when the jump is taken.
+    pub fn append_jump(&mut self, filler: &[u8]) {
+        self.bytes.truncate(self.instr_range.end);
+        self.bytes.push(0xEB); // JMP rel8
+        self.bytes.push(filler.len() as u8);
+        self.bytes.extend_from_slice(filler);
+        self.bytes.push(0xF4);
+        self.sequence_range = Range {
+            start: 0,
+            end:   self.bytes.len(),
+        };
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
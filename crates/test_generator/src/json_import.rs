@@ -0,0 +1,409 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Import legacy JSON test sets into MOO.
+//!
+//! The 8088, V20 and 80186 single-step test sets were published as JSON, one file per opcode, with
+//! each cycle as a tuple of bus status strings. This converts them to MOO files, so the same
+//! tooling can read old and new test sets. The tests themselves are not re-run.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use arduinox86_client::{BusState, QueueOp, Segment};
+use flate2::read::GzDecoder;
+use moo::{
+    prelude::*,
+    types::{MooCpuType, MooFileMetadata, MooRamEntry, MooRegisters16Init, MooRegistersInit, MooStateType},
+};
+use serde::Deserialize;
+
+use crate::{manifest::SetManifest, moo_io};
+
+/// CPUs that have legacy JSON test sets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LegacyCpu {
+    #[value(name = "8088")]
+    Intel8088,
+    #[value(name = "8086")]
+    Intel8086,
+    #[value(name = "v20")]
+    NecV20,
+    #[value(name = "v30")]
+    NecV30,
+    #[value(name = "80188")]
+    Intel80188,
+    #[value(name = "80186")]
+    Intel80186,
+}
+
+impl From<LegacyCpu> for MooCpuType {
+    fn from(cpu: LegacyCpu) -> Self {
+        match cpu {
+            LegacyCpu::Intel8088 => MooCpuType::Intel8088,
+            LegacyCpu::Intel8086 => MooCpuType::Intel8086,
+            LegacyCpu::NecV20 => MooCpuType::NecV20,
+            LegacyCpu::NecV30 => MooCpuType::NecV30,
+            LegacyCpu::Intel80188 => MooCpuType::Intel80188,
+            LegacyCpu::Intel80186 => MooCpuType::Intel80186,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonTest {
+    name:    String,
+    bytes:   Vec<u8>,
+    initial: JsonState,
+    #[serde(rename = "final")]
+    final_:  JsonState,
+    cycles:  Vec<JsonCycle>,
+}
+
+#[derive(Deserialize)]
+struct JsonState {
+    regs:  JsonRegisters,
+    #[serde(default)]
+    ram:   Vec<[u32; 2]>,
+    #[serde(default)]
+    queue: Vec<u8>,
+}
+
+/// Final states only list the registers that changed, so every register is optional.
+#[derive(Deserialize)]
+struct JsonRegisters {
+    ax:    Option<u16>,
+    bx:    Option<u16>,
+    cx:    Option<u16>,
+    dx:    Option<u16>,
+    cs:    Option<u16>,
+    ss:    Option<u16>,
+    ds:    Option<u16>,
+    es:    Option<u16>,
+    sp:    Option<u16>,
+    bp:    Option<u16>,
+    si:    Option<u16>,
+    di:    Option<u16>,
+    ip:    Option<u16>,
+    flags: Option<u16>,
+}
+
+impl JsonRegisters {
+    /// Resolve registers against `base`, which supplies any register not listed.
+    fn resolve(&self, base: &MooRegisters16Init) -> MooRegisters16Init {
+        MooRegisters16Init {
+            ax:    self.ax.unwrap_or(base.ax),
+            bx:    self.bx.unwrap_or(base.bx),
+            cx:    self.cx.unwrap_or(base.cx),
+            dx:    self.dx.unwrap_or(base.dx),
+            cs:    self.cs.unwrap_or(base.cs),
+            ss:    self.ss.unwrap_or(base.ss),
+            ds:    self.ds.unwrap_or(base.ds),
+            es:    self.es.unwrap_or(base.es),
+            sp:    self.sp.unwrap_or(base.sp),
+            bp:    self.bp.unwrap_or(base.bp),
+            si:    self.si.unwrap_or(base.si),
+            di:    self.di.unwrap_or(base.di),
+            ip:    self.ip.unwrap_or(base.ip),
+            flags: self.flags.unwrap_or(base.flags),
+        }
+    }
+
+    /// Return the registers if every one is listed, as in an initial state.
+    fn complete(&self) -> Option<MooRegisters16Init> {
+        Some(MooRegisters16Init {
+            ax:    self.ax?,
+            bx:    self.bx?,
+            cx:    self.cx?,
+            dx:    self.dx?,
+            cs:    self.cs?,
+            ss:    self.ss?,
+            ds:    self.ds?,
+            es:    self.es?,
+            sp:    self.sp?,
+            bp:    self.bp?,
+            si:    self.si?,
+            di:    self.di?,
+            ip:    self.ip?,
+            flags: self.flags?,
+        })
+    }
+}
+
+/// A cycle tuple: pins (ALE in bit 0), address, segment, memory status, io status, data bus,
+/// bus status, T-state, queue operation and queue byte.
+#[derive(Deserialize)]
+struct JsonCycle(u8, u32, String, String, String, u16, String, String, String, u8);
+
+impl JsonCycle {
+    fn to_moo(&self) -> anyhow::Result<MooCycleState> {
+        let mut pins0 = 0;
+        if self.0 & 1 != 0 {
+            pins0 |= MooCycleState::PIN_ALE;
+        }
+
+        Ok(MooCycleState {
+            pins0,
+            address_bus: self.1,
+            segment: parse_segment(&self.2)? as u8,
+            memory_status: parse_status(&self.3)?,
+            io_status: parse_status(&self.4)?,
+            pins1: 0,
+            data_bus: self.5,
+            bus_state: parse_bus_state(&self.6)? as u8,
+            t_state: parse_t_state(&self.7)?,
+            queue_op: parse_queue_op(&self.8)? as u8,
+            queue_byte: self.9,
+        })
+    }
+}
+
+/// The segment status lines are only valid after T1. "--" is reported as CS, which the 8088
+/// encodes the same way as no segment.
+fn parse_segment(s: &str) -> anyhow::Result<Segment> {
+    Ok(match s {
+        "ES" => Segment::ES,
+        "SS" => Segment::SS,
+        "CS" | "--" => Segment::CS,
+        "DS" => Segment::DS,
+        _ => bail!("Bad segment status: {:?}", s),
+    })
+}
+
+/// Convert a three character "RAW" status (read, advanced write, write) to MOO status bits.
+fn parse_status(s: &str) -> anyhow::Result<u8> {
+    if s.len() != 3 {
+        bail!("Bad bus status: {:?}", s);
+    }
+    let mut status = 0;
+    for (bit, (c, active)) in s.chars().zip(['R', 'A', 'W']).enumerate() {
+        if c == active {
+            status |= 0b100 >> bit;
+        }
+        else if c != '-' {
+            bail!("Bad bus status: {:?}", s);
+        }
+    }
+    Ok(status)
+}
+
+fn parse_bus_state(s: &str) -> anyhow::Result<BusState> {
+    Ok(match s {
+        "INTA" => BusState::INTA,
+        "IOR" => BusState::IOR,
+        "IOW" => BusState::IOW,
+        "HALT" => BusState::HALT,
+        "CODE" => BusState::CODE,
+        "MEMR" => BusState::MEMR,
+        "MEMW" => BusState::MEMW,
+        "PASV" => BusState::PASV,
+        _ => bail!("Bad bus state: {:?}", s),
+    })
+}
+
+fn parse_t_state(s: &str) -> anyhow::Result<u8> {
+    Ok(match s.to_ascii_uppercase().as_str() {
+        "TI" => 0,
+        "T1" => 1,
+        "T2" => 2,
+        "T3" => 3,
+        "T4" => 4,
+        "TW" => 5,
+        _ => bail!("Bad T-state: {:?}", s),
+    })
+}
+
+fn parse_queue_op(s: &str) -> anyhow::Result<QueueOp> {
+    Ok(match s {
+        "-" => QueueOp::Idle,
+        "F" => QueueOp::First,
+        "E" => QueueOp::Flush,
+        "S" => QueueOp::Subsequent,
+        _ => bail!("Bad queue operation: {:?}", s),
+    })
+}
+
+fn ram_entries(ram: &[[u32; 2]]) -> Vec<MooRamEntry> {
+    ram.iter()
+        .map(|entry| MooRamEntry {
+            address: entry[0],
+            value:   entry[1] as u8,
+        })
+        .collect()
+}
+
+fn convert_test(test: &JsonTest) -> anyhow::Result<MooTest> {
+    let Some(initial_regs) = test.initial.regs.complete()
+    else {
+        bail!("Initial state is missing registers");
+    };
+    let final_regs = test.final_.regs.resolve(&initial_regs);
+    let initial_init = MooRegistersInit::Sixteen(initial_regs);
+    let final_init = MooRegistersInit::Sixteen(final_regs);
+
+    let initial_state = MooTestState::new(
+        MooStateType::Initial,
+        &initial_init,
+        None,
+        test.initial.queue.clone(),
+        ram_entries(&test.initial.ram),
+    );
+    let final_state = MooTestState::new(
+        MooStateType::Final,
+        &initial_init,
+        Some(&final_init),
+        test.final_.queue.clone(),
+        ram_entries(&test.final_.ram),
+    );
+
+    let cycles = test
+        .cycles
+        .iter()
+        .enumerate()
+        .map(|(i, cycle)| cycle.to_moo().with_context(|| format!("Cycle {}", i)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(MooTest::new(
+        test.name.clone(),
+        None,
+        &test.bytes,
+        initial_state,
+        final_state,
+        &cycles,
+        None,
+        None,
+    ))
+}
+
+/// Return the MOO file name and opcode for a JSON test file, e.g. `F6.4.json.gz` -> `F6.4.MOO`.
+fn moo_name(path: &Path) -> anyhow::Result<(String, u16)> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let lower = file_name.to_ascii_lowercase();
+    let stem_len = lower
+        .strip_suffix(".json.gz")
+        .or_else(|| lower.strip_suffix(".json"))
+        .map(str::len)
+        .with_context(|| format!("{} is not a JSON test file", path.display()))?;
+    let stem = &file_name[..stem_len];
+
+    let opcode_str = stem.split('.').next().unwrap_or_default();
+    let opcode = u16::from_str_radix(opcode_str, 16)
+        .with_context(|| format!("Can't get an opcode from file name {}", file_name))?;
+    Ok((format!("{}.MOO", stem.to_ascii_uppercase()), opcode))
+}
+
+fn read_json_tests(path: &Path) -> anyhow::Result<Vec<JsonTest>> {
+    let file = File::open(path).with_context(|| format!("Opening {}", path.display()))?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz")) {
+        Box::new(GzDecoder::new(file))
+    }
+    else {
+        Box::new(file)
+    };
+    serde_json::from_reader(BufReader::new(reader)).with_context(|| format!("Parsing {}", path.display()))
+}
+
+/// Convert one JSON test file to a MOO file in `output_dir`. Returns the number of tests.
+fn import_file(path: &Path, cpu: MooCpuType, moo_version: u8, output_dir: &Path) -> anyhow::Result<usize> {
+    let (file_name, opcode) = moo_name(path)?;
+    let json_tests = read_json_tests(path)?;
+
+    let mut test_file = MooTestFile::new(moo_version, cpu, json_tests.len());
+    let mut mnemonics: HashMap<&str, usize> = HashMap::new();
+    for (i, json_test) in json_tests.iter().enumerate() {
+        let test = convert_test(json_test).with_context(|| format!("Test {} of {}", i, path.display()))?;
+        test_file.add_test(test);
+
+        if let Some(mnemonic) = json_test.name.split_whitespace().next() {
+            *mnemonics.entry(mnemonic).or_default() += 1;
+        }
+    }
+
+    let mut metadata = MooFileMetadata::new(1, 0, cpu.into(), opcode as u32).with_test_count(json_tests.len() as u32);
+    if let Some((mnemonic, _)) = mnemonics.iter().max_by_key(|entry| entry.1) {
+        metadata = metadata.with_mnemonic(mnemonic.to_string());
+    }
+    test_file.set_metadata(metadata);
+
+    moo_io::write_test_file(&test_file, &output_dir.join(&file_name))?;
+    Ok(json_tests.len())
+}
+
+/// Collect the JSON test files named by `inputs`, expanding directories.
+fn json_files(inputs: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut dir_files = Vec::new();
+            for entry in std::fs::read_dir(input).with_context(|| format!("Reading {}", input.display()))? {
+                let path = entry?.path();
+                if moo_name(&path).is_ok() {
+                    dir_files.push(path);
+                }
+            }
+            dir_files.sort();
+            files.extend(dir_files);
+        }
+        else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
+
+pub fn import_json(inputs: &[PathBuf], cpu: LegacyCpu, moo_version: u8, output_dir: &Path) -> anyhow::Result<()> {
+    let files = json_files(inputs)?;
+    if files.is_empty() {
+        bail!("No JSON test files found.");
+    }
+    std::fs::create_dir_all(output_dir).with_context(|| format!("Creating {}", output_dir.display()))?;
+
+    let mut total = 0;
+    for path in &files {
+        let count = import_file(path, cpu.into(), moo_version, output_dir)?;
+        println!("Imported {} tests from {}", count, path.display());
+        total += count;
+    }
+
+    let manifest = SetManifest::build(
+        output_dir,
+        "1.0".to_string(),
+        format!("{:?}", MooCpuType::from(cpu)),
+        "imported".to_string(),
+        false,
+    )?;
+    manifest.write(output_dir)?;
+    println!(
+        "Imported {} tests from {} file(s) into {}",
+        total,
+        files.len(),
+        output_dir.display()
+    );
+
+    Ok(())
+}
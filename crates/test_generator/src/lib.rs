@@ -0,0 +1,1200 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Test set generation and validation for the 80286 and 386EX. The `test_generator` binary and
+//! the `gen`, `validate` and `moo` subcommands of `ardx86` are thin wrappers around this crate.
+
+mod bus_ops;
+mod coverage;
+mod cpu_common;
+mod cycle_budget;
+mod cycle_check;
+mod cycles;
+mod diff;
+mod display;
+mod dump_programs;
+mod flags;
+mod gen_regs;
+mod gen_tests;
+mod golden;
+mod infer_flags;
+mod instruction;
+mod json_import;
+mod manifest;
+mod mini_trace;
+mod modrm;
+mod moo_io;
+mod parallel;
+mod progress;
+mod rebuild_tests;
+mod registers;
+mod sample;
+mod session;
+mod shutdown;
+mod state;
+mod stats;
+mod synthesizer;
+mod timings;
+mod validate_tests;
+
+use arduinox86_client::{
+    registers_common::SegmentSize,
+    Capabilities,
+    ComparisonPreset,
+    CpuClient,
+    EndCondition,
+    MemoryStrategy,
+    PhaseBudget,
+    ProgramState,
+    RegisterSetType,
+    ServerCpuType,
+};
+use arduinox86_config::{AddressMap, ConnectionArgs, RegionKind};
+use moo::types::MooCpuType;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs,
+    fs::File,
+    io::{BufWriter, Cursor},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::Context;
+pub use cpu_common::{BusOp, BusOpType, BusStatusByte};
+pub use cycle_check::{check_bus_ops, CycleStreamError};
+use cycle_budget::{CycleBudget, SlowTest};
+use gen_tests::SetupStats;
+pub use instruction::TestInstruction;
+pub use json_import::LegacyCpu;
+use manifest::{InterruptRecord, QueueStressRecord, SetAnnotations, VectorRecord};
+pub use mini_trace::{MiniEvent, MiniEventKind, MiniTrace};
+pub use moo_io::{
+    compress_cycle_chunks,
+    expand_cycle_chunks,
+    read_mini_traces,
+    read_test_file,
+    read_test_tags,
+    split_store_dumps,
+    StoreDump,
+    TestTags,
+};
+use parallel::{Shard, WidthShard};
+use progress::RunProgress;
+use serde::{Deserialize, Serialize};
+use session::{Capture, SessionWriter};
+use shutdown::BlockedOpcode;
+use stats::GenStats;
+pub use sample::Stratum;
+pub use synthesizer::InstructionSynthesizer;
+pub use timings::TimingFormat;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum InstructionSize {
+    Sixteen,
+    ThirtyTwo,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum AddressSize {
+    Sixteen,
+    ThirtyTwo,
+}
+
+impl From<InstructionSize> for u32 {
+    fn from(size: InstructionSize) -> Self {
+        match size {
+            InstructionSize::Sixteen => 16,
+            InstructionSize::ThirtyTwo => 32,
+        }
+    }
+}
+
+impl From<InstructionSize> for SegmentSize {
+    fn from(size: InstructionSize) -> Self {
+        match size {
+            InstructionSize::Sixteen => SegmentSize::Sixteen,
+            InstructionSize::ThirtyTwo => SegmentSize::ThirtyTwo,
+        }
+    }
+}
+
+impl InstructionSize {
+    /// The name of the output subdirectory for tests generated with a code segment of this size.
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            InstructionSize::Sixteen => "16",
+            InstructionSize::ThirtyTwo => "32",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum CpuMode {
+    Real,
+    Unreal,
+    Protected,
+}
+
+/// How a test program ends. Every condition but `Queue` also ends on the HALT placed after the
+/// instruction; the others end earlier, at the next code fetch once they are met.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum TerminationCondition {
+    Queue,
+    Halt,
+    /// The IRET of the handler for this vector.
+    Iret(u8),
+    /// A write to this I/O port.
+    PortWrite(u16),
+    /// A code fetch from this address.
+    Fetch(u32),
+    /// This many cycles after execution starts.
+    Cycles(u32),
+}
+
+impl TerminationCondition {
+    /// Whether a HALT is placed after the instruction under test.
+    pub fn appends_halt(&self) -> bool {
+        !matches!(self, TerminationCondition::Queue)
+    }
+
+    /// The server end condition to arm for this termination condition, if any.
+    pub fn end_condition(&self) -> Option<EndCondition> {
+        match *self {
+            TerminationCondition::Queue | TerminationCondition::Halt => None,
+            TerminationCondition::Iret(vector) => Some(EndCondition::Iret(vector)),
+            TerminationCondition::PortWrite(port) => Some(EndCondition::PortWrite(port)),
+            TerminationCondition::Fetch(address) => Some(EndCondition::Fetch(address)),
+            TerminationCondition::Cycles(cycles) => Some(EndCondition::Cycles(cycles)),
+        }
+    }
+}
+
+/// What to do with a test whose instruction writes to bytes already fetched as code.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum SmcPolicy {
+    /// Keep the test and tag it as self-modifying in the set manifest.
+    #[default]
+    Tag,
+    /// Reject the test and generate a new instruction.
+    Reject,
+}
+
+/// Interrupt line raised by interrupt injection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InjectLine {
+    Intr,
+    Nmi,
+}
+
+/// Whether an interrupt injection offset counts instructions or cycles from the start of execution.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InjectMode {
+    /// Requires a CPU with queue status lines.
+    Instruction,
+    Cycle,
+}
+
+/// Raise INTR or NMI partway through a test to capture the interrupt entry sequence.
+#[derive(Clone, Debug, Deserialize)]
+pub struct InterruptInjection {
+    /// Chance that a test has an interrupt injected.
+    chance: f32,
+    /// Chance that an injected interrupt is NMI rather than INTR.
+    nmi_chance: f32,
+    mode: InjectMode,
+    /// Inclusive range of offsets to raise the line at.
+    offset_range: [u32; 2],
+    /// Vector supplied for INTR during the INTA cycle.
+    vector: u8,
+}
+
+/// Where a redirected IVT entry points.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IvtTarget {
+    /// An odd offset, so the first fetch of the handler is unaligned.
+    OddAddress,
+    /// One of the last four bytes of a segment, so fetching the handler wraps the offset.
+    SegmentTop,
+    /// A byte of the instruction under test.
+    InsideInstruction,
+}
+
+/// Point the IVT entry of the vector an INT, INTO or divide test raises at an unusual handler
+/// address before running the test.
+#[derive(Clone, Debug, Deserialize)]
+pub struct IvtStress {
+    /// Chance that a test that can raise a vector has its IVT entry redirected.
+    chance:  f32,
+    /// Targets to choose from, with equal weight.
+    targets: Vec<IvtTarget>,
+}
+
+/// How a test is laid out to exercise the prefetch queue.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueueStressTarget {
+    /// Follow the instruction with a short jump over a few filler bytes to the terminating HALT, so
+    /// the bytes prefetched past the instruction are flushed. Requires the `Halt` termination
+    /// condition.
+    JumpAfter,
+    /// Place the instruction so that it runs past the code segment limit, so the prefetcher reaches
+    /// the limit partway through the instruction. Only applies to 16-bit code segments.
+    SegmentLimit,
+}
+
+/// Lay tests out so that their cycles capture queue flushes and prefetch boundaries, not just a
+/// straight run of fetches. Flow control opcodes are never stressed, as they flush the queue
+/// themselves.
+#[derive(Clone, Debug, Deserialize)]
+pub struct QueueStress {
+    /// Chance that a test is laid out for queue stress.
+    chance:  f32,
+    /// Targets to choose from, with equal weight.
+    targets: Vec<QueueStressTarget>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TestOpcodeSizePrefix {
+    None,
+    OperandSize,
+    AddressSize,
+    OperandAndAddressSize,
+}
+
+impl TestOpcodeSizePrefix {
+    pub fn to_filename_prefix(&self) -> &'static str {
+        match self {
+            TestOpcodeSizePrefix::None => "",
+            TestOpcodeSizePrefix::OperandSize => "66",
+            TestOpcodeSizePrefix::AddressSize => "67",
+            TestOpcodeSizePrefix::OperandAndAddressSize => "6766",
+        }
+    }
+
+    /// Returns an iterator over all valid prefixes for the given CPU.
+    pub fn iter(
+        cpu_type: MooCpuType,
+        opcode: Opcode,
+        disable_operand_size_opcodes: &[u16],
+        disable_address_size_opcodes: &[u16],
+    ) -> Box<dyn Iterator<Item = TestOpcodeSizePrefix>> {
+        match cpu_type {
+            MooCpuType::Intel80386Ex => {
+                let mut iter_vec = vec![TestOpcodeSizePrefix::None];
+
+                let opcode_u16: u16 = opcode.into();
+                let use_operand_size = !disable_operand_size_opcodes.contains(&opcode_u16);
+                let use_address_size = !disable_address_size_opcodes.contains(&opcode_u16);
+
+                if use_operand_size {
+                    iter_vec.push(TestOpcodeSizePrefix::OperandSize);
+                }
+                if use_address_size {
+                    iter_vec.push(TestOpcodeSizePrefix::AddressSize);
+                }
+                if use_operand_size && use_address_size {
+                    iter_vec.push(TestOpcodeSizePrefix::OperandAndAddressSize);
+                }
+                Box::new(iter_vec.into_iter())
+            }
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+
+    pub fn relative_opcode_size(&self, size: SegmentSize) -> InstructionSize {
+        match size {
+            SegmentSize::Sixteen => match self {
+                TestOpcodeSizePrefix::None => InstructionSize::Sixteen,
+                TestOpcodeSizePrefix::OperandSize => InstructionSize::ThirtyTwo,
+                TestOpcodeSizePrefix::AddressSize => InstructionSize::Sixteen,
+                TestOpcodeSizePrefix::OperandAndAddressSize => InstructionSize::ThirtyTwo,
+            },
+            SegmentSize::ThirtyTwo => match self {
+                TestOpcodeSizePrefix::None => InstructionSize::ThirtyTwo,
+                TestOpcodeSizePrefix::OperandSize => InstructionSize::Sixteen,
+                TestOpcodeSizePrefix::AddressSize => InstructionSize::ThirtyTwo,
+                TestOpcodeSizePrefix::OperandAndAddressSize => InstructionSize::Sixteen,
+            },
+        }
+    }
+
+    pub fn relative_address_size(&self, size: SegmentSize) -> AddressSize {
+        match size {
+            SegmentSize::Sixteen => match self {
+                TestOpcodeSizePrefix::None => AddressSize::Sixteen,
+                TestOpcodeSizePrefix::OperandSize => AddressSize::Sixteen,
+                TestOpcodeSizePrefix::AddressSize => AddressSize::ThirtyTwo,
+                TestOpcodeSizePrefix::OperandAndAddressSize => AddressSize::ThirtyTwo,
+            },
+            SegmentSize::ThirtyTwo => match self {
+                TestOpcodeSizePrefix::None => AddressSize::ThirtyTwo,
+                TestOpcodeSizePrefix::OperandSize => AddressSize::ThirtyTwo,
+                TestOpcodeSizePrefix::AddressSize => AddressSize::Sixteen,
+                TestOpcodeSizePrefix::OperandAndAddressSize => AddressSize::Sixteen,
+            },
+        }
+    }
+}
+
+impl From<TestOpcodeSizePrefix> for Vec<u8> {
+    fn from(prefix: TestOpcodeSizePrefix) -> Self {
+        match prefix {
+            TestOpcodeSizePrefix::None => vec![],
+            TestOpcodeSizePrefix::OperandSize => vec![0x66],
+            TestOpcodeSizePrefix::AddressSize => vec![0x67],
+            TestOpcodeSizePrefix::OperandAndAddressSize => vec![0x66, 0x67],
+        }
+    }
+}
+
+/// An opcode, identified by a u16 throughout the generator. One-byte opcodes are `0x00xx` and
+/// opcodes in the extended space are `0x0Fxx`, so config lists, overrides, file names (`0FA4.MOO`)
+/// and file metadata treat both alike.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Opcode {
+    extended: u16,
+}
+
+/// The byte that introduces the extended opcode space on the 286 and later.
+pub const EXTENDED_OPCODE_PREFIX: u8 = 0x0F;
+
+impl From<u8> for Opcode {
+    fn from(value: u8) -> Self {
+        Opcode { extended: value as u16 }
+    }
+}
+
+impl From<u16> for Opcode {
+    fn from(value: u16) -> Self {
+        Opcode { extended: value }
+    }
+}
+
+impl From<Opcode> for u16 {
+    fn from(opcode: Opcode) -> Self {
+        opcode.extended
+    }
+}
+
+impl From<Opcode> for u32 {
+    fn from(opcode: Opcode) -> Self {
+        opcode.extended as u32
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.extended <= 0xFF {
+            write!(f, "{:02X}", self.extended)
+        }
+        else {
+            write!(f, "{:04X}", self.extended)
+        }
+    }
+}
+
+impl Opcode {
+    /// The opcode `0F byte`.
+    pub fn new_extended(byte: u8) -> Self {
+        Opcode {
+            extended: ((EXTENDED_OPCODE_PREFIX as u16) << 8) | byte as u16,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.extended <= 0xFF {
+            vec![self.extended as u8]
+        }
+        else {
+            vec![(self.extended >> 8) as u8, (self.extended & 0xFF) as u8]
+        }
+    }
+
+    pub fn is_extended(&self) -> bool {
+        self.extended > 0xFF
+    }
+
+    /// Whether this is a one-byte opcode or an opcode in the extended space. Anything else can't
+    /// be encoded.
+    pub fn is_valid(&self) -> bool {
+        !self.is_extended() || (self.extended >> 8) as u8 == EXTENDED_OPCODE_PREFIX
+    }
+
+    /// Whether this is one of the prefix bytes in `prefixes`. Extended opcodes never are.
+    pub fn is_prefix(&self, prefixes: &[u8]) -> bool {
+        !self.is_extended() && prefixes.contains(&(self.extended as u8))
+    }
+
+    pub fn base_opcode(&self) -> u8 {
+        (self.extended & 0xFF) as u8
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OpcodeMetadata {
+    status: String,
+    arch: String,
+    flags: Option<String>,
+    #[serde(alias = "flags-mask")]
+    flags_mask: Option<u32>,
+    reg: Option<HashMap<String, OpcodeMetadata>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TestMetadata {
+    repo: String,
+    version: String,
+    syntax_version: u32,
+    cpu: String,
+    cpu_detail: String,
+    generator: String,
+    author: String,
+    date: String,
+    opcodes: HashMap<String, OpcodeMetadata>,
+}
+
+impl TestMetadata {
+    /// Return the flags bits `opcode` leaves undefined, from the `flags-mask` of its metadata
+    /// entry, or of its group extension's entry if it has one. Zero if neither gives a mask.
+    pub fn undefined_flags(&self, opcode: Opcode, opcode_ext: Option<u8>) -> u32 {
+        let Some(entry) = self.opcodes.get(&opcode.to_string())
+        else {
+            return 0;
+        };
+        let ext_mask = opcode_ext
+            .and_then(|ext| entry.reg.as_ref()?.get(&ext.to_string()))
+            .and_then(|ext_entry| ext_entry.flags_mask);
+        ext_mask
+            .or(entry.flags_mask)
+            .map(|mask| !mask & 0xFFFF)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CountOverride {
+    count: usize,
+    opcode_range: [u16; 2],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GroupExtensionOverride {
+    opcode: u16,
+    group_extension_range: [u8; 2],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StackPointerOverride {
+    opcode: u16,
+    min:    u32,
+    max:    u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExceptionSieveEntry {
+    opcode: u16,
+    exception: u8,
+    exception_rate: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModRmOverride {
+    opcode: u16,
+    allow_reg_form: bool,
+    mask: u8,
+    invalid_chance: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Config {
+    test_gen:  TestGen,
+    test_exec: TestExec,
+    metadata:  TestMetadata,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TestExec {
+    polling_sleep: u32,
+    validate_count: u32,
+    max_sieve: u32,
+    max_gen: u32,
+    test_retry: u32,
+    load_retry: u32,
+    test_timeout: u32,
+    print_instruction: bool,
+    print_initial_regs: bool,
+    print_final_regs: bool,
+    show_gen_time: bool,
+    serial_timeout: u32,
+    serial_debug_default: bool,
+    serial_debug_test: Option<usize>,
+    /// Download cycle logs in the encoded format, if the server supports it.
+    #[serde(default)]
+    encode_cycle_log: bool,
+    #[serde(default)]
+    cycle_budget: Option<CycleBudget>,
+    /// Times a test is retried after shutting the CPU down before its opcode is blocked.
+    #[serde(default)]
+    shutdown_retry: u32,
+    /// Comparison policy for validation. Defaults to registers_only.
+    #[serde(default, deserialize_with = "deserialize_comparison_preset")]
+    validate_policy: Option<ComparisonPreset>,
+}
+
+fn deserialize_comparison_preset<'de, D>(deserializer: D) -> Result<Option<ComparisonPreset>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|preset| preset.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TestGen {
+    set_version_major: u8,
+    set_version_minor: u8,
+    cpu_type: MooCpuType,
+    cpu_mode: CpuMode,
+    base_seed: u64,
+    termination_condition: TerminationCondition,
+    test_output_dir: PathBuf,
+    trace_output_dir: PathBuf,
+    verify_trace_output_dir: PathBuf,
+    trace_file_suffix: PathBuf,
+    moo_version: u8,
+    moo_arch: String,
+    /// Code segment sizes to generate tests for. With more than one width, each width is written
+    /// to its own `16` or `32` subdirectory of the test, trace and session log directories.
+    gen_widths: Vec<InstructionSize>,
+
+    address_mask: u32,
+    ip_mask: u16,
+    instruction_address_range: [u32; 2],
+
+    /// Opcodes are u16s, with the extended space at `0x0F00-0x0FFF`.
+    opcode_range: [u16; 2],
+    opcode_override: Option<u16>,
+    group_extension_range: [u8; 2],
+    group_extension_overrides: Vec<GroupExtensionOverride>,
+
+    valid_opcodes: Vec<u16>,
+    excluded_opcodes: Vec<u16>,
+    exclude_esc_opcodes: bool,
+
+    test_count: usize,
+    append_file: bool,
+    /// Write the test file and its progress entry after every this many tests, so an interrupted
+    /// run loses at most this many tests. 0 writes them only when the file is finished.
+    #[serde(default)]
+    checkpoint_interval: usize,
+
+    writeless_null_shifts: bool,
+    shift_mask: u16,
+
+    register_beta: [f64; 2],
+    max_prefixes:  usize,
+    prefix_beta:   [f64; 2],
+
+    lock_prefix_chance: f32,
+    lock_prefix_opcode: u8,
+    rep_prefix_chance:  f32,
+
+    reg_zero_chance: f32,
+    reg_ones_chance: f32,
+    reg_inject_chance: f32,
+    imm_zero_chance: f32,
+    imm_ones_chance: f32,
+    imm_inject_chance: f32,
+    imm8s_min_chance: f32,
+    imm8s_max_chance: f32,
+    imm8s_inject_chance: f32,
+
+    inject_values: Vec<u32>,
+
+    near_branch_ban: u16,
+
+    sp_odd_chance: f32,
+    sp_min_value: u32,
+    sp_max_value: u32,
+    mem_zero_chance: f32,
+    mem_ones_chance: f32,
+    mem_strategy_start: u32,
+    mem_strategy_end: u32,
+
+    group_opcodes: Vec<u16>,
+    esc_opcodes: Vec<u16>,
+    flow_control_opcodes: Vec<u16>,
+    /// Termination condition for flow control opcodes, which may never reach the HALT after the
+    /// instruction. Defaults to `termination_condition`.
+    #[serde(default)]
+    flow_control_termination: Option<TerminationCondition>,
+    prefixes: Vec<u8>,
+    segment_prefixes: Vec<u8>,
+    disable_operand_size_prefix: Vec<u16>,
+    disable_address_size_prefix: Vec<u16>,
+    rep_prefixes: Vec<u8>,
+    rep_opcodes: Vec<u16>,
+    rep_cx_mask: u16,
+
+    disable_seg_overrides: Vec<u16>,
+    disable_lock_prefix:   Vec<u16>,
+
+    sp_overrides:    Vec<StackPointerOverride>,
+    modrm_overrides: Vec<ModRmOverride>,
+    count_overrides: Vec<CountOverride>,
+    exception_sieve: Vec<ExceptionSieveEntry>,
+
+    randomize_mem_interval: usize,
+
+    #[serde(default)]
+    smc_policy: SmcPolicy,
+    #[serde(default)]
+    interrupt_injection: Option<InterruptInjection>,
+    #[serde(default)]
+    ivt_stress: Option<IvtStress>,
+    #[serde(default)]
+    queue_stress: Option<QueueStress>,
+    /// Write the raw register dump of each test into an SDMP chunk in the test file.
+    #[serde(default)]
+    store_dump_chunks: bool,
+    /// Write the cycles of each test run-length encoded, in a CYCR chunk. Files written this way
+    /// can only be read by tools that support CYCR chunks.
+    #[serde(default)]
+    compress_cycles: bool,
+    /// Write an MTRC chunk per test, listing its bus operations and inferred queue flushes, so
+    /// validators can check them without parsing cycles.
+    #[serde(default)]
+    mini_trace_chunks: bool,
+    /// Record the hardware I/O of each accepted test to a session log in this directory, so the
+    /// test set can be rebuilt offline with `--rebuild`.
+    #[serde(default)]
+    session_log_dir: Option<PathBuf>,
+    /// Directory of hand-written golden tests, with a subdirectory per test file. Golden tests are
+    /// run first and count towards the file's test count.
+    #[serde(default)]
+    golden_dir: Option<PathBuf>,
+}
+
+/// Options shared by test generation and validation.
+#[derive(clap::Args, Debug)]
+pub struct GenArgs {
+    /// Path to the TOML config file
+    #[arg(long, value_name = "FILE")]
+    pub config_file: PathBuf,
+
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Write every generated instruction sequence and its initial registers to DIR instead of
+    /// running them, without connecting to the hardware
+    #[arg(long, value_name = "DIR")]
+    pub dump_programs: Option<PathBuf>,
+
+    /// Generate on every board found, sharing the test files out between them. The com port may
+    /// list the boards to use, separated by commas
+    #[arg(long)]
+    pub parallel: bool,
+}
+
+/// Options for inferring undefined flags masks on the CPU.
+#[derive(clap::Args, Debug)]
+pub struct InferFlagsArgs {
+    /// Path to the TOML config file
+    #[arg(long, value_name = "FILE")]
+    pub config_file: PathBuf,
+
+    /// Number of tests to run for each opcode and group extension
+    #[arg(long, default_value_t = 20)]
+    pub tests: usize,
+
+    /// Number of times to run each test. Flags that differ between runs are undefined
+    #[arg(long, default_value_t = 3)]
+    pub runs: usize,
+
+    /// Metadata file to write the masks to: a config with [metadata.opcodes] tables, or a JSON
+    /// metadata file with an opcodes object. Defaults to the config file
+    #[arg(long, value_name = "FILE")]
+    pub metadata: Option<PathBuf>,
+
+    /// Replace the existing masks with the inferred ones, instead of adding the inferred flags
+    #[arg(long)]
+    pub replace: bool,
+
+    /// Print the changed masks without writing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Options for importing legacy JSON test sets.
+#[derive(clap::Args, Debug)]
+pub struct ImportJsonArgs {
+    /// JSON test files (.json or .json.gz), or directories of them
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// The CPU the tests were captured from
+    #[arg(long, value_enum)]
+    pub cpu: LegacyCpu,
+
+    /// Directory to write the MOO files and manifest to
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: PathBuf,
+
+    /// MOO format version to write
+    #[arg(long, default_value_t = 1)]
+    pub moo_version: u8,
+}
+
+/// Options for building a timing table from a test set.
+#[derive(clap::Args, Debug)]
+pub struct TimingsArgs {
+    /// MOO files, or directories of them
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// File to write the timing table to. Only the summary is printed if not given
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Format of the written timing table
+    #[arg(long, value_enum, default_value = "toml")]
+    pub format: TimingFormat,
+}
+
+/// Options for auditing the coverage of a test set.
+#[derive(clap::Args, Debug)]
+pub struct CoverageArgs {
+    /// Directory of MOO files
+    pub dir: PathBuf,
+
+    /// Generator config the set was made from, e.g. cfg/gen_286.toml. Cells it expects that have
+    /// no tests are marked MISS
+    #[arg(long, value_name = "FILE")]
+    pub template: Option<PathBuf>,
+
+    /// Only print rows with missing cells
+    #[arg(long)]
+    pub missing_only: bool,
+}
+
+/// Options for taking a reduced sample of a test set.
+#[derive(clap::Args, Debug)]
+pub struct SampleArgs {
+    /// Directory of MOO files
+    pub dir: PathBuf,
+
+    /// Directory to write the sampled MOO files and manifest to
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: PathBuf,
+
+    /// Number of tests to keep from each test file
+    #[arg(long, default_value_t = 5)]
+    pub per_opcode: usize,
+
+    /// Group tests by these properties and keep one test from each group before keeping a second
+    /// from any: exception, prefix or width. Comma-separated
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub stratify: Vec<Stratum>,
+
+    /// Seed for choosing tests. The same seed picks the same sample
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+/// Options for comparing two MOO files.
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// The original MOO file
+    pub left: PathBuf,
+
+    /// The MOO file to compare against it, e.g. the same file regenerated after a firmware change
+    pub right: PathBuf,
+
+    /// Number of differing tests to print in detail, or 0 for all
+    #[arg(long, default_value_t = 10)]
+    pub max_tests: usize,
+
+    /// Only print the number of differing tests
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Comparison policy: strict, registers_only or emulator_friendly
+    #[arg(long, default_value_t = ComparisonPreset::Strict)]
+    pub policy: ComparisonPreset,
+}
+
+/// What to do with the test set described by the config file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Generate the test set.
+    Generate,
+    /// Run the existing test set against the CPU.
+    Validate,
+    /// Rebuild the test set from its session logs, without connecting to the hardware.
+    Rebuild,
+}
+
+pub struct TestContext {
+    // The hardware connection. None when rebuilding tests from session logs.
+    client: Option<CpuClient>,
+    load_register_buffer: Cursor<Vec<u8>>,
+    store_register_buffer: Vec<u8>,
+    server_cpu: ServerCpuType,
+    register_set_type: RegisterSetType,
+    test_opcode_size_prefix: TestOpcodeSizePrefix,
+    // The code segment size of the width being generated, from `gen_widths`.
+    gen_width: InstructionSize,
+    code_segment_size: SegmentSize,
+    file_seed: u64,
+    gen_start: Instant,
+    gen_stop: Instant,
+    gen_ct: usize,
+    file_gen_ct: usize,
+    // Cycles and time spent in each run phase, over every generation attempt.
+    phase_budget: PhaseBudget,
+    // Setup time and memory uploads per test, over every generation attempt.
+    setup_stats: SetupStats,
+    trace_log: BufWriter<File>,
+    mnemonic_set: HashMap<String, usize>,
+
+    dry_run: bool,
+    last_program_state: Option<ProgramState>,
+
+    // Exceptions detected per vector, over every generation attempt for the current file.
+    exceptions: BTreeMap<u8, usize>,
+
+    // Set when the last generated test wrote to bytes already fetched as code.
+    smc_detected: bool,
+    // The interrupt injected into the last generated test, if any.
+    interrupt: Option<InterruptRecord>,
+    // The IVT entry redirected for the last generated test, if any.
+    vector: Option<VectorRecord>,
+    // The queue stress layout of the last generated test, if any.
+    queue_stress: Option<QueueStressRecord>,
+    // Per-test annotations per file name, written to the set manifest.
+    annotations: SetAnnotations,
+    // Tests that exceeded their cycle budget, written to the slow opcode report.
+    slow_tests: Vec<SlowTest>,
+    // Set when the last test was aborted for exceeding its cycle budget.
+    slow_abort: bool,
+    // The raw register buffer read back at the end of the last generated test.
+    store_dump: Option<StoreDump>,
+    // Store dumps for the accepted tests in the current file.
+    file_store_dumps: Vec<StoreDump>,
+    // Captures rejected for breaking a cycle-stream invariant.
+    cycle_stream_errors: usize,
+    // The memory strategy set for the last test, restored after a shutdown.
+    memory_strategy: MemoryStrategy,
+    // Files skipped because their opcode kept shutting the CPU down.
+    blocked_opcodes: Vec<BlockedOpcode>,
+    // Timing, retry and error counts per file, written to the generation statistics report.
+    stats: GenStats,
+    // The progress through each file of the current width, for resuming an interrupted run.
+    progress: RunProgress,
+    // Constrains where test code is placed and the range the memory strategy applies to.
+    address_map: AddressMap,
+    // The hardware I/O behind the last generated test.
+    capture: Option<Capture>,
+    // The session log for the current file, if session logging is enabled.
+    session_log: Option<SessionWriter>,
+    // The share of the test files this context generates, when generation is spread over boards.
+    shard: Option<Shard>,
+    // The files generated and their annotations for each width, for the coordinator to merge.
+    shard_widths: Vec<WidthShard>,
+}
+
+impl TestContext {
+    fn new(
+        config: &Config,
+        client: Option<CpuClient>,
+        dry_run: bool,
+        address_map: AddressMap,
+        trace_log: BufWriter<File>,
+    ) -> anyhow::Result<TestContext> {
+        let server_cpu = ServerCpuType::from(config.test_gen.cpu_type);
+        let (load_register_buffer, store_register_buffer) = match config.test_gen.cpu_type {
+            MooCpuType::Intel80186 | MooCpuType::Intel80188 => (Cursor::new(vec![0; 28]), vec![0; 28]),
+            MooCpuType::Intel80286 => (Cursor::new(vec![0; 102]), vec![0; 102]),
+            MooCpuType::Intel80386Ex => (Cursor::new(vec![0; 204]), vec![0; 208]),
+            _ => anyhow::bail!("Unsupported CPU type: {:?}", config.test_gen.cpu_type),
+        };
+        if matches!(
+            config.test_gen.cpu_type,
+            MooCpuType::Intel80186 | MooCpuType::Intel80188
+        ) && config.test_gen.cpu_mode != CpuMode::Real
+        {
+            anyhow::bail!("The 80186 has no descriptor caches, so it can only generate Real mode tests.");
+        }
+        if let Some(client) = &client {
+            check_capabilities(config, client.capabilities())?;
+        }
+
+        Ok(TestContext {
+            client,
+            load_register_buffer,
+            store_register_buffer,
+            server_cpu,
+            register_set_type: RegisterSetType::from(server_cpu),
+            test_opcode_size_prefix: TestOpcodeSizePrefix::None,
+            gen_width: InstructionSize::Sixteen,
+            code_segment_size: SegmentSize::Sixteen,
+            file_seed: 0,
+            gen_start: Instant::now(),
+            gen_stop: Instant::now(),
+            gen_ct: 0,
+            file_gen_ct: 0,
+            phase_budget: PhaseBudget::default(),
+            setup_stats: SetupStats::default(),
+            trace_log,
+            mnemonic_set: Default::default(),
+            dry_run,
+            last_program_state: None,
+            exceptions: Default::default(),
+            smc_detected: false,
+            interrupt: None,
+            vector: None,
+            queue_stress: None,
+            annotations: Default::default(),
+            slow_tests: Vec::new(),
+            slow_abort: false,
+            store_dump: None,
+            file_store_dumps: Vec::new(),
+            cycle_stream_errors: 0,
+            memory_strategy: MemoryStrategy::Random,
+            blocked_opcodes: Vec::new(),
+            stats: GenStats::default(),
+            progress: RunProgress::default(),
+            address_map,
+            capture: None,
+            session_log: None,
+            shard: None,
+            shard_widths: Vec::new(),
+        })
+    }
+
+    fn client(&mut self) -> &mut CpuClient {
+        self.client.as_mut().expect("No hardware connection in offline mode")
+    }
+
+    /// Return true if test code may be placed at `address`: within `instruction_address_range`, and
+    /// in RAM according to the address map.
+    fn code_address_allowed(&self, test_gen: &TestGen, address: u32) -> bool {
+        let [start, end] = test_gen.instruction_address_range;
+        (start..end).contains(&address) && self.address_map.kind_at(address) == RegionKind::Ram
+    }
+
+    /// Return the first and last address the memory strategy applies to: the span of the RAM
+    /// regions in the address map if it lists any, otherwise `mem_strategy_start` and
+    /// `mem_strategy_end`.
+    fn mem_strategy_range(&self, test_gen: &TestGen) -> (u32, u32) {
+        match self.address_map.span(RegionKind::Ram) {
+            Some(span) => (*span.start(), *span.end()),
+            None => (test_gen.mem_strategy_start, test_gen.mem_strategy_end),
+        }
+    }
+}
+
+/// Check that the server supports everything `config` needs, and warn about the features that will
+/// be skipped. Older firmware can still generate tests, just without the newer features.
+fn check_capabilities(config: &Config, caps: Capabilities) -> anyhow::Result<()> {
+    if !caps.contains(Capabilities::CYCLE_LOG) {
+        anyhow::bail!("The server has no cycle log, which test generation needs.");
+    }
+    if config.test_gen.interrupt_injection.is_some() && !caps.contains(Capabilities::INTERRUPT_TRIGGER) {
+        anyhow::bail!("The server does not support interrupt triggers, which interrupt_injection needs.");
+    }
+    let termination = [
+        Some(config.test_gen.termination_condition),
+        config.test_gen.flow_control_termination,
+    ];
+    if let Some(condition) = termination.iter().flatten().find(|t| t.end_condition().is_some()) {
+        if !caps.contains(Capabilities::END_CONDITION) {
+            anyhow::bail!(
+                "The server does not support end conditions, which {:?} termination needs.",
+                condition
+            );
+        }
+    }
+    if !caps.contains(Capabilities::MEMORY_STRATEGY) {
+        log::warn!("The server does not support memory strategies; memory will be left as the server fills it.");
+    }
+    if !caps.contains(Capabilities::PROGRAM_BOUNDS) {
+        log::warn!("The server does not support program bounds; they will not be set.");
+    }
+    Ok(())
+}
+
+/// Create the trace output directories if they don't exist.
+fn create_trace_dirs(test_gen: &TestGen) -> anyhow::Result<()> {
+    for dir in [&test_gen.trace_output_dir, &test_gen.verify_trace_output_dir] {
+        if !dir.exists() {
+            fs::create_dir_all(dir).with_context(|| format!("Creating trace output directory: {}", dir.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Create the trace log `name` in the trace output directory, with the configured suffix.
+fn open_trace_log(test_gen: &TestGen, name: &str) -> anyhow::Result<BufWriter<File>> {
+    let trace_filename = PathBuf::from(format!("{}{}", name, test_gen.trace_file_suffix.display()));
+    let trace_log_path = test_gen.trace_output_dir.join(trace_filename);
+    let trace_log_file = File::create(&trace_log_path)
+        .with_context(|| format!("Creating trace log file: {}", trace_log_path.display()))?;
+    Ok(BufWriter::new(trace_log_file))
+}
+
+/// Verify a test output directory against its manifest. Returns an error if the set is not intact.
+pub fn verify_set(dir: &Path) -> anyhow::Result<()> {
+    manifest::verify_set(dir)
+}
+
+/// Convert legacy JSON test files into a MOO test set.
+pub fn import_json(args: &ImportJsonArgs) -> anyhow::Result<()> {
+    json_import::import_json(&args.inputs, args.cpu, args.moo_version, &args.output_dir)
+}
+
+/// Aggregate the cycle counts of a test set into a timing table per opcode, prefix and ModR/M form.
+pub fn timings(args: &TimingsArgs) -> anyhow::Result<()> {
+    timings::timings(&args.inputs, args.output.as_deref(), args.format)
+}
+
+/// Print a matrix of test counts per opcode, group extension and prefix for a test set. Returns
+/// false if a template is given and some of its cells have no tests.
+pub fn coverage(args: &CoverageArgs) -> anyhow::Result<bool> {
+    coverage::coverage(&args.dir, args.template.as_deref(), args.missing_only)
+}
+
+/// Write a reduced sample of a test set, keeping a few tests of each kind from every test file.
+pub fn sample(args: &SampleArgs) -> anyhow::Result<()> {
+    sample::sample(
+        &args.dir,
+        &args.output_dir,
+        args.per_opcode,
+        &args.stratify,
+        args.seed,
+    )
+}
+
+/// Compare two MOO files test by test, printing their differences. Returns true if they hold the
+/// same tests.
+pub fn diff(args: &DiffArgs) -> anyhow::Result<bool> {
+    diff::diff(&args.left, &args.right, &args.policy, args.max_tests, args.summary)
+}
+
+/// Run each opcode's tests several times on the CPU, and write the flags that vary between runs
+/// to the metadata as undefined.
+pub fn infer_flags(conn: &ConnectionArgs, args: &InferFlagsArgs) -> anyhow::Result<()> {
+    let config = read_config(&args.config_file)?;
+    let cpu_client = conn
+        .connect(config.test_exec.serial_timeout as u64)
+        .context("Connecting to the ArduinoX86 server")?;
+
+    create_trace_dirs(&config.test_gen)?;
+    let trace_log = open_trace_log(&config.test_gen, "infer_flags")?;
+    let mut context = TestContext::new(&config, Some(cpu_client), false, conn.address_map()?, trace_log)?;
+    infer_flags::infer_flags(&mut context, &config, args)
+}
+
+/// Read a generator config, with the ESC opcodes added to the excluded opcodes if it asks for it.
+fn read_config(path: &Path) -> anyhow::Result<Config> {
+    // Read the file into a string
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+
+    // Parse as TOML
+    let mut config: Config = toml::from_str(&text).context("parsing TOML into Config")?;
+
+    // Opcodes are u16s, so catch a typo that names an opcode outside both opcode spaces.
+    if let Some(opcode) = config
+        .test_gen
+        .valid_opcodes
+        .iter()
+        .map(|&opcode| Opcode::from(opcode))
+        .find(|opcode| !opcode.is_valid())
+    {
+        anyhow::bail!("valid_opcodes lists {}, which is neither a one-byte opcode nor an 0Fxx opcode", opcode);
+    }
+
+    if config.test_gen.exclude_esc_opcodes {
+        config
+            .test_gen
+            .excluded_opcodes
+            .extend(config.test_gen.esc_opcodes.clone());
+    }
+    Ok(config)
+}
+
+/// Generate, validate or rebuild the test set described by the config file in `args`.
+pub fn run(conn: &ConnectionArgs, args: &GenArgs, mode: Mode) -> anyhow::Result<()> {
+    let config = read_config(&args.config_file)?;
+
+    if args.dump_programs.is_some() && mode != Mode::Generate {
+        anyhow::bail!("--dump-programs can only be used when generating tests");
+    }
+
+    if args.parallel {
+        if mode != Mode::Generate || args.dump_programs.is_some() {
+            anyhow::bail!("--parallel can only be used when generating tests");
+        }
+        return parallel::gen_tests_parallel(conn, args, &config);
+    }
+
+    // Initialize the random number generator
+
+    // Create a cpu_client connection to cpu_server. Rebuilding works from session logs alone, and
+    // dumping programs only runs the generator.
+    let cpu_client = if mode == Mode::Rebuild || args.dump_programs.is_some() {
+        None
+    }
+    else {
+        match conn.connect(config.test_exec.serial_timeout as u64) {
+            Ok(ard_client) => {
+                println!("Opened connection to Arduino_8088 server!");
+                Some(ard_client)
+            }
+            Err(e) => {
+                eprintln!("Error connecting to Arduino_8088 server: {e}");
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // Create the trace output directories if they don't exist.
+    create_trace_dirs(&config.test_gen)?;
+
+    let trace_log = open_trace_log(&config.test_gen, "init")?;
+    let mut context = TestContext::new(&config, cpu_client, args.dry_run, conn.address_map()?, trace_log)?;
+
+    if let Some(dump_dir) = &args.dump_programs {
+        return dump_programs::dump_programs(&mut context, &config, dump_dir);
+    }
+
+    match mode {
+        Mode::Validate => validate_tests::validate_tests(&mut context, &config)?,
+        Mode::Rebuild => rebuild_tests::rebuild_tests(&mut context, &config)?,
+        _ => gen_tests::gen_tests(&mut context, &config)?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,385 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Test-suite manifest generation and verification.
+//!
+//! A manifest (`manifest.toml`) is written to the test output directory after generation. It
+//! lists every MOO file in the set along with its test count, size and SHA-256 digest, and records
+//! the generator version and the CPU the set was generated on, so that consumers of a published
+//! set can check it is complete and unmodified. Entries may also carry annotations for tests with
+//! properties of interest to emulator authors: tags such as self-modifying code, the trigger
+//! point of any interrupt injected during the test, the handler address of any IVT entry redirected
+//! for the test, how the test was laid out for queue stress, and how many tests raised each
+//! exception.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{moo_io, InjectLine, InjectMode, IvtTarget, QueueStressTarget};
+
+pub const MANIFEST_FILENAME: &str = "manifest.toml";
+
+/// Tag for tests that write to bytes already fetched as code.
+pub const TAG_SMC: &str = "smc";
+/// Tag for hand-written golden tests.
+pub const TAG_GOLDEN: &str = "golden";
+
+/// Per-test annotations, per file name.
+pub type SetAnnotations = HashMap<String, FileAnnotations>;
+
+/// Annotations for the tests in a single file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FileAnnotations {
+    /// Test indices grouped by tag name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, Vec<u32>>,
+    /// Interrupts injected during tests, in test order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interrupts: Vec<InterruptRecord>,
+    /// IVT entries redirected before tests, in test order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vectors: Vec<VectorRecord>,
+    /// Tests laid out to exercise the prefetch queue, in test order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queue_stress: Vec<QueueStressRecord>,
+    /// Number of tests that raised each exception, in vector order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exceptions: Vec<ExceptionCount>,
+}
+
+impl FileAnnotations {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.interrupts.is_empty()
+            && self.vectors.is_empty()
+            && self.queue_stress.is_empty()
+            && self.exceptions.is_empty()
+    }
+
+    pub fn tag(&mut self, tag: &str, test_num: u32) {
+        self.tags.entry(tag.to_string()).or_default().push(test_num);
+    }
+
+    pub fn count_exception(&mut self, vector: u8) {
+        match self.exceptions.binary_search_by_key(&vector, |e| e.vector) {
+            Ok(idx) => self.exceptions[idx].count += 1,
+            Err(idx) => self.exceptions.insert(idx, ExceptionCount { vector, count: 1 }),
+        }
+    }
+}
+
+/// The number of tests in a file that raised an exception vector.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExceptionCount {
+    pub vector: u8,
+    pub count:  u32,
+}
+
+/// The point at which an interrupt was raised during a test.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InterruptRecord {
+    pub test:   u32,
+    pub line:   InjectLine,
+    pub mode:   InjectMode,
+    /// Instruction or cycle count from the start of execution, depending on `mode`.
+    pub offset: u32,
+    /// Vector supplied during INTA. Not meaningful for NMI.
+    pub vector: u8,
+}
+
+/// An IVT entry pointed at an unusual handler address before a test.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VectorRecord {
+    pub test:    u32,
+    pub vector:  u8,
+    pub target:  IvtTarget,
+    /// The handler address written to the IVT entry.
+    pub segment: u16,
+    pub offset:  u16,
+}
+
+/// A test laid out to exercise the prefetch queue.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueueStressRecord {
+    pub test:   u32,
+    pub target: QueueStressTarget,
+    /// For `JumpAfter`, the number of filler bytes jumped over. For `SegmentLimit`, the number of
+    /// instruction bytes past the limit.
+    pub bytes:  u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetManifest {
+    pub set: SetInfo,
+    #[serde(default)]
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetInfo {
+    pub generator_version: String,
+    pub set_version: String,
+    pub cpu_type: String,
+    /// The CPU type reported by the ArduinoX86 server the set was generated on.
+    pub server_cpu: String,
+    pub has_fpu: bool,
+    /// Seconds since the Unix epoch when the manifest was written.
+    pub created: u64,
+    pub total_tests: usize,
+    /// Code segment size of the tests in the set, in bits. Not recorded by older generators, whose
+    /// sets are all 16-bit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_width: Option<u32>,
+    /// Execution mode of the tests in the set (Real, Unreal or Protected), which decides how segment
+    /// registers map to addresses. Not recorded by older generators, whose sets are all real mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_mode: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub file: String,
+    pub test_count: usize,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(flatten)]
+    pub annotations: FileAnnotations,
+}
+
+/// A problem found when verifying a set against its manifest.
+#[derive(Clone, Debug)]
+pub enum VerifyError {
+    Missing(String),
+    Unlisted(String),
+    SizeMismatch(String, u64, u64),
+    DigestMismatch(String),
+    CountMismatch(String, usize, usize),
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Missing(file) => write!(f, "{}: listed in manifest but missing", file),
+            VerifyError::Unlisted(file) => write!(f, "{}: present but not listed in manifest", file),
+            VerifyError::SizeMismatch(file, expected, actual) => {
+                write!(f, "{}: size mismatch (expected {}, found {})", file, expected, actual)
+            }
+            VerifyError::DigestMismatch(file) => write!(f, "{}: SHA-256 mismatch", file),
+            VerifyError::CountMismatch(file, expected, actual) => {
+                write!(f, "{}: test count mismatch (expected {}, found {})", file, expected, actual)
+            }
+        }
+    }
+}
+
+impl SetManifest {
+    /// Build a manifest for every MOO file in `dir`.
+    pub fn build(
+        dir: &Path,
+        set_version: String,
+        cpu_type: String,
+        server_cpu: String,
+        has_fpu: bool,
+    ) -> anyhow::Result<Self> {
+        let mut files = Vec::new();
+        for path in moo_files(dir)? {
+            files.push(ManifestEntry::from_file(&path)?);
+        }
+
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Ok(SetManifest {
+            set: SetInfo {
+                generator_version: env!("CARGO_PKG_VERSION").to_string(),
+                set_version,
+                cpu_type,
+                server_cpu,
+                has_fpu,
+                created,
+                total_tests: files.iter().map(|f| f.test_count).sum(),
+                code_width: None,
+                cpu_mode: None,
+            },
+            files,
+        })
+    }
+
+    /// Attach annotations to the matching file entries.
+    pub fn set_annotations(&mut self, annotations: &SetAnnotations) {
+        for entry in &mut self.files {
+            if let Some(file_annotations) = annotations.get(&entry.file) {
+                entry.annotations = file_annotations.clone();
+            }
+        }
+    }
+
+    /// Sum the exception counts of every file entry, by vector.
+    pub fn exception_histogram(&self) -> BTreeMap<u8, u32> {
+        let mut histogram = BTreeMap::new();
+        for entry in &self.files {
+            for exception in &entry.annotations.exceptions {
+                *histogram.entry(exception.vector).or_default() += exception.count;
+            }
+        }
+        histogram
+    }
+
+    /// Collect the annotations of every file entry, keyed by file name.
+    pub fn annotations(&self) -> SetAnnotations {
+        self.files
+            .iter()
+            .filter(|entry| !entry.annotations.is_empty())
+            .map(|entry| (entry.file.clone(), entry.annotations.clone()))
+            .collect()
+    }
+
+    pub fn read(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_FILENAME);
+        let text = fs::read_to_string(&path).with_context(|| format!("Reading manifest: {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Parsing manifest: {}", path.display()))
+    }
+
+    pub fn write(&self, dir: &Path) -> anyhow::Result<()> {
+        let path = dir.join(MANIFEST_FILENAME);
+        let text = toml::to_string_pretty(self).context("Serializing manifest")?;
+        fs::write(&path, text).with_context(|| format!("Writing manifest: {}", path.display()))
+    }
+
+    /// Check every file in `dir` against the manifest. Returns the list of problems found, which is
+    /// empty if the set is intact.
+    pub fn verify(&self, dir: &Path) -> anyhow::Result<Vec<VerifyError>> {
+        let mut errors = Vec::new();
+
+        for entry in &self.files {
+            let path = dir.join(&entry.file);
+            if !path.exists() {
+                errors.push(VerifyError::Missing(entry.file.clone()));
+                continue;
+            }
+
+            let actual = ManifestEntry::from_file(&path)?;
+            if actual.size != entry.size {
+                errors.push(VerifyError::SizeMismatch(entry.file.clone(), entry.size, actual.size));
+            }
+            if !actual.sha256.eq_ignore_ascii_case(&entry.sha256) {
+                errors.push(VerifyError::DigestMismatch(entry.file.clone()));
+            }
+            if actual.test_count != entry.test_count {
+                errors.push(VerifyError::CountMismatch(
+                    entry.file.clone(),
+                    entry.test_count,
+                    actual.test_count,
+                ));
+            }
+        }
+
+        for path in moo_files(dir)? {
+            let name = file_name(&path);
+            if !self.files.iter().any(|entry| entry.file == name) {
+                errors.push(VerifyError::Unlisted(name));
+            }
+        }
+
+        Ok(errors)
+    }
+}
+
+impl ManifestEntry {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Reading test file: {}", path.display()))?;
+
+        let test_file =
+            moo_io::parse_test_file(&bytes).with_context(|| format!("Parsing test file {}", path.display()))?;
+
+        let digest = Sha256::digest(&bytes);
+        let sha256 = digest.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        Ok(ManifestEntry {
+            file: file_name(path),
+            test_count: test_file.test_ct(),
+            size: bytes.len() as u64,
+            sha256,
+            annotations: FileAnnotations::default(),
+        })
+    }
+}
+
+/// Verify the set in `dir` against its manifest, printing a report.
+/// Returns an error if the set is not intact.
+pub fn verify_set(dir: &Path) -> anyhow::Result<()> {
+    let manifest = SetManifest::read(dir)?;
+    println!(
+        "Verifying set {} ({}, generator v{}): {} files, {} tests",
+        manifest.set.set_version,
+        manifest.set.cpu_type,
+        manifest.set.generator_version,
+        manifest.files.len(),
+        manifest.set.total_tests
+    );
+
+    let errors = manifest.verify(dir)?;
+    for error in &errors {
+        println!("  {}", error);
+    }
+
+    if !errors.is_empty() {
+        anyhow::bail!("Set verification failed with {} error(s).", errors.len());
+    }
+    println!("Set OK.");
+    Ok(())
+}
+
+/// Return the sorted list of MOO files in `dir`. Compressed (.MOO.gz) files are not included.
+fn moo_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Reading directory: {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file()
+            && path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("moo"))
+                .unwrap_or(false)
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
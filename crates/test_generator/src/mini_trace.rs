@@ -0,0 +1,220 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Compact per-test event lists ("mini traces") for emulator validators.
+//!
+//! A full CYCL chunk records every pin of every cycle, which is more than a validator that checks
+//! instruction boundaries needs. With `mini_trace_chunks` set, an MTRC chunk is written after the
+//! TEST chunks for each test, listing just the bus operations it performed and the points where
+//! code fetching jumped. It is derived from the cycles at write time, so it never disagrees with
+//! them, and a reader can check fetch addresses and bus activity without parsing cycles at all.
+//!
+//! An MTRC chunk body is the u32 index of its test, a version byte and a u32 event count,
+//! followed by the events. Each event is a kind byte, the u32 index of the cycle the event
+//! started on, a u32 address and a u16 data value, all little-endian.
+//!
+//! The 286 and 386EX have no queue status lines, so queue flushes aren't observed directly. A
+//! [MiniEventKind::Flush] is inferred wherever a code fetch doesn't follow on from the previous
+//! one, which is where the CPU discarded its queue to fetch from a new address.
+
+use crate::cpu_common::{BusOpType, BusStatusByte};
+use moo::{
+    prelude::{MooCycleState, MooTestFile},
+    types::MooCpuType,
+};
+
+pub const CHUNK_MINI_TRACE: &[u8; 4] = b"MTRC";
+
+/// Layout version of the MTRC chunk, stored after the test index.
+const MINI_TRACE_VERSION: u8 = 1;
+/// Size of the MTRC header: test index, version and event count.
+const MINI_TRACE_HEADER_LEN: usize = 9;
+/// Size of an encoded event.
+const EVENT_LEN: usize = 11;
+/// Both CPUs fetch code a word at a time.
+const FETCH_WIDTH: u32 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MiniEventKind {
+    CodeRead = 0,
+    MemRead = 1,
+    MemWrite = 2,
+    IoRead = 3,
+    IoWrite = 4,
+    /// Code fetching resumed at a new address. The address is that of the next code fetch.
+    Flush = 5,
+}
+
+impl From<BusOpType> for MiniEventKind {
+    fn from(op_type: BusOpType) -> Self {
+        match op_type {
+            BusOpType::CodeRead => MiniEventKind::CodeRead,
+            BusOpType::MemRead => MiniEventKind::MemRead,
+            BusOpType::MemWrite => MiniEventKind::MemWrite,
+            BusOpType::IoRead => MiniEventKind::IoRead,
+            BusOpType::IoWrite => MiniEventKind::IoWrite,
+        }
+    }
+}
+
+impl TryFrom<u8> for MiniEventKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MiniEventKind::CodeRead),
+            1 => Ok(MiniEventKind::MemRead),
+            2 => Ok(MiniEventKind::MemWrite),
+            3 => Ok(MiniEventKind::IoRead),
+            4 => Ok(MiniEventKind::IoWrite),
+            5 => Ok(MiniEventKind::Flush),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MiniEvent {
+    pub kind:    MiniEventKind,
+    pub cycle:   u32,
+    pub address: u32,
+    pub data:    u16,
+}
+
+/// The event list of one test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MiniTrace {
+    /// Index of the test within its file.
+    pub test:   u32,
+    pub events: Vec<MiniEvent>,
+}
+
+impl MiniTrace {
+    /// Derive the event list from the cycles of a test. A bus operation is taken from the cycle
+    /// its status is decoded on, and its data from the cycle after, as when collecting bus ops
+    /// during generation.
+    pub fn from_cycles(test: u32, cpu_type: MooCpuType, cycles: &[MooCycleState]) -> Self {
+        let mut events = Vec::new();
+        let mut latched: Option<MiniEvent> = None;
+        let mut next_fetch: Option<u32> = None;
+
+        for (i, cycle) in cycles.iter().enumerate() {
+            if let Some(kind) = bus_op_kind(cpu_type, cycle) {
+                latched = Some(MiniEvent {
+                    kind,
+                    cycle: i as u32,
+                    address: cycle.address_bus,
+                    data: 0,
+                });
+                continue;
+            }
+            let Some(mut event) = latched.take()
+            else {
+                continue;
+            };
+
+            event.data = cycle.data_bus;
+            if event.kind == MiniEventKind::CodeRead {
+                if next_fetch.is_some_and(|next| next != event.address) {
+                    events.push(MiniEvent {
+                        kind: MiniEventKind::Flush,
+                        data: 0,
+                        ..event
+                    });
+                }
+                next_fetch = Some((event.address & !(FETCH_WIDTH - 1)) + FETCH_WIDTH);
+            }
+            events.push(event);
+        }
+
+        MiniTrace { test, events }
+    }
+
+    /// Append this trace's MTRC chunk to `out`.
+    pub fn write_chunk(&self, out: &mut Vec<u8>) {
+        let body_len = MINI_TRACE_HEADER_LEN + self.events.len() * EVENT_LEN;
+        out.extend_from_slice(CHUNK_MINI_TRACE);
+        out.extend_from_slice(&(body_len as u32).to_le_bytes());
+        out.extend_from_slice(&self.test.to_le_bytes());
+        out.push(MINI_TRACE_VERSION);
+        out.extend_from_slice(&(self.events.len() as u32).to_le_bytes());
+        for event in &self.events {
+            out.push(event.kind as u8);
+            out.extend_from_slice(&event.cycle.to_le_bytes());
+            out.extend_from_slice(&event.address.to_le_bytes());
+            out.extend_from_slice(&event.data.to_le_bytes());
+        }
+    }
+
+    pub(crate) fn from_chunk(body: &[u8]) -> Option<Self> {
+        if body.len() < MINI_TRACE_HEADER_LEN || body[4] != MINI_TRACE_VERSION {
+            return None;
+        }
+        let test = u32::from_le_bytes(body[0..4].try_into().unwrap());
+        let count = u32::from_le_bytes(body[5..9].try_into().unwrap()) as usize;
+        let records = &body[MINI_TRACE_HEADER_LEN..];
+        if records.len() != count * EVENT_LEN {
+            return None;
+        }
+
+        let events = records
+            .chunks_exact(EVENT_LEN)
+            .map(|record| {
+                Some(MiniEvent {
+                    kind:    MiniEventKind::try_from(record[0]).ok()?,
+                    cycle:   u32::from_le_bytes(record[1..5].try_into().unwrap()),
+                    address: u32::from_le_bytes(record[5..9].try_into().unwrap()),
+                    data:    u16::from_le_bytes(record[9..11].try_into().unwrap()),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(MiniTrace { test, events })
+    }
+}
+
+/// Derive the mini trace of every test in `test_file`.
+pub fn mini_traces(test_file: &MooTestFile, cpu_type: MooCpuType) -> Vec<MiniTrace> {
+    test_file
+        .tests()
+        .iter()
+        .enumerate()
+        .map(|(i, test)| MiniTrace::from_cycles(i as u32, cpu_type, test.cycles()))
+        .collect()
+}
+
+/// Decode the bus operation started on `cycle`, if any.
+fn bus_op_kind(cpu_type: MooCpuType, cycle: &MooCycleState) -> Option<MiniEventKind> {
+    let status = match cpu_type {
+        MooCpuType::Intel80386Ex => {
+            // The 386EX status is only valid while ADS# is asserted.
+            if cycle.pins0 & MooCycleState::PIN_ALE == 0 {
+                return None;
+            }
+            BusStatusByte::V3(cycle.bus_state)
+        }
+        MooCpuType::Intel80186 | MooCpuType::Intel80188 => BusStatusByte::V1(cycle.bus_state),
+        _ => BusStatusByte::V2(cycle.bus_state),
+    };
+    BusOpType::try_from(status).ok().map(MiniEventKind::from)
+}
@@ -0,0 +1,632 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Crash-safe reading and writing of MOO test files.
+//!
+//! Test files are only written once all tests for an opcode have been generated, so a crash or
+//! power loss during the write would otherwise leave a truncated file behind. Files are written
+//! to a temporary file in the same directory, synced to disk and then renamed over the target,
+//! so the target path always holds either the previous complete file or the new one.
+//!
+//! Files truncated by older versions of the generator can be salvaged - all complete TEST chunks
+//! are kept and the partial chunk at the end of the file is discarded.
+//!
+//! Files may optionally carry the raw register dump read back from the server for each test in
+//! SDMP extension chunks following the TEST chunks, so register parsing bugs can be diagnosed
+//! without re-running the hardware. They may also carry an MTRC chunk per test, holding the compact
+//! event list described in [mini_trace](crate::mini_trace), and a TAGS chunk for each tagged test
+//! listing its tags, such as `smc` for tests that write to bytes held in the prefetch queue.
+//! Extension chunks are stripped before a file is parsed.
+//!
+//! The CYCL chunk of each test may optionally be stored run-length encoded, as a CYCR chunk.
+//! Idle and wait cycles repeat the same bus state many times over, so this makes files much
+//! smaller. CYCR chunks are expanded back to CYCL before a file is parsed, so reading is
+//! transparent here, but other MOO readers need files written without it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use moo::prelude::MooTestFile;
+
+use crate::mini_trace::{MiniTrace, CHUNK_MINI_TRACE};
+
+/// Size of a chunk header: a four byte ASCII chunk type followed by a little-endian u32 length.
+const CHUNK_HEADER_LEN: usize = 8;
+/// Offset of the test count field in the file header chunk, past the chunk header, version byte
+/// and three reserved bytes.
+const HEADER_TEST_COUNT_OFFSET: usize = CHUNK_HEADER_LEN + 4;
+
+const CHUNK_FILE_HEADER: &[u8; 4] = b"MOO ";
+const CHUNK_TEST: &[u8; 4] = b"TEST";
+const CHUNK_STORE_DUMP: &[u8; 4] = b"SDMP";
+const CHUNK_TEST_TAGS: &[u8; 4] = b"TAGS";
+const CHUNK_CYCLES: &[u8; 4] = b"CYCL";
+const CHUNK_CYCLES_RLE: &[u8; 4] = b"CYCR";
+
+/// Layout version of the CYCR chunk, stored as its first byte.
+const CYCLES_RLE_VERSION: u8 = 1;
+/// Size of the CYCR header: version, record length and cycle count.
+const CYCLES_RLE_HEADER_LEN: usize = 7;
+
+/// The raw register buffer read back from the server at the end of a test: LOADALL-format on
+/// the 286, SMM state save on the 386EX.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreDump {
+    /// Index of the test within its file.
+    pub test: u32,
+    /// Register set type reported by the server.
+    pub reg_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl StoreDump {
+    /// Append this dump's SDMP chunk to `out`.
+    pub fn write_chunk(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(CHUNK_STORE_DUMP);
+        out.extend_from_slice(&(5 + self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.test.to_le_bytes());
+        out.push(self.reg_type);
+        out.extend_from_slice(&self.data);
+    }
+
+    fn from_chunk(body: &[u8]) -> Option<Self> {
+        if body.len() < 5 {
+            return None;
+        }
+        Some(StoreDump {
+            test: u32::from_le_bytes(body[0..4].try_into().unwrap()),
+            reg_type: body[4],
+            data: body[5..].to_vec(),
+        })
+    }
+}
+
+/// The tags of a single test. The TAGS chunk body is the u32 test index followed by each tag name
+/// as a length byte and the name in ASCII.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestTags {
+    /// Index of the test within its file.
+    pub test: u32,
+    pub tags: Vec<String>,
+}
+
+impl TestTags {
+    /// Convert test indices grouped by tag name into a tag list per test, in test order.
+    pub fn from_tag_map(tag_map: &BTreeMap<String, Vec<u32>>) -> Vec<TestTags> {
+        let mut by_test: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+        for (tag, tests) in tag_map {
+            for test in tests {
+                by_test.entry(*test).or_default().push(tag.clone());
+            }
+        }
+        by_test
+            .into_iter()
+            .map(|(test, tags)| TestTags { test, tags })
+            .collect()
+    }
+
+    /// Append this test's TAGS chunk to `out`.
+    pub fn write_chunk(&self, out: &mut Vec<u8>) {
+        let mut body = self.test.to_le_bytes().to_vec();
+        for tag in &self.tags {
+            let name = &tag.as_bytes()[..tag.len().min(u8::MAX as usize)];
+            body.push(name.len() as u8);
+            body.extend_from_slice(name);
+        }
+        out.extend_from_slice(CHUNK_TEST_TAGS);
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+    }
+
+    fn from_chunk(body: &[u8]) -> Option<Self> {
+        let test = u32::from_le_bytes(body.get(0..4)?.try_into().unwrap());
+        let mut tags = Vec::new();
+        let mut pos = 4;
+        while pos < body.len() {
+            let len = body[pos] as usize;
+            let name = body.get(pos + 1..pos + 1 + len)?;
+            tags.push(String::from_utf8_lossy(name).into_owned());
+            pos += 1 + len;
+        }
+        Some(TestTags { test, tags })
+    }
+}
+
+/// The result of salvaging a truncated MOO file.
+pub struct SalvagedTestFile {
+    pub test_file: MooTestFile,
+    /// The number of complete TEST chunks recovered.
+    pub tests_recovered: usize,
+    /// The number of trailing bytes discarded.
+    pub bytes_discarded: usize,
+}
+
+/// Write `test_file` to `path` atomically.
+/// The file is written to a temporary file alongside `path`, synced, and renamed into place.
+pub fn write_test_file(test_file: &MooTestFile, path: &Path) -> anyhow::Result<()> {
+    write_test_file_with_dumps(test_file, &[], &[], &[], false, path)
+}
+
+/// Write `test_file` to `path` atomically, followed by an SDMP chunk for each of `dumps`, an MTRC
+/// chunk for each of `traces` and a TAGS chunk for each of `tags`. If `compress_cycles` is set,
+/// cycle chunks are written run-length encoded.
+pub fn write_test_file_with_dumps(
+    test_file: &MooTestFile,
+    dumps: &[StoreDump],
+    traces: &[MiniTrace],
+    tags: &[TestTags],
+    compress_cycles: bool,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let temp_path = temp_path_for(path);
+
+    let result = (|| -> anyhow::Result<()> {
+        let mut image = Cursor::new(Vec::new());
+        test_file
+            .write(&mut image)
+            .with_context(|| format!("Writing test file: {}", temp_path.display()))?;
+        let mut image = image.into_inner();
+        if compress_cycles {
+            image = compress_cycle_chunks(&image)?;
+        }
+        for dump in dumps {
+            dump.write_chunk(&mut image);
+        }
+        for trace in traces {
+            trace.write_chunk(&mut image);
+        }
+        for test_tags in tags {
+            test_tags.write_chunk(&mut image);
+        }
+
+        let file = File::create(&temp_path).with_context(|| format!("Creating temp file: {}", temp_path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(&image)
+            .with_context(|| format!("Writing test file: {}", temp_path.display()))?;
+
+        let file = writer
+            .into_inner()
+            .map_err(|e| anyhow::anyhow!("Flushing temp file {}: {}", temp_path.display(), e.error()))?;
+        file.sync_all()
+            .with_context(|| format!("Syncing temp file: {}", temp_path.display()))?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    std::fs::rename(&temp_path, path)
+        .with_context(|| format!("Renaming {} to {}", temp_path.display(), path.display()))?;
+
+    // Sync the parent directory so the rename itself is durable. Not all platforms allow opening
+    // a directory as a file, so failure here is not fatal.
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        }
+        else {
+            parent
+        };
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Read the test file at `path`. If the file cannot be parsed, attempt to salvage the complete
+/// tests it contains.
+pub fn read_test_file(path: &Path) -> anyhow::Result<MooTestFile> {
+    read_test_file_with_dumps(path).map(|(test_file, _)| test_file)
+}
+
+/// Read the test file at `path` along with any store dumps it carries. If the file cannot be
+/// parsed, attempt to salvage the complete tests it contains.
+pub fn read_test_file_with_dumps(path: &Path) -> anyhow::Result<(MooTestFile, Vec<StoreDump>)> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .with_context(|| format!("Reading test file: {}", path.display()))?;
+
+    let (bytes, dumps) = split_store_dumps(&bytes);
+    let bytes = expand_cycle_chunks(&bytes).with_context(|| format!("Expanding cycles: {}", path.display()))?;
+    read_test_image(&bytes, path).map(|test_file| (test_file, dumps))
+}
+
+/// Parse a MOO file image, ignoring any extension chunks.
+pub fn parse_test_file(bytes: &[u8]) -> anyhow::Result<MooTestFile> {
+    let (bytes, _) = split_store_dumps(bytes);
+    let bytes = expand_cycle_chunks(&bytes)?;
+    MooTestFile::read(&mut BufReader::new(Cursor::new(&bytes))).map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Replace the CYCL chunks in a MOO file image with run-length encoded CYCR chunks, where that
+/// makes them smaller.
+pub fn compress_cycle_chunks(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    rewrite_cycle_chunks(bytes, CHUNK_CYCLES, |body| Ok(compress_cycles_body(body)))
+}
+
+/// Replace any run-length encoded CYCR chunks in a MOO file image with plain CYCL chunks.
+pub fn expand_cycle_chunks(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    rewrite_cycle_chunks(bytes, CHUNK_CYCLES_RLE, |body| expand_cycles_body(body).map(Some))
+}
+
+/// Return the offset of the first sub-chunk in a TEST chunk body, or None if the body isn't a
+/// sequence of sub-chunks. The sub-chunks may follow a test index.
+fn sub_chunk_offset(body: &[u8]) -> Option<usize> {
+    [0, 4].into_iter().find(|&start| {
+        let mut pos = start;
+        while pos + CHUNK_HEADER_LEN <= body.len() {
+            let tag = &body[pos..pos + 4];
+            if !tag.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                return false;
+            }
+            let len = u32::from_le_bytes(body[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            pos = match (pos + CHUNK_HEADER_LEN).checked_add(len) {
+                Some(end) if end <= body.len() => end,
+                _ => return false,
+            };
+        }
+        pos == body.len() && pos > start
+    })
+}
+
+/// Rewrite every `tag` sub-chunk of every TEST chunk in a MOO file image with `rewrite`, which
+/// returns the new chunk body with its tag flipped between CYCL and CYCR, or None to leave the
+/// chunk as it is. Anything that doesn't parse as a chunk is copied unchanged.
+fn rewrite_cycle_chunks(
+    bytes: &[u8],
+    tag: &[u8; 4],
+    rewrite: impl Fn(&[u8]) -> anyhow::Result<Option<Vec<u8>>>,
+) -> anyhow::Result<Vec<u8>> {
+    let new_tag = if tag == CHUNK_CYCLES {
+        CHUNK_CYCLES_RLE
+    }
+    else {
+        CHUNK_CYCLES
+    };
+
+    let mut image = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk_end = match (pos + CHUNK_HEADER_LEN).checked_add(chunk_len) {
+            Some(end) if end <= bytes.len() => end,
+            _ => break,
+        };
+        let body = &bytes[pos + CHUNK_HEADER_LEN..chunk_end];
+
+        let sub_start = if &bytes[pos..pos + 4] == CHUNK_TEST {
+            sub_chunk_offset(body)
+        }
+        else {
+            None
+        };
+        let Some(sub_start) = sub_start
+        else {
+            image.extend_from_slice(&bytes[pos..chunk_end]);
+            pos = chunk_end;
+            continue;
+        };
+
+        let mut test_body = body[..sub_start].to_vec();
+        let mut sub_pos = sub_start;
+        while sub_pos < body.len() {
+            let sub_len = u32::from_le_bytes(body[sub_pos + 4..sub_pos + 8].try_into().unwrap()) as usize;
+            let sub_end = sub_pos + CHUNK_HEADER_LEN + sub_len;
+            let sub_body = &body[sub_pos + CHUNK_HEADER_LEN..sub_end];
+
+            match (&body[sub_pos..sub_pos + 4] == tag)
+                .then(|| rewrite(sub_body))
+                .transpose()?
+            {
+                Some(Some(new_body)) => {
+                    test_body.extend_from_slice(new_tag);
+                    test_body.extend_from_slice(&(new_body.len() as u32).to_le_bytes());
+                    test_body.extend_from_slice(&new_body);
+                }
+                _ => test_body.extend_from_slice(&body[sub_pos..sub_end]),
+            }
+            sub_pos = sub_end;
+        }
+
+        image.extend_from_slice(CHUNK_TEST);
+        image.extend_from_slice(&(test_body.len() as u32).to_le_bytes());
+        image.extend_from_slice(&test_body);
+        pos = chunk_end;
+    }
+    image.extend_from_slice(&bytes[pos..]);
+    Ok(image)
+}
+
+/// Run-length encode a CYCL chunk body: a u32 cycle count followed by fixed-size cycle records.
+/// The CYCR body is a version byte, the u16 record length and the u32 cycle count, followed by a
+/// u16 repeat count and a record for each run of identical cycles. Returns None if the body
+/// doesn't divide into records, or encoding wouldn't make it smaller.
+fn compress_cycles_body(body: &[u8]) -> Option<Vec<u8>> {
+    let count = u32::from_le_bytes(body.get(0..4)?.try_into().unwrap()) as usize;
+    let records = &body[4..];
+    if count == 0 || records.is_empty() || records.len() % count != 0 {
+        return None;
+    }
+    let record_len = records.len() / count;
+    let record_len_u16 = u16::try_from(record_len).ok()?;
+
+    let mut out = Vec::with_capacity(body.len());
+    out.push(CYCLES_RLE_VERSION);
+    out.extend_from_slice(&record_len_u16.to_le_bytes());
+    out.extend_from_slice(&(count as u32).to_le_bytes());
+
+    let mut cycles = records.chunks_exact(record_len).peekable();
+    while let Some(record) = cycles.next() {
+        let mut run: u16 = 1;
+        while run < u16::MAX && cycles.peek() == Some(&record) {
+            cycles.next();
+            run += 1;
+        }
+        out.extend_from_slice(&run.to_le_bytes());
+        out.extend_from_slice(record);
+    }
+
+    (out.len() < body.len()).then_some(out)
+}
+
+/// Expand a CYCR chunk body back into a CYCL chunk body.
+fn expand_cycles_body(body: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if body.len() < CYCLES_RLE_HEADER_LEN {
+        bail!("CYCR chunk is too short");
+    }
+    if body[0] != CYCLES_RLE_VERSION {
+        bail!("Unsupported CYCR chunk version {}", body[0]);
+    }
+    let record_len = u16::from_le_bytes(body[1..3].try_into().unwrap()) as usize;
+    let count = u32::from_le_bytes(body[3..7].try_into().unwrap()) as usize;
+
+    // Check the runs add up to the cycle count before allocating, so a corrupt count can't
+    // exhaust memory.
+    let runs = body[CYCLES_RLE_HEADER_LEN..].chunks(2 + record_len);
+    if runs.clone().any(|run| run.len() != 2 + record_len) {
+        bail!("CYCR chunk ends in a partial run");
+    }
+    let expanded: usize = runs
+        .clone()
+        .map(|run| u16::from_le_bytes(run[0..2].try_into().unwrap()) as usize)
+        .sum();
+    if expanded != count {
+        bail!("CYCR chunk holds {} cycles, expected {}", expanded, count);
+    }
+
+    let mut out = Vec::with_capacity(4 + record_len * count);
+    out.extend_from_slice(&(count as u32).to_le_bytes());
+    for run in runs {
+        let repeat = u16::from_le_bytes(run[0..2].try_into().unwrap()) as usize;
+        for _ in 0..repeat {
+            out.extend_from_slice(&run[2..]);
+        }
+    }
+    Ok(out)
+}
+
+/// Read the mini traces from the MTRC chunks of a MOO file image.
+pub fn read_mini_traces(bytes: &[u8]) -> Vec<MiniTrace> {
+    let mut traces = Vec::new();
+    let mut pos = 0;
+    while pos + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk_end = match (pos + CHUNK_HEADER_LEN).checked_add(chunk_len) {
+            Some(end) if end <= bytes.len() => end,
+            _ => break,
+        };
+        if &bytes[pos..pos + 4] == CHUNK_MINI_TRACE {
+            traces.extend(MiniTrace::from_chunk(&bytes[pos + CHUNK_HEADER_LEN..chunk_end]));
+        }
+        pos = chunk_end;
+    }
+    traces
+}
+
+/// Read the tags from the TAGS chunks of a MOO file image.
+pub fn read_test_tags(bytes: &[u8]) -> Vec<TestTags> {
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    while pos + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk_end = match (pos + CHUNK_HEADER_LEN).checked_add(chunk_len) {
+            Some(end) if end <= bytes.len() => end,
+            _ => break,
+        };
+        if &bytes[pos..pos + 4] == CHUNK_TEST_TAGS {
+            tags.extend(TestTags::from_chunk(&bytes[pos + CHUNK_HEADER_LEN..chunk_end]));
+        }
+        pos = chunk_end;
+    }
+    tags
+}
+
+/// Separate the SDMP chunks from a MOO file image, returning the image without them and the
+/// dumps they held. MTRC and TAGS chunks are dropped too, as they are derived from the cycles and
+/// the set annotations and are rewritten along with the file. Anything that doesn't parse as a
+/// chunk is left in the image.
+pub fn split_store_dumps(bytes: &[u8]) -> (Vec<u8>, Vec<StoreDump>) {
+    let mut image = Vec::with_capacity(bytes.len());
+    let mut dumps = Vec::new();
+    let mut pos = 0;
+
+    while pos + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk_end = match (pos + CHUNK_HEADER_LEN).checked_add(chunk_len) {
+            Some(end) if end <= bytes.len() => end,
+            _ => break,
+        };
+        if &bytes[pos..pos + 4] == CHUNK_STORE_DUMP {
+            if let Some(dump) = StoreDump::from_chunk(&bytes[pos + CHUNK_HEADER_LEN..chunk_end]) {
+                dumps.push(dump);
+            }
+        }
+        else if &bytes[pos..pos + 4] != CHUNK_MINI_TRACE && &bytes[pos..pos + 4] != CHUNK_TEST_TAGS {
+            image.extend_from_slice(&bytes[pos..chunk_end]);
+        }
+        pos = chunk_end;
+    }
+    image.extend_from_slice(&bytes[pos..]);
+    (image, dumps)
+}
+
+fn read_test_image(bytes: &[u8], path: &Path) -> anyhow::Result<MooTestFile> {
+    match MooTestFile::read(&mut BufReader::new(Cursor::new(bytes))) {
+        Ok(test_file) => Ok(test_file),
+        Err(e) => {
+            log::warn!("Failed to read test file {}: {}. Attempting salvage.", path.display(), e);
+            let salvaged =
+                salvage_test_file(bytes).with_context(|| format!("Salvaging test file: {}", path.display()))?;
+            println!(
+                "Salvaged {} tests from truncated file: {} ({} bytes discarded)",
+                salvaged.tests_recovered,
+                path.display(),
+                salvaged.bytes_discarded
+            );
+            Ok(salvaged.test_file)
+        }
+    }
+}
+
+/// Recover all complete TEST chunks from a possibly truncated MOO file image.
+pub fn salvage_test_file(bytes: &[u8]) -> anyhow::Result<SalvagedTestFile> {
+    if bytes.len() < CHUNK_HEADER_LEN || &bytes[0..4] != CHUNK_FILE_HEADER {
+        bail!("Missing MOO file header");
+    }
+
+    let mut pos = 0;
+    let mut good_end = 0;
+    let mut tests_recovered = 0;
+
+    while pos + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_type = &bytes[pos..pos + 4];
+        let chunk_len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk_end = match (pos + CHUNK_HEADER_LEN).checked_add(chunk_len) {
+            Some(end) if end <= bytes.len() => end,
+            _ => break,
+        };
+
+        if !chunk_type.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            log::warn!("Invalid chunk type at offset {:X}, stopping salvage", pos);
+            break;
+        }
+
+        if chunk_type == CHUNK_TEST {
+            tests_recovered += 1;
+        }
+
+        pos = chunk_end;
+        good_end = chunk_end;
+    }
+
+    if good_end == 0 {
+        bail!("File header chunk is incomplete");
+    }
+
+    let mut salvaged = bytes[..good_end].to_vec();
+
+    // Patch the test count in the file header to match the tests we recovered.
+    set_header_test_count(&mut salvaged, tests_recovered as u32);
+
+    let test_file = MooTestFile::read(&mut BufReader::new(Cursor::new(&salvaged)))
+        .map_err(|e| anyhow::anyhow!("Salvaged file could not be parsed: {}", e))?;
+
+    log::debug!(
+        "Salvaged {} tests, discarded {} trailing bytes",
+        tests_recovered,
+        bytes.len() - good_end
+    );
+
+    Ok(SalvagedTestFile {
+        test_file,
+        tests_recovered,
+        bytes_discarded: bytes.len() - good_end,
+    })
+}
+
+/// Set the test count in the file header of a MOO file image, if the header has one.
+fn set_header_test_count(image: &mut [u8], count: u32) {
+    let header_len = u32::from_le_bytes(image[4..8].try_into().unwrap()) as usize;
+    if header_len >= HEADER_TEST_COUNT_OFFSET - CHUNK_HEADER_LEN + 4 {
+        image[HEADER_TEST_COUNT_OFFSET..HEADER_TEST_COUNT_OFFSET + 4].copy_from_slice(&count.to_le_bytes());
+    }
+}
+
+/// Build a MOO file image holding only the tests of `bytes` whose index is in `keep`, in file
+/// order. The test count in the file header, and the index of each kept test, are renumbered to
+/// match. Extension chunks are dropped.
+pub fn select_tests(bytes: &[u8], keep: &BTreeSet<usize>) -> anyhow::Result<Vec<u8>> {
+    let (bytes, _) = split_store_dumps(bytes);
+    if bytes.len() < CHUNK_HEADER_LEN || &bytes[0..4] != CHUNK_FILE_HEADER {
+        bail!("Missing MOO file header");
+    }
+
+    let mut image = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    let mut test_num = 0;
+    let mut kept: u32 = 0;
+    while pos + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_len = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk_end = match (pos + CHUNK_HEADER_LEN).checked_add(chunk_len) {
+            Some(end) if end <= bytes.len() => end,
+            _ => bail!("Truncated chunk at offset {:X}", pos),
+        };
+
+        if &bytes[pos..pos + 4] != CHUNK_TEST {
+            image.extend_from_slice(&bytes[pos..chunk_end]);
+        }
+        else {
+            if keep.contains(&test_num) {
+                let start = image.len() + CHUNK_HEADER_LEN;
+                image.extend_from_slice(&bytes[pos..chunk_end]);
+                if sub_chunk_offset(&bytes[pos + CHUNK_HEADER_LEN..chunk_end]) == Some(4) {
+                    image[start..start + 4].copy_from_slice(&kept.to_le_bytes());
+                }
+                kept += 1;
+            }
+            test_num += 1;
+        }
+        pos = chunk_end;
+    }
+
+    set_header_test_count(&mut image, kept);
+    Ok(image)
+}
+
+/// Build the temporary path used while writing `path`, e.g. `00.MOO` -> `.00.MOO.tmp`.
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(".tmp");
+    path.with_file_name(temp_name)
+}
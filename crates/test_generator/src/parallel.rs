@@ -0,0 +1,213 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Test generation spread over several boards.
+//!
+//! Every board runs the usual generation loop over the whole opcode range, but only generates
+//! every Nth test file, so the work is shared out without the boards talking to each other. Each
+//! test file is written whole by one board, with its trace in the usual place, and each board
+//! gets its own init trace log. Once every board is done, the coordinator merges the boards'
+//! annotations and reports and writes one set manifest per width.
+
+use anyhow::Context;
+use arduinox86_client::{CpuClientPool, ServerCpuType};
+use arduinox86_config::{AddressMap, ConnectionArgs};
+
+use crate::{
+    create_trace_dirs,
+    cycle_budget::SlowTest,
+    gen_tests,
+    manifest::{SetAnnotations, SetManifest},
+    open_trace_log,
+    shutdown::BlockedOpcode,
+    stats::GenStats,
+    Config,
+    GenArgs,
+    TestContext,
+};
+
+/// The share of the test files generated by one board.
+#[derive(Clone, Debug)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+    /// The board's port or transport URI, for the trace logs.
+    pub board: String,
+}
+
+impl Shard {
+    /// Return true if this shard generates the file at `file_index` in generation order.
+    pub fn owns(&self, file_index: usize) -> bool {
+        file_index % self.count == self.index
+    }
+}
+
+/// The files one board generated for a width, and their annotations.
+#[derive(Clone, Debug, Default)]
+pub struct WidthShard {
+    pub annotations: SetAnnotations,
+    pub files: Vec<String>,
+}
+
+/// What a board hands back to the coordinator when it finishes.
+struct ShardReport {
+    server_cpu: ServerCpuType,
+    has_fpu: bool,
+    widths: Vec<WidthShard>,
+    cycle_stream_errors: usize,
+    blocked_opcodes: Vec<BlockedOpcode>,
+    slow_tests: Vec<SlowTest>,
+    stats: GenStats,
+}
+
+/// Generate the test set on every board in the pool, one thread per board.
+pub fn gen_tests_parallel(conn: &ConnectionArgs, args: &GenArgs, config: &Config) -> anyhow::Result<()> {
+    let pool = conn.pool(config.test_exec.serial_timeout as u64)?;
+    println!(
+        "Generating tests on {} board(s): {}",
+        pool.len(),
+        pool.uris()
+            .iter()
+            .map(|uri| uri.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    create_trace_dirs(&config.test_gen)?;
+    let address_map = conn.address_map()?;
+
+    let results = std::thread::scope(|scope| {
+        let handles = (0..pool.len())
+            .map(|index| {
+                let pool = &pool;
+                let address_map = address_map.clone();
+                scope.spawn(move || gen_shard(pool, index, config, args.dry_run, address_map))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Generation thread panicked")))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut reports = Vec::with_capacity(results.len());
+    for (index, result) in results.into_iter().enumerate() {
+        reports.push(result.with_context(|| format!("Board {} ({})", index + 1, pool.uris()[index]))?);
+    }
+
+    merge_reports(config, &reports)
+}
+
+/// Connect to board `index` and generate its share of the test files.
+fn gen_shard(
+    pool: &CpuClientPool,
+    index: usize,
+    config: &Config,
+    dry_run: bool,
+    address_map: AddressMap,
+) -> anyhow::Result<ShardReport> {
+    let board = pool.uris()[index].to_string();
+    let client = pool
+        .connect(index)
+        .with_context(|| format!("Connecting to {}", board))?;
+    println!("Opened connection to board {} at {}", index + 1, board);
+
+    let trace_log = open_trace_log(&config.test_gen, &format!("board{}_init", index + 1))?;
+    let mut context = TestContext::new(config, Some(client), dry_run, address_map, trace_log)?;
+    context.shard = Some(Shard {
+        index,
+        count: pool.len(),
+        board,
+    });
+    gen_tests::gen_tests(&mut context, config)?;
+
+    let (server_cpu, has_fpu) = context.client().cpu_type()?;
+    Ok(ShardReport {
+        server_cpu,
+        has_fpu,
+        widths: context.shard_widths,
+        cycle_stream_errors: context.cycle_stream_errors,
+        blocked_opcodes: context.blocked_opcodes,
+        slow_tests: context.slow_tests,
+        stats: context.stats,
+    })
+}
+
+/// Write the set manifest for each width, with the annotations of the files each board generated
+/// in place of the previous manifest's, and the combined reports.
+fn merge_reports(config: &Config, reports: &[ShardReport]) -> anyhow::Result<()> {
+    let Some(first) = reports.first()
+    else {
+        return Ok(());
+    };
+    // The manifest records one CPU for the whole set, so every board must have reported the same.
+    for (board, report) in reports.iter().enumerate().skip(1) {
+        if report.server_cpu != first.server_cpu || report.has_fpu != first.has_fpu {
+            return Err(anyhow::anyhow!(
+                "Board {} is a {} (FPU: {}), but board 1 is a {} (FPU: {}); all boards must have the same CPU",
+                board + 1,
+                report.server_cpu,
+                report.has_fpu,
+                first.server_cpu,
+                first.has_fpu
+            ));
+        }
+    }
+
+    for (width_index, output) in gen_tests::width_outputs(&config.test_gen)?.iter().enumerate() {
+        let mut annotations = SetManifest::read(&output.test_dir)
+            .map(|manifest| manifest.annotations())
+            .unwrap_or_default();
+        for width in reports.iter().filter_map(|report| report.widths.get(width_index)) {
+            for file in &width.files {
+                annotations.remove(file);
+                if let Some(file_annotations) = width.annotations.get(file) {
+                    annotations.insert(file.clone(), file_annotations.clone());
+                }
+            }
+        }
+        gen_tests::write_manifest(config, output, first.server_cpu, first.has_fpu, &annotations)?;
+    }
+
+    let mut stats = GenStats::default();
+    for report in reports {
+        stats.merge(&report.stats);
+    }
+    gen_tests::write_reports(
+        &config.test_gen.trace_output_dir,
+        reports.iter().map(|report| report.cycle_stream_errors).sum(),
+        &reports
+            .iter()
+            .flat_map(|report| report.blocked_opcodes.iter().cloned())
+            .collect::<Vec<_>>(),
+        &reports
+            .iter()
+            .flat_map(|report| report.slow_tests.iter().cloned())
+            .collect::<Vec<_>>(),
+        &stats,
+    )
+}
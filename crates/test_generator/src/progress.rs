@@ -0,0 +1,197 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Progress manifest for resuming interrupted generation runs.
+//!
+//! The generator records its progress through each test file in a progress file in the width's
+//! test directory: the opcode and group extension, the file seed, the target and completed test
+//! counts, and the annotations of the completed tests. An entry is updated after its test file is
+//! written, at each checkpoint and when the file is finished, so the recorded count never runs
+//! ahead of the tests on disk. With `append_file` set, a later run skips the files recorded as
+//! complete without reading them, resumes the others at the first missing test, and restores their
+//! annotations. Each test is seeded from the file seed and its index, so the resumed tests are the
+//! same as those an uninterrupted run would have generated.
+//!
+//! When generation is spread over several boards, each board keeps its own progress file.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{manifest::FileAnnotations, moo_io, parallel::Shard};
+
+pub const PROGRESS_FILENAME: &str = "gen_progress.toml";
+
+/// Return the name of the progress file for a run, or for one board of a parallel run.
+pub fn progress_filename(shard: Option<&Shard>) -> String {
+    match shard {
+        Some(shard) => format!("gen_progress.board{}.toml", shard.index + 1),
+        None => PROGRESS_FILENAME.to_string(),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    InProgress,
+    Complete,
+    /// Skipped because its opcode kept shutting the CPU down. Retried on the next run.
+    Blocked,
+}
+
+/// The progress through a single test file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileProgress {
+    pub file: String,
+    pub opcode: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<u8>,
+    /// The file seed in hex, as TOML integers are signed.
+    pub file_seed: String,
+    pub target: usize,
+    pub completed: usize,
+    pub status: FileStatus,
+    /// Annotations for the completed tests.
+    #[serde(flatten)]
+    pub annotations: FileAnnotations,
+}
+
+impl FileProgress {
+    pub fn new(file: &str, opcode: u16, extension: Option<u8>, file_seed: u64, target: usize) -> Self {
+        FileProgress {
+            file: file.to_string(),
+            opcode,
+            extension,
+            file_seed: format_seed(file_seed),
+            target,
+            completed: 0,
+            status: FileStatus::InProgress,
+            annotations: FileAnnotations::default(),
+        }
+    }
+
+    /// Return the annotations of the first `count` tests. Exception counts can't be split by test,
+    /// so they are left for the caller to recount from the tests themselves.
+    pub fn annotations_before(&self, count: usize) -> FileAnnotations {
+        let count = count as u32;
+        let mut annotations = self.annotations.clone();
+        for tests in annotations.tags.values_mut() {
+            tests.retain(|&test| test < count);
+        }
+        annotations.tags.retain(|_, tests| !tests.is_empty());
+        annotations.interrupts.retain(|record| record.test < count);
+        annotations.vectors.retain(|record| record.test < count);
+        annotations.queue_stress.retain(|record| record.test < count);
+        annotations.exceptions.clear();
+        annotations
+    }
+}
+
+pub fn format_seed(file_seed: u64) -> String {
+    format!("{:016X}", file_seed)
+}
+
+/// The progress through every file of a width.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunProgress {
+    #[serde(default)]
+    pub files: Vec<FileProgress>,
+    /// Where the progress is written. Nothing is written if this is unset, as for a dry run.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl RunProgress {
+    /// Open the progress file `name` in `dir`. The recorded progress is read if `resume` is set and
+    /// the file exists, and is otherwise discarded on the first write.
+    pub fn open(dir: &Path, name: &str, resume: bool) -> anyhow::Result<Self> {
+        let path = dir.join(name);
+        let mut progress = if resume && path.exists() {
+            let text = fs::read_to_string(&path).with_context(|| format!("Reading progress: {}", path.display()))?;
+            toml::from_str(&text).with_context(|| format!("Parsing progress: {}", path.display()))?
+        }
+        else {
+            RunProgress::default()
+        };
+        progress.path = Some(path);
+        Ok(progress)
+    }
+
+    pub fn get(&self, file: &str) -> Option<&FileProgress> {
+        self.files.iter().find(|entry| entry.file == file)
+    }
+
+    /// Record that generation of a file has started, replacing any previous entry for it.
+    pub fn begin(&mut self, entry: FileProgress) -> anyhow::Result<()> {
+        match self.files.iter_mut().find(|existing| existing.file == entry.file) {
+            Some(existing) => *existing = entry,
+            None => self.files.push(entry),
+        }
+        self.write()
+    }
+
+    /// Record the number of tests written to a file so far, and their annotations.
+    pub fn update(
+        &mut self,
+        file: &str,
+        completed: usize,
+        status: FileStatus,
+        annotations: Option<&FileAnnotations>,
+    ) -> anyhow::Result<()> {
+        let entry = self.entry_mut(file)?;
+        entry.completed = completed;
+        entry.status = status;
+        entry.annotations = annotations.cloned().unwrap_or_default();
+        self.write()
+    }
+
+    /// Change the status of a file, leaving its recorded tests as they were.
+    pub fn set_status(&mut self, file: &str, status: FileStatus) -> anyhow::Result<()> {
+        let entry = self.entry_mut(file)?;
+        entry.status = status;
+        self.write()
+    }
+
+    fn entry_mut(&mut self, file: &str) -> anyhow::Result<&mut FileProgress> {
+        self.files
+            .iter_mut()
+            .find(|entry| entry.file == file)
+            .with_context(|| format!("No progress recorded for {}", file))
+    }
+
+    /// Write the progress via a temp file and rename, so a crash can't leave it truncated.
+    fn write(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path
+        else {
+            return Ok(());
+        };
+        let text = toml::to_string_pretty(self).context("Serializing progress")?;
+        let temp_path = moo_io::temp_path_for(path);
+        fs::write(&temp_path, text).with_context(|| format!("Writing progress: {}", temp_path.display()))?;
+        fs::rename(&temp_path, path).with_context(|| format!("Renaming {} to {}", temp_path.display(), path.display()))
+    }
+}
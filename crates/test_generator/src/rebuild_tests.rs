@@ -0,0 +1,274 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Rebuild MOO files from the session logs recorded during generation, without the hardware.
+
+use std::{fs, io::BufWriter, path::Path};
+
+use crate::{
+    gen_tests::{
+        annotate_test,
+        build_test,
+        choose_interrupt,
+        choose_queue_stress,
+        choose_vector_target,
+        file_tags,
+        width_outputs,
+        WidthOutput,
+    },
+    instruction::TestInstruction,
+    manifest::SetManifest,
+    mini_trace::mini_traces,
+    moo_io,
+    session::{read_session, SESSION_FILE_EXT},
+    trace_banner,
+    trace_log,
+    Config,
+    Opcode,
+    TestContext,
+    TestOpcodeSizePrefix,
+};
+use anyhow::{bail, Context};
+use moo::{
+    prelude::MooTestFile,
+    types::{MooFileMetadata, MooTestGenMetadata},
+};
+
+pub fn rebuild_tests(context: &mut TestContext, config: &Config) -> anyhow::Result<()> {
+    if config.test_gen.session_log_dir.is_none() {
+        bail!("No session_log_dir in the config, so there are no session logs to rebuild from.");
+    }
+
+    context.gen_ct = 0;
+    for output in width_outputs(&config.test_gen)? {
+        output.prepare()?;
+        rebuild_width(context, config, &output)?;
+    }
+    Ok(())
+}
+
+/// Rebuild the files of one code segment width from the session logs in its session directory.
+fn rebuild_width(context: &mut TestContext, config: &Config, output: &WidthOutput) -> anyhow::Result<()> {
+    let Some(session_dir) = &output.session_dir
+    else {
+        return Ok(());
+    };
+
+    let mut session_paths = Vec::new();
+    for entry in fs::read_dir(session_dir)
+        .with_context(|| format!("Reading session log directory: {}", session_dir.display()))?
+    {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(SESSION_FILE_EXT))
+        {
+            session_paths.push(path);
+        }
+    }
+    session_paths.sort();
+    println!(
+        "Rebuilding tests from {} session log(s) in {}",
+        session_paths.len(),
+        session_dir.display()
+    );
+
+    // Keep the annotations and hardware details of files we don't rebuild.
+    let previous_manifest = SetManifest::read(&output.test_dir).ok();
+    context.annotations = previous_manifest
+        .as_ref()
+        .map(|manifest| manifest.annotations())
+        .unwrap_or_default();
+
+    let rebuilt_start = context.gen_ct;
+    for session_path in &session_paths {
+        rebuild_file(context, config, output, session_path)?;
+    }
+
+    let (server_cpu, has_fpu) = match &previous_manifest {
+        Some(manifest) => (manifest.set.server_cpu.clone(), manifest.set.has_fpu),
+        None => (format!("{:?}", context.server_cpu), false),
+    };
+    let mut manifest = SetManifest::build(
+        &output.test_dir,
+        format!(
+            "{}.{}",
+            config.test_gen.set_version_major, config.test_gen.set_version_minor
+        ),
+        format!("{:?}", config.test_gen.cpu_type),
+        server_cpu,
+        has_fpu,
+    )?;
+    manifest.set.code_width = Some(output.width.into());
+    manifest.set.cpu_mode = Some(format!("{:?}", config.test_gen.cpu_mode));
+    manifest.set_annotations(&context.annotations);
+    manifest.write(&output.test_dir)?;
+    println!(
+        "Rebuilt {} tests. Wrote manifest for {} files ({} tests)",
+        context.gen_ct - rebuilt_start,
+        manifest.files.len(),
+        manifest.set.total_tests
+    );
+
+    Ok(())
+}
+
+fn rebuild_file(
+    context: &mut TestContext,
+    config: &Config,
+    output: &WidthOutput,
+    session_path: &Path,
+) -> anyhow::Result<()> {
+    let session = read_session(session_path)?;
+    let file_name = session.header.file_name.clone();
+    if session.bytes_discarded > 0 {
+        println!(
+            "{}: discarded {} bytes of a partial capture at the end of the log",
+            session_path.display(),
+            session.bytes_discarded
+        );
+    }
+
+    // A gap means session logging was enabled partway through the file. Rebuilding it would drop
+    // the tests before the gap, so leave the existing file alone.
+    if let Some((expected, capture)) = session
+        .captures
+        .iter()
+        .enumerate()
+        .find(|(i, capture)| capture.test as usize != *i)
+    {
+        println!(
+            "Skipping {}: session log has no capture for test {} (next capture is test {})",
+            file_name, expected, capture.test
+        );
+        return Ok(());
+    }
+
+    let trace_stem = Path::new(&file_name).file_stem().unwrap_or_default().to_string_lossy();
+    let trace_file_name = format!("{}{}", trace_stem, config.test_gen.trace_file_suffix.display());
+    let trace_file_path = output.trace_dir.join(trace_file_name);
+    let trace_file = fs::File::create(&trace_file_path)
+        .with_context(|| format!("Creating trace file: {}", trace_file_path.display()))?;
+    context.trace_log = BufWriter::new(trace_file);
+
+    context.file_seed = session.header.file_seed;
+    context.file_gen_ct = 0;
+    context.file_store_dumps.clear();
+    context.exceptions.clear();
+    context.mnemonic_set.clear();
+    context.annotations.remove(&file_name);
+
+    let opcode = Opcode::from(session.header.opcode);
+    let mut test_file = MooTestFile::new(
+        config.test_gen.moo_version,
+        config.test_gen.cpu_type,
+        session.captures.len(),
+    );
+
+    for capture in &session.captures {
+        let test_num = capture.test as usize;
+        let test_registers = capture.test_registers(context.register_set_type)?;
+
+        // The sequence bytes carry any size prefixes, so decode relative to the code segment alone.
+        context.code_segment_size = test_registers.regs.segment_size(iced_x86::Register::CS);
+        let test_instruction = TestInstruction::from((
+            TestOpcodeSizePrefix::None.relative_opcode_size(context.code_segment_size),
+            TestOpcodeSizePrefix::None.relative_address_size(context.code_segment_size),
+            capture.bytes.as_slice(),
+        ));
+
+        trace_banner!(context);
+        trace_log!(
+            context,
+            "Rebuilding test {} ({}) from session log",
+            test_num,
+            test_instruction.name()
+        );
+
+        // The injected interrupt is derived from the test seed, so it is the same one generation chose.
+        context.interrupt = choose_interrupt(config, capture.seed);
+        // So is the redirected IVT entry.
+        context.vector = choose_vector_target(config, capture.seed, &test_instruction, &test_registers);
+        // And the queue stress layout, which the captured bytes and registers already have applied.
+        context.queue_stress = choose_queue_stress(
+            context,
+            config,
+            capture.seed,
+            opcode,
+            &test_instruction,
+            &test_registers,
+        );
+
+        let gen_metadata = MooTestGenMetadata {
+            seed:   capture.seed,
+            gen_ct: capture.gen_ct,
+        };
+        let test = build_test(
+            context,
+            config,
+            test_num,
+            capture.gen_ct as usize,
+            opcode,
+            &test_instruction,
+            &test_registers,
+            gen_metadata,
+            capture,
+        )
+        .with_context(|| format!("Rebuilding test {} of {}", test_num, file_name))?;
+
+        annotate_test(context, config, &file_name, test_num, &test);
+        test_file.add_test(test);
+        context.file_gen_ct += 1;
+        context.gen_ct += 1;
+    }
+
+    let mut test_metadata = MooFileMetadata::new(
+        config.test_gen.set_version_major,
+        config.test_gen.set_version_minor,
+        config.test_gen.cpu_type.into(),
+        opcode.into(),
+    )
+    .with_file_seed(context.file_seed)
+    .with_test_count(context.file_gen_ct as u32);
+    if let Some((mnemonic, _)) = context.mnemonic_set.iter().max_by_key(|entry| entry.1) {
+        test_metadata = test_metadata.with_mnemonic(mnemonic.to_string());
+    }
+    test_file.set_metadata(test_metadata);
+
+    let file_path = output.test_dir.join(&file_name);
+    let traces = match config.test_gen.mini_trace_chunks {
+        true => mini_traces(&test_file, config.test_gen.cpu_type),
+        false => Vec::new(),
+    };
+    moo_io::write_test_file_with_dumps(
+        &test_file,
+        &context.file_store_dumps,
+        &traces,
+        &file_tags(context, &file_name),
+        config.test_gen.compress_cycles,
+        &file_path,
+    )?;
+    println!("Rebuilt {} ({} tests)", file_name, context.file_gen_ct);
+
+    Ok(())
+}
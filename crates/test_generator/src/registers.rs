@@ -22,13 +22,14 @@
 */
 
 use arduinox86_client::{
+    moo_registers16_init,
+    moo_registers32_init,
     registers_common::{RandomizeOpts, SegmentSize},
+    RegisterBuffer,
+    Registers16,
     Registers32,
 };
-use moo::{
-    prelude::{MooRegisters16Init, MooRegisters32Init},
-    types::{MooRegisters, MooRegisters16, MooRegisters32, MooRegistersInit},
-};
+use moo::types::{MooRegisters, MooRegisters16, MooRegisters32, MooRegistersInit};
 use rand_distr::Beta;
 use std::io::{Seek, Write};
 
@@ -56,42 +57,9 @@ impl TryFrom<&Registers> for MooRegisters16 {
     type Error = String;
 
     fn try_from(regs: &Registers) -> Result<Self, Self::Error> {
-        match regs {
-            Registers::V1(v1) => Ok((&MooRegisters16Init {
-                ax:    v1.ax,
-                bx:    v1.bx,
-                cx:    v1.cx,
-                dx:    v1.dx,
-                cs:    v1.cs,
-                ss:    v1.ss,
-                ds:    v1.ds,
-                es:    v1.es,
-                sp:    v1.sp,
-                bp:    v1.bp,
-                si:    v1.si,
-                di:    v1.di,
-                ip:    v1.ip,
-                flags: v1.flags,
-            })
-                .into()),
-            Registers::V2(v2) => Ok((&MooRegisters16Init {
-                ax:    v2.ax,
-                bx:    v2.bx,
-                cx:    v2.cx,
-                dx:    v2.dx,
-                cs:    v2.cs,
-                ss:    v2.ss,
-                ds:    v2.ds,
-                es:    v2.es,
-                sp:    v2.sp,
-                bp:    v2.bp,
-                si:    v2.si,
-                di:    v2.di,
-                ip:    v2.ip,
-                flags: v2.flags,
-            })
-                .into()),
-            _ => Err("Unsupported register version for MooRegisters16 conversion".to_string()),
+        match regs.as_registers16() {
+            Some(regs16) => Ok((&moo_registers16_init(regs16)).into()),
+            None => Err("Unsupported register version for MooRegisters16 conversion".to_string()),
         }
     }
 }
@@ -99,82 +67,10 @@ impl TryFrom<&Registers> for MooRegisters16 {
 impl From<&Registers> for MooRegistersInit {
     fn from(regs: &Registers) -> Self {
         match regs {
-            Registers::V1(v1) => MooRegistersInit::Sixteen(MooRegisters16Init {
-                ax:    v1.ax,
-                bx:    v1.bx,
-                cx:    v1.cx,
-                dx:    v1.dx,
-                cs:    v1.cs,
-                ss:    v1.ss,
-                ds:    v1.ds,
-                es:    v1.es,
-                sp:    v1.sp,
-                bp:    v1.bp,
-                si:    v1.si,
-                di:    v1.di,
-                ip:    v1.ip,
-                flags: v1.flags,
-            }),
-            Registers::V2(v2) => MooRegistersInit::Sixteen(MooRegisters16Init {
-                ax:    v2.ax,
-                bx:    v2.bx,
-                cx:    v2.cx,
-                dx:    v2.dx,
-                cs:    v2.cs,
-                ss:    v2.ss,
-                ds:    v2.ds,
-                es:    v2.es,
-                sp:    v2.sp,
-                bp:    v2.bp,
-                si:    v2.si,
-                di:    v2.di,
-                ip:    v2.ip,
-                flags: v2.flags,
-            }),
-            Registers::V3A(v3a) => MooRegistersInit::ThirtyTwo(MooRegisters32Init {
-                cr0: v3a.cr0,
-                cr3: 0,
-                eax: v3a.eax,
-                ebx: v3a.ebx,
-                ecx: v3a.ecx,
-                edx: v3a.edx,
-                esi: v3a.esi,
-                edi: v3a.edi,
-                ebp: v3a.ebp,
-                esp: v3a.esp,
-                cs: v3a.cs as u32,
-                ds: v3a.ds as u32,
-                es: v3a.es as u32,
-                fs: v3a.fs as u32,
-                gs: v3a.gs as u32,
-                ss: v3a.ss as u32,
-                eip: v3a.eip,
-                dr6: v3a.dr6,
-                dr7: v3a.dr7,
-                eflags: v3a.eflags,
-            }),
-            Registers::V3B(v3b) => MooRegistersInit::ThirtyTwo(MooRegisters32Init {
-                cr0: v3b.cr0,
-                cr3: v3b.cr3,
-                eax: v3b.eax,
-                ebx: v3b.ebx,
-                ecx: v3b.ecx,
-                edx: v3b.edx,
-                esi: v3b.esi,
-                edi: v3b.edi,
-                ebp: v3b.ebp,
-                esp: v3b.esp,
-                cs: v3b.cs as u32,
-                ds: v3b.ds as u32,
-                es: v3b.es as u32,
-                fs: v3b.fs as u32,
-                gs: v3b.gs as u32,
-                ss: v3b.ss as u32,
-                eip: v3b.eip,
-                dr6: v3b.dr6,
-                dr7: v3b.dr7,
-                eflags: v3b.eflags,
-            }),
+            Registers::V1(v1) => MooRegistersInit::Sixteen(moo_registers16_init(v1)),
+            Registers::V2(v2) => MooRegistersInit::Sixteen(moo_registers16_init(v2)),
+            Registers::V3A(v3a) => MooRegistersInit::ThirtyTwo(moo_registers32_init(v3a, 0)),
+            Registers::V3B(v3b) => MooRegistersInit::ThirtyTwo(moo_registers32_init(v3b, v3b.cr3)),
         }
     }
 }
@@ -184,58 +80,50 @@ impl TryFrom<&Registers> for MooRegisters32 {
 
     fn try_from(regs: &Registers) -> Result<Self, Self::Error> {
         match regs {
-            Registers::V3A(v3a) => Ok((&MooRegisters32Init {
-                cr0: v3a.cr0,
-                cr3: 0,
-                eax: v3a.eax,
-                ebx: v3a.ebx,
-                ecx: v3a.ecx,
-                edx: v3a.edx,
-                esi: v3a.esi,
-                edi: v3a.edi,
-                ebp: v3a.ebp,
-                esp: v3a.esp,
-                cs: v3a.cs as u32,
-                ds: v3a.ds as u32,
-                es: v3a.es as u32,
-                fs: v3a.fs as u32,
-                gs: v3a.gs as u32,
-                ss: v3a.ss as u32,
-                eip: v3a.eip,
-                dr6: v3a.dr6,
-                dr7: v3a.dr7,
-                eflags: v3a.eflags,
-            })
-                .into()),
-            Registers::V3B(v3b) => Ok((&MooRegisters32Init {
-                cr0: v3b.cr0,
-                cr3: v3b.cr3,
-                eax: v3b.eax,
-                ebx: v3b.ebx,
-                ecx: v3b.ecx,
-                edx: v3b.edx,
-                esi: v3b.esi,
-                edi: v3b.edi,
-                ebp: v3b.ebp,
-                esp: v3b.esp,
-                cs: v3b.cs as u32,
-                ds: v3b.ds as u32,
-                es: v3b.es as u32,
-                fs: v3b.fs as u32,
-                gs: v3b.gs as u32,
-                ss: v3b.ss as u32,
-                eip: v3b.eip,
-                dr6: v3b.dr6,
-                dr7: v3b.dr7,
-                eflags: v3b.eflags,
-            })
-                .into()),
+            Registers::V3A(v3a) => Ok((&moo_registers32_init(v3a, 0)).into()),
+            Registers::V3B(v3b) => Ok((&moo_registers32_init(v3b, v3b.cr3)).into()),
             _ => Err("Unsupported register version for MooRegisters32 conversion".to_string()),
         }
     }
 }
 
 impl Registers {
+    /// Return the register set as a [Registers16] trait object, if it is a 16-bit register set.
+    pub fn as_registers16(&self) -> Option<&dyn Registers16> {
+        match self {
+            Registers::V1(regs) => Some(regs),
+            Registers::V2(regs) => Some(regs),
+            _ => None,
+        }
+    }
+
+    /// Return the register set as a [Registers32] trait object, if it is a 32-bit register set.
+    pub fn as_registers32(&self) -> Option<&dyn Registers32> {
+        match self {
+            Registers::V3A(regs) => Some(regs),
+            Registers::V3B(regs) => Some(regs),
+            _ => None,
+        }
+    }
+
+    /// Return the register set as a mutable [Registers16] trait object, if it is a 16-bit register set.
+    pub fn as_registers16_mut(&mut self) -> Option<&mut dyn Registers16> {
+        match self {
+            Registers::V1(regs) => Some(regs),
+            Registers::V2(regs) => Some(regs),
+            _ => None,
+        }
+    }
+
+    /// Return the register set as a mutable [Registers32] trait object, if it is a 32-bit register set.
+    pub fn as_registers32_mut(&mut self) -> Option<&mut dyn Registers32> {
+        match self {
+            Registers::V3A(regs) => Some(regs),
+            Registers::V3B(regs) => Some(regs),
+            _ => None,
+        }
+    }
+
     pub fn randomize(
         &mut self,
         opts: &RandomizeOpts,
@@ -244,8 +132,14 @@ impl Registers {
         inject_values: &[u32],
     ) {
         match self {
-            Registers::V1(_regs) => {
-                //gen_regs::randomize_v1(&self.context, &self.config.test_gen, regs);
+            Registers::V1(regs) => {
+                // The V1 set is the 286's real mode register file without the machine state, so
+                // randomize it as one and keep the registers the 8088-80186 have.
+                let mut v2 = arduinox86_client::RemoteCpuRegistersV2::default();
+                v2.randomize(opts, rand, beta, inject_values);
+                *regs = arduinox86_client::RemoteCpuRegistersV1::from(&v2);
+                // Flag bits 12-15 always read as set before the 286.
+                regs.flags |= 0xF000;
             }
             Registers::V2(regs) => regs.randomize(opts, rand, beta, inject_values),
             Registers::V3A(regs) => regs.randomize(opts, rand, beta, inject_values),
@@ -257,10 +151,7 @@ impl Registers {
 
     pub fn to_buffer<WS: Write + Seek>(&self, buf: &mut WS) {
         match self {
-            Registers::V1(_regs) => {
-                //gen_regs::write_v1(&mut W, regs);
-                unimplemented!("Writing V1 registers to buffer is not implemented yet");
-            }
+            Registers::V1(regs) => regs.encode(buf).expect("Failed to write V1 registers"),
             Registers::V2(regs) => regs.to_buffer(buf),
             Registers::V3A(regs) => _ = regs.to_buffer(buf),
             Registers::V3B(regs) => {}
@@ -278,10 +169,10 @@ impl Registers {
 
     pub fn calculate_code_address(&self) -> u32 {
         match self {
-            Registers::V1(regs) => regs.calculate_code_address(),
-            Registers::V2(regs) => regs.calculate_code_address(),
-            Registers::V3A(regs) => regs.calculate_code_address(),
-            Registers::V3B(regs) => regs.calculate_code_address(),
+            Registers::V1(regs) => regs.code_address(),
+            Registers::V2(regs) => regs.code_address(),
+            Registers::V3A(regs) => regs.code_address(),
+            Registers::V3B(regs) => regs.code_address(),
         }
     }
 
@@ -296,42 +187,51 @@ impl Registers {
 
     pub fn ip(&self) -> u16 {
         match self {
-            Registers::V1(regs) => regs.ip,
-            Registers::V2(regs) => regs.ip,
-            Registers::V3A(regs) => regs.eip as u16,
-            Registers::V3B(regs) => regs.eip as u16,
+            Registers::V1(regs) => regs.ip(),
+            Registers::V2(regs) => regs.ip(),
+            Registers::V3A(regs) => regs.eip() as u16,
+            Registers::V3B(regs) => regs.eip() as u16,
+        }
+    }
+    /// Set IP. On the 386 this sets EIP, clearing its upper half.
+    pub fn set_ip(&mut self, value: u16) {
+        match self {
+            Registers::V1(regs) => regs.ip = value,
+            Registers::V2(regs) => regs.ip = value,
+            Registers::V3A(regs) => regs.eip = value as u32,
+            Registers::V3B(regs) => regs.eip = value as u32,
         }
     }
     pub fn cs(&self) -> u16 {
         match self {
-            Registers::V1(regs) => regs.cs,
-            Registers::V2(regs) => regs.cs,
-            Registers::V3A(regs) => regs.cs,
-            Registers::V3B(regs) => regs.cs,
+            Registers::V1(regs) => regs.cs(),
+            Registers::V2(regs) => regs.cs(),
+            Registers::V3A(regs) => regs.cs(),
+            Registers::V3B(regs) => regs.cs(),
         }
     }
     pub fn cs_base(&self) -> u32 {
         match self {
-            Registers::V1(regs) => (regs.cs as u32) << 4,
-            Registers::V2(regs) => regs.cs_desc.base_address(),
-            Registers::V3A(regs) => regs.cs_desc.base_address(),
-            Registers::V3B(regs) => regs.cs_desc.base_address(),
+            Registers::V1(regs) => regs.cs_base(),
+            Registers::V2(regs) => regs.cs_base(),
+            Registers::V3A(regs) => regs.cs_base(),
+            Registers::V3B(regs) => regs.cs_base(),
         }
     }
     pub fn ss(&self) -> u16 {
         match self {
-            Registers::V1(regs) => regs.ss,
-            Registers::V2(regs) => regs.ss,
-            Registers::V3A(regs) => regs.ss,
-            Registers::V3B(regs) => regs.ss,
+            Registers::V1(regs) => regs.ss(),
+            Registers::V2(regs) => regs.ss(),
+            Registers::V3A(regs) => regs.ss(),
+            Registers::V3B(regs) => regs.ss(),
         }
     }
     pub fn ss_base(&self) -> u32 {
         match self {
-            Registers::V1(regs) => (regs.ss as u32) << 4,
-            Registers::V2(regs) => regs.ss_desc.base_address(),
-            Registers::V3A(regs) => regs.ss_desc.base_address(),
-            Registers::V3B(regs) => regs.ss_desc.base_address(),
+            Registers::V1(regs) => regs.ss_base(),
+            Registers::V2(regs) => regs.ss_base(),
+            Registers::V3A(regs) => regs.ss_base(),
+            Registers::V3B(regs) => regs.ss_base(),
         }
     }
     pub fn segment_limit(&self, segment: iced_x86::Register) -> Option<u32> {
@@ -356,6 +256,51 @@ impl Registers {
             Registers::V3B(regs) => None,
         }
     }
+    /// Return the base address of `segment`. Register sets with segment descriptors return the
+    /// descriptor base, so this is the address the CPU actually uses in protected mode.
+    pub fn segment_base(&self, segment: iced_x86::Register) -> Option<u32> {
+        match self {
+            Registers::V1(regs) => match segment {
+                iced_x86::Register::DS => Some((regs.ds as u32) << 4),
+                iced_x86::Register::ES => Some((regs.es as u32) << 4),
+                iced_x86::Register::SS => Some((regs.ss as u32) << 4),
+                iced_x86::Register::CS => Some((regs.cs as u32) << 4),
+                _ => None,
+            },
+            Registers::V2(regs) => match segment {
+                iced_x86::Register::DS => Some(regs.ds_desc.base_address()),
+                iced_x86::Register::ES => Some(regs.es_desc.base_address()),
+                iced_x86::Register::SS => Some(regs.ss_desc.base_address()),
+                iced_x86::Register::CS => Some(regs.cs_desc.base_address()),
+                _ => None,
+            },
+            Registers::V3A(regs) => match segment {
+                iced_x86::Register::DS => Some(regs.ds_desc.base()),
+                iced_x86::Register::ES => Some(regs.es_desc.base()),
+                iced_x86::Register::FS => Some(regs.fs_desc.base()),
+                iced_x86::Register::GS => Some(regs.gs_desc.base()),
+                iced_x86::Register::SS => Some(regs.ss_desc.base()),
+                iced_x86::Register::CS => Some(regs.cs_desc.base()),
+                _ => None,
+            },
+            Registers::V3B(_regs) => None,
+        }
+    }
+    /// Return true if `segment` is described as an expand-down data segment, in which case its
+    /// valid offsets lie above the limit rather than below it.
+    pub fn segment_expand_down(&self, segment: iced_x86::Register) -> bool {
+        let desc = match self {
+            Registers::V2(regs) => match segment {
+                iced_x86::Register::DS => &regs.ds_desc,
+                iced_x86::Register::ES => &regs.es_desc,
+                iced_x86::Register::SS => &regs.ss_desc,
+                _ => return false,
+            },
+            _ => return false,
+        };
+        // Only data segments (S set, type bit 3 clear) can expand down.
+        desc.s() == 1 && desc.d_type() & 0x08 == 0 && desc.d_type() & 0x04 != 0
+    }
     pub fn segment_size(&self, segment: iced_x86::Register) -> SegmentSize {
         match self {
             Registers::V1(_regs) => SegmentSize::Sixteen,
@@ -372,6 +317,35 @@ impl Registers {
             Registers::V3B(_regs) => unimplemented!("Segment size for V3B registers is not implemented"),
         }
     }
+
+    /// Switch a 286 register set to protected mode, keeping the segment bases and limits. The
+    /// descriptor caches are loaded directly, as LOADALL does, so no descriptor table is needed.
+    /// Other register sets are left as they are.
+    pub fn enter_protected_mode(&mut self) {
+        if let Registers::V2(regs) = self {
+            regs.enter_protected_mode();
+        }
+    }
+
+    /// Switch the register set to unreal mode, moving every segment `high_base` bytes past its real
+    /// mode base. The 8088-80186 register set has no descriptor caches and is left as it is.
+    pub fn enter_unreal_mode(&mut self, high_base: u32) {
+        match self {
+            Registers::V1(_) | Registers::V3B(_) => {}
+            Registers::V2(regs) => regs.enter_unreal_mode(high_base),
+            Registers::V3A(regs) => regs.enter_unreal_mode(high_base),
+        }
+    }
+
+    /// Set the size of the code segment. Only the 386 has 32-bit segments, so this does nothing for
+    /// older register sets.
+    pub fn set_code_segment_size(&mut self, size: SegmentSize) {
+        match self {
+            Registers::V1(_) | Registers::V2(_) => {}
+            Registers::V3A(regs) => regs.cs_desc = regs.cs_desc.with_segment_size(size),
+            Registers::V3B(regs) => regs.cs_desc = regs.cs_desc.with_segment_size(size),
+        }
+    }
     pub fn cx(&self) -> u16 {
         match self {
             Registers::V1(regs) => regs.cx,
@@ -406,18 +380,18 @@ impl Registers {
     }
     pub fn sp(&self) -> u16 {
         match self {
-            Registers::V1(regs) => regs.sp,
-            Registers::V2(regs) => regs.sp,
-            Registers::V3A(regs) => regs.esp as u16,
-            Registers::V3B(regs) => regs.esp as u16,
+            Registers::V1(regs) => regs.sp(),
+            Registers::V2(regs) => regs.sp(),
+            Registers::V3A(regs) => regs.esp() as u16,
+            Registers::V3B(regs) => regs.esp() as u16,
         }
     }
     pub fn stack_address(&self) -> u32 {
         match self {
-            Registers::V1(regs) => ((regs.ss as u32) << 4) + regs.sp as u32,
-            Registers::V2(regs) => regs.ss_desc.base_address() + regs.sp as u32,
-            Registers::V3A(regs) => regs.ss_desc.base_address() + regs.esp,
-            Registers::V3B(regs) => regs.ss_desc.base_address() + regs.esp,
+            Registers::V1(regs) => regs.stack_address(),
+            Registers::V2(regs) => regs.stack_address(),
+            Registers::V3A(regs) => regs.stack_address(),
+            Registers::V3B(regs) => regs.stack_address(),
         }
     }
     pub fn mask_registers32(&mut self, segment: iced_x86::Register, ea_registers: &[iced_x86::Register]) {
@@ -0,0 +1,214 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Reduce a test set to a small sample.
+//!
+//! A full test set runs to gigabytes, which is more than a quick emulator smoke test needs. This
+//! keeps a few tests from each test file (one per opcode, size prefix and group extension) and
+//! writes them out as a new set. Tests may be grouped by whether they raised an exception, the
+//! prefixes they carry and their operand width; one test is taken from each group in turn, so
+//! every kind of test in a file is kept before any kind is kept twice.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use iced_x86::{Decoder, DecoderOptions, OpKind};
+use moo::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    manifest::{SetManifest, MANIFEST_FILENAME},
+    moo_io,
+    timings::{moo_files, stem_opcode},
+};
+
+/// A property tests can be grouped by when sampling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Stratum {
+    /// The exception the test raised, if any.
+    Exception,
+    /// The prefix bytes before the opcode.
+    Prefix,
+    /// The size of the memory operand, or of the first register operand.
+    Width,
+}
+
+/// The group a test falls in. Properties that aren't being grouped by are left at their default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct GroupKey {
+    exception: Option<u8>,
+    prefixes: Vec<u8>,
+    width: usize,
+}
+
+/// Return the operand width of an instruction in bytes, or 0 if it has no memory or register
+/// operand.
+fn operand_width(bytes: &[u8]) -> usize {
+    let instruction = Decoder::new(16, bytes, DecoderOptions::NO_INVALID_CHECK).decode();
+    if (0..instruction.op_count()).any(|i| instruction.op_kind(i) == OpKind::Memory) {
+        instruction.memory_size().size()
+    }
+    else if instruction.op_count() > 0 && instruction.op_kind(0) == OpKind::Register {
+        instruction.op0_register().size()
+    }
+    else {
+        0
+    }
+}
+
+fn group_key(test: &MooTest, opcode: &[u8], strata: &[Stratum]) -> GroupKey {
+    let bytes = test.bytes();
+    let mut key = GroupKey::default();
+    for stratum in strata {
+        match stratum {
+            Stratum::Exception => key.exception = test.exception().map(|exception| exception.exception_num),
+            Stratum::Prefix => {
+                let opcode_offset = opcode
+                    .first()
+                    .and_then(|first| bytes.iter().position(|b| b == first))
+                    .unwrap_or(0);
+                key.prefixes = bytes[..opcode_offset].to_vec();
+            }
+            Stratum::Width => key.width = operand_width(bytes),
+        }
+    }
+    key
+}
+
+/// Choose up to `count` tests, taking one from each group in turn. Returns the indices of the
+/// chosen tests.
+fn choose(keys: &[GroupKey], count: usize, rng: &mut StdRng) -> BTreeSet<usize> {
+    let mut groups: BTreeMap<&GroupKey, Vec<usize>> = BTreeMap::new();
+    for (index, key) in keys.iter().enumerate() {
+        groups.entry(key).or_default().push(index);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+    for group in &mut groups {
+        group.shuffle(rng);
+    }
+    // Visit the groups in a random order, so a count smaller than the number of groups doesn't
+    // always favor the same ones.
+    groups.shuffle(rng);
+
+    let count = count.min(keys.len());
+    let mut chosen = BTreeSet::new();
+    let mut round = 0;
+    while chosen.len() < count {
+        for group in &groups {
+            if let Some(&index) = group.get(round) {
+                chosen.insert(index);
+                if chosen.len() == count {
+                    break;
+                }
+            }
+        }
+        round += 1;
+    }
+    chosen
+}
+
+/// Write a sample of up to `per_file` tests from each MOO file in `dir` to `output_dir`, grouping
+/// tests by `strata`. The same `seed` picks the same tests. If `dir` has a manifest, one is
+/// written for the sample too.
+pub fn sample(dir: &Path, output_dir: &Path, per_file: usize, strata: &[Stratum], seed: u64) -> anyhow::Result<()> {
+    let files = moo_files(&[dir.to_path_buf()])?;
+    if files.is_empty() {
+        bail!("No MOO files found in {}", dir.display());
+    }
+    fs::create_dir_all(output_dir).with_context(|| format!("Creating {}", output_dir.display()))?;
+    if fs::canonicalize(dir)? == fs::canonicalize(output_dir)? {
+        bail!("The sample can't be written over the test set it's taken from");
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut total_tests = 0;
+    let mut total_kept = 0;
+    for path in &files {
+        let stem = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_uppercase();
+        let opcode = stem_opcode(&stem).unwrap_or_else(|| {
+            log::warn!(
+                "Can't get an opcode from file name {}, not grouping by prefix",
+                path.display()
+            );
+            Vec::new()
+        });
+
+        let bytes = fs::read(path).with_context(|| format!("Reading test file: {}", path.display()))?;
+        let test_file =
+            moo_io::parse_test_file(&bytes).with_context(|| format!("Parsing test file {}", path.display()))?;
+        let mut keys = Vec::new();
+        for test in test_file.tests() {
+            keys.push(group_key(test, &opcode, strata));
+        }
+
+        let keep = choose(&keys, per_file, &mut rng);
+        let image = moo_io::select_tests(&bytes, &keep).with_context(|| format!("Sampling {}", path.display()))?;
+        let mut sample_file =
+            moo_io::parse_test_file(&image).with_context(|| format!("Parsing sample of {}", path.display()))?;
+        if let Some(metadata) = sample_file.metadata().cloned() {
+            sample_file.set_metadata(metadata.with_test_count(keep.len() as u32));
+        }
+        moo_io::write_test_file(&sample_file, &output_dir.join(path.file_name().unwrap_or_default()))?;
+
+        log::debug!(
+            "{}: kept {} of {} tests from {} group(s)",
+            stem,
+            keep.len(),
+            keys.len(),
+            keys.iter().collect::<BTreeSet<_>>().len()
+        );
+        total_tests += keys.len();
+        total_kept += keep.len();
+    }
+
+    if dir.join(MANIFEST_FILENAME).exists() {
+        let source = SetManifest::read(dir)?;
+        let mut manifest = SetManifest::build(
+            output_dir,
+            format!("{}-sample", source.set.set_version),
+            source.set.cpu_type,
+            source.set.server_cpu,
+            source.set.has_fpu,
+        )?;
+        manifest.set.code_width = source.set.code_width;
+        manifest.set.cpu_mode = source.set.cpu_mode;
+        manifest.write(output_dir)?;
+    }
+
+    println!(
+        "Sampled {} of {} tests from {} file(s) into {}",
+        total_kept,
+        total_tests,
+        files.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,350 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Raw session logs, for rebuilding MOO files without the hardware.
+//!
+//! While generating, the hardware I/O behind every accepted test is appended to a session log:
+//! the register set that was loaded, the raw register dump read back, and the cycle states.
+//! Everything else in a test is derived from these, so [rebuild_tests](crate::rebuild_tests) can
+//! regenerate a test set after a format change (a new chunk, a fixed encoding) instead of spending
+//! days of hardware time on it again.
+//!
+//! A session log uses the same chunk framing as a MOO file: a four byte ASCII chunk type followed
+//! by a little-endian u32 length. A SESN chunk opens the log and each CAPT chunk holds one test.
+//! Captures are flushed as soon as a test is accepted, so a log cut short by a crash only loses
+//! the capture that was being written.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use arduinox86_client::{
+    ProgramState,
+    RegisterBuffer,
+    RegisterSetType,
+    RemoteCpuRegistersV1,
+    RemoteCpuRegistersV2,
+    RemoteCpuRegistersV3A,
+    ServerCycleState,
+};
+
+use crate::{gen_regs::TestRegisters, registers::Registers};
+
+/// File extension of session logs.
+pub const SESSION_FILE_EXT: &str = "SES";
+
+const SESSION_VERSION: u8 = 1;
+const CHUNK_HEADER_LEN: usize = 8;
+const CHUNK_SESSION: &[u8; 4] = b"SESN";
+const CHUNK_CAPTURE: &[u8; 4] = b"CAPT";
+/// Size of a cycle state as sent by the server.
+const CYCLE_STATE_LEN: usize = 12;
+
+/// Identifies the test file a session log belongs to.
+#[derive(Clone, Debug)]
+pub struct SessionHeader {
+    /// Name of the MOO file, e.g. `0F05.MOO`.
+    pub file_name: String,
+    pub opcode:    u16,
+    pub file_seed: u64,
+}
+
+/// The hardware I/O for one test.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    /// Index of the test within its file.
+    pub test: u32,
+    pub gen_ct: u16,
+    pub seed: u64,
+    pub instruction_address: u32,
+    /// The instruction sequence uploaded to the server.
+    pub bytes: Vec<u8>,
+    /// The register set loaded onto the CPU, in load buffer format.
+    pub initial_regs: Vec<u8>,
+    /// Register set type reported by the server for the dump.
+    pub reg_type: u8,
+    /// The raw register buffer read back from the server.
+    pub store_regs: Vec<u8>,
+    pub cycles: Vec<ServerCycleState>,
+}
+
+impl Capture {
+    /// Decode the initial register set for a server of the given register set type.
+    pub fn test_registers(&self, register_set_type: RegisterSetType) -> anyhow::Result<TestRegisters> {
+        let regs = match register_set_type {
+            RegisterSetType::Intel8088 => Registers::V1(
+                RemoteCpuRegistersV1::decode(self.initial_regs.as_slice())
+                    .map_err(|e| anyhow::anyhow!("Error parsing V1 registers: {}", e))?,
+            ),
+            RegisterSetType::Intel286 => Registers::V2(
+                RemoteCpuRegistersV2::try_from(self.initial_regs.as_slice())
+                    .map_err(|e| anyhow::anyhow!("Error parsing V2 registers: {}", e))?,
+            ),
+            RegisterSetType::Intel386 => Registers::V3A(
+                RemoteCpuRegistersV3A::try_from(self.initial_regs.as_slice())
+                    .map_err(|e| anyhow::anyhow!("Error parsing V3A registers: {}", e))?,
+            ),
+            _ => bail!("Unsupported register set type for rebuild: {:?}", register_set_type),
+        };
+        Ok(TestRegisters {
+            regs,
+            reg_seed: 0,
+            instruction_address: self.instruction_address,
+        })
+    }
+
+    fn write_body(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.test.to_le_bytes());
+        out.extend_from_slice(&self.gen_ct.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.instruction_address.to_le_bytes());
+        write_block(out, &self.bytes);
+        write_block(out, &self.initial_regs);
+        out.push(self.reg_type);
+        write_block(out, &self.store_regs);
+        out.extend_from_slice(&(self.cycles.len() as u32).to_le_bytes());
+        for cycle in &self.cycles {
+            out.extend_from_slice(&cycle.address_bus.to_le_bytes());
+            out.extend_from_slice(&cycle.data_bus.to_le_bytes());
+            out.push(cycle.cpu_state_bits);
+            out.push(cycle.cpu_status_bits);
+            out.push(cycle.bus_control_bits);
+            out.push(cycle.bus_command_bits);
+            out.extend_from_slice(&cycle.pins.to_le_bytes());
+        }
+    }
+
+    fn read_body(body: &[u8]) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(body);
+        let test = read_u32(&mut cursor)?;
+        let gen_ct = u16::from_le_bytes(read_array(&mut cursor)?);
+        let seed = u64::from_le_bytes(read_array(&mut cursor)?);
+        let instruction_address = read_u32(&mut cursor)?;
+        let bytes = read_block(&mut cursor)?;
+        let initial_regs = read_block(&mut cursor)?;
+        let [reg_type] = read_array(&mut cursor)?;
+        let store_regs = read_block(&mut cursor)?;
+
+        let cycle_ct = read_u32(&mut cursor)? as usize;
+        let mut cycles = Vec::with_capacity(cycle_ct);
+        for _ in 0..cycle_ct {
+            let raw: [u8; CYCLE_STATE_LEN] = read_array(&mut cursor)?;
+            cycles.push(ServerCycleState {
+                program_state: ProgramState::Execute,
+                address_bus: u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]),
+                data_bus: u16::from_le_bytes([raw[4], raw[5]]),
+                cpu_state_bits: raw[6],
+                cpu_status_bits: raw[7],
+                bus_control_bits: raw[8],
+                bus_command_bits: raw[9],
+                pins: u16::from_le_bytes([raw[10], raw[11]]),
+            });
+        }
+
+        Ok(Capture {
+            test,
+            gen_ct,
+            seed,
+            instruction_address,
+            bytes,
+            initial_regs,
+            reg_type,
+            store_regs,
+            cycles,
+        })
+    }
+}
+
+/// Appends captures to a session log.
+pub struct SessionWriter {
+    path:   PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl SessionWriter {
+    /// Open the session log for `header` in `dir`. A new log is started unless `append` is set
+    /// and a log already exists, in which case captures are added to the end of it.
+    pub fn open(dir: &Path, header: &SessionHeader, append: bool) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("Creating session log directory: {}", dir.display()))?;
+        let path = session_path(dir, &header.file_name);
+
+        if append && path.exists() {
+            let file = OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Opening session log: {}", path.display()))?;
+            return Ok(SessionWriter {
+                path,
+                writer: BufWriter::new(file),
+            });
+        }
+
+        let file = File::create(&path).with_context(|| format!("Creating session log: {}", path.display()))?;
+        let mut session = SessionWriter {
+            path,
+            writer: BufWriter::new(file),
+        };
+
+        let mut body = vec![SESSION_VERSION];
+        body.extend_from_slice(&header.opcode.to_le_bytes());
+        body.extend_from_slice(&header.file_seed.to_le_bytes());
+        write_block(&mut body, header.file_name.as_bytes());
+        session.write_chunk(CHUNK_SESSION, &body)?;
+        Ok(session)
+    }
+
+    /// Append a capture and flush it to disk.
+    pub fn write(&mut self, capture: &Capture) -> anyhow::Result<()> {
+        let mut body = Vec::new();
+        capture.write_body(&mut body);
+        self.write_chunk(CHUNK_CAPTURE, &body)
+    }
+
+    fn write_chunk(&mut self, chunk_type: &[u8; 4], body: &[u8]) -> anyhow::Result<()> {
+        let result = (|| -> std::io::Result<()> {
+            self.writer.write_all(chunk_type)?;
+            self.writer.write_all(&(body.len() as u32).to_le_bytes())?;
+            self.writer.write_all(body)?;
+            self.writer.flush()?;
+            self.writer.get_ref().sync_data()
+        })();
+        result.with_context(|| format!("Writing session log: {}", self.path.display()))
+    }
+}
+
+/// The contents of a session log.
+pub struct Session {
+    pub header: SessionHeader,
+    /// One capture per test, in test order. If a test was captured more than once (generation
+    /// was resumed after a crash), the last capture wins.
+    pub captures: Vec<Capture>,
+    /// The number of trailing bytes discarded from a truncated log.
+    pub bytes_discarded: usize,
+}
+
+/// Return the path of the session log for the MOO file `file_name`.
+pub fn session_path(dir: &Path, file_name: &str) -> PathBuf {
+    dir.join(Path::new(file_name).with_extension(SESSION_FILE_EXT))
+}
+
+/// Read a session log, discarding a partial capture at the end of the file.
+pub fn read_session(path: &Path) -> anyhow::Result<Session> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .with_context(|| format!("Reading session log: {}", path.display()))?;
+
+    let mut header = None;
+    let mut captures: Vec<Capture> = Vec::new();
+    let mut offset = 0;
+    while offset + CHUNK_HEADER_LEN <= bytes.len() {
+        let chunk_type = &bytes[offset..offset + 4];
+        let len = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let body_start = offset + CHUNK_HEADER_LEN;
+        if body_start + len > bytes.len() {
+            break;
+        }
+        let body = &bytes[body_start..body_start + len];
+
+        if chunk_type == CHUNK_SESSION {
+            header = Some(read_header(body).with_context(|| format!("Bad session header in {}", path.display()))?);
+        }
+        else if chunk_type == CHUNK_CAPTURE {
+            let capture = Capture::read_body(body)
+                .with_context(|| format!("Bad capture at offset {} in {}", offset, path.display()))?;
+            captures.retain(|c| c.test != capture.test);
+            captures.push(capture);
+        }
+        else {
+            log::warn!(
+                "Skipping unknown chunk {:?} in {}",
+                String::from_utf8_lossy(chunk_type),
+                path.display()
+            );
+        }
+        offset = body_start + len;
+    }
+
+    let Some(header) = header
+    else {
+        bail!("{} is not a session log", path.display());
+    };
+    captures.sort_by_key(|c| c.test);
+
+    Ok(Session {
+        header,
+        captures,
+        bytes_discarded: bytes.len() - offset,
+    })
+}
+
+fn read_header(body: &[u8]) -> anyhow::Result<SessionHeader> {
+    let mut cursor = Cursor::new(body);
+    let [version] = read_array(&mut cursor)?;
+    if version != SESSION_VERSION {
+        bail!("Unsupported session log version {}", version);
+    }
+    let opcode = u16::from_le_bytes(read_array(&mut cursor)?);
+    let file_seed = u64::from_le_bytes(read_array(&mut cursor)?);
+    let file_name = String::from_utf8(read_block(&mut cursor)?)?;
+    Ok(SessionHeader {
+        file_name,
+        opcode,
+        file_seed,
+    })
+}
+
+/// Write a u32 length followed by `data`.
+fn write_block(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+fn read_block(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<Vec<u8>> {
+    let len = read_u32(cursor)? as usize;
+    // Check the length before allocating, so a corrupt length can't exhaust memory.
+    let remaining = cursor.get_ref().len().saturating_sub(cursor.position() as usize);
+    if len > remaining {
+        bail!(
+            "Block of {} bytes overruns the {} bytes left in the session log",
+            len,
+            remaining
+        );
+    }
+    let mut data = vec![0; len];
+    cursor.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(cursor)?))
+}
+
+fn read_array<const N: usize>(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<[u8; N]> {
+    let mut buf = [0; N];
+    cursor.read_exact(&mut buf)?;
+    Ok(buf)
+}
@@ -0,0 +1,121 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Recovery from CPU shutdowns.
+//!
+//! A test that leaves the CPU in the Shutdown state (a triple fault, for instance) no longer
+//! aborts the run. The generator captures the server's last error, hard-resets the CPU, restores
+//! the server flags and memory strategy, and retries the test up to `shutdown_retry` times. An
+//! opcode that keeps shutting the CPU down is classified as blocked: its file is skipped and it is
+//! listed in a blocked opcode report written alongside the trace logs.
+
+use std::{fmt::Display, fs, path::Path};
+
+use anyhow::{bail, Context};
+use arduinox86_client::Capabilities;
+
+use crate::{Config, Opcode, TestContext};
+
+pub const BLOCKED_REPORT_FILENAME: &str = "blocked_opcodes.txt";
+
+/// A test that ended with the CPU in the Shutdown state.
+#[derive(Clone, Debug)]
+pub struct ShutdownError {
+    /// The server's last error at the time of the shutdown.
+    pub last_error: String,
+}
+
+impl Display for ShutdownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Shutdown executing instruction: {}", self.last_error)
+    }
+}
+
+impl std::error::Error for ShutdownError {}
+
+/// A test that shut the CPU down more times than `shutdown_retry` allows.
+#[derive(Clone, Debug)]
+pub struct OpcodeBlocked {
+    pub test_num:   usize,
+    pub shutdowns:  u32,
+    pub last_error: String,
+}
+
+impl Display for OpcodeBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "test {} shut the CPU down {} time(s), last error: {}",
+            self.test_num, self.shutdowns, self.last_error
+        )
+    }
+}
+
+impl std::error::Error for OpcodeBlocked {}
+
+/// A test file that was skipped because its opcode was blocked.
+#[derive(Clone, Debug)]
+pub struct BlockedOpcode {
+    pub file_name: String,
+    pub opcode:    Opcode,
+    pub blocked:   OpcodeBlocked,
+}
+
+impl Display for BlockedOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (opcode {}): {}", self.file_name, self.opcode, self.blocked)
+    }
+}
+
+/// Hard-reset the CPU after a shutdown, then restore the server flags and memory strategy that
+/// were in effect for the test.
+pub fn recover(context: &mut TestContext, config: &Config) -> anyhow::Result<()> {
+    let flags = context.client().get_flags()?;
+    if !context.client().reset_cpu()? {
+        bail!(
+            "Failed to reset CPU after shutdown: {}",
+            context.client().get_last_error()?
+        );
+    }
+    context.client().set_flags(flags)?;
+    let (strategy_start, strategy_end) = context.mem_strategy_range(&config.test_gen);
+    let strategy = context.memory_strategy;
+    if context.client().supports(Capabilities::MEMORY_STRATEGY) {
+        context
+            .client()
+            .set_memory_strategy(strategy, strategy_start, strategy_end)?;
+    }
+    log::debug!("Recovered from shutdown, restored server flags {:08X}", flags);
+    Ok(())
+}
+
+/// Write the blocked opcode report to `dir`, one file per line.
+pub fn write_blocked_report(dir: &Path, blocked_opcodes: &[BlockedOpcode]) -> anyhow::Result<()> {
+    let path = dir.join(BLOCKED_REPORT_FILENAME);
+    let mut report = String::new();
+    for blocked_opcode in blocked_opcodes {
+        report.push_str(&blocked_opcode.to_string());
+        report.push('\n');
+    }
+    fs::write(&path, report).with_context(|| format!("Writing blocked opcode report: {}", path.display()))
+}
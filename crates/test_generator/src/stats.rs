@@ -0,0 +1,229 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Per-file generation statistics.
+//!
+//! The generator keeps a [FileStats] for every test file it works on: how long the file took, how
+//! many runs on the hardware its tests needed, how many of those failed or hit a serial error, and
+//! how many cycles and exceptions its accepted tests had. At the end of a run they are written to
+//! the trace directory as JSON and CSV, so the opcodes that are slow or troublesome to capture can
+//! be found without reading every trace log.
+
+use std::{fmt::Write as _, fs, path::Path, time::Instant};
+
+use anyhow::{Context, Error};
+use arduinox86_client::CpuClientError;
+use moo::prelude::*;
+use serde::Serialize;
+
+pub const STATS_JSON_FILENAME: &str = "gen_stats.json";
+pub const STATS_CSV_FILENAME: &str = "gen_stats.csv";
+/// Number of files listed in the summary printed at the end of a run.
+const SUMMARY_LEN: usize = 5;
+
+/// Generation statistics for one test file.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FileStats {
+    /// The test file name, e.g. `F6.6.MOO`.
+    pub file: String,
+    /// Tests accepted into the file.
+    pub tests: usize,
+    /// Wall time spent on the file, in seconds.
+    pub seconds: f64,
+    /// Test runs on the hardware, including runs to validate a result and retries.
+    pub runs: usize,
+    /// Runs that failed and were retried.
+    pub retries: usize,
+    /// Failed runs caused by a serial read or write error, and reconnects to the server.
+    pub serial_errors: usize,
+    /// Accepted tests that ended in an exception.
+    pub exceptions: usize,
+    /// Cycles logged over every accepted test.
+    pub cycles: usize,
+    /// Whether the file was skipped because its opcode kept shutting the CPU down.
+    pub blocked: bool,
+}
+
+impl FileStats {
+    pub fn seconds_per_test(&self) -> f64 {
+        if self.tests > 0 {
+            self.seconds / self.tests as f64
+        }
+        else {
+            0.0
+        }
+    }
+
+    pub fn cycles_per_test(&self) -> f64 {
+        if self.tests > 0 {
+            self.cycles as f64 / self.tests as f64
+        }
+        else {
+            0.0
+        }
+    }
+}
+
+/// The [FileStats] of every file in a run.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct GenStats {
+    pub files:  Vec<FileStats>,
+    #[serde(skip)]
+    file_start: Option<Instant>,
+}
+
+impl GenStats {
+    /// Start keeping statistics for `file`.
+    pub fn begin_file(&mut self, file: &str) {
+        self.files.push(FileStats {
+            file: file.to_string(),
+            ..Default::default()
+        });
+        self.file_start = Some(Instant::now());
+    }
+
+    /// Stop timing the current file.
+    pub fn end_file(&mut self, blocked: bool) {
+        let elapsed = self.file_start.take().map(|start| start.elapsed()).unwrap_or_default();
+        if let Some(file) = self.files.last_mut() {
+            file.seconds = elapsed.as_secs_f64();
+            file.blocked = blocked;
+        }
+    }
+
+    /// Count a test run on the hardware.
+    pub fn add_run(&mut self, result: &Result<MooTest, Error>) {
+        let Some(file) = self.files.last_mut()
+        else {
+            return;
+        };
+        file.runs += 1;
+        if let Err(e) = result {
+            file.retries += 1;
+            if is_serial_error(e) {
+                file.serial_errors += 1;
+            }
+        }
+    }
+
+    /// Count reconnects to the server, which are serial errors even if the retried command worked.
+    pub fn add_reconnects(&mut self, count: usize) {
+        if let Some(file) = self.files.last_mut() {
+            file.serial_errors += count;
+        }
+    }
+
+    /// Count a test accepted into the current file.
+    pub fn add_test(&mut self, test: &MooTest) {
+        if let Some(file) = self.files.last_mut() {
+            file.tests += 1;
+            file.cycles += test.cycles().len();
+            if test.exception().is_some() {
+                file.exceptions += 1;
+            }
+        }
+    }
+
+    /// Add the files of another run, such as another board's share of a parallel run.
+    pub fn merge(&mut self, other: &GenStats) {
+        self.files.extend(other.files.iter().cloned());
+    }
+
+    /// Write the statistics to `dir` as JSON and CSV.
+    pub fn write(&self, dir: &Path) -> anyhow::Result<()> {
+        let path = dir.join(STATS_JSON_FILENAME);
+        let json = serde_json::to_string_pretty(self).context("Serializing generation statistics")?;
+        fs::write(&path, json).with_context(|| format!("Writing generation statistics: {}", path.display()))?;
+
+        let path = dir.join(STATS_CSV_FILENAME);
+        fs::write(&path, self.to_csv()).with_context(|| format!("Writing generation statistics: {}", path.display()))
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "file,tests,seconds,seconds_per_test,runs,retries,serial_errors,exceptions,cycles_per_test,blocked\n",
+        );
+        for f in &self.files {
+            let _ = writeln!(
+                csv,
+                "{},{},{:.3},{:.3},{},{},{},{},{:.1},{}",
+                f.file,
+                f.tests,
+                f.seconds,
+                f.seconds_per_test(),
+                f.runs,
+                f.retries,
+                f.serial_errors,
+                f.exceptions,
+                f.cycles_per_test(),
+                f.blocked
+            );
+        }
+        csv
+    }
+
+    /// Print the files that took longest per test and the files that needed the most retries.
+    pub fn print_summary(&self) {
+        let mut slowest = self.files.iter().filter(|f| f.tests > 0).collect::<Vec<_>>();
+        slowest.sort_by(|a, b| b.seconds_per_test().total_cmp(&a.seconds_per_test()));
+        if !slowest.is_empty() {
+            println!("Slowest files per test:");
+            for f in slowest.iter().take(SUMMARY_LEN) {
+                println!(
+                    "  {:<16} {:.3}s per test, {:.1} cycles per test",
+                    f.file,
+                    f.seconds_per_test(),
+                    f.cycles_per_test()
+                );
+            }
+        }
+
+        let mut retried = self.files.iter().filter(|f| f.retries > 0).collect::<Vec<_>>();
+        retried.sort_by_key(|f| std::cmp::Reverse(f.retries));
+        if !retried.is_empty() {
+            println!("Most retried files:");
+            for f in retried.iter().take(SUMMARY_LEN) {
+                println!(
+                    "  {:<16} {} retries in {} runs, {} serial errors",
+                    f.file, f.retries, f.runs, f.serial_errors
+                );
+            }
+        }
+    }
+}
+
+/// Return true if `e` was caused by a failed read or write on the serial link, including one the
+/// client reconnected after.
+fn is_serial_error(e: &Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<CpuClientError>(),
+            Some(
+                CpuClientError::ReadFailure
+                    | CpuClientError::WriteFailure
+                    | CpuClientError::ReadTimeout
+                    | CpuClientError::Reconnected(_)
+            )
+        )
+    })
+}
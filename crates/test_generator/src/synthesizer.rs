@@ -0,0 +1,108 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Random instruction synthesis as a library API.
+//!
+//! [InstructionSynthesizer] exposes the engine the generator builds test instructions with -
+//! prefix selection, modrm synthesis and immediate and displacement fixups - so other tools
+//! produce the same instructions without running the generator. Output depends only on the
+//! config, the seed and the requested opcode and test number.
+
+use std::{fs, io, path::Path};
+
+use anyhow::Context;
+use arduinox86_client::registers_common::SegmentSize;
+
+use crate::{
+    instruction::{instruction_seed, SynthContext, TestInstruction},
+    Config,
+    Opcode,
+    TestGen,
+    TestOpcodeSizePrefix,
+};
+
+pub struct InstructionSynthesizer {
+    config: TestGen,
+    seed: u64,
+    size_prefix: TestOpcodeSizePrefix,
+    code_segment_size: SegmentSize,
+}
+
+impl InstructionSynthesizer {
+    /// Create a synthesizer from a generator config. `seed` plays the role of the file seed, so
+    /// passing the seed of a generated file reproduces its instructions.
+    pub fn new(config: &Config, seed: u64) -> Self {
+        Self {
+            config: config.test_gen.clone(),
+            seed,
+            size_prefix: TestOpcodeSizePrefix::None,
+            code_segment_size: SegmentSize::Sixteen,
+        }
+    }
+
+    /// Create a synthesizer from a generator config file.
+    pub fn from_config_file(path: &Path, seed: u64) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let config: Config = toml::from_str(&text).context("parsing TOML into Config")?;
+        Ok(Self::new(&config, seed))
+    }
+
+    /// Prepend an operand or address size prefix to every instruction.
+    pub fn with_size_prefix(mut self, size_prefix: TestOpcodeSizePrefix) -> Self {
+        self.size_prefix = size_prefix;
+        self
+    }
+
+    /// Encode instructions for a code segment of the given size.
+    pub fn with_code_segment_size(mut self, code_segment_size: SegmentSize) -> Self {
+        self.code_segment_size = code_segment_size;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Synthesize instruction `test_num` for `opcode`, with the modrm reg field set to
+    /// `opcode_ext` for group opcodes. Fails for excluded opcodes and prefixes.
+    pub fn synthesize(
+        &self,
+        opcode: Opcode,
+        opcode_ext: Option<u8>,
+        test_num: usize,
+    ) -> anyhow::Result<TestInstruction> {
+        let mut trace_log = io::sink();
+        let mut context = SynthContext {
+            trace_log: &mut trace_log,
+            test_opcode_size_prefix: self.size_prefix,
+            code_segment_size: self.code_segment_size,
+        };
+        TestInstruction::synthesize(
+            &mut context,
+            &self.config,
+            opcode,
+            opcode_ext,
+            instruction_seed(self.seed, test_num, 0),
+        )
+    }
+}
@@ -0,0 +1,319 @@
+/*
+    ArduinoX86 Copyright 2022-2025 Daniel Balsom
+    https://github.com/dbalsom/arduinoX86
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+*/
+
+//! Build a timing table from a test set.
+//!
+//! Every test records the cycles it took, so a test set is also a large sample of instruction
+//! timings. This groups the tests by opcode, prefix bytes and ModR/M form and reports the spread of
+//! cycle counts for each group, along with how much each memory form costs over the cheapest
+//! memory form of the same opcode. The result is the kind of timing reference emulator authors
+//! otherwise compile by hand from datasheets and their own measurements.
+//!
+//! Cycle counts are the number of cycles captured for the test, so they include the prefetch
+//! and bus activity around the instruction. They are best compared against each other.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use iced_x86::{Decoder, DecoderOptions, OpKind};
+use moo::prelude::*;
+use serde::Serialize;
+
+use crate::moo_io;
+
+/// Output formats for the timing table.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimingFormat {
+    #[default]
+    Toml,
+    Csv,
+}
+
+/// Cycle statistics for one opcode, prefix and ModR/M form.
+#[derive(Clone, Debug, Serialize)]
+pub struct TimingEntry {
+    /// The opcode, as named by the test file, e.g. `F6.4`.
+    pub opcode: String,
+    /// The prefix bytes in front of the opcode, in hex, e.g. `26 F3`. Empty if none.
+    pub prefixes: String,
+    /// The ModR/M form: `reg`, a memory form such as `[bx+si+d8]`, or `moffs`.
+    pub form: String,
+    pub count: usize,
+    pub min: usize,
+    pub median: f64,
+    pub max: usize,
+    /// `max - min`.
+    pub spread: usize,
+    /// For memory forms, the median minus the median of the cheapest memory form of the same
+    /// opcode and prefixes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ea_delta: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct TimingTable {
+    entries: Vec<TimingEntry>,
+}
+
+/// The key a test is grouped under.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct TimingKey {
+    opcode: String,
+    prefixes: Vec<u8>,
+    form: String,
+}
+
+/// The 16-bit effective address for each r/m value.
+const EA_FORMS16: [&str; 8] = ["bx+si", "bx+di", "bp+si", "bp+di", "si", "di", "bp", "bx"];
+
+/// Return the opcode bytes named by a test file stem such as `F6.4`, `0F01` or `6681`, minus any
+/// operand or address size prefix, which is also found in the test bytes.
+pub fn stem_opcode(stem: &str) -> Option<Vec<u8>> {
+    let mut hex = stem.split('.').next()?;
+    if hex.len() > 2 && !hex.starts_with("0F") {
+        for size_prefix in ["6766", "66", "67"] {
+            if let Some(rest) = hex.strip_prefix(size_prefix) {
+                hex = rest;
+                break;
+            }
+        }
+    }
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Name the ModR/M form of an instruction, given the offset of its opcode.
+fn modrm_form(bytes: &[u8], opcode_offset: usize, opcode_len: usize, prefixes: &[u8]) -> String {
+    let opcode = bytes[opcode_offset];
+    if opcode_len == 1 && matches!(opcode, 0xA0..=0xA3) {
+        return "moffs".to_string();
+    }
+
+    let instruction = Decoder::new(16, bytes, DecoderOptions::NO_INVALID_CHECK).decode();
+    let has_memory = (0..instruction.op_count()).any(|i| instruction.op_kind(i) == OpKind::Memory);
+    // XLAT addresses memory without a ModR/M byte.
+    if !has_memory || (opcode_len == 1 && opcode == 0xD7) {
+        return "reg".to_string();
+    }
+
+    let Some(&modrm) = bytes.get(opcode_offset + opcode_len)
+    else {
+        return "reg".to_string();
+    };
+    let mod_value = modrm >> 6;
+    let rm = (modrm & 0x07) as usize;
+
+    if prefixes.contains(&0x67) {
+        // 32-bit addressing. Name the raw fields rather than decode SIB forms.
+        return format!("[mod{} rm{}]", mod_value, rm);
+    }
+    match mod_value {
+        0 if rm == 6 => "[d16]".to_string(),
+        0 => format!("[{}]", EA_FORMS16[rm]),
+        1 => format!("[{}+d8]", EA_FORMS16[rm]),
+        2 => format!("[{}+d16]", EA_FORMS16[rm]),
+        _ => "reg".to_string(),
+    }
+}
+
+/// Return the grouping key for a test from `opcode`, or None if the opcode isn't in its bytes.
+fn timing_key(file_opcode: &str, opcode: &[u8], bytes: &[u8]) -> Option<TimingKey> {
+    let opcode_offset = bytes.iter().position(|&b| b == opcode[0])?;
+    let prefixes = bytes[..opcode_offset].to_vec();
+    let form = modrm_form(bytes, opcode_offset, opcode.len(), &prefixes);
+    Some(TimingKey {
+        opcode: file_opcode.to_string(),
+        prefixes,
+        form,
+    })
+}
+
+fn median(sorted: &[usize]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) as f64 / 2.0
+    }
+    else {
+        sorted[mid] as f64
+    }
+}
+
+/// Collect the MOO files named by `inputs`, expanding directories.
+pub fn moo_files(inputs: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut dir_files = Vec::new();
+            for entry in std::fs::read_dir(input).with_context(|| format!("Reading {}", input.display()))? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("moo")) {
+                    dir_files.push(path);
+                }
+            }
+            dir_files.sort();
+            files.extend(dir_files);
+        }
+        else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// Build the timing table for the tests in `files`.
+fn build_table(files: &[PathBuf]) -> anyhow::Result<Vec<TimingEntry>> {
+    let mut groups: BTreeMap<TimingKey, Vec<usize>> = BTreeMap::new();
+
+    for path in files {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let Some(opcode) = stem_opcode(&stem)
+        else {
+            log::warn!("Can't get an opcode from file name {}, skipping", path.display());
+            continue;
+        };
+
+        let test_file = moo_io::read_test_file(path)?;
+        let mut skipped = 0;
+        for test in test_file.tests() {
+            match timing_key(&stem, &opcode, test.bytes()) {
+                Some(key) => groups.entry(key).or_default().push(test.cycles().len()),
+                None => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            log::warn!(
+                "{}: skipped {} test(s) without opcode {}",
+                path.display(),
+                skipped,
+                stem
+            );
+        }
+    }
+
+    let mut entries: Vec<TimingEntry> = groups
+        .into_iter()
+        .map(|(key, mut cycles)| {
+            cycles.sort_unstable();
+            let min = cycles[0];
+            let max = cycles[cycles.len() - 1];
+            TimingEntry {
+                opcode: key.opcode,
+                prefixes: key
+                    .prefixes
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                form: key.form,
+                count: cycles.len(),
+                min,
+                median: median(&cycles),
+                max,
+                spread: max - min,
+                ea_delta: None,
+            }
+        })
+        .collect();
+
+    // Entries are sorted by opcode and prefixes, so each run of them shares a cheapest memory form.
+    let mut start = 0;
+    while start < entries.len() {
+        let end = start
+            + entries[start..]
+                .iter()
+                .take_while(|e| e.opcode == entries[start].opcode && e.prefixes == entries[start].prefixes)
+                .count();
+        let is_memory = |e: &TimingEntry| e.form.starts_with('[');
+        let cheapest = entries[start..end]
+            .iter()
+            .filter(|e| is_memory(e))
+            .map(|e| e.median)
+            .fold(f64::INFINITY, f64::min);
+        for entry in entries[start..end].iter_mut().filter(|e| is_memory(e)) {
+            entry.ea_delta = Some(entry.median - cheapest);
+        }
+        start = end;
+    }
+
+    Ok(entries)
+}
+
+fn to_csv(entries: &[TimingEntry]) -> String {
+    let mut csv = String::from("opcode,prefixes,form,count,min,median,max,spread,ea_delta\n");
+    for e in entries {
+        let ea_delta = e.ea_delta.map(|d| d.to_string()).unwrap_or_default();
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{}",
+            e.opcode, e.prefixes, e.form, e.count, e.min, e.median, e.max, e.spread, ea_delta
+        );
+    }
+    csv
+}
+
+fn print_table(entries: &[TimingEntry]) {
+    println!(
+        "{:<8} {:<12} {:<14} {:>6} {:>5} {:>7} {:>5} {:>6} {:>8}",
+        "Opcode", "Prefixes", "Form", "Tests", "Min", "Median", "Max", "Spread", "EA delta"
+    );
+    for e in entries {
+        let ea_delta = e.ea_delta.map(|d| format!("{:+.1}", d)).unwrap_or_default();
+        println!(
+            "{:<8} {:<12} {:<14} {:>6} {:>5} {:>7.1} {:>5} {:>6} {:>8}",
+            e.opcode, e.prefixes, e.form, e.count, e.min, e.median, e.max, e.spread, ea_delta
+        );
+    }
+}
+
+/// Aggregate the cycle counts of the tests in `inputs` into a timing table, print it, and write it
+/// to `output` if given.
+pub fn timings(inputs: &[PathBuf], output: Option<&Path>, format: TimingFormat) -> anyhow::Result<()> {
+    let files = moo_files(inputs)?;
+    if files.is_empty() {
+        bail!("No MOO files found.");
+    }
+
+    let entries = build_table(&files)?;
+    print_table(&entries);
+
+    if let Some(output) = output {
+        let text = match format {
+            TimingFormat::Toml => {
+                toml::to_string_pretty(&TimingTable { entries }).context("Serializing timing table")?
+            }
+            TimingFormat::Csv => to_csv(&entries),
+        };
+        std::fs::write(output, text).with_context(|| format!("Writing {}", output.display()))?;
+        println!("Timing table written to {}", output.display());
+    }
+    Ok(())
+}
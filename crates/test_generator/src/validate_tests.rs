@@ -25,8 +25,9 @@ use std::{ffi::OsString, io::BufWriter};
 
 use crate::{
     gen_regs::TestRegisters,
-    gen_tests::{compare_registers, generate_test, get_group_extension_range, write_initial_mem},
+    gen_tests::{generate_test, get_group_extension_range, write_initial_mem},
     instruction::TestInstruction,
+    moo_io,
     trace_error,
     trace_log,
     AddressSize,
@@ -36,7 +37,7 @@ use crate::{
     TestContext,
 };
 use anyhow::{bail, Context};
-use arduinox86_client::ServerFlags;
+use arduinox86_client::{ComparisonPolicy, ComparisonPreset, MooTestComparison, ServerFlags};
 use moo::{prelude::MooTestFile, types::MooCpuType};
 
 pub fn validate_tests(context: &mut TestContext, config: &Config) -> anyhow::Result<()> {
@@ -57,14 +58,19 @@ pub fn validate_tests(context: &mut TestContext, config: &Config) -> anyhow::Res
         bail!("Invalid opcode range specified.");
     }
 
+    let policy = config
+        .test_exec
+        .validate_policy
+        .unwrap_or(ComparisonPreset::RegistersOnly);
+    println!("Comparing with the {} policy", policy.name());
+
     // Tell ArduinoX86 to execute instructions automatically.
-    context.client.set_flags(ServerFlags::EXECUTE_AUTOMATIC)?;
+    context.client().set_flags(ServerFlags::EXECUTE_AUTOMATIC)?;
     // Set default serial debug state.
-    context.client.enable_debug(config.test_exec.serial_debug_default)?;
+    context.client().enable_debug(config.test_exec.serial_debug_default)?;
 
     let mut last_opcode = opcode_range_start;
     for opcode_raw in opcode_range_start..=opcode_range_end {
-        let opcode_u8 = opcode_raw as u8;
         let opcode: Opcode = opcode_raw.into();
 
         let mut op_ext_start = 0;
@@ -80,13 +86,16 @@ pub fn validate_tests(context: &mut TestContext, config: &Config) -> anyhow::Res
             continue;
         }
 
-        if !opcode.is_extended() && config.test_gen.prefixes.contains(&opcode_u8) {
+        if opcode.is_prefix(&config.test_gen.prefixes) {
             log::debug!("Skipping prefix: {}", opcode);
             continue;
         }
 
         for opcode_ext in op_ext_start..=op_ext_end {
             last_opcode = opcode_raw;
+            let undefined_flags = config
+                .metadata
+                .undefined_flags(opcode, have_group_ext.then_some(opcode_ext));
 
             let mut op_ext_str = "".to_string();
             if have_group_ext {
@@ -129,10 +138,11 @@ pub fn validate_tests(context: &mut TestContext, config: &Config) -> anyhow::Res
 
             // Open `file_path` for reading as a BufReader.
             match std::fs::File::open(&file_path) {
-                Ok(file) => {
+                Ok(mut file) => {
                     log::debug!("Appending to existing test file: {}", file_path.to_string_lossy());
-                    let mut file_reader = std::io::BufReader::new(file);
-                    test_file = MooTestFile::read(&mut file_reader)?;
+                    let mut bytes = Vec::new();
+                    std::io::Read::read_to_end(&mut file, &mut bytes)?;
+                    test_file = moo_io::parse_test_file(&bytes)?;
 
                     if test_file.metadata().is_none() {
                         return Err(anyhow::anyhow!(
@@ -181,10 +191,10 @@ pub fn validate_tests(context: &mut TestContext, config: &Config) -> anyhow::Res
 
                 // Set flow control end condition
                 if config.test_gen.flow_control_opcodes.contains(&opcode.into()) {
-                    let flags = context.client.get_flags()?;
+                    let flags = context.client().get_flags()?;
                     if flags & ServerFlags::HALT_AFTER_JUMP == 0 {
                         // Enable halt after jump if not already set.
-                        context.client.set_flags(flags | ServerFlags::HALT_AFTER_JUMP)?;
+                        context.client().set_flags(flags | ServerFlags::HALT_AFTER_JUMP)?;
                         log::debug!("Enabled HALT_AFTER_JUMP for opcode {}", opcode);
                     }
                 }
@@ -258,22 +268,32 @@ pub fn validate_tests(context: &mut TestContext, config: &Config) -> anyhow::Res
 
                 if let Ok(test) = test_result {
                     // Check if the test matches the saved test.
-                    if test.final_regs() != tests[test_num].final_regs() {
-                        trace_error!(
-                            context,
-                            "Register mismatch for opcode {} at test number {}!",
-                            opcode,
-                            test_num,
-                        );
-                        compare_registers(&test.final_regs(), tests[test_num].final_regs());
+                    let comparison = MooTestComparison::new(&policy, &tests[test_num], &test, undefined_flags);
+                    if !comparison.is_match() {
+                        trace_error!(context, "Mismatch for opcode {} at test number {}!", opcode, test_num);
+                        if comparison.register_width {
+                            println!("Register sets differ in width.");
+                        }
+                        for mismatch in &comparison.registers {
+                            println!(
+                                "{} mismatch: {:04X} != {:04X}",
+                                mismatch.register, mismatch.expected, mismatch.actual
+                            );
+                        }
+                        for (address, expected, actual) in &comparison.ram {
+                            println!("RAM [{:08X}] mismatch: {:02X?} != {:02X?}", address, expected, actual);
+                        }
+                        if let Some((expected, actual)) = comparison.cycles {
+                            println!("Cycle count mismatch: {} != {}", expected, actual);
+                        }
                         return Err(anyhow::anyhow!(
-                            "Register mismatch for opcode {} at test number {}",
+                            "Mismatch for opcode {} at test number {}",
                             opcode,
                             test_num
                         ));
                     }
                     else {
-                        trace_log!(context, "{}:{:05X} registers validated.", opcode, test_num);
+                        trace_log!(context, "{}:{:05X} validated.", opcode, test_num);
                     }
                 }
                 else {
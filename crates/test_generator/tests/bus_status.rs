@@ -0,0 +1,23 @@
+use test_generator::{BusOpType, BusStatusByte};
+
+#[test]
+fn test_v1_bus_status() {
+    // S2-S0 on the 8086-class CPUs.
+    let decode = |status: u8| BusOpType::try_from(BusStatusByte::V1(status));
+    assert_eq!(decode(0b000), Err(())); // INTA
+    assert_eq!(decode(0b001), Ok(BusOpType::IoRead));
+    assert_eq!(decode(0b010), Ok(BusOpType::IoWrite));
+    assert_eq!(decode(0b011), Err(())); // Halt
+    assert_eq!(decode(0b100), Ok(BusOpType::CodeRead));
+    assert_eq!(decode(0b101), Ok(BusOpType::MemRead));
+    assert_eq!(decode(0b110), Ok(BusOpType::MemWrite));
+    assert_eq!(decode(0b111), Err(())); // Passive
+}
+
+#[test]
+fn test_v1_bus_status_ignores_upper_bits() {
+    assert_eq!(
+        BusOpType::try_from(BusStatusByte::V1(0xF8 | 0b100)),
+        Ok(BusOpType::CodeRead)
+    );
+}
@@ -0,0 +1,50 @@
+use arduinox86_client::CpuWidth;
+use test_generator::{check_bus_ops, BusOp, BusOpType, CycleStreamError};
+
+fn fetch(addr: u32, data: u16) -> BusOp {
+    BusOp {
+        idx: 0,
+        op_type: BusOpType::CodeRead,
+        addr,
+        bhe: true,
+        data,
+        flags: 0,
+    }
+}
+
+/// MOV AX, 1234h at 1000:FFFE, which runs past the end of the segment into 1000:0000.
+const CS_BASE: u32 = 0x10000;
+const CODE: [u8; 3] = [0xB8, 0x34, 0x12];
+
+#[test]
+fn test_fetch_wraps_segment() {
+    let ops = [fetch(0x1FFFE, 0x34B8), fetch(0x10000, 0x9012)];
+    assert_eq!(
+        check_bus_ops(&ops, CpuWidth::Sixteen, CS_BASE, 0xFFFE, &CODE, true),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_code_checked_past_wrap() {
+    // The third byte lives at the segment base, not past the end of the segment.
+    let ops = [fetch(0x1FFFE, 0x34B8), fetch(0x10000, 0x9099)];
+    assert_eq!(
+        check_bus_ops(&ops, CpuWidth::Sixteen, CS_BASE, 0xFFFE, &CODE, true),
+        Err(CycleStreamError::CodeMismatch {
+            bus_op: 1,
+            addr: 0x10000,
+            expected: 0x12,
+            found: 0x99,
+        })
+    );
+}
+
+#[test]
+fn test_fetch_out_of_order() {
+    let ops = [fetch(0x10100, 0x34B8), fetch(0x10000, 0x9090)];
+    assert!(matches!(
+        check_bus_ops(&ops, CpuWidth::Sixteen, CS_BASE, 0x0100, &CODE, true),
+        Err(CycleStreamError::FetchOutOfOrder { bus_op: 1, .. })
+    ));
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use test_generator::{
+    compress_cycle_chunks,
+    expand_cycle_chunks,
+    read_mini_traces,
+    read_test_tags,
+    split_store_dumps,
+    MiniEvent,
+    MiniEventKind,
+    MiniTrace,
+    StoreDump,
+    TestTags,
+};
+
+const RECORD_LEN: usize = 15;
+
+fn chunk(tag: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = tag.to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+/// Build a MOO file image with one test, whose CYCL chunk holds `cycles` records.
+fn moo_image(cycles: &[[u8; RECORD_LEN]]) -> Vec<u8> {
+    let mut cycl = (cycles.len() as u32).to_le_bytes().to_vec();
+    for record in cycles {
+        cycl.extend_from_slice(record);
+    }
+
+    let mut test = 0u32.to_le_bytes().to_vec();
+    test.extend(chunk(b"NAME", b"nop"));
+    test.extend(chunk(b"CYCL", &cycl));
+
+    let mut image = chunk(b"MOO ", &[1, 0, 0, 0, 1, 0, 0, 0]);
+    image.extend(chunk(b"TEST", &test));
+    image
+}
+
+fn idle_cycles(count: usize) -> Vec<[u8; RECORD_LEN]> {
+    let mut cycles = vec![[0u8; RECORD_LEN]; count];
+    for (i, cycle) in cycles.iter_mut().enumerate() {
+        // A run of identical idle cycles with a fetch in the middle.
+        if i == count / 2 {
+            cycle[0] = 0x01;
+            cycle[1] = 0xF0;
+        }
+    }
+    cycles
+}
+
+#[test]
+fn test_cycle_chunks_round_trip() {
+    let image = moo_image(&idle_cycles(40));
+
+    let compressed = compress_cycle_chunks(&image).unwrap();
+    assert!(compressed.len() < image.len());
+    assert!(compressed.windows(4).any(|w| w == b"CYCR"));
+    assert!(!compressed.windows(4).any(|w| w == b"CYCL"));
+
+    assert_eq!(expand_cycle_chunks(&compressed).unwrap(), image);
+}
+
+#[test]
+fn test_cycle_chunks_left_when_not_smaller() {
+    let mut cycles = idle_cycles(4);
+    for (i, cycle) in cycles.iter_mut().enumerate() {
+        cycle[2] = i as u8;
+    }
+    let image = moo_image(&cycles);
+    assert_eq!(compress_cycle_chunks(&image).unwrap(), image);
+}
+
+#[test]
+fn test_cycle_chunks_bad_count() {
+    let compressed = compress_cycle_chunks(&moo_image(&idle_cycles(40))).unwrap();
+    let count_pos = compressed.windows(4).position(|w| w == b"CYCR").unwrap() + 8 + 3;
+
+    // A count the runs don't add up to is rejected rather than allocated for.
+    let mut bad = compressed.clone();
+    bad[count_pos..count_pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(expand_cycle_chunks(&bad).is_err());
+
+    let mut bad = compressed;
+    bad[count_pos..count_pos + 4].copy_from_slice(&39u32.to_le_bytes());
+    assert!(expand_cycle_chunks(&bad).is_err());
+}
+
+#[test]
+fn test_store_dumps_round_trip() {
+    let image = moo_image(&idle_cycles(4));
+    let dumps = vec![
+        StoreDump {
+            test: 0,
+            reg_type: 2,
+            data: (0..102).collect(),
+        },
+        StoreDump {
+            test: 1,
+            reg_type: 3,
+            data: vec![],
+        },
+    ];
+
+    let mut with_dumps = image.clone();
+    for dump in &dumps {
+        dump.write_chunk(&mut with_dumps);
+    }
+    TestTags {
+        test: 0,
+        tags: vec!["smc".to_string()],
+    }
+    .write_chunk(&mut with_dumps);
+
+    let (stripped, read_dumps) = split_store_dumps(&with_dumps);
+    assert_eq!(stripped, image);
+    assert_eq!(read_dumps, dumps);
+}
+
+#[test]
+fn test_mini_traces_round_trip() {
+    let traces = vec![
+        MiniTrace {
+            test:   0,
+            events: vec![
+                MiniEvent {
+                    kind:    MiniEventKind::CodeRead,
+                    cycle:   1,
+                    address: 0xFFFF0,
+                    data:    0x90EA,
+                },
+                MiniEvent {
+                    kind:    MiniEventKind::Flush,
+                    cycle:   9,
+                    address: 0x00400,
+                    data:    0,
+                },
+            ],
+        },
+        MiniTrace {
+            test:   1,
+            events: vec![],
+        },
+    ];
+
+    let mut image = moo_image(&idle_cycles(4));
+    for trace in &traces {
+        trace.write_chunk(&mut image);
+    }
+    assert_eq!(read_mini_traces(&image), traces);
+
+    // Mini traces are dropped along with the store dumps.
+    let (stripped, _) = split_store_dumps(&image);
+    assert_eq!(stripped, moo_image(&idle_cycles(4)));
+}
+
+#[test]
+fn test_tags_round_trip() {
+    let mut tag_map = BTreeMap::new();
+    tag_map.insert("golden".to_string(), vec![2]);
+    tag_map.insert("smc".to_string(), vec![0, 2]);
+
+    let tags = TestTags::from_tag_map(&tag_map);
+    assert_eq!(
+        tags,
+        vec![
+            TestTags {
+                test: 0,
+                tags: vec!["smc".to_string()],
+            },
+            TestTags {
+                test: 2,
+                tags: vec!["golden".to_string(), "smc".to_string()],
+            },
+        ]
+    );
+
+    let mut image = Vec::new();
+    for test_tags in &tags {
+        test_tags.write_chunk(&mut image);
+    }
+    assert_eq!(read_test_tags(&image), tags);
+}
+
+#[test]
+fn test_tags_truncated_chunk() {
+    let mut image = Vec::new();
+    TestTags {
+        test: 1,
+        tags: vec!["smc".to_string()],
+    }
+    .write_chunk(&mut image);
+    image.truncate(image.len() - 1);
+    assert!(read_test_tags(&image).is_empty());
+}
@@ -71,6 +71,18 @@ public:
   static constexpr uint32_t FLAG_LOG_CYCLES         = 0x00000080; // Enable cycle logging
   static constexpr uint32_t FLAG_ALE_INTERRUPT      = 0x00000100; // Enable ALE interrupt to deassert READY at T1
   static constexpr uint32_t FLAG_RESOLVE_BUS_STEP   = 0x00000200; // Resolve the data bus when single-stepping (Execute state)
+  static constexpr uint32_t FLAG_ENCODE_CYCLE_LOG   = 0x00000400; // Send cycle logs in the encoded format
+
+  // Capability bits reported by CmdGetCapabilities, one per optional feature.
+  static constexpr uint32_t CAP_CYCLE_LOG           = 0x00000001; // CmdGetCycleStates, CmdClearCycleLog
+  static constexpr uint32_t CAP_MEMORY_STRATEGY     = 0x00000002; // CmdSetMemoryStrategy
+  static constexpr uint32_t CAP_INTERRUPT_TRIGGER   = 0x00000004; // CmdSetInterruptTrigger
+  static constexpr uint32_t CAP_PROGRAM_BOUNDS      = 0x00000008; // CmdSetProgramBounds
+  static constexpr uint32_t CAP_STATE_SEQUENCE      = 0x00000010; // State sequence number in CmdServerStatus
+  static constexpr uint32_t CAP_STATE_BUDGET        = 0x00000020; // CmdGetStateBudget
+  static constexpr uint32_t CAP_MEMORY_RANGES       = 0x00000040; // CmdSetMemoryRanges
+  static constexpr uint32_t CAP_END_CONDITION       = 0x00000080; // CmdSetEndCondition
+  static constexpr uint32_t CAP_CYCLE_LOG_ENCODING  = 0x00000100; // FLAG_ENCODE_CYCLE_LOG
 
   enum class ServerCommand {
     CmdNone            = 0x00,
@@ -114,6 +126,11 @@ public:
     CmdServerStatus    = 0x26,
     CmdClearCycleLog   = 0x27,
     CmdSetProgramBounds = 0x28,
+    CmdSetInterruptTrigger = 0x29,
+    CmdGetStateBudget  = 0x2A,
+    CmdSetMemoryRanges = 0x2B,
+    CmdSetEndCondition = 0x2C,
+    CmdGetCapabilities = 0x2D,
     CmdInvalid
   };
 
@@ -154,7 +171,7 @@ private:
     static_cast<std::size_t>(ServerCommand::CmdInvalid);
   std::array<CmdFn, CMD_COUNT> commands_;
 
-  static constexpr uint8_t VERSION_NUM = 3;
+  static constexpr uint8_t VERSION_NUM = 9;
   static constexpr uint8_t RESPONSE_FAIL = 0x00;
   static constexpr uint8_t RESPONSE_OK = 0x01;
   static constexpr size_t MAX_COMMAND_BYTES = 255; // Maximum number of bytes for fixed-length parameters.
@@ -163,6 +180,11 @@ private:
 
   BoardController<BoardType,HatType>& controller_;
   ServerState state_ = ServerState::Reset;
+  uint32_t stateSeq_ = 0; // Number of state transitions since boot.
+  static constexpr std::size_t STATE_COUNT = static_cast<std::size_t>(ServerState::Error) + 1;
+  uint32_t stateCycles_[STATE_COUNT] = {0}; // Cycles spent in each state since the last reset.
+  uint32_t stateMicros_[STATE_COUNT] = {0}; // Microseconds spent in each state since the last reset.
+  uint64_t stateBeginCycle_ = 0;
   CommandState commandState_ = CommandState::WaitingForCommand;
   uint8_t commandByte_ = 0;
   ServerCommand cmd_ = ServerCommand::CmdNone;
@@ -273,5 +295,13 @@ private:
   bool cmd_server_status(void);
   bool cmd_clear_cycle_log(void);
   bool cmd_set_program_bounds(void);
+  bool cmd_set_interrupt_trigger(void);
+  bool cmd_get_state_budget(void);
+  bool cmd_set_memory_ranges(void);
+  bool cmd_set_end_condition(void);
+  bool cmd_get_capabilities(void);
   bool cmd_null(void);
+
+  bool read_stream(uint8_t *buf, size_t len, const char *caller);
+  bool read_memory_stream(uint32_t address, uint32_t size, const char *caller);
 };
\ No newline at end of file
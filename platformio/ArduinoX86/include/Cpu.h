@@ -28,6 +28,28 @@
 #include <programs.h>
 #include <registers.h>
 
+// Interrupt line to raise for an interrupt trigger.
+enum class IrqLine : uint8_t {
+  None = 0,
+  Intr = 1,
+  Nmi = 2,
+};
+
+// Whether an interrupt trigger offset counts instructions or cycles from the start of Execute.
+enum class IrqTriggerMode : uint8_t {
+  Instruction = 0,
+  Cycle = 1,
+};
+
+// How automatic execution ends besides running off the end of the program.
+enum class EndKind : uint8_t {
+  None = 0,
+  Iret = 1,      // IRET from the handler for a given interrupt vector.
+  PortWrite = 2, // Write to a given I/O port.
+  Fetch = 3,     // Code fetch from a given address.
+  Cycles = 4,    // A given number of cycles since Execute began.
+};
+
 // This class is slowly being converted from a C structure. Pardon the mess.
 
 // Main CPU State
@@ -133,10 +155,42 @@ public:
     return (address >= program_start_) && (address < program_end_);
   }
 
+  void set_irq_trigger(IrqLine line, IrqTriggerMode mode, uint8_t vector, uint32_t offset) {
+    irq_line_ = line;
+    irq_mode_ = mode;
+    irq_vector_ = vector;
+    irq_offset_ = offset;
+  }
+
+  IrqLine irq_line() const { return irq_line_; }
+  IrqTriggerMode irq_mode() const { return irq_mode_; }
+  uint8_t irq_vector() const { return irq_vector_; }
+  uint32_t irq_offset() const { return irq_offset_; }
+
+  void set_end_condition(EndKind kind, uint32_t value) {
+    end_kind_ = kind;
+    end_value_ = value;
+  }
+
+  EndKind end_kind() const { return end_kind_; }
+  uint32_t end_value() const { return end_value_; }
+
+  bool irq_fired; // Whether the interrupt trigger has raised its line this Execute.
+  bool irq_nmi_pending; // Injected NMI is raised and its handler fetch should not end execution.
+  uint32_t irq_instr_ct; // Number of instructions started since Execute began.
+  bool end_reached; // The end condition was met; HALT is injected at the next code fetch.
+  uint32_t end_return_address; // Return address of the handler watched by an Iret end condition, or 0.
+
 private:
 
   uint32_t program_start_ = 0;
   uint32_t program_end_ = 0;
+  IrqLine irq_line_ = IrqLine::None; // Interrupt trigger, persists across reset until replaced.
+  IrqTriggerMode irq_mode_ = IrqTriggerMode::Instruction;
+  uint8_t irq_vector_ = 0;
+  uint32_t irq_offset_ = 0;
+  EndKind end_kind_ = EndKind::None; // End condition, persists across reset until replaced.
+  uint32_t end_value_ = 0;
   bool use_smm_ = false; // Use SMM for register readout on 386/486 CPUs
   bool is_shutdown_ = false; // Whether the CPU is in a shutdown state.
   uint64_t cycle_ct_ = 0; // Number of cycles executed since reset.
@@ -3,6 +3,7 @@
 
 #include <cstdlib>
 #include <cstddef>
+#include <cstring>
 #include <SDRAM.h>
 
 #include <serial_config.h>
@@ -23,6 +24,20 @@ struct __attribute__((packed)) CycleState {
     static constexpr uint16_t LOCK = 0x0008; // Lock line
 };
 
+// Encoded cycle log format. Each entry is a mask of the fields that changed since the previous
+// entry, followed by those fields in order. A mask of 0 is followed by a repeat count (1-255) of
+// the previous entry. The first entry is encoded against an all-zero entry.
+#define CYCLE_FIELD_ADDRESS       0x01
+#define CYCLE_FIELD_DATA          0x02
+#define CYCLE_FIELD_STATE         0x04
+#define CYCLE_FIELD_STATUS        0x08
+#define CYCLE_FIELD_CONTROL       0x10
+#define CYCLE_FIELD_COMMAND       0x20
+#define CYCLE_FIELD_PINS          0x40
+#define CYCLE_FIELD_ADDRESS_DELTA 0x80 // Address is the previous address plus a signed byte.
+// Set in the cycle count sent ahead of an encoded log.
+#define CYCLE_LOG_ENCODED_BIT 0x80000000UL
+
 // Maximum number of CycleState entries to hold
 #if defined(ARDUINO_GIGA)
 #define MAX_CYCLE_STATES 8192
@@ -83,7 +98,11 @@ public:
     return wrapped_ ? MAX_CYCLE_STATES : next_;
   }
 
-  void dump_states() {
+  void dump_states(bool encode = false) {
+    if (encode) {
+      dump_encoded_states();
+      return;
+    }
     // Dump the current log buffer as raw bytes.
     uint32_t count = len();
 #if DEBUG_DUMP    
@@ -106,7 +125,112 @@ public:
     INBAND_SERIAL.write(reinterpret_cast<const uint8_t*>(buffer_), len() * sizeof(CycleState));
   }
 
+  // Dump the log in the encoded format. The log is encoded twice: once to count the bytes for
+  // the header, and once to send them.
+  void dump_encoded_states() {
+    uint32_t count = len() | CYCLE_LOG_ENCODED_BIT;
+    uint32_t size = encode_states(false);
+#if DEBUG_DUMP
+    DEBUG_SERIAL.print("## CycleStateLogger: Dumping ");
+    DEBUG_SERIAL.print(len());
+    DEBUG_SERIAL.print(" cycles, ");
+    DEBUG_SERIAL.print(size);
+    DEBUG_SERIAL.println(" bytes encoded.");
+#endif
+    INBAND_SERIAL.write(reinterpret_cast<uint8_t*>(&count), sizeof(count));
+    INBAND_SERIAL.write(reinterpret_cast<uint8_t*>(&size), sizeof(size));
+    encode_states(true);
+  }
+
 private:
+  // Encode the log, writing it to the serial port if `send` is set. Returns the encoded size.
+  uint32_t encode_states(bool send) {
+    static constexpr size_t CHUNK_LEN = 64;
+    uint8_t chunk[CHUNK_LEN];
+    size_t chunk_len = 0;
+    uint32_t total = 0;
+    CycleState prev;
+    std::memset(&prev, 0, sizeof(prev));
+
+    size_t count = len();
+    size_t i = 0;
+    while (i < count) {
+      // An entry is at most 13 bytes; flush the chunk if the next one might not fit.
+      if (send && (chunk_len > CHUNK_LEN - 13)) {
+        INBAND_SERIAL.write(chunk, chunk_len);
+        chunk_len = 0;
+      }
+      uint8_t entry[13];
+      size_t n = 0;
+
+      if (std::memcmp(&buffer_[i], &prev, sizeof(CycleState)) == 0) {
+        size_t run = 1;
+        while ((i + run < count) && (run < 255) && (std::memcmp(&buffer_[i + run], &prev, sizeof(CycleState)) == 0)) {
+          run++;
+        }
+        entry[n++] = 0;
+        entry[n++] = static_cast<uint8_t>(run);
+        i += run;
+      }
+      else {
+        const CycleState &s = buffer_[i];
+        uint8_t mask = 0;
+        n = 1;
+        if (s.address_bus != prev.address_bus) {
+          int32_t delta = static_cast<int32_t>(s.address_bus - prev.address_bus);
+          if ((delta >= -128) && (delta <= 127)) {
+            mask |= CYCLE_FIELD_ADDRESS_DELTA;
+            entry[n++] = static_cast<uint8_t>(static_cast<int8_t>(delta));
+          }
+          else {
+            mask |= CYCLE_FIELD_ADDRESS;
+            std::memcpy(&entry[n], &s.address_bus, sizeof(s.address_bus));
+            n += sizeof(s.address_bus);
+          }
+        }
+        if (s.data_bus != prev.data_bus) {
+          mask |= CYCLE_FIELD_DATA;
+          std::memcpy(&entry[n], &s.data_bus, sizeof(s.data_bus));
+          n += sizeof(s.data_bus);
+        }
+        if (s.cpu_state != prev.cpu_state) {
+          mask |= CYCLE_FIELD_STATE;
+          entry[n++] = s.cpu_state;
+        }
+        if (s.cpu_status0 != prev.cpu_status0) {
+          mask |= CYCLE_FIELD_STATUS;
+          entry[n++] = s.cpu_status0;
+        }
+        if (s.bus_control_bits != prev.bus_control_bits) {
+          mask |= CYCLE_FIELD_CONTROL;
+          entry[n++] = s.bus_control_bits;
+        }
+        if (s.bus_command_bits != prev.bus_command_bits) {
+          mask |= CYCLE_FIELD_COMMAND;
+          entry[n++] = s.bus_command_bits;
+        }
+        if (s.pins != prev.pins) {
+          mask |= CYCLE_FIELD_PINS;
+          std::memcpy(&entry[n], &s.pins, sizeof(s.pins));
+          n += sizeof(s.pins);
+        }
+        entry[0] = mask;
+        prev = s;
+        i++;
+      }
+
+      if (send) {
+        std::memcpy(&chunk[chunk_len], entry, n);
+        chunk_len += n;
+      }
+      total += n;
+    }
+    if (send && (chunk_len > 0)) {
+      INBAND_SERIAL.write(chunk, chunk_len);
+    }
+    return total;
+  }
+
   CycleState* buffer_;
   size_t      next_;
   bool        wrapped_;
@@ -191,6 +191,9 @@ void handle_smm_load_386();
 void handle_smm_store_386();
 void handle_store_state();
 void handle_execute_state();
+void handle_irq_trigger();
+void handle_end_condition(bool cpu_iowc);
+void inject_end_halt(uint32_t address);
 void handle_execute_automatic();
 void handle_execute_finalize_state();
 void detect_fpu_type();
@@ -221,6 +221,11 @@ const char* CommandServer<BoardType, ShieldType>::get_command_name(ServerCommand
       case ServerCommand::CmdServerStatus: return "CmdServerStatus";
       case ServerCommand::CmdClearCycleLog: return "CmdClearCycleLog";
       case ServerCommand::CmdSetProgramBounds: return "CmdSetProgramBounds";
+      case ServerCommand::CmdSetInterruptTrigger: return "CmdSetInterruptTrigger";
+      case ServerCommand::CmdGetStateBudget: return "CmdGetStateBudget";
+      case ServerCommand::CmdSetMemoryRanges: return "CmdSetMemoryRanges";
+      case ServerCommand::CmdSetEndCondition: return "CmdSetEndCondition";
+      case ServerCommand::CmdGetCapabilities: return "CmdGetCapabilities";
       case ServerCommand::CmdInvalid: return "CmdInvalid";
       default: return "Unknown";
   }
@@ -370,6 +375,16 @@ bool CommandServer<BoardType, ShieldType>::dispatch_command(ServerCommand cmd) {
         return cmd_clear_cycle_log();
     case ServerCommand::CmdSetProgramBounds:
         return cmd_set_program_bounds();        
+    case ServerCommand::CmdSetInterruptTrigger:
+        return cmd_set_interrupt_trigger();
+    case ServerCommand::CmdGetStateBudget:
+        return cmd_get_state_budget();
+    case ServerCommand::CmdSetMemoryRanges:
+        return cmd_set_memory_ranges();
+    case ServerCommand::CmdSetEndCondition:
+        return cmd_set_end_condition();
+    case ServerCommand::CmdGetCapabilities:
+        return cmd_get_capabilities();
     case ServerCommand::CmdInvalid:
     default:
         return cmd_invalid();
@@ -445,6 +460,11 @@ uint8_t CommandServer<BoardType, ShieldType>::get_command_input_bytes(ServerComm
         case ServerCommand::CmdServerStatus: return 0;
         case ServerCommand::CmdClearCycleLog: return 0; // No parameters needed to clear cycle log
         case ServerCommand::CmdSetProgramBounds: return 8; // Parameters: start_addr (4 bytes), end_addr (4 bytes).
+        case ServerCommand::CmdSetInterruptTrigger: return 7; // Parameters: line, mode, vector (1 byte each), offset (4 bytes).
+        case ServerCommand::CmdGetStateBudget: return 0;
+        case ServerCommand::CmdSetMemoryRanges: return 2; // Parameter: number of ranges (2 bytes). The ranges follow in the stream.
+        case ServerCommand::CmdSetEndCondition: return 5; // Parameters: kind (1 byte), value (4 bytes).
+        case ServerCommand::CmdGetCapabilities: return 0;
         case ServerCommand::CmdInvalid: return 0;
         default: return 0;
     }
@@ -547,6 +567,11 @@ void CommandServer<BoardType, ShieldType>::change_state(ServerState new_state) {
       CPU.execute_cycle_ct = 0;
       CPU.smi_checkpoint = 0;
       CPU.nmi_checkpoint = 0;
+      CPU.irq_fired = false;
+      CPU.irq_nmi_pending = false;
+      CPU.irq_instr_ct = 0;
+      CPU.end_reached = false;
+      CPU.end_return_address = 0;
       CPU.program->reset();
       if (CPU.do_emulation) {
         // Set v_pc to 4 to skip IVT segment:offset
@@ -638,8 +663,25 @@ void CommandServer<BoardType, ShieldType>::change_state(ServerState new_state) {
       get_state_string(new_state));
   }
 
+  // Charge the cycles and time spent in the previous state. The cycle counter restarts when the
+  // CPU is reset, so a count below where the state began means it began at zero.
+  uint64_t cycle_ct = CPU.cycle_ct();
+  uint64_t begin_cycle = (cycle_ct >= stateBeginCycle_) ? stateBeginCycle_ : 0;
+  stateCycles_[static_cast<std::size_t>(state_)] += (uint32_t)(cycle_ct - begin_cycle);
+  if (stateBeginTime_ != 0) {
+    stateMicros_[static_cast<std::size_t>(state_)] += state_end_time - stateBeginTime_;
+  }
+  if (new_state == ServerState::Reset) {
+    // A reset begins a new run.
+    memset(stateCycles_, 0, sizeof(stateCycles_));
+    memset(stateMicros_, 0, sizeof(stateMicros_));
+  }
+
+  stateBeginCycle_ = cycle_ct;
   stateBeginTime_ = micros();
   state_ = new_state;
+  // Count every transition, so clients polling the status can tell when they missed one.
+  stateSeq_++;
 }
 
 
@@ -1544,7 +1586,6 @@ bool CommandServer<BoardType, ShieldType>::cmd_randomize_mem() {
 
 template<typename BoardType, typename ShieldType>
 bool CommandServer<BoardType, ShieldType>::cmd_set_memory() {
-    uint8_t read_buffer[MAX_BUFFER_LEN];
     uint32_t address = commandBuffer_[0] | 
                       (static_cast<uint32_t>(commandBuffer_[1]) << 8) |
                       (static_cast<uint32_t>(commandBuffer_[2]) << 16) |
@@ -1557,7 +1598,75 @@ bool CommandServer<BoardType, ShieldType>::cmd_set_memory() {
 
   controller_.getBoard().debugPrintf(DebugType::CMD, false, "cmd_set_memory(): Setting memory at address: %06lX with size: %lu\n\r", address, size);
 
-  // Read `size` bytes from the serial stream, MAX_BUFFER_LEN bytes at a time.
+  if (!read_memory_stream(address, size, "cmd_set_memory()")) {
+    return false;
+  }
+
+  controller_.getBoard().debugPrintf(DebugType::CMD, false, "cmd_set_memory(): Set %lu bytes of memory successfully\n\r", size);
+  //ArduinoX86::Bus->debug_memory(address, size);
+  return true;
+}
+
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::cmd_set_memory_ranges() {
+  // Sets several ranges of memory in one command, so a client staging many small writes pays the
+  // command latency once. The parameter is the number of ranges (uint16_t). Each range follows
+  // in the stream as:
+  // 4 bytes: Address (uint32_t)
+  // 4 bytes: Size (uint32_t)
+  // Size bytes: Data
+  uint16_t range_ct = commandBuffer_[0] | (static_cast<uint16_t>(commandBuffer_[1]) << 8);
+
+  controller_.getBoard().debugPrintf(DebugType::CMD, false, "cmd_set_memory_ranges(): Setting %u ranges\n\r", range_ct);
+
+  for (uint16_t i = 0; i < range_ct; i++) {
+    uint8_t header[8];
+    if (!read_stream(header, sizeof(header), "cmd_set_memory_ranges()")) {
+      return false;
+    }
+    uint32_t address = header[0] |
+                      (static_cast<uint32_t>(header[1]) << 8) |
+                      (static_cast<uint32_t>(header[2]) << 16) |
+                      (static_cast<uint32_t>(header[3]) << 24);
+    uint32_t size = header[4] |
+                    (static_cast<uint32_t>(header[5]) << 8) |
+                    (static_cast<uint32_t>(header[6]) << 16) |
+                    (static_cast<uint32_t>(header[7]) << 24);
+
+    if (!read_memory_stream(address, size, "cmd_set_memory_ranges()")) {
+      return false;
+    }
+  }
+  return true;
+}
+
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::read_stream(uint8_t *buf, size_t len, const char *caller) {
+  // Read exactly `len` bytes from the serial stream.
+  size_t total_bytes_read = 0;
+  constexpr unsigned long READ_TIMEOUT = 100; // Timeout for reading data in milliseconds
+  unsigned long timeout_time = millis() + READ_TIMEOUT;
+
+  while (total_bytes_read < len) {
+    if (proto_available()) {
+      total_bytes_read += proto_read(buf + total_bytes_read, len - total_bytes_read);
+    }
+    else if (millis() >= timeout_time) {
+      controller_.getBoard().debugPrintf(DebugType::ERROR, false, "%s: Timeout waiting for data\n\r", caller);
+      set_error("%s: Timeout waiting for data", caller);
+      return false;
+    }
+    else {
+      delay(1);
+    }
+  }
+  return true;
+}
+
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::read_memory_stream(uint32_t address, uint32_t size, const char *caller) {
+  uint8_t read_buffer[MAX_BUFFER_LEN];
+  // Read `size` bytes from the serial stream into memory, MAX_BUFFER_LEN bytes at a time.
   size_t total_bytes_read = 0;
   constexpr unsigned long READ_TIMEOUT = 100; // Timeout for reading data in milliseconds
   unsigned long start_time = millis();
@@ -1566,12 +1675,12 @@ bool CommandServer<BoardType, ShieldType>::cmd_set_memory() {
   while (total_bytes_read < size) {
     size_t bytes_available = proto_available();
     if (bytes_available) {
-      size_t bytes_to_read = min(bytes_available, MAX_BUFFER_LEN);
+      size_t bytes_to_read = min(min(bytes_available, MAX_BUFFER_LEN), (size_t)(size - total_bytes_read));
 
       size_t bytes_read = proto_read(read_buffer, bytes_to_read);
       if (bytes_read == 0) {
-        controller_.getBoard().debugPrintf(DebugType::ERROR, false, "cmd_set_memory(): Failed to read available bytes\n\r");
-        set_error("cmd_set_memory(): Failed to read available bytes");
+        controller_.getBoard().debugPrintf(DebugType::ERROR, false, "%s: Failed to read available bytes\n\r", caller);
+        set_error("%s: Failed to read available bytes", caller);
         return false;
       }
       ArduinoX86::Bus->set_memory(address + total_bytes_read, read_buffer, bytes_read);
@@ -1580,23 +1689,20 @@ bool CommandServer<BoardType, ShieldType>::cmd_set_memory() {
     else {
       // Check for timeout
       if (millis() >= timeout_time) {
-        controller_.getBoard().debugPrintf(DebugType::ERROR, false, "cmd_set_memory(): Timeout waiting for memory data\n\r");
-        set_error("cmd_set_memory(): Timeout waiting for memory data");
+        controller_.getBoard().debugPrintf(DebugType::ERROR, false, "%s: Timeout waiting for memory data\n\r", caller);
+        set_error("%s: Timeout waiting for memory data", caller);
         return false;
       }
       // No data available, wait a bit before checking again
       delay(1);
     }
   }
-
-  controller_.getBoard().debugPrintf(DebugType::CMD, false, "cmd_set_memory(): Set %lu bytes of memory successfully\n\r", total_bytes_read);
-  //ArduinoX86::Bus->debug_memory(address, total_bytes_read);
   return true;
 }
 
 template<typename BoardType, typename ShieldType>
 bool CommandServer<BoardType, ShieldType>::cmd_get_cycle_states() {
-  ArduinoX86::CycleLogger->dump_states();
+  ArduinoX86::CycleLogger->dump_states((flags_ & FLAG_ENCODE_CYCLE_LOG) != 0);
   return true;
 }
 
@@ -1699,11 +1805,14 @@ bool CommandServer<BoardType, ShieldType>::cmd_server_status() {
   // 1 byte: Server state (ServerState enum)
   // 8 bytes: Current cycle count (uint64_t)
   // 4 bytes: Current address latch (uint32_t)
+  // 4 bytes: State transition count (uint32_t)
   INBAND_SERIAL.write((uint8_t)state_);
   uint64_t cycle_count = CPU.cycle_ct();
   INBAND_SERIAL.write((uint8_t *)&cycle_count, sizeof(cycle_count));
   uint32_t address_latch = CPU.address_latch();
   INBAND_SERIAL.write((uint8_t *)&address_latch, sizeof(address_latch));
+  uint32_t state_seq = stateSeq_;
+  INBAND_SERIAL.write((uint8_t *)&state_seq, sizeof(state_seq));
   return true;
 }
 
@@ -1732,6 +1841,113 @@ bool CommandServer<BoardType, ShieldType>::cmd_set_program_bounds() {
   return true;
 }
 
+/// @brief Arm an interrupt to be raised during the next automatic execution.
+/// The trigger persists across resets until replaced; send line 0 to disarm it.
+/// Parameters: line (0=none, 1=INTR, 2=NMI), mode (0=instruction, 1=cycle), vector, offset (u32 LE).
+/// @tparam BoardType 
+/// @tparam ShieldType 
+/// @return true if the trigger was accepted.
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::cmd_set_interrupt_trigger() {
+  uint8_t line = commandBuffer_[0];
+  uint8_t mode = commandBuffer_[1];
+  uint8_t vector = commandBuffer_[2];
+  uint32_t offset = commandBuffer_[3] | 
+                    (static_cast<uint32_t>(commandBuffer_[4]) << 8) |
+                    (static_cast<uint32_t>(commandBuffer_[5]) << 16) |
+                    (static_cast<uint32_t>(commandBuffer_[6]) << 24);
+
+  if ((line > static_cast<uint8_t>(IrqLine::Nmi)) || (mode > static_cast<uint8_t>(IrqTriggerMode::Cycle))) {
+    set_error("Invalid interrupt trigger");
+    return false;
+  }
+
+  if ((static_cast<IrqLine>(line) != IrqLine::None) && (static_cast<IrqTriggerMode>(mode) == IrqTriggerMode::Instruction) && !CPU.have_queue_status) {
+    set_error("Instruction-offset interrupt triggers require queue status lines");
+    return false;
+  }
+
+  controller_.getBoard().debugPrintf(DebugType::CMD, false, "cmd_set_interrupt_trigger(): line: %d mode: %d vector: %02X offset: %lu\n\r", line, mode, vector, offset);
+
+  CPU.set_irq_trigger(static_cast<IrqLine>(line), static_cast<IrqTriggerMode>(mode), vector, offset);
+  return true;
+}
+
+/// @brief Arm an additional end condition for automatic execution, for programs that do not run off
+/// the end of their code on their own. Once the condition is met, a HALT is injected at the next code fetch
+/// and execution ends as usual.
+/// The condition persists across resets until replaced; send kind 0 to disarm it.
+/// Parameters: kind (0=none, 1=IRET from vector, 2=I/O port write, 3=fetch address, 4=cycle count), value (u32 LE).
+/// @tparam BoardType 
+/// @tparam ShieldType 
+/// @return true if the condition was accepted.
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::cmd_set_end_condition() {
+  uint8_t kind = commandBuffer_[0];
+  uint32_t value = commandBuffer_[1] | 
+                   (static_cast<uint32_t>(commandBuffer_[2]) << 8) |
+                   (static_cast<uint32_t>(commandBuffer_[3]) << 16) |
+                   (static_cast<uint32_t>(commandBuffer_[4]) << 24);
+
+  if (kind > static_cast<uint8_t>(EndKind::Cycles)) {
+    set_error("Invalid end condition");
+    return false;
+  }
+
+  if ((static_cast<EndKind>(kind) == EndKind::Iret) && (value > 0xFF)) {
+    set_error("Invalid interrupt vector for end condition");
+    return false;
+  }
+
+  controller_.getBoard().debugPrintf(DebugType::CMD, false, "cmd_set_end_condition(): kind: %d value: %08lX\n\r", kind, value);
+
+  CPU.set_end_condition(static_cast<EndKind>(kind), value);
+  return true;
+}
+
+/// @brief Report which optional commands this server supports, so a client can disable features
+/// instead of refusing to connect. Features that depend on objects created at setup are only
+/// reported if those objects exist.
+/// Returns the capability bitmap (u32 LE).
+/// @tparam BoardType 
+/// @tparam ShieldType 
+/// @return Always returns true.
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::cmd_get_capabilities() {
+  uint32_t caps = CAP_INTERRUPT_TRIGGER | CAP_PROGRAM_BOUNDS | CAP_STATE_SEQUENCE | CAP_STATE_BUDGET | CAP_END_CONDITION;
+  if (ArduinoX86::CycleLogger != nullptr) {
+    caps |= CAP_CYCLE_LOG | CAP_CYCLE_LOG_ENCODING;
+  }
+  if (ArduinoX86::Bus != nullptr) {
+    caps |= CAP_MEMORY_STRATEGY | CAP_MEMORY_RANGES;
+  }
+  proto_write(reinterpret_cast<const uint8_t*>(&caps), sizeof(caps));
+  return true;
+}
+
+template<typename BoardType, typename ShieldType>
+bool CommandServer<BoardType, ShieldType>::cmd_get_state_budget() {
+  // Returns the cycles and time spent in each state since the last reset as:
+  // 1 byte: Number of states (N)
+  // N * 8 bytes: Cycles (uint32_t) and microseconds (uint32_t), indexed by ServerState
+  // The current state is charged up to now.
+  uint64_t cycle_ct = CPU.cycle_ct();
+  uint64_t begin_cycle = (cycle_ct >= stateBeginCycle_) ? stateBeginCycle_ : 0;
+  uint8_t state_count = (uint8_t)STATE_COUNT;
+  INBAND_SERIAL.write(state_count);
+  for (std::size_t i = 0; i < STATE_COUNT; i++) {
+    uint32_t cycles = stateCycles_[i];
+    uint32_t us = stateMicros_[i];
+    if (i == static_cast<std::size_t>(state_)) {
+      cycles += (uint32_t)(cycle_ct - begin_cycle);
+      us += micros() - stateBeginTime_;
+    }
+    INBAND_SERIAL.write((uint8_t *)&cycles, sizeof(cycles));
+    INBAND_SERIAL.write((uint8_t *)&us, sizeof(us));
+  }
+  return true;
+}
+
 template<typename BoardType, typename ShieldType>
 bool CommandServer<BoardType, ShieldType>::cmd_null() {
   return true;
@@ -103,4 +103,9 @@ void Cpu::reset(CpuResetResult reset_result, bool preserve_bus_state, bool reset
   wait_state_ct = 0;
   exception_armed = false;
   predicted_fetch = 0;
+  irq_fired = false;
+  irq_nmi_pending = false;
+  irq_instr_ct = 0;
+  end_reached = false;
+  end_return_address = 0;
 }
\ No newline at end of file
@@ -841,6 +841,7 @@ void cycle() {
           // Set flag for first instruction byte fetched
           CPU.q_ff = true;
           CPU.q_fn = 0;  // First byte of instruction
+          CPU.irq_instr_ct++;
           CPU.opcode = CPU.qb;
           CPU.mnemonic = get_opcode_str(CPU.opcode, 0, false);
  
@@ -1990,6 +1991,66 @@ void handle_execute_state() {
   }
 }
 
+/// @brief Raise the armed interrupt line once its instruction or cycle offset has been reached.
+void handle_irq_trigger() {
+  if ((CPU.irq_line() == IrqLine::None) || CPU.irq_fired) {
+    return;
+  }
+
+  uint32_t count = (CPU.irq_mode() == IrqTriggerMode::Cycle) ? static_cast<uint32_t>(CPU.execute_cycle_ct) : CPU.irq_instr_ct;
+  if (count < CPU.irq_offset()) {
+    return;
+  }
+
+  CPU.irq_fired = true;
+  if (CPU.irq_line() == IrqLine::Nmi) {
+    Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: Interrupt trigger reached at %lu. Raising NMI.\n\r", count);
+    CPU.irq_nmi_pending = true;
+    Controller.writePin(OutputPin::Nmi, true);
+  }
+  else {
+    Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: Interrupt trigger reached at %lu. Raising INTR.\n\r", count);
+    Controller.writePin(OutputPin::Intr, true);
+  }
+}
+
+/// @brief Check whether the armed end condition has been met this cycle. The IRET condition is resolved
+/// at code fetch instead, once the watched handler returns.
+void handle_end_condition(bool cpu_iowc) {
+  if (CPU.end_reached) {
+    return;
+  }
+
+  switch (CPU.end_kind()) {
+    case EndKind::PortWrite:
+      if (cpu_iowc && ((CPU.address_latch() & 0xFFFF) == CPU.end_value())) {
+        Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: End condition met: write to port %04lX.\n\r", CPU.end_value());
+        CPU.end_reached = true;
+      }
+      break;
+    case EndKind::Cycles:
+      if (static_cast<uint32_t>(CPU.execute_cycle_ct) >= CPU.end_value()) {
+        Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: End condition met: %lu cycles.\n\r", CPU.end_value());
+        CPU.end_reached = true;
+      }
+      break;
+    default:
+      break;
+  }
+}
+
+/// @brief Write a HALT opcode at the address of a code fetch so that execution ends there.
+void inject_end_halt(uint32_t address) {
+  Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: Injecting HALT opcode at %08lX for end condition.\n\r", address);
+  if (CPU.cpu_type == CpuType::i80386) {
+    // The 386 only fetches at even addresses, so cover both bytes of the fetch.
+    ArduinoX86::Bus->mem_write_bus(address & ~1ul, OPCODE_DOUBLE_HALT, true, false);
+  }
+  else {
+    ArduinoX86::Bus->mem_write_u8(address, OPCODE_HALT);
+  }
+}
+
 /// @brief Handle program execution in automatic mode.
 void handle_execute_automatic() {
 
@@ -2005,6 +2066,22 @@ void handle_execute_automatic() {
 
   bool print = Controller.getBoard().isDebugEnabled();
 
+  handle_irq_trigger();
+  handle_end_condition(cpu_iowc);
+
+  if (CPU.bus_state_latched == INTA) {
+    if (Controller.readALEPin() && (CPU.irq_line() == IrqLine::Intr)) {
+      // INTR has been recognized. Drop it so the handler is only entered once.
+      Controller.writePin(OutputPin::Intr, false);
+    }
+    if (CPU.bus_cycle == WRITE_CYCLE) {
+      // Only the second INTA cycle reads the vector, but supplying it on both is harmless.
+      CPU.data_bus = CPU.irq_vector();
+      Controller.writeDataBus(CPU.data_bus, CPU.data_width);
+      Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: INTA cycle, supplying vector %02X\n\r", CPU.irq_vector());
+    }
+  }
+
   if (cpu_mwtc) {
     // The CPU is writing to memory. Send it to the bus emulator.
     if (print) {
@@ -2082,7 +2159,7 @@ void handle_execute_automatic() {
     }
   }
 
-  if ((READ_NMI_PIN) && (CPU.nmi_checkpoint == 0)) {
+  if ((READ_NMI_PIN) && (CPU.nmi_checkpoint == 0) && !CPU.irq_nmi_pending) {
     // Use checkpoint "1" to specify that NMI has been detected. This just prevents the debug message from
     // printing every cycle after NMI.
     Controller.getBoard().debugPrintln(DebugType::EXECUTE, "## EXECUTE: NMI pin high - Execute will end at IVT fetch.", true);
@@ -2138,6 +2215,21 @@ void handle_execute_automatic() {
         }
       }
 
+      if (CPU.end_reached) {
+        inject_end_halt(CPU.address_latch());
+      }
+      else if ((CPU.end_kind() == EndKind::Fetch) && ((CPU.address_latch() & ~1ul) == (CPU.end_value() & ~1ul))) {
+        Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: End condition met: fetch from %08lX.\n\r", CPU.end_value());
+        CPU.end_reached = true;
+        inject_end_halt(CPU.end_value());
+      }
+      else if ((CPU.end_return_address > 0) && ((CPU.address_latch() & ~1ul) == (CPU.end_return_address & ~1ul))) {
+        // The watched handler has returned to the instruction after the interrupt.
+        Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: End condition met: IRET to %08lX.\n\r", CPU.end_return_address);
+        CPU.end_reached = true;
+        inject_end_halt(CPU.end_return_address);
+      }
+
       if ((CPU.predicted_fetch > 0) && (CPU.address_latch() != CPU.predicted_fetch)) {
         // We have a code fetch at the predicted address.
         Controller.getBoard().debugPrintln(DebugType::EXECUTE, "## EXECUTE: CODE fetch not at predicted address. Flow control change detected!", true);
@@ -2179,6 +2271,12 @@ void handle_execute_automatic() {
         Controller.getBoard().debugPrintf(DebugType::EXECUTE, false, "## EXECUTE: Possible IVT read, far call flag is %d, align is %d\n\r", far_call_flag, (CPU.address_latch() & 0x03));
         if (((CPU.address_latch() & 0x03) == 0) && far_call_flag) {
           Controller.getBoard().debugPrintln(DebugType::EXECUTE, "## EXECUTE: Exception/Interrupt candidate. Entering exception stage 1", true);
+          if ((CPU.end_kind() == EndKind::Iret) && (CPU.end_return_address == 0) && ((CPU.address_latch() >> 2) == CPU.end_value())) {
+            // Entering the watched handler. Its IRET returns to the address in the stack frame just pushed.
+            CallStackFrame frame = ArduinoX86::Bus->log_peek_call_frame();
+            CPU.end_return_address = (static_cast<uint32_t>(frame.cs) << 4) + frame.ip;
+            Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: Entered watched handler, return address is %08lX\n\r", CPU.end_return_address);
+          }
           CPU.exception_stage = 1;
           next_exception_address = CPU.address_latch() + 2;
         }
@@ -2186,14 +2284,21 @@ void handle_execute_automatic() {
 
           exception_number = (uint32_t)((next_exception_address - 2) / 4);
           Controller.getBoard().debugPrintf(DebugType::EXECUTE, true, "## EXECUTE: Detected Exception/Interrupt #%d!\n\r", exception_number);
-          CPU.exception_armed = true;
+          // The handler watched by an IRET end condition must run, so don't end execution at its first fetch.
+          CPU.exception_armed = (CPU.end_kind() != EndKind::Iret) || (exception_number != CPU.end_value());
           CPU.exception_stage = 0;
           next_exception_address = 0;
         }
       }
 
       // NMI is active and CPU is starting a memory bus cycle. Let's check if it is the NMI handler.
-      if ((READ_NMI_PIN) && (CPU.address_latch() == 0x00008)) {
+      if ((READ_NMI_PIN) && (CPU.address_latch() == 0x00008) && CPU.irq_nmi_pending) {
+        // This is the injected NMI being serviced, not a request to end execution.
+        Controller.getBoard().debugPrintln(DebugType::EXECUTE, "## EXECUTE: Injected NMI is fetching its handler. Lowering NMI.", true);
+        CPU.irq_nmi_pending = false;
+        Controller.writePin(OutputPin::Nmi, false);
+      }
+      else if ((READ_NMI_PIN) && (CPU.address_latch() == 0x00008)) {
         Controller.getBoard().debugPrintln(DebugType::EXECUTE, "## EXECUTE: NMI high and fetching NMI handler. Entering ExecuteFinalize...", true);
         CPU.nmi_terminate = true;
         ArduinoX86::Server.change_state(ServerState::ExecuteFinalize);